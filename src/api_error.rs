@@ -0,0 +1,259 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Human-readable rendering of the error payloads of Google APIs (`{"error": {...}}`, see https://google.aip.dev/193).
+
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Renders the error payload: the message and status, then the field violations, quota violations, error info,
+/// and help links in `error.details`. Details of other types are rendered as raw JSON.
+/// Returns None if the value is not an error payload.
+pub(crate) fn render(value: &Value) -> Option<String> {
+    let error = value.get("error")?.as_object()?;
+    let message = error.get("message")?.as_str()?;
+    let mut rendered = message.to_string();
+    if let Some(status) = error.get("status").and_then(Value::as_str) {
+        let _ = write!(rendered, " ({})", status);
+    }
+
+    let details = error
+        .get("details")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut others = Vec::new();
+    for detail in details {
+        let detail_type = detail
+            .get("@type")
+            .and_then(Value::as_str)
+            .and_then(|t| t.rsplit('/').next())
+            .unwrap_or_default();
+        let section = match detail_type {
+            "google.rpc.BadRequest" => field_violations(detail),
+            "google.rpc.QuotaFailure" => quota_violations(detail),
+            "google.rpc.ErrorInfo" => error_info(detail),
+            "google.rpc.Help" => help_links(detail),
+            _ => None,
+        };
+        match section {
+            Some(section) => {
+                rendered.push('\n');
+                rendered.push_str(&section);
+            }
+            None => others.push(detail),
+        }
+    }
+    if !others.is_empty() {
+        rendered.push_str("\nOther details:");
+        for detail in others {
+            let _ = write!(rendered, "\n  {}", detail);
+        }
+    }
+    Some(rendered)
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+/// Renders a list field of the detail as a titled section, or returns None if any item can't be rendered.
+fn list_section(
+    detail: &Value,
+    key: &str,
+    title: &str,
+    line: impl Fn(&Value) -> Option<String>,
+) -> Option<String> {
+    let items = detail.get(key)?.as_array()?;
+    let mut section = format!("{}:", title);
+    for item in items {
+        let _ = write!(section, "\n  - {}", line(item)?);
+    }
+    Some(section)
+}
+
+/// `google.rpc.BadRequest`: `field: description` for each violation.
+fn field_violations(detail: &Value) -> Option<String> {
+    list_section(detail, "fieldViolations", "Field violations", |violation| {
+        let description = str_field(violation, "description")?;
+        Some(match str_field(violation, "field") {
+            Some(field) if !field.is_empty() => format!("{}: {}", field, description),
+            _ => description.to_string(),
+        })
+    })
+}
+
+/// `google.rpc.QuotaFailure`: the subject and description of each violation, with the quota limit (`quotaId`) if given.
+fn quota_violations(detail: &Value) -> Option<String> {
+    list_section(detail, "violations", "Quota violations", |violation| {
+        let mut line = match (
+            str_field(violation, "subject"),
+            str_field(violation, "description"),
+        ) {
+            (Some(subject), Some(description)) => format!("{}: {}", subject, description),
+            (None, Some(text)) | (Some(text), None) => text.to_string(),
+            (None, None) => return None,
+        };
+        if let Some(limit) = str_field(violation, "quotaId") {
+            let _ = write!(line, " (limit: {}", limit);
+            if let Some(value) = violation.get("quotaValue") {
+                let value = value.as_str().map_or(value.to_string(), str::to_string);
+                let _ = write!(line, ", value: {}", value);
+            }
+            line.push(')');
+        }
+        Some(line)
+    })
+}
+
+/// `google.rpc.ErrorInfo`: the reason and domain, then the metadata (e.g., `quota_limit`, `consumer`) as `key: value`.
+fn error_info(detail: &Value) -> Option<String> {
+    let reason = str_field(detail, "reason")?;
+    let mut section = format!("Reason: {}", reason);
+    if let Some(domain) = str_field(detail, "domain") {
+        let _ = write!(section, " ({})", domain);
+    }
+    for (key, value) in detail
+        .get("metadata")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        let value = value.as_str().map_or(value.to_string(), str::to_string);
+        let _ = write!(section, "\n  {}: {}", key, value);
+    }
+    Some(section)
+}
+
+/// `google.rpc.Help`: `description: url` for each link.
+fn help_links(detail: &Value) -> Option<String> {
+    list_section(detail, "links", "Help", |link| {
+        let url = str_field(link, "url")?;
+        Some(match str_field(link, "description") {
+            Some(description) => format!("{}: {}", description, url),
+            None => url.to_string(),
+        })
+    })
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_bad_request() {
+        let payload = json!({
+            "error": {
+                "code": 400,
+                "message": "Request contains an invalid argument.",
+                "status": "INVALID_ARGUMENT",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.BadRequest",
+                        "fieldViolations": [
+                            {"field": "cluster.name", "description": "Name must start with a letter."},
+                            {"field": "cluster.initial_node_count", "description": "Must be positive."}
+                        ]
+                    },
+                    {
+                        "@type": "type.googleapis.com/google.rpc.Help",
+                        "links": [{"description": "Cluster naming", "url": "https://cloud.google.com/kubernetes-engine/docs"}]
+                    },
+                    {
+                        "@type": "type.googleapis.com/google.rpc.RequestInfo",
+                        "requestId": "abc123"
+                    }
+                ]
+            }
+        });
+        assert_eq!(
+            render(&payload).unwrap(),
+            "Request contains an invalid argument. (INVALID_ARGUMENT)\n\
+             Field violations:\n  \
+             - cluster.name: Name must start with a letter.\n  \
+             - cluster.initial_node_count: Must be positive.\n\
+             Help:\n  \
+             - Cluster naming: https://cloud.google.com/kubernetes-engine/docs\n\
+             Other details:\n  \
+             {\"@type\":\"type.googleapis.com/google.rpc.RequestInfo\",\"requestId\":\"abc123\"}"
+        );
+    }
+
+    #[test]
+    fn test_render_quota_failure() {
+        let payload = json!({
+            "error": {
+                "code": 429,
+                "message": "Quota exceeded for quota metric 'Queries' and limit 'Queries per minute'.",
+                "status": "RESOURCE_EXHAUSTED",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                        "reason": "RATE_LIMIT_EXCEEDED",
+                        "domain": "googleapis.com",
+                        "metadata": {
+                            "quota_limit": "DefaultRequestsPerMinutePerProject",
+                            "consumer": "projects/123456789"
+                        }
+                    },
+                    {
+                        "@type": "type.googleapis.com/google.rpc.QuotaFailure",
+                        "violations": [
+                            {
+                                "subject": "project:123456789",
+                                "description": "Queries per minute exceeded.",
+                                "quotaId": "DefaultRequestsPerMinutePerProject",
+                                "quotaValue": "600"
+                            },
+                            {"description": "CPUS quota exceeded in us-central1."}
+                        ]
+                    }
+                ]
+            }
+        });
+        assert_eq!(
+            render(&payload).unwrap(),
+            "Quota exceeded for quota metric 'Queries' and limit 'Queries per minute'. (RESOURCE_EXHAUSTED)\n\
+             Reason: RATE_LIMIT_EXCEEDED (googleapis.com)\n  \
+             consumer: projects/123456789\n  \
+             quota_limit: DefaultRequestsPerMinutePerProject\n\
+             Quota violations:\n  \
+             - project:123456789: Queries per minute exceeded. (limit: DefaultRequestsPerMinutePerProject, value: 600)\n  \
+             - CPUS quota exceeded in us-central1."
+        );
+    }
+
+    #[test]
+    fn test_render_fallbacks() {
+        // Without details
+        let payload = json!({"error": {"code": 404, "message": "Not found: cluster c1."}});
+        assert_eq!(render(&payload).unwrap(), "Not found: cluster c1.");
+
+        // A known type with an unexpected shape is rendered raw
+        let payload = json!({"error": {"message": "Bad.", "details": [
+            {"@type": "type.googleapis.com/google.rpc.BadRequest", "fieldViolations": [{"field": "x"}]}
+        ]}});
+        assert_eq!(
+            render(&payload).unwrap(),
+            "Bad.\nOther details:\n  {\"@type\":\"type.googleapis.com/google.rpc.BadRequest\",\"fieldViolations\":[{\"field\":\"x\"}]}"
+        );
+
+        // Not an error payload
+        assert_eq!(render(&json!({"name": "c1"})), None);
+        assert_eq!(render(&json!({"error": "invalid_grant"})), None);
+    }
+}
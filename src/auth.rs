@@ -0,0 +1,688 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `zg auth`: logging in without gcloud with the OAuth 2.0 device authorization flow (`zg auth login --device`).
+//! The refresh token is stored in `auth.json` in the config directory (readable only by the user), and gives access
+//! tokens when gcloud is unavailable.
+
+use clap::{Args, Subcommand};
+use hyper::Method;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::core;
+use super::credentials::{HyperTransport, Transport};
+use super::error::ZgError;
+
+const AUTH_FILE: &str = "auth.json";
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const ALLOWED_SCOPES_URL: &str =
+    "https://developers.google.com/identity/protocols/oauth2/limited-input-device#allowedscopes";
+
+/// Environment variable of the client secret, not to give it on the command line.
+const CLIENT_SECRET_VAR: &str = "ZG_CLIENT_SECRET";
+
+/// Polling interval when the device code response doesn't give one.
+const DEFAULT_INTERVAL: u64 = 5;
+
+/// Access tokens are refreshed this long before they expire.
+const EXPIRY_MARGIN: i64 = 60;
+
+#[derive(Args, Debug)]
+pub struct AuthArgs {
+    #[command(subcommand)]
+    command: AuthCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthCmd {
+    /// Log in without gcloud. Prints a code to enter at a URL in a browser on another device, and stores the refresh
+    /// token once it's approved. The access tokens are used when gcloud is unavailable.
+    Login(LoginArgs),
+
+    /// Revoke the refresh token stored by `zg auth login` and remove it.
+    Revoke,
+}
+
+#[derive(Args, Debug)]
+struct LoginArgs {
+    /// Use the device authorization flow (the only flow supported).
+    #[arg(long, required = true)]
+    device: bool,
+
+    /// Client id of the OAuth client ("TVs and Limited Input devices" type) to log in with.
+    #[arg(long)]
+    client_id: String,
+
+    /// Client secret of the OAuth client. As it's visible to other users in the process list, prefer
+    /// --client-secret-file or the ZG_CLIENT_SECRET environment variable.
+    #[arg(long, conflicts_with = "client_secret_file")]
+    client_secret: Option<String>,
+
+    /// Read the client secret of the OAuth client from this file.
+    #[arg(long, value_name = "PATH")]
+    client_secret_file: Option<PathBuf>,
+
+    /// OAuth scopes to request (e.g., `openid`). Google grants only a few scopes with the device flow, and not
+    /// cloud-platform; see the allowed scopes in the guide of the device flow.
+    #[arg(long = "scope", value_name = "SCOPE", required = true)]
+    scopes: Vec<String>,
+}
+
+impl LoginArgs {
+    /// The client secret: --client-secret, the content of --client-secret-file, or ZG_CLIENT_SECRET.
+    fn client_secret(&self, env: impl Fn(&str) -> Option<String>) -> Result<String, ZgError> {
+        if let Some(secret) = &self.client_secret {
+            return Ok(secret.clone());
+        }
+        if let Some(path) = &self.client_secret_file {
+            let secret = std::fs::read_to_string(path).map_err(|e| {
+                ZgError::AuthFailure(format!(
+                    "can't read the client secret from {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            return Ok(secret.trim().to_string());
+        }
+        env(CLIENT_SECRET_VAR)
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| {
+                ZgError::AuthFailure(format!(
+                    "no client secret; give --client-secret-file PATH or set {}",
+                    CLIENT_SECRET_VAR
+                ))
+            })
+    }
+}
+
+/// The credential stored by `zg auth login`, with the last access token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct StoredCredential {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    scopes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    access_token: Option<String>,
+    /// When the access token expires, in seconds since the epoch.
+    #[serde(default)]
+    expires_at: i64,
+}
+
+/// The response of the device authorization endpoint.
+#[derive(Deserialize, Debug)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    /// Google returns `verification_url`; RFC 8628 names it `verification_uri`.
+    #[serde(alias = "verification_uri")]
+    verification_url: String,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+pub async fn main(args: &AuthArgs) -> Result<(), ZgError> {
+    let path = auth_path();
+    match &args.command {
+        AuthCmd::Login(login) => {
+            let client_secret = login.client_secret(|var| std::env::var(var).ok())?;
+            let stored = device_login(
+                &HyperTransport,
+                &login.client_id,
+                &client_secret,
+                login.scopes.clone(),
+                |code| {
+                    eprintln!(
+                        "Open {} in a browser and enter the code: {}",
+                        code.verification_url, code.user_code
+                    )
+                },
+                tokio::time::sleep,
+            )
+            .await?;
+            save(&path, &stored)?;
+            eprintln!("Logged in. The credential is stored in {}", path.display());
+            Ok(())
+        }
+        AuthCmd::Revoke => {
+            if !path.exists() {
+                return Err(format!(
+                    "Not logged in with `zg auth login` ({} not found)",
+                    path.display()
+                )
+                .into());
+            }
+            let stored = load(&path)?;
+            if let Err(e) = revoke(&HyperTransport, &stored.refresh_token).await {
+                warn!(
+                    "Failed to revoke the refresh token ({}); removing it anyway",
+                    e
+                );
+            }
+            std::fs::remove_file(&path)?;
+            eprintln!("Removed {}", path.display());
+            Ok(())
+        }
+    }
+}
+
+fn auth_path() -> PathBuf {
+    core::config_dir().join(AUTH_FILE)
+}
+
+/// Whether `zg auth login` has stored a credential.
+pub(crate) fn has_stored_credential() -> bool {
+    auth_path().exists()
+}
+
+/// Returns an access token of the stored credential, refreshing (and storing) it when it's about to expire.
+pub(crate) async fn access_token() -> Result<String, ZgError> {
+    let path = auth_path();
+    let stored = load(&path)?;
+    let (token, refreshed) = fresh_access_token(stored, now(), &HyperTransport).await?;
+    if let Some(refreshed) = refreshed {
+        if let Err(e) = save(&path, &refreshed) {
+            warn!(
+                "Failed to save the access token to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    Ok(token)
+}
+
+/// Discards the stored access token, so that the next `access_token` gets a new one (e.g., when it's rejected).
+pub(crate) fn forget_access_token() {
+    let path = auth_path();
+    if let Ok(stored) = load(&path) {
+        let _ = save(
+            &path,
+            &StoredCredential {
+                access_token: None,
+                ..stored
+            },
+        );
+    }
+}
+
+/// Runs the device authorization flow: requests a device code, shows it with `prompt`, and polls the token endpoint
+/// until the user approves it, waiting with `wait` between the polls.
+async fn device_login<F: Future<Output = ()>>(
+    transport: &impl Transport,
+    client_id: &str,
+    client_secret: &str,
+    scopes: Vec<String>,
+    prompt: impl FnOnce(&DeviceCode),
+    mut wait: impl FnMut(Duration) -> F,
+) -> Result<StoredCredential, ZgError> {
+    let scope = scopes.join(" ");
+    let (status, body) = post_form(
+        transport,
+        DEVICE_CODE_URL,
+        &[("client_id", client_id), ("scope", &scope)],
+    )
+    .await?;
+    if status != 200 {
+        let hint = match error_code(&body).as_deref() {
+            Some("invalid_scope") => format!(
+                "; the device flow allows only some scopes (see {})",
+                ALLOWED_SCOPES_URL
+            ),
+            _ => String::new(),
+        };
+        return Err(ZgError::AuthFailure(format!(
+            "the device authorization request failed: {}{}",
+            oauth_error(status, &body),
+            hint
+        )));
+    }
+    let code: DeviceCode = serde_json::from_str(&body).map_err(|e| {
+        ZgError::AuthFailure(format!("invalid device authorization response ({})", e))
+    })?;
+    prompt(&code);
+
+    let mut interval = code.interval.unwrap_or(DEFAULT_INTERVAL);
+    let mut waited = 0;
+    loop {
+        if waited >= code.expires_in {
+            return Err(ZgError::AuthFailure(
+                "the code expired before it was approved; run `zg auth login --device` again"
+                    .to_string(),
+            ));
+        }
+        wait(Duration::from_secs(interval)).await;
+        waited += interval;
+        let (status, body) = post_form(
+            transport,
+            TOKEN_URL,
+            &[
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("device_code", &code.device_code),
+                ("grant_type", DEVICE_CODE_GRANT_TYPE),
+            ],
+        )
+        .await?;
+        if status == 200 {
+            let token: Value = serde_json::from_str(&body)
+                .map_err(|e| ZgError::AuthFailure(format!("invalid token response ({})", e)))?;
+            let field = |name: &str| token.get(name).and_then(Value::as_str).map(str::to_string);
+            let refresh_token = field("refresh_token").ok_or_else(|| {
+                ZgError::AuthFailure("no refresh token in the token response".to_string())
+            })?;
+            return Ok(StoredCredential {
+                client_id: client_id.to_string(),
+                client_secret: client_secret.to_string(),
+                refresh_token,
+                scopes,
+                access_token: field("access_token"),
+                expires_at: now() + expires_in(&token),
+            });
+        }
+        match error_code(&body).as_deref() {
+            Some("authorization_pending") => debug!("Auth: waiting for the approval"),
+            Some("slow_down") => {
+                interval += 5;
+                debug!("Auth: polling every {} seconds", interval);
+            }
+            Some("access_denied") => {
+                return Err(ZgError::AuthFailure("the login was denied".to_string()))
+            }
+            Some("expired_token") => {
+                return Err(ZgError::AuthFailure(
+                    "the code expired before it was approved; run `zg auth login --device` again"
+                        .to_string(),
+                ))
+            }
+            _ => {
+                return Err(ZgError::AuthFailure(format!(
+                    "the token request failed: {}",
+                    oauth_error(status, &body)
+                )))
+            }
+        }
+    }
+}
+
+/// Returns the access token of the credential if it's valid at `now`, otherwise a new one with the updated credential.
+async fn fresh_access_token(
+    stored: StoredCredential,
+    now: i64,
+    transport: &impl Transport,
+) -> Result<(String, Option<StoredCredential>), ZgError> {
+    if let Some(token) = &stored.access_token {
+        if now + EXPIRY_MARGIN < stored.expires_at {
+            return Ok((token.clone(), None));
+        }
+    }
+    debug!("Auth: refreshing the access token of `zg auth login`");
+    let (status, body) = post_form(
+        transport,
+        TOKEN_URL,
+        &[
+            ("client_id", &stored.client_id),
+            ("client_secret", &stored.client_secret),
+            ("refresh_token", &stored.refresh_token),
+            ("grant_type", "refresh_token"),
+        ],
+    )
+    .await?;
+    if status != 200 {
+        let hint = match error_code(&body).as_deref() {
+            Some("invalid_grant") => {
+                "; the login expired or was revoked, run `zg auth login --device` again"
+            }
+            _ => "",
+        };
+        return Err(ZgError::AuthFailure(format!(
+            "refreshing the access token failed: {}{}",
+            oauth_error(status, &body),
+            hint
+        )));
+    }
+    let token: Value = serde_json::from_str(&body)
+        .map_err(|e| ZgError::AuthFailure(format!("invalid token response ({})", e)))?;
+    let access_token = token
+        .get("access_token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ZgError::AuthFailure("no access token in the token response".to_string()))?
+        .to_string();
+    let refreshed = StoredCredential {
+        access_token: Some(access_token.clone()),
+        expires_at: now + expires_in(&token),
+        ..stored
+    };
+    Ok((access_token, Some(refreshed)))
+}
+
+async fn revoke(transport: &impl Transport, refresh_token: &str) -> Result<(), ZgError> {
+    let (status, body) = post_form(transport, REVOKE_URL, &[("token", refresh_token)]).await?;
+    match status {
+        200 => Ok(()),
+        _ => Err(oauth_error(status, &body).into()),
+    }
+}
+
+async fn post_form(
+    transport: &impl Transport,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<(u16, String), ZgError> {
+    let body = form
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let headers = [(
+        "Content-Type".to_string(),
+        "application/x-www-form-urlencoded".to_string(),
+    )];
+    transport
+        .send(Method::POST, url, &headers, Some(body))
+        .await
+        .map_err(|e| ZgError::AuthFailure(format!("request to {} failed: {}", url, e)))
+}
+
+/// The `error` code of an OAuth error response.
+fn error_code(body: &str) -> Option<String> {
+    serde_json::from_str::<Value>(body)
+        .ok()?
+        .get("error")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn oauth_error(status: u16, body: &str) -> String {
+    format!("status {}: {}", status, body.trim())
+}
+
+fn expires_in(token: &Value) -> i64 {
+    token
+        .get("expires_in")
+        .and_then(Value::as_i64)
+        .unwrap_or(3600)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn load(path: &Path) -> Result<StoredCredential, ZgError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ZgError::AuthFailure(format!(
+            "no credential of `zg auth login` ({}: {})",
+            path.display(),
+            e
+        ))
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        ZgError::AuthFailure(format!("invalid credential file {}: {}", path.display(), e))
+    })
+}
+
+/// Writes the credential readable only by the user, replacing the file atomically.
+fn save(path: &Path, stored: &StoredCredential) -> Result<(), ZgError> {
+    core::write_private_file_atomic(path, serde_json::to_string_pretty(stored)?.as_bytes())
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Answers the requests with the responses in order, recording the URLs and bodies.
+    struct FakeTransport {
+        responses: RefCell<Vec<(u16, &'static str)>>,
+        requests: RefCell<Vec<(String, String)>>,
+    }
+
+    impl FakeTransport {
+        fn new(responses: Vec<(u16, &'static str)>) -> Self {
+            FakeTransport {
+                responses: RefCell::new(responses),
+                requests: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Transport for FakeTransport {
+        async fn send(
+            &self,
+            _method: Method,
+            url: &str,
+            _headers: &[(String, String)],
+            body: Option<String>,
+        ) -> Result<(u16, String), ZgError> {
+            self.requests
+                .borrow_mut()
+                .push((url.to_string(), body.unwrap_or_default()));
+            let (status, body) = self.responses.borrow_mut().remove(0);
+            Ok((status, body.to_string()))
+        }
+    }
+
+    const DEVICE_CODE: &str = r#"{"device_code": "dev-123", "user_code": "ABCD-EFGH", "verification_url": "https://www.google.com/device", "expires_in": 1800, "interval": 5}"#;
+
+    fn stored(access_token: Option<&str>, expires_at: i64) -> StoredCredential {
+        StoredCredential {
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            refresh_token: "refresh".to_string(),
+            scopes: vec!["openid".to_string()],
+            access_token: access_token.map(str::to_string),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_device_login_polls_until_approved() {
+        let transport = FakeTransport::new(vec![
+            (200, DEVICE_CODE),
+            (428, r#"{"error": "authorization_pending"}"#),
+            (403, r#"{"error": "slow_down"}"#),
+            (428, r#"{"error": "authorization_pending"}"#),
+            (
+                200,
+                r#"{"access_token": "at", "refresh_token": "rt", "expires_in": 3599}"#,
+            ),
+        ]);
+        let waits = RefCell::new(vec![]);
+        let mut prompted = None;
+        let stored = device_login(
+            &transport,
+            "client",
+            "secret",
+            vec!["scope-a".to_string(), "scope-b".to_string()],
+            |code| prompted = Some((code.verification_url.clone(), code.user_code.clone())),
+            |duration| {
+                waits.borrow_mut().push(duration.as_secs());
+                std::future::ready(())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            prompted,
+            Some((
+                "https://www.google.com/device".to_string(),
+                "ABCD-EFGH".to_string()
+            ))
+        );
+        assert_eq!(stored.refresh_token, "rt");
+        assert_eq!(stored.access_token.as_deref(), Some("at"));
+        assert_eq!(stored.scopes, ["scope-a", "scope-b"]);
+        // slow_down adds 5 seconds to the interval
+        assert_eq!(*waits.borrow(), [5, 5, 10, 10]);
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].0, DEVICE_CODE_URL);
+        assert_eq!(requests[0].1, "client_id=client&scope=scope-a%20scope-b");
+        assert_eq!(requests[1].0, TOKEN_URL);
+        assert!(requests[1].1.contains("device_code=dev-123"));
+        assert!(requests[1]
+            .1
+            .contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code"));
+    }
+
+    #[tokio::test]
+    async fn test_device_login_failures() {
+        let login = |responses| async move {
+            let transport = FakeTransport::new(responses);
+            device_login(
+                &transport,
+                "client",
+                "secret",
+                vec![],
+                |_| {},
+                |_| std::future::ready(()),
+            )
+            .await
+            .unwrap_err()
+            .to_string()
+        };
+        let denied = login(vec![
+            (200, DEVICE_CODE),
+            (403, r#"{"error": "access_denied"}"#),
+        ])
+        .await;
+        assert!(denied.ends_with("the login was denied"), "{}", denied);
+
+        let expired = login(vec![
+            (
+                200,
+                r#"{"device_code": "d", "user_code": "u", "verification_url": "v", "expires_in": 5, "interval": 5}"#,
+            ),
+            (428, r#"{"error": "authorization_pending"}"#),
+        ])
+        .await;
+        assert!(expired.contains("the code expired"), "{}", expired);
+
+        let rejected = login(vec![(401, r#"{"error": "invalid_client"}"#)]).await;
+        assert!(
+            rejected.contains("the device authorization request failed: status 401"),
+            "{}",
+            rejected
+        );
+        let invalid_scope = login(vec![(400, r#"{"error": "invalid_scope"}"#)]).await;
+        assert!(
+            invalid_scope.contains(ALLOWED_SCOPES_URL),
+            "{}",
+            invalid_scope
+        );
+    }
+
+    #[test]
+    fn test_client_secret() {
+        use clap::Parser;
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            args: LoginArgs,
+        }
+        let parse = |args: &[&str]| {
+            let base = ["zg", "--device", "--client-id", "id", "--scope", "openid"];
+            Cli::parse_from(base.iter().chain(args)).args
+        };
+        let env = |var: &str| (var == CLIENT_SECRET_VAR).then(|| "from-env".to_string());
+
+        let given = parse(&["--client-secret", "s"]);
+        assert_eq!(given.client_secret(env).unwrap(), "s");
+        assert_eq!(parse(&[]).client_secret(env).unwrap(), "from-env");
+        assert!(parse(&[]).client_secret(|_| None).is_err());
+
+        let path = std::env::temp_dir().join(format!("zg-client-secret-{}", std::process::id()));
+        std::fs::write(&path, "from-file\n").unwrap();
+        let file = parse(&["--client-secret-file", path.to_str().unwrap()]);
+        assert_eq!(file.client_secret(env).unwrap(), "from-file");
+        std::fs::remove_file(&path).unwrap();
+
+        let both = [
+            "zg",
+            "--device",
+            "--client-id",
+            "id",
+            "--scope",
+            "openid",
+            "--client-secret",
+            "s",
+        ];
+        assert!(Cli::try_parse_from(both.iter().chain(&["--client-secret-file", "f"])).is_err());
+        // No default scope: the device flow doesn't grant cloud-platform
+        assert!(Cli::try_parse_from(["zg", "--device", "--client-id", "id"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fresh_access_token() {
+        // Valid: no request
+        let transport = FakeTransport::new(vec![]);
+        let (token, refreshed) = fresh_access_token(stored(Some("valid"), 2000), 1000, &transport)
+            .await
+            .unwrap();
+        assert_eq!((token.as_str(), refreshed), ("valid", None));
+
+        // About to expire: refreshed with the refresh token
+        let transport = FakeTransport::new(vec![(
+            200,
+            r#"{"access_token": "new", "expires_in": 3599}"#,
+        )]);
+        let (token, refreshed) = fresh_access_token(stored(Some("old"), 1030), 1000, &transport)
+            .await
+            .unwrap();
+        assert_eq!(token, "new");
+        assert_eq!(refreshed, Some(stored(Some("new"), 4599)));
+        assert!(transport.requests.borrow()[0]
+            .1
+            .contains("refresh_token=refresh&grant_type=refresh_token"));
+
+        // Revoked
+        let transport = FakeTransport::new(vec![(400, r#"{"error": "invalid_grant"}"#)]);
+        let e = fresh_access_token(stored(None, 0), 1000, &transport)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("run `zg auth login --device` again"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("zg-auth-{}", std::process::id()));
+        let path = dir.join(AUTH_FILE);
+        save(&path, &stored(Some("at"), 100)).unwrap();
+        assert_eq!(load(&path).unwrap(), stored(Some("at"), 100));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(load(&path)
+            .unwrap_err()
+            .to_string()
+            .contains("no credential of `zg auth login`"));
+    }
+}
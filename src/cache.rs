@@ -0,0 +1,506 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::{Args, Subcommand};
+use log::warn;
+use rmp_serde::decode::Deserializer;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::core;
+use super::error::ZgError;
+use super::http_cache::HTTP_CACHE_DIR;
+use super::supported_apis::supported_apis;
+
+/// Subdirectories of the config directory holding the cache: converted API files and downloaded discovery documents.
+const CACHE_SUBDIRS: [&str; 2] = ["api", "discovered"];
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCmd {
+    /// Print the cache directory, the number and total size of the cached files, and when they were last updated.
+    Info,
+
+    /// Delete cached files. They are downloaded again when needed.
+    Clean {
+        /// Delete the files of the API (e.g., 'compute', 'gke', or 'compute:beta'). Accepts multiple APIs.
+        #[arg(long = "api", value_name = "NAME")]
+        apis: Vec<String>,
+
+        /// Delete the downloaded discovery documents and the discovery directory.
+        #[arg(long)]
+        discovered: bool,
+
+        /// Delete the responses cached by `zg exec --cache-ttl`.
+        #[arg(long)]
+        http: bool,
+
+        /// Delete all cached files, including the cached responses.
+        #[arg(long)]
+        all: bool,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+    },
+
+    /// Decode every cached file and report the broken ones (e.g., truncated by an interrupted write).
+    Verify {
+        /// Delete the broken files so that they are prepared again on the next use.
+        #[arg(long)]
+        delete: bool,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+    },
+}
+
+#[derive(Args, Debug, Default)]
+struct ConfirmArgs {
+    /// Delete without asking for confirmation.
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Print the files to delete without deleting them.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Main function of `zg cache`.
+pub async fn main(args: &CacheArgs) -> Result<(), ZgError> {
+    let dir = core::config_dir();
+    let mut stdin = std::io::stdin().lock();
+    match &args.command {
+        CacheCmd::Info => {
+            for line in info_lines(&dir, SystemTime::now())? {
+                println!("{}", line);
+            }
+        }
+        CacheCmd::Clean {
+            apis,
+            discovered,
+            http,
+            all,
+            confirm,
+        } => {
+            let files = select_files(&dir, apis, *discovered, *http, *all)?;
+            delete_files(&files, confirm, &mut stdin)?;
+        }
+        CacheCmd::Verify { delete, confirm } => {
+            let broken = verify(&dir)?;
+            if broken.is_empty() {
+                println!("All cached files are valid");
+                return Ok(());
+            }
+            for (path, e) in &broken {
+                println!("{}: {}", path.display(), e);
+            }
+            if *delete {
+                let files: Vec<PathBuf> = broken.into_iter().map(|(path, _)| path).collect();
+                delete_files(&files, confirm, &mut stdin)?;
+            } else {
+                return Err(ZgError::Deserialize(format!(
+                    "{} broken file(s) in the cache; delete them with `zg cache verify --delete`",
+                    broken.len()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files in the subdirectory of the cache, sorted by name. Missing directories have no files.
+fn cached_files(dir: &Path, subdir: &str) -> Result<Vec<PathBuf>, ZgError> {
+    let Ok(entries) = fs::read_dir(dir.join(subdir)) else {
+        return Ok(vec![]);
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the lines printed by `zg cache info`.
+fn info_lines(dir: &Path, now: SystemTime) -> Result<Vec<String>, ZgError> {
+    let mut lines = vec![format!("cache_dir: {}", dir.display())];
+    let mut last_updated: Option<SystemTime> = None;
+    for subdir in CACHE_SUBDIRS {
+        let files = cached_files(dir, subdir)?;
+        let mut size = 0;
+        for file in &files {
+            let metadata = fs::metadata(file)?;
+            size += metadata.len();
+            if let Ok(modified) = metadata.modified() {
+                last_updated = last_updated.max(Some(modified));
+            }
+        }
+        lines.push(format!(
+            "{}: {} file(s), {}",
+            subdir,
+            files.len(),
+            format_size(size)
+        ));
+    }
+    let last_updated = match last_updated {
+        Some(modified) => format_age(now.duration_since(modified).unwrap_or_default()),
+        None => "never".to_string(),
+    };
+    lines.push(format!("last_updated: {}", last_updated));
+    Ok(lines)
+}
+
+/// Selects the files deleted by `zg cache clean`. At least one of the options is required.
+fn select_files(
+    dir: &Path,
+    apis: &[String],
+    discovered: bool,
+    http: bool,
+    all: bool,
+) -> Result<Vec<PathBuf>, ZgError> {
+    if apis.is_empty() && !discovered && !http && !all {
+        return Err(
+            "Specify the files to delete with --api NAME, --discovered, --http, or --all".into(),
+        );
+    }
+    let mut files = Vec::new();
+    if all {
+        for subdir in CACHE_SUBDIRS {
+            files.extend(cached_files(dir, subdir)?);
+        }
+    } else if discovered {
+        files.extend(cached_files(dir, "discovered")?);
+    }
+    if all || http {
+        files.extend(cached_files(dir, HTTP_CACHE_DIR)?);
+    }
+    for api in apis {
+        let stem = api_file_stem(api);
+        let mut matched = Vec::new();
+        for subdir in CACHE_SUBDIRS {
+            matched.extend(cached_files(dir, subdir)?.into_iter().filter(|file| {
+                stem.matches(&file.file_stem().unwrap_or_default().to_string_lossy())
+            }));
+        }
+        if matched.is_empty() {
+            warn!("No cached files found for '{}'", api);
+        }
+        files.extend(matched);
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// File stem (the file name without the extension) of cached files of an API.
+enum ApiFileStem {
+    Exact(String),  // e.g., "compute_beta" for "compute:beta"
+    Prefix(String), // e.g., "compute_" for "compute" (all versions)
+}
+
+impl ApiFileStem {
+    fn matches(&self, stem: &str) -> bool {
+        match self {
+            ApiFileStem::Exact(exact) => stem == exact,
+            ApiFileStem::Prefix(prefix) => stem.len() > prefix.len() && stem.starts_with(prefix),
+        }
+    }
+}
+
+/// Returns the file stem of the API given as NAME or NAME:VERSION, resolving aliases (e.g., "gke" to "container").
+fn api_file_stem(api: &str) -> ApiFileStem {
+    let (name, version) = match api.split_once(':') {
+        Some((name, version)) => (name, Some(version)),
+        None => (api, None),
+    };
+    let name = supported_apis(true)
+        .into_iter()
+        .find(|supported| supported.name == name || supported.aliases.iter().any(|a| a == name))
+        .map(|supported| supported.name)
+        .unwrap_or_else(|| name.to_string());
+    match version {
+        Some(version) => ApiFileStem::Exact(
+            core::api_file_name(&format!("{}:{}", name, version))
+                .trim_end_matches(".msgpack")
+                .to_string(),
+        ),
+        None => ApiFileStem::Prefix(format!("{}_", name)),
+    }
+}
+
+/// Deletes the files after confirmation read from `input`, unless --yes is given. With --dry-run, only prints them.
+/// Returns the number of deleted files.
+fn delete_files(
+    files: &[PathBuf],
+    confirm: &ConfirmArgs,
+    input: &mut impl BufRead,
+) -> Result<usize, ZgError> {
+    if files.is_empty() {
+        println!("Nothing to delete");
+        return Ok(0);
+    }
+    let size: u64 = files
+        .iter()
+        .filter_map(|file| fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    if confirm.dry_run {
+        for file in files {
+            println!("Would delete {}", file.display());
+        }
+        println!(
+            "{} file(s), {} would be deleted",
+            files.len(),
+            format_size(size)
+        );
+        return Ok(0);
+    }
+    if !confirm.yes {
+        eprint!(
+            "Delete {} cached file(s), {}? [y/N] ",
+            files.len(),
+            format_size(size)
+        );
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Aborted");
+            return Ok(0);
+        }
+    }
+    for file in files {
+        fs::remove_file(file)?;
+    }
+    println!("Deleted {} file(s), {}", files.len(), format_size(size));
+    Ok(files.len())
+}
+
+/// Decodes the API files (msgpack) and the discovery documents (JSON) in the cache. Returns the broken ones with the errors.
+pub(crate) fn verify(dir: &Path) -> Result<Vec<(PathBuf, String)>, ZgError> {
+    let mut broken = Vec::new();
+    for file in cached_files(dir, "api")? {
+        if file.extension().is_some_and(|ext| ext == "msgpack") {
+            let reader = BufReader::new(File::open(&file)?);
+            if let Err(e) = core::ZgApi::deserialize(&mut Deserializer::new(reader)) {
+                broken.push((file, e.to_string()));
+            }
+        }
+    }
+    for file in cached_files(dir, "discovered")? {
+        if file.extension().is_some_and(|ext| ext == "json") {
+            let reader = BufReader::new(File::open(&file)?);
+            if let Err(e) = serde_json::from_reader::<_, serde_json::Value>(reader) {
+                broken.push((file, e.to_string()));
+            }
+        }
+    }
+    Ok(broken)
+}
+
+/// Formats the size in bytes, e.g., "1.5 MiB".
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Formats the elapsed time roughly, e.g., "3 days ago".
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} minute(s) ago", secs / 60),
+        3600..86400 => format!("{} hour(s) ago", secs / 3600),
+        _ => format!("{} day(s) ago", secs / 86400),
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a cache directory with an API file, a broken one, and discovery documents.
+    fn setup(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("zg-test-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("api")).unwrap();
+        fs::create_dir_all(dir.join("discovered")).unwrap();
+        let api = rmp_serde::to_vec(&core::ZgApi::testdata()).unwrap();
+        fs::write(dir.join("api").join("container_v1.msgpack"), &api).unwrap();
+        fs::write(
+            dir.join("api").join("compute_v1.msgpack"),
+            &api[..api.len() / 2],
+        )
+        .unwrap();
+        fs::write(dir.join("discovered").join("container_v1.json"), "{}").unwrap();
+        fs::write(
+            dir.join("discovered").join("_discovered_apis.json"),
+            "{\"items\"",
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_info_lines() {
+        let dir = setup("info");
+        let lines = info_lines(&dir, SystemTime::now() + Duration::from_secs(7200)).unwrap();
+        assert_eq!(lines[0], format!("cache_dir: {}", dir.display()));
+        assert!(lines[1].starts_with("api: 2 file(s), "), "{}", lines[1]);
+        assert_eq!(lines[2], "discovered: 2 file(s), 10 B");
+        assert_eq!(lines[3], "last_updated: 2 hour(s) ago");
+
+        let empty = dir.join("empty");
+        let lines = info_lines(&empty, SystemTime::now()).unwrap();
+        assert_eq!(lines[1], "api: 0 file(s), 0 B");
+        assert_eq!(lines[3], "last_updated: never");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_select_files() {
+        let dir = setup("select");
+        let names = |files: Vec<PathBuf>| -> Vec<String> {
+            files
+                .iter()
+                .map(|f| f.strip_prefix(&dir).unwrap().display().to_string())
+                .collect()
+        };
+        assert!(select_files(&dir, &[], false, false, false).is_err());
+        assert_eq!(
+            names(select_files(&dir, &["gke".to_string()], false, false, false).unwrap()),
+            vec!["api/container_v1.msgpack", "discovered/container_v1.json"]
+        );
+        assert_eq!(
+            names(select_files(&dir, &["compute:v1".to_string()], false, false, false).unwrap()),
+            vec!["api/compute_v1.msgpack"]
+        );
+        assert!(
+            select_files(&dir, &["compute:beta".to_string()], false, false, false)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            names(select_files(&dir, &["compute".to_string()], true, false, false).unwrap()),
+            vec![
+                "api/compute_v1.msgpack",
+                "discovered/_discovered_apis.json",
+                "discovered/container_v1.json"
+            ]
+        );
+        assert_eq!(
+            select_files(&dir, &[], false, false, true).unwrap().len(),
+            4
+        );
+
+        // Responses cached by `zg exec --cache-ttl`
+        fs::create_dir_all(dir.join(HTTP_CACHE_DIR)).unwrap();
+        fs::write(dir.join(HTTP_CACHE_DIR).join("cbf29ce484222325.json"), "{}").unwrap();
+        assert_eq!(
+            names(select_files(&dir, &[], false, true, false).unwrap()),
+            vec!["http_cache/cbf29ce484222325.json"]
+        );
+        assert_eq!(
+            select_files(&dir, &[], false, false, true).unwrap().len(),
+            5
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_delete_files_with_confirmation() {
+        let dir = setup("delete");
+        let files = select_files(&dir, &[], false, false, true).unwrap();
+
+        let dry_run = ConfirmArgs {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            delete_files(&files, &dry_run, &mut "y\n".as_bytes()).unwrap(),
+            0
+        );
+        assert!(files.iter().all(|f| f.exists()));
+
+        let ask = ConfirmArgs::default();
+        assert_eq!(
+            delete_files(&files, &ask, &mut "n\n".as_bytes()).unwrap(),
+            0
+        );
+        assert_eq!(delete_files(&files, &ask, &mut "".as_bytes()).unwrap(), 0);
+        assert!(files.iter().all(|f| f.exists()));
+        assert_eq!(
+            delete_files(&files[..1], &ask, &mut "y\n".as_bytes()).unwrap(),
+            1
+        );
+
+        let yes = ConfirmArgs {
+            yes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            delete_files(&files[1..], &yes, &mut "".as_bytes()).unwrap(),
+            3
+        );
+        assert!(files.iter().all(|f| !f.exists()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = setup("verify");
+        let broken: Vec<PathBuf> = verify(&dir).unwrap().into_iter().map(|(f, _)| f).collect();
+        assert_eq!(
+            broken,
+            vec![
+                dir.join("api").join("compute_v1.msgpack"),
+                dir.join("discovered").join("_discovered_apis.json")
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_size_and_age() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+        assert_eq!(format_age(Duration::from_secs(5)), "just now");
+        assert_eq!(format_age(Duration::from_secs(300)), "5 minute(s) ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400)), "3 day(s) ago");
+    }
+}
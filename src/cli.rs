@@ -0,0 +1,352 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `zg` command line: parses the arguments and dispatches the subcommands.
+
+use clap::{Parser, Subcommand};
+
+use super::{
+    auth, cache, color, completions, config, desc, discovery, doctor, error, exec, export, list,
+    logging, open, output, saved, shell, supported_apis, timing, update,
+};
+
+#[derive(Parser)]
+#[command(name = "zg")]
+#[command(version, about)]
+struct Cli {
+    /// Activate debug mode to see more detailed logs.
+    #[arg(long, global = true)]
+    debug: bool,
+
+    /// Log credentials (access tokens, API keys, etc.) as they are with --debug, instead of only their last 4 characters. For local troubleshooting; don't share the output.
+    #[arg(long, global = true)]
+    debug_show_secrets: bool,
+
+    /// Only Gemini API (generativelanguage) requires an API key. Other APIs ignore this value as they use gcloud to retrieve credentials
+    #[arg(long, global = true)]
+    api_key: Option<String>,
+
+    /// Credentials file to get access tokens with, instead of gcloud. Supports Workload Identity Federation (`"type": "external_account"`) files, as created by `gcloud iam workload-identity-pools create-cred-config`.
+    #[arg(long, global = true, value_name = "PATH")]
+    credentials_file: Option<String>,
+
+    /// Universe domain to send requests to instead of googleapis.com (e.g., for Trusted Partner Cloud). Defaults to
+    /// `universe_domain` in the config file, or `core/universe_domain` of gcloud when gcloud gives the access token.
+    #[arg(long, global = true, value_name = "DOMAIN")]
+    universe_domain: Option<String>,
+
+    /// Re-validate the discovery directory with a conditional request before running the command, even if the cached
+    /// one is not expired yet.
+    #[arg(long, global = true)]
+    refresh_discovery: bool,
+
+    /// Allow services that are not supported by zg yet but listed in the discovery directory (see `zg ls --discovered`).
+    #[arg(long, global = true)]
+    any_api: bool,
+
+    /// Send the access token with the requests to download discovery documents, for APIs that reject anonymous ones
+    /// (e.g., private previews). Without it, the token is sent only when the anonymous request is rejected with 401 or 403.
+    #[arg(long, global = true)]
+    auth_discovery: bool,
+
+    /// Re-download the API definition before running the command if it's outdated (older than 30 days by default).
+    #[arg(long, global = true)]
+    auto_refresh: bool,
+
+    /// Print the time spent in each phase (loading API files, gcloud, HTTP, etc.) to stderr at the end.
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Output format of ls, desc, exec, and update. `text` is the human-readable output of each command. `ndjson` prints one compact JSON value per line, e.g., an item of a list (`zg exec --paginate` prints each page as it's fetched). Defaults to `default_output` in the config file, or text.
+    #[arg(long, global = true, value_enum, alias = "output")]
+    format: Option<output::Format>,
+
+    /// When to colorize the output. `auto` colorizes only when stdout is a terminal, and not with the NO_COLOR environment variable or `TERM=dumb`. `--color` alone is `always`. Defaults to `color` in the config file, or auto.
+    #[arg(long, global = true, value_enum, value_name = "WHEN", num_args = 0..=1, require_equals = true, default_missing_value = "always")]
+    color: Option<color::ColorWhen>,
+
+    /// Don't colorize the output. Same as `--color=never`.
+    #[arg(long, global = true, conflicts_with = "color")]
+    no_color: bool,
+
+    /// Format of the logs written to stderr. `json` writes one JSON object per record. Defaults to the ZG_LOG_FORMAT environment variable, or text.
+    #[arg(long, global = true, value_enum)]
+    log_format: Option<logging::LogFormat>,
+
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Update API definitions stored locally.
+    Update(update::UpdateArgs),
+
+    /// List services, resources, or methods (alias: ls).
+    #[clap(aliases = &["ls"])]
+    List(list::ListArgs),
+
+    /// Describe details of services, resources, or methods (aliases: describe, show).
+    ///
+    /// Especially, describing methods is useful to understand the required (minimum) parameters/data to send via `zg exec`. Note that the shown minimum is merely a suggestion, you may need to tweak details.
+    #[clap(aliases = &["describe", "show"])]
+    Desc(desc::DescArgs),
+
+    /// Execute an API method (aliases: ex, execute).
+    #[clap(aliases = &["ex", "execute"])]
+    Exec(Box<exec::ExecArgs>),
+
+    /// Run a request saved with `zg exec --save-as NAME`, optionally overriding its -p, -H, and --data.
+    Run(saved::RunArgs),
+
+    /// Export API definitions into other formats (e.g., `zg export openapi container clusters`).
+    Export(export::ExportArgs),
+
+    /// Open the API reference of services, resources, or methods in a browser (the link shown by `zg desc`).
+    Open(open::OpenArgs),
+
+    /// Get or edit the persistent settings in the config file.
+    Config(config::ConfigArgs),
+
+    /// Diagnose common environment problems (gcloud, project, cache, network) and print hints to fix them.
+    Doctor(doctor::DoctorArgs),
+
+    /// Log in without gcloud (`zg auth login --device`), or revoke the stored credential.
+    Auth(auth::AuthArgs),
+
+    /// Inspect, clean up, or verify the cached API definitions and discovery documents.
+    Cache(cache::CacheArgs),
+
+    /// Run zg commands interactively, keeping the loaded API definitions and the access token between commands.
+    Shell(shell::ShellArgs),
+
+    /// Print the shell completion script (e.g., `zg completions bash > ~/.local/share/bash-completion/completions/zg`).
+    Completions(completions::CompletionsArgs),
+}
+
+/// Runs zg with the command-line arguments of the process. On an error, prints it (with a hint if any) and exits with
+/// its exit code.
+pub async fn main() -> Result<(), error::ZgError> {
+    let cli = Cli::parse();
+
+    let level = if cli.debug { "debug" } else { "info" };
+    let log_format = cli
+        .log_format
+        .or_else(|| logging::LogFormat::from_env(|key| std::env::var(key).ok()))
+        .unwrap_or_default();
+    logging::builder(log_format, level).init();
+    logging::set_show_secrets(cli.debug_show_secrets);
+
+    let timings = cli.timings;
+    let result = run(cli).await;
+    if timings {
+        eprint!("{}", timing::summary());
+    }
+
+    result.map_err(|e| {
+        eprintln!("Error: {}", e);
+        if let Some(hint) = e.hint() {
+            eprintln!("Hint: {}", hint);
+        }
+        std::process::exit(e.exit_code());
+    })
+}
+
+/// Loads the config file and dispatches the subcommand. Command-line flags take precedence over the config.
+async fn run(cli: Cli) -> Result<(), error::ZgError> {
+    // `zg config` and `zg completions` don't depend on the loaded values, so that they work even when the config file has an invalid value.
+    if !matches!(cli.command, Cmd::Config(_) | Cmd::Completions(_)) {
+        init_config(&cli)?;
+    }
+    if let Cmd::Shell(_) = cli.command {
+        use clap::CommandFactory;
+        let commands = Cli::command()
+            .get_subcommands()
+            .flat_map(|cmd| std::iter::once(cmd.get_name()).chain(cmd.get_all_aliases()))
+            .map(str::to_string)
+            .collect();
+        return shell::main(commands, |words| async move {
+            match Cli::try_parse_from(std::iter::once("zg".to_string()).chain(words)) {
+                Ok(cli) => dispatch(cli).await,
+                // Help and version are printed as "errors" by clap
+                Err(e) => Ok(e.print()?),
+            }
+        })
+        .await;
+    }
+    dispatch(cli).await
+}
+
+/// Loads the config file once per process; flags given to the commands in `zg shell` don't change it.
+fn init_config(cli: &Cli) -> Result<(), error::ZgError> {
+    let mut config = config::load_layered(
+        &config::config_path(),
+        config::local_config_path().as_deref(),
+    )?;
+    config.apply_env_overrides(|key| std::env::var(key).ok());
+    if cli.any_api {
+        config.any_api = Some(true);
+    }
+    if cli.auto_refresh {
+        config.auto_refresh = Some(true);
+    }
+    if cli.auth_discovery {
+        config.auth_discovery = Some(true);
+    }
+    if let Some(path) = &cli.credentials_file {
+        config.credentials_file = Some(path.clone());
+    }
+    if let Some(domain) = &cli.universe_domain {
+        config.universe_domain = Some(domain.clone());
+    }
+    config::init(config);
+    supported_apis::init_extra_apis(supported_apis::load_extra_apis(
+        &supported_apis::extra_apis_dir(),
+    )?);
+    Ok(())
+}
+
+async fn dispatch(cli: Cli) -> Result<(), error::ZgError> {
+    let config = config::current();
+
+    if cli.refresh_discovery {
+        discovery::ensure_discovered_apis(true, &discovery::DiscoveryAuth::current()).await?;
+    }
+
+    let format = output::resolve(cli.format, config.default_output.as_deref());
+    let when = match cli.no_color {
+        true => Some(color::ColorWhen::Never),
+        false => cli.color,
+    };
+    let (when, configured) = match &cli.command {
+        Cmd::List(args) => (
+            when.or(args.color_when()),
+            list::ListArgs::configured_color(config),
+        ),
+        _ => (when, config.color),
+    };
+    color::init(when, configured);
+    match cli.command {
+        Cmd::Update(args) => match format {
+            output::Format::Text => update::main(&args, cli.api_key).await,
+            format => {
+                let summary = update::result(&args, cli.api_key).await?;
+                output::print(&summary, format)?;
+                summary.check()
+            }
+        },
+        Cmd::List(mut args) if args.picking() => {
+            args.apply_config(config);
+            match list::pick(&args, cli.api_key.clone()).await? {
+                Some(picked) if args.pick_exec() => {
+                    if picked.confirm_exec(&mut std::io::stdin().lock())? {
+                        run_exec(picked.exec_args()?, config, format, cli.api_key).await
+                    } else {
+                        println!("Aborted");
+                        Ok(())
+                    }
+                }
+                Some(picked) => {
+                    print!("{}", picked.commands());
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+        Cmd::List(mut args) => {
+            args.apply_config(config);
+            match args.format(format) {
+                output::Format::Text => list::main(&args, cli.api_key).await,
+                format => output::print(&list::result(&args, cli.api_key).await?, format),
+            }
+        }
+        Cmd::Desc(mut args) => {
+            args.apply_config(config);
+            match format {
+                output::Format::Text => desc::main(&args, cli.api_key).await,
+                format => output::print(&desc::result(&args, cli.api_key).await?, format),
+            }
+        }
+        Cmd::Exec(args) => run_exec(*args, config, format, cli.api_key).await,
+        Cmd::Run(args) if args.is_list() => saved::list(),
+        Cmd::Run(args) => run_exec(saved::exec_args(&args)?, config, format, cli.api_key).await,
+        Cmd::Export(mut args) => {
+            args.apply_config(config);
+            export::main(&args, format, cli.api_key).await
+        }
+        Cmd::Open(mut args) => {
+            args.apply_config(config);
+            open::main(&args, cli.api_key).await
+        }
+        Cmd::Config(args) => config::main(&args).await,
+        Cmd::Doctor(args) => doctor::main(&args, format, cli.api_key).await,
+        Cmd::Auth(args) => auth::main(&args).await,
+        Cmd::Cache(args) => cache::main(&args).await,
+        Cmd::Shell(_) => Err("zg shell is already running".into()),
+        Cmd::Completions(args) => {
+            use clap::CommandFactory;
+            print!("{}", completions::generate(args.shell, &Cli::command()));
+            Ok(())
+        }
+    }
+}
+
+async fn run_exec(
+    mut args: exec::ExecArgs,
+    config: &config::Config,
+    format: output::Format,
+    api_key: Option<String>,
+) -> Result<(), error::ZgError> {
+    args.apply_config(config);
+    match format {
+        output::Format::Text => exec::main(&args, api_key).await,
+        _ if args.overrides_format() => exec::main(&args, api_key).await,
+        output::Format::Ndjson => exec::ndjson(&args, api_key).await,
+        format => exec::print(&args, api_key, format).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn generate_completions() {
+        use clap::{CommandFactory, ValueEnum};
+        for shell in completions::Shell::value_variants() {
+            let script = completions::generate(*shell, &Cli::command());
+            assert!(script.contains("exec"), "{:?}", shell);
+            assert!(script.contains("refresh-discovery"), "{:?}", shell);
+        }
+    }
+    #[test]
+    fn open_takes_only_the_target_of_desc() {
+        let parse = |args: &[&str]| Cli::try_parse_from(["zg"].iter().chain(args));
+        assert!(parse(&["open", "container", "clusters", "get", "--strict"]).is_ok());
+        for flag in [
+            "--json-schema",
+            "--raw-discovery",
+            "--writable-only",
+            "--response-example",
+        ] {
+            assert!(parse(&["open", "container", "clusters", "get", flag]).is_err());
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use regex::Regex;
+use url::Url;
+
+use super::core::{self, ZgApi, ZgMethod};
+use super::error::ZgError;
+
+/// Entry point of the programmatic API: resolves methods of a loaded API and builds requests for them.
+#[derive(Debug, Clone)]
+pub struct Client {
+    api: ZgApi,
+}
+
+impl Client {
+    pub fn new(api: ZgApi) -> Self {
+        Self { api }
+    }
+
+    /// Loads the API by a service name or alias, optionally with a version (e.g., "gke", "container:v1beta1").
+    /// Downloads the API definition if it's not cached yet.
+    pub async fn load(service: &str) -> Result<Self, ZgError> {
+        Ok(Self::new(core::load_api_file(service, None).await?))
+    }
+
+    pub fn api(&self) -> &ZgApi {
+        &self.api
+    }
+
+    /// Starts building a request for the method id (e.g., "container.projects.locations.clusters.get").
+    /// Ambiguous (partial) ids are errors rather than being resolved by heuristics.
+    pub fn request(&self, method_id: &str) -> Result<RequestBuilder<'_>, ZgError> {
+        let (_, method) = core::find_method_by_id(&self.api, method_id, true)?;
+        Ok(RequestBuilder {
+            api: &self.api,
+            method,
+            params: Vec::new(),
+        })
+    }
+}
+
+/// Builder of a request for a method, created by `Client::request`.
+#[derive(Debug, Clone)]
+pub struct RequestBuilder<'a> {
+    api: &'a ZgApi,
+    method: ZgMethod,
+    params: Vec<(String, String)>,
+}
+
+impl RequestBuilder<'_> {
+    /// Adds a parameter. Parameters in the method's path (e.g., "projectsId") fill the placeholders,
+    /// and the others are sent as query parameters.
+    pub fn param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn method(&self) -> &ZgMethod {
+        &self.method
+    }
+
+    /// HTTP method of the request (e.g., "GET").
+    pub fn http_method(&self) -> &str {
+        &self.method.http_method
+    }
+
+    /// Builds the URL of the request. Fails if any placeholder in the path is left unfilled.
+    pub fn build_url(&self) -> Result<String, ZgError> {
+        let (path, query_params) = fill_path_params(&self.method.flat_path, &self.params);
+
+        let placeholder = Regex::new(r"\{\+?([^}]+)\}")?;
+        let missing: Vec<&str> = placeholder
+            .captures_iter(&path)
+            .filter_map(|c| c.get(1).map(|m| m.as_str()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Missing path parameters for '{}': {}",
+                self.method.id,
+                missing.join(", ")
+            )
+            .into());
+        }
+
+        join_url(&self.api.base_url_for(&path), &path, &query_params)
+    }
+}
+
+/// Fills the placeholders in the flat path (e.g., "v1/projects/{projectsId}") with the matching params,
+/// and returns the filled path with the rest of the params, which are meant to be query parameters.
+pub fn fill_path_params<'a>(
+    flat_path: &str,
+    params: &'a [(String, String)],
+) -> (String, Vec<(&'a str, &'a str)>) {
+    let mut path = flat_path.to_string();
+    let mut query_params = Vec::new();
+    for (key, value) in params {
+        let placeholder = format!("{{{}}}", key);
+        if path.contains(&placeholder) {
+            path = path.replace(&placeholder, value); // path params
+        } else {
+            query_params.push((key.as_str(), value.as_str())); // query params
+        }
+    }
+    (path, query_params)
+}
+
+/// Joins the base URL, the path, and the query parameters into a URL, with one slash between the base URL and the path.
+pub fn join_url(
+    base_url: &str,
+    path: &str,
+    query_params: &[(&str, &str)],
+) -> Result<String, ZgError> {
+    let mut url = Url::parse(&core::join_path(base_url, path)).map_err(|e| {
+        format!(
+            "Failed to build the URL from the base URL '{}' and the path '{}': {}",
+            base_url, path, e
+        )
+    })?;
+    if !query_params.is_empty() {
+        url.query_pairs_mut().extend_pairs(query_params);
+    }
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_path_params() {
+        let params = vec![
+            ("projectsId".to_string(), "p1".to_string()),
+            ("pageSize".to_string(), "10".to_string()),
+        ];
+        let (path, query) = fill_path_params("v1/projects/{projectsId}/clusters", &params);
+        assert_eq!(path, "v1/projects/p1/clusters");
+        assert_eq!(query, vec![("pageSize", "10")]);
+    }
+
+    #[test]
+    fn test_join_url() {
+        assert_eq!(
+            join_url(
+                "https://example.com",
+                "/v1/projects/p",
+                &[("pageSize", "10")]
+            )
+            .unwrap(),
+            "https://example.com/v1/projects/p?pageSize=10"
+        );
+        let e = join_url("example.com/", "v1/projects/p", &[]).unwrap_err();
+        assert!(
+            e.to_string()
+                .contains("base URL 'example.com/' and the path 'v1/projects/p'"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn test_build_url_missing_params() {
+        let api = ZgApi::testdata();
+        let client = Client::new(api);
+        let request = client.request("testapi.projects.testres.list").unwrap();
+        let e = request.build_url().unwrap_err();
+        assert!(e.to_string().contains("projectsId, testresId"), "{}", e);
+
+        let url = request
+            .param("projectsId", "p1")
+            .param("testresId", "r1")
+            .build_url()
+            .unwrap();
+        assert_eq!(url, "https://example.com/v1/projects/p1/testres/r1");
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Whether to colorize the output, decided once per command from `--color`, `--no-color`, the config file, `NO_COLOR`,
+//! `TERM=dumb`, and whether stdout is a terminal. The commands style their output with the helpers here, which leave
+//! the text plain when colors are off.
+
+use clap::ValueEnum;
+use prettytable::{Cell, Table};
+use serde_json::Value;
+use std::fmt::Write;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::error::ZgError;
+
+/// When to colorize the output (`--color[=WHEN]`).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorWhen {
+    /// Colorize when stdout is a terminal, unless `NO_COLOR` is set or `TERM` is `dumb`.
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Resolves whether to colorize: `when` given on the command line, or the config file (`color = true/false`),
+/// otherwise automatically: not with a non-empty `NO_COLOR` or `TERM=dumb`, and only when stdout is a terminal.
+/// Environment variables are looked up by `env`.
+pub fn resolve(
+    when: Option<ColorWhen>,
+    configured: Option<bool>,
+    env: impl Fn(&str) -> Option<String>,
+    is_terminal: bool,
+) -> bool {
+    match (when, configured) {
+        (Some(ColorWhen::Always), _) => return true,
+        (Some(ColorWhen::Never), _) => return false,
+        (None, Some(configured)) => return configured,
+        (Some(ColorWhen::Auto), _) | (None, None) => {}
+    }
+    if env("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    if env("TERM").is_some_and(|term| term == "dumb") {
+        return false;
+    }
+    is_terminal
+}
+
+/// Resolves whether to colorize the output of the command from the environment and stdout, and sets it.
+pub fn init(when: Option<ColorWhen>, configured: Option<bool>) {
+    set_enabled(resolve(
+        when,
+        configured,
+        |name| std::env::var(name).ok(),
+        std::io::stdout().is_terminal(),
+    ));
+}
+
+/// Sets whether to colorize the output of the command. Off until it's set (e.g., in unit tests).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Styles of the output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Bold,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Dim,
+}
+
+impl Style {
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Bold => "1",
+            Style::Red => "31",
+            Style::Green => "32",
+            Style::Yellow => "33",
+            Style::Blue => "34",
+            Style::Dim => "2",
+        }
+    }
+
+    /// The style spec of prettytable.
+    fn spec(self) -> &'static str {
+        match self {
+            Style::Bold => "b",
+            Style::Red => "Fr",
+            Style::Green => "Fg",
+            Style::Yellow => "Fy",
+            Style::Blue => "Fb",
+            Style::Dim => "",
+        }
+    }
+}
+
+/// The style of an HTTP method, to tell the methods that change resources: POST in green, PUT and PATCH in blue, and
+/// DELETE in red.
+pub fn http_method_style(http_method: &str) -> Option<Style> {
+    match http_method {
+        "POST" => Some(Style::Green),
+        "PUT" | "PATCH" => Some(Style::Blue),
+        "DELETE" => Some(Style::Red),
+        _ => None,
+    }
+}
+
+/// Returns the text in the style when colors are on, otherwise as it is.
+pub fn style(text: &str, style: Style) -> String {
+    paint(text, Some(style), enabled())
+}
+
+fn paint(text: &str, style: Option<Style>, enabled: bool) -> String {
+    match style.filter(|_| enabled) {
+        Some(style) => format!("\x1b[{}m{}\x1b[0m", style.ansi(), text),
+        None => text.to_string(),
+    }
+}
+
+/// Returns a table cell in the style when colors are on.
+pub fn cell(text: &str, style: Option<Style>) -> Cell {
+    match style.filter(|_| enabled()) {
+        Some(style) => Cell::new(text).style_spec(style.spec()),
+        None => Cell::new(text),
+    }
+}
+
+/// Prints the table to stdout, with its styles (e.g., bold titles) only when colors are on.
+pub fn print_table(table: &Table) -> Result<(), ZgError> {
+    match enabled() {
+        true => table.print_tty(true)?,
+        false => table.print(&mut std::io::stdout().lock())?,
+    };
+    Ok(())
+}
+
+/// Returns the value as pretty-printed JSON, highlighted when colors are on: keys in blue, strings in green, numbers
+/// and booleans in yellow, and null dimmed.
+pub fn json(value: &Value) -> Result<String, ZgError> {
+    match enabled() {
+        true => {
+            let mut out = String::new();
+            write_json(&mut out, value, 0, true)?;
+            Ok(out)
+        }
+        false => Ok(serde_json::to_string_pretty(value)?),
+    }
+}
+
+/// Writes the value in the layout of `serde_json::to_string_pretty`.
+fn write_json(out: &mut String, value: &Value, depth: usize, enabled: bool) -> Result<(), ZgError> {
+    let indent = |depth: usize| "  ".repeat(depth);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (i, (key, value)) in map.iter().enumerate() {
+                let key = serde_json::to_string(key)?;
+                write!(
+                    out,
+                    "{}{}: ",
+                    indent(depth + 1),
+                    paint(&key, Some(Style::Blue), enabled)
+                )?;
+                write_json(out, value, depth + 1, enabled)?;
+                out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            write!(out, "{}}}", indent(depth))?;
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                write_json(out, item, depth + 1, enabled)?;
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            write!(out, "{}]", indent(depth))?;
+        }
+        Value::String(_) => out.push_str(&paint(&value.to_string(), Some(Style::Green), enabled)),
+        Value::Number(_) | Value::Bool(_) => {
+            out.push_str(&paint(&value.to_string(), Some(Style::Yellow), enabled))
+        }
+        Value::Null => out.push_str(&paint("null", Some(Style::Dim), enabled)),
+        // Empty objects and arrays
+        _ => out.push_str(&value.to_string()),
+    }
+    Ok(())
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let none = env(&[]);
+        let no_color = env(&[("NO_COLOR", "1")]);
+        let dumb = env(&[("TERM", "dumb")]);
+
+        // Auto: only on a terminal
+        assert!(resolve(None, None, none, true));
+        assert!(!resolve(None, None, none, false));
+        assert!(resolve(Some(ColorWhen::Auto), None, none, true));
+        // NO_COLOR (unless empty) and dumb terminals turn it off
+        assert!(!resolve(None, None, no_color, true));
+        assert!(resolve(None, None, env(&[("NO_COLOR", "")]), true));
+        assert!(!resolve(None, None, dumb, true));
+        assert!(resolve(
+            None,
+            None,
+            env(&[("TERM", "xterm-256color")]),
+            true
+        ));
+        // The config file takes precedence over the environment and the terminal
+        assert!(resolve(None, Some(true), no_color, false));
+        assert!(!resolve(None, Some(false), none, true));
+        // --color=auto ignores the config file
+        assert!(!resolve(Some(ColorWhen::Auto), Some(true), none, false));
+        // --color=always and --no-color (never) take precedence over everything
+        assert!(resolve(
+            Some(ColorWhen::Always),
+            Some(false),
+            no_color,
+            false
+        ));
+        assert!(resolve(Some(ColorWhen::Always), None, dumb, false));
+        assert!(!resolve(Some(ColorWhen::Never), Some(true), none, true));
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(
+            paint("DELETE", Some(Style::Red), true),
+            "\x1b[31mDELETE\x1b[0m"
+        );
+        assert_eq!(paint("DELETE", Some(Style::Red), false), "DELETE");
+        assert_eq!(paint("DELETE", None, true), "DELETE");
+        assert_eq!(http_method_style("PATCH"), Some(Style::Blue));
+        assert_eq!(http_method_style("GET"), None);
+        // Off unless set
+        assert_eq!(style("DELETE", Style::Red), "DELETE");
+    }
+
+    #[test]
+    fn test_write_json() {
+        let value =
+            json!({"name": "c1", "nodes": [1, true, null], "labels": {}, "tags": [], "s": "a\"b"});
+        let mut plain = String::new();
+        write_json(&mut plain, &value, 0, false).unwrap();
+        assert_eq!(plain, serde_json::to_string_pretty(&value).unwrap());
+
+        let mut colored = String::new();
+        write_json(&mut colored, &json!({"n": [1, null]}), 0, true).unwrap();
+        assert_eq!(
+            colored,
+            "{\n  \x1b[34m\"n\"\x1b[0m: [\n    \x1b[33m1\x1b[0m,\n    \x1b[2mnull\x1b[0m\n  ]\n}"
+        );
+    }
+}
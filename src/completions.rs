@@ -0,0 +1,34 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Static shell completion scripts generated from the clap command tree by clap_complete.
+//! Completes subcommands (and their aliases), flags, and possible values; services, resources, and methods are not completed.
+
+use clap::Args;
+pub use clap_complete::Shell;
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for. The script is printed to stdout.
+    pub shell: Shell,
+}
+
+/// Generates the completion script of `cmd` for the shell.
+pub fn generate(shell: Shell, cmd: &clap::Command) -> String {
+    let mut cmd = cmd.clone();
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, name, &mut script);
+    String::from_utf8_lossy(&script).into_owned()
+}
@@ -0,0 +1,1267 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::{Args, Subcommand};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml_edit::{DocumentMut, Item, Table};
+
+use super::core;
+use super::discovery;
+use super::error::ZgError;
+use super::supported_apis::{builtin_apis, Category, SupportedApi};
+
+/// Name of the user configuration file in the config directory.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Name of the project-local directory (e.g., at the root of a repository) whose `config.toml` overrides the user
+/// configuration for the commands run under it.
+const LOCAL_DIR: &str = ".zg";
+
+/// File in the config directory recording the project-local config files trusted with `zg config trust`, each with
+/// the SHA-256 of its content when it was trusted.
+const TRUST_FILE: &str = "trusted.toml";
+
+/// Keys of a project-local config file that send the requests (and the access tokens with them) elsewhere. They're
+/// applied only when the file is trusted, so that a cloned repository can't redirect them.
+const TRUSTED_KEYS: &[&str] = &[
+    "endpoints",
+    "universe_domain",
+    "credentials_file",
+    "custom_apis",
+];
+
+/// Environment variables overriding the keys in the config file (e.g., `ZG_PROJECT=my-project zg ex ...`).
+const ENV_OVERRIDES: &[(&str, &str, ConfigField)] = &[
+    ("project", "ZG_PROJECT", |config| &mut config.project),
+    ("region", "ZG_REGION", |config| &mut config.region),
+    ("zone", "ZG_ZONE", |config| &mut config.zone),
+    ("billing_project", "ZG_BILLING_PROJECT", |config| {
+        &mut config.billing_project
+    }),
+    ("credentials_file", "ZG_CREDENTIALS_FILE", |config| {
+        &mut config.credentials_file
+    }),
+    ("universe_domain", "ZG_UNIVERSE_DOMAIN", |config| {
+        &mut config.universe_domain
+    }),
+];
+
+type ConfigField = fn(&mut Config) -> &mut Option<String>;
+
+/// The universe domain of the public Google Cloud.
+pub const DEFAULT_UNIVERSE_DOMAIN: &str = "googleapis.com";
+
+/// The configuration loaded at startup. Falls back to the defaults when not initialized (e.g., in unit tests).
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The project-local directory of the current directory, looked up once.
+static LOCAL: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// User configuration read from `<config_dir>/config.toml`.
+/// Every key is optional; command-line flags always take precedence over the values here.
+///
+/// ```toml
+/// project = "my-project"
+/// billing_project = "my-billing-project"
+///
+/// [list]
+/// long = true
+/// sort = "name"
+///
+/// [method_aliases]
+/// ls = ["list", "aggregatedList"]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Output format used when no format is given on the command line.
+    pub default_output: Option<String>,
+
+    /// Colorize the output (true) or never (false), instead of only on a terminal without `NO_COLOR`.
+    pub color: Option<bool>,
+
+    /// Used to fill project placeholders in URLs instead of `gcloud config get core/project`.
+    pub project: Option<String>,
+
+    /// Used to fill region placeholders in URLs instead of `gcloud config get compute/region`.
+    pub region: Option<String>,
+
+    /// Used to fill zone placeholders in URLs instead of `gcloud config get compute/zone`.
+    pub zone: Option<String>,
+
+    /// Sent as the `x-goog-user-project` header to bill requests (and quota) to the project.
+    pub billing_project: Option<String>,
+
+    /// Credentials file used for access tokens instead of gcloud (same as `--credentials-file`).
+    /// Only Workload Identity Federation (`"type": "external_account"`) files are supported.
+    pub credentials_file: Option<String>,
+
+    /// Universe domain the APIs are served under instead of `googleapis.com` (e.g., a Trusted Partner Cloud deployment),
+    /// same as `--universe-domain`. Defaults to `core/universe_domain` of gcloud when gcloud gives the access token.
+    pub universe_domain: Option<String>,
+
+    /// Download the discovery documents from the hosts of the universe domain too (off by default).
+    pub universe_discovery: Option<bool>,
+
+    /// Send the access token with the requests to download discovery documents (same as `--auth-discovery`). Without
+    /// it, they are anonymous unless rejected with 401 or 403.
+    pub auth_discovery: Option<bool>,
+
+    /// Re-download API definitions older than `api_max_age_days` when loading them (same as `--auto-refresh`).
+    pub auto_refresh: Option<bool>,
+
+    /// Age in days after which API definitions are considered outdated (30 by default).
+    pub api_max_age_days: Option<u64>,
+
+    /// Age in days after which the cached discovery directory is re-validated with a conditional request (7 by
+    /// default). `ZG_DISCOVERY_TTL_DAYS` overrides it.
+    pub discovery_ttl_days: Option<u64>,
+
+    /// Allow any API in the discovery directory, even if it isn't in the supported list (same as `--any-api`).
+    pub any_api: Option<bool>,
+
+    /// Keep all schemas in the API definitions, including the ones prepared on first use (same as
+    /// `zg update --keep-all-schemas`).
+    pub keep_all_schemas: Option<bool>,
+
+    /// History of the requests of `zg exec`, repeated with `--rerun`.
+    pub history: HistoryConfig,
+
+    /// Method aliases extending (or overriding) the built-in `core::METHOD_ALIASES`.
+    pub method_aliases: HashMap<String, Vec<String>>,
+
+    /// APIs outside Google Cloud (e.g., Cloud Endpoints services) registered by name, each with its discovery document URL.
+    pub custom_apis: BTreeMap<String, CustomApiConfig>,
+
+    /// Defaults of `zg list`.
+    pub list: ListConfig,
+
+    /// Defaults of `zg desc`.
+    pub desc: DescConfig,
+
+    /// Defaults of `zg exec`.
+    pub exec: ExecConfig,
+
+    /// Query parameters `zg exec` sends by default, globally and per service.
+    pub defaults: DefaultsConfig,
+
+    /// Versions used instead of the default version of the services (by name or alias) when no `:version` is given,
+    /// e.g., `cloudfunctions = "v1"`.
+    pub versions: BTreeMap<String, String>,
+
+    /// Endpoints `zg exec` sends the requests of the services (by name or alias) to instead of their base URL,
+    /// e.g., `pubsub = "http://localhost:8085"` for an emulator (same as `--endpoint`).
+    pub endpoints: BTreeMap<String, String>,
+}
+
+/// A custom API registered in the config file.
+///
+/// ```toml
+/// [custom_apis.orders]
+/// discovery_url = "https://orders.example.com/$discovery/rest?version={version}"
+/// versions = ["v1"]
+/// aliases = ["ord"]
+/// headers = { Authorization = "Bearer xxx" }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CustomApiConfig {
+    /// URL of the discovery document. `{version}` is replaced with each version. `file://` URLs are also accepted.
+    pub discovery_url: String,
+    #[serde(default = "default_custom_api_versions")]
+    pub versions: Vec<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub title: Option<String>,
+    pub category: Option<Category>,
+    /// Extra headers sent when downloading the discovery document (e.g., Authorization).
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_custom_api_versions() -> Vec<String> {
+    vec!["v1".to_string()]
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record the requests of `zg exec` (off by default).
+    pub enabled: Option<bool>,
+    /// Record --data as well (off by default, as request bodies may have secrets).
+    pub record_bodies: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ListConfig {
+    pub all: Option<bool>,
+    pub aliases: Option<bool>,
+    pub category: Option<bool>,
+    pub long: Option<bool>,
+    pub color: Option<bool>,
+    pub sort: Option<String>,
+    pub reverse: Option<bool>,
+    pub strict: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DescConfig {
+    pub strict: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ExecConfig {
+    pub strict: Option<bool>,
+}
+
+/// Default query parameters, sent when the method has them and they're not given with `-p`.
+/// The ones of the service (by its name, e.g., `compute` rather than an alias) take precedence over the global ones.
+///
+/// ```toml
+/// [defaults.params]
+/// pageSize = 500
+///
+/// [defaults.aiplatform.params]
+/// view = "FULL"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct DefaultsConfig {
+    pub params: BTreeMap<String, toml::Value>,
+    #[serde(flatten)]
+    pub services: BTreeMap<String, ServiceDefaultsConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct ServiceDefaultsConfig {
+    pub params: BTreeMap<String, toml::Value>,
+}
+
+impl DefaultsConfig {
+    /// Returns the default query parameters of the service that the method accepts, sorted by name.
+    pub fn params(&self, service: &str, method: &core::ZgMethod) -> Vec<(String, String)> {
+        let mut params: BTreeMap<&String, &toml::Value> = self.params.iter().collect();
+        if let Some(service) = self.services.get(service) {
+            params.extend(service.params.iter());
+        }
+        params
+            .into_iter()
+            .filter(|(name, _)| method.query_params.iter().any(|qp| qp.name == **name))
+            .map(|(name, value)| {
+                let value = value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), str::to_string);
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+impl Config {
+    /// Returns the custom APIs as supported APIs so that they're treated like the built-in ones.
+    pub fn custom_supported_apis(&self) -> Vec<SupportedApi> {
+        self.custom_apis
+            .iter()
+            .map(|(name, custom)| SupportedApi {
+                name: name.clone(),
+                title: custom.title.clone().unwrap_or_else(|| name.clone()),
+                category: custom.category.unwrap_or(Category::Custom),
+                aliases: custom.aliases.clone(),
+                versions: custom.versions.clone(),
+                status: Default::default(),
+                notes: None,
+                discovery_url_template: None,
+                endpoint_template: None,
+            })
+            .collect()
+    }
+
+    /// Returns the custom APIs as discovery directory items, one per version.
+    pub fn custom_directory_items(&self) -> Vec<discovery::DiscoveryDirectoryItem> {
+        self.custom_apis
+            .iter()
+            .flat_map(|(name, custom)| {
+                custom
+                    .versions
+                    .iter()
+                    .map(move |version| discovery::DiscoveryDirectoryItem {
+                        kind: "discovery#directoryItem".to_string(),
+                        id: format!("{}:{}", name, version),
+                        name: name.clone(),
+                        version: version.clone(),
+                        title: custom.title.clone().unwrap_or_else(|| name.clone()),
+                        description: String::new(),
+                        discovery_rest_url: custom.discovery_url.replace("{version}", version),
+                        documentation_link: None,
+                        preferred: false,
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the key (the name or an alias of the service) and the version pinned in `[versions]`, if any.
+    pub fn pinned_version(&self, api: &SupportedApi) -> Option<(&str, &str)> {
+        std::iter::once(&api.name)
+            .chain(&api.aliases)
+            .find_map(|key| self.versions.get_key_value(key))
+            .map(|(key, version)| (key.as_str(), version.as_str()))
+    }
+
+    /// Returns the universe domain given with `--universe-domain`, `ZG_UNIVERSE_DOMAIN`, or the config file.
+    /// None for the default universe (`googleapis.com`).
+    pub fn universe_domain(&self) -> Option<&str> {
+        self.universe_domain
+            .as_deref()
+            .map(|domain| domain.trim().trim_matches('.'))
+            .filter(|domain| !domain.is_empty() && *domain != DEFAULT_UNIVERSE_DOMAIN)
+    }
+
+    /// Returns the endpoint in `[endpoints]` of the first of the keys that has one (e.g., the service as given, its name,
+    /// and its aliases), if any.
+    pub fn endpoint<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Option<&str> {
+        keys.into_iter()
+            .find_map(|key| self.endpoints.get(key))
+            .map(String::as_str)
+    }
+
+    /// Returns the extra headers to download the discovery document of the custom API, if any.
+    pub fn custom_api_headers(&self, name: &str) -> Vec<(String, String)> {
+        self.custom_apis
+            .get(name)
+            .map(|custom| custom.headers.clone().into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Overrides the values with the environment variables in `ENV_OVERRIDES` (looked up by `env`).
+    /// Returns the overridden keys.
+    pub fn apply_env_overrides<F>(&mut self, env: F) -> Vec<&'static str>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut overridden = Vec::new();
+        for (key, var, field) in ENV_OVERRIDES {
+            if let Some(value) = env(var).filter(|value| !value.is_empty()) {
+                *field(self) = Some(value);
+                overridden.push(*key);
+            }
+        }
+        overridden
+    }
+
+    /// Checks that the custom APIs don't conflict with the built-in APIs.
+    fn validate(&self) -> Result<(), String> {
+        let builtin_apis = builtin_apis(true);
+        for (name, custom) in &self.custom_apis {
+            if custom.versions.is_empty() {
+                return Err(format!(
+                    "custom_apis.{}: at least one version is required",
+                    name
+                ));
+            }
+            for name_or_alias in std::iter::once(name).chain(custom.aliases.iter()) {
+                if let Some(api) = builtin_apis
+                    .iter()
+                    .find(|api| api.name == *name_or_alias || api.aliases.contains(name_or_alias))
+                {
+                    return Err(format!(
+                        "custom_apis.{}: '{}' conflicts with the built-in service '{}'",
+                        name, name_or_alias, api.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of the user configuration file.
+pub fn config_path() -> PathBuf {
+    core::config_dir().join(CONFIG_FILE)
+}
+
+/// Returns the project-local `.zg` directory of the current directory (see `find_local_dir`), if any.
+pub fn local_dir() -> Option<&'static Path> {
+    LOCAL
+        .get_or_init(|| {
+            let cwd = std::env::current_dir().ok()?;
+            let found = find_local_dir(&cwd, dirs::home_dir().as_deref());
+            debug!("Project-local directory: {:?}", found);
+            found
+        })
+        .as_deref()
+}
+
+/// Returns the path of the project-local configuration file, if the project-local directory has one.
+pub fn local_config_path() -> Option<PathBuf> {
+    local_dir()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.is_file())
+}
+
+/// Finds the `.zg` directory in `start` or the nearest of its ancestors, stopping below `home` (whose `.zg` is not
+/// project-local) or at the root. Directories that can't be read are skipped; symlinks are followed.
+fn find_local_dir(start: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if home.is_some_and(|home| dir == home) {
+            return None;
+        }
+        let candidate = dir.join(LOCAL_DIR);
+        if fs::metadata(&candidate).is_ok_and(|metadata| metadata.is_dir()) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Loads the user configuration file with the project-local one merged over it: keys in the local file replace the
+/// user's, and tables (e.g., `[endpoints]`) are merged key by key. Both files are optional.
+/// `TRUSTED_KEYS` of the local file are ignored with a warning unless it's trusted (see `local_table`).
+pub fn load_layered(user: &Path, local: Option<&Path>) -> Result<Config, ZgError> {
+    let Some(local) = local else {
+        return load(user);
+    };
+    let mut merged = read_table(user)?;
+    let (local_table, ignored) = local_table(user, local)?;
+    if !ignored.is_empty() {
+        warn!(
+            "Ignoring {} in {} as the file is not trusted; run `zg config trust` to apply them",
+            ignored.join(", "),
+            local.display()
+        );
+    }
+    merge_table(&mut merged, local_table);
+    let config: Config = toml::Value::Table(merged)
+        .try_into()
+        .map_err(|e: toml::de::Error| ZgError::InvalidConfig {
+            path: local.display().to_string(),
+            message: e.to_string().trim_end().to_string(),
+        })?;
+    config
+        .validate()
+        .map_err(|message| ZgError::InvalidConfig {
+            path: local.display().to_string(),
+            message,
+        })?;
+    Ok(config)
+}
+
+/// Reads the project-local configuration file, without the `TRUSTED_KEYS` unless the trust file next to the user's one
+/// records it with its current content. Returns the keys removed as well.
+fn local_table(user: &Path, local: &Path) -> Result<(toml::Table, Vec<&'static str>), ZgError> {
+    let mut table = read_table(local)?;
+    if is_trusted(&user.with_file_name(TRUST_FILE), local) {
+        return Ok((table, vec![]));
+    }
+    let ignored = TRUSTED_KEYS
+        .iter()
+        .copied()
+        .filter(|key| table.remove(*key).is_some())
+        .collect();
+    Ok((table, ignored))
+}
+
+/// Whether the file is recorded in the trust file with the digest of its current content.
+fn is_trusted(trust_file: &Path, path: &Path) -> bool {
+    let Ok(trusted) = read_trusted(trust_file) else {
+        return false;
+    };
+    match (trust_key(path), content_digest(path)) {
+        (Ok(key), Ok(digest)) => trusted.get(&key) == Some(&digest),
+        _ => false,
+    }
+}
+
+/// Records the file in the trust file with the digest of its current content.
+fn trust(trust_file: &Path, path: &Path) -> Result<(), ZgError> {
+    let mut trusted = read_trusted(trust_file)?;
+    trusted.insert(trust_key(path)?, content_digest(path)?);
+    let content = toml::to_string(&trusted).map_err(|e| ZgError::Other(e.to_string()))?;
+    core::write_file_atomic(trust_file, content.as_bytes())
+}
+
+fn read_trusted(trust_file: &Path) -> Result<BTreeMap<String, String>, ZgError> {
+    let content = match fs::read_to_string(trust_file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    toml::from_str(&content).map_err(|e: toml::de::Error| ZgError::InvalidConfig {
+        path: trust_file.display().to_string(),
+        message: e.to_string().trim_end().to_string(),
+    })
+}
+
+/// The canonical path of the file, so that it's trusted however it's reached (e.g., through a symlink).
+fn trust_key(path: &Path) -> Result<String, ZgError> {
+    Ok(fs::canonicalize(path)?.display().to_string())
+}
+
+/// The SHA-256 of the content of the file in hex.
+fn content_digest(path: &Path) -> Result<String, ZgError> {
+    let digest = ring::digest::digest(&ring::digest::SHA256, &fs::read(path)?);
+    Ok(digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Reads the configuration file as a table, checking that it's valid by itself. Empty if the file doesn't exist.
+fn read_table(path: &Path) -> Result<toml::Table, ZgError> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let content = fs::read_to_string(path)?;
+    let invalid = |message| ZgError::InvalidConfig {
+        path: path.display().to_string(),
+        message,
+    };
+    parse(&content).map_err(invalid)?;
+    toml::from_str(&content).map_err(|e: toml::de::Error| invalid(e.to_string()))
+}
+
+/// Merges `over` into `base`: tables are merged recursively, and other values are replaced.
+fn merge_table(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge_table(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns the file each dotted key of the layered configuration comes from.
+fn origins(user: &Path, local: Option<&Path>) -> Result<BTreeMap<String, String>, ZgError> {
+    let mut tables = vec![(user, read_table(user)?)];
+    if let Some(local) = local {
+        tables.push((local, local_table(user, local)?.0));
+    }
+    let mut origins = BTreeMap::new();
+    for (path, table) in tables {
+        let mut keys = Vec::new();
+        flatten_table("", &table, &mut keys);
+        for (key, _) in keys {
+            origins.insert(key, path.display().to_string());
+        }
+    }
+    Ok(origins)
+}
+
+/// Flattens the table into `dotted.key` and value pairs.
+fn flatten_table(prefix: &str, table: &toml::Table, lines: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten_table(&key, table, lines),
+            value => lines.push((key, value.to_string())),
+        }
+    }
+}
+
+/// Loads the configuration file. Returns the defaults if the file doesn't exist.
+pub fn load(path: &Path) -> Result<Config, ZgError> {
+    if !path.exists() {
+        debug!("No config file found at {:?}", path);
+        return Ok(Config::default());
+    }
+    let content = fs::read_to_string(path)?;
+    parse(&content).map_err(|message| ZgError::InvalidConfig {
+        path: path.display().to_string(),
+        message,
+    })
+}
+
+/// Parses the configuration. The error message points to the line and column of the problem.
+fn parse(content: &str) -> Result<Config, String> {
+    let config: Config =
+        toml::from_str(content).map_err(|e| e.to_string().trim_end().to_string())?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Sets the configuration available through `current()`. Only the first call takes effect.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// Returns the configuration loaded at startup.
+pub fn current() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Resolves a boolean option given as `--flag` or `--no-flag` (whichever comes last, as they override each other),
+/// falling back to the configured value when neither is given.
+pub fn resolve_flag(flag: bool, no_flag: bool, configured: Option<bool>) -> bool {
+    match (flag, no_flag) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => configured.unwrap_or(false),
+    }
+}
+
+// ---------------------- zg config ----------------------------- //
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCmd {
+    /// Print the value of KEY (e.g., 'project', 'list.long').
+    Get { key: String },
+
+    /// Set KEY to VALUE. VALUE is read as a TOML value (e.g., 'true', '10') if possible, otherwise as a string.
+    Set { key: String, value: String },
+
+    /// Remove KEY from the config file.
+    Unset { key: String },
+
+    /// Print the effective settings, including the ones overridden by environment variables (e.g., ZG_PROJECT) and by
+    /// the project-local `.zg/config.toml`.
+    List {
+        /// Show where each value comes from: the environment variable, the project-local or user config file, or the
+        /// default.
+        #[arg(long)]
+        origins: bool,
+    },
+
+    /// Trust the project-local `.zg/config.toml` of the current directory as it is now, applying its `endpoints`,
+    /// `universe_domain`, `credentials_file`, and `custom_apis`, which are ignored otherwise. Editing the file revokes
+    /// the trust.
+    Trust,
+}
+
+/// Main function to get or edit the configuration file.
+pub async fn main(args: &ConfigArgs) -> Result<(), ZgError> {
+    let path = config_path();
+    match &args.command {
+        ConfigCmd::Get { key } => {
+            let doc = read_document(&path)?;
+            match get_value(&doc, key) {
+                Some(value) => println!("{}", value),
+                None => return Err(format!("Key '{}' is not set in {:?}", key, path).into()),
+            }
+        }
+        ConfigCmd::Set { key, value } => {
+            let mut doc = read_document(&path)?;
+            let old = get_value(&doc, key);
+            set_value(&mut doc, key, value)?;
+            write_document(&path, &doc)?;
+            warn_unknown_key(&doc, key, &path);
+            println!(
+                "{}: {} -> {}",
+                key,
+                old.as_deref().unwrap_or("(unset)"),
+                get_value(&doc, key).unwrap_or_default()
+            );
+        }
+        ConfigCmd::Unset { key } => {
+            let mut doc = read_document(&path)?;
+            if unset_value(&mut doc, key) {
+                write_document(&path, &doc)?;
+            } else {
+                debug!("Key '{}' is not set; nothing to do", key);
+            }
+        }
+        ConfigCmd::List { origins: show } => {
+            let local = local_config_path();
+            let mut config = load_layered(&path, local.as_deref())?;
+            let overridden = config.apply_env_overrides(|key| std::env::var(key).ok());
+            let origins = match show {
+                true => Some(origins(&path, local.as_deref())?),
+                false => None,
+            };
+            for line in list_lines(&config, &overridden, origins.as_ref()) {
+                println!("{}", line);
+            }
+            for file in std::iter::once(path).chain(local) {
+                for key in unknown_keys(&read_document(&file)?) {
+                    warn!("Unknown key '{}' in {:?} is ignored", key, file);
+                }
+            }
+        }
+        ConfigCmd::Trust => {
+            let local = local_config_path()
+                .ok_or("No project-local .zg/config.toml found from the current directory")?;
+            trust(&path.with_file_name(TRUST_FILE), &local)?;
+            println!("Trusted {}", local.display());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the effective settings as `dotted.key = value` lines, noting the ones overridden by environment variables.
+/// With `origins` (the files of the keys), every line notes where its value comes from, `default` if from no file.
+fn list_lines(
+    config: &Config,
+    overridden: &[&str],
+    origins: Option<&BTreeMap<String, String>>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Ok(toml::Value::Table(table)) = toml::Value::try_from(config) {
+        flatten_table("", &table, &mut lines);
+    }
+    lines
+        .into_iter()
+        .map(|(key, value)| {
+            let env = ENV_OVERRIDES
+                .iter()
+                .find(|(k, _, _)| *k == key && overridden.contains(k));
+            match (env, origins) {
+                (Some((_, var, _)), _) => format!("{} = {} # from {}", key, value, var),
+                (None, Some(origins)) => format!(
+                    "{} = {} # from {}",
+                    key,
+                    value,
+                    origins.get(&key).map(String::as_str).unwrap_or("default")
+                ),
+                (None, None) => format!("{} = {}", key, value),
+            }
+        })
+        .collect()
+}
+
+/// Returns the dotted keys in the document that zg doesn't know (e.g., typos), which are ignored when loading.
+fn unknown_keys(doc: &DocumentMut) -> Vec<String> {
+    fn walk(prefix: &str, given: &toml::Table, known: &toml::Table, unknown: &mut Vec<String>) {
+        for (key, value) in given {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            match (value, known.get(key)) {
+                (_, None) => unknown.push(path),
+                (toml::Value::Table(given), Some(toml::Value::Table(known))) => {
+                    walk(&path, given, known, unknown)
+                }
+                _ => {}
+            }
+        }
+    }
+    let content = doc.to_string();
+    let (Ok(given), Ok(config)) = (toml::from_str::<toml::Table>(&content), parse(&content)) else {
+        return vec![];
+    };
+    let Ok(toml::Value::Table(known)) = toml::Value::try_from(&config) else {
+        return vec![];
+    };
+    let mut unknown = Vec::new();
+    walk("", &given, &known, &mut unknown);
+    unknown
+}
+
+/// Warns if the key is unknown to zg; it's saved anyway (e.g., for a newer version of zg).
+fn warn_unknown_key(doc: &DocumentMut, key: &str, path: &Path) {
+    if unknown_keys(doc)
+        .iter()
+        .any(|unknown| key == unknown || key.starts_with(&format!("{}.", unknown)))
+    {
+        warn!(
+            "Unknown key '{}' is saved in {:?}, but zg doesn't use it",
+            key, path
+        );
+    }
+}
+
+/// Reads the configuration file as an editable document that keeps comments and formatting.
+fn read_document(path: &Path) -> Result<DocumentMut, ZgError> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    let content = fs::read_to_string(path)?;
+    content
+        .parse::<DocumentMut>()
+        .map_err(|e| ZgError::InvalidConfig {
+            path: path.display().to_string(),
+            message: e.to_string().trim_end().to_string(),
+        })
+}
+
+/// Validates the edited document and writes it to the file atomically.
+fn write_document(path: &Path, doc: &DocumentMut) -> Result<(), ZgError> {
+    let content = doc.to_string();
+    parse(&content).map_err(|message| format!("Invalid value: {}", message))?;
+    core::write_file_atomic(path, content.as_bytes())
+}
+
+/// Returns the value of the dotted key; strings are returned without quotes.
+fn get_value(doc: &DocumentMut, key: &str) -> Option<String> {
+    let mut item = doc.as_item();
+    for segment in key.split('.') {
+        item = item.get(segment)?;
+    }
+    match item {
+        Item::Value(toml_edit::Value::String(s)) => Some(s.value().to_string()),
+        Item::Value(value) => Some(value.to_string().trim().to_string()),
+        Item::Table(table) => Some(table.to_string().trim_end().to_string()),
+        _ => None,
+    }
+}
+
+/// Sets the value of the dotted key, creating intermediate tables as needed.
+/// The value is read as a TOML value (true, 10, ["a", "b"]) if the key accepts it, otherwise as a plain string
+/// (e.g., `zg config set project 12345` stores "12345").
+fn set_value(doc: &mut DocumentMut, key: &str, value: &str) -> Result<(), ZgError> {
+    if let Ok(typed) = value.parse::<toml_edit::Value>() {
+        let mut edited = doc.clone();
+        insert_value(&mut edited, key, typed)?;
+        if parse(&edited.to_string()).is_ok() || parse(&doc.to_string()).is_err() {
+            *doc = edited;
+            return Ok(());
+        }
+    }
+    insert_value(doc, key, toml_edit::Value::from(value))
+}
+
+fn insert_value(doc: &mut DocumentMut, key: &str, value: toml_edit::Value) -> Result<(), ZgError> {
+    let (parents, last) = split_key(key)?;
+    let mut table: &mut Table = doc.as_table_mut();
+    for segment in parents {
+        table = table
+            .entry(segment)
+            .or_insert_with(|| {
+                // Implicit, so that "a.b.c" doesn't leave an empty [a] header
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| format!("'{}' in '{}' is not a table", segment, key))?;
+    }
+
+    match table.get_mut(last) {
+        // Replace only the value to keep the comments around the key
+        Some(Item::Value(existing)) => {
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        _ => {
+            table.insert(last, Item::Value(value.decorated(" ", "")));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the dotted key. Returns false if the key doesn't exist.
+fn unset_value(doc: &mut DocumentMut, key: &str) -> bool {
+    let Ok((parents, last)) = split_key(key) else {
+        return false;
+    };
+    let mut table: &mut Table = doc.as_table_mut();
+    for segment in parents {
+        match table.get_mut(segment).and_then(Item::as_table_mut) {
+            Some(t) => table = t,
+            None => return false,
+        }
+    }
+    table.remove(last).is_some()
+}
+
+/// Splits the dotted key into the parent tables and the last key.
+fn split_key(key: &str) -> Result<(Vec<&str>, &str), ZgError> {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid key '{}'", key).into());
+    }
+    let last = segments.pop().expect("split returns at least one segment");
+    Ok((segments, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse(
+            r#"
+            project = "my-project"
+            [list]
+            long = true
+            sort = "name"
+            [method_aliases]
+            ls = ["list", "aggregatedList"]
+            [endpoints]
+            ps = "http://localhost:8085"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.project.as_deref(), Some("my-project"));
+        assert_eq!(config.list.long, Some(true));
+        assert_eq!(config.list.sort.as_deref(), Some("name"));
+        assert_eq!(config.list.reverse, None);
+        assert_eq!(config.method_aliases["ls"], vec!["list", "aggregatedList"]);
+        assert_eq!(
+            config.endpoint(["pubsub", "ps"]),
+            Some("http://localhost:8085")
+        );
+        assert_eq!(config.endpoint(["spanner"]), None);
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_config_error_with_position() {
+        let e = parse("project = \"p\"\ncolor = yes\n").unwrap_err();
+        assert!(e.contains("line 2, column 9"), "{}", e);
+
+        let e = parse("[list]\nlong = \"yes\"\n").unwrap_err();
+        assert!(e.contains("line 2"), "{}", e);
+    }
+
+    #[test]
+    fn test_custom_apis() {
+        let config = parse(
+            r#"
+            [custom_apis.orders]
+            discovery_url = "https://orders.example.com/$discovery/rest?version={version}"
+            versions = ["v2", "v1"]
+            aliases = ["ord"]
+            headers = { Authorization = "Bearer xxx" }
+            "#,
+        )
+        .unwrap();
+
+        let apis = config.custom_supported_apis();
+        assert_eq!(apis.len(), 1);
+        assert_eq!(apis[0].name, "orders");
+        assert_eq!(apis[0].category, Category::Custom);
+        assert_eq!(apis[0].default_version(), "v2");
+
+        let items = config.custom_directory_items();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].id, "orders:v1");
+        assert_eq!(
+            items[1].discovery_rest_url,
+            "https://orders.example.com/$discovery/rest?version=v1"
+        );
+        assert_eq!(
+            config.custom_api_headers("orders"),
+            vec![("Authorization".to_string(), "Bearer xxx".to_string())]
+        );
+        assert!(config.custom_api_headers("container").is_empty());
+    }
+
+    #[test]
+    fn test_custom_apis_conflict() {
+        let e =
+            parse("[custom_apis.container]\ndiscovery_url = \"file:///tmp/x.json\"\n").unwrap_err();
+        assert!(
+            e.contains("conflicts with the built-in service 'container'"),
+            "{}",
+            e
+        );
+
+        let e = parse(
+            "[custom_apis.mine]\ndiscovery_url = \"file:///tmp/x.json\"\naliases = [\"gke\"]\n",
+        )
+        .unwrap_err();
+        assert!(e.contains("'gke' conflicts"), "{}", e);
+
+        let e =
+            parse("[custom_apis.mine]\ndiscovery_url = \"file:///tmp/x.json\"\nversions = []\n")
+                .unwrap_err();
+        assert!(e.contains("at least one version"), "{}", e);
+    }
+
+    #[test]
+    fn test_edit_round_trip() {
+        let mut doc = "# my settings\nproject = \"p1\"\n"
+            .parse::<DocumentMut>()
+            .unwrap();
+
+        set_value(&mut doc, "project", "p2").unwrap();
+        set_value(&mut doc, "list.long", "true").unwrap();
+        set_value(&mut doc, "list.sort", "name").unwrap();
+        assert_eq!(get_value(&doc, "project").as_deref(), Some("p2"));
+        assert_eq!(get_value(&doc, "list.long").as_deref(), Some("true"));
+        assert_eq!(get_value(&doc, "list.sort").as_deref(), Some("name"));
+
+        let config = parse(&doc.to_string()).unwrap();
+        assert_eq!(config.project.as_deref(), Some("p2"));
+        assert_eq!(config.list.long, Some(true));
+        assert!(doc.to_string().starts_with("# my settings\n"));
+
+        assert!(unset_value(&mut doc, "list.long"));
+        assert!(!unset_value(&mut doc, "list.long"));
+        assert!(!unset_value(&mut doc, "no.such.key"));
+        assert_eq!(get_value(&doc, "list.long"), None);
+
+        assert!(set_value(&mut doc, "project.nested", "x").is_err()); // project is not a table
+        assert!(set_value(&mut doc, "list..long", "x").is_err());
+    }
+
+    #[test]
+    fn test_write_document_rejects_invalid_value() {
+        let path = std::env::temp_dir().join(format!("zg-test-config-{}.toml", std::process::id()));
+        let mut doc = DocumentMut::new();
+        set_value(&mut doc, "list.long", "yes").unwrap(); // stored as a string
+        assert!(write_document(&path, &doc).is_err());
+        assert!(!path.exists());
+
+        set_value(&mut doc, "list.long", "true").unwrap();
+        write_document(&path, &doc).unwrap();
+        assert_eq!(load(&path).unwrap().list.long, Some(true));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_set_value_coerces_to_the_expected_type() {
+        let mut doc = DocumentMut::new();
+        set_value(&mut doc, "project", "12345").unwrap();
+        set_value(&mut doc, "list.long", "true").unwrap();
+        set_value(&mut doc, "api_max_age_days", "7").unwrap();
+        let config = parse(&doc.to_string()).unwrap();
+        assert_eq!(config.project.as_deref(), Some("12345"));
+        assert_eq!(config.list.long, Some(true));
+        assert_eq!(config.api_max_age_days, Some(7));
+    }
+
+    #[test]
+    fn test_config_dir_with_nested_unknown_keys() {
+        let dir = std::env::temp_dir().join(format!(
+            "zg-test-config-unknown-keys-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+
+        let mut doc = read_document(&path).unwrap();
+        set_value(&mut doc, "profiles.work.project", "work-project").unwrap();
+        set_value(&mut doc, "list.sort", "name").unwrap();
+        set_value(&mut doc, "list.colour", "true").unwrap();
+        write_document(&path, &doc).unwrap(); // unknown keys are kept
+
+        let doc = read_document(&path).unwrap();
+        assert_eq!(
+            get_value(&doc, "profiles.work.project").as_deref(),
+            Some("work-project")
+        );
+        assert_eq!(unknown_keys(&doc), vec!["list.colour", "profiles"]);
+        assert_eq!(load(&path).unwrap().list.sort.as_deref(), Some("name"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_params() {
+        let config = parse(
+            r#"
+            [defaults.params]
+            pageSize = 500
+            maxResults = "100"
+            [defaults.compute.params]
+            maxResults = "500"
+            returnPartialSuccess = true
+            "#,
+        )
+        .unwrap();
+        let method = |query_params: &[&str]| core::ZgMethod {
+            query_params: query_params
+                .iter()
+                .map(|name| core::ZgQueryParam {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..core::ZgMethod::testdata()
+        };
+        let params =
+            |service: &str, method: &core::ZgMethod| config.defaults.params(service, method);
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        // The service's value takes precedence over the global one
+        let list = method(&["maxResults", "returnPartialSuccess", "filter"]);
+        assert_eq!(
+            params("compute", &list),
+            pairs(&[("maxResults", "500"), ("returnPartialSuccess", "true")])
+        );
+        assert_eq!(params("storage", &list), pairs(&[("maxResults", "100")]));
+        // Only the parameters the method has
+        assert_eq!(
+            params("container", &method(&["pageSize"])),
+            pairs(&[("pageSize", "500")])
+        );
+        assert!(params("compute", &method(&["view"])).is_empty());
+        assert!(Config::default()
+            .defaults
+            .params("compute", &list)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_list_lines_with_env_overrides() {
+        let mut config = parse("project = \"p1\"\nregion = \"r1\"\n[list]\nlong = true\n").unwrap();
+        let overridden = config.apply_env_overrides(|key| match key {
+            "ZG_REGION" => Some("us-east1".to_string()),
+            "ZG_ZONE" => Some(String::new()), // empty values are ignored
+            _ => None,
+        });
+        assert_eq!(overridden, vec!["region"]);
+        assert_eq!(config.zone, None);
+        assert_eq!(
+            list_lines(&config, &overridden, None),
+            vec![
+                "list.long = true",
+                "project = \"p1\"",
+                "region = \"us-east1\" # from ZG_REGION",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_local_dir() {
+        let root = std::env::temp_dir().join(format!("zg-test-local-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let home = root.join("home");
+        let repo = home.join("repo");
+        let nested = repo.join("services").join("api");
+        fs::create_dir_all(nested.join("src")).unwrap();
+        fs::create_dir_all(home.join(LOCAL_DIR)).unwrap();
+
+        // The user's own ~/.zg is not project-local
+        assert_eq!(find_local_dir(&nested, Some(&home)), None);
+        assert_eq!(find_local_dir(&nested, None), Some(home.join(LOCAL_DIR)));
+
+        // The nearest one wins
+        fs::create_dir_all(repo.join(LOCAL_DIR)).unwrap();
+        fs::create_dir_all(nested.join(LOCAL_DIR)).unwrap();
+        assert_eq!(
+            find_local_dir(&nested.join("src"), Some(&home)),
+            Some(nested.join(LOCAL_DIR))
+        );
+        assert_eq!(
+            find_local_dir(&repo.join("services"), Some(&home)),
+            Some(repo.join(LOCAL_DIR))
+        );
+
+        // A .zg file or a dangling symlink is skipped without failing
+        fs::remove_dir_all(nested.join(LOCAL_DIR)).unwrap();
+        fs::write(nested.join(LOCAL_DIR), "").unwrap();
+        let src = nested.join("src");
+        std::os::unix::fs::symlink(root.join("missing"), src.join(LOCAL_DIR)).unwrap();
+        assert_eq!(
+            find_local_dir(&src, Some(&home)),
+            Some(repo.join(LOCAL_DIR))
+        );
+
+        // A symlinked directory is followed
+        fs::remove_file(src.join(LOCAL_DIR)).unwrap();
+        std::os::unix::fs::symlink(repo.join(LOCAL_DIR), src.join(LOCAL_DIR)).unwrap();
+        assert_eq!(find_local_dir(&src, Some(&home)), Some(src.join(LOCAL_DIR)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_load_layered() {
+        let root = std::env::temp_dir().join(format!("zg-test-layered-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let user = root.join("user.toml");
+        let local = root.join("local.toml");
+        fs::write(
+            &user,
+            "project = \"user-project\"\nregion = \"us-central1\"\n[list]\nlong = true\n",
+        )
+        .unwrap();
+        fs::write(
+            &local,
+            "project = \"repo-project\"\n[list]\nsort = \"name\"\n",
+        )
+        .unwrap();
+
+        let config = load_layered(&user, Some(&local)).unwrap();
+        assert_eq!(config.project.as_deref(), Some("repo-project"));
+        assert_eq!(config.region.as_deref(), Some("us-central1"));
+        assert_eq!(config.list.sort.as_deref(), Some("name"));
+        assert_eq!(config.list.long, Some(true));
+
+        // Without the user file
+        let config = load_layered(&root.join("none.toml"), Some(&local)).unwrap();
+        assert_eq!(config.project.as_deref(), Some("repo-project"));
+        assert_eq!(config.region, None);
+
+        let origins = origins(&user, Some(&local)).unwrap();
+        let user_origin = user.display().to_string();
+        let local_origin = local.display().to_string();
+        assert_eq!(origins.get("project"), Some(&local_origin));
+        assert_eq!(origins.get("region"), Some(&user_origin));
+        assert_eq!(origins.get("list.long"), Some(&user_origin));
+        assert_eq!(origins.get("list.sort"), Some(&local_origin));
+
+        let mut config = load_layered(&user, Some(&local)).unwrap();
+        let overridden = config.apply_env_overrides(|key| match key {
+            "ZG_REGION" => Some("asia-northeast1".to_string()),
+            _ => None,
+        });
+        let lines = list_lines(&config, &overridden, Some(&origins));
+        assert!(lines.contains(&format!(
+            "project = \"repo-project\" # from {}",
+            local_origin
+        )));
+        assert!(lines.contains(&"region = \"asia-northeast1\" # from ZG_REGION".to_string()));
+        assert!(lines.iter().all(|line| line.contains(" # from ")));
+
+        // Keys sending requests elsewhere apply only once the local file is trusted as it is
+        let redirecting =
+            "universe_domain = \"evil.example\"\n[endpoints]\npubsub = \"https://evil.example\"\n";
+        fs::write(
+            &local,
+            format!("project = \"repo-project\"\n{}", redirecting),
+        )
+        .unwrap();
+        let config = load_layered(&user, Some(&local)).unwrap();
+        assert_eq!(config.project.as_deref(), Some("repo-project"));
+        assert_eq!((config.universe_domain, config.endpoints.len()), (None, 0));
+        let keys = super::origins(&user, Some(&local)).unwrap();
+        assert_eq!(keys.get("universe_domain"), None);
+        trust(&root.join(TRUST_FILE), &local).unwrap();
+        let config = load_layered(&user, Some(&local)).unwrap();
+        assert_eq!(config.universe_domain.as_deref(), Some("evil.example"));
+        assert_eq!(
+            config.endpoints.get("pubsub").map(String::as_str),
+            Some("https://evil.example")
+        );
+        // Editing the file revokes the trust
+        fs::write(&local, format!("project = \"other\"\n{}", redirecting)).unwrap();
+        let config = load_layered(&user, Some(&local)).unwrap();
+        assert_eq!((config.universe_domain, config.endpoints.len()), (None, 0));
+
+        // An invalid local file is reported with its path
+        fs::write(&local, "project = 1\n").unwrap();
+        let err = load_layered(&user, Some(&local)).unwrap_err().to_string();
+        assert!(err.contains(&local_origin), "{}", err);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -15,15 +15,22 @@
 use log::{debug, warn};
 use rmp_serde::decode::Deserializer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{create_dir_all, File};
+use std::future::Future;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
 
+use super::config;
 use super::discovery;
+use super::error::ZgError;
 use super::flavors::core_flavors as flavors;
+use super::suggest;
 use super::supported_apis::{standalone_apis, supported_apis};
+use super::timing;
 use super::update;
 
 /// Variants of project-related placeholder names appearing in flat_path.
@@ -36,6 +43,26 @@ pub static PATH_PLACEHOLDERS_REGION: &[&str] = &["regionsId", "region", "locatio
 /// Variants of zone related placeholder names appearing in flat_path.
 pub static PATH_PLACEHOLDERS_ZONE: &[&str] = &["zonesId", "zone"];
 
+/// Whether the placeholder is autofilled by `zg exec` with the project, region, or zone.
+pub fn is_autofill_placeholder(name: &str) -> bool {
+    PATH_PLACEHOLDERS_PROJECT
+        .iter()
+        .chain(PATH_PLACEHOLDERS_REGION)
+        .chain(PATH_PLACEHOLDERS_ZONE)
+        .any(|&placeholder| placeholder == name)
+}
+
+/// Built-in aliases of method names, used only when the given method name doesn't exist on the resource.
+/// Each alias resolves to the first of its targets that exists on the resource (e.g., "mk" -> "insert" for Compute Engine).
+pub static METHOD_ALIASES: &[(&str, &[&str])] = &[
+    ("ls", &["list"]),
+    ("rm", &["delete"]),
+    ("del", &["delete"]),
+    ("mk", &["create", "insert"]),
+    ("add", &["create", "insert"]),
+    ("desc", &["get"]),
+];
+
 // ---------------------- core structs ----------------------------- //
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ZgApi {
@@ -46,9 +73,84 @@ pub struct ZgApi {
     pub base_url: String,
     pub resources: Vec<ZgResource>,
     pub schemas: HashMap<String, discovery::Schema>,
+
+    // API_FORMAT_VERSION at the time the file was generated. Files cached by older zg lack it (0) and are regenerated.
+    #[serde(default)]
+    pub format_version: u32,
+
+    // API-level parameters accepted by every method (e.g., fields, quotaUser, prettyPrint), sorted by name.
+    #[serde(default)]
+    pub common_params: Vec<ZgQueryParam>,
+
+    // documentationLink of the discovery document (e.g., "https://cloud.google.com/kubernetes-engine/docs/"), if any.
+    #[serde(default)]
+    pub documentation_link: Option<String>,
+
+    // OAuth scopes of the API (auth.oauth2.scopes of the discovery document) with their descriptions.
+    #[serde(default)]
+    pub scopes: BTreeMap<String, String>,
+
+    // rootUrl and servicePath of the discovery document, which the flat paths of the methods are relative to.
+    // None for API files cached before these fields existed, which use base_url instead.
+    #[serde(default)]
+    pub root_url: Option<String>,
+    #[serde(default)]
+    pub service_path: Option<String>,
+
+    // Built on the first resource lookup (see `resource_index`); not stored in the file.
+    #[serde(skip)]
+    pub(crate) resource_index: OnceLock<ResourceIndex>,
+}
+
+/// Joins the URL and the path with exactly one slash between them, whether or not the URL ends with a slash and the
+/// path starts with one.
+pub fn join_path(url: &str, path: &str) -> String {
+    match path.trim_start_matches('/') {
+        "" => url.to_string(),
+        path => format!("{}/{}", url.trim_end_matches('/'), path),
+    }
 }
 
+/// Version of the cached API file format, bumped when fields are added that require regenerating cached files
+/// (e.g., 1: enum metadata of query params, 2: `required` of schemas, 3: map types of schema properties, 4: deprecated flags,
+/// 5: API-level common params, 6: types and enums of array items and properties, 7: request bodies of DELETE methods,
+/// 8: documentation links, 9: OAuth scopes, 10: rootUrl and servicePath, 11: parameterOrder,
+/// 12: response schemas). Older files are decoded with the defaults, then prepared again.
+pub const API_FORMAT_VERSION: u32 = 12;
+
 impl ZgApi {
+    /// Returns the base URL to join the path of a method (e.g., "v1/projects/p/clusters") to: rootUrl + servicePath of
+    /// the discovery document, or base_url for API files without them.
+    pub fn base_url_for(&self, path: &str) -> String {
+        match &self.root_url {
+            Some(root_url) => self.base_url_on(root_url, path),
+            None => self.base_url.clone(),
+        }
+    }
+
+    /// Returns the base URL on the root URL (e.g., a regional endpoint) for the path: the root URL + servicePath, or the
+    /// root URL alone when the path already starts with the servicePath (e.g., "v1/" and "v1/projects/p"), not to repeat it.
+    pub fn base_url_on(&self, root_url: &str, path: &str) -> String {
+        let service_path = self
+            .service_path
+            .as_deref()
+            .unwrap_or_default()
+            .trim_matches('/');
+        let path = path.trim_start_matches('/');
+        let overlaps = path == service_path || path.starts_with(&format!("{}/", service_path));
+        match service_path.is_empty() || overlaps {
+            true => format!("{}/", root_url.trim_end_matches('/')),
+            false => format!("{}/", join_path(root_url, service_path)),
+        }
+    }
+
+    /// Returns the index of the resource paths, built on the first call.
+    /// The resources must not be changed afterwards; APIs are read-only once loaded.
+    pub fn resource_index(&self) -> &ResourceIndex {
+        self.resource_index
+            .get_or_init(|| ResourceIndex::new(&self.resources))
+    }
+
     /// Returns a list of all resource paths in the API.
     ///
     /// Sample output:
@@ -60,22 +162,7 @@ impl ZgApi {
     ///      ...
     /// ]
     pub fn all_resource_paths(&self) -> Vec<(String, String)> {
-        fn collect_paths(resource: &ZgResource, paths: &mut Vec<(String, String)>) {
-            if let Some(ref path) = resource.path {
-                paths.push((resource.name.clone(), path.clone()));
-            }
-            if let Some(ref sub_resources) = resource.resources {
-                for sub_resource in sub_resources {
-                    collect_paths(sub_resource, paths);
-                }
-            }
-        }
-
-        let mut resource_paths = Vec::new();
-        for resource in &self.resources {
-            collect_paths(resource, &mut resource_paths);
-        }
-        resource_paths
+        resource_paths(&self.resources)
     }
 
     /// Returns a list of resources with duplicated paths.
@@ -106,6 +193,40 @@ impl ZgApi {
             })
             .collect()
     }
+
+    /// Returns the duplicated resource names sorted by name, each with its paths (sorted),
+    /// the shortest paths to select each one, and the path zg picks by default for the bare name, if any.
+    pub fn resource_ambiguities(&self) -> Vec<ResourceAmbiguity> {
+        let mut duplicated = self.duplicated_resources();
+        duplicated.sort();
+        duplicated
+            .into_iter()
+            .map(|(name, mut paths)| {
+                paths.sort();
+                let found: Vec<&ZgResource> = paths
+                    .iter()
+                    .filter_map(|path| find_resource_by_exact_path(&self.resources, path))
+                    .collect();
+                let default =
+                    flavored_resource(&self.id, &name, found).and_then(|r| r.path.clone());
+                ResourceAmbiguity {
+                    unique_paths: unique_paths(&paths),
+                    name,
+                    paths,
+                    default,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A resource name shared by multiple resources in an API (see `ZgApi::resource_ambiguities`).
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ResourceAmbiguity {
+    pub name: String,
+    pub paths: Vec<String>,
+    pub unique_paths: Vec<String>,
+    pub default: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -128,13 +249,62 @@ pub struct ZgMethod {
     pub flat_path: String,
     pub http_method: String,
     pub query_params: Vec<ZgQueryParam>,
-    // Retrieve the referenced ($ref) object to convert. GET/DELETE: None, other methods: Some(ZgRequestObj).
+    // Retrieve the referenced ($ref) object to convert. None for methods without `request` (e.g., most GET/DELETE methods).
     // Schema's "Output only (readOnly: true)" properties are filtered out in `update::convert_method()`.
     pub request_data_schema: Option<discovery::Schema>,
+    #[serde(default)]
+    pub deprecated: bool,
+    // Copied from Method.parameterOrder: the natural order of the required parameters (e.g., project, zone, instance),
+    // used to assign the positional values of `zg exec`. Cached files without it are prepared again (format 11).
+    #[serde(default)]
+    pub parameter_order: Vec<String>,
+    // Name of the response schema (`$ref` of Method.response) in ZgApi.schemas, used by `zg desc --response-example`.
+    // None for methods without a response body. Files cached before this field existed are prepared again (format 12).
+    #[serde(default)]
+    pub response_schema: Option<String>,
+    // OAuth scopes of the method (Method.scopes), any of which authorizes it. Described in ZgApi.scopes.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl ZgMethod {
+    /// Whether requests of the method have a body: POST/PUT/PATCH methods, and others with a request schema (e.g., a few DELETE methods).
+    pub fn has_request_body(&self) -> bool {
+        self.request_data_schema.is_some()
+            || !["GET", "DELETE"].contains(&self.http_method.as_str())
+    }
+
+    /// The placeholders in the flat_path (e.g., `projectsId` and `clustersId`), in the order they appear.
+    pub fn path_placeholders(&self) -> Vec<&str> {
+        self.flat_path
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}'))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The placeholders that take the positional values of `zg exec` (e.g., `zg exec compute instances get my-instance`):
+    /// those not autofilled, in the order of parameter_order. Placeholders not in it keep their order in the path,
+    /// e.g., `clustersId` of a flat path whose parameterOrder is just `name`.
+    pub fn positional_params(&self) -> Vec<&str> {
+        let mut placeholders: Vec<&str> = self
+            .path_placeholders()
+            .into_iter()
+            .filter(|placeholder| !is_autofill_placeholder(placeholder))
+            .collect();
+        placeholders.sort_by_key(|placeholder| {
+            self.parameter_order
+                .iter()
+                .position(|name| name == placeholder)
+                .unwrap_or(usize::MAX)
+        });
+        placeholders
+    }
 }
 
 /// Query parameters for a method. Path parameters are not included here as they are part of the flat_path.
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
 pub struct ZgQueryParam {
     pub name: String,
     pub description: Option<String>,
@@ -143,15 +313,35 @@ pub struct ZgQueryParam {
     // Despite that, the required flag isn't perfect - you might not realize that a parameter is required until you execute the method actually.
     // Example of undocumented required parameter: “query” in https://cloud.google.com/bigquery/docs/reference/reservations/rest/v1/projects.locations/searchAllAssignments
     pub required: bool,
+
+    // Accepts multiple values (`-p key=a -p key=b`). Defaults to false for API files cached before this field existed.
+    #[serde(default)]
+    pub repeated: bool,
+
+    // Copied from Parameter as is (e.g., param_type: "string", enum_values: ["BASIC", "FULL"], format: "int32").
+    #[serde(default)]
+    pub param_type: Option<String>,
+    #[serde(default)]
+    pub enum_values: Option<Vec<String>>,
+    #[serde(default)]
+    pub enum_descriptions: Option<Vec<String>>,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 // ---------------------- Common functions --------------------------- //
-/// Returns a directory path to store config and cached data ($HOME/.config/zg).
+/// Returns a directory path to store config and cached data.
+/// $XDG_CONFIG_HOME/zg if set, %APPDATA%\zg on Windows, $HOME/.config/zg otherwise (see `resolve_config_dir`).
 pub fn config_dir() -> PathBuf {
-    let config_dir = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join(".config")
-        .join("zg");
+    let config_dir = resolve_config_dir(
+        |key| std::env::var(key).ok(),
+        dirs::home_dir(),
+        cfg!(windows),
+    );
 
     // Create the config dir and its subdirs if they don't exist
     create_dir_all(&config_dir).expect("Failed to create configuration directory");
@@ -163,7 +353,88 @@ pub fn config_dir() -> PathBuf {
     config_dir
 }
 
-/// Returns a directory path to store ZgApi in msgpack ($HOME/.config/zg/api).
+/// Resolves the config directory from the environment variables (looked up by `env`) and the home directory.
+///
+/// An existing legacy directory ($HOME/.config/zg) keeps being used when the new location doesn't exist yet,
+/// so that users who already ran `zg update` don't start over with an empty cache.
+fn resolve_config_dir<F>(env: F, home: Option<PathBuf>, windows: bool) -> PathBuf
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let legacy_dir = home.map(|home| home.join(".config").join("zg"));
+    let non_empty = |key: &str| env(key).filter(|value| !value.is_empty());
+
+    let preferred_dir = match non_empty("XDG_CONFIG_HOME") {
+        Some(xdg_config_home) => Some(PathBuf::from(xdg_config_home).join("zg")),
+        None if windows => non_empty("APPDATA").map(|appdata| PathBuf::from(appdata).join("zg")),
+        None => None,
+    };
+
+    match (preferred_dir, legacy_dir) {
+        (Some(preferred), Some(legacy)) if !preferred.exists() && legacy.exists() => {
+            debug!(
+                "Using the existing config directory {:?} instead of {:?}",
+                legacy, preferred
+            );
+            legacy
+        }
+        (Some(preferred), _) => preferred,
+        (None, Some(legacy)) => legacy,
+        (None, None) => panic!("Failed to get home directory"),
+    }
+}
+
+/// Writes the content to a temporary file next to the path and renames it to the path,
+/// so that readers never see a partially written file.
+pub fn write_file_atomic(path: &Path, content: &[u8]) -> Result<(), ZgError> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path: {:?}", path))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp_path);
+    })?;
+    Ok(())
+}
+
+/// Writes the file atomically like `write_file_atomic`, but readable only by the user (0600), creating the missing
+/// directories readable only by the user as well (0700). For credentials and cached responses.
+pub(crate) fn write_private_file_atomic(path: &Path, content: &[u8]) -> Result<(), ZgError> {
+    if let Some(parent) = path.parent() {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(parent)?;
+    }
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid file path: {:?}", path))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, content));
+    result
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp_path);
+        })?;
+    Ok(())
+}
+
+/// Returns a directory path to store ZgApi in msgpack (<config_dir>/api).
 pub fn api_dir() -> PathBuf {
     config_dir().join("api")
 }
@@ -172,63 +443,291 @@ pub fn api_dir() -> PathBuf {
 pub async fn load_api_file(
     api_string: &str,
     standalone_key: Option<String>,
-) -> Result<ZgApi, Box<dyn Error>> {
-    let (cname, version) =
-        lookup_api(api_string).ok_or_else(|| format!("Service '{}' not found", api_string))?;
+) -> Result<ZgApi, ZgError> {
+    let _span = timing::span("load_api_file");
+    let (cname, version) = resolve_api(api_string).await?;
+    let auth = discovery::DiscoveryAuth::current();
 
-    let path = api_dir().join(format!("{}_{}.msgpack", &cname, &version));
+    let path = api_dir().join(api_file_name(&format!("{}:{}", &cname, &version)));
     debug!("API {}:{} is supported. Open {:?}", &cname, &version, &path);
+    if let Some(api) = kept_api(&path) {
+        debug!("Using {:?} kept in memory", &path);
+        return Ok(api);
+    }
 
     // Attempt to open the file; if it doesn't exist, perform lazy preparation
     let file = match File::open(&path) {
+        Ok(file) if is_stale(&file, api_max_age()) => {
+            let config = config::current();
+            if config.auto_refresh.unwrap_or(false) {
+                match lazy_prep_api_file(&cname, &version, &path, standalone_key.clone(), &auth)
+                    .await
+                {
+                    Ok(refreshed) => refreshed,
+                    Err(e) => {
+                        warn!(
+                            "Failed to refresh {}:{} ({}); using the cached one.",
+                            &cname, &version, e
+                        );
+                        file
+                    }
+                }
+            } else {
+                warn!(
+                    "API definition of {}:{} is older than {} days. Run `zg update` or pass --auto-refresh to refresh it.",
+                    &cname,
+                    &version,
+                    api_max_age().as_secs() / 86400
+                );
+                file
+            }
+        }
         Ok(file) => file,
         Err(_) => {
             debug!(
                 "File not found. Initiating lazy preparation for {}:{}",
                 &cname, &version
             );
-            lazy_prep_api_file(&cname, &version, &path, standalone_key).await?
+            lazy_prep_api_file(&cname, &version, &path, standalone_key.clone(), &auth).await?
+        }
+    };
+
+    // A broken file (e.g., truncated by an interrupted write) is treated as missing and prepared again
+    let api = decode_or_prepare(file, &path, || {
+        lazy_prep_api_file(&cname, &version, &path, standalone_key, &auth)
+    })
+    .await?;
+    keep_api(&path, &api);
+    Ok(api)
+}
+
+/// Resolves the service name and version of the API: the supported APIs first, then the whole discovery directory
+/// with --any-api.
+async fn resolve_api(api_string: &str) -> Result<(String, String), ZgError> {
+    let not_supported = || ZgError::ApiNotSupported {
+        service: api_string.to_string(),
+        candidates: suggest_services(api_string),
+    };
+    match lookup_api(api_string, config::current())? {
+        Some(found) => Ok(found),
+        None if config::current().any_api.unwrap_or(false) => {
+            let directory =
+                discovery::ensure_discovered_apis(false, &discovery::DiscoveryAuth::current())
+                    .await?;
+            let found =
+                lookup_discovered_api(api_string, &directory.items).ok_or_else(not_supported)?;
+            warn!(
+                "{}:{} is not supported by zg yet; resources may be ambiguous as service-specific flavors are missing.",
+                found.0, found.1
+            );
+            Ok(found)
         }
+        None => Err(not_supported()),
+    }
+}
+
+/// Returns the path to the saved discovery document of the API, downloading it if it's not saved
+/// (e.g., removed by hand, or the API file was built elsewhere).
+pub(crate) async fn discovery_document_path(
+    api_string: &str,
+    standalone_key: Option<String>,
+) -> Result<PathBuf, ZgError> {
+    let (cname, version) = resolve_api(api_string).await?;
+    let path = discovery::discovered_file_path(&format!("{}:{}", cname, version));
+    if path.exists() {
+        return Ok(path);
+    }
+    debug!(
+        "{} not found; downloading the discovery document",
+        path.display()
+    );
+    let auth = discovery::DiscoveryAuth::current();
+    download_discovery_document(&cname, &version, standalone_key, &auth)
+        .await?
+        .ok_or_else(|| {
+            ZgError::Other(format!(
+                "Failed to download the discovery document of {}:{}",
+                cname, version
+            ))
+        })
+}
+
+/// Whether to keep the loaded API definitions in memory (see `keep_loaded_apis`).
+static KEEP_LOADED_APIS: AtomicBool = AtomicBool::new(false);
+
+/// API definitions loaded in this process, with the modification time of their files.
+static LOADED_APIS: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, ZgApi)>>> =
+    LazyLock::new(Default::default);
+
+/// Keeps the API definitions in memory once loaded, so that running many commands in a process (`zg shell`)
+/// decodes each file only once. They're loaded again when the files change (e.g., by `update`).
+pub fn keep_loaded_apis() {
+    KEEP_LOADED_APIS.store(true, Ordering::Relaxed);
+}
+
+fn keep_api(path: &Path, api: &ZgApi) {
+    if !KEEP_LOADED_APIS.load(Ordering::Relaxed) {
+        return;
+    }
+    if let (Ok(modified), Ok(mut loaded)) = (
+        std::fs::metadata(path).and_then(|m| m.modified()),
+        LOADED_APIS.lock(),
+    ) {
+        loaded.insert(path.to_path_buf(), (modified, api.clone()));
+    }
+}
+
+/// Returns the API definition of the service kept in memory (see `keep_loaded_apis`), without loading it, e.g., to
+/// complete resource and method names in `zg shell`.
+pub fn kept_api_of(api_string: &str) -> Option<ZgApi> {
+    let (cname, version) = lookup_api(api_string, config::current()).ok()??;
+    kept_api(&api_dir().join(api_file_name(&format!("{}:{}", cname, version))))
+}
+
+fn kept_api(path: &Path) -> Option<ZgApi> {
+    if !KEEP_LOADED_APIS.load(Ordering::Relaxed) {
+        return None;
+    }
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let loaded = LOADED_APIS.lock().ok()?;
+    let (loaded_modified, api) = loaded.get(path)?;
+    (*loaded_modified == modified).then(|| api.clone())
+}
+
+/// Decodes the API file. If it fails, calls `prepare` to prepare the file again and decodes it once more.
+/// Files in an older format (see `API_FORMAT_VERSION`) are prepared again as well, but kept if that fails (e.g., offline).
+async fn decode_or_prepare<F, Fut>(file: File, path: &Path, prepare: F) -> Result<ZgApi, ZgError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<File, ZgError>>,
+{
+    let decode = |file: &File| -> Result<ZgApi, ZgError> {
+        let _span = timing::span("decode_api");
+        Deserialize::deserialize(&mut Deserializer::new(BufReader::new(file)))
+            .map_err(|e| ZgError::Deserialize(format!("'{:?}': {}", path, e)))
     };
 
-    let reader = BufReader::new(&file);
-    Deserialize::deserialize(&mut Deserializer::new(reader))
-        .map_err(|e| format!("Error: Failed to deserialize '{:?}': {}", &file, e).into())
+    match decode(&file) {
+        Ok(api) if api.format_version < API_FORMAT_VERSION => {
+            debug!(
+                "{:?} has format version {} (current: {}); preparing it again",
+                path, api.format_version, API_FORMAT_VERSION
+            );
+            match prepare().await.and_then(|file| decode(&file)) {
+                Ok(regenerated) => Ok(regenerated),
+                Err(e) => {
+                    warn!(
+                        "Failed to regenerate the API file cached by an older zg ({}); using it as is.",
+                        e
+                    );
+                    Ok(api)
+                }
+            }
+        }
+        Ok(api) => Ok(api),
+        Err(e) => {
+            warn!(
+                "Failed to read the cached API file ({}); preparing it again.",
+                e
+            );
+            decode(&prepare().await?)
+        }
+    }
+}
+
+/// Default age of API files considered as outdated, overridable by `api_max_age_days` in the config file.
+const DEFAULT_API_MAX_AGE_DAYS: u64 = 30;
+
+/// Returns the age of API files considered as outdated.
+fn api_max_age() -> Duration {
+    let days = config::current()
+        .api_max_age_days
+        .unwrap_or(DEFAULT_API_MAX_AGE_DAYS);
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
+/// Checks if the API file is older than the max age, based on its modification time.
+fn is_stale(file: &File, max_age: Duration) -> bool {
+    match file.metadata().and_then(|m| m.modified()) {
+        Ok(modified) => is_older_than(modified, SystemTime::now(), max_age),
+        Err(_) => false,
+    }
+}
+
+/// Returns true if `modified` is older than `max_age` at `now`. Modification times in the future are not old.
+fn is_older_than(modified: SystemTime, now: SystemTime, max_age: Duration) -> bool {
+    now.duration_since(modified)
+        .map(|age| age > max_age)
+        .unwrap_or(false)
 }
 
 /// Called when api:version is supported but the API .msgpack file is not found. Possibly `zg update` is not executed.
 /// Prepare the API file "lazy" way - downloading the API description and processing it.
+/// The requests to download it are authorized as `auth` tells.
 async fn lazy_prep_api_file(
     api_name: &str,
     version: &str,
     path: &PathBuf,
     standalone_key: Option<String>,
-) -> Result<File, Box<dyn Error>> {
+    auth: &discovery::DiscoveryAuth,
+) -> Result<File, ZgError> {
+    let apidef_path_option =
+        download_discovery_document(api_name, version, standalone_key, auth).await?;
+
+    let apidef_path = apidef_path_option.expect("API definition path should exist");
+    debug!("Downloaded API definition: {:?}", apidef_path);
+
+    // Extract the API description to build ZgApi from the downloaded JSON file
+    let keep_all_schemas = config::current().keep_all_schemas.unwrap_or(false);
+    let zg_api = update::extract_api(apidef_path, keep_all_schemas)?;
+
+    // Store the extracted API description to a file (in msgpack format)
+    update::store_zgapi_msgpack(zg_api, path)?;
+
+    // Simply try to open the file again (mimicking the previous behavior)
+    File::open(path).map_err(|e| format!("(Lazy) Failed to open file '{:?}': {}", path, e).into())
+}
+
+/// Downloads the discovery document of the API into the `discovered` directory: the standalone and custom APIs
+/// from their own URLs, and others from the URL in the discovery directory. Returns None if it failed to download.
+pub(crate) async fn download_discovery_document(
+    api_name: &str,
+    version: &str,
+    standalone_key: Option<String>,
+    auth: &discovery::DiscoveryAuth,
+) -> Result<Option<PathBuf>, ZgError> {
     // Check if a standalone API is requested
-    let standalone_api = standalone_apis()
+    let standalone_url = standalone_apis()
         .into_iter()
-        .find(|api| api.name == api_name && api.versions.iter().any(|v| v == version));
+        .find(|api| api.name == api_name && api.versions.iter().any(|v| v == version))
+        .and_then(|api| api.discovery_url(version, standalone_key.as_deref()));
 
-    let apidef_path_option: Option<PathBuf> = match standalone_api {
-        Some(standalone_api) => {
+    Ok(match standalone_url {
+        Some(standalone_url) => {
             // Download the standalone API definition
             let standalone_api_id = format!("{}:{}", api_name, version);
-            let key = standalone_key.ok_or_else(|| {
-                format!(
-                    "--api-key is required for standalone API '{}'",
-                    standalone_api_id
-                )
-            })?;
-            debug!(
-                "API key '{}' is provided for standalone API '{}'",
-                key, standalone_api_id
-            );
-            let standalone_url = discovery::standalone_discovery_url(standalone_api.clone(), key);
-            discovery::download_api_definition(standalone_api_id, standalone_url).await?
+            debug!("Download standalone API '{}'", standalone_api_id);
+            discovery::download_api_definition(standalone_api_id, standalone_url?, auth).await?
+        }
+        None if config::current().custom_apis.contains_key(api_name) => {
+            // Download the custom API definition from the URL in the config file
+            let config = config::current();
+            let custom_item = config
+                .custom_directory_items()
+                .into_iter()
+                .find(|item| item.name == api_name && item.version == version)
+                .ok_or_else(|| format!("{}:{} not found in the custom APIs", api_name, version))?;
+            discovery::download_api_definition_with_headers(
+                custom_item.id,
+                custom_item.discovery_rest_url,
+                &config.custom_api_headers(api_name),
+                auth,
+            )
+            .await?
         }
         None => {
             // Find the matching item from discovered APIs or raise an error if not found
-            let discovered_item = discovery::ensure_discovered_apis(false)
+            let discovered_item = discovery::ensure_discovered_apis(false, auth)
                 .await?
                 .items
                 .into_iter()
@@ -240,102 +739,349 @@ async fn lazy_prep_api_file(
             discovery::download_api_definition(
                 discovered_item.id,
                 discovered_item.discovery_rest_url,
+                auth,
             )
             .await?
         }
-    };
-
-    let apidef_path = apidef_path_option.expect("API definition path should exist");
-    debug!("Downloaded API definition: {:?}", apidef_path);
-
-    // Extract the API description to build ZgApi from the downloaded JSON file
-    let zg_api = update::extract_api(apidef_path)?;
-
-    // Store the extracted API description to a file (in msgpack format)
-    update::store_zgapi_msgpack(zg_api, path)?;
-
-    // Simply try to open the file again (mimicking the previous behavior)
-    File::open(path).map_err(|e| format!("(Lazy) Failed to open file '{:?}': {}", path, e).into())
+    })
 }
 
 /// Finds the canonical service id and version for a given service or its alias.
 ///
 /// For example, to find "container:v1", you have multiple ways:
 /// - "container:v1" (explicit version)
-/// - "container" (assumes the default version)
-/// - "gke" (alias with the default version)
+/// - "container" (assumes the version pinned in the config file, or the default version)
+/// - "gke" (alias with the pinned or default version)
 /// - "gke:v1" (alias with version)
-fn lookup_api(api_string: &str) -> Option<(String, String)> {
+///
+/// A pinned version that the service doesn't have is an error listing the available ones.
+fn lookup_api(
+    api_string: &str,
+    config: &config::Config,
+) -> Result<Option<(String, String)>, ZgError> {
     // Split the api_string into the frist part (name or alias) and the optional second part (version)
     let mut parts = api_string.splitn(2, ':');
-    let name_or_alias = parts.next()?;
+    let Some(name_or_alias) = parts.next() else {
+        return Ok(None);
+    };
     let explicit_version = parts.next();
 
     // Find the matching API by name or alias
-    let api = supported_apis(true).into_iter().find(|api| {
-        api.name == name_or_alias || api.aliases.contains(&name_or_alias.to_string())
-    })?;
+    let Some(api) = supported_apis(true)
+        .into_iter()
+        .find(|api| api.name == name_or_alias || api.aliases.contains(&name_or_alias.to_string()))
+    else {
+        return Ok(None);
+    };
 
     // Determine the version
-    let version = match explicit_version {
-        Some(ver) if api.versions.contains(&ver.to_string()) => ver,
-        Some(_) => return None,        // Invalid version is given
-        None => api.default_version(), // Use the default version
+    let version = match (explicit_version, config.pinned_version(&api)) {
+        (Some(ver), _) if api.versions.contains(&ver.to_string()) => ver,
+        (Some(_), _) => return Ok(None), // Invalid version is given
+        (None, Some((_, pinned))) if api.versions.contains(&pinned.to_string()) => pinned,
+        (None, Some((key, pinned))) => {
+            return Err(ZgError::InvalidConfig {
+                path: config::config_path().display().to_string(),
+                message: format!(
+                    "versions.{} = \"{}\" is not a version of {}; available versions: {}",
+                    key,
+                    pinned,
+                    api.name,
+                    api.versions.join(", ")
+                ),
+            })
+        }
+        (None, None) => api.default_version(), // Use the default version
     };
 
     // Return the canonical API name and resolved version
-    Some((api.name.to_string(), version.to_string()))
+    Ok(Some((api.name.to_string(), version.to_string())))
+}
+
+/// Finds the service name and version in the discovery directory, used for APIs not in the supported list (--any-api).
+/// Without an explicit version, prefers the version marked as "preferred", then the first one listed.
+fn lookup_discovered_api(
+    api_string: &str,
+    items: &[discovery::DiscoveryDirectoryItem],
+) -> Option<(String, String)> {
+    let mut parts = api_string.splitn(2, ':');
+    let name = parts.next()?;
+    let explicit_version = parts.next();
+
+    let mut candidates = items.iter().filter(|item| item.name == name);
+    let item = match explicit_version {
+        Some(ver) => candidates.find(|item| item.version == ver)?,
+        None => {
+            let candidates: Vec<_> = candidates.collect();
+            candidates
+                .iter()
+                .find(|item| item.preferred)
+                .or_else(|| candidates.first())
+                .copied()?
+        }
+    };
+    Some((item.name.clone(), item.version.clone()))
+}
+
+/// Returns (name, path) pairs of the given resources and all their nested resources.
+fn resource_paths(resources: &[ZgResource]) -> Vec<(String, String)> {
+    fn collect_paths(resource: &ZgResource, paths: &mut Vec<(String, String)>) {
+        if let Some(ref path) = resource.path {
+            paths.push((resource.name.clone(), path.clone()));
+        }
+        if let Some(ref sub_resources) = resource.resources {
+            for sub_resource in sub_resources {
+                collect_paths(sub_resource, paths);
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    for resource in resources {
+        collect_paths(resource, &mut paths);
+    }
+    paths
+}
+
+/// Suggests supported service names or aliases similar to the user-typed one (version is ignored).
+fn suggest_services(api_string: &str) -> Vec<String> {
+    let name_or_alias = api_string.split(':').next().unwrap_or(api_string);
+    let apis = supported_apis(true);
+    let names = apis.iter().flat_map(|api| {
+        std::iter::once(api.name.as_str()).chain(api.aliases.iter().map(String::as_str))
+    });
+    suggest::suggest(name_or_alias, names)
+}
+
+/// Index of the resource paths of an API, so that `find_resource` doesn't walk the whole tree on every lookup.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceIndex {
+    /// Paths of the resources in the tree order, each with its position (indexes of the resources from the top level).
+    entries: Vec<(String, Vec<usize>)>,
+    /// Indexes of `entries` by the lowercased last segment of the path.
+    by_last_segment: HashMap<String, Vec<usize>>,
+}
+
+impl ResourceIndex {
+    pub fn new(resources: &[ZgResource]) -> Self {
+        fn recursive(
+            resources: &[ZgResource],
+            position: &mut Vec<usize>,
+            index: &mut ResourceIndex,
+        ) {
+            for (i, resource) in resources.iter().enumerate() {
+                position.push(i);
+                if let Some(path) = &resource.path {
+                    let last_segment = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+                    index
+                        .by_last_segment
+                        .entry(last_segment)
+                        .or_default()
+                        .push(index.entries.len());
+                    index.entries.push((path.clone(), position.clone()));
+                }
+                if let Some(sub_resources) = &resource.resources {
+                    recursive(sub_resources, position, index);
+                }
+                position.pop();
+            }
+        }
+
+        let mut index = ResourceIndex::default();
+        recursive(resources, &mut Vec::new(), &mut index);
+        index
+    }
+
+    /// Returns the resources whose paths match the user-typed path (see `path_matches`) in the tree order.
+    /// `resources` must be the ones the index is built from.
+    fn matches<'a>(
+        &self,
+        resources: &'a [ZgResource],
+        resource_path: &str,
+        ignore_case: bool,
+    ) -> Vec<&'a ZgResource> {
+        let last_segment = resource_path
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let Some(candidates) = self.by_last_segment.get(&last_segment) else {
+            return Vec::new();
+        };
+        candidates
+            .iter()
+            .map(|&i| &self.entries[i])
+            .filter(|(path, _)| {
+                if ignore_case {
+                    path_matches(&path.to_lowercase(), &resource_path.to_lowercase())
+                } else {
+                    path_matches(path, resource_path)
+                }
+            })
+            .map(|(_, position)| resource_at(resources, position))
+            .collect()
+    }
+}
+
+/// Returns the resource at the position in the tree (see `ResourceIndex`).
+fn resource_at<'a>(resources: &'a [ZgResource], position: &[usize]) -> &'a ZgResource {
+    let (first, rest) = position.split_first().expect("positions are not empty");
+    rest.iter().fold(&resources[*first], |resource, &i| {
+        &resource
+            .resources
+            .as_deref()
+            .expect("positions point to sub-resources")[i]
+    })
+}
+
+/// Find the target resource in the given API, using the index of the API built on the first lookup.
+/// With `strict`, multiple matches are reported as an error instead of being resolved by flavors.
+pub fn find_api_resource<'a>(
+    api: &'a ZgApi,
+    resource_path: &str,
+    strict: bool,
+) -> Result<&'a ZgResource, ZgError> {
+    find_indexed_resource(
+        &api.id,
+        &api.resources,
+        api.resource_index(),
+        resource_path,
+        strict,
+    )
 }
 
-/// Find the target resource in the given API
+/// Find the target resource in the given resources, building an index for the lookup (see `find_api_resource`).
+/// With `strict`, multiple matches are reported as an error instead of being resolved by flavors.
 pub fn find_resource<'a>(
     api_id: &str,
     resources: &'a [ZgResource],
     resource_path: &str,
-) -> Result<&'a ZgResource, Box<dyn Error>> {
-    let mut found = Vec::<&'a ZgResource>::new();
+    strict: bool,
+) -> Result<&'a ZgResource, ZgError> {
+    let index = ResourceIndex::new(resources);
+    find_indexed_resource(api_id, resources, &index, resource_path, strict)
+}
 
-    fn recursive<'a>(
-        resource_path: &str,
-        resources: &'a [ZgResource],
-        found: &mut Vec<&'a ZgResource>,
-    ) {
-        for resource in resources {
-            if let Some(path) = &resource.path {
-                if path.ends_with(resource_path) {
-                    found.push(resource);
-                }
-            }
+fn find_indexed_resource<'a>(
+    api_id: &str,
+    resources: &'a [ZgResource],
+    index: &ResourceIndex,
+    resource_path: &str,
+    strict: bool,
+) -> Result<&'a ZgResource, ZgError> {
+    let normalized = normalize_resource_path(resource_path);
+    let resource_path = normalized.as_str();
 
-            if let Some(sub_resources) = &resource.resources {
-                recursive(resource_path, sub_resources, found);
-            }
+    // Prefer the exact-case match, then fall back to case-insensitive match (e.g., "nodepools" for "nodePools")
+    let mut found = index.matches(resources, resource_path, false);
+    if found.is_empty() {
+        found = index.matches(resources, resource_path, true);
+        let paths: Vec<String> = found.iter().filter_map(|r| r.path.clone()).collect();
+        debug!(
+            "Matched '{}' case-insensitively: {:?}",
+            resource_path, paths
+        );
+
+        // Distinct resources whose paths differ only by case must not be merged silently
+        let differ_only_by_case = paths.iter().enumerate().any(|(i, a)| {
+            paths[i + 1..]
+                .iter()
+                .any(|b| a != b && a.to_lowercase() == b.to_lowercase())
+        });
+        if differ_only_by_case {
+            return Err(ZgError::AmbiguousResource {
+                api_id: api_id.to_string(),
+                resource: resource_path.to_string(),
+                candidates: paths,
+            });
         }
     }
 
-    recursive(resource_path, resources, &mut found);
-
     // Early return with an error if no matching resource is found
     if found.is_empty() {
-        return Err(format!(
-            "Resource '{}' not found for API '{}'.",
-            resource_path, api_id
-        )
-        .into()); // Convert the error message to Box<dyn Error>
+        return Err(ZgError::ResourceNotFound {
+            api_id: api_id.to_string(),
+            resource: resource_path.to_string(),
+            candidates: suggest_resource_paths(resources, resource_path),
+        });
+    }
+
+    if strict && found.len() > 1 {
+        return Err(ZgError::AmbiguousResource {
+            api_id: api_id.to_string(),
+            resource: resource_path.to_string(),
+            candidates: found.iter().filter_map(|r| r.path.clone()).collect(),
+        });
     }
 
     select_resource(api_id, resource_path, found)
         .ok_or_else(|| format!("Failed to select resource '{}'", resource_path).into())
 }
 
+/// Normalizes the user-typed resource path: slash-separated paths as in the docs and gcloud
+/// (e.g., "projects/locations/clusters", with or without a trailing slash) are converted into dot-separated ones.
+pub fn normalize_resource_path(resource_path: &str) -> String {
+    let trimmed = resource_path.strip_suffix('/').unwrap_or(resource_path);
+    trimmed.replace('/', ".")
+}
+
+/// Suggests resource paths similar to the user-typed one, comparing it with the trailing segments
+/// of every resource path (e.g., "locations.cluster" against "locations.clusters").
+fn suggest_resource_paths(resources: &[ZgResource], resource_path: &str) -> Vec<String> {
+    let resource_path = resource_path.strip_prefix('.').unwrap_or(resource_path);
+    let segment_count = resource_path.split('.').count();
+    let trailing_paths: Vec<String> = resource_paths(resources)
+        .into_iter()
+        .map(|(_, path)| {
+            let segments: Vec<&str> = path.split('.').collect();
+            segments[segments.len().saturating_sub(segment_count)..].join(".")
+        })
+        .collect();
+    suggest::suggest(resource_path, trailing_paths.iter().map(String::as_str))
+}
+
+/// Checks if the user-typed resource path matches the resource path by whole dot-separated segments.
+/// For example, "locations.clusters" matches "container.projects.locations.clusters",
+/// but "sessions" doesn't match "dataproc.projects.locations.workloadSessions".
+/// A leading "." (e.g., ".clusters") is accepted to explicitly anchor the match at a resource boundary.
+fn path_matches(path: &str, resource_path: &str) -> bool {
+    let resource_path = resource_path.strip_prefix('.').unwrap_or(resource_path);
+    if resource_path.is_empty() {
+        return false;
+    }
+    let path_segments: Vec<&str> = path.split('.').collect();
+    let input_segments: Vec<&str> = resource_path.split('.').collect();
+    path_segments.ends_with(&input_segments)
+}
+
+/// Returns the file name of the cached API definition (e.g., "admin_directory_v1.msgpack" for "admin:directory_v1").
+pub fn api_file_name(api_id: &str) -> String {
+    format!("{}.msgpack", api_id.replace(':', "_"))
+}
+
+/// Returns the shortest resource path that points only to the given candidate among all candidates.
+/// For example, with "container.projects.locations.clusters" and "container.projects.zones.clusters",
+/// returns "locations.clusters" for the former.
+pub fn shortest_unique_path(path: &str, candidates: &[String]) -> String {
+    let segments: Vec<&str> = path.split('.').collect();
+    for len in 1..segments.len() {
+        let suffix = segments[segments.len() - len..].join(".");
+        let matches = candidates
+            .iter()
+            .filter(|c| path_matches(c, &suffix))
+            .count();
+        if matches <= 1 {
+            return suffix;
+        }
+    }
+    path.to_string()
+}
+
 /// Selects a resource from a list of found resources based on the API ID and resource path.
 ///
 /// If no resources are found, returns None.
 /// If multiple resources are found, resolves ambiguity with service-specific heuristic (flavors).
 /// If no service-specific logic is defined, just returns one item without thinking.
-///
-/// List of services with duplicate resource names, but no specific flavor is defined:
-/// - "iam:v1" ... keys x 3, locations x 2, operations x 10, providers x 2, roles x 3
 fn select_resource<'a>(
     api_id: &str,
     resource_path: &str, // user-typed resource path
@@ -355,35 +1101,178 @@ fn select_resource<'a>(
             .collect::<Vec<&String>>()
     );
 
+    if let Some(chosen) = flavored_resource(api_id, resource_path, found.clone()) {
+        return Some(chosen);
+    }
+
+    // Return the last resource as the default choice, with warning
+    let candidates: Vec<String> = found.iter().filter_map(|r| r.path.clone()).collect();
+    let chosen = found.last().copied();
+    warn!(
+        "Found multiple resources for '{}': [{}]. Returning the last one '{}'. Specify more detailed path (one of [{}]) or use --strict to fail instead. See also `zg ls {} --duplicates`.",
+        resource_path,
+        candidates.join(", "),
+        chosen.and_then(|r| r.path.as_deref()).unwrap_or_default(),
+        unique_paths(&candidates).join(", "),
+        api_id.split(':').next().unwrap_or(api_id),
+    );
+    chosen
+}
+
+/// Resolves the ambiguity with the service-specific heuristic (flavors); returns None if no flavor is defined.
+fn flavored_resource<'a>(
+    api_id: &str,
+    resource_path: &str,
+    found: Vec<&'a ZgResource>,
+) -> Option<&'a ZgResource> {
     match api_id {
+        "compute:v1" | "compute:beta" => flavors::select_resource_compute(resource_path, found),
         "container:v1" => flavors::select_resource_container(found),
         "dataflow:v1b3" => flavors::select_resource_dataflow(resource_path, found),
+        "iam:v1" => flavors::select_resource_iam(resource_path, found),
+        "logging:v2" => flavors::select_resource_logging(resource_path, found),
         "spanner:v1" => flavors::select_resource_spanner(found),
-        _ => {
-            // Return the last resource as the default choice, with warning
-            warn!("Found multiple resources, so returning the last one (--debug for details). Specify more detailed path like 'locations.clusters' instead of 'clsuters' to resolve ambiguity.");
-            found.last().copied()
-        }
+        _ => None,
     }
 }
 
-/// Find the target method in the resource
-pub fn find_method(resource: &ZgResource, method_name: &str) -> Result<ZgMethod, Box<dyn Error>> {
-    let method = resource
-        .methods
+/// Returns the shortest unique path of each candidate (see `shortest_unique_path`).
+fn unique_paths(candidates: &[String]) -> Vec<String> {
+    candidates
         .iter()
-        .find(|m| m.name == method_name)
-        .cloned()
-        .ok_or_else(|| -> Box<dyn Error> {
-            format!(
-                "Method '{}' not found in the resource '{}'",
-                method_name,
-                resource.path.clone().expect("path should exist")
-            )
-            .into() // Convert the error message to Box<dyn Error>
-        })?;
+        .map(|c| shortest_unique_path(c, candidates))
+        .collect()
+}
+
+/// Finds the resource and the method by a (fully qualified) method id like "container.projects.locations.clusters.get".
+///
+/// A full id is resolved by the exact resource path, bypassing the heuristics of `select_resource`.
+/// Partial ids (e.g., "clusters.get") fall back to the usual resource path + method name lookup.
+pub fn find_method_by_id<'a>(
+    api: &'a ZgApi,
+    method_id: &str,
+    strict: bool,
+) -> Result<(&'a ZgResource, ZgMethod), ZgError> {
+    let (resource_path, method_name) = method_id.rsplit_once('.').ok_or_else(|| {
+        format!(
+            "'{}' is not a method id (e.g., 'projects.locations.clusters.get'). Specify both RESOURCE and METHOD.",
+            method_id
+        )
+    })?;
+
+    // Resource paths start with the API name (e.g., "container"); ids of other services are rejected.
+    let first_segment = method_id.split('.').next().unwrap_or_default();
+    let api_prefixes: HashSet<String> = resource_paths(&api.resources)
+        .into_iter()
+        .filter_map(|(_, path)| path.split('.').next().map(str::to_string))
+        .collect();
+    if !api_prefixes.contains(first_segment)
+        && supported_apis(true).iter().any(|a| a.name == first_segment)
+    {
+        return Err(ZgError::ServiceMismatch {
+            method_id: method_id.to_string(),
+            service: first_segment.to_string(),
+            api_id: api.id.clone(),
+        });
+    }
+
+    if let Some(resource) = find_resource_by_exact_path(&api.resources, resource_path) {
+        if let Ok(method) = find_method(resource, method_name) {
+            debug!(
+                "Resolved method id '{}' by the exact resource path",
+                method_id
+            );
+            return Ok((resource, method));
+        }
+    }
+
+    let resource = find_api_resource(api, resource_path, strict)?;
+    let method = find_method(resource, method_name)?;
+    Ok((resource, method))
+}
+
+/// Finds the resource whose path is exactly the given one.
+fn find_resource_by_exact_path<'a>(
+    resources: &'a [ZgResource],
+    path: &str,
+) -> Option<&'a ZgResource> {
+    resources.iter().find_map(|resource| {
+        if resource.path.as_deref() == Some(path) {
+            return Some(resource);
+        }
+        resource
+            .resources
+            .as_deref()
+            .and_then(|sub_resources| find_resource_by_exact_path(sub_resources, path))
+    })
+}
 
-    Ok(method)
+/// Find the target method in the resource
+pub fn find_method(resource: &ZgResource, method_name: &str) -> Result<ZgMethod, ZgError> {
+    find_method_ref(resource, method_name).cloned()
+}
+
+/// Find the target method in the resource, returning a reference to it.
+/// Prefers the exact-case match, then falls back to case-insensitive match (e.g., "setiampolicy" for "setIamPolicy"),
+/// and finally to the method aliases (e.g., "ls" for "list").
+pub fn find_method_ref<'a>(
+    resource: &'a ZgResource,
+    method_name: &str,
+) -> Result<&'a ZgMethod, ZgError> {
+    let resource_path = || resource.path.clone().expect("path should exist");
+
+    if let Some(method) = resource.methods.iter().find(|m| m.name == method_name) {
+        return Ok(method);
+    }
+
+    let found: Vec<&ZgMethod> = resource
+        .methods
+        .iter()
+        .filter(|m| m.name.eq_ignore_ascii_case(method_name))
+        .collect();
+    match found.as_slice() {
+        [method] => {
+            debug!(
+                "Matched method '{}' case-insensitively to '{}'",
+                method_name, method.name
+            );
+            Ok(method)
+        }
+        [] => match resolve_method_alias(resource, method_name) {
+            Some(method) => {
+                debug!(
+                    "Resolved method alias '{}' to '{}'",
+                    method_name, method.name
+                );
+                Ok(method)
+            }
+            None => Err(ZgError::MethodNotFound {
+                method: method_name.to_string(),
+                resource: resource_path(),
+                candidates: suggest::suggest(
+                    method_name,
+                    resource.methods.iter().map(|m| m.name.as_str()),
+                ),
+            }),
+        },
+        _ => Err(ZgError::AmbiguousMethod {
+            method: method_name.to_string(),
+            resource: resource_path(),
+            candidates: found.iter().map(|m| m.name.clone()).collect(),
+        }),
+    }
+}
+
+/// Resolves the method alias to the first existing method on the resource among the alias targets.
+/// Aliases in the config file (`[method_aliases]`) take precedence over the built-in ones.
+fn resolve_method_alias<'a>(resource: &'a ZgResource, alias: &str) -> Option<&'a ZgMethod> {
+    let targets: Vec<&str> = match config::current().method_aliases.get(alias) {
+        Some(targets) => targets.iter().map(String::as_str).collect(),
+        None => METHOD_ALIASES.iter().find(|(a, _)| *a == alias)?.1.to_vec(),
+    };
+    targets
+        .iter()
+        .find_map(|target| resource.methods.iter().find(|m| m.name == *target))
 }
 
 // ---------------------- macros ----------------------------- //
@@ -407,6 +1296,13 @@ impl ZgApi {
             base_url: "https://example.com/".to_string(),
             resources: vec![ZgResource::testdata()],
             schemas: HashMap::new(),
+            format_version: API_FORMAT_VERSION,
+            common_params: vec![],
+            documentation_link: None,
+            scopes: BTreeMap::new(),
+            root_url: None,
+            service_path: None,
+            resource_index: OnceLock::new(),
         }
     }
 }
@@ -435,6 +1331,10 @@ impl ZgMethod {
             http_method: "GET".to_string(),
             query_params: vec![],
             request_data_schema: None,
+            deprecated: false,
+            parameter_order: vec![],
+            response_schema: None,
+            scopes: vec![],
         }
     }
 }
@@ -444,6 +1344,89 @@ impl ZgMethod {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_positional_params() {
+        // Compute Engine style: the path has the parameters of parameterOrder
+        let method = ZgMethod {
+            flat_path: "projects/{project}/zones/{zone}/disks/{disk}/setLabels/{resource}"
+                .to_string(),
+            parameter_order: ["project", "zone", "resource", "disk"]
+                .map(String::from)
+                .to_vec(),
+            ..ZgMethod::testdata()
+        };
+        assert_eq!(
+            method.path_placeholders(),
+            vec!["project", "zone", "disk", "resource"]
+        );
+        assert_eq!(method.positional_params(), vec!["resource", "disk"]);
+
+        // Flat paths: parameterOrder has `name` instead of the placeholders
+        let method = ZgMethod {
+            flat_path: "v1/projects/{projectsId}/locations/{locationsId}/clusters/{clustersId}/nodePools/{nodePoolsId}"
+                .to_string(),
+            parameter_order: vec!["name".to_string()],
+            ..ZgMethod::testdata()
+        };
+        assert_eq!(
+            method.positional_params(),
+            vec!["clustersId", "nodePoolsId"]
+        );
+
+        // API files cached before parameter_order existed
+        let method = ZgMethod {
+            flat_path: "b/{bucket}/o/{object}".to_string(),
+            ..ZgMethod::testdata()
+        };
+        assert_eq!(method.positional_params(), vec!["bucket", "object"]);
+    }
+
+    #[test]
+    fn test_query_param_without_repeated_field() {
+        // API files cached before `repeated` was added lack the field (msgpack encodes structs as arrays)
+        #[derive(Serialize)]
+        struct OldZgQueryParam {
+            name: String,
+            description: Option<String>,
+            required: bool,
+        }
+        let old = OldZgQueryParam {
+            name: "filter".to_string(),
+            description: None,
+            required: true,
+        };
+        let mut encoded = Vec::new();
+        old.serialize(&mut rmp_serde::Serializer::new(&mut encoded))
+            .unwrap();
+
+        let decoded: ZgQueryParam = rmp_serde::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.name, "filter");
+        assert!(decoded.required);
+        assert!(!decoded.repeated);
+        assert_eq!(decoded.enum_values, None);
+        assert_eq!(decoded.param_type, None);
+    }
+
+    #[test]
+    fn test_query_param_round_trip() {
+        let query_param = ZgQueryParam {
+            name: "view".to_string(),
+            description: Some("Specifies the view.".to_string()),
+            param_type: Some("string".to_string()),
+            enum_values: Some(vecs!["BASIC", "FULL"]),
+            enum_descriptions: Some(vecs!["Basic fields only.", "All fields."]),
+            default: Some("BASIC".to_string()),
+            ..Default::default()
+        };
+        let mut encoded = Vec::new();
+        query_param
+            .serialize(&mut rmp_serde::Serializer::new(&mut encoded))
+            .unwrap();
+        let decoded: ZgQueryParam = rmp_serde::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, query_param);
+    }
 
     #[test]
     fn test_lookup_api() {
@@ -451,20 +1434,493 @@ mod tests {
         fn ans(n: &str, v: &str) -> Option<(String, String)> {
             Some((n.to_string(), v.to_string()))
         }
+        let lookup = |api_string| lookup_api(api_string, &config::Config::default()).unwrap();
 
         // Valid cases
-        assert_eq!(lookup_api("container:v1"), ans("container", "v1"));
-        assert_eq!(lookup_api("container"), ans("container", "v1"));
-        assert_eq!(lookup_api("gke"), ans("container", "v1"));
-        assert_eq!(lookup_api("gke:v1"), ans("container", "v1"));
+        assert_eq!(lookup("container:v1"), ans("container", "v1"));
+        assert_eq!(lookup("container"), ans("container", "v1"));
+        assert_eq!(lookup("gke"), ans("container", "v1"));
+        assert_eq!(lookup("gke:v1"), ans("container", "v1"));
 
         // Invalid name
-        assert_eq!(lookup_api("unknown"), None);
-        assert_eq!(lookup_api("unknown:v1"), None);
+        assert_eq!(lookup("unknown"), None);
+        assert_eq!(lookup("unknown:v1"), None);
 
         // Invalid versions
-        assert_eq!(lookup_api("container:v9999"), None);
-        assert_eq!(lookup_api("container:heyhey"), None);
+        assert_eq!(lookup("container:v9999"), None);
+        assert_eq!(lookup("container:heyhey"), None);
+
+        // Version strings with underscores (Google Workspace APIs)
+        assert_eq!(lookup("admin"), ans("admin", "directory_v1"));
+        assert_eq!(lookup("admin:reports_v1"), ans("admin", "reports_v1"));
+        assert_eq!(lookup("admin:directory"), None);
+        assert_eq!(lookup("drive:v3"), ans("drive", "v3"));
+    }
+
+    #[test]
+    fn test_lookup_api_with_pinned_versions() {
+        fn ans(n: &str, v: &str) -> Option<(String, String)> {
+            Some((n.to_string(), v.to_string()))
+        }
+        let config = config::Config {
+            versions: [("cloudfunctions", "v1"), ("gke", "v1beta1")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let lookup = |api_string| lookup_api(api_string, &config).unwrap();
+
+        // The explicit version, then the pinned one (by name or alias), then the default one
+        assert_eq!(lookup("cloudfunctions:v2"), ans("cloudfunctions", "v2"));
+        assert_eq!(lookup("cloudfunctions"), ans("cloudfunctions", "v1"));
+        assert_eq!(lookup("container"), ans("container", "v1beta1"));
+        assert_eq!(lookup("gke:v1"), ans("container", "v1"));
+        assert_eq!(lookup("run"), ans("run", "v2"));
+
+        let config = config::Config {
+            versions: [("cloudfunctions".to_string(), "v9".to_string())].into(),
+            ..Default::default()
+        };
+        let e = lookup_api("cloudfunctions", &config).unwrap_err();
+        assert!(matches!(e, ZgError::InvalidConfig { .. }));
+        assert!(
+            e.to_string().ends_with(
+                "versions.cloudfunctions = \"v9\" is not a version of cloudfunctions; available versions: v2, v2beta, v2alpha, v1"
+            ),
+            "{}",
+            e
+        );
+        // The explicit version doesn't need the pinned one
+        assert_eq!(
+            lookup_api("cloudfunctions:v1", &config).unwrap(),
+            ans("cloudfunctions", "v1")
+        );
+    }
+
+    #[test]
+    fn test_api_file_name() {
+        assert_eq!(api_file_name("container:v1"), "container_v1.msgpack");
+        assert_eq!(
+            api_file_name("admin:directory_v1"),
+            "admin_directory_v1.msgpack"
+        );
+    }
+
+    #[test]
+    fn test_resolve_config_dir() {
+        let tmp = std::env::temp_dir().join(format!("zg-test-config-dir-{}", std::process::id()));
+        let home = tmp.join("home");
+        let xdg = tmp.join("xdg");
+        let env = |vars: Vec<(&'static str, PathBuf)>| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string_lossy().to_string())
+            }
+        };
+
+        // Falls back to $HOME/.config/zg
+        assert_eq!(
+            resolve_config_dir(env(vec![]), Some(home.clone()), false),
+            home.join(".config/zg")
+        );
+        // XDG_CONFIG_HOME is respected when no legacy directory exists
+        assert_eq!(
+            resolve_config_dir(
+                env(vec![("XDG_CONFIG_HOME", xdg.clone())]),
+                Some(home.clone()),
+                false
+            ),
+            xdg.join("zg")
+        );
+        // %APPDATA% on Windows
+        assert_eq!(
+            resolve_config_dir(
+                env(vec![("APPDATA", tmp.join("appdata"))]),
+                Some(home.clone()),
+                true
+            ),
+            tmp.join("appdata").join("zg")
+        );
+
+        // The legacy directory keeps being used until the new one exists
+        create_dir_all(home.join(".config/zg")).unwrap();
+        assert_eq!(
+            resolve_config_dir(
+                env(vec![("XDG_CONFIG_HOME", xdg.clone())]),
+                Some(home.clone()),
+                false
+            ),
+            home.join(".config/zg")
+        );
+        create_dir_all(xdg.join("zg")).unwrap();
+        assert_eq!(
+            resolve_config_dir(
+                env(vec![("XDG_CONFIG_HOME", xdg.clone())]),
+                Some(home.clone()),
+                false
+            ),
+            xdg.join("zg")
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_lookup_discovered_api() {
+        let item = |name: &str, version: &str, preferred: bool| discovery::DiscoveryDirectoryItem {
+            id: format!("{}:{}", name, version),
+            name: name.to_string(),
+            version: version.to_string(),
+            preferred,
+            ..discovery::DiscoveryDirectoryItem::testdata()
+        };
+        let items = vec![
+            item("firebasehosting", "v1beta1", false),
+            item("firebasehosting", "v1", true),
+            item("newapi", "v2alpha", false),
+        ];
+        let ans = |n: &str, v: &str| Some((n.to_string(), v.to_string()));
+
+        assert_eq!(
+            lookup_discovered_api("firebasehosting", &items),
+            ans("firebasehosting", "v1")
+        );
+        assert_eq!(
+            lookup_discovered_api("firebasehosting:v1beta1", &items),
+            ans("firebasehosting", "v1beta1")
+        );
+        assert_eq!(
+            lookup_discovered_api("newapi", &items),
+            ans("newapi", "v2alpha")
+        );
+        assert_eq!(lookup_discovered_api("firebasehosting:v9", &items), None);
+        assert_eq!(lookup_discovered_api("unknown", &items), None);
+
+        // Aliases are resolved only through the supported APIs, which take precedence
+        assert_eq!(lookup_discovered_api("gke", &items), None);
+        assert_eq!(
+            lookup_api("gke", &config::Config::default()).unwrap(),
+            ans("container", "v1")
+        );
+    }
+
+    #[test]
+    fn test_lookup_discovered_api_underscored_versions() {
+        // Excerpt of the discovery directory
+        let items: Vec<discovery::DiscoveryDirectoryItem> = serde_json::from_str(
+            r#"[
+                {"kind": "discovery#directoryItem", "id": "admin:datatransfer_v1", "name": "admin", "version": "datatransfer_v1",
+                 "title": "Admin SDK API", "description": "Admin SDK lets administrators of enterprise domains to view and manage resources like user, groups etc.",
+                 "discoveryRestUrl": "https://admin.googleapis.com/$discovery/rest?version=datatransfer_v1",
+                 "documentationLink": "https://developers.google.com/admin-sdk/", "preferred": false},
+                {"kind": "discovery#directoryItem", "id": "admin:directory_v1", "name": "admin", "version": "directory_v1",
+                 "title": "Admin SDK API", "description": "Admin SDK lets administrators of enterprise domains to view and manage resources like user, groups etc.",
+                 "discoveryRestUrl": "https://admin.googleapis.com/$discovery/rest?version=directory_v1",
+                 "documentationLink": "https://developers.google.com/admin-sdk/", "preferred": true}
+            ]"#,
+        )
+        .unwrap();
+        let ans = |n: &str, v: &str| Some((n.to_string(), v.to_string()));
+
+        assert_eq!(
+            lookup_discovered_api("admin", &items),
+            ans("admin", "directory_v1")
+        );
+        assert_eq!(
+            lookup_discovered_api("admin:datatransfer_v1", &items),
+            ans("admin", "datatransfer_v1")
+        );
+        assert_eq!(
+            api_file_name(&items[1].id),
+            format!("{}_{}.msgpack", items[1].name, items[1].version)
+        );
+    }
+
+    #[test]
+    fn test_is_older_than() {
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let max_age = day * 30;
+        assert!(!is_older_than(now, now, max_age));
+        assert!(!is_older_than(now - day * 29, now, max_age));
+        assert!(is_older_than(now - day * 31, now, max_age));
+        assert!(!is_older_than(now + day, now, max_age)); // mtime in the future
+    }
+
+    #[test]
+    fn test_resource_ambiguities() {
+        // Spanner shape: 'operations' under instanceConfigs, instances, and databases
+        let resource = |path: &str, resources: Option<Vec<ZgResource>>| ZgResource {
+            name: path.rsplit('.').next().unwrap().to_string(),
+            path: Some(path.to_string()),
+            resources,
+            ..ZgResource::testdata()
+        };
+        let mut api = ZgApi {
+            id: "spanner:v1".to_string(),
+            resources: vec![resource(
+                "spanner.projects",
+                Some(vec![
+                    resource(
+                        "spanner.projects.instanceConfigs",
+                        Some(vec![resource(
+                            "spanner.projects.instanceConfigs.operations",
+                            None,
+                        )]),
+                    ),
+                    resource(
+                        "spanner.projects.instances",
+                        Some(vec![
+                            resource(
+                                "spanner.projects.instances.databases",
+                                Some(vec![resource(
+                                    "spanner.projects.instances.databases.operations",
+                                    None,
+                                )]),
+                            ),
+                            resource("spanner.projects.instances.operations", None),
+                        ]),
+                    ),
+                ]),
+            )],
+            ..ZgApi::testdata()
+        };
+
+        let ambiguities = api.resource_ambiguities();
+        assert_eq!(
+            ambiguities,
+            vec![ResourceAmbiguity {
+                name: "operations".to_string(),
+                paths: vecs![
+                    "spanner.projects.instanceConfigs.operations",
+                    "spanner.projects.instances.databases.operations",
+                    "spanner.projects.instances.operations"
+                ],
+                unique_paths: vecs![
+                    "instanceConfigs.operations",
+                    "databases.operations",
+                    "instances.operations"
+                ],
+                default: Some("spanner.projects.instances.operations".to_string()),
+            }]
+        );
+
+        // Without a flavor, no default is reported (the last candidate is picked with a warning)
+        api.id = "testapi:v1".to_string();
+        assert_eq!(api.resource_ambiguities()[0].default, None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_or_prepare_recovers_truncated_file() {
+        let path =
+            std::env::temp_dir().join(format!("zg-test-truncated-{}.msgpack", std::process::id()));
+        let mut encoded = Vec::new();
+        ZgApi::testdata()
+            .serialize(&mut rmp_serde::Serializer::new(&mut encoded))
+            .unwrap();
+
+        // Simulate an interrupted write
+        std::fs::write(&path, &encoded[..encoded.len() / 2]).unwrap();
+        let api = decode_or_prepare(File::open(&path).unwrap(), &path, || async {
+            write_file_atomic(&path, &encoded)?;
+            Ok(File::open(&path)?)
+        })
+        .await
+        .unwrap();
+        assert_eq!(api.id, "testapi:v1");
+
+        // Failing to prepare again is reported
+        std::fs::write(&path, &encoded[..1]).unwrap();
+        let result = decode_or_prepare(File::open(&path).unwrap(), &path, || async {
+            Err::<File, ZgError>("offline".into())
+        })
+        .await;
+        assert!(matches!(result, Err(ZgError::Other(m)) if m == "offline"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_join_path() {
+        let expected = "https://example.com/v1/projects/p";
+        assert_eq!(join_path("https://example.com/", "v1/projects/p"), expected);
+        assert_eq!(join_path("https://example.com", "v1/projects/p"), expected);
+        assert_eq!(
+            join_path("https://example.com/", "/v1/projects/p"),
+            expected
+        );
+        assert_eq!(
+            join_path("https://example.com//", "//v1/projects/p"),
+            expected
+        );
+        assert_eq!(
+            join_path("https://example.com/", ""),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_base_url_for() {
+        let api = |root_url: Option<&str>, service_path: Option<&str>| ZgApi {
+            base_url: "https://legacy.example.com/base/".to_string(),
+            root_url: root_url.map(str::to_string),
+            service_path: service_path.map(str::to_string),
+            ..ZgApi::testdata()
+        };
+
+        // API files without rootUrl use base_url
+        assert_eq!(
+            api(None, None).base_url_for("v1/projects/p"),
+            "https://legacy.example.com/base/"
+        );
+        // rootUrl + servicePath, with or without slashes
+        for (root_url, service_path) in [
+            ("https://bigquery.googleapis.com/", "bigquery/v2/"),
+            ("https://bigquery.googleapis.com", "bigquery/v2"),
+            ("https://bigquery.googleapis.com/", "/bigquery/v2/"),
+        ] {
+            assert_eq!(
+                api(Some(root_url), Some(service_path)).base_url_for("projects/p/datasets"),
+                "https://bigquery.googleapis.com/bigquery/v2/"
+            );
+        }
+        // No servicePath
+        assert_eq!(
+            api(Some("https://container.googleapis.com/"), Some("")).base_url_for("v1/projects/p"),
+            "https://container.googleapis.com/"
+        );
+        // The path already starts with the servicePath
+        let overlapping = api(Some("https://example.googleapis.com/"), Some("v1/"));
+        assert_eq!(
+            overlapping.base_url_for("v1/projects/p"),
+            "https://example.googleapis.com/"
+        );
+        assert_eq!(
+            overlapping.base_url_for("v1beta/projects/p"),
+            "https://example.googleapis.com/v1/"
+        );
+        // On a regional root URL
+        assert_eq!(
+            overlapping.base_url_on("https://us-central1-example.googleapis.com", "projects/p"),
+            "https://us-central1-example.googleapis.com/v1/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_or_prepare_regenerates_old_format() {
+        let path =
+            std::env::temp_dir().join(format!("zg-test-old-format-{}.msgpack", std::process::id()));
+        let encode = |api: ZgApi| {
+            let mut encoded = Vec::new();
+            api.serialize(&mut rmp_serde::Serializer::new(&mut encoded))
+                .unwrap();
+            encoded
+        };
+        let old = encode(ZgApi {
+            format_version: 0,
+            ..ZgApi::testdata()
+        });
+        let current = encode(ZgApi::testdata());
+
+        std::fs::write(&path, &old).unwrap();
+        let api = decode_or_prepare(File::open(&path).unwrap(), &path, || async {
+            write_file_atomic(&path, &current)?;
+            Ok(File::open(&path)?)
+        })
+        .await
+        .unwrap();
+        assert_eq!(api.format_version, API_FORMAT_VERSION);
+
+        // The old one is still usable when it can't be prepared again
+        std::fs::write(&path, &old).unwrap();
+        let api = decode_or_prepare(File::open(&path).unwrap(), &path, || async {
+            Err::<File, ZgError>("offline".into())
+        })
+        .await
+        .unwrap();
+        assert_eq!(api.format_version, 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_api_file_not_supported() {
+        let result = load_api_file("unknown", None).await;
+        assert!(
+            matches!(result, Err(ZgError::ApiNotSupported { service, .. }) if service == "unknown")
+        );
+    }
+
+    #[test]
+    fn test_find_resource_not_found() {
+        let resources = [ZgResource::testdata()];
+        let result = find_resource("testapi:v1", &resources, "nothing", false);
+        assert!(matches!(
+            result,
+            Err(ZgError::ResourceNotFound { resource, .. }) if resource == "nothing"
+        ));
+    }
+
+    #[test]
+    fn test_find_resource_suggestions() {
+        let top_resources = vec![ZgResource {
+            name: "clusters".to_string(),
+            path: Some("container.projects.locations.clusters".to_string()),
+            ..ZgResource::testdata()
+        }];
+        let result = find_resource("container:v1", &top_resources, "cluster", false);
+        assert!(matches!(
+            result,
+            Err(ZgError::ResourceNotFound { candidates, .. }) if candidates == vec!["clusters"]
+        ));
+
+        let result = find_resource("container:v1", &top_resources, "locations.clustr", false);
+        assert!(matches!(
+            result,
+            Err(ZgError::ResourceNotFound { candidates, .. }) if candidates == vec!["locations.clusters"]
+        ));
+    }
+
+    #[test]
+    fn test_find_resource_ignore_case() {
+        let top_resources = vec![ZgResource {
+            name: "nodePools".to_string(),
+            path: Some("container.projects.locations.clusters.nodePools".to_string()),
+            ..ZgResource::testdata()
+        }];
+        for input in ["nodePools", "nodepools", "NODEPOOLS", "clusters.NodePools"] {
+            let result = find_resource("container:v1", &top_resources, input, true).unwrap();
+            assert_eq!(result.name, "nodePools", "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_find_resource_exact_case_preferred() {
+        let top_resources = vec![
+            ZgResource {
+                name: "nodePools".to_string(),
+                path: Some("testapi.projects.nodePools".to_string()),
+                ..ZgResource::testdata()
+            },
+            ZgResource {
+                name: "nodepools".to_string(),
+                path: Some("testapi.projects.nodepools".to_string()),
+                ..ZgResource::testdata()
+            },
+        ];
+        let result = find_resource("testapi:v1", &top_resources, "nodepools", true).unwrap();
+        assert_eq!(result.path.as_deref(), Some("testapi.projects.nodepools"));
+        let result = find_resource("testapi:v1", &top_resources, "nodePools", true).unwrap();
+        assert_eq!(result.path.as_deref(), Some("testapi.projects.nodePools"));
+
+        // Neither matches exactly; names differing only by case are not merged
+        let result = find_resource("testapi:v1", &top_resources, "NODEPOOLS", false);
+        assert!(matches!(
+            result,
+            Err(ZgError::AmbiguousResource { candidates, .. }) if candidates.len() == 2
+        ));
     }
 
     #[test]
@@ -474,7 +1930,7 @@ mod tests {
             path: Some("container.projects.locations.clusters".to_string()),
             ..ZgResource::testdata()
         }];
-        let result = find_resource("container", &top_resources, "clusters");
+        let result = find_resource("container", &top_resources, "clusters", false);
         assert!(result.is_ok(), "Expected to find a 'clusters' resource");
         assert_eq!(result.unwrap().name, "clusters");
     }
@@ -486,7 +1942,7 @@ mod tests {
             path: Some("container.projects.locations.clusters".to_string()),
             ..ZgResource::testdata()
         }];
-        let result = find_resource("container", &top_resources, "locations.clusters");
+        let result = find_resource("container", &top_resources, "locations.clusters", false);
         assert!(
             result.is_ok(),
             "Expected to find a 'locations.clusters' resource"
@@ -494,6 +1950,70 @@ mod tests {
         assert_eq!(result.unwrap().name, "clusters");
     }
 
+    #[test]
+    fn test_normalize_resource_path() {
+        assert_eq!(
+            normalize_resource_path("locations.clusters"),
+            "locations.clusters"
+        );
+        assert_eq!(
+            normalize_resource_path("projects/locations/clusters"),
+            "projects.locations.clusters"
+        );
+        assert_eq!(
+            normalize_resource_path("locations/clusters/"),
+            "locations.clusters"
+        );
+        assert_eq!(normalize_resource_path("/clusters"), ".clusters");
+        assert_eq!(normalize_resource_path("clusters"), "clusters");
+
+        let top_resources = vec![ZgResource {
+            name: "clusters".to_string(),
+            path: Some("container.projects.locations.clusters".to_string()),
+            ..ZgResource::testdata()
+        }];
+        let result = find_resource(
+            "container:v1",
+            &top_resources,
+            "projects/locations/clusters/",
+            true,
+        );
+        assert_eq!(result.unwrap().name, "clusters");
+    }
+
+    #[test]
+    fn test_path_matches() {
+        let path = "container.projects.locations.clusters";
+        assert!(path_matches(path, "clusters"));
+        assert!(path_matches(path, "locations.clusters"));
+        assert!(path_matches(path, ".clusters"));
+        assert!(path_matches(path, "container.projects.locations.clusters"));
+        assert!(!path_matches(path, "sters"));
+        assert!(!path_matches(path, "ations.clusters"));
+        assert!(!path_matches(path, "zones.clusters"));
+        assert!(!path_matches(path, ""));
+        assert!(!path_matches(path, "."));
+    }
+
+    #[test]
+    fn test_find_resource_substring_is_not_a_match() {
+        let top_resources = vec![ZgResource {
+            name: "workloadSessions".to_string(),
+            path: Some("dataproc.projects.locations.workloadSessions".to_string()),
+            resources: Some(vec![ZgResource {
+                name: "sessions".to_string(),
+                path: Some("dataproc.projects.locations.sessions".to_string()),
+                ..ZgResource::testdata()
+            }]),
+            ..ZgResource::testdata()
+        }];
+        let result = find_resource("dataproc:v1", &top_resources, "sessions", false).unwrap();
+        assert_eq!(
+            result.path.as_deref(),
+            Some("dataproc.projects.locations.sessions")
+        );
+    }
+
     #[test]
     fn test_select_resource_single_match() {
         let top_resources = [ZgResource {
@@ -535,8 +2055,8 @@ mod tests {
             },
         ];
         let found = vec![
-            find_resource("container", &top_resources, "locations.clusters").unwrap(),
-            find_resource("container", &top_resources, "zones.clusters").unwrap(),
+            find_resource("container", &top_resources, "locations.clusters", false).unwrap(),
+            find_resource("container", &top_resources, "zones.clusters", false).unwrap(),
         ];
         let result = select_resource("container:v1", "unused_resource_path", found);
         assert!(result.is_some());
@@ -546,6 +2066,53 @@ mod tests {
         ); // Should prioritize locations.clusters
     }
 
+    #[test]
+    fn test_find_resource_strict_ambiguous() {
+        let top_resources = vec![
+            ZgResource {
+                name: "clusters".to_string(),
+                path: Some("container.projects.locations.clusters".to_string()),
+                ..ZgResource::testdata()
+            },
+            ZgResource {
+                name: "clusters".to_string(),
+                path: Some("container.projects.zones.clusters".to_string()),
+                ..ZgResource::testdata()
+            },
+        ];
+        // Default mode resolves the ambiguity with the flavor
+        assert!(find_resource("container:v1", &top_resources, "clusters", false).is_ok());
+
+        let result = find_resource("container:v1", &top_resources, "clusters", true);
+        assert!(matches!(
+            result,
+            Err(ZgError::AmbiguousResource { ref candidates, .. }) if candidates.len() == 2
+        ));
+
+        // Unique paths are fine even in strict mode
+        let result = find_resource("container:v1", &top_resources, "zones.clusters", true);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_shortest_unique_path() {
+        let candidates = vecs![
+            "container.projects.locations.clusters",
+            "container.projects.zones.clusters"
+        ];
+        assert_eq!(
+            shortest_unique_path(&candidates[0], &candidates),
+            "locations.clusters"
+        );
+        assert_eq!(
+            shortest_unique_path(&candidates[1], &candidates),
+            "zones.clusters"
+        );
+
+        let candidates = vecs!["a.b.c", "b.c"];
+        assert_eq!(shortest_unique_path("a.b.c", &candidates), "a.b.c");
+    }
+
     #[test]
     fn test_find_method_success() {
         let resource = ZgResource::testdata();
@@ -563,6 +2130,281 @@ mod tests {
         let method_name = "nonexistent_method";
         let result = find_method(&resource, method_name);
 
-        assert!(result.is_err(), "Expected an error");
+        assert!(
+            matches!(result, Err(ZgError::MethodNotFound { .. })),
+            "Expected MethodNotFound"
+        );
+
+        let result = find_method(&resource, "lst");
+        assert!(matches!(
+            result,
+            Err(ZgError::MethodNotFound { candidates, .. }) if candidates == vec!["list"]
+        ));
+    }
+
+    #[test]
+    fn test_find_method_ignore_case() {
+        let resource = ZgResource {
+            methods: vec![
+                ZgMethod {
+                    name: "setIamPolicy".to_string(),
+                    ..ZgMethod::testdata()
+                },
+                ZgMethod::testdata(),
+            ],
+            ..ZgResource::testdata()
+        };
+        assert_eq!(
+            find_method(&resource, "setiampolicy").unwrap().name,
+            "setIamPolicy"
+        );
+        assert_eq!(find_method(&resource, "LIST").unwrap().name, "list");
+    }
+
+    #[test]
+    fn test_find_method_aliases() {
+        let method = |name: &str| ZgMethod {
+            name: name.to_string(),
+            ..ZgMethod::testdata()
+        };
+        let resource = ZgResource {
+            methods: vec![method("list"), method("delete"), method("insert")],
+            ..ZgResource::testdata()
+        };
+        assert_eq!(find_method(&resource, "ls").unwrap().name, "list");
+        assert_eq!(find_method(&resource, "rm").unwrap().name, "delete");
+        assert_eq!(find_method(&resource, "mk").unwrap().name, "insert");
+        assert!(find_method(&resource, "desc").is_err()); // no "get" on the resource
+
+        // The literal method name wins over the alias
+        let resource = ZgResource {
+            methods: vec![method("list"), method("ls")],
+            ..ZgResource::testdata()
+        };
+        assert_eq!(find_method(&resource, "ls").unwrap().name, "ls");
+    }
+
+    #[test]
+    fn test_find_method_by_id() {
+        let method = |name: &str| ZgMethod {
+            name: name.to_string(),
+            ..ZgMethod::testdata()
+        };
+        let api = ZgApi {
+            id: "container:v1".to_string(),
+            resources: vec![ZgResource {
+                name: "projects".to_string(),
+                path: Some("container.projects".to_string()),
+                methods: vec![],
+                resources: Some(vec![
+                    ZgResource {
+                        name: "clusters".to_string(),
+                        path: Some("container.projects.locations.clusters".to_string()),
+                        methods: vec![method("get"), method("setMasterAuth")],
+                        resources: None,
+                        parent_path: None,
+                    },
+                    ZgResource {
+                        name: "clusters".to_string(),
+                        path: Some("container.projects.zones.clusters".to_string()),
+                        methods: vec![method("get"), method("setMasterAuth")],
+                        resources: None,
+                        parent_path: None,
+                    },
+                ]),
+                parent_path: None,
+            }],
+            ..ZgApi::testdata()
+        };
+
+        // Full id: resolved by the exact path, even in the strict mode
+        let (resource, method) = find_method_by_id(
+            &api,
+            "container.projects.zones.clusters.setMasterAuth",
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            resource.path.as_deref(),
+            Some("container.projects.zones.clusters")
+        );
+        assert_eq!(method.name, "setMasterAuth");
+
+        // Partial id: falls back to the resource path + method name
+        let (resource, method) = find_method_by_id(&api, "locations.clusters.get", true).unwrap();
+        assert_eq!(
+            resource.path.as_deref(),
+            Some("container.projects.locations.clusters")
+        );
+        assert_eq!(method.name, "get");
+        assert!(matches!(
+            find_method_by_id(&api, "clusters.get", true),
+            Err(ZgError::AmbiguousResource { .. })
+        ));
+
+        // Id of another service
+        assert!(matches!(
+            find_method_by_id(&api, "compute.instances.get", false),
+            Err(ZgError::ServiceMismatch { service, .. }) if service == "compute"
+        ));
+
+        // Not an id
+        assert!(find_method_by_id(&api, "get", false).is_err());
+
+        // The prefix of any resource is the API's, not only the first one's
+        let mut resources = vec![ZgResource {
+            name: "operations".to_string(),
+            path: Some("compute.operations".to_string()),
+            methods: vec![ZgMethod::testdata()],
+            resources: None,
+            parent_path: None,
+        }];
+        resources.extend(api.resources.clone());
+        let api = ZgApi { resources, ..api };
+        let (_, method) =
+            find_method_by_id(&api, "container.projects.zones.clusters.get", true).unwrap();
+        assert_eq!(method.name, "get");
+    }
+
+    #[test]
+    fn test_suggest_services() {
+        assert_eq!(suggest_services("containers:v1"), vec!["container"]);
+        assert!(suggest_services("zzzzzzzz").is_empty());
+    }
+
+    /// The resource lookup before `ResourceIndex`: walks the whole tree for every lookup.
+    fn scan_resources<'a>(
+        resource_path: &str,
+        resources: &'a [ZgResource],
+        ignore_case: bool,
+        found: &mut Vec<&'a ZgResource>,
+    ) {
+        for resource in resources {
+            if let Some(path) = &resource.path {
+                let matched = if ignore_case {
+                    path_matches(&path.to_lowercase(), &resource_path.to_lowercase())
+                } else {
+                    path_matches(path, resource_path)
+                };
+                if matched {
+                    found.push(resource);
+                }
+            }
+            if let Some(sub_resources) = &resource.resources {
+                scan_resources(resource_path, sub_resources, ignore_case, found);
+            }
+        }
+    }
+
+    /// User-typed paths to look up in the API: every suffix of every resource path, in various forms.
+    fn lookup_inputs(api: &ZgApi) -> Vec<String> {
+        let mut inputs = vec!["".to_string(), ".".to_string(), "nothing".to_string()];
+        for (_, path) in api.all_resource_paths() {
+            let segments: Vec<&str> = path.split('.').collect();
+            for i in 0..segments.len() {
+                let suffix = segments[i..].join(".");
+                inputs.push(format!(".{}", suffix));
+                inputs.push(suffix.to_uppercase());
+                inputs.push(suffix[1..].to_string());
+                inputs.push(suffix);
+            }
+        }
+        inputs
+    }
+
+    #[test]
+    fn test_resource_index_equivalent_to_scan() {
+        let fixtures = [
+            "tests/test_data/container_v1_nested.json",
+            "tests/test_data/run_v2_required.json",
+        ];
+        for fixture in fixtures {
+            let api = update::extract_api(PathBuf::from(fixture), false).unwrap();
+            for input in lookup_inputs(&api) {
+                for ignore_case in [false, true] {
+                    let mut scanned = Vec::new();
+                    scan_resources(&input, &api.resources, ignore_case, &mut scanned);
+                    let indexed = api
+                        .resource_index()
+                        .matches(&api.resources, &input, ignore_case);
+                    assert!(
+                        scanned.len() == indexed.len()
+                            && scanned
+                                .iter()
+                                .zip(&indexed)
+                                .all(|(a, b)| std::ptr::eq(*a, *b)),
+                        "{} '{}' (ignore_case: {})",
+                        fixture,
+                        input,
+                        ignore_case
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "timing-dependent micro-benchmark; run with `cargo test -- --ignored`"]
+    fn test_resource_index_lookup_speed() {
+        // Micro-benchmark on a tree of compute:v1's size: 20 top-level resources with 20 sub-resources each
+        let resources: Vec<ZgResource> = (0..20)
+            .map(|i| ZgResource {
+                name: format!("parent{}", i),
+                path: Some(format!("compute.projects.parent{}", i)),
+                resources: Some(
+                    (0..20)
+                        .map(|j| ZgResource {
+                            name: format!("child{}", j),
+                            path: Some(format!("compute.projects.parent{}.child{}", i, j)),
+                            ..ZgResource::testdata()
+                        })
+                        .collect(),
+                ),
+                ..ZgResource::testdata()
+            })
+            .collect();
+        let api = ZgApi {
+            resources,
+            ..ZgApi::testdata()
+        };
+        let inputs = ["parent3.child7", "CHILD19", "parent19", "nothing"];
+
+        let started = Instant::now();
+        for _ in 0..200 {
+            for input in inputs {
+                let mut found = Vec::new();
+                scan_resources(input, &api.resources, false, &mut found);
+                if found.is_empty() {
+                    scan_resources(input, &api.resources, true, &mut found);
+                }
+            }
+        }
+        let scanned = started.elapsed();
+
+        let started = Instant::now();
+        for _ in 0..200 {
+            for input in inputs {
+                let index = api.resource_index();
+                if index.matches(&api.resources, input, false).is_empty() {
+                    index.matches(&api.resources, input, true);
+                }
+            }
+        }
+        let indexed = started.elapsed();
+
+        debug!("scan: {:?}, index: {:?}", scanned, indexed);
+        assert!(
+            indexed < scanned,
+            "scan: {:?}, index: {:?}",
+            scanned,
+            indexed
+        );
+        assert_eq!(
+            find_api_resource(&api, "parent3.child7", true)
+                .unwrap()
+                .path
+                .as_deref(),
+            Some("compute.projects.parent3.child7")
+        );
     }
 }
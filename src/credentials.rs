@@ -0,0 +1,531 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Credentials files given with `--credentials-file` (or `credentials_file` in the config file), used instead of gcloud.
+//! Supports Workload Identity Federation (`"type": "external_account"`): a subject token from a file or URL is exchanged
+//! for a Google access token at the STS endpoint, optionally impersonating a service account.
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request};
+use log::debug;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use super::config;
+use super::error::ZgError;
+use super::exec;
+use super::logging;
+use super::timing;
+
+pub(crate) const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Tokens are renewed this long before they expire, so that a request doesn't carry an expiring token.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Tokens obtained in this process per credentials file, with when they expire.
+static TOKENS: LazyLock<Mutex<HashMap<PathBuf, (Instant, String)>>> =
+    LazyLock::new(Default::default);
+
+/// An `external_account` credentials file, as generated by `gcloud iam workload-identity-pools create-cred-config`.
+#[derive(Deserialize, Debug)]
+struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+    token_url: String,
+    service_account_impersonation_url: Option<String>,
+    credential_source: CredentialSource,
+    /// The universe domain the tokens are for (`googleapis.com` if omitted).
+    universe_domain: Option<String>,
+}
+
+/// Where to read the subject token (e.g., an OIDC token of the CI job) from.
+#[derive(Deserialize, Debug)]
+struct CredentialSource {
+    file: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// Set for AWS ("aws1"), which needs request signing and is not supported.
+    environment_id: Option<String>,
+    format: Option<SourceFormat>,
+}
+
+/// Format of the subject token source: the whole content ("text"), or a field of a JSON object ("json").
+#[derive(Deserialize, Debug)]
+struct SourceFormat {
+    #[serde(rename = "type")]
+    format_type: String,
+    subject_token_field_name: Option<String>,
+}
+
+/// Sends the HTTP requests of the token exchange. Replaced with a fake in tests.
+pub(crate) trait Transport {
+    /// Sends a request and returns the status code and the body.
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(String, String)],
+        body: Option<String>,
+    ) -> Result<(u16, String), ZgError>;
+}
+
+pub(crate) struct HyperTransport;
+
+impl Transport for HyperTransport {
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(String, String)],
+        body: Option<String>,
+    ) -> Result<(u16, String), ZgError> {
+        let client = exec::build_client::<Full<Bytes>>()?;
+        let mut req = Request::builder().method(method).uri(url);
+        for (key, value) in headers {
+            req = req.header(key, value);
+        }
+        let req = req.body(Full::new(Bytes::from(body.unwrap_or_default())))?;
+        let response = client.request(req).await?;
+        let status = response.status().as_u16();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+}
+
+/// Returns an access token for the credentials file, reusing the one obtained earlier until it expires.
+pub(crate) async fn access_token(path: &Path) -> Result<String, ZgError> {
+    if let Ok(tokens) = TOKENS.lock() {
+        if let Some((expires_at, token)) = tokens.get(path) {
+            if Instant::now() + EXPIRY_MARGIN < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+    }
+    let _span = timing::span("credentials");
+    let account = load(path)?;
+    check_universe(&account, config::current().universe_domain())?;
+    let (token, lifetime) = exchange(&account, &HyperTransport).await?;
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.insert(
+            path.to_path_buf(),
+            (Instant::now() + lifetime, token.clone()),
+        );
+    }
+    Ok(token)
+}
+
+/// Discards the token obtained for the credentials file, so that the next `access_token` gets a new one.
+pub(crate) fn forget_access_token(path: &Path) {
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.remove(path);
+    }
+}
+
+fn load(path: &Path) -> Result<ExternalAccount, ZgError> {
+    let fail = |message: String| {
+        ZgError::AuthFailure(format!(
+            "invalid credentials file {}: {}",
+            path.display(),
+            message
+        ))
+    };
+    let content = std::fs::read_to_string(path).map_err(|e| fail(e.to_string()))?;
+    let value: Value = serde_json::from_str(&content).map_err(|e| fail(e.to_string()))?;
+    match value.get("type").and_then(Value::as_str) {
+        Some("external_account") => {
+            serde_json::from_value(value).map_err(|e| fail(e.to_string()))
+        }
+        Some(other) => Err(fail(format!(
+            "type '{}' is not supported; only 'external_account' (Workload Identity Federation) is. For service account keys, run `gcloud auth activate-service-account --key-file`.",
+            other
+        ))),
+        None => Err(fail("no 'type' field".to_string())),
+    }
+}
+
+/// Checks that the credentials are for the universe domain zg sends requests to (None for `googleapis.com`), as
+/// tokens of another universe are rejected by the APIs.
+fn check_universe(account: &ExternalAccount, universe_domain: Option<&str>) -> Result<(), ZgError> {
+    let expected = universe_domain.unwrap_or(config::DEFAULT_UNIVERSE_DOMAIN);
+    let actual = account
+        .universe_domain
+        .as_deref()
+        .unwrap_or(config::DEFAULT_UNIVERSE_DOMAIN);
+    if actual != expected {
+        return Err(ZgError::AuthFailure(format!(
+            "the credentials file is for the universe domain '{}', but requests go to '{}'. Set universe_domain (or --universe-domain) to match it.",
+            actual, expected
+        )));
+    }
+    Ok(())
+}
+
+/// Exchanges the subject token for an access token, returning the token and its lifetime.
+async fn exchange(
+    account: &ExternalAccount,
+    transport: &impl Transport,
+) -> Result<(String, Duration), ZgError> {
+    let subject_token = subject_token(&account.credential_source, transport).await?;
+
+    debug!(
+        "Auth: exchanging the subject token at {}",
+        account.token_url
+    );
+    let form = [
+        ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+        ("audience", &account.audience),
+        ("scope", CLOUD_PLATFORM_SCOPE),
+        ("requested_token_type", ACCESS_TOKEN_TYPE),
+        ("subject_token", &subject_token),
+        ("subject_token_type", &account.subject_token_type),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+    .collect::<Vec<_>>()
+    .join("&");
+    let headers = [(
+        "Content-Type".to_string(),
+        "application/x-www-form-urlencoded".to_string(),
+    )];
+    let sts = post_json(transport, &account.token_url, &headers, form)
+        .await
+        .map_err(|e| step_error("token exchange", &account.token_url, e))?;
+    let sts_token = string_field(&sts, "access_token")
+        .map_err(|e| step_error("token exchange", &account.token_url, e))?;
+    let lifetime = Duration::from_secs(
+        sts.get("expires_in")
+            .and_then(Value::as_u64)
+            .unwrap_or(3600),
+    );
+
+    let Some(url) = &account.service_account_impersonation_url else {
+        return Ok((sts_token, lifetime));
+    };
+    debug!("Auth: impersonating the service account at {}", url);
+    let headers = [
+        ("Authorization".to_string(), format!("Bearer {}", sts_token)),
+        ("Content-Type".to_string(), "application/json".to_string()),
+    ];
+    let body = json!({ "scope": [CLOUD_PLATFORM_SCOPE] }).to_string();
+    let impersonated = post_json(transport, url, &headers, body)
+        .await
+        .map_err(|e| step_error("service account impersonation", url, e))?;
+    let token = string_field(&impersonated, "accessToken")
+        .map_err(|e| step_error("service account impersonation", url, e))?;
+    // generateAccessToken issues tokens for 1 hour by default, at least as long as the STS token
+    Ok((token, lifetime))
+}
+
+/// Reads the subject token from the file or the URL of the credential source.
+async fn subject_token(
+    source: &CredentialSource,
+    transport: &impl Transport,
+) -> Result<String, ZgError> {
+    let (origin, content) = match (&source.file, &source.url, &source.environment_id) {
+        (Some(file), _, _) => {
+            debug!("Auth: reading the subject token from {}", file);
+            let content = std::fs::read_to_string(file)
+                .map_err(|e| step_error("reading the subject token", file, e.to_string()))?;
+            (file, content)
+        }
+        (None, Some(url), _) => {
+            debug!(
+                "Auth: fetching the subject token from {}",
+                logging::redact_url(url)
+            );
+            let headers: Vec<(String, String)> = source
+                .headers
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            let (status, body) = transport
+                .send(Method::GET, url, &headers, None)
+                .await
+                .map_err(|e| step_error("fetching the subject token", url, e.to_string()))?;
+            if status != 200 {
+                return Err(step_error(
+                    "fetching the subject token",
+                    url,
+                    format!("status {}: {}", status, body),
+                ));
+            }
+            (url, body)
+        }
+        (None, None, Some(environment_id)) => {
+            return Err(ZgError::AuthFailure(format!(
+                "credential source '{}' is not supported; use a file or URL source",
+                environment_id
+            )))
+        }
+        (None, None, None) => {
+            return Err(ZgError::AuthFailure(
+                "the credential source has neither 'file' nor 'url'".to_string(),
+            ))
+        }
+    };
+
+    match &source.format {
+        Some(SourceFormat {
+            format_type,
+            subject_token_field_name,
+        }) if format_type == "json" => {
+            let field = subject_token_field_name.as_deref().unwrap_or_default();
+            let value: Value = serde_json::from_str(&content)
+                .map_err(|e| step_error("reading the subject token", origin, e.to_string()))?;
+            string_field(&value, field)
+                .map_err(|e| step_error("reading the subject token", origin, e))
+        }
+        _ => Ok(content.trim().to_string()),
+    }
+}
+
+async fn post_json(
+    transport: &impl Transport,
+    url: &str,
+    headers: &[(String, String)],
+    body: String,
+) -> Result<Value, String> {
+    let (status, body) = transport
+        .send(Method::POST, url, headers, Some(body))
+        .await
+        .map_err(|e| e.to_string())?;
+    if status != 200 {
+        return Err(format!("status {}: {}", status, body.trim()));
+    }
+    serde_json::from_str(&body).map_err(|e| format!("invalid response ({})", e))
+}
+
+fn string_field(value: &Value, field: &str) -> Result<String, String> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| format!("no '{}' in the response", field))
+}
+
+fn step_error(step: &str, target: &str, message: impl std::fmt::Display) -> ZgError {
+    ZgError::AuthFailure(format!("{} ({}) failed: {}", step, target, message))
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Method, URL, headers, and body of a request sent.
+    type SentRequest = (Method, String, Vec<(String, String)>, Option<String>);
+
+    /// Answers the requests with the responses in order, recording the requests.
+    struct FakeTransport {
+        responses: RefCell<Vec<(u16, &'static str)>>,
+        requests: RefCell<Vec<SentRequest>>,
+    }
+
+    impl FakeTransport {
+        fn new(responses: Vec<(u16, &'static str)>) -> Self {
+            FakeTransport {
+                responses: RefCell::new(responses),
+                requests: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Transport for FakeTransport {
+        async fn send(
+            &self,
+            method: Method,
+            url: &str,
+            headers: &[(String, String)],
+            body: Option<String>,
+        ) -> Result<(u16, String), ZgError> {
+            self.requests
+                .borrow_mut()
+                .push((method, url.to_string(), headers.to_vec(), body));
+            let (status, body) = self.responses.borrow_mut().remove(0);
+            Ok((status, body.to_string()))
+        }
+    }
+
+    fn external_account(credential_source: Value, impersonation: bool) -> ExternalAccount {
+        let mut value = json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/123/locations/global/workloadIdentityPools/ci/providers/github",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": "https://sts.googleapis.com/v1/token",
+            "credential_source": credential_source,
+        });
+        if impersonation {
+            value["service_account_impersonation_url"] = json!("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/ci@p.iam.gserviceaccount.com:generateAccessToken");
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exchange_with_url_source_and_impersonation() {
+        let transport = FakeTransport::new(vec![
+            (200, r#"{"value": "oidc-jwt"}"#),
+            (
+                200,
+                r#"{"access_token": "sts-token", "expires_in": 1800, "token_type": "Bearer"}"#,
+            ),
+            (
+                200,
+                r#"{"accessToken": "sa-token", "expireTime": "2025-01-01T00:00:00Z"}"#,
+            ),
+        ]);
+        let account = external_account(
+            json!({
+                "url": "https://token.actions.example.com/?audience=x",
+                "headers": { "Authorization": "bearer request-token" },
+                "format": { "type": "json", "subject_token_field_name": "value" }
+            }),
+            true,
+        );
+
+        let (token, lifetime) = exchange(&account, &transport).await.unwrap();
+        assert_eq!(token, "sa-token");
+        assert_eq!(lifetime, Duration::from_secs(1800));
+
+        let requests = transport.requests.borrow();
+        assert_eq!(requests[0].0, Method::GET);
+        assert_eq!(
+            requests[0].2,
+            vec![(
+                "Authorization".to_string(),
+                "bearer request-token".to_string()
+            )]
+        );
+        let form = requests[1].3.as_deref().unwrap();
+        assert!(form.contains("subject_token=oidc-jwt"), "{}", form);
+        assert!(
+            form.contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Atoken-exchange")
+        );
+        assert!(requests[2].1.ends_with(":generateAccessToken"));
+        assert!(requests[2]
+            .2
+            .contains(&("Authorization".to_string(), "Bearer sts-token".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_with_file_source() {
+        let file =
+            std::env::temp_dir().join(format!("zg-test-subject-token-{}", std::process::id()));
+        std::fs::write(&file, "file-jwt\n").unwrap();
+        let transport = FakeTransport::new(vec![(200, r#"{"access_token": "sts-token"}"#)]);
+        let account = external_account(json!({ "file": file.to_str().unwrap() }), false);
+
+        let (token, lifetime) = exchange(&account, &transport).await.unwrap();
+        assert_eq!(token, "sts-token");
+        assert_eq!(lifetime, Duration::from_secs(3600));
+        assert!(transport.requests.borrow()[0]
+            .3
+            .as_deref()
+            .unwrap()
+            .contains("subject_token=file-jwt&"));
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exchange_errors_name_the_step() {
+        let transport = FakeTransport::new(vec![(400, r#"{"error": "invalid_grant"}"#)]);
+        let account = external_account(json!({ "file": "/nonexistent/zg-token" }), false);
+        let error = exchange(&account, &transport)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("reading the subject token (/nonexistent/zg-token) failed"),
+            "{}",
+            error
+        );
+
+        let account = external_account(json!({ "url": "https://token.example.com/" }), true);
+        let transport =
+            FakeTransport::new(vec![(200, "jwt"), (400, r#"{"error": "invalid_grant"}"#)]);
+        let error = exchange(&account, &transport)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(
+                "token exchange (https://sts.googleapis.com/v1/token) failed: status 400"
+            ),
+            "{}",
+            error
+        );
+
+        let transport = FakeTransport::new(vec![
+            (200, "jwt"),
+            (200, r#"{"access_token": "sts-token"}"#),
+            (
+                403,
+                "Permission 'iam.serviceAccounts.getAccessToken' denied",
+            ),
+        ]);
+        let error = exchange(&account, &transport)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("service account impersonation"), "{}", error);
+        assert!(error.contains("status 403"), "{}", error);
+    }
+
+    #[test]
+    fn test_check_universe() {
+        let mut account = external_account(json!({"file": "/tmp/token"}), false);
+        assert!(check_universe(&account, None).is_ok());
+        let error = check_universe(&account, Some("apis.example.com"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(
+                "for the universe domain 'googleapis.com', but requests go to 'apis.example.com'"
+            ),
+            "{}",
+            error
+        );
+
+        account.universe_domain = Some("apis.example.com".to_string());
+        assert!(check_universe(&account, Some("apis.example.com")).is_ok());
+        assert!(check_universe(&account, None).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_other_types() {
+        let file =
+            std::env::temp_dir().join(format!("zg-test-credentials-{}.json", std::process::id()));
+        std::fs::write(
+            &file,
+            r#"{"type": "service_account", "private_key": "..."}"#,
+        )
+        .unwrap();
+        let error = load(&file).unwrap_err().to_string();
+        assert!(
+            error.contains("type 'service_account' is not supported"),
+            "{}",
+            error
+        );
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -13,71 +13,665 @@
 // limitations under the License.
 
 use clap::Args;
-use log::debug;
+use log::{debug, warn};
 use regex::Regex;
+use serde::Serialize;
 use serde_json::{json, to_string_pretty, Value};
-use std::collections::HashMap;
-use std::{error::Error, panic};
+use std::collections::{BTreeMap, HashMap};
+use std::panic;
 use urlencoding::encode;
 
 use crate::discovery;
 
+use super::color;
+use super::config;
 use super::core;
+use super::error::ZgError;
 use super::flavors::desc_flavors as flavors;
+use super::json_schema;
+use super::supported_apis;
+use super::timing;
+use super::wrap;
 
+/// The service, resource, and method that `zg desc` and `zg open` point to.
 #[derive(Args, Debug)]
-pub struct DescArgs {
+pub struct TargetArgs {
     /// Required. Service that has the resource to execute a method (e.g., 'container').
     service: String,
 
-    /// A Resource to describe (e.g., 'clusters'). Supports resource_path to strictly point an unique resource (e.g., `locations.clusters`)
+    /// A Resource to describe (e.g., 'clusters'). Supports resource_path to strictly point an unique resource (e.g., `locations.clusters` or `locations/clusters`), or a method id (e.g., `container.projects.locations.clusters.get`) to describe the method.
     resource: Option<String>,
 
     /// A Method to describe (e.g., 'get).
     method: Option<String>,
+
+    /// Fail when the resource is ambiguous instead of picking one of the candidates.
+    #[arg(long, alias = "exact", overrides_with = "no_strict")]
+    strict: bool,
+
+    /// Pick one of the candidates of an ambiguous resource, even if `strict` is set in the config file.
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
+}
+
+impl TargetArgs {
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.strict = config::resolve_flag(self.strict, self.no_strict, config.desc.strict);
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DescArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Print the request body of the method as a JSON Schema (draft 2020-12) document, with the referenced schemas in `$defs`.
+    #[arg(long)]
+    json_schema: bool,
+
+    /// With --json-schema, leave out the read-only (output only) properties instead of marking them with `readOnly`.
+    #[arg(long, requires = "json_schema")]
+    writable_only: bool,
+
+    /// Print the service, resource, or method as it is in the original discovery document (downloaded if not saved yet),
+    /// e.g., to see attributes zg doesn't show (`mediaUpload`, `supportsMediaDownload`).
+    #[arg(long, conflicts_with = "json_schema")]
+    raw_discovery: bool,
+
+    /// Print a synthetic example of the response of the method, built from its response schema with placeholder
+    /// values: every field (including output-only ones), and one element per array. No request is sent.
+    #[arg(long, conflicts_with_all = ["json_schema", "raw_discovery"])]
+    response_example: bool,
+
+    /// Wrap long text (e.g., descriptions of query parameters and notes) at N columns instead of the terminal width.
+    /// The text isn't wrapped when the output is piped or in JSON or YAML.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(20..))]
+    width: Option<u16>,
+}
+
+impl DescArgs {
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.target.apply_config(config);
+    }
 }
 
 /// Main function to describe services, resources, or methods.
 /// standalone_api_key is only used for lazy loading (downloading) the API file through discovery url.
-pub async fn main(
+pub async fn main(args: &DescArgs, standalone_api_key: Option<String>) -> Result<(), ZgError> {
+    let description = result(args, standalone_api_key).await?;
+    let _span = timing::span("render");
+    let width = wrap::output_width(args.width.map(usize::from));
+    match &description {
+        Description::Service(service) => describe_service(service, width),
+        Description::Resource(resource) => describe_resource(resource),
+        Description::Method(method) => describe_method(method, width),
+        Description::JsonSchema(document) | Description::RawDiscovery(document) => {
+            println!("{}", to_string_pretty(document)?);
+            Ok(())
+        }
+        Description::ResponseExample(example) => {
+            println!(
+                "# Synthetic example built from the response schema {}, not a real response",
+                example.schema_name
+            );
+            println!("{}", to_string_pretty(&example.example)?);
+            Ok(())
+        }
+    }
+}
+
+/// Returns what `zg desc` describes, printed as text by `main`, or serialized for `--format json` and `--format yaml`.
+pub async fn result(
+    args: &DescArgs,
+    standalone_api_key: Option<String>,
+) -> Result<Description, ZgError> {
+    let api = core::load_api_file(&args.target.service, standalone_api_key.clone()).await?;
+    if args.raw_discovery {
+        return Ok(Description::RawDiscovery(
+            raw_discovery(&api, args, standalone_api_key).await?,
+        ));
+    }
+    let _span = timing::span("render");
+    if args.json_schema {
+        return Ok(Description::JsonSchema(request_json_schema(&api, args)?));
+    }
+    if args.response_example {
+        let (schema_name, example) = method_response_example(&api, args)?;
+        return Ok(Description::ResponseExample(ResponseExample {
+            schema_name,
+            example,
+        }));
+    }
+    Ok(match resolve_target(&api, &args.target)? {
+        Target::Service => Description::Service(Box::new(service_description(&api))),
+        Target::Resource(resource) => Description::Resource(resource_description(resource)),
+        Target::Method(method) => Description::Method(Box::new(method_description(&method, &api)?)),
+    })
+}
+
+/// The structured output of `zg desc`, with the same keys as the text output.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Description {
+    Service(Box<ServiceDescription>),
+    Resource(ResourceDescription),
+    Method(Box<MethodDescription>),
+    /// The JSON Schema document of the request body (`--json-schema`).
+    JsonSchema(Value),
+    /// The part of the original discovery document (`--raw-discovery`).
+    RawDiscovery(Value),
+    /// The synthetic example of the response (`--response-example`).
+    ResponseExample(ResponseExample),
+}
+
+/// A synthetic example of the response of a method, serialized as the example itself.
+pub struct ResponseExample {
+    schema_name: String,
+    example: Value,
+}
+
+impl Serialize for ResponseExample {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.example.serialize(serializer)
+    }
+}
+
+/// Returns the service, resource, or method given in the arguments as it is in the saved discovery document of the API
+/// (`--raw-discovery`). The document is downloaded if it's not saved.
+async fn raw_discovery(
+    api: &core::ZgApi,
     args: &DescArgs,
     standalone_api_key: Option<String>,
-) -> Result<(), Box<dyn Error>> {
-    let api = core::load_api_file(&args.service, standalone_api_key).await?;
+) -> Result<Value, ZgError> {
+    let path = core::discovery_document_path(&args.target.service, standalone_api_key).await?;
+    let read_error = |e: &dyn std::fmt::Display| {
+        ZgError::Other(format!(
+            "Failed to read the discovery document {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    let content = std::fs::read_to_string(&path).map_err(|e| read_error(&e))?;
+    let document: Value = serde_json::from_str(&content).map_err(|e| read_error(&e))?;
+    let target = resolve_target(api, &args.target)?;
+    raw_discovery_node(&document, &target).cloned()
+}
+
+/// Finds the node of the target in the discovery document. Methods are found by their ids in the document
+/// (`original_id` if the hierarchy was rebuilt), and resources by one of their methods, or by their paths if they have no methods.
+fn raw_discovery_node<'a>(document: &'a Value, target: &Target) -> Result<&'a Value, ZgError> {
+    let not_found = |what: String| {
+        ZgError::Other(format!(
+            "{} is not found in the discovery document; it may have changed since the API was imported. Run `zg update` to import it again.",
+            what
+        ))
+    };
+    match target {
+        Target::Service => Ok(document),
+        Target::Method(method) => {
+            let id = method.original_id.as_deref().unwrap_or(&method.id);
+            raw_resource_with_method(document, id)
+                .and_then(|resource| raw_method(resource, id))
+                .ok_or_else(|| not_found(format!("Method '{}'", id)))
+        }
+        Target::Resource(resource) => {
+            let path = resource.path.as_deref().unwrap_or(&resource.name);
+            let by_method = resource.methods.first().and_then(|method| {
+                raw_resource_with_method(
+                    document,
+                    method.original_id.as_deref().unwrap_or(&method.id),
+                )
+            });
+            by_method
+                .or_else(|| {
+                    // The path starts with the service name (e.g., `container.projects.locations`)
+                    path.split('.')
+                        .skip(1)
+                        .try_fold(document, |node, name| node.get("resources")?.get(name))
+                })
+                .ok_or_else(|| not_found(format!("Resource '{}'", path)))
+        }
+    }
+}
+
+/// Finds the resource that has the method of the id in `resources` of the node, recursively.
+fn raw_resource_with_method<'a>(node: &'a Value, method_id: &str) -> Option<&'a Value> {
+    node.get("resources")?
+        .as_object()?
+        .values()
+        .find_map(|resource| match raw_method(resource, method_id) {
+            Some(_) => Some(resource),
+            None => raw_resource_with_method(resource, method_id),
+        })
+}
+
+fn raw_method<'a>(resource: &'a Value, method_id: &str) -> Option<&'a Value> {
+    resource
+        .get("methods")?
+        .as_object()?
+        .values()
+        .find(|method| method.get("id").and_then(Value::as_str) == Some(method_id))
+}
+
+/// Returns the JSON Schema of the request body of the method given in the arguments (`--json-schema`).
+fn request_json_schema(api: &core::ZgApi, args: &DescArgs) -> Result<Value, ZgError> {
+    match resolve_target(api, &args.target)? {
+        Target::Method(method) => json_schema::request_schema(&method, api, args.writable_only),
+        _ => Err(ZgError::Other(
+            "--json-schema describes the request body of a method; specify RESOURCE and METHOD (or a method id)."
+                .to_string(),
+        )),
+    }
+}
+
+/// Returns the name of the response schema of the method given in the arguments and an example of it (`--response-example`).
+fn method_response_example(api: &core::ZgApi, args: &DescArgs) -> Result<(String, Value), ZgError> {
+    let Target::Method(method) = resolve_target(api, &args.target)? else {
+        return Err(ZgError::Other(
+            "--response-example describes the response of a method; specify RESOURCE and METHOD (or a method id)."
+                .to_string(),
+        ));
+    };
+    let Some(schema_name) = &method.response_schema else {
+        return Err(format!(
+            "No response schema for '{}': the method returns no body.",
+            method.id
+        )
+        .into());
+    };
+    let example = response_example(&method, schema_name, &api.schemas).ok_or_else(|| {
+        format!(
+            "The response schema '{}' is not in the API file",
+            schema_name
+        )
+    })?;
+    Ok((schema_name.clone(), example))
+}
+
+/// Builds an example of the response schema: every property (including read-only ones) with the placeholders of
+/// `minimum_data`, one element per array, and `$ref`s resolved. A schema nested in itself is replaced with a
+/// `<<recursive: NAME>>` string. None if the schema is not found.
+fn response_example(
+    method: &core::ZgMethod,
+    schema_name: &str,
+    schemas: &HashMap<String, discovery::Schema>,
+) -> Option<Value> {
+    let schema = schemas.get(schema_name)?;
+    let mut visiting = vec![schema_name.to_string()];
+    Some(object_example(method, schema, schemas, &mut visiting))
+}
+
+fn object_example(
+    method: &core::ZgMethod,
+    schema: &discovery::Schema,
+    schemas: &HashMap<String, discovery::Schema>,
+    visiting: &mut Vec<String>,
+) -> Value {
+    let mut object = serde_json::Map::new();
+    for (field, prop) in schema.properties.iter().flatten() {
+        object.insert(
+            field.clone(),
+            property_example(method, field, prop, schemas, visiting),
+        );
+    }
+    Value::Object(object)
+}
+
+fn property_example(
+    method: &core::ZgMethod,
+    field: &str,
+    prop: &discovery::SchemaProperty,
+    schemas: &HashMap<String, discovery::Schema>,
+    visiting: &mut Vec<String>,
+) -> Value {
+    match prop.prop_type.as_deref() {
+        // The first value is as representative as any for enums
+        Some("string") => match prop.enum_values.as_ref().and_then(|values| values.first()) {
+            Some(value) => Value::String(value.clone()),
+            None => Value::String(string_placeholder(method, field, prop)),
+        },
+        Some("number") => json!(0.0),
+        Some("array") => match &prop.items {
+            Some(items) => json!([schema_example(method, field, items, schemas, visiting)]),
+            None => json!([]),
+        },
+        Some("object") => match (&prop.additional_properties, &prop.properties) {
+            (Some(value_prop), _) => {
+                json!({ "key": property_example(method, "", value_prop, schemas, visiting) })
+            }
+            (None, Some(properties)) => Value::Object(
+                properties
+                    .iter()
+                    .map(|(name, schema)| {
+                        let value = schema_example(method, name, schema, schemas, visiting);
+                        (name.clone(), value)
+                    })
+                    .collect(),
+            ),
+            (None, None) => json!({}),
+        },
+        None => match &prop.ref_name {
+            Some(ref_name) if visiting.contains(ref_name) => {
+                Value::String(format!("<<recursive: {}>>", ref_name))
+            }
+            Some(ref_name) => match schemas.get(ref_name) {
+                Some(nested) => {
+                    visiting.push(ref_name.clone());
+                    let value = object_example(method, nested, schemas, visiting);
+                    visiting.pop();
+                    value
+                }
+                None => Value::String("<<See API Reference for details>>".to_string()),
+            },
+            None => Value::String("<<See API Reference for details>>".to_string()),
+        },
+        // integer, boolean, and any
+        _ => property_placeholder(method, field, prop, schemas),
+    }
+}
+
+/// The example of a schema describing array items or an inline property (e.g., `{"$ref": "Disk"}`).
+fn schema_example(
+    method: &core::ZgMethod,
+    field: &str,
+    schema: &discovery::Schema,
+    schemas: &HashMap<String, discovery::Schema>,
+    visiting: &mut Vec<String>,
+) -> Value {
+    if schema.properties.is_some() {
+        return object_example(method, schema, schemas, visiting);
+    }
+    let prop = discovery::SchemaProperty {
+        description: schema.description.clone(),
+        prop_type: schema.schema_type.clone(),
+        format: schema.format.clone(),
+        items: schema.items.clone(),
+        properties: None,
+        ref_name: schema.ref_name.clone(),
+        read_only: false,
+        annotations: None,
+        additional_properties: None,
+        deprecated: false,
+        enum_values: schema.enum_values.clone(),
+    };
+    property_example(method, field, &prop, schemas, visiting)
+}
+
+/// The structured output of `zg desc SERVICE`.
+#[derive(Serialize)]
+pub struct ServiceDescription {
+    service: String,
+    version: String,
+    revision: String,
+    base_url: String,
+    category: Option<&'static str>,
+    status: Option<String>,
+    regional_endpoint: Option<String>,
+    notes: Option<String>,
+    preferred_version: Option<String>,
+    top_level_resources: Vec<String>,
+    common_parameters: Vec<String>,
+    /// OAuth scopes of the API with their descriptions.
+    scopes: BTreeMap<String, String>,
+    // Details only the text output shows
+    #[serde(skip)]
+    common_parameter_types: Vec<Option<String>>,
+    #[serde(skip)]
+    preferred_version_hint: Option<String>,
+}
+
+fn service_description(api: &core::ZgApi) -> ServiceDescription {
+    let service_name = api.id.split(':').next().unwrap_or_default();
+    let supported = supported_apis::supported_apis(true)
+        .into_iter()
+        .find(|s| s.name == service_name);
+    let items = discovery::cached_discovered_apis()
+        .map(|directory| directory.items)
+        .unwrap_or_default();
+    ServiceDescription {
+        service: api.name.clone(),
+        version: api.version.clone(),
+        revision: api.revision.clone(),
+        base_url: api.base_url.clone(),
+        category: supported.as_ref().map(|s| s.category.as_str()),
+        status: supported.as_ref().map(|s| s.status.to_string()),
+        regional_endpoint: supported.as_ref().and_then(|s| s.endpoint_template.clone()),
+        notes: supported.as_ref().and_then(|s| s.notes.clone()),
+        preferred_version: supported
+            .as_ref()
+            .and_then(|s| preferred_version(s, &items))
+            .map(|item| item.version.clone()),
+        top_level_resources: api.resources.iter().map(|r| r.name.clone()).collect(),
+        common_parameters: api.common_params.iter().map(|p| p.name.clone()).collect(),
+        scopes: api.scopes.clone(),
+        common_parameter_types: api
+            .common_params
+            .iter()
+            .map(|p| p.param_type.clone())
+            .collect(),
+        preferred_version_hint: supported
+            .as_ref()
+            .and_then(|s| preferred_version_hint(s, &items)),
+    }
+}
+
+/// The structured output of `zg desc SERVICE RESOURCE`.
+#[derive(Serialize)]
+pub struct ResourceDescription {
+    resource_name: String,
+    resource_path: Option<String>,
+    parent_path: Option<String>,
+    methods: Vec<String>,
+    child_resources: Vec<String>,
+}
+
+fn resource_description(resource: &core::ZgResource) -> ResourceDescription {
+    ResourceDescription {
+        resource_name: resource.name.clone(),
+        resource_path: resource.path.clone(),
+        parent_path: resource.parent_path.clone(),
+        methods: resource.methods.iter().map(|m| m.name.clone()).collect(),
+        child_resources: resource
+            .resources
+            .iter()
+            .flatten()
+            .map(|r| r.name.clone())
+            .collect(),
+    }
+}
+
+/// The structured output of `zg desc SERVICE RESOURCE METHOD`.
+#[derive(Serialize)]
+pub struct MethodDescription {
+    method_name: String,
+    method_id: String,
+    original_method_id: Option<String>,
+    http_method: String,
+    request_url: String,
+    autofill_params: Vec<String>,
+    /// Path parameters that take the positional values of `zg exec` after METHOD, in order.
+    positional_params: Vec<String>,
+    /// Method.parameterOrder of the discovery document.
+    parameter_order: Vec<String>,
+    required_params: Vec<String>,
+    query_params: Vec<core::ZgQueryParam>,
+    /// Query parameters `zg exec` adds from `[defaults]` of the config file, as `name=value`.
+    default_params: Vec<String>,
+    /// The suggested request data; a string with the patterns and notes for methods with flavors.
+    minimum_data: Option<Value>,
+    documentation_link: Option<String>,
+    /// OAuth scopes of the method, any of which authorizes it.
+    scopes: Vec<String>,
+    // Details only the text output shows
+    #[serde(skip)]
+    undescribed_scopes: Vec<String>,
+    #[serde(skip)]
+    api_id: String,
+}
+
+fn method_description(
+    method: &core::ZgMethod,
+    api: &core::ZgApi,
+) -> Result<MethodDescription, ZgError> {
+    let mut query_params = method.query_params.clone();
+    query_params.sort_by(|a, b| a.name.cmp(&b.name));
+    let minimum_data = match method.has_request_body() {
+        true => Some(minimum_data(method, api)?),
+        false => None,
+    };
+    Ok(MethodDescription {
+        method_name: method.name.clone(),
+        method_id: method.id.clone(),
+        original_method_id: method.original_id.clone(),
+        http_method: method.http_method.clone(),
+        request_url: request_url(method, api),
+        autofill_params: autofill_params(method),
+        positional_params: method
+            .positional_params()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        parameter_order: method.parameter_order.clone(),
+        required_params: required_params(method)?,
+        query_params,
+        default_params: default_params(method, api),
+        minimum_data,
+        documentation_link: documentation_link(api, &Target::Method(Box::new(method.clone())), &[]),
+        scopes: method.scopes.clone(),
+        undescribed_scopes: undescribed_scopes(method, api)
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        api_id: api.id.clone(),
+    })
+}
+
+/// What the arguments of `zg desc` (and `zg open`) point to.
+pub enum Target<'a> {
+    Service,
+    Resource(&'a core::ZgResource),
+    Method(Box<core::ZgMethod>),
+}
+
+/// Resolves the resource and the method given in the arguments.
+pub fn resolve_target<'a>(api: &'a core::ZgApi, args: &TargetArgs) -> Result<Target<'a>, ZgError> {
     match (&args.resource, &args.method) {
-        (None, None) => describe_service(&api),
+        (None, None) => Ok(Target::Service),
         (Some(resource_path), None) => {
-            let resource = core::find_resource(&api.id, &api.resources, resource_path)?;
-            describe_resource(resource)
+            match core::find_api_resource(api, resource_path, args.strict) {
+                Ok(resource) => Ok(Target::Resource(resource)),
+                // Not a resource; try as a method id (e.g., `container.projects.locations.clusters.get`)
+                Err(e) => match core::find_method_by_id(api, resource_path, args.strict) {
+                    Ok((_, method)) => Ok(Target::Method(Box::new(method))),
+                    Err(mismatch @ ZgError::ServiceMismatch { .. }) => Err(mismatch),
+                    Err(_) => Err(e),
+                },
+            }
         }
         (Some(resource_path), Some(method_name)) => {
-            let resource = core::find_resource(&api.id, &api.resources, resource_path)?;
-            let method = core::find_method(resource, method_name)?;
-            describe_method(&method, &api)
+            let resource = core::find_api_resource(api, resource_path, args.strict)?;
+            Ok(Target::Method(Box::new(core::find_method(
+                resource,
+                method_name,
+            )?)))
         }
         (None, Some(_)) => panic!("Fatal: Method cannot be specified without a resource."),
     }
 }
 
 /// Describes the service. Prints only the top-level resources (ignore nested resources).
-fn describe_service(api: &core::ZgApi) -> Result<(), Box<dyn Error>> {
-    println!("service: {}", &api.name);
-    println!("version: {}", &api.version);
-    println!("revision: {}", &api.revision);
-    println!("base_url: {}", api.base_url);
+fn describe_service(service: &ServiceDescription, width: Option<usize>) -> Result<(), ZgError> {
+    println!("service: {}", service.service);
+    println!("version: {}", service.version);
+    println!("revision: {}", service.revision);
+    println!("base_url: {}", service.base_url);
+    if let Some(category) = service.category {
+        println!("category: {}", category);
+    }
+    if let Some(status) = &service.status {
+        println!("status: {}", status);
+    }
+    if let Some(template) = &service.regional_endpoint {
+        println!("regional_endpoint: {} (for non-global locations)", template);
+    }
+    if let Some(notes) = &service.notes {
+        println!("{}", wrap::wrap(notes, width, "notes: ", 7).join("\n"));
+    }
+    if let Some(hint) = &service.preferred_version_hint {
+        println!("{}", hint);
+    }
     println!("top_level_resources:");
-    for resource in &api.resources {
-        println!("- {}", resource.name);
+    for resource in &service.top_level_resources {
+        println!("- {}", resource);
+    }
+    if !service.common_parameters.is_empty() {
+        println!("common_parameters:");
+        for (name, param_type) in service
+            .common_parameters
+            .iter()
+            .zip(&service.common_parameter_types)
+        {
+            match param_type {
+                Some(param_type) => println!("- {} ({})", name, param_type),
+                None => println!("- {}", name),
+            }
+        }
+    }
+    if !service.scopes.is_empty() {
+        println!("scopes:");
+        for (scope, description) in &service.scopes {
+            let item = format!("- {}", scope);
+            match description.is_empty() {
+                true => println!("{}", item),
+                false => println!(
+                    "{}",
+                    wrap::wrap(description, width, &format!("{}: ", item), 4).join("\n")
+                ),
+            }
+        }
     }
     Ok(())
 }
 
+/// Returns a line telling the preferred version in the discovery directory, only when it differs from zg's default version.
+/// zg doesn't follow the preferred flag (see `DiscoveryDirectoryItem`), so this is just for information.
+fn preferred_version_hint(
+    supported: &supported_apis::SupportedApi,
+    items: &[discovery::DiscoveryDirectoryItem],
+) -> Option<String> {
+    preferred_version(supported, items).map(|preferred| {
+        format!(
+            "preferred_version: {} (marked as preferred by Google; zg uses {} by default. Try `zg desc {}`)",
+            preferred.version,
+            supported.default_version(),
+            preferred.id
+        )
+    })
+}
+
+/// Returns the version marked as preferred in the discovery directory, only when it differs from zg's default version.
+fn preferred_version<'a>(
+    supported: &supported_apis::SupportedApi,
+    items: &'a [discovery::DiscoveryDirectoryItem],
+) -> Option<&'a discovery::DiscoveryDirectoryItem> {
+    items
+        .iter()
+        .find(|item| item.name == supported.name && item.preferred)
+        .filter(|preferred| preferred.version != supported.default_version())
+}
+
 /// Describes the resource. Prints the direct children resources and methods (ignores nested resources).
-fn describe_resource(resource: &core::ZgResource) -> Result<(), Box<dyn Error>> {
-    println!("resource_name: {}", resource.name);
+fn describe_resource(resource: &ResourceDescription) -> Result<(), ZgError> {
+    println!("resource_name: {}", resource.resource_name);
     println!(
         "resource_path: {}",
-        resource.path.as_deref().unwrap_or("N/A")
+        resource.resource_path.as_deref().unwrap_or("N/A")
     );
     println!(
         "parent_path: {}",
@@ -86,47 +680,119 @@ fn describe_resource(resource: &core::ZgResource) -> Result<(), Box<dyn Error>>
     if !resource.methods.is_empty() {
         println!("methods:");
         for method in &resource.methods {
-            println!("- {}", method.name);
+            println!("- {}", method);
         }
     }
-    if let Some(children) = &resource.resources {
-        if !children.is_empty() {
-            println!("\nchild_resources:");
-            for child in resource.resources.as_ref().unwrap() {
-                println!("- {}", child.name);
-            }
+    if !resource.child_resources.is_empty() {
+        println!("\nchild_resources:");
+        for child in &resource.child_resources {
+            println!("- {}", child);
         }
     }
     Ok(())
 }
 
 /// Describes the method. Prints information useful for executing the method.
-fn describe_method(method: &core::ZgMethod, api: &core::ZgApi) -> Result<(), Box<dyn Error>> {
-    println!("method_name: {}", method.name);
-    println!("method_id: {}", method.id);
-    if let Some(original_id) = &method.original_id {
+fn describe_method(method: &MethodDescription, width: Option<usize>) -> Result<(), ZgError> {
+    println!("method_name: {}", method.method_name);
+    println!("method_id: {}", method.method_id);
+    if let Some(original_id) = &method.original_method_id {
         println!("original_method_id: {}", original_id);
     }
-    println!("http_method: {}", method.http_method);
-    println!("request_url: {}{}", &api.base_url, method.flat_path);
-    println!("autofill_params: {}", autofill_params(method).join(", "));
+    println!(
+        "http_method: {}",
+        match color::http_method_style(&method.http_method) {
+            Some(style) => color::style(&method.http_method, style),
+            None => method.http_method.clone(),
+        }
+    );
+    println!("request_url: {}", method.request_url);
+    println!("autofill_params: {}", method.autofill_params.join(", "));
+    println!(
+        "positional_params: {}",
+        match method.positional_params.is_empty() {
+            true => "None".to_string(),
+            false => method.positional_params.join(", "),
+        }
+    );
+    if !method.parameter_order.is_empty() {
+        println!("parameter_order: {}", method.parameter_order.join(", "));
+    }
 
-    let required_params = build_required_params_string(method)?;
-    println!("\nrequired_params: {}", required_params);
+    println!(
+        "\nrequired_params: {}",
+        build_required_params_string(&method.required_params)
+    );
 
-    // Only show suggested minimum data for non-GET/DELETE methods
-    if !["GET", "DELETE"].contains(&method.http_method.as_str()) {
-        println!("{}", payload_suggestion(method, api)?);
+    if !method.query_params.is_empty() {
+        println!("\nquery_params:");
+        for line in query_param_lines(&method.query_params, width) {
+            println!("{}", line);
+        }
+    }
+
+    if !method.default_params.is_empty() {
+        println!(
+            "\ndefault_params (config file): {}",
+            method.default_params.join(" ")
+        );
+    }
+
+    if !method.scopes.is_empty() {
+        println!("\nscopes (any of):");
+        for scope in &method.scopes {
+            match method.undescribed_scopes.contains(scope) {
+                true => println!("- {} (not described by the API)", scope),
+                false => println!("- {}", scope),
+            }
+        }
+        if !method.undescribed_scopes.is_empty() {
+            warn!(
+                "{} lists OAuth scopes not described in the auth block of {}: {}",
+                method.method_id,
+                method.api_id,
+                method.undescribed_scopes.join(", ")
+            );
+        }
+    }
+
+    // Only show suggested minimum data for methods with a request body
+    if let Some(minimum_data) = &method.minimum_data {
+        println!(
+            "{}",
+            wrap::wrap_list_items(&payload_suggestion(minimum_data)?, width)
+        );
     }
 
     // Generate and display the document search result URL
-    if let Some(doc_url) = generate_documentation_link(&method.id) {
+    if let Some(doc_url) = &method.documentation_link {
         println!("\nFind API Reference: {}", doc_url);
     }
 
     Ok(())
 }
 
+/// The URL of the method with the placeholders, in the universe domain of the config file if any.
+fn request_url(method: &core::ZgMethod, api: &core::ZgApi) -> String {
+    let base_url = supported_apis::effective_base_url(api, &method.flat_path);
+    let base_url = match config::current().universe_domain() {
+        Some(domain) => supported_apis::in_universe(&base_url, domain),
+        None => base_url,
+    };
+    core::join_path(&base_url, &method.flat_path)
+}
+
+/// Returns the query parameters that `zg exec` adds from the config file (`[defaults.params]`), as `name=value`.
+fn default_params(method: &core::ZgMethod, api: &core::ZgApi) -> Vec<String> {
+    let service_name = api.id.split(':').next().unwrap_or_default();
+    config::current()
+        .defaults
+        .params(service_name, method)
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect()
+}
+
 /// Extracts the placeholders that will be autofilled in `zg exec`.
 fn autofill_params(method: &core::ZgMethod) -> Vec<String> {
     // Extract all placeholders from the flat_path
@@ -137,23 +803,15 @@ fn autofill_params(method: &core::ZgMethod) -> Vec<String> {
         .map(|m| m.as_str().to_string())
         .collect();
 
-    // Combine all placeholders that will be autofilled
-    let autofill_placeholders: Vec<&str> = core::PATH_PLACEHOLDERS_PROJECT
-        .iter()
-        .chain(core::PATH_PLACEHOLDERS_REGION.iter())
-        .chain(core::PATH_PLACEHOLDERS_ZONE.iter())
-        .cloned()
-        .collect();
-
     // Filter placeholders that will be autofilled in `zg exec`
     placeholders
         .into_iter()
-        .filter(|param| autofill_placeholders.contains(&param.as_str()))
+        .filter(|param| core::is_autofill_placeholder(param))
         .collect()
 }
 
-/// Builds the required parameters string.
-fn build_required_params_string(method: &core::ZgMethod) -> Result<String, Box<dyn Error>> {
+/// Collects the required path parameters not autofilled, and the required query parameters.
+fn required_params(method: &core::ZgMethod) -> Result<Vec<String>, ZgError> {
     let re = Regex::new(r"\{([^}]+)\}")?;
 
     // Collect required "path" params
@@ -172,42 +830,106 @@ fn build_required_params_string(method: &core::ZgMethod) -> Result<String, Box<d
         .map(|qp| qp.name.as_str())
         .collect();
     required_params.extend(required_query_params);
+    Ok(required_params.into_iter().map(String::from).collect())
+}
 
+/// Builds the required parameters string.
+fn build_required_params_string(required_params: &[String]) -> String {
     if required_params.is_empty() {
-        Ok("None".to_string())
+        "None".to_string()
     } else {
         let params_line = required_params
             .iter()
             .map(|param| format!("-p {}=\"\"", param))
             .collect::<Vec<String>>()
             .join(" ");
-        Ok(format!("\n{}", params_line))
+        format!("\n{}", params_line)
     }
 }
 
-/// Generates a suggestion for the minimum request data to be sent with the method.
-fn payload_suggestion(
-    method: &core::ZgMethod,
-    api: &core::ZgApi,
-) -> Result<String, Box<dyn Error>> {
-    match method.id.as_str() {
-        "bigquery.projects.jobs.insert" => flavors::bigquery_jobs_insert(),
-        "sqladmin.projects.instances.insert" => flavors::sqladmin_instances_insert(),
-        "container.projects.locations.clusters.create"
-        | "container.projects.zones.clusters.create" => flavors::container_clusters_create(),
-        _ => {
-            // When no flavored logic is defined for the method, builds the suggested minimum request data string,
-            // by generating a JSON template with placeholder values for required fields.
-            let request_data_schema = match &method.request_data_schema {
-                Some(s) => s,
-                None => return Ok("\nminimum_data:\n--data '{}'".to_string()), // Doc says "The request body must be empty"
-            };
-
-            let data = minimum_data_suggestion(method, request_data_schema, &api.schemas);
-            let output = format!("\nminimum_data:\n--data '{}'", to_string_pretty(&data)?);
-
-            Ok(output)
-        }
+/// Lists the query parameters (sorted by name by `method_description`), with markers of required and repeated (`-p` can be given multiple times)
+/// and their descriptions, wrapped at `width` with the continuation lines under the description.
+fn query_param_lines(query_params: &[core::ZgQueryParam], width: Option<usize>) -> Vec<String> {
+    query_params
+        .iter()
+        .flat_map(|qp| {
+            let mut item = format!("- {}", qp.name);
+            if qp.required {
+                item.push_str(" (required)");
+            }
+            if qp.repeated {
+                item.push_str(" (repeated)");
+            }
+            match qp.description.as_deref().filter(|d| !d.trim().is_empty()) {
+                Some(description) => {
+                    let prefix = format!("{}: ", item);
+                    wrap::wrap(description, width, &prefix, prefix.chars().count())
+                }
+                None => vec![item],
+            }
+        })
+        .collect()
+}
+
+/// Returns the scopes of the method missing from the scopes of the API (auth.oauth2.scopes), which may be a mistake in
+/// the discovery document. None are reported if the API describes no scopes at all (e.g., its auth block is missing).
+fn undescribed_scopes<'a>(method: &'a core::ZgMethod, api: &core::ZgApi) -> Vec<&'a str> {
+    if api.scopes.is_empty() {
+        return vec![];
+    }
+    method
+        .scopes
+        .iter()
+        .filter(|scope| !api.scopes.contains_key(*scope))
+        .map(String::as_str)
+        .collect()
+}
+
+/// Returns the minimum request data of a method with the notes, as `zg desc` prints it.
+type PayloadFlavor = fn() -> Result<String, ZgError>;
+
+/// Flavors suggesting the minimum request data of the methods, by method id.
+const PAYLOAD_FLAVORS: &[(&str, PayloadFlavor)] = &[
+    (
+        "bigquery.projects.jobs.insert",
+        flavors::bigquery_jobs_insert,
+    ),
+    (
+        "sqladmin.projects.instances.insert",
+        flavors::sqladmin_instances_insert,
+    ),
+    (
+        "container.projects.locations.clusters.create",
+        flavors::container_clusters_create,
+    ),
+    (
+        "container.projects.zones.clusters.create",
+        flavors::container_clusters_create,
+    ),
+];
+
+/// Suggests the minimum request data to be sent with the method: the patterns and notes of its flavor as a string if
+/// any, otherwise a JSON template with placeholder values for required fields.
+fn minimum_data(method: &core::ZgMethod, api: &core::ZgApi) -> Result<Value, ZgError> {
+    if let Some((_, flavor)) = PAYLOAD_FLAVORS.iter().find(|(id, _)| *id == method.id) {
+        let patterns = flavor()?;
+        let patterns = patterns.trim().trim_start_matches("minimum_data:");
+        return Ok(Value::String(patterns.trim_start().to_string()));
+    }
+    Ok(match &method.request_data_schema {
+        Some(schema) => minimum_data_suggestion(method, schema, &api.schemas),
+        None => json!({}), // Doc says "The request body must be empty"
+    })
+}
+
+/// Renders the suggested minimum request data for the text output.
+fn payload_suggestion(minimum_data: &Value) -> Result<String, ZgError> {
+    match minimum_data {
+        Value::String(patterns) => Ok(format!("\nminimum_data:\n{}", patterns)),
+        data => Ok(format!(
+            "\nminimum_data:\n--data '{}'",
+            to_string_pretty(data)?
+        )),
     }
 }
 
@@ -224,47 +946,155 @@ fn minimum_data_suggestion(
     };
 
     let mut min_data = serde_json::Map::new();
-    let unsupported_msg = Value::String("<<See API Reference for details>>".to_string());
 
     // Iterate over the properties and add placeholder values to build template JSON
     for (field, prop) in properties.iter() {
-        if !is_required(method, field, prop, properties.len() == 1) {
+        if !is_required(method, schema, field, prop) {
             continue;
         }
 
-        let placeholder_value = match prop.prop_type.as_deref() {
-            Some("string") => Value::String("".to_string()),
-            Some("integer") => Value::Number(0.into()),
-            Some("boolean") => Value::Bool(false),
-            Some(_) => unsupported_msg.clone(),
-            None => match &prop.ref_name {
-                None => unsupported_msg.clone(), // no prop_type and no "$ref (ref_name)" - expect not to happen
-                // no prop_type but Some(ref_name); try to recursively resolve the nested schema
-                Some(ref_name) => match schemas.get(ref_name) {
-                    None => unsupported_msg.clone(),
-                    Some(nested_schema) => minimum_data_suggestion(method, nested_schema, schemas),
-                },
-            },
-        };
+        let placeholder_value = property_placeholder(method, field, prop, schemas);
         min_data.insert(field.clone(), placeholder_value);
     }
 
     serde_json::Value::Object(min_data)
 }
 
-/// Determines if a property is required based on its description and annotations.
+/// Returns a placeholder value for the property (`field` in its schema), resolving nested schemas and map values where necessary.
+fn property_placeholder(
+    method: &core::ZgMethod,
+    field: &str,
+    prop: &discovery::SchemaProperty,
+    schemas: &HashMap<String, discovery::Schema>,
+) -> Value {
+    let unsupported_msg = Value::String("<<See API Reference for details>>".to_string());
+    match prop.prop_type.as_deref() {
+        Some("string") => Value::String(string_placeholder(method, field, prop)),
+        Some("integer") => Value::Number(0.into()),
+        Some("boolean") => Value::Bool(false),
+        // Map (e.g., labels): a single entry with the placeholder of the value type
+        Some("object") => match &prop.additional_properties {
+            Some(value_prop) => {
+                json!({ "key": property_placeholder(method, "", value_prop, schemas) })
+            }
+            None => unsupported_msg,
+        },
+        Some(_) => unsupported_msg,
+        None => match &prop.ref_name {
+            None => unsupported_msg, // no prop_type and no "$ref (ref_name)" - expect not to happen
+            // no prop_type but Some(ref_name); try to recursively resolve the nested schema
+            Some(ref_name) => match schemas.get(ref_name) {
+                None => unsupported_msg,
+                Some(nested_schema) => minimum_data_suggestion(method, nested_schema, schemas),
+            },
+        },
+    }
+}
+
+/// Returns an example of the string property by its format (e.g., `3.5s` for durations) or, for resource names
+/// (`name` and `parent` described with their format or as resource names), a skeleton like `projects/PROJECT/locations/LOCATION`
+/// from the method's flat_path. Empty for other strings, including enums, whose values are listed in the API reference.
+fn string_placeholder(
+    method: &core::ZgMethod,
+    field: &str,
+    prop: &discovery::SchemaProperty,
+) -> String {
+    if prop.enum_values.is_some() {
+        return String::new();
+    }
+    match prop.format.as_deref() {
+        Some("google-duration") => return "3.5s".to_string(),
+        Some("int64" | "uint64") => return "0".to_string(),
+        Some("byte") => return "<base64>".to_string(),
+        Some("google-datetime") => return "2025-01-31T09:00:00Z".to_string(),
+        _ => {}
+    }
+    let describes_resource_name = prop.description.as_deref().is_some_and(|description| {
+        let description = description.to_lowercase();
+        description.contains("resource name") || description.contains("projects/")
+    });
+    match field {
+        "parent" | "name" if describes_resource_name => {
+            resource_name_skeleton(&method.flat_path, field == "parent").unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Builds the resource name in the flat_path (the part from the first collection followed by a placeholder, e.g.,
+/// `projects/{projectsId}/locations/{locationsId}/clusters`) with the placeholders in upper snake case
+/// (`projects/PROJECT/locations/LOCATION/clusters/CLUSTER`), and a placeholder added for a trailing collection.
+/// With `parent`, the trailing collection is dropped instead. None if the path has no placeholders.
+fn resource_name_skeleton(flat_path: &str, parent: bool) -> Option<String> {
+    let path = flat_path.split(':').next().unwrap_or(flat_path);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let start = segments
+        .windows(2)
+        .position(|pair| pair[1].starts_with('{'))?;
+    let mut skeleton: Vec<String> = Vec::new();
+    let mut collection = "";
+    for segment in &segments[start..] {
+        if segment.starts_with('{') {
+            skeleton.push(placeholder_name(collection));
+        } else {
+            collection = segment;
+            skeleton.push(segment.to_string());
+        }
+    }
+    let ends_with_collection = !segments.last()?.starts_with('{');
+    match (ends_with_collection, parent) {
+        (true, true) => {
+            skeleton.pop();
+        }
+        (true, false) => skeleton.push(placeholder_name(collection)),
+        // The method's own resource; its parent is the part before the last collection
+        (false, true) => skeleton.truncate(skeleton.len().saturating_sub(2)),
+        (false, false) => {}
+    }
+    (!skeleton.is_empty()).then(|| skeleton.join("/"))
+}
+
+/// The placeholder for the id in a collection: singular in upper snake case (e.g., `NODE_POOL` for `nodePools`).
+fn placeholder_name(collection: &str) -> String {
+    let singular = if let Some(stem) = collection.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if let Some(stem) = collection.strip_suffix("sses") {
+        format!("{}ss", stem)
+    } else {
+        collection
+            .strip_suffix('s')
+            .unwrap_or(collection)
+            .to_string()
+    };
+    let mut name = String::new();
+    for c in singular.chars() {
+        if c.is_ascii_uppercase() && !name.is_empty() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+/// Determines if a property is required based on the schema's `required` array, its description, and annotations.
 /// If the property is read-only, it is not considered required as users don't send it to call the API.
 fn is_required(
     method: &core::ZgMethod,
+    schema: &discovery::Schema,
     field: &String,
     prop: &discovery::SchemaProperty,
-    is_only_prop: bool,
 ) -> bool {
     if prop.read_only {
         return false;
     }
     debug_property(field, prop);
 
+    // Required if the schema lists the property in its `required` array
+    if schema.is_property_required(field) {
+        return true;
+    }
+    let is_only_prop = schema.properties.as_ref().is_some_and(|p| p.len() == 1);
+
     // If the description suggests the property is optional. Don't immediately return false, rather support other conditions.
     let desc_indicates_optional = prop.description.as_deref().is_some_and(|desc| {
         let desc_lower = desc.to_lowercase();
@@ -297,6 +1127,66 @@ fn is_required(
     (desc_indicates_requirement || annotated_as_required) && !desc_indicates_optional
 }
 
+/// Returns the link to the API reference of the target (also opened by `zg open`): the documentationLink of the API
+/// file or in `directory_items` for the service, or a search result page for resources and methods.
+pub fn documentation_link(
+    api: &core::ZgApi,
+    target: &Target,
+    directory_items: &[discovery::DiscoveryDirectoryItem],
+) -> Option<String> {
+    let service_name = api.id.split(':').next().unwrap_or_default();
+    let workspace = supported_apis::is_workspace_api(service_name);
+    match target {
+        Target::Service => api
+            .documentation_link
+            .clone()
+            .or_else(|| {
+                directory_items
+                    .iter()
+                    .find(|item| item.id == api.id)
+                    .and_then(|item| item.documentation_link.clone())
+            })
+            .or_else(|| {
+                Some(match workspace {
+                    true => search_link(None, service_name),
+                    false => search_link(Some(service_name), "REST reference"),
+                })
+            }),
+        Target::Resource(resource) => {
+            // e.g., "container.projects.locations.clusters"; Workspace paths start with the product (e.g., "directory.users")
+            let (product, resource_path) = resource.path.as_deref()?.split_once('.')?;
+            Some(match workspace {
+                true => search_link(
+                    None,
+                    &format!("\"REST Resource:\" {} {}", product, resource_path),
+                ),
+                false => search_link(
+                    Some(product),
+                    &format!("\"REST Resource:\" {}", resource_path),
+                ),
+            })
+        }
+        Target::Method(method) if workspace => generate_workspace_documentation_link(&method.id),
+        Target::Method(method) => generate_documentation_link(&method.id),
+    }
+}
+
+/// Returns the URL of the documentation search results: the Google Cloud docs of the service,
+/// or the Workspace docs (developers.google.com) if no service is given.
+fn search_link(cloud_service: Option<&str>, query: &str) -> String {
+    match cloud_service {
+        Some(service_name) => format!(
+            "https://cloud.google.com/s/results/{}/docs?q={}",
+            service_name,
+            encode(query)
+        ),
+        None => format!(
+            "https://developers.google.com/s/results/workspace?q={}",
+            encode(query)
+        ),
+    }
+}
+
 /// Generates a link to the method documentation (in reality, a search result page).
 fn generate_documentation_link(method_id: &str) -> Option<String> {
     let parts: Vec<&str> = method_id.split('.').collect();
@@ -308,13 +1198,22 @@ fn generate_documentation_link(method_id: &str) -> Option<String> {
     };
 
     let search_query = format!("\"Method:\" {} {}", resource_path, method_name);
-    let encoded_query = encode(&search_query);
-    let url = format!(
-        "https://cloud.google.com/s/results/{}/docs?q={}",
-        service_name, encoded_query
-    );
+    Some(search_link(Some(service_name), &search_query))
+}
+
+/// Generates a link to the method documentation of Google Workspace APIs, which are hosted in developers.google.com.
+/// Method ids of Workspace APIs don't always start with the service name (e.g., "directory.users.list" in admin:directory_v1).
+fn generate_workspace_documentation_link(method_id: &str) -> Option<String> {
+    let parts: Vec<&str> = method_id.split('.').collect();
+    let (product, resource_path, method_name) = match parts.as_slice() {
+        [product, resource @ .., method_name] if !resource.is_empty() => {
+            (product, resource.join("."), method_name)
+        }
+        _ => return None,
+    };
 
-    Some(url)
+    let search_query = format!("\"Method:\" {} {} {}", product, resource_path, method_name);
+    Some(search_link(None, &search_query))
 }
 
 fn debug_property(field: &String, prop: &discovery::SchemaProperty) {
@@ -344,9 +1243,54 @@ mod tests {
             ..core::ZgMethod::testdata()
         };
 
-        let result = build_required_params_string(&method);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "\n-p param1=\"\"");
+        let required_params = required_params(&method).unwrap();
+        assert_eq!(
+            build_required_params_string(&required_params),
+            "\n-p param1=\"\""
+        );
+        assert_eq!(build_required_params_string(&[]), "None");
+    }
+
+    #[test]
+    fn test_query_param_lines() {
+        let query_param = |name: &str, required: bool, repeated: bool| core::ZgQueryParam {
+            name: name.to_string(),
+            required,
+            repeated,
+            ..Default::default()
+        };
+        let method = core::ZgMethod {
+            query_params: vec![
+                query_param("pageSize", false, false),
+                query_param("fields", false, true),
+                query_param("query", true, false),
+            ],
+            ..core::ZgMethod::testdata()
+        };
+        // Sorted by name in the description
+        let description = method_description(&method, &core::ZgApi::testdata()).unwrap();
+        assert_eq!(
+            query_param_lines(&description.query_params, None),
+            vec!["- fields (repeated)", "- pageSize", "- query (required)"]
+        );
+
+        // Descriptions are wrapped under themselves
+        let query_params = vec![core::ZgQueryParam {
+            description: Some(
+                "Required. The query to search for.\nSee https://cloud.google.com/bigquery/docs/reference/standard-sql/query-syntax".to_string(),
+            ),
+            ..query_param("query", true, false)
+        }];
+        assert_eq!(
+            query_param_lines(&query_params, Some(50)),
+            vec![
+                "- query (required): Required. The query to search",
+                "                    for.",
+                "                    See",
+                "                    https://cloud.google.com/bigquery/docs/reference/standard-sql/query-syntax",
+            ]
+        );
+        assert_eq!(query_param_lines(&query_params, None).len(), 2);
     }
 
     #[test]
@@ -392,14 +1336,28 @@ mod tests {
         };
 
         // The result should only contain the required field
-        let result = payload_suggestion(&method, &core::ZgApi::testdata());
-        assert!(result.is_ok());
+        let data = minimum_data(&method, &core::ZgApi::testdata()).unwrap();
         assert_eq!(
-            result.unwrap(),
+            payload_suggestion(&data).unwrap(),
             "\nminimum_data:\n--data '{\n  \"requiredField\": \"\"\n}'"
         );
     }
 
+    #[test]
+    fn test_minimum_data_flavor() {
+        let method = core::ZgMethod {
+            id: "sqladmin.projects.instances.insert".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let data = minimum_data(&method, &core::ZgApi::testdata()).unwrap();
+        assert!(data.is_string());
+        // The text output is the flavor's as is
+        assert_eq!(
+            payload_suggestion(&data).unwrap(),
+            flavors::sqladmin_instances_insert().unwrap().trim_end()
+        );
+    }
+
     #[test]
     fn test_is_required_description() {
         // Case where description contains "Required"
@@ -411,9 +1369,9 @@ mod tests {
         assert!(
             is_required(
                 &core::ZgMethod::testdata(),
+                &discovery::Schema::testdata(),
                 &String::from("myfield"),
-                &prop,
-                false
+                &prop
             ),
             "Expected true due to 'Required' in description."
         );
@@ -427,9 +1385,9 @@ mod tests {
         assert!(
             is_required(
                 &core::ZgMethod::testdata(),
+                &discovery::Schema::testdata(),
                 &String::from("myfield2"),
-                &prop2,
-                false
+                &prop2
             ),
             "Expected true as description starts with 'Identifier.'"
         );
@@ -454,11 +1412,355 @@ mod tests {
         };
 
         assert!(
-            is_required(meth, &String::from("myfield"), &prop, false),
+            is_required(
+                meth,
+                &discovery::Schema::testdata(),
+                &String::from("myfield"),
+                &prop
+            ),
             "Expected true due to matching annotation method ID."
         );
     }
 
+    #[test]
+    fn test_is_required_schema_array() {
+        let prop = discovery::SchemaProperty {
+            description: Some("The template used to create executions.".to_string()),
+            ..discovery::SchemaProperty::testdata()
+        };
+        let schema = discovery::Schema {
+            required: Some(vecs!["template"]),
+            ..discovery::Schema::testdata()
+        };
+        let method = core::ZgMethod::testdata();
+        assert!(is_required(
+            &method,
+            &schema,
+            &String::from("template"),
+            &prop
+        ));
+        assert!(!is_required(&method, &schema, &String::from("name"), &prop));
+
+        // Read-only properties are never required
+        let read_only = discovery::SchemaProperty {
+            read_only: true,
+            ..prop
+        };
+        assert!(!is_required(
+            &method,
+            &schema,
+            &String::from("template"),
+            &read_only
+        ));
+    }
+
+    #[test]
+    fn test_response_example() {
+        let api = crate::update::extract_api(
+            std::path::PathBuf::from("tests/test_data/response_nested.json"),
+            false,
+        )
+        .unwrap();
+        let method = core::find_method_by_id(&api, "testapi.projects.things.list", false)
+            .unwrap()
+            .1;
+        let example = response_example(
+            &method,
+            method.response_schema.as_deref().unwrap(),
+            &api.schemas,
+        )
+        .unwrap();
+        // Read-only fields are included, arrays have one element, and Thing nested in itself is cut
+        assert_eq!(
+            example,
+            json!({
+                "nextPageToken": "",
+                "things": [{
+                    "config": {
+                        "children": ["<<recursive: Thing>>"],
+                        "owner": { "email": "" },
+                        "tags": [""]
+                    },
+                    "createTime": "2025-01-31T09:00:00Z",
+                    "enabled": false,
+                    "labels": { "key": "" },
+                    "name": "projects/PROJECT/things/THING",
+                    "ratio": 0.0,
+                    "replicas": 0,
+                    "sizeBytes": "0",
+                    "state": "STATE_UNSPECIFIED"
+                }],
+                "unreachable": [""]
+            })
+        );
+
+        // Methods without a response
+        let delete = core::find_method_by_id(&api, "testapi.projects.things.delete", false)
+            .unwrap()
+            .1;
+        assert_eq!(delete.response_schema, None);
+        assert!(response_example(&method, "Missing", &api.schemas).is_none());
+    }
+
+    #[test]
+    fn test_payload_suggestion_schema_required_only() {
+        // run:v2 marks required fields only with schema-level `required` arrays, including nested schemas
+        let api = crate::update::extract_api(
+            std::path::PathBuf::from("tests/test_data/run_v2_required.json"),
+            false,
+        )
+        .unwrap();
+        let method = core::find_method_by_id(&api, "run.projects.locations.jobs.create", false)
+            .unwrap()
+            .1;
+        assert_eq!(
+            payload_suggestion(&minimum_data(&method, &api).unwrap()).unwrap(),
+            "\nminimum_data:\n--data '{\n  \"template\": {\n    \"template\": {\n      \"serviceAccount\": \"\"\n    }\n  }\n}'"
+        );
+    }
+
+    #[test]
+    fn test_property_placeholder_maps() {
+        let method = core::ZgMethod::testdata();
+        let map_of = |value_prop: discovery::SchemaProperty| discovery::SchemaProperty {
+            prop_type: Some("object".to_string()),
+            additional_properties: Some(Box::new(value_prop)),
+            ..discovery::SchemaProperty::testdata()
+        };
+
+        // String-valued map (e.g., labels)
+        let labels = map_of(discovery::SchemaProperty::testdata());
+        assert_eq!(
+            property_placeholder(&method, "labels", &labels, &HashMap::new()),
+            json!({ "key": "" })
+        );
+
+        // $ref-valued map, resolved into the nested schema's required fields
+        let mut properties = HashMap::new();
+        properties.insert(
+            "value".to_string(),
+            discovery::SchemaProperty {
+                description: Some("Required. The value.".to_string()),
+                prop_type: Some("integer".to_string()),
+                ..discovery::SchemaProperty::testdata()
+            },
+        );
+        properties.insert("note".to_string(), discovery::SchemaProperty::testdata());
+        let schemas = HashMap::from([(
+            "Quota".to_string(),
+            discovery::Schema {
+                properties: Some(properties),
+                ..discovery::Schema::testdata()
+            },
+        )]);
+        let quotas = map_of(discovery::SchemaProperty {
+            prop_type: None,
+            ref_name: Some("Quota".to_string()),
+            ..discovery::SchemaProperty::testdata()
+        });
+        assert_eq!(
+            property_placeholder(&method, "quotas", &quotas, &schemas),
+            json!({ "key": { "value": 0 } })
+        );
+
+        // Objects without a value type are still unsupported
+        let object = discovery::SchemaProperty {
+            prop_type: Some("object".to_string()),
+            ..discovery::SchemaProperty::testdata()
+        };
+        assert_eq!(
+            property_placeholder(&method, "object", &object, &HashMap::new()),
+            json!("<<See API Reference for details>>")
+        );
+    }
+
+    #[test]
+    fn test_string_placeholder_formats() {
+        let method = core::ZgMethod::testdata();
+        let string = |format: Option<&str>| discovery::SchemaProperty {
+            format: format.map(str::to_string),
+            ..discovery::SchemaProperty::testdata()
+        };
+        let placeholder = |prop: &discovery::SchemaProperty| string_placeholder(&method, "x", prop);
+        assert_eq!(placeholder(&string(Some("google-duration"))), "3.5s");
+        assert_eq!(placeholder(&string(Some("int64"))), "0");
+        assert_eq!(placeholder(&string(Some("uint64"))), "0");
+        assert_eq!(placeholder(&string(Some("byte"))), "<base64>");
+        assert_eq!(
+            placeholder(&string(Some("google-datetime"))),
+            "2025-01-31T09:00:00Z"
+        );
+        assert_eq!(placeholder(&string(Some("google-fieldmask"))), "");
+        assert_eq!(placeholder(&string(None)), "");
+        // Enums are left empty, whatever the format
+        let enum_prop = discovery::SchemaProperty {
+            enum_values: Some(vec!["RUNNING".to_string()]),
+            ..string(Some("int64"))
+        };
+        assert_eq!(placeholder(&enum_prop), "");
+        // Through property_placeholder, e.g., in a map
+        let map = discovery::SchemaProperty {
+            prop_type: Some("object".to_string()),
+            additional_properties: Some(Box::new(string(Some("google-duration")))),
+            ..discovery::SchemaProperty::testdata()
+        };
+        assert_eq!(
+            property_placeholder(&method, "timeouts", &map, &HashMap::new()),
+            json!({ "key": "3.5s" })
+        );
+    }
+
+    #[test]
+    fn test_string_placeholder_resource_names() {
+        let method = |flat_path: &str| core::ZgMethod {
+            flat_path: flat_path.to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let described = |description: &str| discovery::SchemaProperty {
+            description: Some(description.to_string()),
+            format: None,
+            ..discovery::SchemaProperty::testdata()
+        };
+        let parent = described(
+            "Required. The parent (project and location) where the cluster will be created. Specified in the format `projects/*/locations/*`.",
+        );
+        let name = described("The resource name of the node pool.");
+        let create = method(
+            "v1/projects/{projectsId}/locations/{locationsId}/clusters/{clustersId}/nodePools",
+        );
+        assert_eq!(
+            string_placeholder(&create, "parent", &parent),
+            "projects/PROJECT/locations/LOCATION/clusters/CLUSTER"
+        );
+        assert_eq!(
+            string_placeholder(&create, "name", &name),
+            "projects/PROJECT/locations/LOCATION/clusters/CLUSTER/nodePools/NODE_POOL"
+        );
+        // Custom methods on a resource
+        let set_size = method(
+            "v1/projects/{projectsId}/locations/{locationsId}/policies/{policiesId}:setSize",
+        );
+        assert_eq!(
+            string_placeholder(&set_size, "name", &name),
+            "projects/PROJECT/locations/LOCATION/policies/POLICY"
+        );
+        assert_eq!(
+            string_placeholder(&set_size, "parent", &parent),
+            "projects/PROJECT/locations/LOCATION"
+        );
+        // Names not described as resource names (e.g., the short name of a cluster), and paths without placeholders
+        let short_name = described("The name of this cluster.");
+        assert_eq!(string_placeholder(&create, "name", &short_name), "");
+        assert_eq!(string_placeholder(&create, "displayName", &name), "");
+        assert_eq!(
+            string_placeholder(&method("v1/operations"), "parent", &parent),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_method_description_yaml() {
+        let method = core::ZgMethod {
+            query_params: vec![core::ZgQueryParam {
+                name: "pageSize".to_string(),
+                ..Default::default()
+            }],
+            ..core::ZgMethod::testdata()
+        };
+        let description = method_description(&method, &core::ZgApi::testdata()).unwrap();
+        let yaml = crate::output::serialize(&description, crate::output::Format::Yaml).unwrap();
+        let expected = r#"method_name: list
+method_id: testapi.projects.testres.list
+original_method_id: null
+http_method: GET
+request_url: https://example.com/v1/projects/{projectsId}/testres/{testresId}
+autofill_params:
+- projectsId
+positional_params:
+- testresId
+parameter_order: []
+required_params:
+- testresId
+query_params:
+- name: pageSize
+  description: null
+  required: false
+  repeated: false
+  param_type: null
+  enum_values: null
+  enum_descriptions: null
+  default: null
+  format: null
+  deprecated: false
+default_params: []
+minimum_data: null
+documentation_link: https://cloud.google.com/s/results/testapi/docs?q=%22Method%3A%22%20projects.testres%20list
+scopes: []
+"#;
+        assert_eq!(yaml, expected);
+    }
+
+    #[test]
+    fn test_undescribed_scopes() {
+        let api = crate::update::extract_api(
+            std::path::PathBuf::from("tests/test_data/scopes_v1.json"),
+            false,
+        )
+        .unwrap();
+        let (_, method) =
+            core::find_method_by_id(&api, "scopes.projects.widgets.list", false).unwrap();
+        assert_eq!(
+            undescribed_scopes(&method, &api),
+            vec!["https://www.googleapis.com/auth/widgets"]
+        );
+
+        // Nothing to check against without the auth block
+        let api = core::ZgApi {
+            scopes: BTreeMap::new(),
+            ..core::ZgApi::testdata()
+        };
+        assert!(undescribed_scopes(&method, &api).is_empty());
+    }
+
+    #[test]
+    fn test_preferred_version_hint() {
+        let item = |version: &str, preferred: bool| discovery::DiscoveryDirectoryItem {
+            id: format!("run:{}", version),
+            name: "run".to_string(),
+            version: version.to_string(),
+            preferred,
+            ..discovery::DiscoveryDirectoryItem::testdata()
+        };
+        let run = supported_apis::supported_apis(true)
+            .into_iter()
+            .find(|api| api.name == "run")
+            .unwrap();
+        let default_version = run.default_version().to_string();
+        let other_version = if default_version == "v1" { "v2" } else { "v1" };
+
+        // The default is preferred
+        let items = [item(&default_version, true), item(other_version, false)];
+        assert_eq!(preferred_version_hint(&run, &items), None);
+
+        // Another version is preferred
+        let items = [item(&default_version, false), item(other_version, true)];
+        let hint = preferred_version_hint(&run, &items).unwrap();
+        assert!(hint.starts_with(&format!("preferred_version: {} ", other_version)));
+        assert!(hint.contains(&format!("zg uses {} by default", default_version)));
+
+        // Not in the (cached) directory
+        assert_eq!(preferred_version_hint(&run, &[]), None);
+    }
+
+    #[test]
+    fn test_generate_workspace_documentation_link() {
+        assert_eq!(
+            generate_workspace_documentation_link("directory.users.list").unwrap(),
+            "https://developers.google.com/s/results/workspace?q=%22Method%3A%22%20directory%20users%20list"
+        );
+        assert_eq!(generate_workspace_documentation_link("drive.about"), None);
+    }
+
     #[test]
     fn test_generate_documentation_link() {
         let method_id = "compute.instances.insert";
@@ -469,4 +1771,95 @@ mod tests {
             "https://cloud.google.com/s/results/compute/docs?q=%22Method%3A%22%20instances%20insert"
         );
     }
+
+    #[test]
+    fn test_documentation_link() {
+        let api = core::ZgApi::testdata();
+        let resource = &api.resources[0];
+        let method = resource.methods[0].clone();
+
+        // Service: documentationLink in the discovery directory if any, otherwise a search
+        let item = discovery::DiscoveryDirectoryItem {
+            id: "testapi:v1".to_string(),
+            documentation_link: Some("https://cloud.google.com/testapi/docs".to_string()),
+            ..discovery::DiscoveryDirectoryItem::testdata()
+        };
+        assert_eq!(
+            documentation_link(&api, &Target::Service, &[item]).unwrap(),
+            "https://cloud.google.com/testapi/docs"
+        );
+        // The link stored in the API file takes precedence
+        let stored = core::ZgApi {
+            documentation_link: Some("https://cloud.google.com/testapi/".to_string()),
+            ..core::ZgApi::testdata()
+        };
+        assert_eq!(
+            documentation_link(&stored, &Target::Service, &[]).unwrap(),
+            "https://cloud.google.com/testapi/"
+        );
+        assert_eq!(
+            documentation_link(&api, &Target::Service, &[]).unwrap(),
+            "https://cloud.google.com/s/results/testapi/docs?q=REST%20reference"
+        );
+
+        // Resource: a search for the REST resource page
+        assert_eq!(
+            documentation_link(&api, &Target::Resource(resource), &[]).unwrap(),
+            "https://cloud.google.com/s/results/testapi/docs?q=%22REST%20Resource%3A%22%20projects.testres"
+        );
+
+        // Method: same as `zg desc`
+        assert_eq!(
+            documentation_link(&api, &Target::Method(Box::new(method)), &[]).unwrap(),
+            "https://cloud.google.com/s/results/testapi/docs?q=%22Method%3A%22%20projects.testres%20list"
+        );
+    }
+
+    #[test]
+    fn test_raw_discovery_node() {
+        let path = std::path::PathBuf::from("tests/test_data/container_v1_nested.json");
+        let document: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let api = crate::update::extract_api(path, false).unwrap();
+
+        // A nested method and its resource
+        let resource = core::find_api_resource(&api, "nodePools", false).unwrap();
+        let method = core::find_method(resource, "get").unwrap();
+        let node = raw_discovery_node(&document, &Target::Method(Box::new(method))).unwrap();
+        assert_eq!(
+            node["id"],
+            "container.projects.locations.clusters.nodePools.get"
+        );
+        assert_eq!(node["httpMethod"], "GET");
+        let node = raw_discovery_node(&document, &Target::Resource(resource)).unwrap();
+        assert!(node["methods"]["get"].is_object());
+
+        // A resource without methods is found by its path
+        let resource = core::find_api_resource(&api, "projects.locations", false).unwrap();
+        let node = raw_discovery_node(&document, &Target::Resource(resource)).unwrap();
+        assert!(node["resources"]["clusters"].is_object());
+
+        // A method moved by the rebuilt hierarchy is found by its original id
+        let moved = core::ZgMethod {
+            id: "container.clusters.get".to_string(),
+            original_id: Some("container.projects.locations.clusters.get".to_string()),
+            ..core::ZgMethod::testdata()
+        };
+        let node = raw_discovery_node(&document, &Target::Method(Box::new(moved))).unwrap();
+        assert_eq!(node["id"], "container.projects.locations.clusters.get");
+
+        let missing = core::ZgMethod {
+            id: "container.projects.locations.clusters.delete".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let e = raw_discovery_node(&document, &Target::Method(Box::new(missing))).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("Method 'container.projects.locations.clusters.delete' is not found in the discovery document"));
+
+        assert_eq!(
+            raw_discovery_node(&document, &Target::Service).unwrap(),
+            &document
+        );
+    }
 }
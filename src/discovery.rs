@@ -14,23 +14,203 @@
 
 use bytes::Bytes;
 use http_body_util::{BodyExt, Empty};
-use hyper::{Method as HyperMethod, Request as HyperRequest, Uri};
+use hyper::{HeaderMap, Method as HyperMethod, Request as HyperRequest, Uri};
 use hyper_rustls::HttpsConnectorBuilder;
-use hyper_util::{client::legacy::Client, rt::TokioExecutor};
-use log::debug;
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use log::{debug, warn, Level};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_json::{to_writer_pretty, Map};
+use serde_json::{to_vec_pretty, Map};
 use std::collections::{BTreeMap, HashMap};
-use std::error::Error;
-use std::fs::File;
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use super::config;
 use super::core;
-use super::supported_apis::SupportedApi;
+use super::error::ZgError;
+use super::exec;
+use super::logging::{self, log_kv};
+use super::supported_apis;
+use super::timing;
 
-/// Simple HTTP GET function using hyper
-async fn http_get(url: &str) -> Result<(u16, String), Box<dyn Error>> {
+/// Timeouts and retries of HTTP requests to download discovery documents.
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    attempts: u32,             // including the first one
+    connect_timeout: Duration, // to establish a connection
+    request_timeout: Duration, // for each attempt, from sending the request until receiving the whole body
+    backoff: Duration,         // delay before the 2nd attempt, doubled for each subsequent attempt
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            connect_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(60),
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// User-Agent sent with the requests to the Discovery Service.
+const USER_AGENT: &str = concat!("zg/", env!("CARGO_PKG_VERSION"));
+
+/// How the requests to download discovery documents are authorized. They are anonymous by default, and retried with the
+/// access token `zg exec` would send when rejected with 401 or 403 (e.g., private previews or VPC Service Controls).
+/// With `always` (`--auth-discovery`), the token is sent from the start. The token is sent only to the hosts of
+/// `googleapis.com` and the universe domain over HTTPS.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryAuth {
+    always: bool,
+    /// The token to send instead of the one of `zg exec`, which is got only when needed.
+    token: Option<String>,
+}
+
+impl DiscoveryAuth {
+    /// As configured with `--auth-discovery` (or `auth_discovery` in the config file).
+    pub fn current() -> DiscoveryAuth {
+        DiscoveryAuth {
+            always: config::current().auth_discovery.unwrap_or(false),
+            token: None,
+        }
+    }
+
+    async fn access_token(&self) -> Result<String, ZgError> {
+        match &self.token {
+            Some(token) => Ok(token.clone()),
+            None => exec::access_token().await,
+        }
+    }
+}
+
+/// Whether the access token may be sent to the URL (see `DiscoveryAuth`): HTTPS to a host of `googleapis.com` or the
+/// universe domain.
+fn is_trusted_host(url: &str) -> bool {
+    let Some(host) = url::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "https")
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+    else {
+        return false;
+    };
+    let under = |domain: &str| host == domain || host.ends_with(&format!(".{}", domain));
+    under(config::DEFAULT_UNIVERSE_DOMAIN) || config::current().universe_domain().is_some_and(under)
+}
+
+/// HTTP GET function using hyper, with extra request headers, authorized as `auth` tells. Returns the response headers
+/// as well (e.g., to read ETag). Connection errors, timeouts, and 5xx responses are retried (see `RetryPolicy`).
+/// Requests that already have an Authorization header (e.g., custom APIs in the config file) are sent as they are.
+async fn http_get_authorized(
+    url: &str,
+    headers: &[(&str, String)],
+    auth: &DiscoveryAuth,
+) -> Result<(u16, HeaderMap, String), ZgError> {
+    http_get_authorized_with(url, headers, auth, &RetryPolicy::default(), is_trusted_host).await
+}
+
+/// `http_get_authorized` with the hosts the token may be sent to decided by `trusted`.
+async fn http_get_authorized_with(
+    url: &str,
+    headers: &[(&str, String)],
+    auth: &DiscoveryAuth,
+    policy: &RetryPolicy,
+    trusted: impl Fn(&str) -> bool,
+) -> Result<(u16, HeaderMap, String), ZgError> {
+    let authorizable = trusted(url)
+        && !headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case("authorization"));
+    let with_token = |token: String| {
+        let mut headers = headers.to_vec();
+        headers.push(("Authorization", format!("Bearer {}", token)));
+        headers
+    };
+    if auth.always && authorizable {
+        let headers = with_token(auth.access_token().await?);
+        return http_get_with_retries(url, &headers, policy).await;
+    }
+    let response = http_get_with_retries(url, headers, policy).await?;
+    match response.0 {
+        401 | 403 if authorizable => {
+            debug!(
+                "GET {} was rejected with {}; retrying with the access token",
+                logging::redact_url(url),
+                response.0
+            );
+            match auth.access_token().await {
+                Ok(token) => http_get_with_retries(url, &with_token(token), policy).await,
+                Err(e) => {
+                    debug!("No access token to retry with: {}", e);
+                    Ok(response)
+                }
+            }
+        }
+        _ => Ok(response),
+    }
+}
+
+async fn http_get_with_retries(
+    url: &str,
+    headers: &[(&str, String)],
+    policy: &RetryPolicy,
+) -> Result<(u16, HeaderMap, String), ZgError> {
+    let mut attempt = 1;
+    loop {
+        let result = http_get_once(url, headers, policy).await;
+        let failure = match &result {
+            Ok((status, _, _)) if *status >= 500 => Some(format!("status: {}", status)),
+            Err(ZgError::Network(e)) => Some(e.clone()),
+            _ => None,
+        };
+        match failure {
+            Some(failure) if attempt < policy.attempts => {
+                let delay = policy.backoff * 2u32.pow(attempt - 1);
+                let url = logging::redact_url(url);
+                log_kv!(
+                    Level::Warn,
+                    {
+                        url = &url,
+                        error = &failure,
+                        attempt = attempt,
+                        max_attempts = policy.attempts,
+                        delay_ms = delay.as_millis(),
+                    },
+                    "GET {} failed ({}); retrying in {:?} (attempt {}/{})",
+                    url,
+                    failure,
+                    delay,
+                    attempt,
+                    policy.attempts
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            _ => {
+                return result.map_err(|e| match e {
+                    ZgError::Network(e) => ZgError::Network(format!(
+                        "GET {} failed after {} attempt(s): {}",
+                        logging::redact_url(url),
+                        attempt,
+                        e
+                    )),
+                    e => e,
+                })
+            }
+        }
+    }
+}
+
+async fn http_get_once(
+    url: &str,
+    headers: &[(&str, String)],
+    policy: &RetryPolicy,
+) -> Result<(u16, HeaderMap, String), ZgError> {
     // Install rustls crypto provider
     let _ = rustls::crypto::ring::default_provider().install_default();
 
@@ -41,32 +221,65 @@ async fn http_get(url: &str) -> Result<(u16, String), Box<dyn Error>> {
         .with_root_certificates(root_store)
         .with_no_client_auth();
 
+    let mut http_connector = HttpConnector::new();
+    http_connector.enforce_http(false);
+    http_connector.set_connect_timeout(Some(policy.connect_timeout));
     let https_connector = HttpsConnectorBuilder::new()
         .with_tls_config(config)
         .https_or_http()
         .enable_http1()
         .enable_http2()
-        .build();
+        .wrap_connector(http_connector);
 
     let client = Client::builder(TokioExecutor::new()).build(https_connector);
 
     let uri: Uri = url.parse()?;
-    let req = HyperRequest::builder()
+    let mut req = HyperRequest::builder()
         .method(HyperMethod::GET)
         .uri(uri)
-        .body(Empty::<Bytes>::new())?;
+        .header("User-Agent", USER_AGENT);
+    for (key, value) in headers {
+        req = req.header(*key, value);
+    }
+    let req = req.body(Empty::<Bytes>::new())?;
 
-    let response = client.request(req).await?;
-    let status = response.status().as_u16();
-    let body_bytes = response.into_body().collect().await?.to_bytes();
+    let _span = timing::span("http");
+    let request = async {
+        let response = client.request(req).await?;
+        let status = response.status().as_u16();
+        let response_headers = response.headers().clone();
+        let body_bytes = response.into_body().collect().await?.to_bytes();
+        Ok::<_, ZgError>((status, response_headers, body_bytes))
+    };
+    let (status, response_headers, body_bytes) =
+        tokio::time::timeout(policy.request_timeout, request)
+            .await
+            .map_err(|_| {
+                ZgError::Network(format!("timed out after {:?}", policy.request_timeout))
+            })??;
     let body_text = String::from_utf8(body_bytes.to_vec())?;
 
-    Ok((status, body_text))
+    Ok((status, response_headers, body_text))
 }
 
 const DISCOVERED_APIS_FILE: &str = "_discovered_apis.json";
+const DISCOVERED_APIS_META_FILE: &str = "_discovered_apis.meta.json";
 const DISCOVERY_URL: &str = "https://discovery.googleapis.com/discovery/v1/apis";
 
+/// Returns the URL on the host of the universe domain with `universe_discovery` (see `supported_apis::in_universe`),
+/// or as it is.
+fn universe_url(url: &str) -> String {
+    let config = config::current();
+    match (config.universe_discovery, config.universe_domain()) {
+        (Some(true), Some(domain)) => supported_apis::in_universe(url, domain),
+        _ => url.to_string(),
+    }
+}
+
+/// Default period the cached discovery directory is trusted without re-validation, overridable by
+/// `discovery_ttl_days` in the config file and the `ZG_DISCOVERY_TTL_DAYS` environment variable.
+const DEFAULT_DISCOVERY_TTL_DAYS: u64 = 7;
+
 // ---------------------- Discovery structs ---------------------------------------- //
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -108,13 +321,33 @@ pub struct ApiDescription {
     pub discovery_version: String, // Typically, same as version
     pub base_url: String,
     pub base_path: Option<String>,
+    pub root_url: Option<String>, // e.g., "https://bigquery.googleapis.com/"
+    pub service_path: Option<String>, // e.g., "bigquery/v2/"; baseUrl is rootUrl + servicePath
     pub documentation_link: String,
+    pub auth: Option<Auth>,
     pub parameters: Option<HashMap<String, Parameter>>,
     pub protocol: String, // "rest"
     pub resources: Option<HashMap<String, Resource>>,
     pub schemas: Option<HashMap<String, Schema>>,
 }
 
+/// The authorization of the API, i.e., the OAuth scopes it supports.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Auth {
+    pub oauth2: Option<OAuth2>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OAuth2 {
+    // Scope URL (e.g., "https://www.googleapis.com/auth/cloud-platform") -> its description
+    pub scopes: Option<HashMap<String, Scope>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Scope {
+    pub description: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Resource {
     pub methods: Option<HashMap<String, Method>>,
@@ -134,6 +367,8 @@ pub struct Method {
     pub request: Option<Request>,
     pub response: Option<Response>,
     pub scopes: Option<Vec<String>>,
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -143,12 +378,17 @@ pub struct Parameter {
     pub location: String,            // "path" or "query"
     #[serde(rename = "type")]
     pub param_type: String, // "type" is a reserved keyword in Rust, so renamed
+    #[serde(rename = "enum")]
     pub enum_values: Option<Vec<String>>,
     pub enum_descriptions: Option<Vec<String>>,
     pub default: Option<String>,
     pub format: Option<String>,
     pub pattern: Option<String>,
     pub required: Option<bool>,
+    #[serde(default)]
+    pub repeated: bool, // accepts multiple values, sent as repeated query params (e.g., "?fields=a&fields=b")
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -169,7 +409,29 @@ pub struct Schema {
     pub id: Option<String>,
     pub description: Option<String>,
     pub properties: Option<HashMap<String, SchemaProperty>>,
-    // pub required: Option<Vec<String>>, // Not used - comment out to avoid confusion
+    // Names of required properties. Only some APIs (e.g., run:v2, batch:v1) use it, instead of "Required." in descriptions.
+    #[serde(default)]
+    pub required: Option<Vec<String>>,
+    // The fields below are set when the schema describes array items or inline properties, e.g., {"$ref": "Disk"}
+    #[serde(default, rename = "type")]
+    pub schema_type: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default, rename = "$ref")]
+    pub ref_name: Option<String>,
+    #[serde(default, rename = "enum")]
+    pub enum_values: Option<Vec<String>>,
+    #[serde(default)]
+    pub items: Option<Box<Schema>>,
+}
+
+impl Schema {
+    /// Checks if the property is listed in the schema-level `required` array.
+    pub fn is_property_required(&self, name: &str) -> bool {
+        self.required
+            .as_ref()
+            .is_some_and(|required| required.iter().any(|r| r == name))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -186,6 +448,13 @@ pub struct SchemaProperty {
     #[serde(default)]
     pub read_only: bool, // default to false if not present
     pub annotations: Option<SchemaPropertyAnnotation>, // Used in limited services: compute and storage
+    // Type of the values of a map (`"type": "object"`), e.g., {"type": "string"} for labels
+    #[serde(default)]
+    pub additional_properties: Option<Box<SchemaProperty>>,
+    #[serde(default)]
+    pub deprecated: bool,
+    #[serde(default, rename = "enum")]
+    pub enum_values: Option<Vec<String>>,
 }
 
 // Used in limited services: compute and storage
@@ -195,6 +464,22 @@ pub struct SchemaPropertyAnnotation {
     pub required: Vec<String>,
 }
 
+/// Validators stored alongside `_discovered_apis.json` to re-validate the cache with a conditional GET.
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct DiscoveryCacheMeta {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: u64, // UNIX epoch seconds of the last successful fetch or re-validation
+}
+
+/// How `ensure_discovered_apis` should treat the cached discovery directory.
+#[derive(Debug, PartialEq)]
+enum CacheAction {
+    UseCache,   // Fresh enough; read the cached file as-is
+    Revalidate, // Cached, but stale (or forced); send a conditional GET
+    Fetch,      // No cache; download unconditionally
+}
+
 // ---------------------- dummy data for tests ----------------------------- //
 #[cfg(test)]
 impl Schema {
@@ -203,6 +488,29 @@ impl Schema {
             id: Some("testdata".to_string()),
             description: Some("Test schema".to_string()),
             properties: Some(HashMap::new()),
+            required: None,
+            schema_type: Some("object".to_string()),
+            format: None,
+            ref_name: None,
+            enum_values: None,
+            items: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl DiscoveryDirectoryItem {
+    pub fn testdata() -> Self {
+        Self {
+            kind: "discovery#directoryItem".to_string(),
+            id: "testapi:v1".to_string(),
+            name: "testapi".to_string(),
+            version: "v1".to_string(),
+            title: "Test API".to_string(),
+            description: "Test API description".to_string(),
+            discovery_rest_url: "https://example.com/$discovery/rest?version=v1".to_string(),
+            documentation_link: None,
+            preferred: true,
         }
     }
 }
@@ -219,33 +527,115 @@ impl SchemaProperty {
             ref_name: None,
             read_only: false,
             annotations: None,
+            additional_properties: None,
+            deprecated: false,
+            enum_values: None,
         }
     }
 }
 
 /// Ensure that the discovered APIs are cached in a file, and return the list of APIs.
-/// Set `replace` to true to force re-discovery and overwrite the local DISCOVERED_APIS_FILE.
+/// Set `replace` to true to re-validate the DISCOVERED_APIS_FILE regardless of the age of the local one.
+/// The cache is re-validated with a conditional GET (ETag/Last-Modified) once it gets older than the TTL;
+/// "304 Not Modified" keeps the cache, and a changed response overwrites it.
 pub async fn ensure_discovered_apis(
     replace: bool,
-) -> Result<DiscoveryDirectoryList, Box<dyn Error>> {
+    auth: &DiscoveryAuth,
+) -> Result<DiscoveryDirectoryList, ZgError> {
     let discovered_apis_file_path = discovered_dir().join(DISCOVERED_APIS_FILE);
+    let meta_file_path = discovered_dir().join(DISCOVERED_APIS_META_FILE);
+    let meta = read_cache_meta(&meta_file_path);
 
-    let discovered_apis_json: Value = if !discovered_apis_file_path.exists() && !replace {
-        debug!("Discoverying APIs via: {}", DISCOVERY_URL);
-        let (_status, discovered_apis_json_text) = http_get(DISCOVERY_URL).await?;
-        let j = sort_json(serde_json::from_str(&discovered_apis_json_text)?);
+    let age = discovered_apis_file_path
+        .exists()
+        .then(|| cache_age(&discovered_apis_file_path, meta.as_ref()))
+        .flatten();
+    let action = cache_action(
+        discovered_apis_file_path.exists(),
+        age,
+        discovery_ttl(),
+        replace,
+    );
+    debug!(
+        "Discovery directory cache: age={:?}, action={:?}",
+        age, action
+    );
 
-        // Save the discovered APIs JSON to a file
-        to_writer_pretty(&mut File::create(&discovered_apis_file_path)?, &j)?;
+    let discovered_apis_json: Value = match action {
+        CacheAction::UseCache => read_cached_directory(&discovered_apis_file_path)?,
+        CacheAction::Fetch | CacheAction::Revalidate => {
+            let mut headers = Vec::new();
+            if action == CacheAction::Revalidate {
+                if let Some(etag) = meta.as_ref().and_then(|m| m.etag.clone()) {
+                    headers.push(("If-None-Match", etag));
+                }
+                if let Some(lm) = meta.as_ref().and_then(|m| m.last_modified.clone()) {
+                    headers.push(("If-Modified-Since", lm));
+                }
+            }
 
-        j
-    } else {
-        debug!(
-            "Discovered APIs file found at {}",
-            discovered_apis_file_path.display()
-        );
-        let cached_discovered_apis_json_text = std::fs::read_to_string(&discovered_apis_file_path)?;
-        sort_json(serde_json::from_str(&cached_discovered_apis_json_text)?)
+            let discovery_url = universe_url(DISCOVERY_URL);
+            debug!(
+                "Discoverying APIs via: {} (headers: {:?})",
+                discovery_url, headers
+            );
+            let (status, response_headers, text) =
+                match http_get_authorized(&discovery_url, &headers, auth).await {
+                    Ok(response) => response,
+                    Err(e) if action == CacheAction::Revalidate => {
+                        // e.g., offline; the stale cache is better than nothing
+                        warn!(
+                            "Failed to refresh the discovery directory ({}); using the cached one",
+                            e
+                        );
+                        let cached = read_cached_directory(&discovered_apis_file_path)?;
+                        return Ok(serde_json::from_value(cached)?);
+                    }
+                    Err(e) => return Err(e),
+                };
+            let header_value = |name: &str| {
+                response_headers
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+
+            match status {
+                304 if action == CacheAction::Revalidate => {
+                    debug!("Discovery directory not modified; keep the cache");
+                    write_cache_meta(
+                        &meta_file_path,
+                        &DiscoveryCacheMeta {
+                            fetched_at: now_epoch_secs(),
+                            ..meta.unwrap_or_default()
+                        },
+                    )?;
+                    read_cached_directory(&discovered_apis_file_path)?
+                }
+                200 => {
+                    let j = sort_json(serde_json::from_str(&text)?);
+                    // Save the discovered APIs JSON to a file
+                    core::write_file_atomic(&discovered_apis_file_path, &to_vec_pretty(&j)?)?;
+                    write_cache_meta(
+                        &meta_file_path,
+                        &DiscoveryCacheMeta {
+                            etag: header_value("etag"),
+                            last_modified: header_value("last-modified"),
+                            fetched_at: now_epoch_secs(),
+                        },
+                    )?;
+                    j
+                }
+                _ if action == CacheAction::Revalidate => {
+                    warn!(
+                        "Failed to refresh the discovery directory (status: {}); using the cached one",
+                        status
+                    );
+                    read_cached_directory(&discovered_apis_file_path)?
+                }
+                _ => return Err(ZgError::HttpStatus { status, body: text }),
+            }
+        }
     };
 
     let discovered_apis: DiscoveryDirectoryList = serde_json::from_value(discovered_apis_json)?;
@@ -254,15 +644,110 @@ pub async fn ensure_discovered_apis(
     Ok(discovered_apis)
 }
 
+/// Decides whether the cached discovery directory can be used as-is.
+/// `age` is None when it cannot be determined, which is treated as stale.
+fn cache_action(exists: bool, age: Option<Duration>, ttl: Duration, force: bool) -> CacheAction {
+    match (exists, age) {
+        (false, _) => CacheAction::Fetch,
+        (true, _) if force => CacheAction::Revalidate,
+        (true, Some(age)) if age <= ttl => CacheAction::UseCache,
+        (true, _) => CacheAction::Revalidate,
+    }
+}
+
+/// Returns the age of the cached discovery directory (None if it's not downloaded yet) and its TTL.
+pub(crate) fn discovered_apis_cache_age() -> (Option<Duration>, Duration) {
+    let path = discovered_dir().join(DISCOVERED_APIS_FILE);
+    let meta = read_cache_meta(&discovered_dir().join(DISCOVERED_APIS_META_FILE));
+    let age = path
+        .exists()
+        .then(|| cache_age(&path, meta.as_ref()))
+        .flatten();
+    (age, discovery_ttl())
+}
+
+/// Returns the TTL of the cached discovery directory.
+fn discovery_ttl() -> Duration {
+    discovery_ttl_with(
+        env::var("ZG_DISCOVERY_TTL_DAYS").ok(),
+        config::current().discovery_ttl_days,
+    )
+}
+
+/// The TTL of `ZG_DISCOVERY_TTL_DAYS` if it's a number, or the configured one, or the default.
+fn discovery_ttl_with(env: Option<String>, configured: Option<u64>) -> Duration {
+    let days = env
+        .and_then(|v| v.parse::<u64>().ok())
+        .or(configured)
+        .unwrap_or(DEFAULT_DISCOVERY_TTL_DAYS);
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
+/// Age of the cache, preferring the recorded fetch time over the file mtime.
+fn cache_age(path: &Path, meta: Option<&DiscoveryCacheMeta>) -> Option<Duration> {
+    let fetched_at = match meta {
+        Some(m) => UNIX_EPOCH + Duration::from_secs(m.fetched_at),
+        None => std::fs::metadata(path).and_then(|m| m.modified()).ok()?,
+    };
+    SystemTime::now().duration_since(fetched_at).ok()
+}
+
+/// Returns the discovery directory cached by `zg update` (or any command that fetched it), without network access.
+/// None if it's not cached or can't be read.
+pub fn cached_discovered_apis() -> Option<DiscoveryDirectoryList> {
+    let value = read_cached_directory(&discovered_dir().join(DISCOVERED_APIS_FILE)).ok()?;
+    serde_json::from_value(value).ok()
+}
+
+fn read_cached_directory(path: &Path) -> Result<Value, ZgError> {
+    debug!("Discovered APIs file found at {}", path.display());
+    let cached_discovered_apis_json_text = std::fs::read_to_string(path)?;
+    Ok(sort_json(serde_json::from_str(
+        &cached_discovered_apis_json_text,
+    )?))
+}
+
+fn read_cache_meta(path: &Path) -> Option<DiscoveryCacheMeta> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_cache_meta(path: &Path, meta: &DiscoveryCacheMeta) -> Result<(), ZgError> {
+    core::write_file_atomic(path, &to_vec_pretty(meta)?)
+}
+
+fn now_epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 pub async fn download_api_definition(
     api_id: String,
     discovery_rest_url: String,
-) -> Result<Option<PathBuf>, Box<dyn Error>> {
-    println!("Downloading API definition: {}", discovery_rest_url);
-    let (status, api) = http_get(&discovery_rest_url).await?;
+    auth: &DiscoveryAuth,
+) -> Result<Option<PathBuf>, ZgError> {
+    download_api_definition_with_headers(api_id, discovery_rest_url, &[], auth).await
+}
+
+/// Same as `download_api_definition`, sending extra headers (e.g., for custom APIs requiring authorization).
+pub async fn download_api_definition_with_headers(
+    api_id: String,
+    discovery_rest_url: String,
+    headers: &[(String, String)],
+    auth: &DiscoveryAuth,
+) -> Result<Option<PathBuf>, ZgError> {
+    let discovery_rest_url = universe_url(&discovery_rest_url);
+    // Progress goes to stderr, keeping stdout for the output of the command (e.g., `zg ls SERVICE --format json` on the first use)
+    eprintln!(
+        "Downloading API definition: {}",
+        logging::redact_url(&discovery_rest_url)
+    );
+    let (status, api) = fetch_discovery_document(&discovery_rest_url, headers, auth).await?;
 
     if status != 200 {
-        println!(
+        eprintln!(
             "  -> Failed to download API definition for {}. Status: {}. Skipping.",
             api_id, status
         );
@@ -271,14 +756,13 @@ pub async fn download_api_definition(
     match serde_json::from_str::<Value>(&api) {
         Ok(json_value) => {
             let json = sort_json(json_value);
-            let filepath = discovered_dir().join(format!("{}.json", api_id.replace(":", "_")));
+            let filepath = discovered_file_path(&api_id);
             debug!("Saving API definition: {}", filepath.display());
-            let mut f = File::create(&filepath)?;
-            to_writer_pretty(&mut f, &json)?;
+            core::write_file_atomic(&filepath, &to_vec_pretty(&json)?)?;
             Ok(Some(filepath))
         }
         Err(e) => {
-            println!(
+            eprintln!(
                 "  -> Failed to parse API definition for {}. Error: {}. Skipping.",
                 api_id, e
             );
@@ -287,21 +771,21 @@ pub async fn download_api_definition(
     }
 }
 
-/// Currently, only Gemini API (generativelanguage) uses this strategy.
-pub fn standalone_discovery_url(standalone_api: SupportedApi, api_key: String) -> String {
-    match standalone_api.name.as_str() {
-        "generativelanguage" => {
-            let version = standalone_api
-                .versions
-                .first()
-                .expect("at least one version");
-            format!(
-                "https://generativelanguage.googleapis.com/$discovery/rest?version={}&key={}",
-                version, api_key
-            )
-        }
-        _ => panic!("Unsupported standalone API: {}", standalone_api.name),
+/// Fetches a discovery document over HTTP(S), or reads it from a local file for `file://` URLs.
+async fn fetch_discovery_document(
+    url: &str,
+    headers: &[(String, String)],
+    auth: &DiscoveryAuth,
+) -> Result<(u16, String), ZgError> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Ok((200, fs::read_to_string(path)?));
     }
+    let headers: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.clone()))
+        .collect();
+    let (status, _headers, body) = http_get_authorized(url, &headers, auth).await?;
+    Ok((status, body))
 }
 
 /// Returns the path to the directory where discovered API JSON files are stored.
@@ -310,6 +794,11 @@ fn discovered_dir() -> PathBuf {
     core::config_dir().join("discovered")
 }
 
+/// Returns the path where the discovery document of the API (e.g., `container:v1`) is saved.
+pub(crate) fn discovered_file_path(api_id: &str) -> PathBuf {
+    discovered_dir().join(format!("{}.json", api_id.replace(":", "_")))
+}
+
 /// Sorts JSON fields before into files, so that we can detect exact changes easily. Doesn't sort arrays.
 fn sort_json(value: Value) -> Value {
     match value {
@@ -327,6 +816,248 @@ fn sort_json(value: Value) -> Value {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Starts a local HTTP server answering each request with `respond(n)` (n: 0-based request count).
+    /// `None` never answers, to simulate a wedged connection. Returns the URL and the request counter.
+    async fn serve(respond: fn(usize) -> Option<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/discovery", listener.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    let _ = socket.read(&mut buf).await;
+                    match respond(n) {
+                        Some(response) => {
+                            let _ = socket.write_all(response.as_bytes()).await;
+                        }
+                        None => tokio::time::sleep(Duration::from_secs(60)).await,
+                    }
+                });
+            }
+        });
+        (url, count)
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            connect_timeout: Duration::from_millis(500),
+            request_timeout: Duration::from_millis(300),
+            backoff: Duration::from_millis(10),
+        }
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+    const UNAVAILABLE: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn test_http_get_retries_server_errors() {
+        // Fails twice, then succeeds
+        let (url, count) = serve(|n| Some(if n < 2 { UNAVAILABLE } else { OK })).await;
+        let (status, _, body) = http_get_with_retries(&url, &[], &fast_policy())
+            .await
+            .unwrap();
+        assert_eq!((status, body.as_str()), (200, "{}"));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // Keeps failing: the last response is returned after all attempts
+        let (url, count) = serve(|_| Some(UNAVAILABLE)).await;
+        let (status, _, _) = http_get_with_retries(&url, &[], &fast_policy())
+            .await
+            .unwrap();
+        assert_eq!(status, 503);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_http_get_times_out() {
+        // The first request hangs, and the retry succeeds
+        let (url, _) = serve(|n| if n == 0 { None } else { Some(OK) }).await;
+        let (status, _, _) = http_get_with_retries(&url, &[], &fast_policy())
+            .await
+            .unwrap();
+        assert_eq!(status, 200);
+
+        // All requests hang
+        let (url, count) = serve(|_| None).await;
+        let policy = RetryPolicy {
+            attempts: 2,
+            ..fast_policy()
+        };
+        let err = http_get_with_retries(&url, &[], &policy).await.unwrap_err();
+        assert!(matches!(err, ZgError::Network(_)));
+        let message = err.to_string();
+        assert!(message.contains(&url), "{}", message);
+        assert!(message.contains("after 2 attempt(s)"), "{}", message);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    const TOKEN: &str = "ya29.test-token";
+
+    /// Starts a local HTTP server requiring `Authorization: Bearer TOKEN`: OK with it, and 403 without. Returns the URL
+    /// and the counts of the requests with and without the token.
+    async fn serve_authorized() -> (String, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/discovery", listener.local_addr().unwrap());
+        let (authorized, anonymous) =
+            (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let counts = (authorized.clone(), anonymous.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+                let response = match request.contains(&format!("authorization: bearer {}", TOKEN)) {
+                    true => {
+                        counts.0.fetch_add(1, Ordering::SeqCst);
+                        OK
+                    }
+                    false => {
+                        counts.1.fetch_add(1, Ordering::SeqCst);
+                        FORBIDDEN
+                    }
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, authorized, anonymous)
+    }
+
+    const FORBIDDEN: &str =
+        "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    /// Trusts the local test servers, which `is_trusted_host` doesn't.
+    fn trusted(url: &str) -> bool {
+        url.starts_with("http://127.0.0.1:")
+    }
+
+    fn auth_with_token(always: bool) -> DiscoveryAuth {
+        DiscoveryAuth {
+            always,
+            token: Some(TOKEN.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_get_authorized_falls_back_to_token() {
+        // Anonymous first, retried with the token on 403
+        let (url, authorized, anonymous) = serve_authorized().await;
+        let (status, _, body) =
+            http_get_authorized_with(&url, &[], &auth_with_token(false), &fast_policy(), trusted)
+                .await
+                .unwrap();
+        assert_eq!((status, body.as_str()), (200, "{}"));
+        assert_eq!(anonymous.load(Ordering::SeqCst), 1);
+        assert_eq!(authorized.load(Ordering::SeqCst), 1);
+
+        // A public document is fetched anonymously, without asking for the token
+        let (url, count) = serve(|_| Some(OK)).await;
+        let (status, _, _) = http_get_authorized_with(
+            &url,
+            &[],
+            &DiscoveryAuth::default(),
+            &fast_policy(),
+            trusted,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_get_authorized_always() {
+        // --auth-discovery sends the token from the start
+        let (url, authorized, anonymous) = serve_authorized().await;
+        let (status, _, _) =
+            http_get_authorized_with(&url, &[], &auth_with_token(true), &fast_policy(), trusted)
+                .await
+                .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(anonymous.load(Ordering::SeqCst), 0);
+        assert_eq!(authorized.load(Ordering::SeqCst), 1);
+
+        // An Authorization header given for the API (e.g., a custom API) is kept
+        let (url, authorized, anonymous) = serve_authorized().await;
+        let headers = [("Authorization", "Bearer other".to_string())];
+        let (status, _, _) = http_get_authorized_with(
+            &url,
+            &headers,
+            &auth_with_token(true),
+            &fast_policy(),
+            trusted,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, 403);
+        assert_eq!(anonymous.load(Ordering::SeqCst), 1);
+        assert_eq!(authorized.load(Ordering::SeqCst), 0);
+
+        // The token isn't sent to untrusted hosts, including the loopback address over HTTP
+        let (url, authorized, anonymous) = serve_authorized().await;
+        let (status, _, _) = http_get_authorized_with(
+            &url,
+            &[],
+            &auth_with_token(true),
+            &fast_policy(),
+            is_trusted_host,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, 403);
+        assert_eq!(anonymous.load(Ordering::SeqCst), 1);
+        assert_eq!(authorized.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_is_trusted_host() {
+        assert!(is_trusted_host(
+            "https://container.googleapis.com/$discovery/rest?version=v1"
+        ));
+        assert!(is_trusted_host("https://GOOGLEAPIS.COM/discovery/v1/apis"));
+        // The token isn't sent to other hosts, the loopback address, or over plain HTTP
+        assert!(!is_trusted_host("http://127.0.0.1:8080/discovery"));
+        assert!(!is_trusted_host("https://localhost/discovery"));
+        assert!(!is_trusted_host(
+            "http://container.googleapis.com/$discovery/rest"
+        ));
+        assert!(!is_trusted_host("https://example.com/discovery.json"));
+        assert!(!is_trusted_host(
+            "https://googleapis.com.example.com/discovery.json"
+        ));
+        assert!(!is_trusted_host("https://evilgoogleapis.com/"));
+        assert!(!is_trusted_host("not a url"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_discovery_document_from_file_url() {
+        let fixture = std::fs::canonicalize("tests/test_data/container_v1_nested.json").unwrap();
+        let url = format!("file://{}", fixture.display());
+        let (status, body) = fetch_discovery_document(&url, &[], &DiscoveryAuth::default())
+            .await
+            .unwrap();
+        assert_eq!(status, 200);
+        let api: ApiDescription = serde_json::from_str(&body).unwrap();
+        assert_eq!(api.id, "container:v1");
+
+        let missing = format!("file://{}.missing", fixture.display());
+        assert!(
+            fetch_discovery_document(&missing, &[], &DiscoveryAuth::default())
+                .await
+                .is_err()
+        );
+    }
 
     #[test]
     fn test_sort_json() {
@@ -351,4 +1082,45 @@ mod tests {
 
         assert_eq!(sorted_json, expected_json);
     }
+
+    #[test]
+    fn test_cache_action_staleness() {
+        let ttl = Duration::from_secs(7 * 24 * 60 * 60);
+        let fresh = Some(Duration::from_secs(60));
+        let stale = Some(ttl + Duration::from_secs(1));
+
+        assert_eq!(cache_action(false, None, ttl, false), CacheAction::Fetch);
+        assert_eq!(cache_action(true, fresh, ttl, false), CacheAction::UseCache);
+        assert_eq!(
+            cache_action(true, stale, ttl, false),
+            CacheAction::Revalidate
+        );
+        assert_eq!(
+            cache_action(true, None, ttl, false),
+            CacheAction::Revalidate
+        );
+    }
+
+    #[test]
+    fn test_cache_action_replace_existing_cache() {
+        // Regression: `replace` used to be ignored when the cache file already existed.
+        // It re-validates the cache with a conditional GET, so that 304 only refreshes the timestamp.
+        let ttl = Duration::from_secs(60);
+        let fresh = Some(Duration::from_secs(1));
+        assert_eq!(
+            cache_action(true, fresh, ttl, true),
+            CacheAction::Revalidate
+        );
+        assert_eq!(cache_action(false, None, ttl, true), CacheAction::Fetch);
+    }
+
+    #[test]
+    fn test_discovery_ttl() {
+        let days = |days: u64| Duration::from_secs(days * 24 * 60 * 60);
+        assert_eq!(discovery_ttl_with(None, None), days(7));
+        assert_eq!(discovery_ttl_with(None, Some(3)), days(3));
+        // The environment variable overrides the config file, unless it's not a number
+        assert_eq!(discovery_ttl_with(Some("1".to_string()), Some(3)), days(1));
+        assert_eq!(discovery_ttl_with(Some("x".to_string()), Some(3)), days(3));
+    }
 }
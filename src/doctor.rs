@@ -0,0 +1,688 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use clap::{Args, ValueEnum};
+use http_body_util::Empty;
+use hyper::{Method, Request};
+use prettytable::{format, row, Table};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use super::cache;
+use super::config;
+use super::core;
+use super::discovery;
+use super::error::ZgError;
+use super::exec;
+use super::output::{self, Format};
+use super::supported_apis::{supported_apis, Category, SupportedApi};
+
+/// Host and port checked by the network check.
+const DISCOVERY_HOST: &str = "discovery.googleapis.com:443";
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Skip the checks (e.g., `--skip network`). Accepts multiple checks.
+    #[arg(long, value_enum)]
+    skip: Vec<Check>,
+
+    /// Instead of the checks, audit the supported APIs against the discovery directory: versions listed in zg but not
+    /// upstream, preferred versions upstream missing from zg, and standalone APIs whose discovery documents can't be
+    /// downloaded. Fails when zg lists versions that don't exist.
+    #[arg(long, conflicts_with = "skip")]
+    apis: bool,
+
+    /// Output the audit in JSON. Same as the global `--format json`.
+    #[arg(long, requires = "apis")]
+    json: bool,
+}
+
+/// The checks run by `zg doctor`, in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Check {
+    Gcloud,
+    Project,
+    ConfigDir,
+    Discovery,
+    Cache,
+    Network,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Pass => write!(f, "PASS"),
+            Status::Warn => write!(f, "WARN"),
+            Status::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Result of a check, with a hint to fix it unless passed.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Outcome {
+    fn pass(message: impl Into<String>) -> Self {
+        Outcome {
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Outcome {
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(message: impl Into<String>, hint: Option<String>) -> Self {
+        Outcome {
+            status: Status::Fail,
+            message: message.into(),
+            hint,
+        }
+    }
+}
+
+/// Main function to diagnose the environment. Fails if any check fails; warnings don't.
+/// With --apis, audits the supported APIs instead (see `audit_apis`), printed in `format`.
+pub async fn main(
+    args: &DoctorArgs,
+    format: Format,
+    standalone_api_key: Option<String>,
+) -> Result<(), ZgError> {
+    if args.apis {
+        let audit = audit_apis(standalone_api_key.as_deref()).await?;
+        match args.json {
+            true => output::print(&audit, Format::Json)?,
+            false if format == Format::Text => print!("{}", audit.render()),
+            false => output::print(&audit, format)?,
+        }
+        return audit.check();
+    }
+    let mut failures = 0;
+    for check in Check::value_variants() {
+        let name = check.to_possible_value().expect("no skipped variants");
+        if args.skip.contains(check) {
+            println!("SKIP  {}", name.get_name());
+            continue;
+        }
+        let outcome = run(*check).await;
+        println!(
+            "{}  {}: {}",
+            outcome.status,
+            name.get_name(),
+            outcome.message
+        );
+        if let Some(hint) = &outcome.hint {
+            println!("      hint: {}", hint);
+        }
+        if outcome.status == Status::Fail {
+            failures += 1;
+        }
+    }
+    match failures {
+        0 => Ok(()),
+        n => Err(format!("{} check(s) failed", n).into()),
+    }
+}
+
+async fn run(check: Check) -> Outcome {
+    match check {
+        Check::Gcloud => check_gcloud(exec::get_access_token()),
+        Check::Project => {
+            let configured = config::current().project.as_deref();
+            match configured {
+                Some(project) => check_project(Some(project), None),
+                None => check_project(None, Some(exec::get_gcloud_config_value("core/project"))),
+            }
+        }
+        Check::ConfigDir => check_config_dir(&core::config_dir()),
+        Check::Discovery => {
+            let (age, ttl) = discovery::discovered_apis_cache_age();
+            check_discovery_cache(age, ttl)
+        }
+        Check::Cache => check_cache(&core::config_dir()),
+        Check::Network => check_network(DISCOVERY_HOST, NETWORK_TIMEOUT).await,
+    }
+}
+
+/// Checks that gcloud is installed and returns an access token.
+fn check_gcloud(token: Result<String, ZgError>) -> Outcome {
+    match token {
+        Ok(_) => Outcome::pass("gcloud is installed and authenticated"),
+        Err(e @ ZgError::GcloudMissing) => Outcome::fail(e.to_string(), e.hint()),
+        Err(e) => Outcome::fail(
+            format!("gcloud failed to print an access token ({})", e),
+            Some("Run `gcloud auth login`, and check the system clock if the token is rejected as expired.".to_string()),
+        ),
+    }
+}
+
+/// Checks that a project is set in the config file or gcloud (`core/project`), which fills `{projectsId}` and the like.
+fn check_project(configured: Option<&str>, gcloud: Option<Result<String, ZgError>>) -> Outcome {
+    match (configured, gcloud) {
+        (Some(project), _) => Outcome::pass(format!("project '{}' (config file)", project)),
+        (None, Some(Ok(project))) => {
+            Outcome::pass(format!("project '{}' (gcloud core/project)", project))
+        }
+        (None, _) => Outcome::warn(
+            "no project is set; project placeholders must be given with -p",
+            "Run `gcloud config set project PROJECT_ID` or `zg config set project PROJECT_ID`.",
+        ),
+    }
+}
+
+/// Checks that the config directory is writable, where the cache and the config file are stored.
+fn check_config_dir(dir: &Path) -> Outcome {
+    let probe = dir.join(".doctor");
+    match core::write_file_atomic(&probe, b"ok").and_then(|_| Ok(std::fs::remove_file(&probe)?)) {
+        Ok(()) => Outcome::pass(format!("{} is writable", dir.display())),
+        Err(e) => Outcome::fail(
+            format!("{} is not writable ({})", dir.display(), e),
+            Some("Fix the permissions, or set XDG_CONFIG_HOME to another directory.".to_string()),
+        ),
+    }
+}
+
+/// Checks that the discovery directory is downloaded and within the TTL.
+fn check_discovery_cache(age: Option<Duration>, ttl: Duration) -> Outcome {
+    let days = |duration: Duration| duration.as_secs() / 86400;
+    match age {
+        None => Outcome::warn(
+            "the discovery directory is not downloaded yet",
+            "Run `zg update` (it's also downloaded on the first use).",
+        ),
+        Some(age) if age > ttl => Outcome::warn(
+            format!(
+                "the discovery directory is {} day(s) old (TTL: {} days)",
+                days(age),
+                days(ttl)
+            ),
+            "It's re-validated on the next use; run with --refresh-discovery to download it now.",
+        ),
+        Some(age) => Outcome::pass(format!(
+            "the discovery directory is {} day(s) old",
+            days(age)
+        )),
+    }
+}
+
+/// Checks that every cached file can be decoded (see `zg cache verify`).
+fn check_cache(dir: &Path) -> Outcome {
+    match cache::verify(dir) {
+        Ok(broken) if broken.is_empty() => Outcome::pass("all cached files are valid"),
+        Ok(broken) => Outcome::fail(
+            format!(
+                "{} broken file(s), e.g., {}",
+                broken.len(),
+                broken[0].0.display()
+            ),
+            Some(
+                "Run `zg cache verify --delete`; they are prepared again on the next use."
+                    .to_string(),
+            ),
+        ),
+        Err(e) => Outcome::fail(format!("failed to read the cache ({})", e), None),
+    }
+}
+
+/// Checks that a TCP connection to the host can be established.
+async fn check_network(host: &str, timeout: Duration) -> Outcome {
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(host)).await {
+        Ok(Ok(_)) => Outcome::pass(format!("{} is reachable", host)),
+        Ok(Err(e)) => Outcome::fail(
+            format!("{} is not reachable ({})", host, e),
+            Some(
+                "Check the network and proxy settings. Cached API definitions still work offline."
+                    .to_string(),
+            ),
+        ),
+        Err(_) => Outcome::fail(
+            format!("{} timed out after {:?}", host, timeout),
+            Some(
+                "Check the network and proxy settings. Cached API definitions still work offline."
+                    .to_string(),
+            ),
+        ),
+    }
+}
+
+// ---------------------- API audit ----------------------------- //
+
+/// What the audit found about a version of a supported API.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum FindingKind {
+    /// Listed in zg, but not in the discovery directory (e.g., retired), which fails when it's used.
+    NotUpstream,
+    /// Preferred in the discovery directory, but not listed in zg.
+    MissingPreferred,
+    /// The discovery document of a standalone API returns HTTP 404, which fails when it's used.
+    NotFound,
+    /// The discovery document of a standalone API can't be downloaded for another reason (e.g., a network error or
+    /// HTTP 503), which may be transient.
+    Unreachable,
+    /// The discovery document of a standalone API is not checked, e.g., as it requires an API key.
+    Unchecked,
+}
+
+impl FindingKind {
+    /// Whether zg references a version that doesn't exist, which fails the audit.
+    fn fails(self) -> bool {
+        matches!(self, FindingKind::NotUpstream | FindingKind::NotFound)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct ApiFinding {
+    api: String,
+    version: String,
+    kind: FindingKind,
+    detail: String,
+}
+
+/// The result of `zg doctor --apis`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ApiAudit {
+    /// Number of the versions of the supported APIs checked.
+    checked_versions: usize,
+    findings: Vec<ApiFinding>,
+}
+
+impl ApiAudit {
+    /// Renders the findings as a table with a summary line.
+    fn render(&self) -> String {
+        let failures = self.findings.iter().filter(|f| f.kind.fails()).count();
+        let summary = format!(
+            "Checked {} version(s) of the supported APIs: {} not upstream or not found, {} other finding(s)\n",
+            self.checked_versions,
+            failures,
+            self.findings.len() - failures
+        );
+        if self.findings.is_empty() {
+            return summary;
+        }
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_CLEAN);
+        table.set_titles(row![bu->"api", b->"version", b->"finding", b->"detail"]);
+        for finding in &self.findings {
+            let kind = serde_json::to_value(finding.kind)
+                .ok()
+                .and_then(|kind| kind.as_str().map(str::to_string))
+                .unwrap_or_default();
+            table.add_row(row![finding.api, finding.version, kind, finding.detail]);
+        }
+        format!("{}{}", table, summary)
+    }
+
+    /// Fails if zg lists versions that don't exist.
+    fn check(&self) -> Result<(), ZgError> {
+        match self.findings.iter().filter(|f| f.kind.fails()).count() {
+            0 => Ok(()),
+            n => Err(format!(
+                "{} version(s) of the supported APIs don't exist upstream",
+                n
+            )
+            .into()),
+        }
+    }
+}
+
+/// Cross-checks the supported APIs (except custom ones) with the discovery directory, downloaded again first,
+/// and downloads the discovery documents of standalone APIs to check that they exist.
+async fn audit_apis(api_key: Option<&str>) -> Result<ApiAudit, ZgError> {
+    let directory =
+        discovery::ensure_discovered_apis(true, &discovery::DiscoveryAuth::current()).await?;
+    let apis: Vec<SupportedApi> = supported_apis(true)
+        .into_iter()
+        .filter(|api| api.category != Category::Custom)
+        .collect();
+    let mut findings = compare_with_directory(&apis, &directory.items);
+    for api in apis
+        .iter()
+        .filter(|api| api.discovery_url_template.is_some())
+    {
+        for version in &api.versions {
+            let status = match api.discovery_url(version, api_key) {
+                Some(Ok(url)) => Some(fetch_status(&url).await),
+                _ => None,
+            };
+            findings.extend(standalone_finding(api, version, status));
+        }
+    }
+    Ok(ApiAudit {
+        checked_versions: apis.iter().map(|api| api.versions.len()).sum(),
+        findings,
+    })
+}
+
+/// Compares the APIs listed in the discovery directory (not standalone ones) with the directory items.
+fn compare_with_directory(
+    apis: &[SupportedApi],
+    items: &[discovery::DiscoveryDirectoryItem],
+) -> Vec<ApiFinding> {
+    let upstream: HashSet<(&str, &str)> = items
+        .iter()
+        .map(|item| (item.name.as_str(), item.version.as_str()))
+        .collect();
+    let mut findings = Vec::new();
+    for api in apis
+        .iter()
+        .filter(|api| api.discovery_url_template.is_none())
+    {
+        let listed_upstream = items.iter().any(|item| item.name == api.name);
+        for version in &api.versions {
+            if !upstream.contains(&(api.name.as_str(), version.as_str())) {
+                findings.push(ApiFinding {
+                    api: api.name.clone(),
+                    version: version.clone(),
+                    kind: FindingKind::NotUpstream,
+                    detail: match listed_upstream {
+                        true => "not in the discovery directory (retired?)".to_string(),
+                        false => "the API is not in the discovery directory".to_string(),
+                    },
+                });
+            }
+        }
+        let preferred = items
+            .iter()
+            .filter(|item| item.name == api.name && item.preferred)
+            .filter(|item| !api.versions.contains(&item.version));
+        for item in preferred {
+            findings.push(ApiFinding {
+                api: api.name.clone(),
+                version: item.version.clone(),
+                kind: FindingKind::MissingPreferred,
+                detail: "preferred in the discovery directory, but not listed in zg".to_string(),
+            });
+        }
+    }
+    findings
+}
+
+/// The finding of a standalone API version from the HTTP status of its discovery document, if not OK.
+/// `status` is None when it's not checked.
+fn standalone_finding(
+    api: &SupportedApi,
+    version: &str,
+    status: Option<Result<u16, ZgError>>,
+) -> Option<ApiFinding> {
+    let (kind, detail) = match status {
+        Some(Ok(200)) => return None,
+        Some(Ok(404)) => (
+            FindingKind::NotFound,
+            "the discovery document returned HTTP 404".to_string(),
+        ),
+        Some(Ok(status)) => (
+            FindingKind::Unreachable,
+            format!("the discovery document returned HTTP {}", status),
+        ),
+        Some(Err(e)) => (
+            FindingKind::Unreachable,
+            format!("failed to download the discovery document ({})", e),
+        ),
+        None => (
+            FindingKind::Unchecked,
+            "the discovery document requires an API key; give --api-key to check it".to_string(),
+        ),
+    };
+    Some(ApiFinding {
+        api: api.name.clone(),
+        version: version.to_string(),
+        kind,
+        detail,
+    })
+}
+
+/// Sends a GET request to the URL and returns the status.
+async fn fetch_status(url: &str) -> Result<u16, ZgError> {
+    let client = exec::build_client::<Empty<Bytes>>()?;
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(url)
+        .body(Empty::<Bytes>::new())?;
+    match tokio::time::timeout(NETWORK_TIMEOUT, client.request(request)).await {
+        Ok(response) => Ok(response?.status().as_u16()),
+        Err(_) => Err(format!("timed out after {:?}", NETWORK_TIMEOUT).into()),
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_gcloud() {
+        assert_eq!(check_gcloud(Ok("token".to_string())).status, Status::Pass);
+        let missing = check_gcloud(Err(ZgError::GcloudMissing));
+        assert_eq!(missing.status, Status::Fail);
+        assert!(missing.hint.is_some());
+        let unauthenticated = check_gcloud(Err(ZgError::AuthFailure("empty".to_string())));
+        assert_eq!(unauthenticated.status, Status::Fail);
+        assert!(unauthenticated.hint.unwrap().contains("gcloud auth login"));
+    }
+
+    #[test]
+    fn test_check_project() {
+        assert_eq!(
+            check_project(Some("p1"), None),
+            Outcome::pass("project 'p1' (config file)")
+        );
+        assert_eq!(
+            check_project(None, Some(Ok("p2".to_string()))),
+            Outcome::pass("project 'p2' (gcloud core/project)")
+        );
+        let unset = check_project(None, Some(Err("No 'core/project'".into())));
+        assert_eq!(unset.status, Status::Warn);
+    }
+
+    #[test]
+    fn test_check_config_dir() {
+        let dir = std::env::temp_dir().join(format!("zg-test-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(check_config_dir(&dir).status, Status::Pass);
+        assert!(!dir.join(".doctor").exists());
+
+        // A file in place of the directory
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(check_config_dir(&file).status, Status::Fail);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_discovery_cache() {
+        let day = Duration::from_secs(86400);
+        assert_eq!(check_discovery_cache(None, 7 * day).status, Status::Warn);
+        assert_eq!(
+            check_discovery_cache(Some(2 * day), 7 * day),
+            Outcome::pass("the discovery directory is 2 day(s) old")
+        );
+        let stale = check_discovery_cache(Some(10 * day), 7 * day);
+        assert_eq!(stale.status, Status::Warn);
+        assert_eq!(
+            stale.message,
+            "the discovery directory is 10 day(s) old (TTL: 7 days)"
+        );
+    }
+
+    #[test]
+    fn test_check_cache() {
+        let dir = std::env::temp_dir().join(format!("zg-test-doctor-cache-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("api")).unwrap();
+        let api = rmp_serde::to_vec(&core::ZgApi::testdata()).unwrap();
+        std::fs::write(dir.join("api").join("testapi_v1.msgpack"), &api).unwrap();
+        assert_eq!(check_cache(&dir).status, Status::Pass);
+
+        std::fs::write(dir.join("api").join("broken_v1.msgpack"), &api[..10]).unwrap();
+        let broken = check_cache(&dir);
+        assert_eq!(broken.status, Status::Fail);
+        assert!(
+            broken.message.starts_with("1 broken file(s)"),
+            "{}",
+            broken.message
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_network() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        assert_eq!(
+            check_network(&addr, NETWORK_TIMEOUT).await.status,
+            Status::Pass
+        );
+        drop(listener);
+        assert_eq!(
+            check_network(&addr, NETWORK_TIMEOUT).await.status,
+            Status::Fail
+        );
+    }
+
+    fn supported(name: &str, versions: &[&str], standalone: bool) -> SupportedApi {
+        SupportedApi {
+            name: name.to_string(),
+            title: name.to_string(),
+            category: Category::Compute,
+            aliases: vec![],
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            status: Default::default(),
+            notes: None,
+            discovery_url_template: standalone
+                .then(|| "https://{host}/$discovery/rest?version={version}".to_string()),
+            endpoint_template: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_with_directory() {
+        let item = |name: &str, version: &str, preferred: bool| discovery::DiscoveryDirectoryItem {
+            id: format!("{}:{}", name, version),
+            name: name.to_string(),
+            version: version.to_string(),
+            preferred,
+            ..discovery::DiscoveryDirectoryItem::testdata()
+        };
+        let items = vec![
+            item("compute", "v1", true),
+            item("compute", "beta", false),
+            item("run", "v1", false),
+            item("run", "v2", true),
+        ];
+        let apis = vec![
+            supported("compute", &["v1", "beta", "alpha2"], false),
+            supported("run", &["v1"], false),
+            supported("retired", &["v1"], false),
+            // Standalone APIs aren't in the directory
+            supported("standalone", &["v1"], true),
+        ];
+        let findings: Vec<_> = compare_with_directory(&apis, &items)
+            .into_iter()
+            .map(|f| (f.api, f.version, f.kind))
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (
+                    "compute".to_string(),
+                    "alpha2".to_string(),
+                    FindingKind::NotUpstream
+                ),
+                (
+                    "run".to_string(),
+                    "v2".to_string(),
+                    FindingKind::MissingPreferred
+                ),
+                (
+                    "retired".to_string(),
+                    "v1".to_string(),
+                    FindingKind::NotUpstream
+                ),
+            ]
+        );
+
+        let audit = ApiAudit {
+            checked_versions: 6,
+            findings: compare_with_directory(&apis, &items),
+        };
+        assert_eq!(
+            audit.check().unwrap_err().to_string(),
+            "2 version(s) of the supported APIs don't exist upstream"
+        );
+        let rendered = audit.render();
+        assert!(rendered.contains("not_upstream"), "{}", rendered);
+        assert!(rendered.ends_with(
+            "Checked 6 version(s) of the supported APIs: 2 not upstream or not found, 1 other finding(s)\n"
+        ));
+        let json = serde_json::to_value(&audit).unwrap();
+        assert_eq!(json["findings"][1]["kind"], "missing_preferred");
+
+        // Only preferred versions missing from zg don't fail
+        let apis = vec![supported("run", &["v1"], false)];
+        let audit = ApiAudit {
+            checked_versions: 1,
+            findings: compare_with_directory(&apis, &items),
+        };
+        assert!(audit.check().is_ok());
+    }
+
+    #[test]
+    fn test_standalone_finding() {
+        let api = supported("standalone", &["v1"], true);
+        assert_eq!(standalone_finding(&api, "v1", Some(Ok(200))), None);
+        let finding = standalone_finding(&api, "v1", Some(Ok(404))).unwrap();
+        assert_eq!(finding.kind, FindingKind::NotFound);
+        assert_eq!(finding.detail, "the discovery document returned HTTP 404");
+        assert!(finding.kind.fails());
+        // Other errors may be transient, and are reported without failing
+        let finding = standalone_finding(&api, "v1", Some(Ok(503))).unwrap();
+        assert_eq!(finding.kind, FindingKind::Unreachable);
+        assert!(!finding.kind.fails());
+        let finding = standalone_finding(
+            &api,
+            "v1",
+            Some(Err(ZgError::Network("refused".to_string()))),
+        )
+        .unwrap();
+        assert_eq!(finding.kind, FindingKind::Unreachable);
+        assert!(!finding.kind.fails());
+        let finding = standalone_finding(&api, "v1", None).unwrap();
+        assert_eq!(finding.kind, FindingKind::Unchecked);
+        assert!(!finding.kind.fails());
+    }
+}
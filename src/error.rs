@@ -0,0 +1,298 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use thiserror::Error;
+
+/// Errors returned across zygen. Each variant maps to a distinct exit code (see `exit_code`).
+#[derive(Error, Debug)]
+pub enum ZgError {
+    #[error("Service '{service}' not found")]
+    ApiNotSupported {
+        service: String,
+        candidates: Vec<String>,
+    },
+
+    #[error("Resource '{resource}' not found for API '{api_id}'.")]
+    ResourceNotFound {
+        api_id: String,
+        resource: String,
+        candidates: Vec<String>,
+    },
+
+    #[error(
+        "Resource '{resource}' is ambiguous for API '{api_id}'. Candidates:{}",
+        ambiguous_candidate_lines(api_id, candidates)
+    )]
+    AmbiguousResource {
+        api_id: String,
+        resource: String,
+        candidates: Vec<String>,
+    },
+
+    #[error("Method '{method}' not found in the resource '{resource}'")]
+    MethodNotFound {
+        method: String,
+        resource: String,
+        candidates: Vec<String>,
+    },
+
+    #[error(
+        "Method '{method}' is ambiguous in the resource '{resource}'. Candidates: {}",
+        candidates.join(", ")
+    )]
+    AmbiguousMethod {
+        method: String,
+        resource: String,
+        candidates: Vec<String>,
+    },
+
+    #[error("Method id '{method_id}' belongs to the service '{service}', not '{api_id}'")]
+    ServiceMismatch {
+        method_id: String,
+        service: String,
+        api_id: String,
+    },
+
+    #[error("Authentication failed: {0}")]
+    AuthFailure(String),
+
+    /// The client certificate of mTLS is missing, can't be read, expired, or was rejected by the server.
+    #[error("Client certificate error: {0}")]
+    ClientCertificate(String),
+
+    #[error("gcloud CLI not found on PATH — install it or use `-H 'Authorization: Bearer TOKEN'` or --credentials-file")]
+    GcloudMissing,
+
+    /// gcloud exited with a non-zero status, e.g., when no account is logged in.
+    #[error("`gcloud {command}` failed: {stderr}")]
+    GcloudFailed { command: String, stderr: String },
+
+    #[error("HTTP request failed with status {status}: {body}")]
+    HttpStatus { status: u16, body: String },
+
+    /// The status is not one of `zg exec --expect-status`.
+    #[error("Expected HTTP status {expected}, got {status}: {body}")]
+    UnexpectedStatus {
+        status: u16,
+        expected: String,
+        body: String,
+    },
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("Invalid config file '{path}': {message}")]
+    InvalidConfig { path: String, message: String },
+
+    #[error("Failed to deserialize: {0}")]
+    Deserialize(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Anything else, typically built from a formatted message (`format!(...).into()`).
+    #[error("{0}")]
+    Other(String),
+}
+
+impl ZgError {
+    /// Exit code of the process when the error reaches main().
+    /// 2 is left for clap's usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ZgError::ApiNotSupported { .. }
+            | ZgError::ResourceNotFound { .. }
+            | ZgError::AmbiguousResource { .. }
+            | ZgError::MethodNotFound { .. }
+            | ZgError::AmbiguousMethod { .. }
+            | ZgError::ServiceMismatch { .. } => 3,
+            ZgError::AuthFailure(_)
+            | ZgError::ClientCertificate(_)
+            | ZgError::GcloudFailed { .. } => 4,
+            ZgError::GcloudMissing => 5,
+            ZgError::HttpStatus { .. } | ZgError::Network(_) => 6,
+            ZgError::Deserialize(_) => 7,
+            ZgError::Io(_) => 8,
+            ZgError::InvalidConfig { .. } => 9,
+            ZgError::UnexpectedStatus { .. } => 10,
+            ZgError::Other(_) => 1,
+        }
+    }
+
+    /// A hint printed after the error message to help users resolve the error, if any.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            ZgError::ApiNotSupported { candidates, .. }
+            | ZgError::ResourceNotFound { candidates, .. }
+            | ZgError::MethodNotFound { candidates, .. }
+                if !candidates.is_empty() =>
+            {
+                Some(format!("Did you mean: {}?", candidates.join(", ")))
+            }
+            ZgError::ApiNotSupported { .. } => {
+                Some("Run `zg ls --all` to find supported services.".to_string())
+            }
+            ZgError::ResourceNotFound { .. } => {
+                Some("Run `zg ls SERVICE` to see the resources.".to_string())
+            }
+            ZgError::AmbiguousResource { .. } => Some(
+                "Specify a more detailed resource path as shown above, or drop --strict to let zg pick one."
+                    .to_string(),
+            ),
+            ZgError::MethodNotFound { .. } => {
+                Some("Run `zg ls SERVICE RESOURCE` to see the methods.".to_string())
+            }
+            ZgError::AuthFailure(_) => Some("Try `gcloud auth login`.".to_string()),
+            ZgError::ClientCertificate(_) => Some(
+                "Give a valid certificate and key with --client-cert and --client-key, or check Endpoint Verification on this device."
+                    .to_string(),
+            ),
+            ZgError::GcloudFailed { .. } => Some(
+                "Run `gcloud auth login` to log in, or check the output of gcloud above.".to_string(),
+            ),
+            ZgError::GcloudMissing => Some(
+                "Install the gcloud CLI (https://cloud.google.com/sdk/docs/install) and make sure it's in your PATH."
+                    .to_string(),
+            ),
+            ZgError::InvalidConfig { .. } => {
+                Some("Fix the config file, or remove it to use the defaults.".to_string())
+            }
+            ZgError::Deserialize(_) => {
+                Some("The cached API definitions may be outdated; try `zg update`.".to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Renders each candidate of an ambiguous resource with the command that points to it uniquely.
+fn ambiguous_candidate_lines(api_id: &str, candidates: &[String]) -> String {
+    candidates
+        .iter()
+        .map(|path| {
+            format!(
+                "\n  - {}  (zg ls {} {})",
+                path,
+                api_id,
+                crate::core::shortest_unique_path(path, candidates)
+            )
+        })
+        .collect()
+}
+
+impl From<String> for ZgError {
+    fn from(message: String) -> Self {
+        ZgError::Other(message)
+    }
+}
+
+impl From<&str> for ZgError {
+    fn from(message: &str) -> Self {
+        ZgError::Other(message.to_string())
+    }
+}
+
+/// Implements `From<$t> for ZgError` converting the error into the given variant with its message.
+macro_rules! from_error {
+    ($($t:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$t> for ZgError {
+                fn from(e: $t) -> Self {
+                    ZgError::$variant(e.to_string())
+                }
+            }
+        )*
+    };
+}
+
+from_error!(
+    serde_json::Error => Deserialize,
+    serde_yaml::Error => Deserialize,
+    rmp_serde::decode::Error => Deserialize,
+    rmp_serde::encode::Error => Other,
+    hyper::Error => Network,
+    hyper::http::Error => Network,
+    hyper::http::uri::InvalidUri => Other,
+    hyper::header::InvalidHeaderName => Other,
+    hyper::header::InvalidHeaderValue => Other,
+    hyper_util::client::legacy::Error => Network,
+    url::ParseError => Other,
+    regex::Error => Other,
+    std::env::VarError => Other,
+    std::fmt::Error => Other,
+    std::string::FromUtf8Error => Other,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_conversions() {
+        let e: ZgError = "plain message".into();
+        assert!(matches!(e, ZgError::Other(ref m) if m == "plain message"));
+
+        let e: ZgError = serde_json::from_str::<serde_json::Value>("{")
+            .unwrap_err()
+            .into();
+        assert!(matches!(e, ZgError::Deserialize(_)));
+
+        let e: ZgError = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+        assert!(matches!(e, ZgError::Io(_)));
+    }
+
+    #[test]
+    fn test_exit_codes_and_hints() {
+        assert_eq!(ZgError::Other("x".to_string()).exit_code(), 1);
+        let e = ZgError::ApiNotSupported {
+            service: "x".to_string(),
+            candidates: vec![],
+        };
+        assert_eq!(e.exit_code(), 3);
+        assert!(e.hint().unwrap().contains("zg ls --all"));
+        assert_eq!(ZgError::GcloudMissing.exit_code(), 5);
+        assert!(ZgError::GcloudMissing.hint().is_some());
+        let e = ZgError::GcloudFailed {
+            command: "auth print-access-token".to_string(),
+            stderr: "ERROR: no active account".to_string(),
+        };
+        assert_eq!(e.exit_code(), 4);
+        assert_eq!(
+            e.to_string(),
+            "`gcloud auth print-access-token` failed: ERROR: no active account"
+        );
+        assert!(e.hint().unwrap().contains("gcloud auth login"));
+
+        let e = ZgError::ResourceNotFound {
+            api_id: "container:v1".to_string(),
+            resource: "cluster".to_string(),
+            candidates: vec!["clusters".to_string()],
+        };
+        assert_eq!(e.exit_code(), 3);
+        assert_eq!(e.hint().unwrap(), "Did you mean: clusters?");
+
+        let e = ZgError::AmbiguousResource {
+            api_id: "container:v1".to_string(),
+            resource: "clusters".to_string(),
+            candidates: vec![
+                "container.projects.locations.clusters".to_string(),
+                "container.projects.zones.clusters".to_string(),
+            ],
+        };
+        assert_eq!(e.exit_code(), 3);
+        assert!(e
+            .to_string()
+            .contains("container.projects.zones.clusters  (zg ls container:v1 zones.clusters)"));
+    }
+}
@@ -23,449 +23,3237 @@ use hyper_rustls::HttpsConnectorBuilder;
 use hyper_util::{
     client::legacy::connect::HttpConnector, client::legacy::Client, rt::TokioExecutor,
 };
-use log::debug;
+use log::{debug, warn, Level};
 
+use serde::Serialize;
 use serde_json::{from_str, json, Value};
+use std::cell::RefCell;
 use std::env;
-use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use url::Url;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use super::api_error;
+use super::auth;
+use super::client;
+use super::color;
+use super::config;
 use super::core;
+use super::credentials;
+use super::error::ZgError;
+use super::history;
+use super::http_cache::HttpCache;
+use super::logging::{self, log_kv};
+use super::metadata;
+use super::mtls;
+use super::output;
+use super::pager;
+use super::saved;
+use super::spinner;
+use super::substitute;
+use super::suggest;
+use super::supported_apis;
+use super::template;
+use super::timing;
+use super::trace;
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
+#[command(group(clap::ArgGroup::new("body").args(["data", "data_binary"])))]
 pub struct ExecArgs {
     /// Required. Service that has the resource to execute a method (e.g., 'spanner').
-    service: String,
+    #[arg(required_unless_present_any = ["rerun", "rerun_id"], conflicts_with_all = ["rerun", "rerun_id"])]
+    service: Option<String>,
 
-    /// Required. Resource that has the method to execute (e.g., 'databases'). Supports resource_path to strictly point an unique resource (e.g., `projects.instances.databases`)
-    resource: String,
+    /// Required. Resource that has the method to execute (e.g., 'databases'). Supports resource_path to strictly point an unique resource (e.g., `projects.instances.databases` or `projects/instances/databases`)
+    #[arg(required_unless_present_any = ["rerun", "rerun_id"])]
+    resource: Option<String>,
 
-    /// Required. Method to execute (e.g., 'create').
-    method: String,
+    /// Method to execute (e.g., 'create'). Can be omitted when RESOURCE is a method id (e.g., `spanner.projects.instances.databases.create`).
+    method: Option<String>,
+
+    /// Values of the path parameters not autofilled, in the order of the method's parameterOrder (see `positional_params`
+    /// in `zg desc`), e.g., `zg exec compute instances get my-instance`. Parameters given with -p are skipped.
+    #[arg(value_name = "VALUE")]
+    values: Vec<String>,
+
+    /// Send the request to this endpoint instead of the base URL of the API, keeping the path (e.g., 'http://localhost:8085'
+    /// for the Pub/Sub emulator, or a Private Service Connect endpoint). A path in it is put before the path of the method.
+    /// Defaults to `[endpoints]` in the config file, or the emulator host in PUBSUB_EMULATOR_HOST or SPANNER_EMULATOR_HOST.
+    #[arg(long, value_name = "URL")]
+    endpoint: Option<String>,
+
+    /// Send the request without the Authorization header (no access token is fetched), e.g., to a local emulator.
+    /// The default when the endpoint comes from an emulator environment variable.
+    #[arg(long)]
+    no_auth: bool,
+
+    /// Reuse the response of the same GET request (the URL and the headers, including the credential) for this many
+    /// seconds, from `http_cache/` of the config directory. Only successful responses are cached, and requests without
+    /// authentication or to other endpoints (--endpoint, emulators, and `[endpoints]`) are never cached.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    cache_ttl: Option<u64>,
+
+    /// With --cache-ttl, send the request instead of using a cached response, and cache the new one.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Send the request to the mTLS host of the API (e.g., 'compute.mtls.googleapis.com') with a client certificate, for
+    /// certificate-based access. The certificate is --client-cert and --client-key, or the one of Endpoint Verification.
+    /// The default when gcloud is set to use the client certificate (`context_aware/use_client_certificate`).
+    #[arg(long)]
+    mtls: bool,
+
+    /// PEM file of the client certificate (chain) to present with --mtls, which is implied.
+    #[arg(long, value_name = "PATH", requires = "client_key")]
+    client_cert: Option<String>,
+
+    /// PEM file of the private key of --client-cert (PKCS#8, PKCS#1, or SEC1, not encrypted).
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    client_key: Option<String>,
 
     /// Extra headers to include in requests. For example, you can override the default Authorization header (`gcloud auth print-access-token`).
     #[arg(short = 'H', long, num_args = 1.., value_parser = parse_headers)]
     headers: Option<Vec<(String, String)>>,
 
     #[arg(short, long, aliases = &["parameters", "parameter", "param"], num_args = 1.., value_parser = parse_params, help = "Parameters to be used in the request. Accept multiple params (e.g., '-p databaseId=xxx -p key1=value1 -p key2=value2')\n\
-    \t(1) Path parameters: Replace placeholders in the URL (e.g., 'v1/xxx/{databaseId}/yyy').\n\
-    \t(2) Query parameters: Add key-value pairs to the query string (e.g., v1/xxx?key1=value1&key2=value2).")]
-    params: Option<Vec<(String, String)>>,
+    \t(1) Path parameters: Replace placeholders in the URL (e.g., 'v1/xxx/{databaseId}/yyy'). They can't be empty.\n\
+    \t(2) Query parameters: Add key-value pairs to the query string (e.g., v1/xxx?key1=value1&key2=value2).\n\
+    \tA boolean parameter can be given as a flag ('-p validateOnly' for 'validateOnly=true'), and 'key=' sends an empty value.")]
+    params: Option<Vec<(String, Option<String>)>>,
 
-    /// HTTP request Body. Used when executing a method with http_method=POST/PUT/PATCH.
-    /// Format should be JSON string (-d '{"name": "foo"}') or a curl-style filename (-d @body.json). When omitted, it defaults to empty JSON (-d '{}').
+    /// HTTP request Body. GET and DELETE methods without a request body in their definition reject it (see --force-body).
+    /// Format should be JSON string (-d '{"name": "foo"}') or a curl-style filename (-d @body.json, or YAML with -d @body.yaml),
+    /// or -d @- to read JSON from stdin. When omitted, POST/PUT/PATCH send empty JSON (-d '{}').
     #[arg(short, long)]
     data: Option<String>,
 
+    /// Send the request body as it is instead of as JSON, e.g., for CSV imports or protobuf bodies (`-p alt=proto`):
+    /// the bytes of the file with @file (or stdin with @-), or the string otherwise. The Content-Type is
+    /// application/octet-stream unless --content-type (or -H) gives it.
+    #[arg(long, value_name = "DATA")]
+    data_binary: Option<String>,
+
+    /// Content-Type of the request body instead of application/json (application/octet-stream with --data-binary).
+    /// A Content-Type header given with -H takes precedence.
+    #[arg(long, value_name = "TYPE")]
+    content_type: Option<String>,
+
+    /// Send --data (or --data-binary) even with a GET or DELETE method that takes no request body, for the rare APIs accepting one.
+    #[arg(long, requires = "body")]
+    force_body: bool,
+
     #[arg(long)]
     equivalent_curl: bool,
-}
 
-/// Parse the parameters in the form of KEY=value
-fn parse_params(s: &str) -> Result<(String, String), String> {
-    let pos = s
-        .find('=')
-        .ok_or("No '=' found. Params must '-p Key=Value'")?;
-    Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
-}
+    /// Fail when the resource is ambiguous instead of picking one of the candidates.
+    #[arg(long, alias = "exact", overrides_with = "no_strict")]
+    strict: bool,
 
-/// Parse the headers in the form of -H "Key: Value"
-fn parse_headers(s: &str) -> Result<(String, String), String> {
-    let pos = s
-        .find(':')
-        .ok_or("No ':' found. HTTP headers must be in the form '-H \"Key: Value\"'")?;
-    let key = s[..pos].trim().to_string();
-    let value = s[pos + 1..].trim().to_string();
-    Ok((key, value))
-}
+    /// Pick one of the candidates of an ambiguous resource, even if `strict` is set in the config file.
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
 
-/// main function to execute a method.
-pub async fn main(
-    args: &ExecArgs,
-    standalone_api_key: Option<String>,
-) -> Result<(), Box<dyn Error>> {
-    let api = core::load_api_file(&args.service, standalone_api_key).await?;
-    debug!("Loaded API: {:?}", &api.id);
+    /// Follow `nextPageToken` of a GET method and merge the list fields of all pages into one response.
+    #[arg(long)]
+    paginate: bool,
 
-    let resource = core::find_resource(&api.id, &api.resources, &args.resource)?;
-    debug!("Found resource.path: {:?}", &resource.path);
+    /// With --paginate, stop after this many pages. The token to resume from is printed to stderr.
+    #[arg(long, requires = "paginate", value_parser = clap::value_parser!(u64).range(1..))]
+    max_pages: Option<u64>,
 
-    let method = core::find_method(resource, &args.method)?;
-    debug!("Found method: {} {}", &method.name, &method.flat_path);
+    /// With --paginate, stop once this many items are fetched, shrinking the page size of the last page to fit.
+    /// The token to resume from is printed to stderr.
+    #[arg(long, requires = "paginate", value_parser = clap::value_parser!(u64).range(1..))]
+    limit: Option<u64>,
 
-    if args.equivalent_curl {
-        println!("{}", generate_curl(&api.base_url, &method, args)?);
-        return Ok(());
-    }
+    /// Start from the page of this token (the `nextPageToken` printed by a previous run), same as `-p pageToken=TOKEN`.
+    #[arg(long, value_name = "TOKEN")]
+    resume_token: Option<String>,
 
-    let url = build_url(&api.base_url, &method, &args.params)?;
-    let headers = build_headers(&args.headers)?;
+    /// Render the response through a Handlebars template instead of printing JSON
+    /// (e.g., '{{#each clusters}}{{name}} {{status}}\n{{/each}}'). `\n` and `\t` are read as a newline and a tab.
+    /// With --paginate, the merged response is rendered.
+    #[arg(long, conflicts_with_all = ["template_file", "equivalent_curl"])]
+    template: Option<String>,
 
-    // Execute the method by sending a request to the URL
-    let res = match method.http_method.as_str() {
-        "GET" => {
-            let client = build_client::<Empty<Bytes>>()?;
-            let uri: Uri = url.parse()?;
-            let mut req = Request::builder().method(Method::GET).uri(uri);
-
-            // Add headers
-            for (key, value) in headers.iter() {
-                req = req.header(key, value);
-            }
+    /// Same as --template, reading the template from the file.
+    #[arg(long, value_name = "PATH", conflicts_with = "equivalent_curl")]
+    template_file: Option<String>,
 
-            let req = req.body(Empty::<Bytes>::new())?;
-            let response = client.request(req).await?;
-            let body_bytes = response.into_body().collect().await?.to_bytes();
-            String::from_utf8(body_bytes.to_vec())?
-        }
-        "DELETE" => {
-            let client = build_client::<Empty<Bytes>>()?;
-            let uri: Uri = url.parse()?;
-            let mut req = Request::builder().method(Method::DELETE).uri(uri);
+    /// Print the response body as it's received, without parsing it as JSON or handling empty and non-JSON responses.
+    #[arg(long, conflicts_with_all = ["paginate", "template", "template_file", "equivalent_curl"])]
+    raw: bool,
 
-            // Add headers
-            for (key, value) in headers.iter() {
-                req = req.header(key, value);
-            }
+    /// Print the response directly instead of through `$PAGER` (`less -FRX` by default), which is used only when stdout
+    /// is a terminal and the response is taller than it.
+    #[arg(long)]
+    no_pager: bool,
 
-            let req = req.body(Empty::<Bytes>::new())?;
-            let response = client.request(req).await?;
-            let body_bytes = response.into_body().collect().await?.to_bytes();
-            String::from_utf8(body_bytes.to_vec())?
-        }
-        "POST" | "PUT" | "PATCH" => {
-            let client = build_client::<Full<Bytes>>()?;
-            debug!("{} request w/ Data: {:?}", &method.http_method, &args.data);
+    /// Print only a summary of the response: the number of items of a list response (of all pages with --paginate),
+    /// its list field, and whether more pages remain (`nextPageToken`). For other responses, the top-level keys.
+    #[arg(long, conflicts_with_all = ["template", "template_file", "raw", "equivalent_curl"])]
+    summary: bool,
 
-            // If no --data option is provided, assume an empty JSON (= `--data '{}'`).
-            let data = args.data.as_deref().unwrap_or("{}");
-            let json_string = prepare_json_string(data)?;
+    /// Fail unless the response has one of these statuses: codes (e.g., 200) or classes (e.g., 2xx), separated by commas
+    /// or given more than once. A matching error status (e.g., `--expect-status 404` after deleting a resource) succeeds.
+    /// Applies to the response after retrying on 401, and to every page with --paginate.
+    #[arg(long, value_name = "STATUS", value_delimiter = ',', value_parser = parse_status_pattern)]
+    expect_status: Option<Vec<StatusPattern>>,
 
-            let hyper_method = match method.http_method.as_str() {
-                "POST" => Method::POST,
-                "PUT" => Method::PUT,
-                "PATCH" => Method::PATCH,
-                _ => return Err(format!("Unsupported HTTP method: {}", method.http_method).into()),
-            };
+    /// Don't expand the template functions in -p values and --data (`{{uuid}}`, `{{now}}`, `{{now+SECONDS}}`, `{{rand:LENGTH}}`,
+    /// and `{{env:VAR}}`) and environment variables (`${env:VAR}`), e.g., when the payload has them as they are. A single
+    /// one can be kept with a backslash (`\{{uuid}}`, `\${env:VAR}`).
+    #[arg(long)]
+    no_template: bool,
 
-            let uri: Uri = url.parse()?;
-            let mut req = Request::builder().method(hyper_method).uri(uri);
+    /// Also expand bare environment variables (`$VAR` and `${VAR}`) in -p values and --data, not only `${env:VAR}`.
+    #[arg(long, conflicts_with = "no_template")]
+    env_expand: bool,
 
-            // Add headers
-            for (key, value) in headers.iter() {
-                req = req.header(key, value);
-            }
+    /// Print how long the request took (total and time to first byte) to stderr, for each page with --paginate.
+    #[arg(long)]
+    show_timing: bool,
 
-            let req = req.body(Full::new(Bytes::from(json_string)))?;
-            let response = client.request(req).await?;
-            let body_bytes = response.into_body().collect().await?.to_bytes();
-            String::from_utf8(body_bytes.to_vec())?
-        }
-        _ => {
-            return Err(format!(
-                "Method '{}' uses unsupported HTTP method '{}'",
-                &method.name, &method.http_method
-            )
-            .into())
-        }
-    };
+    /// Don't show the spinner with the elapsed time on stderr while waiting for the response. It's shown only when
+    /// stderr is a terminal, and not when the output of --format json, yaml, or ndjson is piped.
+    #[arg(long, short = 'q')]
+    quiet: bool,
 
-    debug!("Raw Response: {:?}", &res);
+    /// Ask the API to trace the request in Cloud Trace (`X-Cloud-Trace-Context`), and print the trace id, its Cloud Console
+    /// URL, and the request ids in the response headers (e.g., `x-goog-request-id`) to stderr, e.g., to share with support.
+    #[arg(long)]
+    trace: bool,
 
-    // Print the result to stdout in pretty JSON format
-    let json: Value = if res.is_empty() {
-        json!({})
-    } else {
-        from_str(&res)?
-    };
-    println!("{}", serde_json::to_string_pretty(&json)?);
+    /// Repeat the Nth most recent request in the history (the last one by default): its service, resource, method,
+    /// -p, and --data. -p and --data given with it override the recorded ones (by the parameter name).
+    /// Requires `history.enabled = true` in the config file.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1", conflicts_with = "rerun_id", value_parser = clap::value_parser!(u64).range(1..))]
+    rerun: Option<u64>,
 
-    Ok(())
-}
+    /// Same as --rerun, choosing the request by its id in the history.
+    #[arg(long, value_name = "ID")]
+    rerun_id: Option<String>,
 
-/// Build the URL to send a request to
-fn build_url(
-    base_url: &String,
-    method: &core::ZgMethod,
-    params: &Option<Vec<(String, String)>>,
-) -> Result<String, Box<dyn Error>> {
-    let mut path = method.flat_path.clone();
-    let mut query_params = Vec::new();
-
-    if let Some(params) = params {
-        for (key, value) in params {
-            if path.contains(&format!("{{{}}}", key)) {
-                path = path.replace(&format!("{{{}}}", key), value); // path params
-            } else {
-                query_params.push((key.as_str(), value.as_str())); // query params
-            }
-        }
-    }
+    /// Save the request as NAME in `requests.toml` in the config directory, to run it with `zg run NAME`: the service,
+    /// the resolved resource and method, the positional values, -p, -H (except Authorization), and --data as given.
+    /// The template functions are kept, and expanded at each run.
+    #[arg(long, value_name = "NAME")]
+    save_as: Option<String>,
 
-    // Autofill: replace placeholders (project_id, region, and zone) with values stored in gcloud CLI.
-    // If these autofill targets are specified with -p explicitly, they are already replaced in the previous loop.
-    path = replace_placeholders(&path, core::PATH_PLACEHOLDERS_PROJECT, "core/project")?;
-    path = replace_placeholders(&path, core::PATH_PLACEHOLDERS_REGION, "compute/region")?;
-    path = replace_placeholders(&path, core::PATH_PLACEHOLDERS_ZONE, "compute/zone")?;
+    /// Overwrite the request saved with the name of --save-as without asking.
+    #[arg(long, requires = "save_as")]
+    force: bool,
 
-    let mut url = Url::parse(&format!("{}{}", base_url, path)).expect("Failed to parse URL");
-    if !query_params.is_empty() {
-        url.query_pairs_mut().extend_pairs(&query_params);
-    }
+    /// Write each item of a list response (of all pages with --paginate) to its own pretty-printed JSON file in the
+    /// directory, named by --split-key, and print their paths (and the count to stderr) instead of the response.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["template", "template_file", "raw", "summary", "equivalent_curl"])]
+    split_output: Option<String>,
 
-    debug!("Built URL: {}", &url);
-    Ok(url.to_string())
+    /// The field of the items to name the files of --split-output by, with slashes converted to `__`
+    /// (e.g., `projects__p__secrets__s1.json`). Duplicates get `-N` appended, and items without it are `unnamed-N.json`.
+    #[arg(
+        long,
+        value_name = "FIELD",
+        default_value = "name",
+        requires = "split_output"
+    )]
+    split_key: String,
 }
 
-/// Replace placeholders in the path with values from gcloud config.
-/// Only calls get_gcloud_config_value when placeholders are found in the path.
-fn replace_placeholders(
-    path: &str,
-    placeholders: &[&str],
-    gcloud_key: &str,
-) -> Result<String, Box<dyn Error>> {
-    if placeholders
-        .iter()
-        .any(|&ph| path.contains(&format!("{{{}}}", ph)))
-    {
-        match get_gcloud_config_value(gcloud_key) {
-            Ok(value) => {
-                let mut new_path = path.to_string();
-                for &placeholder in placeholders {
-                    let placeholder_fmt = format!("{{{}}}", placeholder);
-                    new_path = new_path.replace(&placeholder_fmt, &value);
-                }
-                Ok(new_path)
-            }
-            Err(e) => {
-                debug!("{}", e);
-                Ok(path.to_string())
-            }
+/// An expected status of `--expect-status`: a code, or a class by its first digit (`2xx`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatusPattern {
+    Code(u16),
+    Class(u16),
+}
+
+impl StatusPattern {
+    fn matches(&self, status: u16) -> bool {
+        match self {
+            StatusPattern::Code(code) => *code == status,
+            StatusPattern::Class(class) => status / 100 == *class,
         }
-    } else {
-        Ok(path.to_string()) // No placeholders found; return the path as is
     }
 }
 
-/// Get the value of the given key from gcloud CLI
-fn get_gcloud_config_value(key: &str) -> Result<String, Box<dyn Error>> {
-    let output = Command::new("gcloud")
-        .arg("config")
-        .arg("get")
-        .arg(key)
-        .env("PATH", env::var("PATH")?)
-        .output()?;
-
-    let value = String::from_utf8(output.stdout)?.trim().to_string();
-    if value.is_empty() {
-        return Err(format!(
-            "No '{}' found in gcloud config. Consider: 'gcloud config set {} {}'",
-            key,
-            key,
-            key.split('/').next_back().unwrap_or("").to_uppercase()
-        )
-        .into());
+impl std::fmt::Display for StatusPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusPattern::Code(code) => write!(f, "{}", code),
+            StatusPattern::Class(class) => write!(f, "{}xx", class),
+        }
     }
-
-    debug!("Retrieved 'gcloud config get {}' => {:?}", key, &value);
-    Ok(value)
 }
 
-/// Build a hyper client with HTTPS support
-fn build_client<B>(
-) -> Result<Client<hyper_rustls::HttpsConnector<HttpConnector>, B>, Box<dyn Error>>
-where
-    B: hyper::body::Body + Send + 'static,
-    B::Data: Send,
-    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
-{
-    // Install rustls crypto provider
-    let _ = rustls::crypto::ring::default_provider().install_default();
+/// Parse a status code (100-599) or class (1xx-5xx) of --expect-status
+fn parse_status_pattern(s: &str) -> Result<StatusPattern, String> {
+    let invalid = || {
+        format!(
+            "'{}' is not a status code (e.g., 200) or class (e.g., 2xx)",
+            s
+        )
+    };
+    let s = s.trim();
+    let pattern = match s.to_ascii_lowercase().strip_suffix("xx") {
+        Some(class) => StatusPattern::Class(class.parse().map_err(|_| invalid())?),
+        None => StatusPattern::Code(s.parse().map_err(|_| invalid())?),
+    };
+    let first_digit = match pattern {
+        StatusPattern::Code(code) => code / 100,
+        StatusPattern::Class(class) => class,
+    };
+    match (pattern, first_digit) {
+        (StatusPattern::Code(code), _) if code < 100 => Err(invalid()),
+        (_, 1..=5) => Ok(pattern),
+        _ => Err(invalid()),
+    }
+}
 
-    let mut root_store = rustls::RootCertStore::empty();
-    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+/// Maximum length of the body shown when the status is not expected.
+const BODY_EXCERPT_LENGTH: usize = 500;
 
-    let config = rustls::ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
+/// Fails with the status and an excerpt of the body unless the status matches one of the expected patterns.
+fn check_status(expected: &[StatusPattern], status: u16, body: &str) -> Result<(), ZgError> {
+    if expected.iter().any(|pattern| pattern.matches(status)) {
+        return Ok(());
+    }
+    let body = body.trim();
+    let excerpt = match body.char_indices().nth(BODY_EXCERPT_LENGTH) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
+    };
+    Err(ZgError::UnexpectedStatus {
+        status,
+        expected: expected
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        body: excerpt,
+    })
+}
 
-    let https_connector = HttpsConnectorBuilder::new()
-        .with_tls_config(config)
-        .https_or_http()
-        .enable_http1()
-        .enable_http2()
-        .build();
+/// Parse the parameters in the form of KEY=value, or KEY alone for a boolean flag (see `resolve_params`)
+fn parse_params(s: &str) -> Result<(String, Option<String>), String> {
+    match s.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), Some(value.to_string()))),
+        None => Ok((s.to_string(), None)),
+    }
+}
 
-    let client = Client::builder(TokioExecutor::new()).build(https_connector);
+/// -p parameters as given, with None for the flag-style ones (`-p validateOnly`).
+type GivenParams = Vec<(String, Option<String>)>;
 
-    Ok(client)
+/// The recorded -p parameters (of the history or a saved request) with the given ones, which replace all the recorded
+/// values of the parameters.
+fn merged(
+    recorded: &[(String, Option<String>)],
+    given: &Option<GivenParams>,
+) -> Option<GivenParams> {
+    let overrides = given.clone().unwrap_or_default();
+    let mut params: Vec<_> = recorded
+        .iter()
+        .filter(|(key, _)| !overrides.iter().any(|(overridden, _)| overridden == key))
+        .cloned()
+        .collect();
+    params.extend(overrides);
+    (!params.is_empty()).then_some(params)
 }
 
-/// Get access token from gcloud CLI
-fn get_access_token() -> Result<String, Box<dyn Error>> {
-    let output = Command::new("gcloud")
-        .arg("auth")
-        .arg("print-access-token")
-        .env("PATH", env::var("PATH")?)
-        .output()?;
-    let access_token = String::from_utf8(output.stdout)?;
-    Ok(access_token.trim().to_string())
+/// Assigns the positional values to the path parameters of the method not given with -p, in the order of
+/// `ZgMethod::positional_params`, adding them to the -p parameters.
+fn with_positional_values(
+    params: &Option<Vec<(String, Option<String>)>>,
+    values: &[String],
+    method: &core::ZgMethod,
+) -> Result<Option<GivenParams>, ZgError> {
+    if values.is_empty() {
+        return Ok(params.clone());
+    }
+    let given = |name: &str| params.iter().flatten().any(|(key, _)| key == name);
+    let remaining: Vec<&str> = method
+        .positional_params()
+        .into_iter()
+        .filter(|name| !given(name))
+        .collect();
+    if values.len() > remaining.len() {
+        let takes = match remaining.as_slice() {
+            [] => "no positional values".to_string(),
+            names => format!("{} ({})", names.len(), names.join(", ")),
+        };
+        let unexpected: Vec<String> = values[remaining.len()..]
+            .iter()
+            .map(|value| format!("'{}'", value))
+            .collect();
+        return Err(ZgError::Other(format!(
+            "Too many values: {} given, but '{}' takes {}; unexpected {}. Autofilled parameters (project, region, and zone) and query parameters are given with -p KEY=VALUE.",
+            values.len(),
+            method.name,
+            takes,
+            unexpected.join(", ")
+        )));
+    }
+    let mut params = params.clone().unwrap_or_default();
+    for (name, value) in remaining.into_iter().zip(values) {
+        debug!(
+            "Positional value '{}' for the path parameter '{}'",
+            value, name
+        );
+        params.push((name.to_string(), Some(value.clone())));
+    }
+    Ok(Some(params))
 }
 
-/// Build headers for the request
-fn build_headers(
-    custom_headers: &Option<Vec<(String, String)>>,
-) -> Result<HeaderMap<HeaderValue>, Box<dyn Error>> {
-    let mut headers = HeaderMap::new();
+/// Resolves the -p parameters of the method: flag-style parameters (`-p validateOnly`) become `true` if the parameter
+/// is a boolean, and empty path parameters are rejected as they'd leave an empty segment (`//`) in the URL.
+fn resolve_params(
+    params: &Option<Vec<(String, Option<String>)>>,
+    method: &core::ZgMethod,
+    api: &core::ZgApi,
+) -> Result<Option<Vec<(String, String)>>, ZgError> {
+    let Some(params) = params else {
+        return Ok(None);
+    };
+    let known_params = method.query_params.iter().chain(&api.common_params);
+    params
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Some(value) => value.clone(),
+                None => {
+                    let param_type = known_params
+                        .clone()
+                        .find(|param| &param.name == key)
+                        .and_then(|param| param.param_type.as_deref());
+                    if param_type != Some("boolean") {
+                        return Err(ZgError::Other(format!(
+                            "-p {} has no value; only boolean parameters can be given without one{}. Use -p {}=VALUE (or -p {}= for an empty value).",
+                            key,
+                            param_type.map(|t| format!(" ('{}' is {})", key, t)).unwrap_or_default(),
+                            key,
+                            key
+                        )));
+                    }
+                    "true".to_string()
+                }
+            };
+            if value.is_empty() && method.flat_path.contains(&format!("{{{}}}", key)) {
+                return Err(ZgError::Other(format!(
+                    "Path parameter '{}' is empty, which would leave an empty segment in the URL. Give it a value with -p {}=VALUE.",
+                    key, key
+                )));
+            }
+            Ok((key.clone(), value))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
 
-    // Get access token from gcloud CLI
-    let access_token = get_access_token()?;
+/// Parse the headers in the form of -H "Key: Value"
+fn parse_headers(s: &str) -> Result<(String, String), String> {
+    let pos = s
+        .find(':')
+        .ok_or("No ':' found. HTTP headers must be in the form '-H \"Key: Value\"'")?;
+    let key = s[..pos].trim().to_string();
+    let value = s[pos + 1..].trim().to_string();
+    Ok((key, value))
+}
 
-    // Inject 'Authorization' header with the (Bearer) access token
-    headers.insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("Bearer {}", access_token))?,
-    );
+impl ExecArgs {
+    /// Returns the arguments to repeat the request in the history, with -p and --data given on the command line
+    /// overriding the recorded ones: a -p parameter replaces all the recorded values of the parameter.
+    fn with_rerun(&self, entry: &history::Entry) -> ExecArgs {
+        let mut args = self.clone();
+        args.service = Some(entry.service.clone());
+        args.resource = Some(entry.resource.clone());
+        args.method = entry.method.clone();
+        args.values = entry.values.clone();
+        args.params = merged(&entry.params, &self.params);
+        args.merge_body(&entry.data, &entry.data_binary);
+        args.content_type = self
+            .content_type
+            .clone()
+            .or_else(|| entry.content_type.clone());
+        args.force_body = self.force_body || entry.force_body;
+        args.rerun = None;
+        args.rerun_id = None;
+        args
+    }
 
-    // Inject 'Content-Type' header with 'application/json'
-    headers.insert(
-        "Content-Type",
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
+    /// Returns the arguments to run the saved request, with -p, -H, and --data given on the command line overriding the
+    /// saved ones: a -p parameter (or a header) replaces all the saved values of it. The service, resource, method, and
+    /// positional values are taken from the command line, which `zg run` builds from the saved request.
+    pub(crate) fn with_saved(&self, saved: &saved::SavedRequest) -> Result<ExecArgs, ZgError> {
+        let mut args = self.clone();
+        let params: Vec<_> = saved
+            .params
+            .iter()
+            .map(|param| parse_params(param))
+            .collect::<Result<_, _>>()?;
+        args.params = merged(&params, &self.params);
+        let headers: Vec<_> = saved
+            .headers
+            .iter()
+            .map(|header| parse_headers(header))
+            .collect::<Result<_, _>>()?;
+        let overrides = self.headers.clone().unwrap_or_default();
+        let mut headers: Vec<_> = headers
+            .into_iter()
+            .filter(|(key, _)| {
+                !overrides
+                    .iter()
+                    .any(|(overridden, _)| overridden.eq_ignore_ascii_case(key))
+            })
+            .collect();
+        headers.extend(overrides);
+        args.headers = (!headers.is_empty()).then_some(headers);
+        args.merge_body(&saved.data, &saved.data_binary);
+        args.content_type = self
+            .content_type
+            .clone()
+            .or_else(|| saved.content_type.clone());
+        args.force_body = self.force_body || saved.force_body;
+        Ok(args)
+    }
 
-    if let Some(hs) = custom_headers {
-        for (key, value) in hs.iter() {
-            headers.insert(key.parse::<HeaderName>()?, value.parse::<HeaderValue>()?);
+    /// Takes the request body (--data or --data-binary) of a recorded or saved request, unless one is given on the
+    /// command line, which replaces it whichever of them it is.
+    fn merge_body(&mut self, data: &Option<String>, data_binary: &Option<String>) {
+        if self.data.is_none() && self.data_binary.is_none() {
+            self.data = data.clone();
+            self.data_binary = data_binary.clone();
         }
     }
-    debug!("Headers: {:?}", headers);
-
-    Ok(headers)
-}
-
-/// Prepares the JSON string from the given data argument.
-/// If the data starts with '@', it reads the content from the file.
-/// Otherwise, it treats the data as a JSON string.
-fn prepare_json_string(data: &str) -> Result<String, Box<dyn Error>> {
-    let json_data: Value = if data.starts_with('@') {
-        let filename = data.trim_start_matches('@');
-        debug!("Reading data from file: {}", filename);
-        let file_content = fs::read_to_string(filename)
-            .map_err(|e| format!("Failed to read file '{}': {}", filename, e))?;
-        serde_json::from_str(&file_content)
-            .map_err(|e| format!("Invalid JSON syntax in file '{}': {}", filename, e))?
-    } else {
-        serde_json::from_str(data).map_err(|e| format!("Invalid JSON syntax: {}", e))?
-    };
 
-    let json_string = serde_json::to_string(&json_data)
-        .map_err(|e| format!("Failed to serialize JSON data: {}", e))?;
-    Ok(json_string)
-}
+    /// Returns the arguments to save with --save-as, for the resolved resource and method. The Authorization header
+    /// is not saved, not to store the token.
+    pub(crate) fn saved_request(
+        &self,
+        service: &str,
+        resource: &str,
+        method: &str,
+    ) -> saved::SavedRequest {
+        let headers = self.headers.iter().flatten().filter(|(key, _)| {
+            let authorization = key.eq_ignore_ascii_case("Authorization");
+            if authorization {
+                warn!("The Authorization header is not saved with --save-as");
+            }
+            !authorization
+        });
+        saved::SavedRequest {
+            service: service.to_string(),
+            resource: resource.to_string(),
+            method: method.to_string(),
+            values: self.values.clone(),
+            params: self
+                .params
+                .iter()
+                .flatten()
+                .map(|(key, value)| match value {
+                    Some(value) => format!("{}={}", key, value),
+                    None => key.clone(),
+                })
+                .collect(),
+            headers: headers
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect(),
+            data: self.data.clone(),
+            data_binary: self.data_binary.clone(),
+            content_type: self.content_type.clone(),
+            force_body: self.force_body,
+        }
+    }
 
-/// Generates an equivalent curl command for the given HTTP method and arguments.
-fn generate_curl(
-    base_url: &String,
-    method: &core::ZgMethod,
-    args: &ExecArgs,
-) -> Result<String, Box<dyn Error>> {
-    let mut curl_command = format!("curl -X {}", method.http_method);
+    /// Returns the arguments with METHOD moved to the front of the positional values, for a method id given as RESOURCE
+    /// (e.g., `zg exec spanner projects.instances.get my-instance`, where clap takes `my-instance` for METHOD).
+    fn with_method_as_value(&self) -> ExecArgs {
+        ExecArgs {
+            method: None,
+            values: self.method.iter().chain(&self.values).cloned().collect(),
+            ..self.clone()
+        }
+    }
 
-    let mut custom_header_keys = Vec::<String>::new();
-    if let Some(headers) = &args.headers {
-        for (key, value) in headers {
-            curl_command.push_str(&format!(" \\\n  -H \"{}: {}\"", key, value));
-            custom_header_keys.push(key.to_lowercase());
+    /// Returns the history entry of the arguments. --data and --data-binary are recorded only with
+    /// `history.record_bodies`.
+    fn history_entry(&self, service: &str, resource: &str) -> history::Entry {
+        let record_bodies = config::current().history.record_bodies.unwrap_or(false);
+        history::Entry {
+            method: self.method.clone(),
+            values: self.values.clone(),
+            params: self.params.clone().unwrap_or_default(),
+            data: self.data.clone().filter(|_| record_bodies),
+            data_binary: self.data_binary.clone().filter(|_| record_bodies),
+            data_omitted: (self.data.is_some() || self.data_binary.is_some()) && !record_bodies,
+            content_type: self.content_type.clone(),
+            force_body: self.force_body,
+            ..history::Entry::new(service, resource)
         }
     }
 
-    if !custom_header_keys.contains(&"authorization".to_string()) {
-        curl_command
-            .push_str(" \\\n  -H \"Authorization: Bearer $(gcloud auth print-access-token)\"");
+    /// The `zg exec` command line of the arguments (without the other flags), quoted for shells.
+    fn command_line(&self) -> String {
+        let mut words = vec!["zg", "exec"];
+        words.extend(self.service.as_deref());
+        words.extend(self.resource.as_deref());
+        words.extend(self.method.as_deref());
+        words.extend(self.values.iter().map(String::as_str));
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .flatten()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", key, value),
+                None => key.clone(),
+            })
+            .collect();
+        for param in &params {
+            words.extend(["-p", param.as_str()]);
+        }
+        if let Some(data) = &self.data {
+            words.extend(["-d", data.as_str()]);
+        }
+        if let Some(data) = &self.data_binary {
+            words.extend(["--data-binary", data.as_str()]);
+        }
+        if let Some(content_type) = &self.content_type {
+            words.extend(["--content-type", content_type.as_str()]);
+        }
+        if self.force_body {
+            words.push("--force-body");
+        }
+        shlex::try_join(words).unwrap_or_else(|_| format!("{:?}", self))
     }
 
-    if !custom_header_keys.contains(&"content-type".to_string()) {
-        curl_command.push_str(" \\\n  -H \"Content-Type: application/json; charset=utf-8\"");
+    /// Returns the arguments with the template functions and environment variables in -p values and --data (including
+    /// the content of `@FILE`) expanded (see `substitute::expand_with`). Fails with all the environment variables referenced
+    /// but not set, before expanding anything.
+    fn with_functions_expanded(&self) -> Result<ExecArgs, ZgError> {
+        self.with_functions_expanded_with(|name| std::env::var(name).ok())
     }
 
-    if let Some(data) = &args.data {
-        let json_string = prepare_json_string(data)?; // If --data @filename, expand the content here; otherwise, treat as JSON string
-        let json_data: Value = serde_json::from_str(&json_string)?;
-        let mut json_pretty = serde_json::to_string_pretty(&json_data)?;
+    /// `with_functions_expanded` with the environment variables looked up by `env`.
+    fn with_functions_expanded_with(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<ExecArgs, ZgError> {
+        let now = std::time::SystemTime::now();
+        let mut args = self.clone();
+        // The content of the data file, which is given as JSON with the expanded content
+        let data_file = match args.data.as_deref().and_then(|data| data.strip_prefix('@')) {
+            Some(filename) if filename != "-" => {
+                Some((filename.to_string(), read_data_file(filename)?))
+            }
+            _ => None,
+        };
+        let mut values: Vec<&str> = args.values.iter().map(String::as_str).collect();
+        if let Some(params) = &args.params {
+            values.extend(params.iter().filter_map(|(_, value)| value.as_deref()));
+        }
+        match &data_file {
+            Some((_, content)) => values.push(content),
+            None => values.extend(args.data.as_deref()),
+        }
+        let unset = substitute::unset_variables_with(values, args.env_expand, &env);
+        if !unset.is_empty() {
+            return Err(format!("Environment variables not set: {}", unset.join(", ")).into());
+        }
 
-        // If the JSON data is not empty, add a newline before the JSON string
-        if !(json_data.is_object() && json_data.as_object().unwrap().is_empty()) {
-            json_pretty = format!("\n{}", json_pretty);
+        for value in &mut args.values {
+            *value = substitute::expand_with(value, args.env_expand, now, &env)?;
         }
-        curl_command.push_str(&format!(" \\\n  -d '{}'", json_pretty));
+        if let Some(params) = &mut args.params {
+            for value in params.iter_mut().filter_map(|(_, value)| value.as_mut()) {
+                *value = substitute::expand_with(value, args.env_expand, now, &env)?;
+            }
+        }
+        match data_file {
+            Some((filename, content)) => {
+                let expanded = substitute::expand_with(&content, args.env_expand, now, &env)?;
+                if expanded != content {
+                    let json_data = parse_data_file(&filename, &expanded)?;
+                    args.data = Some(serde_json::to_string(&json_data)?);
+                }
+            }
+            None => {
+                if let Some(data) = &mut args.data {
+                    *data = substitute::expand_with(data, args.env_expand, now, &env)?;
+                }
+            }
+        }
+        Ok(args)
     }
 
-    curl_command.push_str(&format!(
-        " \\\n  \"{}\"",
-        build_url(base_url, method, &args.params)?
-    ));
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.strict = config::resolve_flag(self.strict, self.no_strict, config.exec.strict);
+    }
 
-    Ok(curl_command)
+    /// Whether the output is decided by `--template`, `--template-file`, `--raw`, or `--split-output`, which replace
+    /// `--format`.
+    pub fn overrides_format(&self) -> bool {
+        self.template.is_some()
+            || self.template_file.is_some()
+            || self.raw
+            || self.split_output.is_some()
+    }
+
+    /// Returns the template given with --template or --template-file.
+    fn load_template(&self) -> Result<Option<String>, ZgError> {
+        match (&self.template, &self.template_file) {
+            (Some(template), _) => Ok(Some(template::unescape(template))),
+            (None, Some(path)) => Ok(Some(fs::read_to_string(path).map_err(|e| {
+                ZgError::Other(format!(
+                    "Failed to read the template file '{}': {}",
+                    path, e
+                ))
+            })?)),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Summary of a response printed with `--summary`: the number of items and the list field of a list response,
+/// or the top-level keys of others.
+#[derive(Serialize, Debug, Default, PartialEq)]
+struct Summary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_page_token: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keys: Option<Vec<String>>,
+}
 
-    #[test]
-    fn test_build_url_with_path_params() {
-        let base_url = "https://example.com/".to_string();
-        let method = core::ZgMethod {
-            flat_path: "v1/{xyzId}/{locationId}/instances".to_string(),
-            ..core::ZgMethod::testdata()
+impl Summary {
+    fn of(response: &Value) -> Self {
+        let has_next_page = response
+            .get("nextPageToken")
+            .and_then(Value::as_str)
+            .is_some_and(|token| !token.is_empty());
+        let list = |count, field: Option<&str>| Summary {
+            count: Some(count),
+            field: field.map(str::to_string),
+            next_page_token: Some(has_next_page),
+            keys: None,
         };
-        let params = Some(vec![
-            ("xyzId".to_string(), "my-project".to_string()),
-            ("locationId".to_string(), "us-central1".to_string()),
-        ]);
-        let url = build_url(&base_url, &method, &params).unwrap();
-        assert_eq!(
-            url,
-            "https://example.com/v1/my-project/us-central1/instances"
-        );
+        match (response, output::list_field(response)) {
+            (Value::Array(items), _) => list(items.len(), None),
+            (_, Some((field, items))) => list(items.len(), Some(field)),
+            // List responses without items omit the list field (e.g., `{}`)
+            (Value::Object(object), None)
+                if object
+                    .keys()
+                    .all(|key| ["nextPageToken", "unreachable"].contains(&key.as_str())) =>
+            {
+                list(0, None)
+            }
+            (Value::Object(object), None) => Summary {
+                keys: Some(object.keys().cloned().collect()),
+                ..Summary::default()
+            },
+            _ => Summary::default(),
+        }
     }
+}
 
-    #[test]
-    fn test_build_url_with_query_params() {
-        let base_url = "https://example.com/".to_string();
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.count, &self.keys) {
+            (Some(count), _) => {
+                write!(f, "{} item{}", count, if count == 1 { "" } else { "s" })?;
+                if let Some(field) = &self.field {
+                    write!(f, " in '{}'", field)?;
+                }
+                match self.next_page_token {
+                    Some(true) => write!(f, "; more pages remain (nextPageToken)"),
+                    _ => write!(f, "; no more pages"),
+                }
+            }
+            (None, Some(keys)) => write!(f, "Not a list; top-level keys: {}", keys.join(", ")),
+            (None, None) => write!(f, "Not a list"),
+        }
+    }
+}
+
+/// main function to execute a method.
+pub async fn main(args: &ExecArgs, standalone_api_key: Option<String>) -> Result<(), ZgError> {
+    // Read the template first not to send the request when the file is missing
+    let template = args.load_template()?;
+    let output = execute(
+        args,
+        standalone_api_key,
+        None,
+        spinner::enabled(args.quiet, false),
+    )
+    .await?;
+    if args.summary {
+        println!("{}", Summary::of(&success(output)?.into_value()));
+        return Ok(());
+    }
+    if let Some(template) = template {
+        print!(
+            "{}",
+            template::render(&template, &success(output)?.into_value())?
+        );
+        return Ok(());
+    }
+    if let Some(dir) = &args.split_output {
+        if let Output::Error { status, body } = &output {
+            return Err(error_status(*status, body));
+        }
+        let paths = output::write_split(Path::new(dir), &output.into_value(), &args.split_key)?;
+        for path in &paths {
+            println!("{}", path.display());
+        }
+        eprintln!("Wrote {} file(s) to {}", paths.len(), dir);
+        return Ok(());
+    }
+    match output {
+        // Print the result to stdout in pretty JSON format
+        Output::Json(json) => pager::print(&format!("{}\n", color::json(&json)?), !args.no_pager)?,
+        Output::Error { status, body } => return Err(error_status(status, &body)),
+        Output::NoContent(status) => eprintln!("(no content, HTTP {})", status),
+        Output::Text(body) if args.raw => print!("{}", body),
+        Output::Text(text) => pager::print(
+            &format!("{}\n", text.strip_suffix('\n').unwrap_or(&text)),
+            !args.no_pager,
+        )?,
+    }
+    Ok(())
+}
+
+/// Returns the error of the response, with the error payload rendered by `api_error::render` (or as JSON if it's not
+/// in the standard format). The raw JSON follows with --debug. Bodies that are not JSON are given as they are.
+fn error_status(status: u16, body: &Value) -> ZgError {
+    if let Value::String(text) = body {
+        let body = match text.trim().is_empty() {
+            true => "(no content)".to_string(),
+            false => text.clone(),
+        };
+        return ZgError::HttpStatus { status, body };
+    }
+    let raw = serde_json::to_string_pretty(body).unwrap_or_else(|_| body.to_string());
+    let body = match api_error::render(body) {
+        Some(rendered) if log::log_enabled!(Level::Debug) => format!("{}\n{}", rendered, raw),
+        Some(rendered) => rendered,
+        None => raw,
+    };
+    ZgError::HttpStatus { status, body }
+}
+
+/// Fails with `error_status` when the output is an error response, so that API errors exit with the same status in
+/// every output format.
+fn success(output: Output) -> Result<Output, ZgError> {
+    match output {
+        Output::Error { status, body } => Err(error_status(status, &body)),
+        output => Ok(output),
+    }
+}
+
+/// Executes the method and prints the response in `format` (`--format json|yaml`), or the equivalent curl command as
+/// a string with `--equivalent-curl`. Empty responses are `{}`, and non-JSON responses (or any with --raw) are strings.
+/// With --summary, the summary of the response is printed instead (see `Summary`).
+/// Error responses are printed as they are, and then fail with `error_status` as in the text format.
+pub async fn print(
+    args: &ExecArgs,
+    standalone_api_key: Option<String>,
+    format: output::Format,
+) -> Result<(), ZgError> {
+    let progress = spinner::enabled(args.quiet, true);
+    let output = execute(args, standalone_api_key, None, progress).await?;
+    if args.summary {
+        let summary = Summary::of(&success(output)?.into_value());
+        return output::print(&summary, format);
+    }
+    match output {
+        Output::Error { status, body } => {
+            output::print(&body, format)?;
+            Err(error_status(status, &body))
+        }
+        output => output::print(&output.into_value(), format),
+    }
+}
+
+/// Executes the method and prints the response as NDJSON (`--format ndjson`, see `output::write_ndjson`).
+/// With --paginate, each page is printed as it's fetched instead of merging all pages in memory.
+pub async fn ndjson(args: &ExecArgs, standalone_api_key: Option<String>) -> Result<(), ZgError> {
+    let progress = spinner::enabled(args.quiet, true);
+    if args.summary {
+        let output = execute(args, standalone_api_key, None, progress).await?;
+        let summary = serde_json::to_value(Summary::of(&success(output)?.into_value()))?;
+        return output::write_ndjson(&mut std::io::stdout().lock(), &summary);
+    }
+    let mut stdout = std::io::stdout().lock();
+    let mut print_page = |page: Value| {
+        // Empty pages (e.g., `{}` for no more items) have nothing to print
+        if page.as_object().is_some_and(|page| page.is_empty()) {
+            return Ok(());
+        }
+        output::write_ndjson(&mut stdout, &page)?;
+        Ok(stdout.flush()?)
+    };
+    let output = execute(args, standalone_api_key, Some(&mut print_page), progress).await?;
+    match output {
+        _ if args.paginate => Ok(()),
+        Output::Error { status, body } => {
+            print_page(body.clone())?;
+            Err(error_status(status, &body))
+        }
+        output => print_page(output.into_value()),
+    }
+}
+
+/// Finds the method by RESOURCE and METHOD.
+fn find_resource_method<'a>(
+    api: &'a core::ZgApi,
+    resource_arg: &str,
+    method_name: &str,
+    strict: bool,
+) -> Result<(&'a core::ZgResource, core::ZgMethod), ZgError> {
+    let resource = core::find_api_resource(api, resource_arg, strict)?;
+    Ok((resource, core::find_method(resource, method_name)?))
+}
+
+/// Executes the method. With --paginate and `on_page`, the pages are passed to it as they're fetched, and `Value::Null` is
+/// returned instead of the merged response. With `progress`, a spinner is shown while waiting for each response.
+async fn execute(
+    args: &ExecArgs,
+    standalone_api_key: Option<String>,
+    on_page: Option<&mut dyn FnMut(Value) -> Result<(), ZgError>>,
+    progress: bool,
+) -> Result<Output, ZgError> {
+    let rerun = match (args.rerun, &args.rerun_id) {
+        (Some(n), _) => Some(history::find(&history::Selector::Nth(n as usize))?),
+        (None, Some(id)) => Some(history::find(&history::Selector::Id(id.clone()))?),
+        (None, None) => None,
+    };
+    let rerun_args;
+    let args = match &rerun {
+        Some(entry) => {
+            rerun_args = args.with_rerun(entry);
+            eprintln!(
+                "Rerunning {} ({}): {}",
+                entry.id,
+                entry.time,
+                rerun_args.command_line()
+            );
+            if entry.data_omitted && args.data.is_none() && args.data_binary.is_none() {
+                warn!(
+                    "The request body of {} wasn't recorded (history.record_bodies is off); rerunning without it. Give it with -d or --data-binary.",
+                    entry.id
+                );
+            }
+            &rerun_args
+        }
+        None => args,
+    };
+    let (Some(service), Some(resource_arg)) = (args.service.as_deref(), args.resource.as_deref())
+    else {
+        return Err("SERVICE and RESOURCE are required".into());
+    };
+    let api = core::load_api_file(service, standalone_api_key.clone()).await?;
+    debug!("Loaded API: {:?}", &api.id);
+    logging::set_context("api_id", &api.id);
+
+    let ((resource, method), method_is_value) = match &args.method {
+        Some(method_name) => {
+            match find_resource_method(&api, resource_arg, method_name, args.strict) {
+                Ok(found) => (found, false),
+                // `zg exec SERVICE METHOD_ID VALUE...`: the first value was taken for METHOD
+                Err(err) => match core::find_method_by_id(&api, resource_arg, args.strict) {
+                    Ok(found) => (found, true),
+                    Err(_) => return Err(err),
+                },
+            }
+        }
+        None => (
+            core::find_method_by_id(&api, resource_arg, args.strict)?,
+            false,
+        ),
+    };
+    let shifted;
+    let args = match method_is_value {
+        true => {
+            shifted = args.with_method_as_value();
+            &shifted
+        }
+        false => args,
+    };
+    debug!("Found resource.path: {:?}", &resource.path);
+    debug!("Found method: {} {}", &method.name, &method.flat_path);
+
+    let mut history_entry = args.history_entry(service, resource_arg);
+    // Saved before the template functions are expanded, to be expanded at each run
+    let unexpanded = args;
+
+    // Expanded first so that --equivalent-curl shows what's sent
+    let expanded;
+    let args = match args.no_template {
+        true => args,
+        false => {
+            expanded = args.with_functions_expanded()?;
+            &expanded
+        }
+    };
+    let api_key = api_key_auth(&api.id, standalone_api_key);
+    let endpoint = resolve_endpoint(args, service, &api.id, |var| env::var(var).ok());
+    if let Some(endpoint) = &endpoint {
+        debug!("Sending the request to the endpoint {}", endpoint.url);
+    }
+
+    if let Some(name) = &args.save_as {
+        let request = unexpanded.saved_request(
+            service,
+            resource.path.as_deref().unwrap_or(resource_arg),
+            &method.name,
+        );
+        saved::save(name, &request, args.force)?;
+    }
+
+    let params = with_positional_values(&args.params, &args.values, &method)?;
+    let params = resolve_params(&params, &method, &api)?;
+    let known_params = method.query_params.iter().chain(&api.common_params);
+    for name in duplicated_query_params(known_params, &params) {
+        warn!(
+            "Query parameter '{}' is given more than once, but it doesn't accept multiple values; the API may reject the request or use only one of them.",
+            name
+        );
+    }
+
+    let identity = client_identity(args, endpoint.as_ref())?;
+    let destination = Destination {
+        universe_domain: universe_domain(gcloud_auth(args, api_key.as_deref(), endpoint.as_ref())),
+        mtls: identity.is_some()
+            || (args.equivalent_curl && (args.mtls || args.client_cert.is_some())),
+        endpoint,
+    };
+    if let (true, Some(universe_domain)) = (destination.mtls, &destination.universe_domain) {
+        return Err(format!(
+            "mTLS hosts are in {}, not in the universe domain {}",
+            config::DEFAULT_UNIVERSE_DOMAIN,
+            universe_domain
+        )
+        .into());
+    }
+    if args.equivalent_curl {
+        return Ok(Output::Text(generate_curl(
+            &api,
+            &method,
+            args,
+            &params,
+            api_key.as_deref(),
+            &destination,
+        )?));
+    }
+
+    let params = with_api_key(
+        &with_pretty_print_disabled(&api, &params),
+        api_key.as_deref(),
+    );
+    let access_token = match api_key {
+        Some(_) => None,
+        None if no_auth(args, destination.endpoint.as_ref()) => None,
+        None => Some(access_token().await?),
+    };
+    let headers = RefCell::new(build_headers(
+        &args.headers,
+        access_token.as_deref(),
+        content_type(args),
+    )?);
+    // The token is refreshed on 401 unless the Authorization header is given with -H
+    let reauth = access_token.is_some() && !has_header(&args.headers, "Authorization");
+    // A trace context given with -H is sent as is
+    let trace = (args.trace && !has_header(&args.headers, trace::HEADER)).then(|| {
+        let trace = trace::TraceContext::new();
+        logging::set_context("trace_id", &trace.trace_id);
+        trace
+    });
+    if let Some(trace) = &trace {
+        headers
+            .borrow_mut()
+            .insert(trace::HEADER, HeaderValue::from_str(&trace.header_value())?);
+    }
+    history_entry.trace_id = trace.as_ref().map(|trace| trace.trace_id.clone());
+    let body = match &args.data_binary {
+        Some(data) => Some(binary_body(data, args.force_body, &method)?),
+        None => request_body(args, &method)?.map(Bytes::from),
+    };
+    history::record(&history_entry);
+    let cache = response_cache(args, &method, &destination);
+
+    if args.paginate {
+        if method.http_method != "GET" {
+            return Err(format!(
+                "--paginate works with GET methods, but '{}' uses {}",
+                &method.name, &method.http_method
+            )
+            .into());
+        }
+        let page_size_param = PAGE_SIZE_PARAMS
+            .into_iter()
+            .find(|name| method.query_params.iter().any(|qp| qp.name == *name));
+        let headers = &headers;
+        let api_id = api.id.as_str();
+        let expect_status = args.expect_status.as_deref();
+        let pages = std::cell::Cell::new(0);
+        let show_timing = args.show_timing;
+        let trace = trace.as_ref();
+        let identity = identity.as_ref();
+        let body = &body;
+        let cache = cache.as_ref();
+        let fetch = |page_token, max_items: Option<u64>| {
+            let params = page_params(&params, page_token, page_size_param.zip(max_items));
+            let url = build_url(&api, &method, &params, &destination);
+            pages.set(pages.get() + 1);
+            let page = pages.get();
+            let message = format!("{} (page {}, {} fetched)", method.id, page, page - 1);
+            async move {
+                let url = url?;
+                let res = send_cached(
+                    cache,
+                    &url,
+                    headers,
+                    spinner::with_spinner(
+                        progress,
+                        message,
+                        send_with_reauth(
+                            headers,
+                            reauth.then_some(refresh_access_token),
+                            |headers| send(Method::GET, &url, headers, body.clone(), identity),
+                        ),
+                    ),
+                )
+                .await;
+                if let Some(trace) = trace {
+                    if page == 1 {
+                        print_trace(trace);
+                    }
+                    print_request_ids(res.as_ref().ok());
+                }
+                let res = expected_failure(res, expect_status)?;
+                if show_timing {
+                    eprintln!("Page {}: HTTP {} in {}", page, res.status, res.timing);
+                }
+                if let Some(expected) = expect_status {
+                    check_status(expected, res.status, &res.body)?;
+                }
+                parse_response(api_id, &res).map(Output::into_value)
+            }
+        };
+        let (response, next_page_token) = match on_page {
+            Some(on_page) => {
+                let token = for_each_page(
+                    args.resume_token.clone(),
+                    args.max_pages,
+                    args.limit,
+                    fetch,
+                    on_page,
+                )
+                .await?;
+                (Value::Null, token)
+            }
+            None => {
+                fetch_pages(args.resume_token.clone(), args.max_pages, args.limit, fetch).await?
+            }
+        };
+        if let Some(token) = next_page_token {
+            eprintln!(
+                "More results are available. Resume with: --resume-token {}",
+                token
+            );
+        }
+        return Ok(Output::Json(response));
+    }
+
+    let params = page_params(&params, args.resume_token.clone(), None);
+    let url = build_url(&api, &method, &params, &destination)?;
+
+    let hyper_method = match method.http_method.as_str() {
+        "GET" => Method::GET,
+        "DELETE" => Method::DELETE,
+        "POST" => Method::POST,
+        "PUT" => Method::PUT,
+        "PATCH" => Method::PATCH,
+        _ => {
+            return Err(format!(
+                "Method '{}' uses unsupported HTTP method '{}'",
+                &method.name, &method.http_method
+            )
+            .into())
+        }
+    };
+
+    // Execute the method by sending a request to the URL
+    let res = send_cached(
+        cache.as_ref(),
+        &url,
+        &headers,
+        spinner::with_spinner(
+            progress,
+            method.id.clone(),
+            send_with_reauth(
+                &headers,
+                reauth.then_some(refresh_access_token),
+                |headers| {
+                    send(
+                        hyper_method.clone(),
+                        &url,
+                        headers,
+                        body.clone(),
+                        identity.as_ref(),
+                    )
+                },
+            ),
+        ),
+    )
+    .await;
+    if let Some(trace) = &trace {
+        print_trace(trace);
+        print_request_ids(res.as_ref().ok());
+    }
+    let res = expected_failure(res, args.expect_status.as_deref())?;
+    if args.show_timing {
+        eprintln!("HTTP {} in {}", res.status, res.timing);
+    }
+
+    let Some(expected) = &args.expect_status else {
+        if args.raw {
+            return Ok(Output::Text(res.body));
+        }
+        return parse_response(&api.id, &res);
+    };
+    check_status(expected, res.status, &res.body)?;
+    if args.raw {
+        return Ok(Output::Text(res.body));
+    }
+    // The expected error is the response to print, not a failure
+    match parse_response(&api.id, &res)? {
+        Output::Error {
+            status,
+            body: Value::String(text),
+        } if text.trim().is_empty() => Ok(Output::NoContent(status)),
+        Output::Error {
+            body: Value::String(text),
+            ..
+        } => Ok(Output::Text(text)),
+        Output::Error { body, .. } => Ok(Output::Json(body)),
+        output => Ok(output),
+    }
+}
+
+/// Prints the trace id of --trace to stderr, with its Cloud Console URL when the project is known.
+fn print_trace(trace: &trace::TraceContext) {
+    let project = config::current()
+        .project
+        .clone()
+        .or_else(|| get_gcloud_config_value("core/project").ok());
+    match project {
+        Some(project) => eprintln!(
+            "Trace: {} ({})",
+            trace.trace_id,
+            trace.console_url(&project)
+        ),
+        None => eprintln!("Trace: {}", trace.trace_id),
+    }
+}
+
+/// Prints the request ids in the response headers to stderr (see `trace::REQUEST_ID_HEADERS`).
+fn print_request_ids(res: Option<&HttpResponse>) {
+    for (name, value) in res
+        .map(|res| res.request_ids.as_slice())
+        .unwrap_or_default()
+    {
+        eprintln!("{}: {}", name, value);
+    }
+}
+
+/// Turns the 401 that `send_with_reauth` fails with (after retrying) back into the response when --expect-status is given,
+/// so that it's checked like other statuses.
+fn expected_failure(
+    res: Result<HttpResponse, ZgError>,
+    expect_status: Option<&[StatusPattern]>,
+) -> Result<HttpResponse, ZgError> {
+    match (res, expect_status) {
+        (Err(ZgError::HttpStatus { status, body }), Some(_)) => Ok(HttpResponse {
+            status,
+            content_type: None,
+            body,
+            timing: Default::default(),
+            request_ids: Vec::new(),
+        }),
+        (res, _) => res,
+    }
+}
+
+/// Returns the JSON body of the request from --data. GET and DELETE methods without a request schema (most of them) fail
+/// with --data, which is likely meant as query parameters, unless --force-body is given; a few DELETE methods take a body.
+/// Without --data, POST/PUT/PATCH requests send an empty JSON (= `--data '{}'`), and others send no body.
+fn request_body(args: &ExecArgs, method: &core::ZgMethod) -> Result<Option<String>, ZgError> {
+    debug!("{} request w/ Data: {:?}", &method.http_method, &args.data);
+
+    let data = match (&args.data, method.http_method.as_str()) {
+        (Some(data), "GET" | "DELETE") if method.request_data_schema.is_none() => {
+            let json = prepare_json_string(data)?;
+            if !args.force_body {
+                return Err(bodiless_method_error(method, &json));
+            }
+            warn!(
+                "Method '{}' uses {}, which takes no request body; sending --data anyway (--force-body).",
+                &method.name, &method.http_method
+            );
+            return Ok(Some(json));
+        }
+        (Some(data), _) => {
+            if method.request_data_schema.is_none() {
+                warn!(
+                    "Method '{}' has no request body in its definition; sending --data anyway.",
+                    &method.name
+                );
+            }
+            data.as_str()
+        }
+        (None, "GET" | "DELETE") => return Ok(None),
+        (None, _) => "{}",
+    };
+    prepare_json_string(data).map(Some)
+}
+
+/// Returns the body of --data-binary as it is. GET and DELETE methods without a request schema reject it unless
+/// --force-body is given, as with --data.
+fn binary_body(data: &str, force_body: bool, method: &core::ZgMethod) -> Result<Bytes, ZgError> {
+    if matches!(method.http_method.as_str(), "GET" | "DELETE")
+        && method.request_data_schema.is_none()
+        && !force_body
+    {
+        return Err(format!(
+            "Method '{}' uses {}, which takes no request body, but --data-binary is given. Send it anyway with --force-body.",
+            &method.name, &method.http_method
+        )
+        .into());
+    }
+    read_binary_with(data, || {
+        let mut content = Vec::new();
+        std::io::stdin().read_to_end(&mut content)?;
+        Ok(content)
+    })
+}
+
+/// Reads the bytes of --data-binary: the file of @file, stdin of @-, or the string itself.
+fn read_binary_with(
+    data: &str,
+    read_stdin: impl FnOnce() -> std::io::Result<Vec<u8>>,
+) -> Result<Bytes, ZgError> {
+    let bytes = match data.strip_prefix('@') {
+        Some("-") => read_stdin().map_err(|e| format!("Failed to read data from stdin: {}", e))?,
+        Some(filename) => fs::read(filename).map_err(|e| {
+            format!(
+                "Failed to read file '{}' ({}): {}",
+                filename,
+                file_location(filename),
+                e
+            )
+        })?,
+        None => data.as_bytes().to_vec(),
+    };
+    Ok(Bytes::from(bytes))
+}
+
+/// Content-Type of the request body: --content-type, or the default of --data-binary or JSON.
+fn content_type(args: &ExecArgs) -> &str {
+    match (&args.content_type, &args.data_binary) {
+        (Some(content_type), _) => content_type,
+        (None, Some(_)) => "application/octet-stream",
+        (None, None) => "application/json; charset=utf-8",
+    }
+}
+
+/// The error for --data given to a GET or DELETE method without a request body, suggesting the query parameters of
+/// the method similar to the top-level keys of the body (e.g., `-p filter=VALUE` for `{"filter": ...}`).
+fn bodiless_method_error(method: &core::ZgMethod, json: &str) -> ZgError {
+    let keys: Vec<String> = match serde_json::from_str::<Value>(json) {
+        Ok(Value::Object(body)) => body.keys().cloned().collect(),
+        _ => vec![],
+    };
+    let mut similar: Vec<String> = Vec::new();
+    for key in &keys {
+        let names = method.query_params.iter().map(|qp| qp.name.as_str());
+        for name in suggest::suggest(key, names) {
+            if !similar.contains(&name) {
+                similar.push(name);
+            }
+        }
+    }
+    let hint = match similar.is_empty() {
+        true => String::new(),
+        false => format!(
+            " (similar to the body: {})",
+            similar
+                .iter()
+                .map(|name| format!("-p {}=VALUE", name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    };
+    format!(
+        "Method '{}' uses {}, which takes no request body, but --data is given. Give query parameters with -p KEY=VALUE instead{}, or send the body anyway with --force-body.",
+        &method.name, &method.http_method, hint
+    )
+    .into()
+}
+
+/// Status code, Content-Type, and body of a response, how long it took, and the headers identifying the request.
+#[derive(Debug, Clone, PartialEq)]
+struct HttpResponse {
+    status: u16,
+    content_type: Option<String>,
+    body: String,
+    timing: timing::RequestTiming,
+    request_ids: Vec<(String, String)>,
+}
+
+/// Sends the request and returns the response. Methods without `body` send an empty one.
+/// With `identity`, its client certificate is presented to the server (mTLS).
+async fn send(
+    method: Method,
+    url: &str,
+    headers: HeaderMap<HeaderValue>,
+    body: Option<Bytes>,
+    identity: Option<&mtls::ClientIdentity>,
+) -> Result<HttpResponse, ZgError> {
+    let uri: Uri = url.parse()?;
+    let mut req = Request::builder().method(method).uri(uri);
+
+    // Add headers
+    for (key, value) in headers.iter() {
+        req = req.header(key, value);
+    }
+
+    let _span = timing::span("http");
+    let request = async {
+        let response = match body {
+            Some(body) => {
+                let client = build_client_with_identity::<Full<Bytes>>(identity)?;
+                client
+                    .request(req.body(Full::new(body))?)
+                    .await
+                    .map_err(|e| request_error(e, identity))?
+            }
+            None => {
+                let client = build_client_with_identity::<Empty<Bytes>>(identity)?;
+                client
+                    .request(req.body(Empty::<Bytes>::new())?)
+                    .await
+                    .map_err(|e| request_error(e, identity))?
+            }
+        };
+        Ok::<_, ZgError>(response)
+    };
+    let read_body = |response: hyper::Response<hyper::body::Incoming>| async move {
+        log_kv!(
+            Level::Debug,
+            { status = response.status().as_u16() },
+            "Response status: {}",
+            response.status()
+        );
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let request_ids = trace::REQUEST_ID_HEADERS
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.headers().get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+        let body_bytes = response.into_body().collect().await?.to_bytes();
+        Ok((
+            status,
+            content_type,
+            String::from_utf8(body_bytes.to_vec())?,
+            request_ids,
+        ))
+    };
+    let ((status, content_type, body, request_ids), timing) =
+        timing::measure_request(request, read_body).await?;
+    Ok(HttpResponse {
+        status,
+        content_type,
+        body,
+        timing,
+        request_ids,
+    })
+}
+
+/// Returns the fresh response cached for the URL and the headers with --cache-ttl, or sends the request with `send` and
+/// caches its response. The headers are the ones before sending, as a refreshed token is a new key anyway.
+async fn send_cached(
+    cache: Option<&HttpCache>,
+    url: &str,
+    headers: &RefCell<HeaderMap<HeaderValue>>,
+    send: impl Future<Output = Result<HttpResponse, ZgError>>,
+) -> Result<HttpResponse, ZgError> {
+    let Some(cache) = cache else {
+        return send.await;
+    };
+    let key = HttpCache::key(url, &headers.borrow());
+    if let Some((cached, age)) = cache.get(&key) {
+        eprintln!("(cached, age {}s)", age.as_secs());
+        return Ok(HttpResponse {
+            status: cached.status,
+            content_type: cached.content_type,
+            body: cached.body,
+            timing: Default::default(),
+            request_ids: vec![],
+        });
+    }
+    let res = send.await?;
+    cache.put(
+        &key,
+        url,
+        res.status,
+        res.content_type.as_deref(),
+        &res.body,
+    );
+    Ok(res)
+}
+
+/// The cache of --cache-ttl for the request, if it can be cached: GET methods sent with authentication to the default
+/// hosts. The other responses could differ under the same URL (e.g., an emulator being reset).
+fn response_cache(
+    args: &ExecArgs,
+    method: &core::ZgMethod,
+    destination: &Destination,
+) -> Option<HttpCache> {
+    let ttl = args.cache_ttl?;
+    if method.http_method != "GET" {
+        warn!(
+            "--cache-ttl caches only GET methods; the response of {} ({}) is not cached",
+            method.name, method.http_method
+        );
+        return None;
+    }
+    if no_auth(args, destination.endpoint.as_ref()) || destination.endpoint.is_some() {
+        debug!(
+            "Not caching the response of a request without authentication or to another endpoint"
+        );
+        return None;
+    }
+    Some(HttpCache::new(ttl, !args.no_cache))
+}
+
+/// Converts the error of a request, telling when the server rejected the client certificate of `identity`.
+fn request_error(
+    e: hyper_util::client::legacy::Error,
+    identity: Option<&mtls::ClientIdentity>,
+) -> ZgError {
+    identity
+        .and_then(|identity| mtls::rejection(&e, identity))
+        .unwrap_or_else(|| e.into())
+}
+
+/// Sends the request with `send`, and returns the response. On 401, when `refresh` is given, the access token
+/// is refreshed with it, the Authorization header is rebuilt (also for the following requests sharing `headers`),
+/// and the request is retried once; a second 401 fails with the body of the first.
+async fn send_with_reauth<S, SFut, R, RFut>(
+    headers: &RefCell<HeaderMap<HeaderValue>>,
+    refresh: Option<R>,
+    mut send: S,
+) -> Result<HttpResponse, ZgError>
+where
+    S: FnMut(HeaderMap<HeaderValue>) -> SFut,
+    SFut: Future<Output = Result<HttpResponse, ZgError>>,
+    R: FnOnce() -> RFut,
+    RFut: Future<Output = Result<String, ZgError>>,
+{
+    let current = headers.borrow().clone();
+    let response = send(current).await?;
+    let (401, Some(refresh)) = (response.status, refresh) else {
+        return Ok(response);
+    };
+
+    warn!("The request was rejected with 401; refreshing the access token and retrying once.");
+    let access_token = refresh().await?;
+    headers.borrow_mut().insert(
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {}", access_token))?,
+    );
+    let current = headers.borrow().clone();
+    match send(current).await? {
+        HttpResponse { status: 401, .. } => Err(ZgError::HttpStatus {
+            status: 401,
+            body: response.body.trim().to_string(),
+        }),
+        retried => Ok(retried),
+    }
+}
+
+/// Returns whether the header is given with -H (case-insensitive).
+fn has_header(custom_headers: &Option<Vec<(String, String)>>, name: &str) -> bool {
+    custom_headers
+        .iter()
+        .flatten()
+        .any(|(key, _)| key.eq_ignore_ascii_case(name))
+}
+
+/// A response of `zg exec`, by how it's printed.
+#[derive(Debug, PartialEq)]
+enum Output {
+    Json(Value),
+    /// A response with an error status (4xx or 5xx), failing with the error rendered by `api_error::render` in the text
+    /// format. Bodies that are not JSON (e.g., an HTML error page of a load balancer, or no body) are kept as strings.
+    Error {
+        status: u16,
+        body: Value,
+    },
+    /// No content (e.g., 204 No Content); a notice is printed to stderr, and nothing to stdout.
+    NoContent(u16),
+    /// A body that is not JSON (e.g., text/plain, or an HTML error page of a load balancer), printed verbatim.
+    Text(String),
+}
+
+impl Output {
+    /// The response as a value for `--format json|yaml|ndjson`: `{}` for no content, and a string for non-JSON bodies.
+    fn into_value(self) -> Value {
+        match self {
+            Output::Json(value) | Output::Error { body: value, .. } => value,
+            Output::NoContent(_) => json!({}),
+            Output::Text(body) => Value::String(body),
+        }
+    }
+}
+
+/// Parses the response body by its status and Content-Type, warning with a hint when it's an insufficient scope error.
+/// Bodies are parsed as JSON when the Content-Type is JSON, or when it's missing or something else but the body is valid JSON anyway.
+/// Every error status is an `Output::Error`, whatever the body and the Content-Type.
+fn parse_response(api_id: &str, res: &HttpResponse) -> Result<Output, ZgError> {
+    debug!("Raw Response: {:?}", res);
+
+    if let Some(hint) = scope_error_hint(api_id, &res.body) {
+        warn!("{}", hint);
+    }
+
+    let _span = timing::span("parse_response");
+    if res.status >= 400 {
+        return Ok(Output::Error {
+            status: res.status,
+            body: from_str(&res.body).unwrap_or_else(|_| Value::String(res.body.clone())),
+        });
+    }
+    if res.status == 204 || res.body.trim().is_empty() {
+        return Ok(Output::NoContent(res.status));
+    }
+    let content_type = res.content_type.as_deref().unwrap_or_default();
+    if content_type.contains("json") {
+        return Ok(Output::Json(from_str(&res.body)?));
+    }
+    match from_str(&res.body) {
+        Ok(value) => Ok(Output::Json(value)),
+        Err(_) => {
+            warn!(
+                "The response is not JSON (HTTP {}, Content-Type: {}); printing it as is.",
+                res.status,
+                res.content_type.as_deref().unwrap_or("none")
+            );
+            Ok(Output::Text(res.body.clone()))
+        }
+    }
+}
+
+/// Query parameters limiting the number of items in a page: `pageSize` in most APIs, `maxResults` in older ones (e.g., Compute Engine).
+const PAGE_SIZE_PARAMS: [&str; 2] = ["pageSize", "maxResults"];
+
+/// Returns the params for the page: `pageToken` is replaced with the token if given, and the page size
+/// parameter is capped at the number of items still wanted (`page_size`).
+fn page_params(
+    params: &Option<Vec<(String, String)>>,
+    page_token: Option<String>,
+    page_size: Option<(&str, u64)>,
+) -> Option<Vec<(String, String)>> {
+    if page_token.is_none() && page_size.is_none() {
+        return params.clone();
+    }
+    let mut params = params.clone().unwrap_or_default();
+    if let Some(token) = page_token {
+        params.retain(|(key, _)| key != "pageToken");
+        params.push(("pageToken".to_string(), token));
+    }
+    if let Some((name, max_items)) = page_size {
+        let given = params
+            .iter()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, value)| value.parse::<u64>().ok());
+        params.retain(|(key, _)| key != name);
+        let size = given.map_or(max_items, |given| given.min(max_items));
+        params.push((name.to_string(), size.to_string()));
+    }
+    Some(params)
+}
+
+/// Fetches pages following `nextPageToken`, starting from `page_token`, and merges them (see `merge_page`).
+/// Stops like `for_each_page`; then the token of the next page is returned with the merged response
+/// (also kept in it as `nextPageToken`) to resume from.
+async fn fetch_pages<F, Fut>(
+    page_token: Option<String>,
+    max_pages: Option<u64>,
+    limit: Option<u64>,
+    fetch: F,
+) -> Result<(Value, Option<String>), ZgError>
+where
+    F: FnMut(Option<String>, Option<u64>) -> Fut,
+    Fut: Future<Output = Result<Value, ZgError>>,
+{
+    let mut merged = Value::Null;
+    let next_page_token = for_each_page(page_token, max_pages, limit, fetch, |page| {
+        merge_page(&mut merged, page);
+        Ok(())
+    })
+    .await?;
+    if let (Value::Object(merged), Some(token)) = (&mut merged, &next_page_token) {
+        merged.insert("nextPageToken".to_string(), Value::from(token.as_str()));
+    }
+    Ok((merged, next_page_token))
+}
+
+/// Fetches pages following `nextPageToken`, starting from `page_token`, and passes each page (without `nextPageToken`)
+/// to `on_page` as it's fetched. `fetch` is called with the page token and the number of items still wanted under `limit`.
+/// Stops at the last page, or after `max_pages` pages or `limit` items; then the token of the next page is returned to resume from.
+async fn for_each_page<F, Fut, P>(
+    mut page_token: Option<String>,
+    max_pages: Option<u64>,
+    limit: Option<u64>,
+    mut fetch: F,
+    mut on_page: P,
+) -> Result<Option<String>, ZgError>
+where
+    F: FnMut(Option<String>, Option<u64>) -> Fut,
+    Fut: Future<Output = Result<Value, ZgError>>,
+    P: FnMut(Value) -> Result<(), ZgError>,
+{
+    let mut pages = 0;
+    let mut items = 0;
+    loop {
+        let mut page = fetch(page_token.take(), limit.map(|limit| limit - items)).await?;
+        pages += 1;
+        let next_page_token = page
+            .as_object_mut()
+            .and_then(|page| page.remove("nextPageToken"))
+            .and_then(|token| token.as_str().map(str::to_string))
+            .filter(|token| !token.is_empty());
+        items += count_items(&page);
+        on_page(page)?;
+        debug!("Fetched page {} ({} items so far)", pages, items);
+
+        let Some(token) = next_page_token else {
+            return Ok(None);
+        };
+        if max_pages.is_some_and(|max_pages| pages >= max_pages)
+            || limit.is_some_and(|limit| items >= limit)
+        {
+            return Ok(Some(token));
+        }
+        page_token = Some(token);
+    }
+}
+
+/// Number of items in the page, i.e., the length of its longest list field (e.g., `clusters`).
+fn count_items(page: &Value) -> u64 {
+    page.as_object()
+        .into_iter()
+        .flat_map(|page| page.values())
+        .filter_map(|value| value.as_array().map(|items| items.len() as u64))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Appends the list fields of the page to those of the merged response. Other fields are kept from the first page.
+fn merge_page(merged: &mut Value, page: Value) {
+    match (merged, page) {
+        (Value::Object(merged), Value::Object(page)) => {
+            for (key, value) in page {
+                match (merged.get_mut(&key), value) {
+                    (Some(Value::Array(items)), Value::Array(more)) => items.extend(more),
+                    (None, value) => {
+                        merged.insert(key, value);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        (merged, page) => *merged = page,
+    }
+}
+
+/// Returns the API key to send as the `key=` query parameter, if the API authenticates with it
+/// (see `SupportedApi::requires_api_key`). Other APIs use the gcloud access token even if --api-key is given.
+fn api_key_auth(api_id: &str, api_key: Option<String>) -> Option<String> {
+    let name = api_id.split(':').next().unwrap_or(api_id);
+    let requires_api_key = supported_apis::standalone_apis()
+        .iter()
+        .any(|api| api.name == name && api.requires_api_key());
+    api_key.filter(|_| requires_api_key)
+}
+
+/// The universe domain to send requests to: `--universe-domain` (or the config file), or `core/universe_domain` of
+/// gcloud when `gcloud_auth`, as the access token of gcloud is valid only in its universe.
+/// None for the default universe (googleapis.com).
+fn universe_domain(gcloud_auth: bool) -> Option<String> {
+    if let Some(domain) = config::current().universe_domain() {
+        return Some(domain.to_string());
+    }
+    if !gcloud_auth {
+        return None;
+    }
+    gcloud_property(
+        "core",
+        "universe_domain",
+        |var| env::var(var).ok(),
+        dirs::home_dir(),
+    )
+    .filter(|domain| domain != config::DEFAULT_UNIVERSE_DOMAIN)
+}
+
+/// Reads a property of the active gcloud configuration without running gcloud, which takes a second or so to start:
+/// `CLOUDSDK_<SECTION>_<NAME>`, or `name` in `[section]` of `configurations/config_<NAME>` in the gcloud config
+/// directory (`CLOUDSDK_CONFIG`, `%APPDATA%\gcloud` on Windows, or `~/.config/gcloud`). The active configuration is
+/// `CLOUDSDK_ACTIVE_CONFIG_NAME`, the one in `active_config`, or `default`.
+fn gcloud_property(
+    section: &str,
+    name: &str,
+    env: impl Fn(&str) -> Option<String>,
+    home: Option<PathBuf>,
+) -> Option<String> {
+    let var = format!("CLOUDSDK_{}_{}", section, name).to_uppercase();
+    if let Some(value) = env(&var).filter(|value| !value.is_empty()) {
+        return Some(value);
+    }
+    let dir = match (env("CLOUDSDK_CONFIG"), env("APPDATA")) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(appdata)) if cfg!(windows) => Path::new(&appdata).join("gcloud"),
+        (None, _) => home?.join(".config").join("gcloud"),
+    };
+    let active = env("CLOUDSDK_ACTIVE_CONFIG_NAME")
+        .or_else(|| fs::read_to_string(dir.join("active_config")).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "default".to_string());
+    let path = dir
+        .join("configurations")
+        .join(format!("config_{}", active));
+    let value = properties_value(&fs::read_to_string(&path).ok()?, section, name);
+    debug!(
+        "Read {}/{} from {}: {:?}",
+        section,
+        name,
+        path.display(),
+        value
+    );
+    value
+}
+
+/// Returns the value of `name` in `[section]` of a gcloud properties file (INI), if it's set to a non-empty value.
+fn properties_value(content: &str, section: &str, name: &str) -> Option<String> {
+    let mut current = "";
+    for line in content.lines().map(str::trim) {
+        if line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current = header.trim();
+            continue;
+        }
+        match line.split_once(['=', ':']) {
+            Some((key, value)) if current == section && key.trim() == name => {
+                return Some(value.trim().to_string()).filter(|value| !value.is_empty());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether the access token of the request comes from gcloud: no API key, credentials file, Authorization header
+/// given with -H, or endpoint (where the universe domain doesn't matter), and not --no-auth.
+fn gcloud_auth(args: &ExecArgs, api_key: Option<&str>, endpoint: Option<&Endpoint>) -> bool {
+    api_key.is_none()
+        && endpoint.is_none()
+        && !args.no_auth
+        && config::current().credentials_file.is_none()
+        && !has_header(&args.headers, "Authorization")
+}
+
+/// Whether to send the request without authentication: with --no-auth, or to an emulator.
+fn no_auth(args: &ExecArgs, endpoint: Option<&Endpoint>) -> bool {
+    args.no_auth || endpoint.is_some_and(|endpoint| endpoint.emulator)
+}
+
+/// Environment variables of the emulator hosts (e.g., `localhost:8085`), as set by `gcloud beta emulators ... env-init`.
+const EMULATOR_HOSTS: [(&str, &str); 2] = [
+    ("pubsub", "PUBSUB_EMULATOR_HOST"),
+    ("spanner", "SPANNER_EMULATOR_HOST"),
+];
+
+/// The client certificate to present: --client-cert and --client-key, or the one of Endpoint Verification with --mtls
+/// or when gcloud uses it (except for endpoints given otherwise). None without mTLS. Expired certificates are rejected here.
+fn client_identity(
+    args: &ExecArgs,
+    endpoint: Option<&Endpoint>,
+) -> Result<Option<mtls::ClientIdentity>, ZgError> {
+    if args.equivalent_curl {
+        // curl is given the files of --client-cert and --client-key as they are
+        return Ok(None);
+    }
+    let identity = match (&args.client_cert, &args.client_key) {
+        (Some(cert), Some(key)) => {
+            mtls::ClientIdentity::from_files(Path::new(cert), Path::new(key))?
+        }
+        _ if args.mtls => mtls::ClientIdentity::from_provider()?,
+        _ if endpoint.is_none() && mtls::gcloud_uses_client_certificate() => {
+            mtls::ClientIdentity::from_provider()?
+        }
+        _ => return Ok(None),
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    identity.check_validity(now)?;
+    debug!("Presenting the client certificate in {}", identity.source);
+    Ok(Some(identity))
+}
+
+/// Where to send the request, other than the base URL of the API (see `build_url`).
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Destination {
+    /// The universe domain replacing googleapis.com.
+    universe_domain: Option<String>,
+    /// Whether to use the mTLS host (`*.mtls.googleapis.com`).
+    mtls: bool,
+    endpoint: Option<Endpoint>,
+}
+
+/// The endpoint to send requests to instead of the base URL of the API.
+#[derive(Debug, Clone, PartialEq)]
+struct Endpoint {
+    url: String,
+    /// Whether it comes from an emulator environment variable, which sends requests without authentication by default.
+    emulator: bool,
+}
+
+/// Returns the endpoint of `--endpoint`, the emulator environment variable of the service (looked up by `env`),
+/// or `[endpoints]` in the config file (by the service as given, its name, or an alias), in this order.
+fn resolve_endpoint(
+    args: &ExecArgs,
+    service: &str,
+    api_id: &str,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<Endpoint> {
+    let name = api_id.split(':').next().unwrap_or(api_id);
+    if let Some(url) = &args.endpoint {
+        return Some(Endpoint {
+            url: url.clone(),
+            emulator: false,
+        });
+    }
+    let emulator_host = EMULATOR_HOSTS
+        .iter()
+        .find(|(emulated, _)| *emulated == name)
+        .and_then(|(_, var)| {
+            env(var)
+                .filter(|host| !host.is_empty())
+                .map(|host| (var, host))
+        });
+    if let Some((var, host)) = emulator_host {
+        debug!("Endpoint from {}: {}", var, host);
+        let url = match host.contains("://") {
+            true => host,
+            false => format!("http://{}", host),
+        };
+        return Some(Endpoint {
+            url,
+            emulator: true,
+        });
+    }
+    let aliases = supported_apis::supported_apis(true)
+        .into_iter()
+        .find(|supported| supported.name == name)
+        .map(|supported| supported.aliases)
+        .unwrap_or_default();
+    let keys = [service, name]
+        .into_iter()
+        .chain(aliases.iter().map(String::as_str));
+    config::current().endpoint(keys).map(|url| Endpoint {
+        url: url.to_string(),
+        emulator: false,
+    })
+}
+
+/// Replaces the scheme, host, and port of the URL with the endpoint's, keeping the path and the query.
+/// A path in the endpoint (e.g., `https://gateway.example.com/pubsub`) is put before the path of the URL.
+fn with_endpoint(url: &str, endpoint: &str) -> Result<String, ZgError> {
+    let invalid = || {
+        ZgError::Other(format!(
+            "Invalid endpoint '{}'. Give a URL with the scheme, e.g., 'http://localhost:8085' or 'https://pubsub-myendpoint.p.googleapis.com'.",
+            endpoint
+        ))
+    };
+    let endpoint_url = url::Url::parse(endpoint).map_err(|_| invalid())?;
+    let host = match endpoint_url.host_str() {
+        Some(host) if ["http", "https"].contains(&endpoint_url.scheme()) => host,
+        _ => return Err(invalid()),
+    };
+    let url = url::Url::parse(url)?;
+    let mut rewritten = format!("{}://{}", endpoint_url.scheme(), host);
+    if let Some(port) = endpoint_url.port() {
+        rewritten.push_str(&format!(":{}", port));
+    }
+    rewritten.push_str(endpoint_url.path().trim_end_matches('/'));
+    rewritten.push_str(url.path());
+    if let Some(query) = url.query() {
+        rewritten.push('?');
+        rewritten.push_str(query);
+    }
+    Ok(rewritten)
+}
+
+/// Appends the API key to the params so that it's sent as the `key=` query parameter.
+fn with_api_key(
+    params: &Option<Vec<(String, String)>>,
+    api_key: Option<&str>,
+) -> Option<Vec<(String, String)>> {
+    match api_key {
+        Some(key) => {
+            let mut params = params.clone().unwrap_or_default();
+            params.push(("key".to_string(), key.to_string()));
+            Some(params)
+        }
+        None => params.clone(),
+    }
+}
+
+/// Returns the names of the non-repeated query parameters given more than once with `-p`.
+/// Repeated ones (e.g., `-p fields=a -p fields=b`) are sent as multiple query params and are not reported.
+/// `known_params` are the query parameters of the method and the API-level common parameters.
+fn duplicated_query_params<'a>(
+    known_params: impl IntoIterator<Item = &'a core::ZgQueryParam>,
+    params: &Option<Vec<(String, String)>>,
+) -> Vec<String> {
+    let params = params.as_deref().unwrap_or_default();
+    let mut duplicated: Vec<String> = known_params
+        .into_iter()
+        .filter(|qp| !qp.repeated)
+        .filter(|qp| params.iter().filter(|(key, _)| *key == qp.name).count() > 1)
+        .map(|qp| qp.name.clone())
+        .collect();
+    duplicated.sort();
+    duplicated
+}
+
+/// Adds `prettyPrint=false` unless it's given with `-p`, as zg formats the response by itself.
+/// Only for APIs accepting `prettyPrint` as a common parameter.
+fn with_pretty_print_disabled(
+    api: &core::ZgApi,
+    params: &Option<Vec<(String, String)>>,
+) -> Option<Vec<(String, String)>> {
+    let accepts_pretty_print = api.common_params.iter().any(|p| p.name == "prettyPrint");
+    let given = params.iter().flatten().any(|(key, _)| key == "prettyPrint");
+    if !accepts_pretty_print || given {
+        return params.clone();
+    }
+    let mut params = params.clone().unwrap_or_default();
+    params.push(("prettyPrint".to_string(), "false".to_string()));
+    Some(params)
+}
+
+/// Adds the default query parameters in the config file (`[defaults.params]`) that the method has, unless they're given with `-p`.
+fn with_default_params(
+    params: &Option<Vec<(String, String)>>,
+    defaults: Vec<(String, String)>,
+) -> Option<Vec<(String, String)>> {
+    let mut merged = params.clone().unwrap_or_default();
+    for (name, value) in defaults {
+        if !merged.iter().any(|(key, _)| *key == name) {
+            debug!(
+                "Default query parameter from the config file: {}={}",
+                name, value
+            );
+            merged.push((name, value));
+        }
+    }
+    match (params, merged.is_empty()) {
+        (None, true) => None,
+        _ => Some(merged),
+    }
+}
+
+/// Build the URL to send a request to, in the universe domain, on the mTLS host, and on the endpoint if any (see `with_endpoint`)
+fn build_url(
+    api: &core::ZgApi,
+    method: &core::ZgMethod,
+    params: &Option<Vec<(String, String)>>,
+    destination: &Destination,
+) -> Result<String, ZgError> {
+    let config = config::current();
+    let service_name = api.id.split(':').next().unwrap_or_default();
+    let params = with_default_params(params, config.defaults.params(service_name, method));
+    let (mut path, query_params) =
+        client::fill_path_params(&method.flat_path, params.as_deref().unwrap_or_default());
+
+    // Autofill: replace placeholders (project_id, region, and zone) with values in the config file or gcloud CLI.
+    // If these autofill targets are specified with -p explicitly, they are already replaced in the previous loop.
+    path = replace_placeholders(
+        &path,
+        core::PATH_PLACEHOLDERS_PROJECT,
+        &config.project,
+        "core/project",
+    )?;
+    path = replace_placeholders(
+        &path,
+        core::PATH_PLACEHOLDERS_REGION,
+        &config.region,
+        "compute/region",
+    )?;
+    path = replace_placeholders(
+        &path,
+        core::PATH_PLACEHOLDERS_ZONE,
+        &config.zone,
+        "compute/zone",
+    )?;
+
+    let mut base_url = supported_apis::effective_base_url(api, &path);
+    if let Some(universe_domain) = &destination.universe_domain {
+        base_url = supported_apis::in_universe(&base_url, universe_domain);
+    }
+    if destination.mtls {
+        base_url = supported_apis::mtls_url(&base_url);
+    }
+    let mut url = client::join_url(&base_url, &path, &query_params)?;
+    if let Some(endpoint) = &destination.endpoint {
+        url = with_endpoint(&url, &endpoint.url)?;
+    }
+
+    if log::log_enabled!(Level::Debug) {
+        let redacted = logging::redact_url(&url);
+        log_kv!(
+            Level::Debug,
+            { url = &redacted },
+            "Built URL: {}",
+            &redacted
+        );
+    }
+    Ok(url)
+}
+
+/// Replace placeholders in the path with the configured value, or values from gcloud config if not configured.
+/// Only calls get_gcloud_config_value when placeholders are found in the path.
+/// The project falls back to the metadata server when gcloud doesn't have it (e.g., on GCE without gcloud).
+fn replace_placeholders(
+    path: &str,
+    placeholders: &[&str],
+    configured: &Option<String>,
+    gcloud_key: &str,
+) -> Result<String, ZgError> {
+    if placeholders
+        .iter()
+        .any(|&ph| path.contains(&format!("{{{}}}", ph)))
+    {
+        let value = match configured {
+            Some(value) => Ok(value.clone()),
+            None => get_gcloud_config_value(gcloud_key).or_else(|e| match gcloud_key {
+                "core/project" => {
+                    debug!("{}; trying the metadata server for the project", e);
+                    metadata::project_id().map_err(|metadata_error| {
+                        debug!("Metadata server: {}", metadata_error);
+                        e
+                    })
+                }
+                _ => Err(e),
+            }),
+        };
+        match value {
+            Ok(value) => {
+                let mut new_path = path.to_string();
+                for &placeholder in placeholders {
+                    let placeholder_fmt = format!("{{{}}}", placeholder);
+                    new_path = new_path.replace(&placeholder_fmt, &value);
+                }
+                Ok(new_path)
+            }
+            Err(e) => {
+                debug!("{}", e);
+                Ok(path.to_string())
+            }
+        }
+    } else {
+        Ok(path.to_string()) // No placeholders found; return the path as is
+    }
+}
+
+/// Returns a hint when the request to a Google Workspace API was rejected for the missing OAuth scopes.
+/// The access token from `gcloud auth print-access-token` has only the scopes gcloud requested at login.
+fn scope_error_hint(api_id: &str, response: &str) -> Option<String> {
+    let service_name = api_id.split(':').next().unwrap_or_default();
+    let insufficient_scopes = response.contains("ACCESS_TOKEN_SCOPE_INSUFFICIENT")
+        || response.contains("insufficient authentication scopes");
+    if !supported_apis::is_workspace_api(service_name) || !insufficient_scopes {
+        return None;
+    }
+    Some(format!(
+        "The access token from gcloud lacks the OAuth scopes required by '{}'. Log in with the scopes (e.g., `gcloud auth login --enable-gdrive-access` for Drive), or pass a token with the scopes via `-H 'Authorization: Bearer TOKEN'`.",
+        api_id
+    ))
+}
+
+/// Get the value of the given key from gcloud CLI
+pub(crate) fn get_gcloud_config_value(key: &str) -> Result<String, ZgError> {
+    let value = gcloud(&["config", "get", key], &path_var())?;
+    if value.is_empty() {
+        return Err(format!(
+            "No '{}' found in gcloud config. Consider: 'gcloud config set {} {}'",
+            key,
+            key,
+            key.split('/').next_back().unwrap_or("").to_uppercase()
+        )
+        .into());
+    }
+
+    debug!("Retrieved 'gcloud config get {}' => {:?}", key, &value);
+    Ok(value)
+}
+
+/// Build a hyper client with HTTPS support
+pub(crate) fn build_client<B>(
+) -> Result<Client<hyper_rustls::HttpsConnector<HttpConnector>, B>, ZgError>
+where
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    build_client_with_identity(None)
+}
+
+/// Build a hyper client with HTTPS support, presenting the client certificate of `identity` when the server asks for one
+pub(crate) fn build_client_with_identity<B>(
+    identity: Option<&mtls::ClientIdentity>,
+) -> Result<Client<hyper_rustls::HttpsConnector<HttpConnector>, B>, ZgError>
+where
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    // Install rustls crypto provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
+    let config = match identity {
+        Some(identity) => builder
+            .with_client_auth_cert(identity.certs.clone(), identity.key.clone_key())
+            .map_err(|e| {
+                ZgError::ClientCertificate(format!(
+                    "the certificate or key in {} can't be used: {}",
+                    identity.source, e
+                ))
+            })?,
+        None => builder.with_no_client_auth(),
+    };
+
+    let https_connector = HttpsConnectorBuilder::new()
+        .with_tls_config(config)
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .build();
+
+    let client = Client::builder(TokioExecutor::new()).build(https_connector);
+
+    Ok(client)
+}
+
+/// Period to reuse the access token from gcloud within a process (e.g., `zg shell`). Tokens live longer (typically 1 hour).
+const ACCESS_TOKEN_REUSE: Duration = Duration::from_secs(5 * 60);
+
+/// The last access token from gcloud and when it was retrieved.
+static ACCESS_TOKEN: Mutex<Option<(Instant, String)>> = Mutex::new(None);
+
+/// Get access token from gcloud CLI, reusing the one retrieved in the last `ACCESS_TOKEN_REUSE`.
+/// When gcloud fails (not installed or not logged in), the token of the instance's service account is taken from the metadata server.
+pub(crate) fn get_access_token() -> Result<String, ZgError> {
+    let e = match reused_or_gcloud_access_token() {
+        Ok(token) => return Ok(token),
+        Err(e) => e,
+    };
+    debug!("Auth: gcloud failed ({}); trying the metadata server", e);
+    match metadata::access_token() {
+        Ok(token) => {
+            debug!("Auth: using the token from the metadata server");
+            keep_access_token(&token);
+            Ok(token)
+        }
+        Err(metadata_error) => {
+            debug!("Auth: metadata server failed ({})", metadata_error);
+            // The gcloud error tells how to fix it outside Google Cloud
+            Err(e)
+        }
+    }
+}
+
+/// The access token retrieved in the last `ACCESS_TOKEN_REUSE`, or a new one from gcloud, kept to be reused.
+fn reused_or_gcloud_access_token() -> Result<String, ZgError> {
+    if let Some(token) = reused_access_token() {
+        return Ok(token);
+    }
+    debug!("Auth: trying `gcloud auth print-access-token`");
+    let access_token = gcloud_access_token()?;
+    keep_access_token(&access_token);
+    Ok(access_token)
+}
+
+/// The access token retrieved in the last `ACCESS_TOKEN_REUSE`, if any.
+fn reused_access_token() -> Option<String> {
+    let cached = ACCESS_TOKEN.lock().ok()?;
+    let (retrieved_at, token) = cached.as_ref()?;
+    (retrieved_at.elapsed() < ACCESS_TOKEN_REUSE).then(|| token.clone())
+}
+
+fn keep_access_token(token: &str) {
+    if let Ok(mut cached) = ACCESS_TOKEN.lock() {
+        *cached = Some((Instant::now(), token.to_string()));
+    }
+}
+
+/// Get access token from gcloud, or from the credential of `zg auth login` when gcloud fails. Preferred to the
+/// metadata server, as the user logged in explicitly.
+async fn get_access_token_or_login() -> Result<String, ZgError> {
+    let e = match reused_or_gcloud_access_token() {
+        Ok(token) => return Ok(token),
+        Err(e) => e,
+    };
+    debug!(
+        "Auth: gcloud failed ({}); using the credential of `zg auth login`",
+        e
+    );
+    let access_token = auth::access_token().await?;
+    keep_access_token(&access_token);
+    Ok(access_token)
+}
+
+/// Discards the cached access token and gets a new one, for when the API rejects the cached one (e.g., expired).
+async fn refresh_access_token() -> Result<String, ZgError> {
+    if let Ok(mut cached) = ACCESS_TOKEN.lock() {
+        *cached = None;
+    }
+    if let Some(path) = &config::current().credentials_file {
+        credentials::forget_access_token(Path::new(path));
+    } else if auth::has_stored_credential() {
+        auth::forget_access_token();
+    }
+    access_token().await
+}
+
+/// Returns the access token from the credentials file if one is configured (`--credentials-file`), otherwise from gcloud
+/// (or `zg auth login` when gcloud is unavailable).
+pub(crate) async fn access_token() -> Result<String, ZgError> {
+    match &config::current().credentials_file {
+        Some(path) => {
+            debug!("Auth: using the credentials file {}", path);
+            credentials::access_token(Path::new(path)).await
+        }
+        None if auth::has_stored_credential() => get_access_token_or_login().await,
+        None => get_access_token(),
+    }
+}
+
+fn gcloud_access_token() -> Result<String, ZgError> {
+    let access_token = gcloud(&["auth", "print-access-token"], &path_var())?;
+    if access_token.is_empty() {
+        return Err(ZgError::AuthFailure(
+            "gcloud returned an empty access token".to_string(),
+        ));
+    }
+    Ok(access_token)
+}
+
+fn path_var() -> OsString {
+    env::var_os("PATH").unwrap_or_default()
+}
+
+/// Runs gcloud found in the given PATH, and returns its trimmed stdout.
+/// Fails with GcloudMissing when gcloud isn't found, and with GcloudFailed carrying its stderr when it exits with an error.
+fn gcloud(args: &[&str], path: &OsStr) -> Result<String, ZgError> {
+    let _span = timing::span("gcloud");
+    let mut command = Command::new("gcloud");
+    command.args(args).env("PATH", path);
+    // The access token has to be of the universe zg sends requests to
+    if let Some(domain) = config::current().universe_domain() {
+        command.env("CLOUDSDK_CORE_UNIVERSE_DOMAIN", domain);
+    }
+    let output = command.output().map_err(gcloud_spawn_error)?;
+    if !output.status.success() {
+        return Err(ZgError::GcloudFailed {
+            command: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Maps a failure to spawn the gcloud command; "not found" means gcloud is not installed.
+fn gcloud_spawn_error(e: std::io::Error) -> ZgError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => ZgError::GcloudMissing,
+        _ => e.into(),
+    }
+}
+
+/// Build headers for the request
+/// The access token is `None` when the API key authenticates the request. Headers given with -H override the others.
+fn build_headers(
+    custom_headers: &Option<Vec<(String, String)>>,
+    access_token: Option<&str>,
+    content_type: &str,
+) -> Result<HeaderMap<HeaderValue>, ZgError> {
+    let mut headers = HeaderMap::new();
+
+    if let Some(access_token) = access_token {
+        // Inject 'Authorization' header with the (Bearer) access token
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", access_token))?,
+        );
+    }
+
+    // Inject 'Content-Type' header, 'application/json' unless --content-type or --data-binary is given
+    headers.insert("Content-Type", HeaderValue::from_str(content_type)?);
+
+    // Inject 'x-goog-user-project' header if the billing project is configured
+    if let Some(billing_project) = &config::current().billing_project {
+        headers.insert(
+            "x-goog-user-project",
+            HeaderValue::from_str(billing_project)?,
+        );
+    }
+
+    if let Some(hs) = custom_headers {
+        for (key, value) in hs.iter() {
+            headers.insert(key.parse::<HeaderName>()?, value.parse::<HeaderValue>()?);
+        }
+    }
+    debug!(
+        "Headers: {}",
+        logging::redact_headers(
+            headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or("<binary>")))
+        )
+    );
+
+    Ok(headers)
+}
+
+/// Prepares the JSON string from the given data argument.
+/// If the data starts with '@', it reads the content from the file: YAML for `.yaml` and `.yml` files, JSON otherwise.
+/// `@-` reads JSON from the standard input. Otherwise, it treats the data as a JSON string.
+fn prepare_json_string(data: &str) -> Result<String, ZgError> {
+    prepare_json_string_with(data, || {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    })
+}
+
+fn prepare_json_string_with(
+    data: &str,
+    read_stdin: impl FnOnce() -> std::io::Result<String>,
+) -> Result<String, ZgError> {
+    let json_data: Value = match data.strip_prefix('@') {
+        Some("-") => {
+            debug!("Reading data from stdin");
+            let content =
+                read_stdin().map_err(|e| format!("Failed to read data from stdin: {}", e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid JSON syntax in stdin: {}", e))?
+        }
+        Some(filename) => parse_data_file(filename, &read_data_file(filename)?)?,
+        None => serde_json::from_str(data).map_err(|e| format!("Invalid JSON syntax: {}", e))?,
+    };
+
+    let json_string = serde_json::to_string(&json_data)
+        .map_err(|e| format!("Failed to serialize JSON data: {}", e))?;
+    Ok(json_string)
+}
+
+/// Reads the file of `--data @FILE`.
+fn read_data_file(filename: &str) -> Result<String, ZgError> {
+    debug!("Reading data from file: {}", filename);
+    let content = fs::read_to_string(filename).map_err(|e| {
+        format!(
+            "Failed to read file '{}' ({}): {}",
+            filename,
+            file_location(filename),
+            e
+        )
+    })?;
+    Ok(content)
+}
+
+/// Parses the content of the data file: YAML for `.yaml` and `.yml` files, JSON otherwise.
+fn parse_data_file(filename: &str, content: &str) -> Result<Value, ZgError> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    let json_data = match extension.as_deref() {
+        Some("yaml" | "yml") => serde_yaml::from_str(content)
+            .map_err(|e| format!("Invalid YAML syntax in file '{}': {}", filename, e))?,
+        _ => serde_json::from_str(content)
+            .map_err(|e| format!("Invalid JSON syntax in file '{}': {}", filename, e))?,
+    };
+    Ok(json_data)
+}
+
+/// Describes where the file was looked for, as relative paths are resolved from the current directory:
+/// the absolute path (canonicalized if it exists) and the current directory.
+fn file_location(filename: &str) -> String {
+    let Ok(cwd) = env::current_dir() else {
+        return "current directory unknown".to_string();
+    };
+    let path = fs::canonicalize(filename).unwrap_or_else(|_| cwd.join(filename));
+    format!(
+        "resolved to {}, current directory: {}",
+        path.display(),
+        cwd.display()
+    )
+}
+
+/// Generates an equivalent curl command for the given HTTP method, arguments, and -p parameters (see `resolve_params`).
+fn generate_curl(
+    api: &core::ZgApi,
+    method: &core::ZgMethod,
+    args: &ExecArgs,
+    params: &Option<Vec<(String, String)>>,
+    api_key: Option<&str>,
+    destination: &Destination,
+) -> Result<String, ZgError> {
+    let mut curl_command = format!("curl -X {}", method.http_method);
+    if let (Some(cert), Some(key)) = (&args.client_cert, &args.client_key) {
+        curl_command.push_str(&format!(" \\\n  --cert {} --key {}", cert, key));
+    }
+
+    let mut custom_header_keys = Vec::<String>::new();
+    if let Some(headers) = &args.headers {
+        for (key, value) in headers {
+            curl_command.push_str(&format!(" \\\n  -H \"{}: {}\"", key, value));
+            custom_header_keys.push(key.to_lowercase());
+        }
+    }
+
+    if api_key.is_none()
+        && !no_auth(args, destination.endpoint.as_ref())
+        && !custom_header_keys.contains(&"authorization".to_string())
+    {
+        curl_command
+            .push_str(" \\\n  -H \"Authorization: Bearer $(gcloud auth print-access-token)\"");
+    }
+
+    if !custom_header_keys.contains(&"content-type".to_string()) {
+        curl_command.push_str(&format!(
+            " \\\n  -H \"Content-Type: {}\"",
+            content_type(args)
+        ));
+    }
+
+    if let Some(billing_project) = &config::current().billing_project {
+        if !custom_header_keys.contains(&"x-goog-user-project".to_string()) {
+            curl_command.push_str(&format!(
+                " \\\n  -H \"x-goog-user-project: {}\"",
+                billing_project
+            ));
+        }
+    }
+
+    if let Some(data) = &args.data {
+        let json_string = prepare_json_string(data)?; // If --data @filename, expand the content here; otherwise, treat as JSON string
+        let json_data: Value = serde_json::from_str(&json_string)?;
+        let mut json_pretty = serde_json::to_string_pretty(&json_data)?;
+
+        // If the JSON data is not empty, add a newline before the JSON string
+        if !(json_data.is_object() && json_data.as_object().unwrap().is_empty()) {
+            json_pretty = format!("\n{}", json_pretty);
+        }
+        curl_command.push_str(&format!(" \\\n  -d '{}'", json_pretty));
+    }
+    if let Some(data) = &args.data_binary {
+        let quoted = shlex::try_quote(data).map_err(|e| ZgError::Other(e.to_string()))?;
+        curl_command.push_str(&format!(" \\\n  --data-binary {}", quoted));
+    }
+
+    curl_command.push_str(&format!(
+        " \\\n  \"{}\"",
+        build_url(api, method, &with_api_key(params, api_key), destination)?
+    ));
+
+    Ok(curl_command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_with_default_params() {
+        let defaults = || {
+            vec![
+                ("pageSize".to_string(), "500".to_string()),
+                ("view".to_string(), "FULL".to_string()),
+            ]
+        };
+        // -p takes precedence over the defaults
+        let params = Some(vec![("pageSize".to_string(), "10".to_string())]);
+        assert_eq!(
+            with_default_params(&params, defaults()),
+            Some(vec![
+                ("pageSize".to_string(), "10".to_string()),
+                ("view".to_string(), "FULL".to_string()),
+            ])
+        );
+        assert_eq!(with_default_params(&None, defaults()).unwrap().len(), 2);
+        assert_eq!(with_default_params(&None, vec![]), None);
+        assert_eq!(with_default_params(&params, vec![]), params);
+    }
+
+    #[test]
+    fn test_build_url_with_path_params() {
+        let api = core::ZgApi::testdata();
+        let method = core::ZgMethod {
+            flat_path: "v1/{xyzId}/{locationId}/instances".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let params = Some(vec![
+            ("xyzId".to_string(), "my-project".to_string()),
+            ("locationId".to_string(), "us-central1".to_string()),
+        ]);
+        let url = build_url(&api, &method, &params, &Destination::default()).unwrap();
+        assert_eq!(
+            url,
+            "https://example.com/v1/my-project/us-central1/instances"
+        );
+    }
+
+    #[test]
+    fn test_build_url_with_query_params() {
+        let api = core::ZgApi::testdata();
+        let method = core::ZgMethod {
+            flat_path: "v1/instances".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let params = Some(vec![
+            ("filter".to_string(), "active".to_string()),
+            ("pageSize".to_string(), "10".to_string()),
+        ]);
+        let url = build_url(&api, &method, &params, &Destination::default()).unwrap();
+        assert_eq!(
+            url,
+            "https://example.com/v1/instances?filter=active&pageSize=10"
+        );
+    }
+
+    #[test]
+    fn test_resolve_params() {
+        let query_param = |name: &str, param_type: &str| core::ZgQueryParam {
+            name: name.to_string(),
+            param_type: Some(param_type.to_string()),
+            ..Default::default()
+        };
+        let api = core::ZgApi {
+            common_params: vec![query_param("prettyPrint", "boolean")],
+            ..core::ZgApi::testdata()
+        };
+        let method = core::ZgMethod {
+            flat_path: "v1/instances/{instancesId}".to_string(),
+            query_params: vec![
+                query_param("validateOnly", "boolean"),
+                query_param("filter", "string"),
+            ],
+            ..core::ZgMethod::testdata()
+        };
+        let resolve = |args: &[&str]| {
+            let args = parse_args(&[&["svc", "res", "m", "-p"], args].concat());
+            resolve_params(&args.params, &method, &api)
+        };
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+
+        // Boolean parameters, including the common ones, can be flags
+        assert_eq!(
+            resolve(&["instancesId=i1", "validateOnly", "prettyPrint"]).unwrap(),
+            Some(vec![
+                pair("instancesId", "i1"),
+                pair("validateOnly", "true"),
+                pair("prettyPrint", "true")
+            ])
+        );
+        let e = resolve(&["filter"]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "-p filter has no value; only boolean parameters can be given without one ('filter' is string). Use -p filter=VALUE (or -p filter= for an empty value)."
+        );
+        assert!(resolve(&["unknown"]).is_err());
+
+        // Empty values are sent for query parameters, but not for path parameters
+        assert_eq!(
+            resolve(&["instancesId=i1", "filter="]).unwrap(),
+            Some(vec![pair("instancesId", "i1"), pair("filter", "")])
+        );
+        let url = build_url(
+            &api,
+            &method,
+            &resolve(&["instancesId=i1", "filter="]).unwrap(),
+            &Destination::default(),
+        );
+        assert_eq!(url.unwrap(), "https://example.com/v1/instances/i1?filter=");
+        let e = resolve(&["instancesId="]).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("Path parameter 'instancesId' is empty"),
+            "{}",
+            e
+        );
+        // Values containing '=' are kept
+        assert_eq!(
+            resolve(&["instancesId=i1", "filter=a=b"]).unwrap().unwrap()[1],
+            pair("filter", "a=b")
+        );
+    }
+
+    #[test]
+    fn test_with_positional_values() {
+        let method = core::ZgMethod {
+            name: "get".to_string(),
+            flat_path: "projects/{project}/zones/{zone}/disks/{disk}/snapshots/{snapshot}"
+                .to_string(),
+            parameter_order: ["project", "zone", "disk", "snapshot"]
+                .map(String::from)
+                .to_vec(),
+            ..core::ZgMethod::testdata()
+        };
+        let assign = |args: &[&str]| {
+            let args = parse_args(&[&["compute", "snapshots", "get"], args].concat());
+            with_positional_values(&args.params, &args.values, &method)
+        };
+        let pair = |key: &str, value: &str| (key.to_string(), Some(value.to_string()));
+
+        // In the order of parameterOrder, skipping the autofilled project and zone
+        assert_eq!(
+            assign(&["d1", "s1"]).unwrap(),
+            Some(vec![pair("disk", "d1"), pair("snapshot", "s1")])
+        );
+        assert_eq!(assign(&["d1"]).unwrap(), Some(vec![pair("disk", "d1")]));
+        assert_eq!(assign(&[]).unwrap(), None);
+
+        // -p wins, and the values go to the rest
+        assert_eq!(
+            assign(&["s1", "-p", "disk=d1", "zone=z1"]).unwrap(),
+            Some(vec![
+                pair("disk", "d1"),
+                pair("zone", "z1"),
+                pair("snapshot", "s1")
+            ])
+        );
+
+        // Too many values
+        assert_eq!(
+            assign(&["d1", "s1", "x", "y"]).unwrap_err().to_string(),
+            "Too many values: 4 given, but 'get' takes 2 (disk, snapshot); unexpected 'x', 'y'. Autofilled parameters (project, region, and zone) and query parameters are given with -p KEY=VALUE."
+        );
+        let e = assign(&["s1", "-p", "disk=d1", "snapshot=s2"]).unwrap_err();
+        assert!(
+            e.to_string().starts_with(
+                "Too many values: 1 given, but 'get' takes no positional values; unexpected 's1'."
+            ),
+            "{}",
+            e
+        );
+
+        // Empty values are rejected like -p
+        let api = core::ZgApi::testdata();
+        let params = assign(&["", "s1"]).unwrap();
+        assert!(resolve_params(&params, &method, &api).is_err());
+    }
+
+    #[test]
+    fn test_repeated_query_params() {
+        let query_param = |name: &str, repeated: bool| core::ZgQueryParam {
+            name: name.to_string(),
+            repeated,
+            ..Default::default()
+        };
         let method = core::ZgMethod {
             flat_path: "v1/instances".to_string(),
+            query_params: vec![query_param("fields", true), query_param("pageSize", false)],
+            ..core::ZgMethod::testdata()
+        };
+        let params = Some(vec![
+            ("fields".to_string(), "name".to_string()),
+            ("fields".to_string(), "status".to_string()),
+            ("pageSize".to_string(), "10".to_string()),
+            ("pageSize".to_string(), "20".to_string()),
+        ]);
+
+        // Only the non-repeated one is reported
+        assert_eq!(
+            duplicated_query_params(&method.query_params, &params),
+            vec!["pageSize"]
+        );
+        assert!(duplicated_query_params(&method.query_params, &None).is_empty());
+
+        // All values are sent
+        let url = build_url(
+            &core::ZgApi::testdata(),
+            &method,
+            &params,
+            &Destination::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://example.com/v1/instances?fields=name&fields=status&pageSize=10&pageSize=20"
+        );
+    }
+
+    #[test]
+    fn test_with_pretty_print_disabled() {
+        let api = core::ZgApi {
+            common_params: vec![core::ZgQueryParam {
+                name: "prettyPrint".to_string(),
+                ..Default::default()
+            }],
+            ..core::ZgApi::testdata()
+        };
+        let params = Some(vec![("filter".to_string(), "active".to_string())]);
+        assert_eq!(
+            with_pretty_print_disabled(&api, &params),
+            Some(vec![
+                ("filter".to_string(), "active".to_string()),
+                ("prettyPrint".to_string(), "false".to_string())
+            ])
+        );
+
+        // Given explicitly
+        let params = Some(vec![("prettyPrint".to_string(), "true".to_string())]);
+        assert_eq!(with_pretty_print_disabled(&api, &params), params);
+
+        // APIs without the common parameter (e.g., custom APIs) are untouched
+        assert_eq!(
+            with_pretty_print_disabled(&core::ZgApi::testdata(), &None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_build_url_with_mixed_params() {
+        let api = core::ZgApi::testdata();
+        let method = core::ZgMethod {
+            flat_path: "v1/{xyzId}/instances".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let params = Some(vec![
+            ("xyzId".to_string(), "my-project".to_string()),
+            ("filter".to_string(), "active".to_string()),
+        ]);
+        let url = build_url(&api, &method, &params, &Destination::default()).unwrap();
+        assert_eq!(
+            url,
+            "https://example.com/v1/my-project/instances?filter=active"
+        );
+    }
+
+    #[test]
+    fn test_with_endpoint() {
+        let url = "https://pubsub.googleapis.com/v1/projects/p/topics/t:publish?prettyPrint=false";
+        assert_eq!(
+            with_endpoint(url, "http://localhost:8085").unwrap(),
+            "http://localhost:8085/v1/projects/p/topics/t:publish?prettyPrint=false"
+        );
+        // Trailing slash, no port
+        assert_eq!(
+            with_endpoint(url, "https://pubsub-psc.p.googleapis.com/").unwrap(),
+            "https://pubsub-psc.p.googleapis.com/v1/projects/p/topics/t:publish?prettyPrint=false"
+        );
+        // The path of the endpoint goes before the path of the method
+        assert_eq!(
+            with_endpoint(url, "https://gateway.example.com:8443/pubsub").unwrap(),
+            "https://gateway.example.com:8443/pubsub/v1/projects/p/topics/t:publish?prettyPrint=false"
+        );
+        // Default ports are dropped by the URL parser
+        assert_eq!(
+            with_endpoint("https://spanner.googleapis.com/v1/x", "http://127.0.0.1:80").unwrap(),
+            "http://127.0.0.1/v1/x"
+        );
+
+        for invalid in ["localhost:8085", "ftp://example.com", "not a url"] {
+            let e = with_endpoint(url, invalid).unwrap_err();
+            assert!(
+                e.to_string()
+                    .starts_with(&format!("Invalid endpoint '{}'", invalid)),
+                "{}",
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_endpoint() {
+        let env = |var: &str| (var == "PUBSUB_EMULATOR_HOST").then(|| "localhost:8085".to_string());
+        let resolve = |args: &[&str], service: &str, api_id: &str| {
+            resolve_endpoint(&parse_args(args), service, api_id, env)
+        };
+        let endpoint = |url: &str, emulator: bool| {
+            Some(Endpoint {
+                url: url.to_string(),
+                emulator,
+            })
+        };
+
+        // The emulator host, without authentication by default
+        let emulator = resolve(&["pubsub", "topics", "list"], "pubsub", "pubsub:v1");
+        assert_eq!(emulator, endpoint("http://localhost:8085", true));
+        let args = parse_args(&["pubsub", "topics", "list"]);
+        assert!(no_auth(&args, emulator.as_ref()));
+
+        // --endpoint wins, with authentication
+        let given = resolve(
+            &[
+                "pubsub",
+                "topics",
+                "list",
+                "--endpoint",
+                "https://psc.example.com",
+            ],
+            "pubsub",
+            "pubsub:v1",
+        );
+        assert_eq!(given, endpoint("https://psc.example.com", false));
+        assert!(!no_auth(&args, given.as_ref()));
+        assert!(no_auth(
+            &parse_args(&["pubsub", "topics", "list", "--no-auth"]),
+            given.as_ref()
+        ));
+
+        // Other services don't use the emulator variables of others
+        assert_eq!(
+            resolve(&["spanner", "instances", "list"], "spanner", "spanner:v1"),
+            None
+        );
+        assert_eq!(
+            resolve(&["compute", "instances", "list"], "compute", "compute:v1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_build_url_with_regional_endpoint() {
+        let api = core::ZgApi {
+            id: "aiplatform:v1".to_string(),
+            base_url: "https://aiplatform.googleapis.com/".to_string(),
+            ..core::ZgApi::testdata()
+        };
+        let method = core::ZgMethod {
+            flat_path: "v1/projects/{projectsId}/locations/{locationsId}/endpoints".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let params = |location: &str| {
+            Some(vec![
+                ("projectsId".to_string(), "my-project".to_string()),
+                ("locationsId".to_string(), location.to_string()),
+            ])
+        };
+
+        // The region given with -p goes to the host
+        assert_eq!(
+            build_url(&api, &method, &params("us-central1"), &Destination::default()).unwrap(),
+            "https://us-central1-aiplatform.googleapis.com/v1/projects/my-project/locations/us-central1/endpoints"
+        );
+        // In another universe domain
+        let universe = Destination {
+            universe_domain: Some("apis.example.com".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_url(&api, &method, &params("us-central1"), &universe).unwrap(),
+            "https://us-central1-aiplatform.apis.example.com/v1/projects/my-project/locations/us-central1/endpoints"
+        );
+        assert_eq!(
+            build_url(&api, &method, &params("global"), &universe).unwrap(),
+            "https://aiplatform.apis.example.com/v1/projects/my-project/locations/global/endpoints"
+        );
+        // On the mTLS host
+        let mtls = Destination {
+            mtls: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            build_url(&api, &method, &params("us-central1"), &mtls).unwrap(),
+            "https://us-central1-aiplatform.mtls.googleapis.com/v1/projects/my-project/locations/us-central1/endpoints"
+        );
+        // The global location uses the base_url
+        assert_eq!(
+            build_url(&api, &method, &params("global"), &Destination::default()).unwrap(),
+            "https://aiplatform.googleapis.com/v1/projects/my-project/locations/global/endpoints"
+        );
+        // So do paths without a location
+        let method = core::ZgMethod {
+            flat_path: "v1/publishers/{publishersId}/models".to_string(),
             ..core::ZgMethod::testdata()
         };
-        let params = Some(vec![
-            ("filter".to_string(), "active".to_string()),
-            ("pageSize".to_string(), "10".to_string()),
-        ]);
-        let url = build_url(&base_url, &method, &params).unwrap();
+        let params = Some(vec![("publishersId".to_string(), "google".to_string())]);
+        assert_eq!(
+            build_url(&api, &method, &params, &Destination::default()).unwrap(),
+            "https://aiplatform.googleapis.com/v1/publishers/google/models"
+        );
+    }
+
+    #[test]
+    fn test_gcloud_spawn_error() {
+        let e = gcloud_spawn_error(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(e, ZgError::GcloudMissing));
+        let e = gcloud_spawn_error(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(matches!(e, ZgError::Io(_)));
+    }
+
+    /// Writes a gcloud stub with the script body into a new directory to use as PATH.
+    #[cfg(unix)]
+    fn gcloud_stub(name: &str, script: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let dir =
+            std::env::temp_dir().join(format!("zg-test-gcloud-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stub = dir.join("gcloud");
+        fs::write(&stub, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_gcloud() {
+        let ok = gcloud_stub("ok", r#"echo " ya29.token-for-$1 ""#);
+        assert_eq!(
+            gcloud(&["auth", "print-access-token"], ok.as_os_str()).unwrap(),
+            "ya29.token-for-auth"
+        );
+
+        let failing = gcloud_stub(
+            "failing",
+            "echo 'ERROR: (gcloud.auth.print-access-token) You do not currently have an active account selected.' >&2; exit 1",
+        );
+        match gcloud(&["auth", "print-access-token"], failing.as_os_str()) {
+            Err(ZgError::GcloudFailed { command, stderr }) => {
+                assert_eq!(command, "auth print-access-token");
+                assert!(stderr.contains("You do not currently have an active account selected."));
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let empty =
+            std::env::temp_dir().join(format!("zg-test-gcloud-none-{}", std::process::id()));
+        fs::create_dir_all(&empty).unwrap();
+        assert!(matches!(
+            gcloud(&["config", "get", "core/project"], empty.as_os_str()),
+            Err(ZgError::GcloudMissing)
+        ));
+
+        for dir in [ok, failing, empty] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_properties_value() {
+        let content = "[core]\naccount = a@example.com\n# universe_domain = commented.example\n\
+                       universe_domain = example.goog\nproject =\n\n[compute]\nuniverse_domain = other\n";
+        assert_eq!(
+            properties_value(content, "core", "universe_domain").as_deref(),
+            Some("example.goog")
+        );
+        assert_eq!(properties_value(content, "core", "project"), None);
+        assert_eq!(
+            properties_value(content, "compute", "universe_domain").as_deref(),
+            Some("other")
+        );
+        assert_eq!(properties_value(content, "auth", "universe_domain"), None);
+    }
+
+    #[test]
+    fn test_gcloud_property() {
+        let home = env::temp_dir().join(format!("zg-test-gcloud-home-{}", std::process::id()));
+        let dir = home.join(".config/gcloud");
+        fs::create_dir_all(dir.join("configurations")).unwrap();
+        fs::write(
+            dir.join("configurations/config_default"),
+            "[core]\nuniverse_domain = a.goog\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("configurations/config_work"),
+            "[core]\nuniverse_domain = b.goog\n",
+        )
+        .unwrap();
+        let property = |vars: &[(&str, &str)]| {
+            let env = |var: &str| {
+                let found = vars.iter().find(|(name, _)| *name == var);
+                found.map(|(_, value)| value.to_string())
+            };
+            gcloud_property("core", "universe_domain", env, Some(home.clone()))
+        };
+
+        assert_eq!(property(&[]).as_deref(), Some("a.goog"));
+        fs::write(dir.join("active_config"), "work\n").unwrap();
+        assert_eq!(property(&[]).as_deref(), Some("b.goog"));
         assert_eq!(
-            url,
-            "https://example.com/v1/instances?filter=active&pageSize=10"
+            property(&[("CLOUDSDK_ACTIVE_CONFIG_NAME", "default")]).as_deref(),
+            Some("a.goog")
+        );
+        assert_eq!(
+            property(&[("CLOUDSDK_CORE_UNIVERSE_DOMAIN", "c.goog")]).as_deref(),
+            Some("c.goog")
         );
+        assert_eq!(property(&[("CLOUDSDK_CONFIG", "/nonexistent")]), None);
+
+        let _ = fs::remove_dir_all(home);
     }
 
     #[test]
-    fn test_build_url_with_mixed_params() {
-        let base_url = "https://example.com/".to_string();
-        let method = core::ZgMethod {
-            flat_path: "v1/{xyzId}/instances".to_string(),
-            ..core::ZgMethod::testdata()
+    fn test_summary() {
+        let summary = |response: Value| {
+            let summary = Summary::of(&response);
+            (summary.to_string(), serde_json::to_value(&summary).unwrap())
         };
-        let params = Some(vec![
-            ("xyzId".to_string(), "my-project".to_string()),
-            ("filter".to_string(), "active".to_string()),
+        assert_eq!(
+            summary(json!({"clusters": [{"name": "a"}, {"name": "b"}], "nextPageToken": "t"})),
+            (
+                "2 items in 'clusters'; more pages remain (nextPageToken)".to_string(),
+                json!({"count": 2, "field": "clusters", "next_page_token": true})
+            )
+        );
+        // Merged pages of --paginate without more pages, and `unreachable` ignored
+        assert_eq!(
+            summary(json!({"items": [1], "unreachable": ["us-east1"]})).0,
+            "1 item in 'items'; no more pages"
+        );
+        assert_eq!(
+            summary(json!({})),
+            (
+                "0 items; no more pages".to_string(),
+                json!({"count": 0, "next_page_token": false})
+            )
+        );
+        assert_eq!(summary(json!([1, 2, 3])).0, "3 items; no more pages");
+        assert_eq!(
+            summary(json!({"name": "c1", "status": "RUNNING", "nodePools": [], "locations": []})),
+            (
+                "Not a list; top-level keys: locations, name, nodePools, status".to_string(),
+                json!({"keys": ["locations", "name", "nodePools", "status"]})
+            )
+        );
+        assert_eq!(
+            summary(json!("text")),
+            ("Not a list".to_string(), json!({}))
+        );
+    }
+
+    #[test]
+    fn test_parse_status_pattern() {
+        assert_eq!(parse_status_pattern("200"), Ok(StatusPattern::Code(200)));
+        assert_eq!(parse_status_pattern("404"), Ok(StatusPattern::Code(404)));
+        assert_eq!(parse_status_pattern("2xx"), Ok(StatusPattern::Class(2)));
+        assert_eq!(parse_status_pattern("5XX"), Ok(StatusPattern::Class(5)));
+        for invalid in [
+            "", "ok", "20", "600", "099", "0xx", "6xx", "xx", "2x", "-200",
+        ] {
+            assert!(parse_status_pattern(invalid).is_err(), "{}", invalid);
+        }
+
+        let args = parse_args(&["gke", "clusters", "list", "--expect-status", "200,4xx"]);
+        assert_eq!(
+            args.expect_status,
+            Some(vec![StatusPattern::Code(200), StatusPattern::Class(4)])
+        );
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "get",
+            "--expect-status",
+            "404",
+            "--expect-status",
+            "410",
         ]);
-        let url = build_url(&base_url, &method, &params).unwrap();
         assert_eq!(
-            url,
-            "https://example.com/v1/my-project/instances?filter=active"
+            args.expect_status,
+            Some(vec![StatusPattern::Code(404), StatusPattern::Code(410)])
+        );
+    }
+
+    #[test]
+    fn test_check_status() {
+        let expected = [StatusPattern::Code(200), StatusPattern::Class(4)];
+        assert!(check_status(&expected, 200, "{}").is_ok());
+        assert!(check_status(&expected, 404, "").is_ok());
+        assert!(check_status(&expected, 429, "").is_ok());
+        match check_status(&expected, 204, "") {
+            Err(ZgError::UnexpectedStatus {
+                status,
+                expected,
+                body,
+            }) => {
+                assert_eq!(status, 204);
+                assert_eq!(expected, "200, 4xx");
+                assert_eq!(body, "");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        let e = check_status(&[StatusPattern::Class(2)], 503, &"x".repeat(600)).unwrap_err();
+        assert_eq!(e.exit_code(), 10);
+        assert_eq!(
+            e.to_string(),
+            format!("Expected HTTP status 2xx, got 503: {}...", "x".repeat(500))
         );
+
+        // The 401 after retrying is checked like other statuses only with --expect-status
+        let unauthorized = || {
+            Err(ZgError::HttpStatus {
+                status: 401,
+                body: "Unauthorized".to_string(),
+            })
+        };
+        let res = expected_failure(unauthorized(), Some(&[StatusPattern::Code(401)])).unwrap();
+        assert_eq!(res.status, 401);
+        assert!(expected_failure(unauthorized(), None).is_err());
+    }
+
+    #[test]
+    fn test_scope_error_hint() {
+        let body = r#"{"error": {"code": 403, "status": "PERMISSION_DENIED", "details": [{"reason": "ACCESS_TOKEN_SCOPE_INSUFFICIENT"}]}}"#;
+        assert!(scope_error_hint("drive:v3", body).is_some());
+        assert!(scope_error_hint("admin:directory_v1", body).is_some());
+        // Not a Workspace API, or not a scope error
+        assert!(scope_error_hint("container:v1", body).is_none());
+        assert!(scope_error_hint("drive:v3", r#"{"files": []}"#).is_none());
     }
 
     #[test]
@@ -499,32 +3287,121 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_prepare_json_string_from_files_and_stdin() {
+        let dir = env::temp_dir().join(format!("zg-data-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            format!("@{}", path.display())
+        };
+        let no_stdin = || -> std::io::Result<String> { panic!("stdin shouldn't be read") };
+        let expected = r#"{"labels":{"env":"dev"},"name":"c1","nodes":3}"#;
+
+        let json = write(
+            "body.json",
+            r#"{"name": "c1", "nodes": 3, "labels": {"env": "dev"}}"#,
+        );
+        assert_eq!(prepare_json_string_with(&json, no_stdin).unwrap(), expected);
+        for name in ["body.yaml", "body.YML"] {
+            let yaml = write(name, "name: c1\nnodes: 3\nlabels:\n  env: dev\n");
+            assert_eq!(prepare_json_string_with(&yaml, no_stdin).unwrap(), expected);
+        }
+        let invalid = write("invalid.yaml", "name: [c1\n");
+        let e = prepare_json_string_with(&invalid, no_stdin).unwrap_err();
+        assert!(
+            e.to_string().starts_with("Invalid YAML syntax in file"),
+            "{}",
+            e
+        );
+
+        let stdin = || Ok(r#"{"name": "c1", "nodes": 3, "labels": {"env": "dev"}}"#.to_string());
+        assert_eq!(prepare_json_string_with("@-", stdin).unwrap(), expected);
+        let e = prepare_json_string_with("@-", || Ok("name: c1".to_string())).unwrap_err();
+        assert!(
+            e.to_string().starts_with("Invalid JSON syntax in stdin"),
+            "{}",
+            e
+        );
+
+        // Missing files are reported with the resolved path and the current directory
+        let e = prepare_json_string_with("@missing/body.json", no_stdin).unwrap_err();
+        let cwd = env::current_dir().unwrap();
+        assert!(
+            e.to_string().starts_with(&format!(
+                "Failed to read file 'missing/body.json' (resolved to {}, current directory: {}): ",
+                cwd.join("missing/body.json").display(),
+                cwd.display()
+            )),
+            "{}",
+            e
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_generate_curl() {
-        let base_url = "https://example.com/".to_string();
+        let api = core::ZgApi::testdata();
         let method = core::ZgMethod {
             http_method: "PUT".to_string(),
             flat_path: "v1/resources/{resourcesId}".to_string(),
             ..core::ZgMethod::testdata()
         };
         let args = ExecArgs {
-            service: "test_service".to_string(),
-            resource: "test_resource".to_string(),
-            method: "test_method".to_string(),
+            service: Some("test_service".to_string()),
+            resource: Some("test_resource".to_string()),
+            method: Some("test_method".to_string()),
+            values: vec![],
+            endpoint: None,
+            no_auth: false,
+            cache_ttl: None,
+            no_cache: false,
+            mtls: false,
+            client_cert: None,
+            client_key: None,
             headers: Some(vec![(
                 "X-Custom-Header".to_string(),
                 "CustomValue".to_string(),
             )]),
             params: Some(vec![
-                ("resourcesId".to_string(), "myResourceId".to_string()),
-                ("qp1".to_string(), "value1".to_string()),
-                ("qp2".to_string(), "value2".to_string()),
+                ("resourcesId".to_string(), Some("myResourceId".to_string())),
+                ("qp1".to_string(), Some("value1".to_string())),
+                ("qp2".to_string(), Some("value2".to_string())),
             ]),
             data: Some("{\"key\":\"value\"}".to_string()),
+            data_binary: None,
+            content_type: None,
+            force_body: false,
             equivalent_curl: false,
+            strict: false,
+            no_strict: false,
+            paginate: false,
+            max_pages: None,
+            limit: None,
+            resume_token: None,
+            template: None,
+            template_file: None,
+            raw: false,
+            no_pager: false,
+            summary: false,
+            expect_status: None,
+            no_template: false,
+            env_expand: false,
+            show_timing: false,
+            quiet: false,
+            trace: false,
+            rerun: None,
+            rerun_id: None,
+            save_as: None,
+            force: false,
+            split_output: None,
+            split_key: "name".to_string(),
         };
 
-        let curl_command = generate_curl(&base_url, &method, &args).unwrap();
+        let params = resolve_params(&args.params, &method, &api).unwrap();
+        let curl_command =
+            generate_curl(&api, &method, &args, &params, None, &Destination::default()).unwrap();
 
         let expected_command = concat!(
             "curl -X PUT \\\n",
@@ -537,4 +3414,737 @@ mod tests {
 
         assert_eq!(curl_command, expected_command);
     }
+
+    /// Parses the exec arguments as on the command line, e.g., `["svc", "res", "delete", "-d", "{}"]`.
+    fn parse_args(args: &[&str]) -> ExecArgs {
+        use clap::Parser;
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            args: ExecArgs,
+        }
+        Cli::parse_from(std::iter::once("zg").chain(args.iter().copied())).args
+    }
+
+    #[test]
+    fn test_with_method_as_value() {
+        // clap takes the first value for METHOD after a method id
+        let args = parse_args(&["spanner", "spanner.projects.instances.get", "i1", "x"]);
+        assert_eq!(args.method.as_deref(), Some("i1"));
+        let shifted = args.with_method_as_value();
+        assert_eq!(shifted.method, None);
+        assert_eq!(shifted.values, vec!["i1", "x"]);
+        assert_eq!(shifted.resource, args.resource);
+    }
+
+    #[test]
+    fn test_with_rerun() {
+        let entry = history::Entry {
+            method: Some("list".to_string()),
+            params: vec![
+                (
+                    "parent".to_string(),
+                    Some("projects/p/locations/-".to_string()),
+                ),
+                ("fields".to_string(), Some("clusters.name".to_string())),
+                ("fields".to_string(), Some("clusters.status".to_string())),
+            ],
+            data_omitted: true,
+            ..history::Entry::new("gke", "clusters")
+        };
+
+        // Recorded arguments as they are
+        let args = parse_args(&["--rerun"]).with_rerun(&entry);
+        assert_eq!(
+            args.command_line(),
+            "zg exec gke clusters list -p 'parent=projects/p/locations/-' -p 'fields=clusters.name' -p 'fields=clusters.status'"
+        );
+        assert_eq!(args.rerun, None);
+
+        // -p replaces all the recorded values of the parameter, and adds new ones; --data is given
+        let args = parse_args(&[
+            "--rerun",
+            "2",
+            "-p",
+            "fields=clusters",
+            "pageSize=5",
+            "-d",
+            r#"{"a": 1}"#,
+        ]);
+        assert_eq!(args.rerun, Some(2));
+        let args = args.with_rerun(&entry);
+        assert_eq!(
+            args.command_line(),
+            r#"zg exec gke clusters list -p 'parent=projects/p/locations/-' -p 'fields=clusters' -p 'pageSize=5' -d '{"a": 1}'"#
+        );
+
+        // A binary body is rerun with its options, unless another body is given
+        let upload = history::Entry {
+            method: Some("create".to_string()),
+            data_binary: Some("@rows.csv".to_string()),
+            content_type: Some("text/csv".to_string()),
+            force_body: true,
+            ..history::Entry::new("gke", "clusters")
+        };
+        let args = parse_args(&["--rerun"]).with_rerun(&upload);
+        assert_eq!(
+            args.command_line(),
+            "zg exec gke clusters create --data-binary @rows.csv --content-type text/csv --force-body"
+        );
+        let args = parse_args(&["--rerun", "-d", "{}"]).with_rerun(&upload);
+        assert_eq!((args.data.as_deref(), args.data_binary), (Some("{}"), None));
+
+        // Positional arguments can't be combined with --rerun
+        use clap::Parser;
+        #[derive(Parser, Debug)]
+        struct Cli {
+            #[command(flatten)]
+            args: ExecArgs,
+        }
+        assert!(Cli::try_parse_from(["zg", "gke", "clusters", "list", "--rerun-id", "x"]).is_err());
+        assert!(Cli::try_parse_from(["zg", "gke"]).is_err());
+    }
+
+    #[test]
+    fn test_with_functions_expanded() {
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "create",
+            "-p",
+            "requestId={{uuid}}",
+            "-d",
+            r#"{"name": "c-{{rand:6}}", "labels": {"kept": "\{{rand:6}}"}}"#,
+        ]);
+        let expanded = args.with_functions_expanded().unwrap();
+        let params = expanded.params.unwrap();
+        assert_eq!(params[0].0, "requestId");
+        assert_eq!(params[0].1.as_ref().unwrap().len(), 36);
+        let data: Value = from_str(&expanded.data.unwrap()).unwrap();
+        assert_eq!(data["name"].as_str().unwrap().len(), "c-".len() + 6);
+        assert_eq!(data["labels"]["kept"], "{{rand:6}}");
+
+        // --no-template keeps them as they are
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "create",
+            "-p",
+            "x={{uuid}}",
+            "--no-template",
+        ]);
+        assert!(args.no_template);
+    }
+
+    #[test]
+    fn test_env_expanded() {
+        let env = |name: &str| match name {
+            "ZG_TEST_EXEC_ZONE" => Some("us-central1-a".to_string()),
+            "ZG_TEST_EXEC_NAME" => Some("c1".to_string()),
+            _ => None,
+        };
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "create",
+            "-p",
+            "parent=projects/p/locations/${env:ZG_TEST_EXEC_ZONE}",
+            "-d",
+            r#"{"name": "$ZG_TEST_EXEC_NAME", "kept": "\${env:ZG_TEST_EXEC_NAME}"}"#,
+        ]);
+        let expanded = args.with_functions_expanded_with(env).unwrap();
+        assert_eq!(
+            expanded.params.unwrap()[0].1.as_deref(),
+            Some("projects/p/locations/us-central1-a")
+        );
+        // Bare references are left without --env-expand
+        assert_eq!(
+            expanded.data.unwrap(),
+            r#"{"name": "$ZG_TEST_EXEC_NAME", "kept": "${env:ZG_TEST_EXEC_NAME}"}"#
+        );
+
+        // Data files are expanded too, parsed by their extension
+        let path = std::env::temp_dir().join(format!("zg-test-env-{}.yaml", std::process::id()));
+        fs::write(
+            &path,
+            "name: ${ZG_TEST_EXEC_NAME}\nzone: $ZG_TEST_EXEC_ZONE\n",
+        )
+        .unwrap();
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "create",
+            "--env-expand",
+            "-d",
+            &format!("@{}", path.display()),
+        ]);
+        let expanded = args.with_functions_expanded_with(env).unwrap();
+        let data: Value = from_str(&expanded.data.unwrap()).unwrap();
+        assert_eq!(data, json!({"name": "c1", "zone": "us-central1-a"}));
+        fs::remove_file(&path).unwrap();
+
+        // All the unset variables are reported at once
+        let args = parse_args(&[
+            "gke",
+            "clusters",
+            "create",
+            "--env-expand",
+            "-p",
+            "a=${env:ZG_TEST_EXEC_UNSET_B}",
+            "-d",
+            r#"{"a": "$ZG_TEST_EXEC_UNSET_A", "b": "{{env:ZG_TEST_EXEC_UNSET_B}}"}"#,
+        ]);
+        assert_eq!(
+            args.with_functions_expanded_with(env)
+                .unwrap_err()
+                .to_string(),
+            "Environment variables not set: ZG_TEST_EXEC_UNSET_A, ZG_TEST_EXEC_UNSET_B"
+        );
+    }
+
+    #[test]
+    fn test_request_body() {
+        let method = |http_method: &str, with_schema: bool| core::ZgMethod {
+            http_method: http_method.to_string(),
+            request_data_schema: with_schema.then(crate::discovery::Schema::testdata),
+            ..core::ZgMethod::testdata()
+        };
+        let without_data = parse_args(&["svc", "res", "m"]);
+        let with_data = parse_args(&["svc", "res", "m", "-d", r#"{"etag": "abc"}"#]);
+
+        // GET and DELETE send no body without --data, as before
+        assert_eq!(
+            request_body(&without_data, &method("GET", false)).unwrap(),
+            None
+        );
+        assert_eq!(
+            request_body(&without_data, &method("DELETE", false)).unwrap(),
+            None
+        );
+        assert_eq!(
+            request_body(&without_data, &method("DELETE", true)).unwrap(),
+            None
+        );
+        assert_eq!(
+            request_body(&without_data, &method("POST", true))
+                .unwrap()
+                .as_deref(),
+            Some("{}")
+        );
+        // --data is sent with methods taking a body, or POST even without a request schema
+        for (http_method, with_schema) in [("DELETE", true), ("POST", true), ("POST", false)] {
+            assert_eq!(
+                request_body(&with_data, &method(http_method, with_schema))
+                    .unwrap()
+                    .as_deref(),
+                Some(r#"{"etag":"abc"}"#)
+            );
+        }
+        // GET and DELETE without a request schema reject it, unless --force-body is given
+        let forced = parse_args(&[
+            "svc",
+            "res",
+            "m",
+            "-d",
+            r#"{"etag": "abc"}"#,
+            "--force-body",
+        ]);
+        for http_method in ["GET", "DELETE"] {
+            let e = request_body(&with_data, &method(http_method, false)).unwrap_err();
+            assert!(e.to_string().contains("--force-body"), "{}", e);
+            assert_eq!(
+                request_body(&forced, &method(http_method, false))
+                    .unwrap()
+                    .as_deref(),
+                Some(r#"{"etag":"abc"}"#)
+            );
+        }
+    }
+
+    #[test]
+    fn test_binary_body() {
+        // Bytes are sent as they are, even if they're neither JSON nor UTF-8
+        let path = env::temp_dir().join(format!("zg-binary-{}.bin", std::process::id()));
+        let bytes = b"\x08\x96\x01name,size\n\xff{not json";
+        fs::write(&path, bytes).unwrap();
+        let data = format!("@{}", path.display());
+        let read = read_binary_with(&data, || unreachable!()).unwrap();
+        assert_eq!(read.as_ref(), bytes);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            read_binary_with("@-", || Ok(b"a,b\n1,2\n".to_vec()))
+                .unwrap()
+                .as_ref(),
+            b"a,b\n1,2\n"
+        );
+        assert_eq!(
+            read_binary_with("key: value", || unreachable!())
+                .unwrap()
+                .as_ref(),
+            b"key: value"
+        );
+        assert!(read_binary_with("@/no/such/file", || unreachable!()).is_err());
+
+        // GET and DELETE without a request schema reject it unless --force-body is given
+        let get = core::ZgMethod {
+            http_method: "GET".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        assert!(binary_body("raw", false, &get)
+            .unwrap_err()
+            .to_string()
+            .contains("--force-body"));
+        assert_eq!(binary_body("raw", true, &get).unwrap().as_ref(), b"raw");
+
+        // --data and --data-binary can't be combined
+        use clap::Parser;
+        #[derive(Parser, Debug)]
+        struct Cli {
+            #[command(flatten)]
+            args: ExecArgs,
+        }
+        assert!(
+            Cli::try_parse_from(["zg", "s", "r", "m", "-d", "{}", "--data-binary", "x"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_content_type() {
+        let json = parse_args(&["svc", "res", "m", "-d", "{}"]);
+        assert_eq!(content_type(&json), "application/json; charset=utf-8");
+        let binary = parse_args(&["svc", "res", "m", "--data-binary", "@a.csv"]);
+        assert_eq!(content_type(&binary), "application/octet-stream");
+        let csv = parse_args(&[
+            "svc",
+            "res",
+            "m",
+            "--data-binary",
+            "@a.csv",
+            "--content-type",
+            "text/csv",
+        ]);
+        assert_eq!(content_type(&csv), "text/csv");
+
+        // --content-type replaces the default, and -H replaces both
+        let headers = build_headers(&None, None, content_type(&csv)).unwrap();
+        assert_eq!(headers["Content-Type"], "text/csv");
+        let custom = Some(vec![(
+            "content-type".to_string(),
+            "application/x-protobuf".to_string(),
+        )]);
+        let headers = build_headers(&custom, None, content_type(&csv)).unwrap();
+        assert_eq!(headers["Content-Type"], "application/x-protobuf");
+    }
+
+    #[test]
+    fn test_bodiless_method_error() {
+        let method = core::ZgMethod {
+            query_params: ["filter", "pageSize", "orderBy"]
+                .into_iter()
+                .map(|name| core::ZgQueryParam {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..core::ZgMethod::testdata()
+        };
+        assert_eq!(
+            bodiless_method_error(&method, r#"{"filter": "status=RUNNING", "page_size": 10, "x": 1}"#)
+                .to_string(),
+            "Method 'list' uses GET, which takes no request body, but --data is given. Give query parameters with -p KEY=VALUE instead (similar to the body: -p filter=VALUE -p pageSize=VALUE), or send the body anyway with --force-body."
+        );
+        assert_eq!(
+            bodiless_method_error(&method, "[1]").to_string(),
+            "Method 'list' uses GET, which takes no request body, but --data is given. Give query parameters with -p KEY=VALUE instead, or send the body anyway with --force-body."
+        );
+    }
+
+    #[test]
+    fn test_generate_curl_data_binary() {
+        let api = core::ZgApi::testdata();
+        let method = core::ZgMethod {
+            http_method: "POST".to_string(),
+            flat_path: "v1/resources/{resourcesId}:import".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let args = parse_args(&[
+            "svc",
+            "res",
+            "m",
+            "-p",
+            "resourcesId=r1",
+            "--data-binary",
+            "@rows.csv",
+            "--content-type",
+            "text/csv",
+        ]);
+        let params = resolve_params(&args.params, &method, &api).unwrap();
+        let curl =
+            generate_curl(&api, &method, &args, &params, None, &Destination::default()).unwrap();
+        assert!(curl.contains("-H \"Content-Type: text/csv\""), "{}", curl);
+        assert!(
+            curl.contains(" \\\n  --data-binary @rows.csv \\\n"),
+            "{}",
+            curl
+        );
+        assert!(!curl.contains("application/json"), "{}", curl);
+    }
+
+    #[test]
+    fn test_generate_curl_delete() {
+        let api = core::ZgApi::testdata();
+        let method = core::ZgMethod {
+            http_method: "DELETE".to_string(),
+            flat_path: "v1/resources/{resourcesId}".to_string(),
+            ..core::ZgMethod::testdata()
+        };
+        let plain = parse_args(&["svc", "res", "m", "-p", "resourcesId=r1"]);
+        let params = resolve_params(&plain.params, &method, &api).unwrap();
+        let curl = generate_curl(
+            &api,
+            &method,
+            &plain,
+            &params,
+            None,
+            &Destination::default(),
+        )
+        .unwrap();
+        assert!(curl.starts_with("curl -X DELETE"));
+        assert!(!curl.contains(" -d "));
+
+        let with_data = parse_args(&[
+            "svc",
+            "res",
+            "m",
+            "-p",
+            "resourcesId=r1",
+            "-d",
+            r#"{"force": true}"#,
+        ]);
+        let params = resolve_params(&with_data.params, &method, &api).unwrap();
+        let curl = generate_curl(
+            &api,
+            &method,
+            &with_data,
+            &params,
+            None,
+            &Destination::default(),
+        )
+        .unwrap();
+        assert!(
+            curl.contains("  -d '\n{\n  \"force\": true\n}' \\\n"),
+            "{}",
+            curl
+        );
+    }
+
+    #[test]
+    fn test_api_key_auth() {
+        let key = Some("my-key".to_string());
+        assert_eq!(
+            api_key_auth("generativelanguage:v1beta", key.clone()),
+            key.clone()
+        );
+        assert_eq!(api_key_auth("generativelanguage:v1beta", None), None);
+        // APIs in the Discovery Service keep using the access token
+        assert_eq!(api_key_auth("container:v1", key), None);
+
+        let params = with_api_key(&None, Some("my-key")).unwrap();
+        assert_eq!(params, vec![("key".to_string(), "my-key".to_string())]);
+        assert_eq!(with_api_key(&None, None), None);
+    }
+
+    /// Serves 6 items, 2 per page (or fewer if asked), with the offset of the next item as the page token.
+    async fn fake_page(
+        page_token: Option<String>,
+        max_items: Option<u64>,
+    ) -> Result<Value, ZgError> {
+        let start: usize = page_token.map_or(0, |token| token.parse().unwrap());
+        let end = (start + max_items.map_or(2, |max| max.min(2) as usize)).min(6);
+        let mut page = json!({
+            "items": (start..end).map(|i| format!("item{}", i)).collect::<Vec<_>>(),
+            "unreachable": [],
+        });
+        if end < 6 {
+            page["nextPageToken"] = json!(end.to_string());
+        }
+        Ok(page)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_pages() {
+        let all = json!(["item0", "item1", "item2", "item3", "item4", "item5"]);
+        let (merged, token) = fetch_pages(None, None, None, fake_page).await.unwrap();
+        assert_eq!(merged, json!({"items": all, "unreachable": []}));
+        assert_eq!(token, None);
+
+        // Stops after 2 pages, and resuming from the token fetches the rest without duplicates
+        let (first, token) = fetch_pages(None, Some(2), None, fake_page).await.unwrap();
+        assert_eq!(first["items"], json!(["item0", "item1", "item2", "item3"]));
+        assert_eq!(first["nextPageToken"], "4");
+        assert_eq!(token.as_deref(), Some("4"));
+        let (rest, token) = fetch_pages(Some("4".to_string()), None, None, fake_page)
+            .await
+            .unwrap();
+        assert_eq!(rest["items"], json!(["item4", "item5"]));
+        assert_eq!(token, None);
+
+        // The last page is shrunk to fit the limit
+        let (first, token) = fetch_pages(None, None, Some(3), fake_page).await.unwrap();
+        assert_eq!(first["items"], json!(["item0", "item1", "item2"]));
+        assert_eq!(token.as_deref(), Some("3"));
+        let (rest, _) = fetch_pages(token, None, Some(3), fake_page).await.unwrap();
+        assert_eq!(rest["items"], json!(["item3", "item4", "item5"]));
+        assert!(rest.get("nextPageToken").is_none());
+
+        // --template renders the merged response
+        let template = template::unescape(r"{{#each items}}{{this}}\n{{/each}}");
+        assert_eq!(
+            template::render(&template, &merged).unwrap(),
+            "item0\nitem1\nitem2\nitem3\nitem4\nitem5\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_for_each_page_ndjson() {
+        // Each page is written before the next one is fetched
+        let lines = RefCell::new(Vec::new());
+        let fetch = |page_token: Option<String>, max_items| {
+            let written = String::from_utf8(lines.borrow().clone()).unwrap();
+            assert_eq!(
+                written.lines().count(),
+                page_token
+                    .as_deref()
+                    .map_or(0, |token: &str| token.parse().unwrap())
+            );
+            fake_page(page_token, max_items)
+        };
+        let token = for_each_page(None, None, None, fetch, |page| {
+            output::write_ndjson(&mut *lines.borrow_mut(), &page)
+        })
+        .await
+        .unwrap();
+        assert_eq!(token, None);
+
+        let output = String::from_utf8(lines.into_inner()).unwrap();
+        let records: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[5], "item5");
+    }
+
+    #[test]
+    fn test_page_params() {
+        let params = Some(vec![
+            ("pageToken".to_string(), "old".to_string()),
+            ("pageSize".to_string(), "50".to_string()),
+        ]);
+        assert_eq!(page_params(&params, None, None), params);
+        assert_eq!(
+            page_params(&params, Some("new".to_string()), Some(("pageSize", 10))).unwrap(),
+            vec![
+                ("pageToken".to_string(), "new".to_string()),
+                ("pageSize".to_string(), "10".to_string()),
+            ]
+        );
+        // A smaller page size given with -p is kept
+        assert_eq!(
+            page_params(&params, None, Some(("pageSize", 100))).unwrap()[1],
+            ("pageSize".to_string(), "50".to_string())
+        );
+        assert_eq!(
+            page_params(&None, None, Some(("maxResults", 3))),
+            Some(vec![("maxResults".to_string(), "3".to_string())])
+        );
+    }
+
+    type Ready<T> = std::future::Ready<Result<T, ZgError>>;
+
+    /// Answers the requests with the responses in order, recording the Authorization header of each to `sent`.
+    fn fake_sender(
+        responses: Vec<(u16, &'static str)>,
+        sent: &Rc<RefCell<Vec<String>>>,
+    ) -> impl FnMut(HeaderMap<HeaderValue>) -> Ready<HttpResponse> {
+        let mut responses = responses.into_iter();
+        let sent = sent.clone();
+        move |headers| {
+            let authorization = headers["Authorization"].to_str().unwrap().to_string();
+            sent.borrow_mut().push(authorization);
+            let (status, body) = responses.next().unwrap();
+            std::future::ready(Ok(response(status, Some("application/json"), body)))
+        }
+    }
+
+    fn response(status: u16, content_type: Option<&str>, body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            content_type: content_type.map(str::to_string),
+            body: body.to_string(),
+            timing: Default::default(),
+            request_ids: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_timing() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A slow server: waits before sending the headers, then before sending the body
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/items", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Goog-Request-Id: req-123\r\nContent-Length: 2\r\nConnection: close\r\n\r\n";
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            socket.write_all(b"{}").await.unwrap();
+        });
+
+        let res = send(Method::GET, &url, HeaderMap::new(), None, None)
+            .await
+            .unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, "{}");
+        assert_eq!(
+            res.request_ids,
+            vec![("x-goog-request-id".to_string(), "req-123".to_string())]
+        );
+        let timing = res.timing;
+        assert!(timing.ttfb >= Duration::from_millis(100), "{:?}", timing);
+        assert!(
+            timing.total >= timing.ttfb + Duration::from_millis(100),
+            "{:?}",
+            timing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_with_reauth() {
+        let refresh = || async { Ok("new-token".to_string()) };
+        let headers =
+            RefCell::new(build_headers(&None, Some("old-token"), "application/json").unwrap());
+
+        let sent = Rc::default();
+        let send = fake_sender(vec![(401, "expired"), (200, "{}")], &sent);
+        let res = send_with_reauth(&headers, Some(refresh), send)
+            .await
+            .unwrap();
+        assert_eq!(res.body, "{}");
+        assert_eq!(*sent.borrow(), vec!["Bearer old-token", "Bearer new-token"]);
+        // The following requests use the new token
+        assert_eq!(headers.borrow()["Authorization"], "Bearer new-token");
+
+        let sent = Rc::default();
+        let send = fake_sender(vec![(401, "expired\n"), (401, "still expired")], &sent);
+        match send_with_reauth(&headers, Some(refresh), send).await {
+            Err(ZgError::HttpStatus { status, body }) => {
+                assert_eq!(status, 401);
+                assert_eq!(body, "expired");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert_eq!(sent.borrow().len(), 2);
+
+        // Without refresh (e.g., -H Authorization given), the 401 response is returned as is
+        let sent = Rc::default();
+        let send = fake_sender(vec![(401, "{\"error\": {}}")], &sent);
+        let no_refresh: Option<fn() -> Ready<String>> = None;
+        let res = send_with_reauth(&headers, no_refresh, send).await.unwrap();
+        assert_eq!(res.status, 401);
+        assert_eq!(res.body, "{\"error\": {}}");
+        assert_eq!(sent.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let parse = |status, content_type, body| {
+            parse_response("container:v1", &response(status, content_type, body)).unwrap()
+        };
+        let json_type = Some("application/json; charset=UTF-8");
+
+        assert_eq!(
+            parse(200, json_type, "{\"name\": \"c1\"}"),
+            Output::Json(json!({"name": "c1"}))
+        );
+        assert_eq!(parse(204, None, ""), Output::NoContent(204));
+        assert_eq!(parse(200, json_type, "\n"), Output::NoContent(200));
+        assert_eq!(parse(204, None, "").into_value(), json!({}));
+
+        let text = parse(200, Some("text/plain"), "OK\n");
+        assert_eq!(text, Output::Text("OK\n".to_string()));
+        assert_eq!(text.into_value(), json!("OK\n"));
+        let error = json!({"error": {"code": 404, "message": "Not found."}});
+        let error_body = error.to_string();
+        assert_eq!(
+            parse(404, json_type, &error_body),
+            Output::Error {
+                status: 404,
+                body: error.clone()
+            }
+        );
+        match error_status(404, &error) {
+            ZgError::HttpStatus { status, body } => {
+                assert_eq!(status, 404);
+                assert_eq!(body, "Not found.");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        // Error responses fail with the same exit status whatever the format
+        let failed = success(parse(404, json_type, &error_body)).unwrap_err();
+        assert_eq!(failed.exit_code(), 6);
+        assert_eq!(
+            success(parse(204, None, "")).unwrap(),
+            Output::NoContent(204)
+        );
+        // An error without a body is not "no content"
+        assert_eq!(
+            parse(404, None, ""),
+            Output::Error {
+                status: 404,
+                body: json!("")
+            }
+        );
+        let failed = success(parse(404, None, "")).unwrap_err();
+        assert_eq!(failed.exit_code(), 6);
+        assert_eq!(
+            failed.to_string(),
+            "HTTP request failed with status 404: (no content)"
+        );
+        // Error pages that are not JSON fail as well, with the body as it is
+        let html = "<html><body><h1>502 Server Error</h1></body></html>";
+        let failed = success(parse(502, Some("text/html; charset=UTF-8"), html)).unwrap_err();
+        assert_eq!(failed.exit_code(), 6);
+        assert_eq!(
+            failed.to_string(),
+            format!("HTTP request failed with status 502: {}", html)
+        );
+        let broken = success(parse(500, json_type, "{\"error\":")).unwrap_err();
+        assert_eq!(broken.exit_code(), 6);
+        // JSON bodies are parsed whatever the Content-Type says
+        assert_eq!(
+            parse(200, Some("text/plain"), "[1, 2]"),
+            Output::Json(json!([1, 2]))
+        );
+        assert_eq!(parse(200, None, "{}"), Output::Json(json!({})));
+
+        // Broken JSON with a JSON Content-Type is still an error
+        assert!(matches!(
+            parse_response("container:v1", &response(200, json_type, "{\"name\":")),
+            Err(ZgError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_has_header() {
+        let headers = Some(vec![("authorization".to_string(), "Bearer x".to_string())]);
+        assert!(has_header(&headers, "Authorization"));
+        assert!(!has_header(&headers, "X-Goog-User-Project"));
+        assert!(!has_header(&None, "Authorization"));
+    }
 }
@@ -0,0 +1,634 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of the API definitions into other formats, e.g., `zg export openapi`.
+
+use clap::{Args, Subcommand};
+use log::debug;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::sync::LazyLock;
+
+use super::config;
+use super::core;
+use super::error::ZgError;
+use super::json_schema::{Converter, Dialect};
+use super::output;
+use super::timing;
+
+/// Version of the generated OpenAPI documents.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Path parameters in a flat path (e.g., `{projectsId}`), including the reserved expansion of `path` (e.g., `{+name}`).
+static PATH_PARAM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\+?([^}]+)\}").unwrap());
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    command: ExportCmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum ExportCmd {
+    /// Export the methods of a service, or of a resource and its nested resources, as an OpenAPI 3.0 document (JSON, or YAML with `--format yaml`).
+    Openapi(OpenApiArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct OpenApiArgs {
+    /// Required. Service to export (e.g., 'container').
+    service: String,
+
+    /// A resource to limit the export to, with its nested resources (e.g., 'clusters' or 'locations.clusters').
+    resource: Option<String>,
+
+    /// Fail when the resource is ambiguous instead of picking one of the candidates.
+    #[arg(long, alias = "exact", overrides_with = "no_strict")]
+    strict: bool,
+
+    /// Pick one of the candidates of an ambiguous resource, even if `strict` is set in the config file.
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
+}
+
+impl ExportArgs {
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        match &mut self.command {
+            ExportCmd::Openapi(args) => {
+                args.strict = config::resolve_flag(args.strict, args.no_strict, config.desc.strict)
+            }
+        }
+    }
+}
+
+/// Main function to export API definitions. `Text` is printed as JSON.
+pub async fn main(
+    args: &ExportArgs,
+    format: output::Format,
+    standalone_api_key: Option<String>,
+) -> Result<(), ZgError> {
+    match &args.command {
+        ExportCmd::Openapi(args) => {
+            let api = core::load_api_file(&args.service, standalone_api_key).await?;
+            let _span = timing::span("render");
+            let resource = match &args.resource {
+                Some(path) => Some(core::find_api_resource(&api, path, args.strict)?),
+                None => None,
+            };
+            output::print(&openapi(&api, resource), format)
+        }
+    }
+}
+
+/// Returns the OpenAPI 3.0 document of the methods of the API, or of the resource and its nested resources.
+///
+/// Paths come from the flat paths of the methods, with the base URL as the server. Request bodies refer to the
+/// schemas converted into `components/schemas`. Responses are not described in the API files, so each operation
+/// has a generic successful response.
+pub fn openapi(api: &core::ZgApi, resource: Option<&core::ZgResource>) -> Value {
+    let mut converter = Converter::new(&api.schemas, None, false).with_dialect(Dialect::OpenApi);
+    let mut paths = Map::new();
+    let resources = match resource {
+        Some(resource) => std::slice::from_ref(resource),
+        None => api.resources.as_slice(),
+    };
+    for resource in resources {
+        add_operations(&mut paths, resource, &mut converter);
+    }
+
+    let mut document = json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": api.id,
+            "version": api.version,
+            "description": format!("Generated by zg from the discovery document of {} (revision {}).", api.id, api.revision),
+        },
+        "servers": [{"url": api.base_url.trim_end_matches('/')}],
+        "paths": paths,
+    });
+    let schemas = converter.definitions();
+    if !schemas.is_empty() {
+        document["components"] = json!({ "schemas": schemas });
+    }
+    document
+}
+
+/// Adds the operations of the methods of the resource and its nested resources to the paths.
+fn add_operations(
+    paths: &mut Map<String, Value>,
+    resource: &core::ZgResource,
+    converter: &mut Converter,
+) {
+    let tag = resource.path.as_deref().unwrap_or(&resource.name);
+    for method in &resource.methods {
+        let path = format!(
+            "/{}",
+            PATH_PARAM.replace_all(method.flat_path.trim_start_matches('/'), "{$1}")
+        );
+        let item = paths
+            .entry(path.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let http_method = method.http_method.to_lowercase();
+        if item.get(&http_method).is_some() {
+            debug!(
+                "Skipping {} as {} {} is already exported",
+                method.id, method.http_method, path
+            );
+            continue;
+        }
+        item[http_method] = operation(method, &path, tag, converter);
+    }
+    for child in resource.resources.iter().flatten() {
+        add_operations(paths, child, converter);
+    }
+}
+
+/// Converts the method into an Operation Object.
+fn operation(method: &core::ZgMethod, path: &str, tag: &str, converter: &mut Converter) -> Value {
+    let mut parameters: Vec<Value> = PATH_PARAM
+        .captures_iter(path)
+        .map(|cap| {
+            json!({
+                "name": &cap[1],
+                "in": "path",
+                "required": true,
+                "schema": {"type": "string"},
+            })
+        })
+        .collect();
+    parameters.extend(method.query_params.iter().map(query_parameter));
+
+    let mut operation = json!({
+        "operationId": method.original_id.as_deref().unwrap_or(&method.id),
+        "tags": [tag],
+        "responses": {"200": {"description": "Successful response"}},
+    });
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+    if let Some(schema) = &method.request_data_schema {
+        let schema = match &schema.id {
+            Some(id) => converter.reference(id),
+            None => converter.schema(schema),
+        };
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": {"application/json": {"schema": schema}},
+        });
+    }
+    if method.deprecated {
+        operation["deprecated"] = Value::Bool(true);
+    }
+    operation
+}
+
+/// Converts the query parameter into a Parameter Object. Repeated parameters are arrays of the type.
+fn query_parameter(param: &core::ZgQueryParam) -> Value {
+    let mut schema = Map::new();
+    // "any" has no OpenAPI type; any value is accepted without `type`
+    if let Some(param_type) = param.param_type.as_deref().filter(|t| *t != "any") {
+        schema.insert("type".to_string(), Value::from(param_type));
+    }
+    if let Some(format) = &param.format {
+        schema.insert("format".to_string(), Value::from(format.as_str()));
+    }
+    if let Some(values) = &param.enum_values {
+        schema.insert("enum".to_string(), Value::from(values.clone()));
+    }
+    if let Some(default) = &param.default {
+        schema.insert(
+            "default".to_string(),
+            typed_default(default, param.param_type.as_deref()),
+        );
+    }
+    let schema = if param.repeated {
+        json!({"type": "array", "items": schema})
+    } else {
+        Value::Object(schema)
+    };
+
+    let mut parameter = json!({
+        "name": param.name,
+        "in": "query",
+        "schema": schema,
+    });
+    if let Some(description) = &param.description {
+        parameter["description"] = Value::from(description.as_str());
+    }
+    if param.required {
+        parameter["required"] = Value::Bool(true);
+    }
+    if param.deprecated {
+        parameter["deprecated"] = Value::Bool(true);
+    }
+    parameter
+}
+
+/// Discovery documents give defaults as strings (e.g., "true", "500"); OpenAPI expects values of the parameter type.
+fn typed_default(default: &str, param_type: Option<&str>) -> Value {
+    let parsed = match param_type {
+        Some("boolean") => default.parse::<bool>().ok().map(Value::from),
+        Some("integer") => default.parse::<i64>().ok().map(Value::from),
+        Some("number") => default.parse::<f64>().ok().map(Value::from),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::from(default))
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    const HTTP_METHODS: [&str; 8] = [
+        "get", "put", "post", "delete", "options", "head", "patch", "trace",
+    ];
+
+    /// Fixed fields of the Schema Object of OpenAPI 3.0, which doesn't allow others except `x-` extensions.
+    const SCHEMA_FIELDS: [&str; 31] = [
+        "title",
+        "multipleOf",
+        "maximum",
+        "exclusiveMaximum",
+        "minimum",
+        "exclusiveMinimum",
+        "maxLength",
+        "minLength",
+        "pattern",
+        "maxItems",
+        "minItems",
+        "uniqueItems",
+        "maxProperties",
+        "minProperties",
+        "required",
+        "enum",
+        "type",
+        "not",
+        "allOf",
+        "oneOf",
+        "anyOf",
+        "items",
+        "properties",
+        "additionalProperties",
+        "description",
+        "format",
+        "default",
+        "nullable",
+        "readOnly",
+        "deprecated",
+        "example",
+    ];
+
+    /// Checks the document against the rules of the OpenAPI 3.0 schema for the objects zg generates
+    /// (a minimal validator, as no validator crate is available): required fields, fixed fields of Schema Objects,
+    /// types, parameter locations, path templates matching the path parameters, unique operation ids,
+    /// and references resolving to `components/schemas`.
+    fn validate_openapi(document: &Value) -> Result<(), String> {
+        fn check_schema(
+            schema: &Value,
+            components: &Map<String, Value>,
+            at: &str,
+        ) -> Result<(), String> {
+            let object = schema
+                .as_object()
+                .ok_or_else(|| format!("{}: a schema must be an object", at))?;
+            if let Some(reference) = object.get("$ref") {
+                // A Reference Object
+                let name = reference
+                    .as_str()
+                    .and_then(|r| r.strip_prefix("#/components/schemas/"))
+                    .ok_or_else(|| format!("{}/$ref: not in components/schemas", at))?;
+                if !components.contains_key(name) {
+                    return Err(format!("{}/$ref: '{}' is not defined", at, name));
+                }
+                return Ok(());
+            }
+            for (field, value) in object {
+                let at = format!("{}/{}", at, field);
+                if !SCHEMA_FIELDS.contains(&field.as_str()) && !field.starts_with("x-") {
+                    return Err(format!("{}: not a field of Schema Object", at));
+                }
+                match field.as_str() {
+                    "type" => {
+                        let types = ["array", "boolean", "integer", "number", "object", "string"];
+                        if !value.as_str().is_some_and(|t| types.contains(&t)) {
+                            return Err(format!("{}: invalid type {}", at, value));
+                        }
+                    }
+                    "items" | "not" => check_schema(value, components, &at)?,
+                    "additionalProperties" if !value.is_boolean() => {
+                        check_schema(value, components, &at)?
+                    }
+                    "properties" => {
+                        for (name, property) in value
+                            .as_object()
+                            .ok_or_else(|| format!("{}: not an object", at))?
+                        {
+                            check_schema(property, components, &format!("{}/{}", at, name))?;
+                        }
+                    }
+                    "required" | "enum" => {
+                        let items = value
+                            .as_array()
+                            .ok_or_else(|| format!("{}: not an array", at))?;
+                        let unique: BTreeSet<String> = items.iter().map(Value::to_string).collect();
+                        if items.is_empty() || unique.len() != items.len() {
+                            return Err(format!("{}: must be non-empty and unique", at));
+                        }
+                    }
+                    "readOnly" | "deprecated" | "nullable" if !value.is_boolean() => {
+                        return Err(format!("{}: not a boolean", at))
+                    }
+                    "description" | "format" | "title" if !value.is_string() => {
+                        return Err(format!("{}: not a string", at))
+                    }
+                    _ => {}
+                }
+            }
+            if object.get("type").and_then(Value::as_str) == Some("array")
+                && !object.contains_key("items")
+            {
+                return Err(format!("{}: an array schema must have items", at));
+            }
+            Ok(())
+        }
+
+        if !document["openapi"]
+            .as_str()
+            .is_some_and(|v| v.starts_with("3.0."))
+        {
+            return Err("openapi is not 3.0.x".to_string());
+        }
+        if !document["info"]["title"].is_string() || !document["info"]["version"].is_string() {
+            return Err("info must have title and version".to_string());
+        }
+        for server in document["servers"].as_array().into_iter().flatten() {
+            if !server["url"].is_string() {
+                return Err("servers: url must be a string".to_string());
+            }
+        }
+        let no_components = Map::new();
+        let components = document["components"]["schemas"]
+            .as_object()
+            .unwrap_or(&no_components);
+        for (name, schema) in components {
+            check_schema(
+                schema,
+                components,
+                &format!("#/components/schemas/{}", name),
+            )?;
+        }
+
+        let paths = document["paths"]
+            .as_object()
+            .ok_or("paths must be an object")?;
+        let mut operation_ids = BTreeSet::new();
+        for (path, item) in paths {
+            if !path.starts_with('/') {
+                return Err(format!("{}: a path must start with '/'", path));
+            }
+            let templated: BTreeSet<&str> = PATH_PARAM
+                .captures_iter(path)
+                .map(|cap| cap.get(1).unwrap().as_str())
+                .collect();
+            for (method, operation) in item.as_object().ok_or("a path item must be an object")? {
+                let at = format!("{} {}", method, path);
+                if !HTTP_METHODS.contains(&method.as_str()) {
+                    return Err(format!("{}: unknown operation", at));
+                }
+                let id = operation["operationId"]
+                    .as_str()
+                    .ok_or(format!("{}: no operationId", at))?;
+                if !operation_ids.insert(id.to_string()) {
+                    return Err(format!("{}: duplicated operationId {}", at, id));
+                }
+                if !operation["responses"]
+                    .as_object()
+                    .is_some_and(|r| !r.is_empty())
+                {
+                    return Err(format!("{}: responses must not be empty", at));
+                }
+                for response in operation["responses"].as_object().unwrap().values() {
+                    if !response["description"].is_string() {
+                        return Err(format!("{}: a response must have a description", at));
+                    }
+                }
+
+                let mut path_params = BTreeSet::new();
+                let mut seen = BTreeSet::new();
+                for parameter in operation["parameters"].as_array().into_iter().flatten() {
+                    let name = parameter["name"]
+                        .as_str()
+                        .ok_or(format!("{}: a parameter must have a name", at))?;
+                    let location = parameter["in"].as_str().unwrap_or_default();
+                    if !["query", "header", "path", "cookie"].contains(&location) {
+                        return Err(format!("{}: invalid location of {}", at, name));
+                    }
+                    if !seen.insert((name, location)) {
+                        return Err(format!("{}: duplicated parameter {}", at, name));
+                    }
+                    if location == "path" {
+                        if parameter["required"] != true {
+                            return Err(format!(
+                                "{}: path parameter {} must be required",
+                                at, name
+                            ));
+                        }
+                        path_params.insert(name);
+                    }
+                    check_schema(
+                        &parameter["schema"],
+                        components,
+                        &format!("{}/{}", at, name),
+                    )?;
+                }
+                if path_params != templated {
+                    return Err(format!("{}: path parameters don't match the template", at));
+                }
+                if let Some(body) = operation.get("requestBody") {
+                    let content = body["content"]
+                        .as_object()
+                        .ok_or(format!("{}: requestBody must have content", at))?;
+                    for media_type in content.values() {
+                        check_schema(
+                            &media_type["schema"],
+                            components,
+                            &format!("{}/requestBody", at),
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_api(file: &str) -> core::ZgApi {
+        crate::update::extract_api(PathBuf::from(format!("tests/test_data/{}", file)), false)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_openapi_from_container_fixture() {
+        let api = extract_api("container_v1_nested.json");
+        let document = openapi(&api, None);
+        validate_openapi(&document).unwrap();
+
+        assert_eq!(document["openapi"], OPENAPI_VERSION);
+        assert_eq!(
+            document["servers"][0]["url"],
+            "https://container.googleapis.com"
+        );
+        let get = &document["paths"]
+            ["/v1/projects/{projectId}/locations/{location}/clusters/{clusterId}"]["get"];
+        assert_eq!(
+            get["operationId"],
+            "container.projects.locations.clusters.get"
+        );
+        let names: Vec<&str> = get["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|p| p["in"] == "path")
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["projectId", "location", "clusterId"]);
+        let operations: usize = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|item| item.as_object().unwrap().len())
+            .sum();
+        assert_eq!(
+            operations,
+            api.resources.iter().map(count_methods).sum::<usize>()
+        );
+
+        // Limited to a resource subtree
+        let clusters = core::find_api_resource(&api, "locations.clusters", false).unwrap();
+        let document = openapi(&api, Some(clusters));
+        validate_openapi(&document).unwrap();
+        let paths: Vec<&String> = document["paths"].as_object().unwrap().keys().collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/v1/projects/{projectId}/locations/{location}/clusters/{clusterId}",
+                "/v1/projects/{projectId}/locations/{location}/clusters/{clusterId}/nodePools/{nodePoolId}",
+            ]
+        );
+    }
+
+    fn count_methods(resource: &core::ZgResource) -> usize {
+        resource.methods.len()
+            + resource
+                .resources
+                .iter()
+                .flatten()
+                .map(count_methods)
+                .sum::<usize>()
+    }
+
+    #[test]
+    fn test_openapi_request_bodies() {
+        let api = extract_api("run_v2_required.json");
+        let document = openapi(&api, None);
+        validate_openapi(&document).unwrap();
+
+        let create =
+            &document["paths"]["/v2/projects/{projectsId}/locations/{locationsId}/jobs"]["post"];
+        assert_eq!(
+            create["requestBody"]["content"]["application/json"]["schema"],
+            json!({"$ref": "#/components/schemas/GoogleCloudRunV2Job"})
+        );
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.contains_key("GoogleCloudRunV2ExecutionTemplate"));
+        let job_id = create["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == "jobId")
+            .unwrap();
+        assert_eq!(job_id["in"], "query");
+    }
+
+    #[test]
+    fn test_query_parameter() {
+        let param = core::ZgQueryParam {
+            name: "view".to_string(),
+            required: true,
+            repeated: true,
+            param_type: Some("string".to_string()),
+            enum_values: Some(vec!["BASIC".to_string(), "FULL".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            query_parameter(&param),
+            json!({
+                "name": "view",
+                "in": "query",
+                "required": true,
+                "schema": {"type": "array", "items": {"type": "string", "enum": ["BASIC", "FULL"]}},
+            })
+        );
+        let param = core::ZgQueryParam {
+            name: "pageSize".to_string(),
+            param_type: Some("integer".to_string()),
+            format: Some("int32".to_string()),
+            default: Some("500".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query_parameter(&param)["schema"],
+            json!({"type": "integer", "format": "int32", "default": 500})
+        );
+    }
+
+    #[test]
+    fn test_validate_openapi_rejects_invalid() {
+        let valid = json!({
+            "openapi": "3.0.3",
+            "info": {"title": "t", "version": "v1"},
+            "paths": {"/v1/{id}": {"get": {
+                "operationId": "a",
+                "parameters": [{"name": "id", "in": "path", "required": true, "schema": {"type": "string"}}],
+                "responses": {"200": {"description": "ok"}},
+            }}},
+        });
+        assert!(validate_openapi(&valid).is_ok());
+
+        let mut missing_path_param = valid.clone();
+        missing_path_param["paths"]["/v1/{id}"]["get"]["parameters"] = json!([]);
+        let mut unknown_field = valid.clone();
+        unknown_field["paths"]["/v1/{id}"]["get"]["parameters"][0]["schema"] =
+            json!({"type": "string", "contentEncoding": "base64"});
+        let mut dangling_ref = valid.clone();
+        dangling_ref["paths"]["/v1/{id}"]["get"]["requestBody"] = json!({"content": {"application/json": {"schema": {"$ref": "#/components/schemas/Missing"}}}});
+        let mut no_responses = valid.clone();
+        no_responses["paths"]["/v1/{id}"]["get"]["responses"] = json!({});
+        let mut version = valid.clone();
+        version["openapi"] = json!("3.1.0");
+        for document in [
+            missing_path_param,
+            unknown_field,
+            dangling_ref,
+            no_responses,
+            version,
+        ] {
+            assert!(validate_openapi(&document).is_err(), "{}", document);
+        }
+    }
+}
@@ -17,18 +17,20 @@ use log::debug;
 
 /// Select "container" resources, priorizing regional clusters/nodePools over zonal ones.
 ///
-///     projects
-///       aggregated
-///         usableSubnetworks
-///       locations
-///         operations
-///         clusters *1 <<<=== select
-///           nodePools *2 <<<=== select
-///           well-known
-///       zones
-///         clusters *1
-///           nodePools *2
-///         operations
+/// ```text
+/// projects
+///   aggregated
+///     usableSubnetworks
+///   locations
+///     operations
+///     clusters *1 <<<=== select
+///       nodePools *2 <<<=== select
+///       well-known
+///   zones
+///     clusters *1
+///       nodePools *2
+///     operations
+/// ```
 pub fn select_resource_container(found: Vec<&core::ZgResource>) -> Option<&core::ZgResource> {
     debug!("Prioritize regional clusters (locations.clusters) over zonal clsuters. Ref: https://cloud.google.com/blog/products/containers-kubernetes/choosing-a-regional-vs-zonal-gke-cluster");
     found
@@ -49,23 +51,25 @@ pub fn select_resource_container(found: Vec<&core::ZgResource>) -> Option<&core:
 /// - *2: "templates" ... under projects or locations. Prefer the regional one: "locations.templates".
 /// - *3: "snapshots" ... methods under snapshots are undocumented, but calling [gcloud dataflow snapshots delete](https://cloud.google.com/sdk/gcloud/reference/dataflow/snapshots/delete) with `--log-http` indicates `locations.snapshots` is used.
 ///
-///     projects
+/// ```text
+/// projects
+///   snapshots *3
+///   jobs *1
+///     workItems *1
+///     messages *1
+///     debug *1
+///   templates *2
+///   locations
+///     flexTemplates
+///     templates *2 <<<=== select
+///     jobs *1 <<<=== select (and its *1 subresources)
+///       debug *1
+///       stages
 ///       snapshots *3
-///       jobs *1
-///         workItems *1
-///         messages *1
-///         debug *1
-///       templates *2
-///       locations
-///         flexTemplates
-///         templates *2 <<<=== select
-///         jobs *1 <<<=== select (and its *1 subresources)
-///           debug *1
-///           stages
-///           snapshots *3
-///           messages *1
-///           workItems *1
-///         snapshots *3 <<<=== select
+///       messages *1
+///       workItems *1
+///     snapshots *3 <<<=== select
+/// ```
 pub fn select_resource_dataflow<'a>(
     resource_path: &str,
     found: Vec<&'a core::ZgResource>,
@@ -96,27 +100,29 @@ pub fn select_resource_dataflow<'a>(
 
 /// Select "spanner" resources, assuming 'instances.operations' as the default choice for 'operations' resource.
 ///
-///     scans
-///     projects
-///       instanceConfigOperations
-///       instanceConfigs
-///         ssdCaches
-///           operations *
-///         operations *
-///       instances
-///         databaseOperations
-///         databases
-///           sessions
-///           backupSchedules
-///           databaseRoles
-///           operations *
-///         instancePartitions
-///           operations *
-///         backupOperations
-///         operations * <<<=== select
-///         backups
-///           operations *
-///         instancePartitionOperations
+/// ```text
+/// scans
+/// projects
+///   instanceConfigOperations
+///   instanceConfigs
+///     ssdCaches
+///       operations *
+///     operations *
+///   instances
+///     databaseOperations
+///     databases
+///       sessions
+///       backupSchedules
+///       databaseRoles
+///       operations *
+///     instancePartitions
+///       operations *
+///     backupOperations
+///     operations * <<<=== select
+///     backups
+///       operations *
+///     instancePartitionOperations
+/// ```
 pub fn select_resource_spanner(found: Vec<&core::ZgResource>) -> Option<&core::ZgResource> {
     debug!("Spanner has 6 resources named 'operations'. 'instances.operations' and 'databases.operations' are common, and here select one under 'instnaces'. Ref: https://cloud.google.com/spanner/docs/manage-and-observe-long-running-operations");
     found
@@ -126,6 +132,177 @@ pub fn select_resource_spanner(found: Vec<&core::ZgResource>) -> Option<&core::Z
         .or_else(|| found.last().copied())
 }
 
+/// Select "iam" resources, prioritizing the project-level resources around service accounts
+/// over the workforce pool (organization-wide identity federation) variants.
+/// - *1: "keys" ... almost always means service account keys: "projects.serviceAccounts.keys".
+/// - *2: "roles" ... the bare name means predefined roles: "iam.roles". Custom roles are selected by "projects.roles" or "organizations.roles".
+/// - *3: "providers" ... prefer the workload identity pool providers in projects: "projects.locations.workloadIdentityPools.providers".
+/// - *4: "operations" (x 10) ... prefer the one closest to the project (i.e., the shallowest path under "projects"). Same for "locations" (x 2).
+///
+/// ```text
+/// locations
+///   workforcePools
+///     operations *4
+///     providers *3
+///       keys *1
+///         operations *4
+///       operations *4
+///     subjects
+///       operations *4
+/// organizations
+///   roles *2
+/// projects
+///   locations
+///     workloadIdentityPools
+///       operations *4 <<<=== select
+///       providers *3 <<<=== select
+///         keys *1
+///           operations *4
+///         operations *4
+///   roles *2
+///   serviceAccounts
+///     keys *1 <<<=== select
+/// roles *2 <<<=== select
+/// ```
+pub fn select_resource_iam<'a>(
+    resource_path: &str,
+    found: Vec<&'a core::ZgResource>,
+) -> Option<&'a core::ZgResource> {
+    let find_path = |path: &str| {
+        found
+            .iter()
+            .find(|r| r.path.as_deref() == Some(path))
+            .copied()
+    };
+
+    let selected = if resource_path.ends_with("keys") {
+        debug!("Prefer service account keys for 'keys'. Specify 'providers.keys' for workload/workforce identity pool provider keys.");
+        find_path("iam.projects.serviceAccounts.keys")
+    } else if resource_path.ends_with("roles") {
+        debug!("Prefer predefined roles ('iam.roles') for 'roles'. Specify 'projects.roles' or 'organizations.roles' for custom roles.");
+        find_path("iam.roles")
+    } else if resource_path.ends_with("providers") {
+        debug!("Prefer workload identity pool providers for 'providers'. Specify 'workforcePools.providers' for workforce identity pool providers.");
+        find_path("iam.projects.locations.workloadIdentityPools.providers")
+    } else {
+        debug!("Prefer the resource closest to the project (e.g., 'workloadIdentityPools.operations' for 'operations'). Specify a more detailed path for the others.");
+        found
+            .iter()
+            .filter(|r| r.path.as_ref().unwrap().starts_with("iam.projects."))
+            .min_by_key(|r| r.path.as_ref().unwrap().matches('.').count())
+            .copied()
+    };
+
+    selected.or_else(|| found.last().copied())
+}
+
+/// Parent resource types in "logging" other than "projects".
+const LOGGING_OTHER_PARENTS: [&str; 3] = ["folders", "organizations", "billingAccounts"];
+
+/// Select "logging" resources, prioritizing the ones under projects.
+/// Most of the resources (sinks, logs, exclusions, buckets, etc.) are duplicated under projects, folders, organizations, and billingAccounts.
+/// Projects are the most common target; the other parents are selected only when the user-typed path explicitly contains them.
+///
+/// ```text
+/// billingAccounts
+///   sinks
+///   ...
+/// folders
+///   sinks
+///   ...
+/// organizations
+///   sinks
+///   ...
+/// projects
+///   sinks <<<=== select
+///   ...
+/// ```
+pub fn select_resource_logging<'a>(
+    resource_path: &str,
+    found: Vec<&'a core::ZgResource>,
+) -> Option<&'a core::ZgResource> {
+    let parent = resource_path
+        .split('.')
+        .find(|segment| LOGGING_OTHER_PARENTS.contains(segment))
+        .unwrap_or("projects");
+    let name = resource_path.rsplit('.').next().unwrap_or(resource_path);
+    debug!(
+        "Select '{}' under '{}'. Specify 'folders.{}', 'organizations.{}', or 'billingAccounts.{}' to target the other parents.",
+        name, parent, name, name, name
+    );
+    let prefix = format!("logging.{}.", parent);
+    found
+        .iter()
+        .find(|r| r.path.as_ref().unwrap().starts_with(&prefix))
+        .copied()
+        .or_else(|| found.last().copied())
+}
+
+/// Location scope of a "compute" resource, inferred from its path.
+#[derive(Debug, PartialEq)]
+enum ComputeScope {
+    Zonal,
+    Regional,
+    Global,
+}
+
+impl ComputeScope {
+    fn of(path: &str) -> Self {
+        if path.contains(".zones.") {
+            ComputeScope::Zonal
+        } else if path.contains(".regions.") {
+            ComputeScope::Regional
+        } else {
+            ComputeScope::Global
+        }
+    }
+}
+
+/// Preferred scopes of "compute" resources that exist in multiple scopes. Resources not listed here prefer the global one.
+const COMPUTE_PREFERRED_SCOPES: &[(&str, ComputeScope)] = &[
+    // VMs and their disks live in a zone; the zonal variants are what `gcloud compute instances/disks` use by default.
+    ("instances", ComputeScope::Zonal),
+    ("disks", ComputeScope::Zonal),
+    // Managed instance groups and autoscalers are zonal by default in gcloud (`--zone`), regional ones are opt-in (`--region`).
+    ("instanceGroups", ComputeScope::Zonal),
+    ("instanceGroupManagers", ComputeScope::Zonal),
+    ("autoscalers", ComputeScope::Zonal),
+    // Load balancing and networking resources are mostly regional nowadays (e.g., regional external/internal load balancers).
+    ("forwardingRules", ComputeScope::Regional),
+    ("backendServices", ComputeScope::Regional),
+    ("healthChecks", ComputeScope::Regional),
+    ("networkEndpointGroups", ComputeScope::Regional),
+    ("sslCertificates", ComputeScope::Regional),
+    ("targetHttpProxies", ComputeScope::Regional),
+    ("targetHttpsProxies", ComputeScope::Regional),
+    ("urlMaps", ComputeScope::Regional),
+    ("securityPolicies", ComputeScope::Regional),
+    ("addresses", ComputeScope::Regional),
+];
+
+/// Select "compute" resources existing in multiple location scopes (global, regional, zonal), following `COMPUTE_PREFERRED_SCOPES`.
+/// The scope is determined by "zones" or "regions" in the resource path (e.g., "compute.projects.zones.instances").
+pub fn select_resource_compute<'a>(
+    resource_path: &str,
+    found: Vec<&'a core::ZgResource>,
+) -> Option<&'a core::ZgResource> {
+    let name = resource_path.rsplit('.').next().unwrap_or(resource_path);
+    let preferred = COMPUTE_PREFERRED_SCOPES
+        .iter()
+        .find(|(resource, _)| *resource == name)
+        .map(|(_, scope)| scope)
+        .unwrap_or(&ComputeScope::Global);
+    debug!(
+        "Prefer the {:?} variant of '{}'. Specify 'zones.{}', 'regions.{}', or 'projects.{}' to select the others.",
+        preferred, name, name, name, name
+    );
+    found
+        .iter()
+        .find(|r| ComputeScope::of(r.path.as_ref().unwrap()) == *preferred)
+        .copied()
+        .or_else(|| found.last().copied())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -185,4 +362,125 @@ mod tests {
             Some("spanner.projects.instances.operations")
         );
     }
+
+    fn resources(paths: &[&str]) -> Vec<core::ZgResource> {
+        paths
+            .iter()
+            .map(|path| core::ZgResource {
+                path: Some(path.to_string()),
+                ..core::ZgResource::testdata()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_select_resource_logging() {
+        let res = resources(&["logging.billingAccounts.sinks", "logging.projects.sinks"]);
+        let selected = select_resource_logging("sinks", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("logging.projects.sinks")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_logging_explicit_parent() {
+        let res = resources(&[
+            "logging.organizations.locations.buckets",
+            "logging.projects.locations.buckets",
+        ]);
+        let selected =
+            select_resource_logging("organizations.locations.buckets", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("logging.organizations.locations.buckets")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_compute_zonal() {
+        let res = resources(&[
+            "compute.projects.regions.instanceGroupManagers",
+            "compute.projects.zones.instanceGroupManagers",
+        ]);
+        let selected = select_resource_compute("instanceGroupManagers", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("compute.projects.zones.instanceGroupManagers")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_compute_regional() {
+        let res = resources(&[
+            "compute.projects.backendServices",
+            "compute.projects.regions.backendServices",
+        ]);
+        let selected = select_resource_compute("backendServices", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("compute.projects.regions.backendServices")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_compute_global() {
+        let res = resources(&[
+            "compute.projects.regions.operations",
+            "compute.projects.operations",
+        ]);
+        let selected = select_resource_compute("operations", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("compute.projects.operations")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_iam_keys() {
+        let res = resources(&[
+            "iam.locations.workforcePools.providers.keys",
+            "iam.projects.locations.workloadIdentityPools.providers.keys",
+            "iam.projects.serviceAccounts.keys",
+        ]);
+        let selected = select_resource_iam("keys", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("iam.projects.serviceAccounts.keys")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_iam_roles() {
+        let res = resources(&["iam.organizations.roles", "iam.projects.roles", "iam.roles"]);
+        let selected = select_resource_iam("roles", res.iter().collect());
+        assert_eq!(selected.unwrap().path.as_deref(), Some("iam.roles"));
+    }
+
+    #[test]
+    fn test_select_resource_iam_providers() {
+        let res = resources(&[
+            "iam.locations.workforcePools.providers",
+            "iam.projects.locations.workloadIdentityPools.providers",
+        ]);
+        let selected = select_resource_iam("providers", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("iam.projects.locations.workloadIdentityPools.providers")
+        );
+    }
+
+    #[test]
+    fn test_select_resource_iam_operations() {
+        let res = resources(&[
+            "iam.locations.workforcePools.operations",
+            "iam.projects.locations.workloadIdentityPools.operations",
+            "iam.projects.locations.workloadIdentityPools.providers.operations",
+        ]);
+        let selected = select_resource_iam("operations", res.iter().collect());
+        assert_eq!(
+            selected.unwrap().path.as_deref(),
+            Some("iam.projects.locations.workloadIdentityPools.operations")
+        );
+    }
 }
@@ -27,14 +27,14 @@
 
     Note that we prefer to implement flavors when there is little to no guidance and it's difficult to reach the functional minimum except by fair amount of trial and error.
 */
+use crate::error::ZgError;
 use serde_json::{json, to_string_pretty, Value};
-use std::error::Error;
 
 /// Generate the output for zg desc.
 fn generate_minimum_data_and_notes(
     data_patterns: Vec<(Option<&str>, Value)>,
     notes: Vec<&str>,
-) -> Result<String, Box<dyn Error>> {
+) -> Result<String, ZgError> {
     let mut output = String::from("\nminimum_data:\n");
     for (title_option, data) in data_patterns {
         if let Some(title) = title_option {
@@ -80,7 +80,7 @@ macro_rules! template {
 /// [Justification]
 /// The description text of query, load, copy, and extract fields in JobConfiguration start with "[Pick one]," which is an unique strategy to represent Enum-like requirement, but no other services use such expression.
 /// Instead of handling "[Pick one]" in desc.rs which only affects BigQuery Jobs insert, it'd be better to treat it as a flavor logic here.
-pub fn bigquery_jobs_insert() -> Result<String, Box<dyn Error>> {
+pub fn bigquery_jobs_insert() -> Result<String, ZgError> {
     template!(
         "Pattern (1). Query Job" >>> {
             "configuration": {
@@ -103,7 +103,7 @@ pub fn bigquery_jobs_insert() -> Result<String, Box<dyn Error>> {
 /// [Justification]
 /// No programmatic way to determine the minimum data required to create an instance. We might be able to assume "name" is required as it's an identifier in general, but not sure this assumption works for other services.
 /// Even if we could extract "name" as a required field, we would not know that "tier" is required to create an instance unless we execute the API.
-pub fn sqladmin_instances_insert() -> Result<String, Box<dyn Error>> {
+pub fn sqladmin_instances_insert() -> Result<String, ZgError> {
     template!(
         {"name": "", "settings": {"tier":""}}
         <<notes>>
@@ -114,7 +114,7 @@ pub fn sqladmin_instances_insert() -> Result<String, Box<dyn Error>> {
 /// [Justification]
 /// When you pass "cluster > name" only, the API response indicates Cluster.initial_node_count must be greater than zero, but the field is deprecated.
 /// In reallity, we have two valid patterns: (1) specifying nodePool(s), or (2) enable Autopilot.
-pub fn container_clusters_create() -> Result<String, Box<dyn Error>> {
+pub fn container_clusters_create() -> Result<String, ZgError> {
     template!(
         "Pattern (1). Standard Cluster" >>> {"cluster": {"name": "", "nodePools": [{"name": ""}]}},
         "Pattern (2). Autopilot Cluster" >>> {"cluster": {"name": "", "autopilot": {"enabled": true}}}
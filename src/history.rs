@@ -0,0 +1,255 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! History of the requests of `zg exec` (`history.jsonl` in the config directory), recorded when `history.enabled`
+//! is set in the config file, and repeated with `zg exec --rerun`.
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::config;
+use super::core;
+use super::error::ZgError;
+use super::substitute;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Number of the entries kept when the file is trimmed, which happens once it has twice as many.
+const MAX_ENTRIES: usize = 1000;
+
+/// A request in the history: the arguments of `zg exec` as given (before the template functions are expanded).
+/// Headers are never recorded, and --data (or --data-binary) only with `history.record_bodies`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct Entry {
+    pub(crate) id: String,
+    /// When the request was sent, in RFC 3339.
+    pub(crate) time: String,
+    pub(crate) service: String,
+    pub(crate) resource: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) method: Option<String>,
+    /// The positional values of the path parameters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) values: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) params: Vec<(String, Option<String>)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) data_binary: Option<String>,
+    /// Whether --data or --data-binary was given but not recorded.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) data_omitted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) force_body: bool,
+    /// The trace id of --trace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trace_id: Option<String>,
+}
+
+impl Entry {
+    /// Creates an entry with a new id and the current time.
+    pub(crate) fn new(service: &str, resource: &str) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        Entry {
+            id: format!("{:08x}", rand::random::<u32>()),
+            time: substitute::rfc3339(seconds),
+            service: service.to_string(),
+            resource: resource.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which entry `zg exec --rerun` repeats.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Selector {
+    /// The Nth most recent one, from 1.
+    Nth(usize),
+    Id(String),
+}
+
+fn history_path() -> PathBuf {
+    core::config_dir().join(HISTORY_FILE)
+}
+
+/// Records the entry if the history is enabled. Failures are warned about, not to fail the request.
+pub(crate) fn record(entry: &Entry) {
+    if !config::current().history.enabled.unwrap_or(false) {
+        return;
+    }
+    let path = history_path();
+    if let Err(e) = append(&path, entry) {
+        warn!("Failed to save the history to {:?}: {}", path, e);
+    }
+}
+
+/// Finds the entry in the history.
+pub(crate) fn find(selector: &Selector) -> Result<Entry, ZgError> {
+    find_in(&history_path(), selector)
+}
+
+fn append(path: &Path, entry: &Entry) -> Result<(), ZgError> {
+    // Readable only by the user, as -p values and bodies may be secrets
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    drop(file);
+
+    let content = fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() > MAX_ENTRIES * 2 {
+        let kept = lines[lines.len() - MAX_ENTRIES..].join("\n") + "\n";
+        core::write_private_file_atomic(path, kept.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Reads the entries, oldest first. Lines that can't be read (e.g., written by another version) are skipped.
+fn read(path: &Path) -> Result<Vec<Entry>, ZgError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| debug!("Skipping a history entry ({}): {}", e, line))
+                .ok()
+        })
+        .collect())
+}
+
+fn find_in(path: &Path, selector: &Selector) -> Result<Entry, ZgError> {
+    let entries = read(path)?;
+    if entries.is_empty() {
+        let hint = match config::current().history.enabled {
+            Some(true) => "",
+            _ => " Enable it with `zg config set history.enabled true`.",
+        };
+        return Err(format!("No requests in the history ({}).{}", path.display(), hint).into());
+    }
+    match selector {
+        Selector::Nth(n) => entries
+            .len()
+            .checked_sub(*n)
+            .map(|index| entries[index].clone())
+            .ok_or_else(|| {
+                format!(
+                    "The history has {} request(s); can't rerun #{}",
+                    entries.len(),
+                    n
+                )
+                .into()
+            }),
+        Selector::Id(id) => entries
+            .into_iter()
+            .rev()
+            .find(|entry| &entry.id == id)
+            .ok_or_else(|| format!("No request with id '{}' in the history", id).into()),
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_find() {
+        let path = std::env::temp_dir().join(format!("zg-history-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(find_in(&path, &Selector::Nth(1))
+            .unwrap_err()
+            .to_string()
+            .starts_with("No requests in the history"));
+
+        let entry = |id: &str, method: &str| Entry {
+            id: id.to_string(),
+            method: Some(method.to_string()),
+            ..Entry::new("gke", "clusters")
+        };
+        let last = entry("b2", "get");
+        append(&path, &entry("a1", "list")).unwrap();
+        append(&path, &last).unwrap();
+        fs::write(
+            &path,
+            fs::read_to_string(&path).unwrap() + "{\"broken\": \n",
+        )
+        .unwrap();
+
+        assert_eq!(find_in(&path, &Selector::Nth(1)).unwrap(), last);
+        assert_eq!(find_in(&path, &Selector::Nth(2)).unwrap().id, "a1");
+        assert_eq!(
+            find_in(&path, &Selector::Nth(3)).unwrap_err().to_string(),
+            "The history has 2 request(s); can't rerun #3"
+        );
+        assert_eq!(
+            find_in(&path, &Selector::Id("a1".to_string()))
+                .unwrap()
+                .method,
+            Some("list".to_string())
+        );
+        assert!(find_in(&path, &Selector::Id("zz".to_string())).is_err());
+
+        // Readable only by the user
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trim() {
+        let path =
+            std::env::temp_dir().join(format!("zg-history-trim-{}.jsonl", std::process::id()));
+        let line = serde_json::to_string(&Entry::new("gke", "clusters")).unwrap();
+        fs::write(&path, format!("{}\n", line).repeat(MAX_ENTRIES * 2)).unwrap();
+        let last = Entry {
+            id: "last".to_string(),
+            ..Entry::new("gke", "clusters")
+        };
+        append(&path, &last).unwrap();
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.last().unwrap(), &last);
+        // The trimmed file is rewritten readable only by the user
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
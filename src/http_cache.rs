@@ -0,0 +1,277 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cache of the successful responses of GET requests for `zg exec --cache-ttl`, in `http_cache/` of the config
+//! directory. Each response is a JSON file named by the hash of the URL and the headers of the request, readable only
+//! by the user as responses may have secrets.
+
+use hyper::header::{HeaderMap, HeaderValue};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::core;
+use super::error::ZgError;
+
+/// Subdirectory of the config directory holding the cached responses.
+pub(crate) const HTTP_CACHE_DIR: &str = "http_cache";
+
+/// A cached response, with when it was stored (seconds since the Unix epoch).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct CachedResponse {
+    pub(crate) url: String,
+    pub(crate) stored_at: u64,
+    pub(crate) status: u16,
+    pub(crate) content_type: Option<String>,
+    pub(crate) body: String,
+}
+
+/// The responses cached for the TTL. Without `read` (--no-cache), cached responses are not used, but new ones are
+/// still stored.
+pub(crate) struct HttpCache {
+    dir: PathBuf,
+    ttl: Duration,
+    read: bool,
+}
+
+impl HttpCache {
+    pub(crate) fn new(ttl_secs: u64, read: bool) -> HttpCache {
+        HttpCache {
+            dir: core::config_dir().join(HTTP_CACHE_DIR),
+            ttl: Duration::from_secs(ttl_secs),
+            read,
+        }
+    }
+
+    /// The key of the request: a hash of the URL and the headers, in any order. The Authorization header is part of
+    /// it, so that the responses of a credential are not returned for another.
+    pub(crate) fn key(url: &str, headers: &HeaderMap<HeaderValue>) -> String {
+        let mut pairs: Vec<(&str, &[u8])> = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+            .collect();
+        pairs.sort();
+        let mut hash = Fnv64::new();
+        hash.write(url.as_bytes());
+        for (name, value) in pairs {
+            hash.write(b"\n");
+            hash.write(name.as_bytes());
+            hash.write(b":");
+            hash.write(value);
+        }
+        format!("{:016x}", hash.0)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Returns the cached response of the key and its age, if it's younger than the TTL.
+    pub(crate) fn get(&self, key: &str) -> Option<(CachedResponse, Duration)> {
+        self.get_at(key, SystemTime::now())
+    }
+
+    fn get_at(&self, key: &str, now: SystemTime) -> Option<(CachedResponse, Duration)> {
+        if !self.read {
+            return None;
+        }
+        let content = fs::read_to_string(self.path(key)).ok()?;
+        let cached: CachedResponse = match serde_json::from_str(&content) {
+            Ok(cached) => cached,
+            Err(e) => {
+                debug!("Ignoring the broken cached response {}: {}", key, e);
+                return None;
+            }
+        };
+        let age = Duration::from_secs(epoch_secs(now).saturating_sub(cached.stored_at));
+        if age >= self.ttl {
+            debug!(
+                "The cached response {} expired ({}s old)",
+                key,
+                age.as_secs()
+            );
+            return None;
+        }
+        Some((cached, age))
+    }
+
+    /// Stores the response of the key if it's successful (2xx). Failures to write are warned, not to fail the command.
+    pub(crate) fn put(
+        &self,
+        key: &str,
+        url: &str,
+        status: u16,
+        content_type: Option<&str>,
+        body: &str,
+    ) {
+        self.put_at(key, url, status, content_type, body, SystemTime::now())
+    }
+
+    fn put_at(
+        &self,
+        key: &str,
+        url: &str,
+        status: u16,
+        content_type: Option<&str>,
+        body: &str,
+        now: SystemTime,
+    ) {
+        if !(200..300).contains(&status) {
+            return;
+        }
+        let cached = CachedResponse {
+            url: url.to_string(),
+            stored_at: epoch_secs(now),
+            status,
+            content_type: content_type.map(str::to_string),
+            body: body.to_string(),
+        };
+        let result = serde_json::to_vec(&cached)
+            .map_err(ZgError::from)
+            .and_then(|content| core::write_private_file_atomic(&self.path(key), &content));
+        if let Err(e) = result {
+            warn!("Failed to cache the response: {}", e);
+        }
+    }
+}
+
+fn epoch_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`, so that cached files are found by later versions.
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Fnv64 {
+        Fnv64(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn cache(name: &str, read: bool) -> HttpCache {
+        HttpCache {
+            dir: std::env::temp_dir().join(format!(
+                "zg-test-http-cache-{}-{}",
+                name,
+                std::process::id()
+            )),
+            ttl: Duration::from_secs(60),
+            read,
+        }
+    }
+
+    const URL: &str = "https://container.googleapis.com/v1/projects/p/locations/-/clusters";
+
+    #[test]
+    fn test_key() {
+        let key = HttpCache::key(
+            URL,
+            &headers(&[("authorization", "Bearer a"), ("x-goog-user-project", "p")]),
+        );
+        assert_eq!(key.len(), 16);
+        // The order of the headers doesn't matter
+        assert_eq!(
+            key,
+            HttpCache::key(
+                URL,
+                &headers(&[("x-goog-user-project", "p"), ("authorization", "Bearer a")])
+            )
+        );
+        // Another credential, header value, or URL is another key
+        for other in [
+            HttpCache::key(
+                URL,
+                &headers(&[("authorization", "Bearer b"), ("x-goog-user-project", "p")]),
+            ),
+            HttpCache::key(URL, &headers(&[("authorization", "Bearer a")])),
+            HttpCache::key(
+                &format!("{}?pageSize=5", URL),
+                &headers(&[("authorization", "Bearer a"), ("x-goog-user-project", "p")]),
+            ),
+        ] {
+            assert_ne!(key, other);
+        }
+        // Stable across builds
+        assert_eq!(HttpCache::key("", &HeaderMap::new()), "cbf29ce484222325");
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let cache = cache("ttl", true);
+        let stored = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(cache.get_at("k", stored), None);
+
+        cache.put_at("k", URL, 200, Some("application/json"), "{}", stored);
+        let (cached, age) = cache.get_at("k", stored + Duration::from_secs(42)).unwrap();
+        assert_eq!(age, Duration::from_secs(42));
+        assert_eq!(cached.body, "{}");
+        assert_eq!(cached.content_type.as_deref(), Some("application/json"));
+        assert_eq!(cache.get_at("k", stored + Duration::from_secs(60)), None);
+
+        // Errors are not cached
+        cache.put_at("e", URL, 404, None, "{\"error\": {}}", stored);
+        assert_eq!(cache.get_at("e", stored), None);
+
+        // Readable only by the user
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode =
+                |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&cache.dir), 0o700);
+            assert_eq!(mode(&cache.path("k")), 0o600);
+        }
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_cache_bypasses_reads() {
+        let cache = cache("bypass", false);
+        let now = SystemTime::now();
+        cache.put_at("k", URL, 200, None, "fresh", now);
+        // Not read, but stored for the next runs
+        assert_eq!(cache.get_at("k", now), None);
+        let reading = HttpCache {
+            read: true,
+            ..cache
+        };
+        assert_eq!(reading.get_at("k", now).unwrap().0.body, "fresh");
+        fs::remove_dir_all(&reading.dir).unwrap();
+    }
+}
@@ -0,0 +1,528 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of discovery schemas into JSON Schema (draft 2020-12), e.g., for `zg desc --json-schema`.
+
+use log::debug;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::core;
+use super::discovery;
+use super::error::ZgError;
+
+/// The `$schema` of the generated documents.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Returns a self-contained JSON Schema document of the request body of the method.
+/// Referenced schemas are resolved from `api.schemas` into `$defs`. Read-only properties are marked with
+/// `readOnly`, or left out with `writable_only`.
+pub fn request_schema(
+    method: &core::ZgMethod,
+    api: &core::ZgApi,
+    writable_only: bool,
+) -> Result<Value, ZgError> {
+    let schema = method.request_data_schema.as_ref().ok_or_else(|| {
+        ZgError::Other(format!(
+            "Method '{}' ({}) has no request body to describe as JSON Schema.",
+            method.id, method.http_method
+        ))
+    })?;
+    let mut converter = Converter::new(&api.schemas, method.original_id.as_deref(), writable_only);
+    let mut document = converter.schema(schema);
+    let defs = converter.definitions();
+
+    document.insert("$schema".to_string(), Value::from(DIALECT));
+    if let Some(id) = &schema.id {
+        document.insert("title".to_string(), Value::from(id.as_str()));
+    }
+    if !defs.is_empty() {
+        document.insert("$defs".to_string(), Value::Object(defs));
+    }
+    Ok(Value::Object(document))
+}
+
+/// Where the converted schemas are used, which decides how references and binary strings are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// JSON Schema draft 2020-12, with the referenced schemas in `$defs`.
+    #[default]
+    JsonSchema,
+    /// Schema Objects of OpenAPI 3.0, with the referenced schemas in `components/schemas` (e.g., for `zg export openapi`).
+    OpenApi,
+}
+
+impl Dialect {
+    fn ref_prefix(self) -> &'static str {
+        match self {
+            Dialect::JsonSchema => "#/$defs/",
+            Dialect::OpenApi => "#/components/schemas/",
+        }
+    }
+}
+
+/// Converts schemas, collecting the names of the referenced ones to define in `$defs` (or `components/schemas`).
+pub struct Converter<'a> {
+    schemas: &'a HashMap<String, discovery::Schema>,
+    // Original id of the method, for properties annotated as required by the method (compute and storage)
+    method_id: Option<&'a str>,
+    writable_only: bool,
+    dialect: Dialect,
+    referenced: BTreeSet<String>,
+}
+
+impl<'a> Converter<'a> {
+    pub fn new(
+        schemas: &'a HashMap<String, discovery::Schema>,
+        method_id: Option<&'a str>,
+        writable_only: bool,
+    ) -> Self {
+        Converter {
+            schemas,
+            method_id,
+            writable_only,
+            dialect: Dialect::default(),
+            referenced: BTreeSet::new(),
+        }
+    }
+
+    /// Sets the dialect of the converted schemas (JSON Schema by default).
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Converts a reference to the named schema, which is then included in `definitions`.
+    pub fn reference(&mut self, name: &str) -> Map<String, Value> {
+        self.referenced.insert(name.to_string());
+        let mut reference = Map::new();
+        reference.insert(
+            "$ref".to_string(),
+            Value::from(format!("{}{}", self.dialect.ref_prefix(), name)),
+        );
+        reference
+    }
+
+    /// Converts the referenced schemas, including those referenced from them, into the `$defs` (or `components/schemas`) object.
+    /// Each schema is defined once, so self-referencing (cyclic) schemas end.
+    pub fn definitions(&mut self) -> Map<String, Value> {
+        let mut defs = BTreeMap::new();
+        while let Some(name) = self
+            .referenced
+            .iter()
+            .find(|name| !defs.contains_key(*name))
+            .cloned()
+        {
+            let def = match self.schemas.get(&name) {
+                Some(schema) => Value::Object(self.schema(schema)),
+                None => {
+                    debug!("Schema '{}' is not found; defined as any value", name);
+                    Value::Object(Map::new())
+                }
+            };
+            defs.insert(name, def);
+        }
+        defs.into_iter().collect()
+    }
+
+    /// Converts a schema: a top-level schema, array items, or an inline property.
+    pub fn schema(&mut self, schema: &discovery::Schema) -> Map<String, Value> {
+        if let Some(name) = &schema.ref_name {
+            return self.reference(name);
+        }
+        let mut converted = Map::new();
+        let schema_type = schema
+            .schema_type
+            .as_deref()
+            .or(schema.properties.as_ref().map(|_| "object"));
+        self.insert_type(&mut converted, schema_type, schema.format.as_deref());
+        if let Some(description) = &schema.description {
+            converted.insert("description".to_string(), Value::from(description.as_str()));
+        }
+        if let Some(values) = &schema.enum_values {
+            converted.insert("enum".to_string(), Value::from(values.clone()));
+        }
+        if let Some(items) = &schema.items {
+            converted.insert("items".to_string(), Value::Object(self.schema(items)));
+        }
+
+        if let Some(properties) = &schema.properties {
+            let mut names: Vec<&String> = properties.keys().collect();
+            names.sort();
+            let mut converted_properties = Map::new();
+            let mut required = Vec::new();
+            for name in names {
+                let property = &properties[name];
+                if self.writable_only && property.read_only {
+                    continue;
+                }
+                if self.is_required(schema, name, property) {
+                    required.push(Value::from(name.as_str()));
+                }
+                converted_properties.insert(name.clone(), Value::Object(self.property(property)));
+            }
+            converted.insert(
+                "properties".to_string(),
+                Value::Object(converted_properties),
+            );
+            if !required.is_empty() {
+                converted.insert("required".to_string(), Value::Array(required));
+            }
+        }
+        converted
+    }
+
+    /// Converts a property of a schema.
+    pub fn property(&mut self, property: &discovery::SchemaProperty) -> Map<String, Value> {
+        let mut converted = match &property.ref_name {
+            Some(name) => self.reference(name),
+            None => {
+                let mut converted = Map::new();
+                self.insert_type(
+                    &mut converted,
+                    property.prop_type.as_deref(),
+                    property.format.as_deref(),
+                );
+                converted
+            }
+        };
+        if let Some(description) = &property.description {
+            converted.insert("description".to_string(), Value::from(description.as_str()));
+        }
+        if let Some(values) = &property.enum_values {
+            converted.insert("enum".to_string(), Value::from(values.clone()));
+        }
+        if let Some(items) = &property.items {
+            converted.insert("items".to_string(), Value::Object(self.schema(items)));
+        }
+        if let Some(properties) = &property.properties {
+            let mut names: Vec<&String> = properties.keys().collect();
+            names.sort();
+            let converted_properties: Map<String, Value> = names
+                .into_iter()
+                .map(|name| (name.clone(), Value::Object(self.schema(&properties[name]))))
+                .collect();
+            converted.insert(
+                "properties".to_string(),
+                Value::Object(converted_properties),
+            );
+        }
+        if let Some(value_property) = &property.additional_properties {
+            converted.insert(
+                "additionalProperties".to_string(),
+                Value::Object(self.property(value_property)),
+            );
+        }
+        if property.read_only {
+            converted.insert("readOnly".to_string(), Value::Bool(true));
+        }
+        if property.deprecated {
+            converted.insert("deprecated".to_string(), Value::Bool(true));
+        }
+        converted
+    }
+
+    /// Inserts `type` and `format` (or `contentEncoding` for base64 strings in JSON Schema) converted from the discovery type and format.
+    fn insert_type(
+        &self,
+        converted: &mut Map<String, Value>,
+        discovery_type: Option<&str>,
+        format: Option<&str>,
+    ) {
+        // "any" has no JSON Schema type; any value is accepted without `type`
+        if let Some(schema_type) = discovery_type.filter(|t| *t != "any") {
+            converted.insert("type".to_string(), Value::from(schema_type));
+        }
+        match format {
+            None => {}
+            Some("byte") if self.dialect == Dialect::JsonSchema => {
+                converted.insert("contentEncoding".to_string(), Value::from("base64"));
+            }
+            Some("google-datetime") => {
+                converted.insert("format".to_string(), Value::from("date-time"));
+            }
+            // Others (e.g., int64, google-duration) are kept as they are; validators ignore unknown formats
+            Some(format) => {
+                converted.insert("format".to_string(), Value::from(format));
+            }
+        }
+    }
+
+    /// Required if listed in the schema's `required` array, described as "Required.", or annotated as required by the method.
+    /// Read-only properties are never required.
+    fn is_required(
+        &self,
+        schema: &discovery::Schema,
+        name: &str,
+        property: &discovery::SchemaProperty,
+    ) -> bool {
+        if property.read_only {
+            return false;
+        }
+        let described_as_required = property
+            .description
+            .as_deref()
+            .is_some_and(|description| description.starts_with("Required"));
+        let annotated_as_required = self.method_id.is_some_and(|method_id| {
+            property
+                .annotations
+                .as_ref()
+                .is_some_and(|annotations| annotations.required.iter().any(|m| m == method_id))
+        });
+        schema.is_property_required(name) || described_as_required || annotated_as_required
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    /// Keywords of JSON Schema draft 2020-12 with the JSON type of their values, for the keywords the converter emits.
+    fn keyword_type(keyword: &str) -> Option<&'static str> {
+        match keyword {
+            "$schema" | "$ref" | "title" | "description" | "format" | "contentEncoding" => {
+                Some("string")
+            }
+            "type" => Some("string"),
+            "enum" | "required" => Some("array"),
+            "readOnly" | "deprecated" => Some("boolean"),
+            "properties" | "$defs" => Some("schema map"),
+            "items" | "additionalProperties" => Some("schema"),
+            _ => None,
+        }
+    }
+
+    /// Checks the document against the meta-schema rules for the keywords in use (a minimal meta-validator):
+    /// known keywords with values of the right types, valid `type` names, unique `required` and `enum` strings,
+    /// and `$ref`s that resolve to `$defs` of the document.
+    fn validate_meta(document: &Value) -> Result<(), String> {
+        fn check(schema: &Value, defs: &Map<String, Value>, at: &str) -> Result<(), String> {
+            let object = schema
+                .as_object()
+                .ok_or_else(|| format!("{}: a schema must be an object", at))?;
+            for (keyword, value) in object {
+                let at = format!("{}/{}", at, keyword);
+                match keyword_type(keyword) {
+                    None => return Err(format!("{}: unknown keyword", at)),
+                    Some("string") if !value.is_string() => {
+                        return Err(format!("{}: not a string", at))
+                    }
+                    Some("boolean") if !value.is_boolean() => {
+                        return Err(format!("{}: not a boolean", at))
+                    }
+                    Some("array") => {
+                        let items = value
+                            .as_array()
+                            .ok_or_else(|| format!("{}: not an array", at))?;
+                        let unique: BTreeSet<String> = items.iter().map(Value::to_string).collect();
+                        if unique.len() != items.len() {
+                            return Err(format!("{}: duplicated items", at));
+                        }
+                        if keyword == "required" && !items.iter().all(Value::is_string) {
+                            return Err(format!("{}: not strings", at));
+                        }
+                    }
+                    Some("schema") => check(value, defs, &at)?,
+                    Some("schema map") => {
+                        let map = value
+                            .as_object()
+                            .ok_or_else(|| format!("{}: not an object", at))?;
+                        for (name, schema) in map {
+                            check(schema, defs, &format!("{}/{}", at, name))?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(schema_type) = object.get("type").and_then(Value::as_str) {
+                let types = [
+                    "null", "boolean", "object", "array", "number", "string", "integer",
+                ];
+                if !types.contains(&schema_type) {
+                    return Err(format!("{}/type: invalid type '{}'", at, schema_type));
+                }
+            }
+            if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                let name = reference
+                    .strip_prefix("#/$defs/")
+                    .ok_or_else(|| format!("{}/$ref: not in $defs", at))?;
+                if !defs.contains_key(name) {
+                    return Err(format!("{}/$ref: '{}' is not defined", at, name));
+                }
+            }
+            Ok(())
+        }
+
+        if document["$schema"] != DIALECT {
+            return Err("$schema is not draft 2020-12".to_string());
+        }
+        let no_defs = Map::new();
+        let defs = document["$defs"].as_object().unwrap_or(&no_defs);
+        check(document, defs, "#")
+    }
+
+    fn schema(value: Value) -> discovery::Schema {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// An API whose request schema has a cycle, arrays of refs, enums, maps, and read-only properties.
+    fn test_api() -> (core::ZgApi, core::ZgMethod) {
+        let instance = schema(json!({
+            "id": "Instance",
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "description": "Required. Name of the instance."},
+                "id": {"type": "string", "format": "uint64", "readOnly": true, "description": "Output only."},
+                "status": {"type": "string", "enum": ["RUNNING", "STOPPED"], "readOnly": true},
+                "machineType": {"type": "string", "annotations": {"required": ["compute.instances.insert"]}},
+                "disks": {"type": "array", "items": {"$ref": "AttachedDisk"}},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "labels": {"type": "object", "additionalProperties": {"type": "string"}},
+                "createTime": {"type": "string", "format": "google-datetime"},
+                "metadata": {"type": "any"}
+            }
+        }));
+        let disk = schema(json!({
+            "id": "AttachedDisk",
+            "type": "object",
+            "properties": {
+                "source": {"type": "string", "format": "byte"},
+                "mode": {"type": "string", "enum": ["READ_WRITE", "READ_ONLY"]},
+                // A cycle: AttachedDisk -> Instance -> AttachedDisk
+                "attachedTo": {"$ref": "Instance"}
+            }
+        }));
+        let api = core::ZgApi {
+            schemas: HashMap::from([
+                ("Instance".to_string(), instance.clone()),
+                ("AttachedDisk".to_string(), disk),
+            ]),
+            ..core::ZgApi::testdata()
+        };
+        let method = core::ZgMethod {
+            original_id: Some("compute.instances.insert".to_string()),
+            http_method: "POST".to_string(),
+            request_data_schema: Some(instance),
+            ..core::ZgMethod::testdata()
+        };
+        (api, method)
+    }
+
+    #[test]
+    fn test_request_schema() {
+        let (api, method) = test_api();
+        let document = request_schema(&method, &api, false).unwrap();
+        validate_meta(&document).unwrap();
+
+        assert_eq!(document["title"], "Instance");
+        assert_eq!(document["type"], "object");
+        assert_eq!(document["required"], json!(["machineType", "name"]));
+        assert_eq!(
+            document["properties"]["id"],
+            json!({"type": "string", "format": "uint64", "readOnly": true, "description": "Output only."})
+        );
+        assert_eq!(
+            document["properties"]["status"]["enum"],
+            json!(["RUNNING", "STOPPED"])
+        );
+        assert_eq!(
+            document["properties"]["disks"],
+            json!({"type": "array", "items": {"$ref": "#/$defs/AttachedDisk"}})
+        );
+        assert_eq!(
+            document["properties"]["tags"]["items"],
+            json!({"type": "string"})
+        );
+        assert_eq!(
+            document["properties"]["labels"],
+            json!({"type": "object", "additionalProperties": {"type": "string"}})
+        );
+        assert_eq!(document["properties"]["createTime"]["format"], "date-time");
+        assert_eq!(document["properties"]["metadata"], json!({}));
+
+        // Both schemas of the cycle are defined once
+        let defs = document["$defs"].as_object().unwrap();
+        assert_eq!(
+            defs.keys().collect::<Vec<_>>(),
+            vec!["AttachedDisk", "Instance"]
+        );
+        assert_eq!(
+            defs["AttachedDisk"]["properties"]["attachedTo"],
+            json!({"$ref": "#/$defs/Instance"})
+        );
+        assert_eq!(
+            defs["AttachedDisk"]["properties"]["source"],
+            json!({"type": "string", "contentEncoding": "base64"})
+        );
+    }
+
+    #[test]
+    fn test_request_schema_writable_only() {
+        let (api, method) = test_api();
+        let document = request_schema(&method, &api, true).unwrap();
+        validate_meta(&document).unwrap();
+        let properties = document["properties"].as_object().unwrap();
+        assert!(!properties.contains_key("id"));
+        assert!(!properties.contains_key("status"));
+        assert!(properties.contains_key("name"));
+    }
+
+    #[test]
+    fn test_request_schema_from_fixture() {
+        let api = crate::update::extract_api(
+            PathBuf::from("tests/test_data/run_v2_required.json"),
+            false,
+        )
+        .unwrap();
+        let (_, method) =
+            core::find_method_by_id(&api, "run.projects.locations.jobs.create", true).unwrap();
+        let document = request_schema(&method, &api, false).unwrap();
+        validate_meta(&document).unwrap();
+        assert_eq!(document["required"], json!(["template"]));
+        assert_eq!(
+            document["properties"]["template"]["$ref"],
+            "#/$defs/GoogleCloudRunV2ExecutionTemplate"
+        );
+        assert_eq!(
+            document["$defs"]["GoogleCloudRunV2TaskTemplate"]["required"],
+            json!(["serviceAccount"])
+        );
+
+        let get = core::ZgMethod {
+            request_data_schema: None,
+            ..method
+        };
+        assert!(request_schema(&get, &api, false).is_err());
+    }
+
+    #[test]
+    fn test_validate_meta_rejects_invalid() {
+        let valid = json!({"$schema": DIALECT, "type": "object"});
+        assert!(validate_meta(&valid).is_ok());
+        let invalid = [
+            json!({"$schema": DIALECT, "type": "any"}),
+            json!({"$schema": DIALECT, "properties": {"a": {"$ref": "#/$defs/Missing"}}}),
+            json!({"$schema": DIALECT, "required": ["a", "a"]}),
+            json!({"$schema": DIALECT, "readonly": true}),
+            json!({"type": "object"}),
+        ];
+        for document in invalid {
+            assert!(validate_meta(&document).is_err(), "{}", document);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! zygen: navigate and call Google Cloud APIs based on their discovery documents.
+//!
+//! The `zg` CLI is a thin layer over this library. To build requests programmatically:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), zygen::error::ZgError> {
+//! let client = zygen::Client::load("container").await?;
+//! let url = client
+//!     .request("container.projects.locations.clusters.get")?
+//!     .param("projectsId", "my-project")
+//!     .param("locationsId", "us-central1")
+//!     .param("clustersId", "my-cluster")
+//!     .build_url()?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod api_error;
+mod auth;
+mod cache;
+#[doc(hidden)]
+pub mod cli; // The zg binary; not a part of the library API
+pub mod client;
+mod color;
+mod completions;
+mod config;
+pub mod core;
+mod credentials;
+mod desc;
+mod discovery;
+mod doctor;
+pub mod error;
+mod exec;
+mod export;
+mod flavors;
+mod history;
+mod http_cache;
+mod json_schema;
+mod list;
+mod logging;
+mod metadata;
+mod mtls;
+mod natural;
+mod open;
+mod output;
+mod pager;
+mod picker;
+mod saved;
+mod shell;
+mod spinner;
+mod substitute;
+mod suggest;
+mod supported_apis;
+mod template;
+mod timing;
+mod trace;
+mod update;
+mod wrap;
+
+pub use client::{Client, RequestBuilder};
+pub use update::extract_api;
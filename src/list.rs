@@ -12,60 +12,190 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use clap::Args;
+use clap::{Args, Parser};
 use prettytable::{format, row, Cell, Row, Table};
+use serde::Serialize;
 use std::cmp::max;
-use std::error::Error;
 use std::fmt::Write;
+use std::io::{BufRead, IsTerminal};
 use std::str::FromStr;
 
+use super::color;
+use super::config;
 use super::core;
-use super::supported_apis::{supported_apis, SupportedApi};
+use super::discovery;
+use super::error::ZgError;
+use super::exec::ExecArgs;
+use super::natural;
+use super::output::Format;
+use super::picker;
+use super::supported_apis::{supported_apis, workspace_apis, ApiStatus, SupportedApi};
+use super::timing;
+use super::wrap;
 
 #[derive(Args, Debug, Default)]
 pub struct ListArgs {
     /// The service (e.g., "compute") for which list underlying resources. If omitted, lists all available services (APIs).
     service: Option<String>,
 
-    /// The resource (e.g., "instances") for which list underlying methods. Requires [SERVICE] argument.
+    /// The resource (e.g., "instances") for which list underlying methods. Requires [SERVICE] argument. Accepts dot- or slash-separated paths (e.g., "zones.instances" or "zones/instances").
     resource: Option<String>,
 
     /// The method (e.g., "delete") of a resource. Requires [SERVICE] and [RESOURCE] argument. Typically, listing a single method is not helpful.
     method: Option<String>, // Typically not helpful, but allowed for compatibility with other commands (desc, exec)
 
     /// List all items.
-    #[arg(short = 'A', long)]
+    #[arg(short = 'A', long, overrides_with = "no_all")]
     all: bool,
 
+    /// Don't list all items, even if `all` is set in the config file.
+    #[arg(long, overrides_with = "all")]
+    no_all: bool,
+
+    /// Include Google Workspace APIs (e.g., drive, sheets, admin). Effective only when listing services; --all includes them as well.
+    #[arg(short = 'w', long)]
+    workspace: bool,
+
+    /// Show only services of the given status. Effective only when listing services.
+    #[arg(long, value_enum, ignore_case = true)]
+    status: Option<ApiStatus>,
+
     /// Show aliases of services. Effective only when listing services without --long.
-    #[arg(short = 'a', long)]
+    #[arg(short = 'a', long, overrides_with = "no_aliases")]
     aliases: bool,
 
+    /// Don't show aliases of services, even if `aliases` is set in the config file.
+    #[arg(long, overrides_with = "aliases")]
+    no_aliases: bool,
+
     /// Show service category with title. Effective only when listing services.
-    #[arg(short = 'c', long)]
+    #[arg(short = 'c', long, overrides_with = "no_category")]
     category: bool,
 
+    /// Don't show service category, even if `category` is set in the config file.
+    #[arg(long, overrides_with = "category")]
+    no_category: bool,
+
     /// Display detailed information in long format.
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_long")]
     long: bool,
 
-    /// Colorize the output.
-    #[arg(short = 'C', long)]
-    color: bool,
+    /// Don't use the long format, even if `long` is set in the config file.
+    #[arg(long, overrides_with = "long")]
+    no_long: bool,
+
+    /// Colorize the output. Same as the global `--color=always`.
+    #[arg(short = 'C')]
+    color_always: bool,
 
     #[arg(
         short = 'S',
         long,
         help = "Sort services, methods, or resources by the given field.\n\
-    \tServices' sortable fields: [id, name, aliases, versions]\n\
+    \tServices' sortable fields: [id, name, aliases, versions, status]\n\
     \tResources' sortable fields: [name, depth, path, methods]. Effective only with --long.\n\
     \tMethods' sortable fields: [name, http, path]"
     )]
     sort: Option<String>,
 
     /// Reverse the sort order. Reversing resources takes effect only with --long.
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_reverse")]
     reverse: bool,
+
+    /// Don't reverse the sort order, even if `reverse` is set in the config file.
+    #[arg(long, overrides_with = "reverse")]
+    no_reverse: bool,
+
+    /// Fail when the resource is ambiguous instead of picking one of the candidates.
+    #[arg(long, alias = "exact", overrides_with = "no_strict")]
+    strict: bool,
+
+    /// Pick one of the candidates of an ambiguous resource, even if `strict` is set in the config file.
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
+
+    /// List APIs in the discovery directory that are not supported by zg yet (usable with --any-api).
+    #[arg(long)]
+    discovered: bool,
+
+    /// List all versions of the service in the discovery directory, with whether each is supported by zg and cached locally.
+    #[arg(long)]
+    versions: bool,
+
+    /// List resource names shared by multiple resources of the service, with the paths to select each one.
+    #[arg(long)]
+    duplicates: bool,
+
+    /// Output in JSON. Same as the global `--format json`.
+    #[arg(long)]
+    json: bool,
+
+    /// Choose a resource of the service and then one of its methods in a fuzzy finder on the terminal (type to filter,
+    /// arrow keys to move, Enter to choose, Escape to cancel), and print the commands to describe and execute it.
+    /// With RESOURCE, only its methods are listed. Only on Unix-like systems.
+    #[arg(long, requires = "service", conflicts_with_all = PICK_CONFLICTS)]
+    pick: bool,
+
+    /// Like --pick, but execute the chosen method instead of printing the commands. Methods other than GET are
+    /// executed only after confirmation.
+    #[arg(long, requires = "service", conflicts_with_all = PICK_CONFLICTS)]
+    pick_exec: bool,
+}
+
+/// Options that don't make sense with the fuzzy finder.
+const PICK_CONFLICTS: [&str; 6] = [
+    "method",
+    "long",
+    "json",
+    "versions",
+    "duplicates",
+    "discovered",
+];
+
+impl ListArgs {
+    /// When to colorize the output as given by `-C`, resolved by the global color policy with `color` of `[list]` in
+    /// the config file.
+    pub fn color_when(&self) -> Option<color::ColorWhen> {
+        self.color_always.then_some(color::ColorWhen::Always)
+    }
+
+    /// Returns `color` of `[list]` in the config file, or the global one.
+    pub fn configured_color(config: &config::Config) -> Option<bool> {
+        config.list.color.or(config.color)
+    }
+
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        let list = &config.list;
+        self.all = config::resolve_flag(self.all, self.no_all, list.all);
+        self.aliases = config::resolve_flag(self.aliases, self.no_aliases, list.aliases);
+        self.category = config::resolve_flag(self.category, self.no_category, list.category);
+        self.long = config::resolve_flag(self.long, self.no_long, list.long);
+        self.reverse = config::resolve_flag(self.reverse, self.no_reverse, list.reverse);
+        self.strict = config::resolve_flag(self.strict, self.no_strict, list.strict);
+        if self.sort.is_none() {
+            self.sort = list.sort.clone();
+        }
+    }
+
+    /// Whether to choose the method in the fuzzy finder (`--pick` or `--pick-exec`) instead of listing.
+    pub fn picking(&self) -> bool {
+        self.pick || self.pick_exec
+    }
+
+    /// Whether to execute the method chosen in the fuzzy finder.
+    pub fn pick_exec(&self) -> bool {
+        self.pick_exec
+    }
+
+    /// Returns the output format, where `--json` takes precedence over the global `--format`.
+    pub fn format(&self, global: Format) -> Format {
+        if self.json {
+            Format::Json
+        } else {
+            global
+        }
+    }
 }
 
 /// Main function to handle listing of services, resources, or methods.
@@ -76,15 +206,31 @@ pub struct ListArgs {
 ///   - If no resource path, the function lists all resources for the service.
 ///   - If a resource path is specified, the function lists the methods of the resource.
 ///     - If a method is specified, it lists only that method (Note: This is not very useful).
-pub async fn main(
-    args: &ListArgs,
-    standalone_api_key: Option<String>,
-) -> Result<(), Box<dyn Error>> {
+pub async fn main(args: &ListArgs, standalone_api_key: Option<String>) -> Result<(), ZgError> {
     let output = match (&args.service, &args.resource, &args.method) {
+        (None, _, _) if args.discovered => {
+            // No service specified; list APIs beyond the supported ones
+            let directory =
+                discovery::ensure_discovered_apis(false, &discovery::DiscoveryAuth::current())
+                    .await?;
+            list_discovered(&directory.items, args)
+        }
         (None, _, _) => {
             // No service specified; list all services
             list_services(args)
         }
+        (Some(svc), None, _) if args.versions => {
+            // Service specified with --versions; list versions in the discovery directory
+            let directory =
+                discovery::ensure_discovered_apis(false, &discovery::DiscoveryAuth::current())
+                    .await?;
+            list_versions(svc, &directory.items)
+        }
+        (Some(svc), None, _) if args.duplicates => {
+            // Service specified with --duplicates; list ambiguous resource names
+            let api = core::load_api_file(svc, standalone_api_key).await?;
+            list_duplicates(&api)
+        }
         (Some(svc), None, _) => {
             // Service specified; list resources
             let api = core::load_api_file(svc, standalone_api_key).await?;
@@ -101,53 +247,380 @@ pub async fn main(
     Ok(())
 }
 
+/// A method chosen in the fuzzy finder of `--pick`.
+#[derive(Debug, PartialEq)]
+pub struct Picked {
+    service: String,
+    resource: String,
+    method: String,
+    http_method: String,
+}
+
+impl Picked {
+    /// The commands to describe and execute the method, one per line.
+    pub fn commands(&self) -> String {
+        format!("{}\n{}\n", self.command("desc"), self.command("ex"))
+    }
+
+    /// The `zg` command of the subcommand with the method.
+    fn command(&self, subcommand: &str) -> String {
+        let words = [subcommand, &self.service, &self.resource, &self.method];
+        format!("zg {}", shlex::try_join(words).unwrap_or_default())
+    }
+
+    /// Whether to execute the method for `--pick-exec`. GET methods run as they are; the others only after the user
+    /// answers yes (read from `input`) to the prompt with the `zg ex` command.
+    pub fn confirm_exec(&self, input: &mut impl BufRead) -> Result<bool, ZgError> {
+        if self.http_method == "GET" {
+            return Ok(true);
+        }
+        eprint!(
+            "{}\nExecute this {} method? [y/N] ",
+            self.command("ex"),
+            self.http_method
+        );
+        std::io::Write::flush(&mut std::io::stderr())?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    /// The arguments of `zg exec` to execute the method.
+    pub fn exec_args(&self) -> Result<ExecArgs, ZgError> {
+        #[derive(Parser)]
+        #[command(name = "zg ex")]
+        struct Cli {
+            #[command(flatten)]
+            args: ExecArgs,
+        }
+        let words = ["zg ex", &self.service, &self.resource, &self.method];
+        let cli = Cli::try_parse_from(words).map_err(|e| ZgError::Other(e.to_string()))?;
+        Ok(cli.args)
+    }
+}
+
+/// Lets the user choose a resource of the service (unless given) and then a method of it in the fuzzy finder. Returns
+/// None if it's canceled. Fails unless it's a Unix-like system and stdin is a terminal (see `pick_unavailable`).
+pub async fn pick(
+    args: &ListArgs,
+    standalone_api_key: Option<String>,
+) -> Result<Option<Picked>, ZgError> {
+    let service = args.service.as_deref().unwrap_or_default();
+    if let Some(reason) = pick_unavailable(service, cfg!(unix), std::io::stdin().is_terminal()) {
+        return Err(reason.into());
+    }
+    let api = core::load_api_file(service, standalone_api_key).await?;
+    let resource = match &args.resource {
+        Some(resource) => resource.clone(),
+        None => {
+            let resources = resource_choices(&api);
+            match picker::pick(&format!("{} ", service), resources.clone())? {
+                Some(i) => resources[i].clone(),
+                None => return Ok(None),
+            }
+        }
+    };
+    let api_resource = core::find_api_resource(&api, &resource, args.strict)?;
+    let (methods, labels) = method_choices(api_resource);
+    let Some(i) = picker::pick(&format!("{} {} ", service, resource), labels)? else {
+        return Ok(None);
+    };
+    Ok(Some(Picked {
+        service: service.to_string(),
+        resource,
+        method: methods[i].clone(),
+        http_method: api_resource.methods[i].http_method.clone(),
+    }))
+}
+
+/// Why the fuzzy finder can't run, if it can't: the terminal is put in raw mode with `stty`, which is only on Unix-like
+/// systems, and the keys are read from stdin, which has to be a terminal.
+fn pick_unavailable(service: &str, unix: bool, stdin_terminal: bool) -> Option<String> {
+    let reason = match (unix, stdin_terminal) {
+        (false, _) => "--pick is only supported on Unix-like systems (Linux and macOS)",
+        (true, false) => "--pick needs an interactive terminal, but stdin is not a terminal",
+        (true, true) => return None,
+    };
+    Some(format!(
+        "{}; list the resources with `zg ls {}` instead",
+        reason, service
+    ))
+}
+
+/// The paths of the resources with methods (e.g., "projects.locations.clusters"), without the name of the API.
+fn resource_choices(api: &core::ZgApi) -> Vec<String> {
+    api.all_resource_paths()
+        .into_iter()
+        .filter_map(|(_, path)| path.split_once('.').map(|(_, path)| path.to_string()))
+        .filter(|path| {
+            core::find_api_resource(api, path, false)
+                .is_ok_and(|resource| !resource.methods.is_empty())
+        })
+        .collect()
+}
+
+/// The names of the methods of the resource, and their labels in the fuzzy finder with the HTTP methods and the paths.
+fn method_choices(resource: &core::ZgResource) -> (Vec<String>, Vec<String>) {
+    let width = resource
+        .methods
+        .iter()
+        .map(|m| m.name.len())
+        .max()
+        .unwrap_or(0);
+    resource
+        .methods
+        .iter()
+        .map(|method| {
+            let label = format!(
+                "{:<width$}  {:<6}  {}",
+                method.name, method.http_method, method.flat_path
+            );
+            (method.name.clone(), label)
+        })
+        .unzip()
+}
+
+/// Returns what `main` lists as a serializable value, for `--format json` and `--format yaml`.
+/// Items are filtered and sorted by the same options, while `--long` and the other display options are ignored.
+pub async fn result(
+    args: &ListArgs,
+    standalone_api_key: Option<String>,
+) -> Result<Listing, ZgError> {
+    let listing = match (&args.service, &args.resource) {
+        (None, _) if args.discovered => {
+            let directory =
+                discovery::ensure_discovered_apis(false, &discovery::DiscoveryAuth::current())
+                    .await?;
+            Listing::Discovered(
+                select_discovered(&directory.items, args)
+                    .into_iter()
+                    .map(|item| DiscoveredItem {
+                        id: item.id.clone(),
+                        title: item.title.clone(),
+                    })
+                    .collect(),
+            )
+        }
+        (None, _) => {
+            let directory_items = cached_directory_items();
+            Listing::Services(
+                select_services(args)
+                    .into_iter()
+                    .map(|api| ServiceItem {
+                        documentation_link: docs_link(
+                            &directory_items,
+                            &api.name,
+                            pinned_version(&api).unwrap_or(api.default_version()),
+                        ),
+                        default_version: pinned_version(&api)
+                            .unwrap_or(api.default_version())
+                            .to_string(),
+                        pinned: pinned_version(&api).is_some(),
+                        category: api.category.as_str(),
+                        status: api.status.to_string(),
+                        name: api.name,
+                        title: api.title,
+                        aliases: api.aliases,
+                        versions: api.versions,
+                    })
+                    .collect(),
+            )
+        }
+        (Some(svc), None) if args.versions => {
+            let directory =
+                discovery::ensure_discovered_apis(false, &discovery::DiscoveryAuth::current())
+                    .await?;
+            Listing::Versions(collect_versions(svc, &directory.items, |id| {
+                core::api_dir().join(core::api_file_name(id)).exists()
+            }))
+        }
+        (Some(svc), None) if args.duplicates => {
+            let api = core::load_api_file(svc, standalone_api_key).await?;
+            Listing::Duplicates(api.resource_ambiguities())
+        }
+        (Some(svc), None) => {
+            let api = core::load_api_file(svc, standalone_api_key).await?;
+            Listing::Resources(resource_items(&api.resources))
+        }
+        (Some(svc), Some(resource_path)) => {
+            let api = core::load_api_file(svc, standalone_api_key).await?;
+            Listing::Methods(
+                select_methods(&api, resource_path, args)?
+                    .into_iter()
+                    .map(MethodItem::from)
+                    .collect(),
+            )
+        }
+    };
+    Ok(listing)
+}
+
+/// The structured output of `zg ls`, serialized as the list of the items.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Listing {
+    Services(Vec<ServiceItem>),
+    Discovered(Vec<DiscoveredItem>),
+    Versions(Vec<VersionInfo>),
+    Duplicates(Vec<core::ResourceAmbiguity>),
+    Resources(Vec<ResourceItem>),
+    Methods(Vec<MethodItem>),
+}
+
+/// A service in the structured output of `zg ls`.
+#[derive(Serialize)]
+pub struct ServiceItem {
+    name: String,
+    title: String,
+    category: &'static str,
+    aliases: Vec<String>,
+    versions: Vec<String>,
+    default_version: String,
+    /// Whether the default version is pinned in `[versions]` of the config file.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    status: String,
+    /// The documentationLink of the default version in the cached discovery directory.
+    documentation_link: Option<String>,
+}
+
+/// An API in the discovery directory in the structured output of `zg ls --discovered`.
+#[derive(Serialize)]
+pub struct DiscoveredItem {
+    id: String,
+    title: String,
+}
+
+/// A resource with its methods and sub-resources in the structured output of `zg ls SERVICE`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ResourceItem {
+    name: String,
+    path: Option<String>,
+    methods: Vec<String>,
+    resources: Vec<ResourceItem>,
+}
+
+fn resource_items(resources: &[core::ZgResource]) -> Vec<ResourceItem> {
+    resources
+        .iter()
+        .map(|resource| ResourceItem {
+            name: resource.name.clone(),
+            path: resource.path.clone(),
+            methods: resource.methods.iter().map(|m| m.name.clone()).collect(),
+            resources: resource_items(resource.resources.as_deref().unwrap_or_default()),
+        })
+        .collect()
+}
+
+/// A method in the structured output of `zg ls SERVICE RESOURCE`.
+#[derive(Serialize)]
+pub struct MethodItem {
+    name: String,
+    id: String,
+    http_method: String,
+    path: String,
+    /// The schema of the request body (see `body_label`), or null if the method takes none.
+    body: Option<String>,
+}
+
+impl From<&core::ZgMethod> for MethodItem {
+    fn from(method: &core::ZgMethod) -> Self {
+        MethodItem {
+            name: method.name.clone(),
+            id: method.id.clone(),
+            http_method: method.http_method.clone(),
+            path: method.flat_path.clone(),
+            body: body_label(method),
+        }
+    }
+}
+
+/// The schema id of the request body of the method (e.g., `Cluster`), `(inline)` for a schema without an id,
+/// or None if the method takes no body.
+fn body_label(method: &core::ZgMethod) -> Option<String> {
+    let schema = method.request_data_schema.as_ref()?;
+    Some(schema.id.clone().unwrap_or_else(|| "(inline)".to_string()))
+}
+
 #[rustfmt::skip]
 #[allow(clippy::wildcard_in_or_patterns)]
-/// Function to list all available services. With the `--all` flag, it lists all services including the SUB_SUPPORTED_APIS.
-fn list_services(args: &ListArgs) -> Result<String, Box<dyn Error>> {
+/// Selects the services to list, filtered and sorted by the options. With the `--all` flag, it includes the SUB_SUPPORTED_APIS.
+fn select_services(args: &ListArgs) -> Vec<SupportedApi> {
     let mut apis = supported_apis(args.all);
+    if args.workspace && !args.all {
+        apis.extend(workspace_apis());
+    }
+    if let Some(status) = args.status {
+        apis.retain(|api| api.status == status);
+    }
 
     // Sort the services based on the --sort field; default sort key is name.
     let sort_field = &args.sort.as_deref().unwrap_or("name");
     apis.sort_by(|a, b| {
         let sorted = match *sort_field {
             "title" | "api_title" => a.title.cmp(&b.title),
-            "category" | "categories" => a.category.cmp(&b.category),
+            "category" | "categories" => a.category.cmp(&b.category).then(a.name.cmp(&b.name)),
             "aliases" | "alias" => a.aliases.cmp(&b.aliases),
-            "versions" | "version" => a.versions.cmp(&b.versions),
-            "default_version" => a.default_version().cmp(b.default_version()), // practically same as "versions"
-            "name" | "api_name" | _ => a.name.cmp(&b.name), // fallback
+            "versions" | "version" => natural::cmp_version_lists(&a.versions, &b.versions),
+            "status" => a.status.cmp(&b.status).then(a.name.cmp(&b.name)),
+            "default_version" => natural::cmp_versions(a.default_version(), b.default_version()), // practically same as "versions"
+            "name" | "api_name" | _ => natural::cmp_names(&a.name, &b.name), // fallback
         };
         if args.reverse { sorted.reverse() } else { sorted }
     });
+    apis
+}
+
+/// Returns the version pinned in `[versions]` of the config file if the service has it.
+fn pinned_version(api: &SupportedApi) -> Option<&str> {
+    config::current()
+        .pinned_version(api)
+        .map(|(_, version)| version)
+        .filter(|version| api.versions.iter().any(|v| v == version))
+}
+
+/// Function to list all available services (see `select_services`).
+fn list_services(args: &ListArgs) -> Result<String, ZgError> {
+    let _span = timing::span("render");
+    let apis = select_services(args);
 
     if args.long {
+        let directory_items = cached_directory_items();
         let mut table = initialize_services_table();
         for api in apis {
+            let version = pinned_version(&api).unwrap_or(api.default_version());
+            let docs = docs_link(&directory_items, &api.name, version);
             table.add_row(row![
                 api.name,
                 api.title,
                 api.category,
                 api.aliases.join(", "),
                 api.versions.join(", "),
-                api.default_version()
+                match pinned_version(&api) {
+                    Some(version) => format!("{} (pinned)", version),
+                    None => api.default_version().to_string(),
+                },
+                api.status,
+                short_link(docs.as_deref().unwrap_or_default())
             ]);
         }
 
-        table.print_tty(true)?;
-        Ok(String::new()) // Return empty string since --long format is printed directly by print_tty() above
+        color::print_table(&table)?;
+        Ok(String::new()) // Return empty string since --long format is printed directly by print_table() above
     } else {
-        let service_line = |api: &SupportedApi| {
-            match (args.aliases && !api.aliases.is_empty(), args.category) {
+        let service_line =
+            |api: &SupportedApi| match (args.aliases && !api.aliases.is_empty(), args.category) {
                 (true, true) => format!(
                     "[{}] {} - {} ({})",
-                    api.category, api.title, api.name, api.aliases.join(", ")
+                    api.category,
+                    api.title,
+                    api.name,
+                    api.aliases.join(", ")
                 ),
                 (true, false) => format!("{} ({})", api.name, api.aliases.join(", ")),
                 (false, true) => format!("[{}] {} - {}", api.category, api.title, api.name),
                 (false, false) => api.name.to_owned(),
-            }
-        };
+            };
 
         let output = apis.iter().map(service_line).collect::<Vec<_>>().join("\n");
 
@@ -155,22 +628,93 @@ fn list_services(args: &ListArgs) -> Result<String, Box<dyn Error>> {
     }
 }
 
+/// Returns the items of the discovery directory cached locally, or none if it's not cached (not to access the network
+/// only for the documentation links).
+fn cached_directory_items() -> Vec<discovery::DiscoveryDirectoryItem> {
+    discovery::cached_discovered_apis()
+        .map(|directory| directory.items)
+        .unwrap_or_default()
+}
+
+/// Returns the documentationLink of the version of the service in the discovery directory.
+fn docs_link(
+    items: &[discovery::DiscoveryDirectoryItem],
+    name: &str,
+    version: &str,
+) -> Option<String> {
+    items
+        .iter()
+        .find(|item| item.name == name && item.version == version)
+        .and_then(|item| item.documentation_link.clone())
+}
+
+/// Maximum width of the links in the `docs` column of the tables.
+const SHORT_LINK_WIDTH: usize = 40;
+
+/// Shortens the link for the `docs` column: without the scheme and the trailing slash, and cut with `...` if it's
+/// still longer than `SHORT_LINK_WIDTH`.
+fn short_link(link: &str) -> String {
+    let link = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"))
+        .unwrap_or(link)
+        .trim_end_matches('/');
+    wrap::truncate(link, SHORT_LINK_WIDTH)
+}
+
+/// Selects the APIs in the discovery directory that are not in the supported APIs, sorted by id.
+fn select_discovered<'a>(
+    items: &'a [discovery::DiscoveryDirectoryItem],
+    args: &ListArgs,
+) -> Vec<&'a discovery::DiscoveryDirectoryItem> {
+    let supported: Vec<String> = supported_apis(true)
+        .into_iter()
+        .map(|api| api.name)
+        .collect();
+    let mut items: Vec<_> = items
+        .iter()
+        .filter(|item| !supported.contains(&item.name))
+        .collect();
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+    if args.reverse {
+        items.reverse();
+    }
+    items
+}
+
+/// Function to list APIs in the discovery directory that are not in the supported APIs.
+fn list_discovered(
+    items: &[discovery::DiscoveryDirectoryItem],
+    args: &ListArgs,
+) -> Result<String, ZgError> {
+    let output = select_discovered(items, args)
+        .iter()
+        .map(|item| match args.long {
+            true => format!("{} - {}", item.id, item.title),
+            false => item.id.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(format!("{}\n", output))
+}
+
 fn initialize_services_table() -> Table {
     let mut t = Table::new();
     t.set_format(*format::consts::FORMAT_CLEAN);
-    t.set_titles(row![bu->"name", b->"title", b-> "category", b->"aliases", b->"versions", b->"default_version"]);
+    t.set_titles(row![bu->"name", b->"title", b-> "category", b->"aliases", b->"versions", b->"default_version", b->"status", b->"docs"]);
     t
 }
 
 /// Returns a string of all resources in the API.
-fn list_resources(api: &core::ZgApi, args: &ListArgs) -> Result<String, Box<dyn Error>> {
+fn list_resources(api: &core::ZgApi, args: &ListArgs) -> Result<String, ZgError> {
+    let _span = timing::span("render");
     let resources = &api.resources;
 
     if args.long {
         let mut table = initialize_resources_table();
 
-        // With --color option, find duplicated resource names to highlight
-        let duplicated_resources = if args.color {
+        // With colors, find duplicated resource names to highlight
+        let duplicated_resources = if color::enabled() {
             api.duplicated_resources()
         } else {
             Default::default()
@@ -183,15 +727,114 @@ fn list_resources(api: &core::ZgApi, args: &ListArgs) -> Result<String, Box<dyn
             table = sort_resources_table(&table, sort_field, args.reverse)?;
         }
 
-        table.print_tty(true)?;
+        color::print_table(&table)?;
 
-        Ok(String::new()) // Return empty string since --long format is printed directly by print_tty() above
+        Ok(String::new()) // Return empty string since --long format is printed directly by print_table() above
     } else {
         // Without --long option, print only the resource names in a tree (indented) format
         render_resources_tree(resources, "")
     }
 }
 
+/// A version of a service in the discovery directory (see `collect_versions`).
+#[derive(Debug, PartialEq, Serialize)]
+pub struct VersionInfo {
+    version: String,
+    preferred: bool,
+    supported: bool,
+    cached: bool,
+    title: String,
+    documentation_link: Option<String>,
+}
+
+/// Collects the versions of the service (name or alias) in the discovery directory, sorted by version.
+/// `is_cached` tells whether the API definition of the id (e.g., "container:v1") is cached locally.
+fn collect_versions(
+    service: &str,
+    items: &[discovery::DiscoveryDirectoryItem],
+    is_cached: impl Fn(&str) -> bool,
+) -> Vec<VersionInfo> {
+    let supported = supported_apis(true)
+        .into_iter()
+        .find(|api| api.name == service || api.aliases.iter().any(|a| a == service));
+    let name = supported.as_ref().map_or(service, |api| api.name.as_str());
+
+    let mut versions: Vec<VersionInfo> = items
+        .iter()
+        .filter(|item| item.name == name)
+        .map(|item| VersionInfo {
+            version: item.version.clone(),
+            preferred: item.preferred,
+            supported: supported
+                .as_ref()
+                .is_some_and(|api| api.versions.contains(&item.version)),
+            cached: is_cached(&item.id),
+            title: item.title.clone(),
+            documentation_link: item.documentation_link.clone(),
+        })
+        .collect();
+    versions.sort_by(|a, b| natural::cmp_versions(&a.version, &b.version));
+    versions
+}
+
+/// Prints the versions of the service in the discovery directory as a table.
+fn list_versions(
+    service: &str,
+    items: &[discovery::DiscoveryDirectoryItem],
+) -> Result<String, ZgError> {
+    let versions = collect_versions(service, items, |id| {
+        core::api_dir().join(core::api_file_name(id)).exists()
+    });
+    if versions.is_empty() {
+        return Err(format!(
+            "No versions of '{}' found in the discovery directory.",
+            service
+        )
+        .into());
+    }
+
+    let mark = |flag: bool| if flag { "yes" } else { "" };
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_CLEAN);
+    table.set_titles(
+        row![bu->"version", b->"preferred", b->"supported", b->"cached", b->"title", b->"docs"],
+    );
+    for v in versions {
+        table.add_row(row![
+            v.version,
+            mark(v.preferred),
+            mark(v.supported),
+            mark(v.cached),
+            v.title,
+            short_link(v.documentation_link.as_deref().unwrap_or_default())
+        ]);
+    }
+    color::print_table(&table)?;
+    Ok(String::new()) // Return empty string since the table is printed directly by print_table() above
+}
+
+/// Returns the resource names shared by multiple resources, with their full paths and the shortest paths to select each.
+/// The path marked "(default)" is picked by zg when only the name is given.
+fn list_duplicates(api: &core::ZgApi) -> Result<String, ZgError> {
+    let ambiguities = api.resource_ambiguities();
+    if ambiguities.is_empty() {
+        return Ok(format!("No duplicated resource names in {}.\n", api.id));
+    }
+
+    let mut output = String::new();
+    for ambiguity in &ambiguities {
+        writeln!(output, "{}", ambiguity.name)?;
+        for (path, unique_path) in ambiguity.paths.iter().zip(&ambiguity.unique_paths) {
+            let default = match ambiguity.default.as_ref() {
+                Some(default) if default == path => " (default)",
+                _ => "",
+            };
+            writeln!(output, "  {}  [{}]{}", path, unique_path, default)?;
+        }
+    }
+    Ok(output)
+}
+
 /// Initialize a table with headers to store resources.
 fn initialize_resources_table() -> Table {
     let mut t = Table::new();
@@ -213,20 +856,23 @@ fn add_resource_rows(
     duplicated_resources: &Vec<(String, Vec<String>)>,
 ) {
     for resource in resources {
-        let mut method_names: Vec<String> =
-            resource.methods.iter().map(|m| m.name.clone()).collect();
-
-        method_names.sort_by_key(|name| (name.len(), name.clone())); // Sort method names by length, then alphabetically
+        let mut methods: Vec<&core::ZgMethod> = resource.methods.iter().collect();
+        methods.sort_by_key(|m| (m.name.len(), m.name.clone())); // Sort method names by length, then alphabetically
+                                                                 // With the schema of the request body, as in the listing of methods (e.g., `create(Cluster)`)
+        let method_names: Vec<String> = methods
+            .iter()
+            .map(|m| match body_label(m) {
+                Some(body) => format!("{}({})", m.name, body),
+                None => m.name.clone(),
+            })
+            .collect();
 
         // Colorize the resource name if it has duplicates (i.e, same name but different paths)
-        let resource_name_cell = if duplicated_resources
+        let duplicated = duplicated_resources
             .iter()
-            .any(|(name, _)| name == &resource.name)
-        {
-            Cell::new(&resource.name).style_spec("Fb")
-        } else {
-            Cell::new(&resource.name)
-        };
+            .any(|(name, _)| name == &resource.name);
+        let resource_name_cell =
+            color::cell(&resource.name, duplicated.then_some(color::Style::Blue));
 
         // Calculate the depth of the resource path - starting from 0
         let depth_cell = Cell::new(
@@ -259,11 +905,7 @@ fn add_resource_rows(
 
 #[allow(clippy::wildcard_in_or_patterns)]
 /// Helper function to sort the resources in the table based on the --sort field.
-fn sort_resources_table(
-    table: &Table,
-    sort_field: &str,
-    reverse: bool,
-) -> Result<Table, Box<dyn Error>> {
+fn sort_resources_table(table: &Table, sort_field: &str, reverse: bool) -> Result<Table, ZgError> {
     let mut rows: Vec<Row> = table.row_iter().cloned().collect();
 
     // Internal helper function to fetch cell content and parse it into a specific type
@@ -277,24 +919,25 @@ fn sort_resources_table(
         match sort_field {
             "name" | "resource_name" => {
                 // Primary sort by resource name (column idx: 0), secondary by depth (column idx: 1), then by path (column idx: 2)
-                cell::<String>(a, 0)
-                    .cmp(&cell::<String>(b, 0))
+                natural::cmp_names(&cell::<String>(a, 0), &cell::<String>(b, 0))
                     .then_with(|| cell::<usize>(a, 1).cmp(&cell::<usize>(b, 1)))
-                    .then_with(|| cell::<String>(a, 2).cmp(&cell::<String>(b, 2)))
+                    .then_with(|| natural::cmp_names(&cell::<String>(a, 2), &cell::<String>(b, 2)))
             }
             "depth" => {
                 // Primary sort by depth (column idx: 1), secondary by resource name (column idx: 0)
                 cell::<usize>(a, 1)
                     .cmp(&cell::<usize>(b, 1))
-                    .then_with(|| cell::<String>(a, 0).cmp(&cell::<String>(b, 0)))
+                    .then_with(|| natural::cmp_names(&cell::<String>(a, 0), &cell::<String>(b, 0)))
             }
             "method" | "methods" | "method_count" => {
                 // Primary sort by method count (column idx: 3), secondary by path (column idx: 2)
                 cell::<usize>(a, 3)
                     .cmp(&cell::<usize>(b, 3))
-                    .then_with(|| cell::<String>(a, 2).cmp(&cell::<String>(b, 2)))
+                    .then_with(|| natural::cmp_names(&cell::<String>(a, 2), &cell::<String>(b, 2)))
             }
-            "path" | "resource_path" | _ => cell::<String>(a, 2).cmp(&cell::<String>(b, 2)), // fallback
+            "path" | "resource_path" | _ => {
+                natural::cmp_names(&cell::<String>(a, 2), &cell::<String>(b, 2))
+            } // fallback
         }
     });
 
@@ -311,10 +954,7 @@ fn sort_resources_table(
 }
 
 /// Helper function to render resources in a tree-like indented format (used without --long).
-fn render_resources_tree(
-    resources: &[core::ZgResource],
-    indent: &str,
-) -> Result<String, Box<dyn Error>> {
+fn render_resources_tree(resources: &[core::ZgResource], indent: &str) -> Result<String, ZgError> {
     let mut output = String::new();
     for resource in resources {
         writeln!(output, "{}{}", indent, resource.name)?;
@@ -328,27 +968,17 @@ fn render_resources_tree(
 
 #[rustfmt::skip]
 #[allow(clippy::wildcard_in_or_patterns)]
-/// Function to list methods of a specific resource.
-fn list_methods(
-    api: &core::ZgApi,
+/// Selects the methods of a specific resource (or the one given as METHOD), sorted by the options.
+fn select_methods<'a>(
+    api: &'a core::ZgApi,
     resource_path: &str,
     args: &ListArgs,
-) -> Result<String, Box<dyn Error>> {
-    let resource = core::find_resource(&api.id, &api.resources, resource_path)
-        .map_err(|e| format!("Error finding resource '{}': {}", resource_path, e))?;
+) -> Result<Vec<&'a core::ZgMethod>, ZgError> {
+    let resource = core::find_api_resource(api, resource_path, args.strict)?;
 
     let mut methods = if let Some(ref method_name) = args.method {
         // When you specify a method, only show that method; return Err if not found.
-        vec![resource
-            .methods
-            .iter()
-            .find(|m| m.name == *method_name)
-            .ok_or_else(|| {
-                format!(
-                    "Method '{}' not found for resource '{}'.",
-                    method_name, resource_path
-                )
-            })?]
+        vec![core::find_method_ref(resource, method_name)?]
     } else {
         // When no method is specified, list all methods in the resource.
         resource.methods.iter().collect::<Vec<_>>()
@@ -364,40 +994,53 @@ fn list_methods(
         };
         if args.reverse { sorted.reverse() } else { sorted }
     });
+    Ok(methods)
+}
+
+/// Function to list methods of a specific resource.
+fn list_methods(
+    api: &core::ZgApi,
+    resource_path: &str,
+    args: &ListArgs,
+) -> Result<String, ZgError> {
+    let _span = timing::span("render");
+    let methods = select_methods(api, resource_path, args)?;
 
     let output = if args.long {
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_CLEAN);
-        table.set_titles(row![bu->"method_name", b->"http_method", b->"path"]);
-        for method in methods {
-            let row = if args.color {
-                // Colorize based on the HTTP methods (POST: green, PUT/PATCH: blue, DELETE: red).
-                match method.http_method.as_str() {
-                    "POST" => row![Fg => method.name, method.http_method, method.flat_path],
-                    "PUT" | "PATCH" => row![Fb => method.name, method.http_method, method.flat_path],
-                    "DELETE" => row![Fr => method.name, method.http_method, method.flat_path],
-                    _ => row![method.name, method.http_method, method.flat_path],
-                }
-            } else {
-                row![method.name, method.http_method, method.flat_path]
-            };
-            table.add_row(row);
-        }
-        table.print_tty(true)?;
+        color::print_table(&methods_table(&methods))?;
         String::new() // Return empty string since --long format is printed directly here
     } else {
         // Without --long option, return only the method names
-        methods
-            .iter()
-            .fold(String::new(), |mut output, method| {
-                let _ = writeln!(output, "{}", method.name);
-                output
-            })
+        methods.iter().fold(String::new(), |mut output, method| {
+            let _ = writeln!(output, "{}", method.name);
+            output
+        })
     };
 
     Ok(output)
 }
 
+/// Builds the table of methods for --long, with the schema of the request body or `-` if the method takes none.
+fn methods_table(methods: &[&core::ZgMethod]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_CLEAN);
+    table.set_titles(row![bu->"method_name", b->"http_method", b->"path", b->"body"]);
+    for method in methods {
+        let body = body_label(method).unwrap_or_else(|| "-".to_string());
+        // Colorize based on the HTTP methods
+        let style = color::http_method_style(&method.http_method);
+        table.add_row(Row::new(
+            [&method.name, &method.http_method, &method.flat_path, &body]
+                .iter()
+                .map(|text| color::cell(text, style))
+                .collect(),
+        ));
+    }
+    table
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -437,6 +1080,140 @@ mod tests {
         }]
     }
 
+    #[test]
+    fn test_apply_config() {
+        let config: config::Config = toml::from_str(
+            r#"
+            color = true
+            [list]
+            long = true
+            sort = "name"
+            "#,
+        )
+        .unwrap();
+
+        let mut args = ListArgs::default();
+        args.apply_config(&config);
+        assert!(args.long && !args.reverse);
+        // `color` is resolved by the global color policy
+        assert!(!args.color_always);
+        assert_eq!(ListArgs::configured_color(&config), Some(true));
+        assert_eq!(args.sort.as_deref(), Some("name"));
+
+        // Command-line values take precedence
+        let mut args = ListArgs {
+            sort: Some("path".to_string()),
+            ..Default::default()
+        };
+        args.apply_config(&config);
+        assert_eq!(args.sort.as_deref(), Some("path"));
+
+        // `--no-long` turns off `long = true` of the config file
+        let mut args = ListArgs {
+            no_long: true,
+            ..Default::default()
+        };
+        args.apply_config(&config);
+        assert!(!args.long);
+    }
+
+    #[test]
+    fn test_list_discovered() {
+        let items = vec![
+            discovery::DiscoveryDirectoryItem {
+                id: "container:v1".to_string(),
+                name: "container".to_string(),
+                ..discovery::DiscoveryDirectoryItem::testdata()
+            },
+            discovery::DiscoveryDirectoryItem::testdata(),
+        ];
+        let output = list_discovered(&items, &ListArgs::default()).unwrap();
+        assert_eq!(output, "testapi:v1\n");
+    }
+
+    #[test]
+    fn test_collect_versions() {
+        // Excerpt of the discovery directory
+        let items: Vec<discovery::DiscoveryDirectoryItem> = serde_json::from_str(
+            r#"[
+                {"kind": "discovery#directoryItem", "id": "container:v1beta1", "name": "container", "version": "v1beta1",
+                 "title": "Kubernetes Engine API", "description": "Builds and manages container-based applications.",
+                 "discoveryRestUrl": "https://container.googleapis.com/$discovery/rest?version=v1beta1", "preferred": false},
+                {"kind": "discovery#directoryItem", "id": "container:v1", "name": "container", "version": "v1",
+                 "title": "Kubernetes Engine API", "description": "Builds and manages container-based applications.",
+                 "discoveryRestUrl": "https://container.googleapis.com/$discovery/rest?version=v1",
+                 "documentationLink": "https://cloud.google.com/kubernetes-engine/docs/", "preferred": true},
+                {"kind": "discovery#directoryItem", "id": "container:v2alpha1", "name": "container", "version": "v2alpha1",
+                 "title": "Kubernetes Engine API", "description": "Builds and manages container-based applications.",
+                 "discoveryRestUrl": "https://container.googleapis.com/$discovery/rest?version=v2alpha1", "preferred": false},
+                {"kind": "discovery#directoryItem", "id": "run:v2", "name": "run", "version": "v2",
+                 "title": "Cloud Run Admin API", "description": "Deploy and manage user provided container images.",
+                 "discoveryRestUrl": "https://run.googleapis.com/$discovery/rest?version=v2", "preferred": true}
+            ]"#,
+        )
+        .unwrap();
+
+        // Aliases are resolved, and the versions are in natural order (beta before GA)
+        let versions = collect_versions("gke", &items, |id| id == "container:v1");
+        assert_eq!(
+            versions,
+            vec![
+                VersionInfo {
+                    version: "v1beta1".to_string(),
+                    preferred: false,
+                    supported: true,
+                    cached: false,
+                    title: "Kubernetes Engine API".to_string(),
+                    documentation_link: None,
+                },
+                VersionInfo {
+                    version: "v1".to_string(),
+                    preferred: true,
+                    supported: true,
+                    cached: true,
+                    title: "Kubernetes Engine API".to_string(),
+                    documentation_link: Some(
+                        "https://cloud.google.com/kubernetes-engine/docs/".to_string()
+                    ),
+                },
+                VersionInfo {
+                    version: "v2alpha1".to_string(),
+                    preferred: false,
+                    supported: false,
+                    cached: false,
+                    title: "Kubernetes Engine API".to_string(),
+                    documentation_link: None,
+                },
+            ]
+        );
+
+        assert!(collect_versions("unknown", &items, |_| false).is_empty());
+    }
+
+    #[test]
+    fn test_docs_link() {
+        let item = discovery::DiscoveryDirectoryItem {
+            id: "container:v1".to_string(),
+            name: "container".to_string(),
+            documentation_link: Some(
+                "https://cloud.google.com/kubernetes-engine/docs/".to_string(),
+            ),
+            ..discovery::DiscoveryDirectoryItem::testdata()
+        };
+        let link = docs_link(&[item], "container", "v1");
+        assert_eq!(
+            short_link(link.as_deref().unwrap()),
+            "cloud.google.com/kubernetes-engine/docs"
+        );
+        assert_eq!(docs_link(&[], "container", "v1"), None);
+
+        assert_eq!(
+            short_link("https://developers.google.com/workspace/admin/directory/reference/rest"),
+            "developers.google.com/workspace/admin..."
+        );
+        assert_eq!(short_link(""), "");
+    }
+
     #[test]
     fn test_list_services() {
         let output = list_services(&ListArgs {
@@ -455,6 +1232,119 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_list_services_workspace() {
+        let output = list_services(&ListArgs::default()).unwrap();
+        assert!(!output.lines().any(|line| line == "drive"));
+
+        let output = list_services(&ListArgs {
+            workspace: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(output.lines().any(|line| line == "drive"));
+        assert!(output.lines().any(|line| line == "compute"));
+    }
+
+    #[test]
+    fn test_list_services_status() {
+        let output = list_services(&ListArgs {
+            all: true,
+            status: Some(ApiStatus::Deprecated),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(output.lines().any(|line| line == "pubsublite"));
+        assert!(!output.lines().any(|line| line == "compute"));
+    }
+
+    #[test]
+    fn test_pick_choices() {
+        let api = core::ZgApi {
+            resources: setup_resources(),
+            ..core::ZgApi::testdata()
+        };
+        // Resources without methods can't be chosen
+        assert_eq!(
+            resource_choices(&api),
+            vec!["projects.zones.clusters", "projects.locations.clusters"]
+        );
+
+        let resource = core::ZgResource {
+            methods: vec![
+                core::ZgMethod::testdata(),
+                core::ZgMethod {
+                    name: "delete".to_string(),
+                    http_method: "DELETE".to_string(),
+                    ..core::ZgMethod::testdata()
+                },
+            ],
+            ..core::ZgResource::testdata()
+        };
+        let (methods, labels) = method_choices(&resource);
+        assert_eq!(methods, vec!["list", "delete"]);
+        assert_eq!(
+            labels,
+            vec![
+                "list    GET     v1/projects/{projectsId}/testres/{testresId}",
+                "delete  DELETE  v1/projects/{projectsId}/testres/{testresId}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_picked() {
+        let picked = Picked {
+            service: "gke".to_string(),
+            resource: "projects.locations.clusters".to_string(),
+            method: "list".to_string(),
+            http_method: "GET".to_string(),
+        };
+        assert_eq!(
+            picked.commands(),
+            "zg desc gke projects.locations.clusters list\nzg ex gke projects.locations.clusters list\n"
+        );
+        assert!(picked.exec_args().is_ok());
+
+        // GET methods run without asking; the others only when confirmed
+        assert!(picked.confirm_exec(&mut "".as_bytes()).unwrap());
+        let picked = Picked {
+            method: "delete".to_string(),
+            http_method: "DELETE".to_string(),
+            ..picked
+        };
+        assert!(picked.confirm_exec(&mut "y\n".as_bytes()).unwrap());
+        assert!(!picked.confirm_exec(&mut "\n".as_bytes()).unwrap());
+        assert!(!picked.confirm_exec(&mut "".as_bytes()).unwrap());
+
+        // Only on a Unix-like system with a terminal
+        assert!(pick_unavailable("gke", true, true).is_none());
+        assert_eq!(
+            pick_unavailable("gke", true, false).unwrap(),
+            "--pick needs an interactive terminal, but stdin is not a terminal; list the resources with `zg ls gke` instead"
+        );
+        assert!(pick_unavailable("gke", false, true)
+            .unwrap()
+            .starts_with("--pick is only supported on Unix-like systems"));
+
+        // Only with a service, and not with the options of listing
+        let parse = |words: &[&str]| {
+            #[derive(Parser)]
+            struct Cli {
+                #[command(flatten)]
+                args: ListArgs,
+            }
+            Cli::try_parse_from(std::iter::once("zg").chain(words.iter().copied()))
+        };
+        assert!(parse(&["gke", "--pick"]).unwrap().args.picking());
+        assert!(parse(&["gke", "clusters", "--pick-exec"])
+            .unwrap()
+            .args
+            .pick_exec());
+        assert!(parse(&["--pick"]).is_err());
+        assert!(parse(&["gke", "--pick", "--long"]).is_err());
+    }
+
     #[test]
     fn test_list_resources() {
         let api = core::ZgApi {
@@ -474,6 +1364,59 @@ mod tests {
         assert_eq!(output, expected)
     }
 
+    #[test]
+    fn test_list_duplicates() {
+        let api = core::ZgApi {
+            id: "container:v1".to_string(),
+            resources: setup_resources(),
+            ..core::ZgApi::testdata()
+        };
+
+        let output = list_duplicates(&api).unwrap();
+        let expected = "clusters\n  container.projects.locations.clusters  [locations.clusters] (default)\n  container.projects.zones.clusters  [zones.clusters]\n";
+        assert_eq!(output, expected);
+
+        // The structured output of `--duplicates`
+        let json = serde_json::to_value(api.resource_ambiguities()).unwrap();
+        assert_eq!(json[0]["name"], "clusters");
+        assert_eq!(json[0]["unique_paths"][1], "zones.clusters");
+        assert_eq!(json[0]["default"], "container.projects.locations.clusters");
+    }
+
+    #[test]
+    fn test_resource_items_formats() {
+        let resources = setup_resources();
+        let items = resource_items(&resources[..1]);
+        assert_eq!(items[0].name, "projects");
+        assert_eq!(items[0].resources[0].resources[0].methods, vec!["list"]);
+
+        let json = crate::output::serialize(&items[0].resources[0], Format::Json).unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "name": "zones",
+  "path": "container.projects.zones",
+  "methods": [],
+  "resources": [
+    {
+      "name": "clusters",
+      "path": "container.projects.zones.clusters",
+      "methods": [
+        "list"
+      ],
+      "resources": []
+    }
+  ]
+}
+"#
+        );
+        let yaml = crate::output::serialize(&items[0].resources[0], Format::Yaml).unwrap();
+        assert_eq!(
+            yaml,
+            "name: zones\npath: container.projects.zones\nmethods: []\nresources:\n- name: clusters\n  path: container.projects.zones.clusters\n  methods:\n  - list\n  resources: []\n"
+        );
+    }
+
     #[test]
     fn test_add_resource_rows() {
         let mut table = initialize_resources_table();
@@ -493,6 +1436,23 @@ mod tests {
             table.get_row(0).unwrap().get_cell(0).unwrap().get_content(),
             "projects"
         );
+
+        let mut table = initialize_resources_table();
+        let create = core::ZgMethod {
+            name: "create".to_string(),
+            request_data_schema: Some(crate::discovery::Schema {
+                id: Some("Cluster".to_string()),
+                ..crate::discovery::Schema::testdata()
+            }),
+            ..core::ZgMethod::testdata()
+        };
+        let resources = vec![core::ZgResource {
+            methods: vec![create, core::ZgMethod::testdata()],
+            ..core::ZgResource::testdata()
+        }];
+        add_resource_rows(&mut table, &resources, &args, &vec![]);
+        let methods = table.get_row(0).unwrap().get_cell(4).unwrap().get_content();
+        assert_eq!(methods, "list, create(Cluster)");
     }
 
     #[test]
@@ -514,6 +1474,13 @@ mod tests {
 
         assert_eq!(depth_reverse_sorted_table.get_row(0).unwrap().get_cell(0).unwrap().get_content(), "instances");
         assert_eq!(depth_reverse_sorted_table.get_row(2).unwrap().get_cell(0).unwrap().get_content(), "projects");
+
+        // Digits in names are compared by value
+        let mut table = initialize_resources_table();
+        table.add_row(row!["instance10", "0", "compute.instance10", "0", ""]);
+        table.add_row(row!["instance2", "0", "compute.instance2", "0", ""]);
+        let path_sorted_table = sort_resources_table(&table, "path", false).expect("sort_resources_table by path failed");
+        assert_eq!(path_sorted_table.get_row(0).unwrap().get_cell(0).unwrap().get_content(), "instance2");
     }
 
     #[test]
@@ -543,4 +1510,51 @@ mod tests {
             output
         );
     }
+
+    #[test]
+    fn test_method_body() {
+        let methods = [
+            core::ZgMethod::testdata(),
+            core::ZgMethod {
+                name: "create".to_string(),
+                http_method: "POST".to_string(),
+                request_data_schema: Some(crate::discovery::Schema {
+                    id: Some("Cluster".to_string()),
+                    ..crate::discovery::Schema::testdata()
+                }),
+                ..core::ZgMethod::testdata()
+            },
+            core::ZgMethod {
+                name: "setLabels".to_string(),
+                http_method: "POST".to_string(),
+                request_data_schema: Some(crate::discovery::Schema {
+                    id: None,
+                    ..crate::discovery::Schema::testdata()
+                }),
+                ..core::ZgMethod::testdata()
+            },
+            // POST without a request in the definition
+            core::ZgMethod {
+                name: "start".to_string(),
+                http_method: "POST".to_string(),
+                ..core::ZgMethod::testdata()
+            },
+        ];
+        let methods: Vec<&core::ZgMethod> = methods.iter().collect();
+
+        let table = methods_table(&methods);
+        let bodies: Vec<String> = table
+            .row_iter()
+            .map(|row| row.get_cell(3).unwrap().get_content())
+            .collect();
+        assert_eq!(bodies, ["-", "Cluster", "(inline)", "-"]);
+
+        let items: Vec<serde_json::Value> = methods
+            .iter()
+            .map(|method| serde_json::to_value(MethodItem::from(*method)).unwrap())
+            .collect();
+        assert_eq!(items[0]["body"], serde_json::Value::Null);
+        assert_eq!(items[1]["body"], "Cluster");
+        assert_eq!(items[1]["name"], "create");
+    }
 }
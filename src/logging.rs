@@ -0,0 +1,305 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Format of the log records written to stderr, given with `--log-format` or the `ZG_LOG_FORMAT` environment variable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// env_logger's human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per record, with timestamp, level, target, message, and the fields of the record.
+    Json,
+}
+
+impl LogFormat {
+    /// Reads `ZG_LOG_FORMAT` (case-insensitive) for when `--log-format` is not given. Unknown values are ignored.
+    pub fn from_env(env: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        env("ZG_LOG_FORMAT").and_then(|value| LogFormat::from_str(&value, true).ok())
+    }
+}
+
+/// Fields attached to every JSON record after being set (e.g., `api_id` once the API is loaded).
+static CONTEXT: Mutex<Vec<(&'static str, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Fields of the record being logged by `log_kv!` on this thread. The logger formats records synchronously.
+    static FIELDS: RefCell<Vec<(&'static str, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets a contextual field included in the following JSON records, replacing the previous value of the key.
+pub fn set_context(key: &'static str, value: impl ToString) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.retain(|(k, _)| *k != key);
+        context.push((key, value.to_string()));
+    }
+}
+
+/// Runs `log` with the fields attached to the record (see `log_kv!`).
+pub fn with_fields(fields: Vec<(&'static str, String)>, log: impl FnOnce()) {
+    FIELDS.with(|current| *current.borrow_mut() = fields);
+    log();
+    FIELDS.with(|current| current.borrow_mut().clear());
+}
+
+/// Logs a message with key-value fields. The text format shows only the message as before,
+/// while the JSON format has the fields as separate keys, e.g.,
+/// `log_kv!(Level::Debug, { url = &url }, "Built URL: {}", &url)`.
+macro_rules! log_kv {
+    ($level:expr, { $($key:ident = $value:expr),* $(,)? }, $($arg:tt)+) => {
+        if log::log_enabled!($level) {
+            $crate::logging::with_fields(
+                vec![$((stringify!($key), $value.to_string())),*],
+                || log::log!($level, $($arg)+),
+            )
+        }
+    };
+}
+pub(crate) use log_kv;
+
+/// Whether credentials are logged as they are (`--debug-show-secrets`), for local troubleshooting.
+static SHOW_SECRETS: AtomicBool = AtomicBool::new(false);
+
+/// Query parameters carrying credentials, redacted by `redact_url`.
+const SECRET_PARAMS: [&str; 3] = ["key", "access_token", "api_key"];
+
+/// Headers carrying credentials, redacted by `redact_headers`.
+const SECRET_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "x-goog-api-key",
+    "cookie",
+];
+
+pub fn set_show_secrets(show: bool) {
+    SHOW_SECRETS.store(show, Ordering::Relaxed);
+}
+
+/// Masks a secret (access token, API key, etc.) to be logged, unless `--debug-show-secrets` is given.
+pub fn redact(secret: &str) -> String {
+    if SHOW_SECRETS.load(Ordering::Relaxed) {
+        return secret.to_string();
+    }
+    mask(secret)
+}
+
+/// Replaces all but the last 4 characters with `****`. Short secrets are masked entirely.
+fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    format!(
+        "****{}",
+        chars[chars.len() - 4..].iter().collect::<String>()
+    )
+}
+
+/// Redacts the values of the query parameters carrying credentials (e.g., `key=` of the Gemini API) in the URL.
+pub fn redact_url(url: &str) -> String {
+    let Some((base, rest)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match rest.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (rest, None),
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) if SECRET_PARAMS.contains(&name) => {
+                format!("{}={}", name, redact(value))
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    match fragment {
+        Some(fragment) => format!("{}?{}#{}", base, query, fragment),
+        None => format!("{}?{}", base, query),
+    }
+}
+
+/// Formats the headers to be logged, redacting the credentials (e.g., `Authorization: Bearer ****abcd`).
+pub fn redact_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let headers: Vec<String> = headers
+        .into_iter()
+        .map(|(name, value)| {
+            if !SECRET_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                return format!("{:?}: {:?}", name, value);
+            }
+            // Keep the scheme (e.g., Bearer) to tell how the request is authenticated
+            let value = match value.split_once(' ') {
+                Some((scheme, credentials)) => format!("{} {}", scheme, redact(credentials)),
+                None => redact(value),
+            };
+            format!("{:?}: {:?}", name, value)
+        })
+        .collect();
+    format!("{{{}}}", headers.join(", "))
+}
+
+/// Renders the record as a JSON line: timestamp, level, target, message, then the contextual fields and the fields of the record.
+fn json_line(timestamp: &str, record: &log::Record) -> String {
+    let mut object = Map::new();
+    object.insert("timestamp".to_string(), Value::from(timestamp));
+    object.insert("level".to_string(), Value::from(record.level().as_str()));
+    object.insert("target".to_string(), Value::from(record.target()));
+    object.insert(
+        "message".to_string(),
+        Value::from(record.args().to_string()),
+    );
+    if let Ok(context) = CONTEXT.lock() {
+        for (key, value) in context.iter() {
+            object.insert(key.to_string(), Value::from(value.as_str()));
+        }
+    }
+    FIELDS.with(|fields| {
+        for (key, value) in fields.borrow().iter() {
+            object.insert(key.to_string(), Value::from(value.as_str()));
+        }
+    });
+    Value::Object(object).to_string()
+}
+
+/// Returns the logger builder for the format, with the level filter from `RUST_LOG` or `default_level`.
+pub fn builder(format: LogFormat, default_level: &str) -> env_logger::Builder {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level));
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            writeln!(buf, "{}", json_line(&timestamp, record))
+        });
+    }
+    builder
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, Log};
+    use std::sync::Arc;
+
+    /// Collects the output of the logger.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let captured = Captured::default();
+        let logger = builder(LogFormat::Json, "debug")
+            .target(env_logger::Target::Pipe(Box::new(captured.clone())))
+            .build();
+
+        set_context("api_id", "container:v1");
+        let url = "https://container.googleapis.com/v1/projects/p \"quoted\"";
+        with_fields(vec![("url", url.to_string())], || {
+            logger.log(
+                &log::Record::builder()
+                    .level(Level::Debug)
+                    .target("zygen::exec")
+                    .args(format_args!("Built URL: {}", url))
+                    .build(),
+            )
+        });
+        logger.log(
+            &log::Record::builder()
+                .level(Level::Warn)
+                .target("zygen::discovery")
+                .args(format_args!("multi\nline"))
+                .build(),
+        );
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["level"], "DEBUG");
+        assert_eq!(records[0]["target"], "zygen::exec");
+        assert_eq!(records[0]["message"], format!("Built URL: {}", url));
+        assert_eq!(records[0]["url"], url);
+        assert_eq!(records[0]["api_id"], "container:v1");
+        assert!(records[0]["timestamp"].as_str().unwrap().ends_with('Z'));
+        // Fields are attached only to the record logged with them
+        assert_eq!(records[1]["message"], "multi\nline");
+        assert!(records[1].get("url").is_none());
+    }
+
+    const FAKE_TOKEN: &str = "ya29.FAKE-planted-token-0123456789wxyz";
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(mask(FAKE_TOKEN), "****wxyz");
+        assert_eq!(mask("short"), "****");
+        assert_eq!(mask(""), "****");
+
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models?pageSize=10&key={}#top",
+            FAKE_TOKEN
+        );
+        let redacted = redact_url(&url);
+        assert_eq!(
+            redacted,
+            "https://generativelanguage.googleapis.com/v1beta/models?pageSize=10&key=****wxyz#top"
+        );
+        let url = "https://container.googleapis.com/v1/projects/p?monkey=banana";
+        assert_eq!(redact_url(url), url);
+
+        let authorization = format!("Bearer {}", FAKE_TOKEN);
+        let headers = redact_headers([
+            ("authorization", authorization.as_str()),
+            ("X-Goog-Api-Key", FAKE_TOKEN),
+            ("content-type", "application/json"),
+        ]);
+        assert_eq!(
+            headers,
+            r#"{"authorization": "Bearer ****wxyz", "X-Goog-Api-Key": "****wxyz", "content-type": "application/json"}"#
+        );
+
+        // None of the formatted debug strings has the planted token
+        for formatted in [redacted, headers, format!("Auth: {}", redact(FAKE_TOKEN))] {
+            assert!(!formatted.contains(FAKE_TOKEN), "{}", formatted);
+            assert!(!formatted.contains("planted"), "{}", formatted);
+        }
+    }
+
+    #[test]
+    fn test_log_format_from_env() {
+        let env = |value: &'static str| move |_: &str| Some(value.to_string());
+        assert_eq!(LogFormat::from_env(env("JSON")), Some(LogFormat::Json));
+        assert_eq!(LogFormat::from_env(env("text")), Some(LogFormat::Text));
+        assert_eq!(LogFormat::from_env(env("xml")), None);
+        assert_eq!(LogFormat::from_env(|_| None), None);
+    }
+}
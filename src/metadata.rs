@@ -0,0 +1,184 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client of the metadata server on GCE, Cloud Run, GKE, and the like, used when gcloud is unavailable.
+
+use log::debug;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::error::ZgError;
+use super::timing;
+
+/// The metadata server's link-local address, which doesn't need DNS resolution.
+/// Override with the `GCE_METADATA_HOST` environment variable (HOST or HOST:PORT), as Google's client libraries do.
+const DEFAULT_HOST: &str = "169.254.169.254";
+
+/// Short enough not to make zg hang outside Google Cloud, where nothing answers at the address.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+const TOKEN_PATH: &str = "/computeMetadata/v1/instance/service-accounts/default/token";
+const PROJECT_ID_PATH: &str = "/computeMetadata/v1/project/project-id";
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+fn host() -> String {
+    std::env::var("GCE_METADATA_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string())
+}
+
+/// Returns an access token of the default service account attached to the instance.
+pub(crate) fn access_token() -> Result<String, ZgError> {
+    access_token_from(&host(), TIMEOUT)
+}
+
+/// Returns the id of the project the instance runs in.
+pub(crate) fn project_id() -> Result<String, ZgError> {
+    get(&host(), PROJECT_ID_PATH, TIMEOUT)
+}
+
+fn access_token_from(host: &str, timeout: Duration) -> Result<String, ZgError> {
+    let body = get(host, TOKEN_PATH, timeout)?;
+    let token: TokenResponse = serde_json::from_str(&body)?;
+    Ok(token.access_token)
+}
+
+/// Sends a GET request over plain HTTP/1.0 with the `Metadata-Flavor: Google` header, and returns the body.
+fn get(host: &str, path: &str, timeout: Duration) -> Result<String, ZgError> {
+    let _span = timing::span("metadata");
+    let network_error =
+        |e: std::io::Error| ZgError::Network(format!("metadata server {}: {}", host, e));
+    let host_port = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    let addr = host_port
+        .to_socket_addrs()
+        .map_err(network_error)?
+        .next()
+        .ok_or_else(|| ZgError::Network(format!("metadata server {}: no address", host)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(network_error)?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(network_error)?;
+    stream
+        .set_write_timeout(Some(timeout))
+        .map_err(network_error)?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: metadata.google.internal\r\nMetadata-Flavor: Google\r\n\r\n",
+        path
+    )
+    .map_err(network_error)?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(network_error)?;
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .unwrap_or(0);
+    debug!("GET {}{} => {}", host, path, status);
+    if status != 200 {
+        return Err(ZgError::HttpStatus {
+            status,
+            body: body.trim().to_string(),
+        });
+    }
+    Ok(body.trim().to_string())
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    /// Serves the responses to the requests in order, returning the address and the requests received.
+    fn fake_server(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn test_access_token_from_fake_server() {
+        let (addr, handle) = fake_server(vec![
+            "HTTP/1.1 200 OK\r\nMetadata-Flavor: Google\r\nContent-Type: application/json\r\n\r\n{\"access_token\":\"ya29.token\",\"expires_in\":3599,\"token_type\":\"Bearer\"}",
+            "HTTP/1.1 403 Forbidden\r\n\r\nMissing Metadata-Flavor:Google header.",
+        ]);
+        assert_eq!(access_token_from(&addr, TIMEOUT).unwrap(), "ya29.token");
+        match access_token_from(&addr, TIMEOUT) {
+            Err(ZgError::HttpStatus { status, .. }) => assert_eq!(status, 403),
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with(&format!("GET {} HTTP/1.0\r\n", TOKEN_PATH)));
+        assert!(requests[0].contains("Metadata-Flavor: Google\r\n"));
+    }
+
+    #[test]
+    fn test_project_id_from_fake_server() {
+        let (addr, handle) = fake_server(vec!["HTTP/1.1 200 OK\r\n\r\nmy-project-12345"]);
+        assert_eq!(
+            get(&addr, PROJECT_ID_PATH, TIMEOUT).unwrap(),
+            "my-project-12345"
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_unreachable_server() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        assert!(matches!(
+            access_token_from(&addr, TIMEOUT),
+            Err(ZgError::Network(_))
+        ));
+    }
+}
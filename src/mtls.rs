@@ -0,0 +1,492 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client certificates of `zg exec --mtls` for certificate-based access, where requests go to the mTLS hosts
+//! (`*.mtls.googleapis.com`) with the certificate of the device: `--client-cert` and `--client-key`, or the one
+//! the certificate provider of Endpoint Verification prints (as gcloud does with `context_aware/use_client_certificate`).
+
+use log::debug;
+use rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer, PrivateSec1KeyDer,
+};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use super::error::ZgError;
+use super::exec;
+use super::substitute;
+
+/// Metadata of Endpoint Verification in the home directory, which has the command printing the certificate and key.
+const CONTEXT_AWARE_METADATA: &str = ".secureConnect/context_aware_metadata.json";
+
+#[derive(Deserialize, Debug)]
+struct ContextAwareMetadata {
+    #[serde(default)]
+    cert_provider_command: Vec<String>,
+}
+
+/// A certificate chain (the leaf first) and its private key to present in the TLS handshake.
+#[derive(Debug)]
+pub(crate) struct ClientIdentity {
+    pub(crate) certs: Vec<CertificateDer<'static>>,
+    pub(crate) key: PrivateKeyDer<'static>,
+    /// Where the certificate comes from, in messages (e.g., the file path).
+    pub(crate) source: String,
+}
+
+impl ClientIdentity {
+    /// Loads the PEM files of --client-cert and --client-key (which may be the same file).
+    pub(crate) fn from_files(cert: &Path, key: &Path) -> Result<Self, ZgError> {
+        let read = |path: &Path, what: &str| {
+            fs::read_to_string(path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => ZgError::ClientCertificate(format!(
+                    "{} file {} not found",
+                    what,
+                    path.display()
+                )),
+                _ => ZgError::ClientCertificate(format!(
+                    "can't read the {} file {}: {}",
+                    what,
+                    path.display(),
+                    e
+                )),
+            })
+        };
+        let cert_pem = read(cert, "client certificate")?;
+        let key_pem = read(key, "client key")?;
+        Self::from_pem(&cert_pem, &key_pem, &cert.display().to_string())
+    }
+
+    /// Loads the certificate and key printed by the certificate provider of Endpoint Verification.
+    pub(crate) fn from_provider() -> Result<Self, ZgError> {
+        let path = metadata_path().ok_or_else(|| {
+            ZgError::ClientCertificate("can't find the home directory".to_string())
+        })?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ZgError::ClientCertificate(format!(
+                    "no client certificate; give --client-cert and --client-key, or set up Endpoint Verification, which writes {}",
+                    path.display()
+                )))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let metadata: ContextAwareMetadata = serde_json::from_str(&content)?;
+        let [program, args @ ..] = metadata.cert_provider_command.as_slice() else {
+            return Err(ZgError::ClientCertificate(format!(
+                "no cert_provider_command in {}",
+                path.display()
+            )));
+        };
+        debug!(
+            "Running the certificate provider: {:?}",
+            &metadata.cert_provider_command
+        );
+        let output = Command::new(program).args(args).output().map_err(|e| {
+            ZgError::ClientCertificate(format!(
+                "failed to run the certificate provider {}: {}",
+                program, e
+            ))
+        })?;
+        if !output.status.success() {
+            return Err(ZgError::ClientCertificate(format!(
+                "the certificate provider {} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let pem = String::from_utf8_lossy(&output.stdout);
+        Self::from_pem(&pem, &pem, program)
+    }
+
+    /// Reads the certificates (`CERTIFICATE` blocks) of `cert_pem` and the first private key of `key_pem`, either
+    /// PKCS#8 (`PRIVATE KEY`), PKCS#1 (`RSA PRIVATE KEY`), or SEC1 (`EC PRIVATE KEY`). Encrypted keys aren't supported.
+    fn from_pem(cert_pem: &str, key_pem: &str, source: &str) -> Result<Self, ZgError> {
+        let invalid = |what: &str| {
+            ZgError::ClientCertificate(format!("invalid PEM of the {} in {}", what, source))
+        };
+        let certs: Vec<CertificateDer<'static>> = pem_blocks(cert_pem)
+            .ok_or_else(|| invalid("certificate"))?
+            .into_iter()
+            .filter(|(label, _)| label == "CERTIFICATE")
+            .map(|(_, der)| CertificateDer::from(der))
+            .collect();
+        if certs.is_empty() {
+            return Err(ZgError::ClientCertificate(format!(
+                "no certificate (BEGIN CERTIFICATE) in {}",
+                source
+            )));
+        }
+
+        let mut key = None;
+        for (label, der) in pem_blocks(key_pem).ok_or_else(|| invalid("private key"))? {
+            key = match label.as_str() {
+                "PRIVATE KEY" => Some(PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(der))),
+                "RSA PRIVATE KEY" => Some(PrivateKeyDer::Pkcs1(PrivatePkcs1KeyDer::from(der))),
+                "EC PRIVATE KEY" => Some(PrivateKeyDer::Sec1(PrivateSec1KeyDer::from(der))),
+                "ENCRYPTED PRIVATE KEY" | "ENCRYPTED" => {
+                    return Err(ZgError::ClientCertificate(format!(
+                        "the private key for {} is encrypted, which isn't supported; decrypt it (e.g., `openssl pkey -in KEY -out DECRYPTED_KEY`)",
+                        source
+                    )))
+                }
+                _ => continue,
+            };
+            break;
+        }
+        let key = key.ok_or_else(|| {
+            ZgError::ClientCertificate(format!("no private key (BEGIN PRIVATE KEY) for {}", source))
+        })?;
+        Ok(ClientIdentity {
+            certs,
+            key,
+            source: source.to_string(),
+        })
+    }
+
+    /// Fails if the leaf certificate is expired or not yet valid at `now` (seconds since the Unix epoch), which
+    /// servers reject without telling why.
+    pub(crate) fn check_validity(&self, now: i64) -> Result<(), ZgError> {
+        let (not_before, not_after) = validity(&self.certs[0]).ok_or_else(|| {
+            ZgError::ClientCertificate(format!("can't read the certificate in {}", self.source))
+        })?;
+        if now > not_after {
+            return Err(ZgError::ClientCertificate(format!(
+                "the client certificate in {} expired on {}",
+                self.source,
+                substitute::rfc3339(not_after)
+            )));
+        }
+        if now < not_before {
+            return Err(ZgError::ClientCertificate(format!(
+                "the client certificate in {} is not valid until {}",
+                self.source,
+                substitute::rfc3339(not_before)
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn metadata_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(CONTEXT_AWARE_METADATA))
+}
+
+/// `context_aware/use_client_certificate` of gcloud, asked once per process.
+static GCLOUD_USE_CLIENT_CERTIFICATE: OnceLock<bool> = OnceLock::new();
+
+/// Whether gcloud is set to use the client certificate of Endpoint Verification, in which case requests go to the mTLS
+/// hosts without --mtls. gcloud is asked only when the metadata of Endpoint Verification exists.
+pub(crate) fn gcloud_uses_client_certificate() -> bool {
+    if !metadata_path().is_some_and(|path| path.exists()) {
+        return false;
+    }
+    *GCLOUD_USE_CLIENT_CERTIFICATE.get_or_init(|| {
+        exec::get_gcloud_config_value("context_aware/use_client_certificate")
+            .inspect_err(|e| debug!("{}", e))
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true"))
+    })
+}
+
+/// Turns a failed request into an error telling that the server rejected the client certificate, if the handshake
+/// failed with such an alert. None for other failures.
+pub(crate) fn rejection(
+    e: &(dyn std::error::Error + 'static),
+    identity: &ClientIdentity,
+) -> Option<ZgError> {
+    use rustls::AlertDescription::*;
+    let mut current = Some(e);
+    while let Some(error) = current {
+        // tokio-rustls wraps the TLS error in an io::Error, whose source() skips it
+        let tls_error = error.downcast_ref::<rustls::Error>().or_else(|| {
+            error
+                .downcast_ref::<std::io::Error>()
+                .and_then(std::io::Error::get_ref)
+                .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        });
+        if let Some(rustls::Error::AlertReceived(
+            alert @ (BadCertificate
+            | UnsupportedCertificate
+            | CertificateRevoked
+            | CertificateExpired
+            | CertificateUnknown
+            | CertificateRequired
+            | UnknownCA
+            | AccessDenied),
+        )) = tls_error
+        {
+            return Some(ZgError::ClientCertificate(format!(
+                "the server rejected the client certificate in {} ({:?}); check that it's issued for this device and not revoked",
+                identity.source, alert
+            )));
+        }
+        current = error.source();
+    }
+    None
+}
+
+// ---------------------- PEM and DER ----------------------------- //
+
+/// Splits the PEM text into the labels and decoded contents of its blocks. Legacy encrypted keys (with a `Proc-Type`
+/// header) are labeled `ENCRYPTED`. None if a block is broken.
+fn pem_blocks(pem: &str) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut blocks = Vec::new();
+    let mut lines = pem.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(label) = line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|rest| rest.strip_suffix("-----"))
+        else {
+            continue;
+        };
+        let end = format!("-----END {}-----", label);
+        let mut encoded = String::new();
+        let mut encrypted = false;
+        loop {
+            let line = lines.next()?;
+            if line == end {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                encrypted |= name == "Proc-Type" && value.contains("ENCRYPTED");
+                continue;
+            }
+            encoded.push_str(line);
+        }
+        let label = match encrypted {
+            true => "ENCRYPTED".to_string(),
+            false => label.to_string(),
+        };
+        blocks.push((label, base64_decode(&encoded)?));
+    }
+    Some(blocks)
+}
+
+/// Decodes standard base64 (with or without padding).
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in encoded
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+/// Splits the first DER element of `data` into its tag and contents, and returns them with the rest of `data`.
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&first, data) = data.split_first()?;
+    let (length, data) = match first {
+        0..=0x7f => (usize::from(first), data),
+        0x81..=0x84 => {
+            let count = usize::from(first & 0x7f);
+            let bytes = data.get(..count)?;
+            let length = bytes
+                .iter()
+                .fold(0usize, |length, byte| (length << 8) | usize::from(*byte));
+            (length, &data[count..])
+        }
+        _ => return None,
+    };
+    let contents = data.get(..length)?;
+    Some((tag, contents, &data[length..]))
+}
+
+/// The validity (notBefore and notAfter, in seconds since the Unix epoch) of the X.509 certificate.
+fn validity(cert: &[u8]) -> Option<(i64, i64)> {
+    const SEQUENCE: u8 = 0x30;
+    let (SEQUENCE, certificate, _) = der_element(cert)? else {
+        return None;
+    };
+    let (SEQUENCE, tbs, _) = der_element(certificate)? else {
+        return None;
+    };
+    let (tag, _, mut rest) = der_element(tbs)?;
+    if tag == 0xa0 {
+        // The explicit version; the serial number follows
+        rest = der_element(rest)?.2;
+    }
+    // The signature algorithm and the issuer, then the validity
+    let rest = der_element(der_element(rest)?.2)?.2;
+    let (SEQUENCE, validity, _) = der_element(rest)? else {
+        return None;
+    };
+    let (tag, not_before, rest) = der_element(validity)?;
+    let not_before = parse_time(tag, not_before)?;
+    let (tag, not_after, _) = der_element(rest)?;
+    Some((not_before, parse_time(tag, not_after)?))
+}
+
+/// Parses UTCTime (`YYMMDDHHMMSSZ`) or GeneralizedTime (`YYYYMMDDHHMMSSZ`) into seconds since the Unix epoch.
+fn parse_time(tag: u8, time: &[u8]) -> Option<i64> {
+    let time = std::str::from_utf8(time).ok()?.strip_suffix('Z')?;
+    let (year, rest) = match tag {
+        0x17 => {
+            let year: i64 = time.get(..2)?.parse().ok()?;
+            // RFC 5280: 50 or later is 19YY
+            (
+                if year >= 50 { 1900 + year } else { 2000 + year },
+                &time[2..],
+            )
+        }
+        0x18 => (time.get(..4)?.parse().ok()?, &time[4..]),
+        _ => return None,
+    };
+    if rest.len() != 10 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |i: usize| rest[i..i + 2].parse::<i64>().unwrap_or_default();
+    let days = days_from_civil(year, field(0), field(2));
+    Some(days * 86400 + field(4) * 3600 + field(6) * 60 + field(8))
+}
+
+/// Days since the Unix epoch of the civil date (http://howardhinnant.github.io/date_algorithms.html#days_from_civil).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = "tests/test_data/mtls/client.pem";
+    const KEY: &str = "tests/test_data/mtls/client.key";
+
+    #[test]
+    fn test_from_files() {
+        let identity = ClientIdentity::from_files(Path::new(CERT), Path::new(KEY)).unwrap();
+        assert_eq!(identity.certs.len(), 1);
+        assert!(matches!(identity.key, PrivateKeyDer::Pkcs8(_)));
+        assert_eq!(identity.source, CERT);
+        assert!(
+            exec::build_client_with_identity::<http_body_util::Empty<bytes::Bytes>>(Some(
+                &identity
+            ))
+            .is_ok()
+        );
+
+        let e = ClientIdentity::from_files(Path::new("missing.pem"), Path::new(KEY)).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Client certificate error: client certificate file missing.pem not found"
+        );
+        // The key file given as the certificate
+        let e = ClientIdentity::from_files(Path::new(KEY), Path::new(KEY)).unwrap_err();
+        assert!(e.to_string().contains("no certificate (BEGIN CERTIFICATE)"));
+    }
+
+    #[test]
+    fn test_from_pem() {
+        let cert = fs::read_to_string(CERT).unwrap();
+        let key = fs::read_to_string(KEY).unwrap();
+        // The certificate provider prints both
+        let both = format!("{}{}", cert, key);
+        let identity = ClientIdentity::from_pem(&both, &both, "provider").unwrap();
+        assert_eq!(identity.certs.len(), 1);
+
+        let sec1 = key.replace("PRIVATE KEY", "EC PRIVATE KEY");
+        let identity = ClientIdentity::from_pem(&cert, &sec1, "x").unwrap();
+        assert!(matches!(identity.key, PrivateKeyDer::Sec1(_)));
+
+        let e = ClientIdentity::from_pem(&cert, &cert, "x").unwrap_err();
+        assert!(e.to_string().contains("no private key"), "{}", e);
+        let encrypted = key.replace("PRIVATE KEY", "ENCRYPTED PRIVATE KEY");
+        let e = ClientIdentity::from_pem(&cert, &encrypted, "x").unwrap_err();
+        assert!(e.to_string().contains("encrypted"), "{}", e);
+        let broken = cert.replace("-----END CERTIFICATE-----", "");
+        assert!(ClientIdentity::from_pem(&broken, &key, "x").is_err());
+    }
+
+    #[test]
+    fn test_check_validity() {
+        let identity = ClientIdentity::from_files(Path::new(CERT), Path::new(KEY)).unwrap();
+        // 2025-01-01T00:00:00Z to 2035-01-01T00:00:00Z
+        assert_eq!(validity(&identity.certs[0]), Some((1735689600, 2051222400)));
+        assert!(identity.check_validity(1_800_000_000).is_ok());
+        let e = identity.check_validity(2051222401).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Client certificate error: the client certificate in tests/test_data/mtls/client.pem expired on 2035-01-01T00:00:00Z"
+        );
+        assert!(matches!(e, ZgError::ClientCertificate(_)));
+        let e = identity.check_validity(1_700_000_000).unwrap_err();
+        assert!(
+            e.to_string().contains("not valid until 2025-01-01"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time(0x17, b"700101000000Z"), Some(0));
+        assert_eq!(parse_time(0x17, b"000229120000Z"), Some(951825600));
+        assert_eq!(parse_time(0x18, b"20500101000000Z"), Some(2524608000));
+        assert_eq!(parse_time(0x17, b"7001010000Z"), None);
+        assert_eq!(parse_time(0x04, b"700101000000Z"), None);
+        assert_eq!(base64_decode("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(base64_decode("aGVs\nbG8"), Some(b"hello".to_vec()));
+        assert_eq!(base64_decode("a*"), None);
+    }
+
+    #[test]
+    fn test_rejection() {
+        let identity = ClientIdentity::from_files(Path::new(CERT), Path::new(KEY)).unwrap();
+        let rejected = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::AlertReceived(rustls::AlertDescription::BadCertificate),
+        );
+        let e = rejection(&rejected, &identity).unwrap();
+        assert!(
+            e.to_string()
+                .contains("the server rejected the client certificate in tests/test_data/mtls/client.pem (BadCertificate)"),
+            "{}",
+            e
+        );
+        let other = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::AlertReceived(rustls::AlertDescription::HandshakeFailure),
+        );
+        assert!(rejection(&other, &identity).is_none());
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(rejection(&refused, &identity).is_none());
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Natural ordering of API versions (`v1beta1` < `v1` < `v1p1beta1` < `v2`) and names (`instance2` < `instance10`).
+
+use std::cmp::Ordering;
+
+/// A run of digits or letters in a version.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Token<'a> {
+    Number(&'a str),
+    Word(&'a str),
+}
+
+impl Token<'_> {
+    /// The rank of the token against the end of the version (the GA release of what precedes it): stages are
+    /// before it, and further numbers (point releases) after it.
+    fn rank(&self) -> u8 {
+        match self {
+            Token::Word(word) => match word.to_ascii_lowercase().as_str() {
+                "alpha" | "a" => 0,
+                "beta" | "b" => 1,
+                _ => 2,
+            },
+            Token::Number(_) => 4,
+        }
+    }
+}
+
+const END_RANK: u8 = 3;
+
+/// Splits the version into runs of digits and letters, dropping the other characters (e.g., `.` and `_`). A `p`
+/// between numbers is a point (`v1p1beta1` is 1.1 beta 1), so it's dropped too.
+fn tokenize(version: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for run in runs(version) {
+        match run.chars().next() {
+            Some(c) if c.is_ascii_digit() => tokens.push(Token::Number(run)),
+            Some(c) if c.is_alphabetic() => tokens.push(Token::Word(run)),
+            _ => {}
+        }
+    }
+    let is_point = |i: usize| {
+        matches!(tokens[i], Token::Word(w) if w.eq_ignore_ascii_case("p"))
+            && i > 0
+            && matches!(tokens[i - 1], Token::Number(_))
+            && matches!(tokens.get(i + 1), Some(Token::Number(_)))
+    };
+    (0..tokens.len())
+        .filter(|&i| !is_point(i))
+        .map(|i| tokens[i])
+        .collect()
+}
+
+/// Splits the text into runs of digits, letters, and the other characters.
+fn runs(text: &str) -> Vec<&str> {
+    fn kind(c: char) -> u8 {
+        match c {
+            c if c.is_ascii_digit() => 0,
+            c if c.is_alphabetic() => 1,
+            _ => 2,
+        }
+    }
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        match chars.peek() {
+            Some(&(next, n)) if kind(n) != kind(c) => {
+                runs.push(&text[start..next]);
+                start = next;
+            }
+            Some(_) => {}
+            None => runs.push(&text[start..]),
+        }
+    }
+    runs
+}
+
+/// Compares digit runs by their values, without overflowing on long runs.
+fn cmp_numbers(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compares API versions: numbers by value, and at equal numbers, alpha < beta < GA (nothing after the numbers) <
+/// point releases. E.g., `v1alpha1` < `v1beta1` < `v1b3` < `v1` < `v1p1beta1` < `v2alpha` < `v2`.
+pub(crate) fn cmp_versions(a: &str, b: &str) -> Ordering {
+    let (ta, tb) = (tokenize(a), tokenize(b));
+    for i in 0..ta.len().max(tb.len()) {
+        let ordering = match (ta.get(i), tb.get(i)) {
+            (Some(Token::Number(x)), Some(Token::Number(y))) => cmp_numbers(x, y),
+            (Some(x), Some(y)) => x.rank().cmp(&y.rank()).then_with(|| match (x, y) {
+                // Unknown words (not a stage) in text order
+                (Token::Word(wx), Token::Word(wy)) if x.rank() == 2 => wx.cmp(wy),
+                _ => Ordering::Equal,
+            }),
+            (Some(x), None) => x.rank().cmp(&END_RANK),
+            (None, Some(y)) => END_RANK.cmp(&y.rank()),
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.cmp(b)
+}
+
+/// Compares the lists of versions element by element with `cmp_versions`.
+pub(crate) fn cmp_version_lists(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| cmp_versions(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Compares names with their digit runs by value, e.g., `instance2` < `instance10`.
+pub(crate) fn cmp_names(a: &str, b: &str) -> Ordering {
+    let (ra, rb) = (runs(a), runs(b));
+    for (x, y) in ra.iter().zip(&rb) {
+        let both_numbers = x.starts_with(|c: char| c.is_ascii_digit())
+            && y.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = match both_numbers {
+            true => cmp_numbers(x, y),
+            false => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    ra.len().cmp(&rb.len()).then_with(|| a.cmp(b))
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(items: &[&str], cmp: fn(&str, &str) -> Ordering) -> Vec<String> {
+        let mut items: Vec<String> = items.iter().map(|s| s.to_string()).collect();
+        items.sort_by(|a, b| cmp(a, b));
+        items
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("v1p1beta1"),
+            vec![
+                Token::Word("v"),
+                Token::Number("1"),
+                Token::Number("1"),
+                Token::Word("beta"),
+                Token::Number("1")
+            ]
+        );
+        assert_eq!(
+            tokenize("directory_v1.2"),
+            vec![
+                Token::Word("directory"),
+                Token::Word("v"),
+                Token::Number("1"),
+                Token::Number("2")
+            ]
+        );
+        // `p` not between numbers is a word
+        assert_eq!(tokenize("vp1"), vec![Token::Word("vp"), Token::Number("1")]);
+    }
+
+    #[test]
+    fn test_cmp_versions() {
+        assert_eq!(
+            sorted(
+                &[
+                    "v2",
+                    "v1p1beta1",
+                    "v1",
+                    "v1beta1",
+                    "v2alpha",
+                    "v1b3",
+                    "v1alpha1",
+                    "v1beta"
+                ],
+                cmp_versions
+            ),
+            vec![
+                "v1alpha1",
+                "v1beta",
+                "v1beta1",
+                "v1b3",
+                "v1",
+                "v1p1beta1",
+                "v2alpha",
+                "v2"
+            ]
+        );
+        assert_eq!(cmp_versions("v10", "v9"), Ordering::Greater);
+        assert_eq!(cmp_versions("v1p2beta1", "v1p10beta1"), Ordering::Less);
+        assert_eq!(cmp_versions("v1.1", "v1p1"), Ordering::Less); // equal in tokens, tie-broken by the text
+        assert_eq!(cmp_versions("v1", "v1.0"), Ordering::Less);
+        assert_eq!(cmp_versions("v3beta1", "v2"), Ordering::Greater);
+        assert_eq!(cmp_versions("v1beta2", "v1beta10"), Ordering::Less);
+        // Unknown words are before GA, in text order
+        assert_eq!(
+            sorted(&["v1", "v1main", "v1internal", "v1beta"], cmp_versions),
+            vec!["v1beta", "v1internal", "v1main", "v1"]
+        );
+        // Versions without numbers
+        assert_eq!(
+            sorted(&["beta", "alpha", "v1"], cmp_versions),
+            vec!["alpha", "beta", "v1"]
+        );
+        assert_eq!(cmp_versions("v1", "v1"), Ordering::Equal);
+        // Long numbers don't overflow
+        assert_eq!(
+            cmp_versions("v99999999999999999999999", "v100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_cmp_version_lists() {
+        let list =
+            |versions: &[&str]| -> Vec<String> { versions.iter().map(|v| v.to_string()).collect() };
+        assert_eq!(
+            cmp_version_lists(&list(&["v1beta1", "v1"]), &list(&["v1"])),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp_version_lists(&list(&["v1"]), &list(&["v1", "v1beta1"])),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp_version_lists(&list(&["v2"]), &list(&["v10"])),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_cmp_names() {
+        assert_eq!(
+            sorted(
+                &[
+                    "instance10",
+                    "instance2",
+                    "instance1",
+                    "instances",
+                    "instance"
+                ],
+                cmp_names
+            ),
+            vec![
+                "instance",
+                "instance1",
+                "instance2",
+                "instance10",
+                "instances"
+            ]
+        );
+        assert_eq!(
+            sorted(
+                &[
+                    "projects.locations.v10",
+                    "projects.locations.v2",
+                    "projects.jobs"
+                ],
+                cmp_names
+            ),
+            vec![
+                "projects.jobs",
+                "projects.locations.v2",
+                "projects.locations.v10"
+            ]
+        );
+        assert_eq!(cmp_names("a01", "a1"), Ordering::Less); // equal values, tie-broken by the text
+        assert_eq!(cmp_names("clusters", "clusters"), Ordering::Equal);
+    }
+}
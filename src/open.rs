@@ -0,0 +1,84 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::Args;
+use log::warn;
+use std::process::Command;
+
+use super::config;
+use super::core;
+use super::desc;
+use super::discovery;
+use super::error::ZgError;
+
+#[derive(Args, Debug)]
+pub struct OpenArgs {
+    #[command(flatten)]
+    target: desc::TargetArgs,
+
+    /// Print the URL without opening it in a browser.
+    #[arg(long)]
+    print_only: bool,
+}
+
+impl OpenArgs {
+    /// Fills the options not given on the command line with the values in the config file.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.target.apply_config(config);
+    }
+}
+
+/// Main function to open the API reference of services, resources, or methods (the link shown by `zg desc`).
+pub async fn main(args: &OpenArgs, standalone_api_key: Option<String>) -> Result<(), ZgError> {
+    let api = core::load_api_file(args.target.service(), standalone_api_key).await?;
+    let target = desc::resolve_target(&api, &args.target)?;
+    let directory_items = discovery::cached_discovered_apis()
+        .map(|directory| directory.items)
+        .unwrap_or_default();
+    let url = desc::documentation_link(&api, &target, &directory_items)
+        .ok_or_else(|| format!("No API reference found for {}", api.id))?;
+
+    if args.print_only {
+        println!("{}", url);
+        return Ok(());
+    }
+    match opener(&url).status() {
+        Ok(status) if status.success() => eprintln!("Opened {}", url),
+        result => {
+            let reason = match result {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            warn!("Failed to open a browser ({}); open the URL below.", reason);
+            println!("{}", url);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the command opening the URL with the platform's default browser.
+fn opener(url: &str) -> Command {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // The empty argument is the window title; otherwise `start` takes the quoted URL as the title
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(url);
+    command
+}
@@ -0,0 +1,280 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::ValueEnum;
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::error::ZgError;
+
+/// Output format of the commands, given with the global `--format` flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable output rendered by each command (tables, trees, and `key: value` lines).
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    /// Newline-delimited JSON: one compact JSON value per line, e.g., an item of a list response (see `ndjson_records`).
+    Ndjson,
+}
+
+/// Returns the format given on the command line, or `default_output` in the config file. An unknown default is warned and ignored.
+pub fn resolve(given: Option<Format>, default_output: Option<&str>) -> Format {
+    given
+        .or_else(|| {
+            let default_output = default_output?;
+            Format::from_str(default_output, true)
+                .map_err(|_| {
+                    warn!(
+                        "Ignoring default_output = '{}' in the config file; expected text, json, yaml, or ndjson.",
+                        default_output
+                    )
+                })
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Serializes the result of a command. `Text` is rendered by each command instead, so it's treated as JSON here.
+pub fn serialize<T: Serialize + ?Sized>(value: &T, format: Format) -> Result<String, ZgError> {
+    match format {
+        Format::Yaml => Ok(serde_yaml::to_string(value)?),
+        Format::Text | Format::Json => Ok(format!("{}\n", serde_json::to_string_pretty(value)?)),
+        Format::Ndjson => {
+            let mut lines = Vec::new();
+            write_ndjson(&mut lines, &serde_json::to_value(value)?)?;
+            Ok(String::from_utf8(lines)?)
+        }
+    }
+}
+
+/// Returns the name and items of the only array field of an object, i.e., the list of a list response
+/// (e.g., `clusters`; `unreachable` locations of AIP-217 list responses don't count).
+pub fn list_field(value: &Value) -> Option<(&str, &Vec<Value>)> {
+    let mut arrays = value
+        .as_object()?
+        .iter()
+        .filter(|(key, _)| *key != "unreachable")
+        .filter_map(|(key, value)| Some((key.as_str(), value.as_array()?)));
+    match (arrays.next(), arrays.next()) {
+        (Some(list), None) => Some(list),
+        _ => None,
+    }
+}
+
+/// Splits the value into NDJSON records: the items of an array, or of the list field of an object (see `list_field`).
+/// Other values are a record by themselves.
+pub fn ndjson_records(value: &Value) -> Vec<&Value> {
+    match (value, list_field(value)) {
+        (Value::Array(items), _) | (_, Some((_, items))) => items.iter().collect(),
+        _ => vec![value],
+    }
+}
+
+/// Writes the records of the value (see `ndjson_records`) as compact JSON, one per line.
+pub fn write_ndjson(out: &mut impl Write, value: &Value) -> Result<(), ZgError> {
+    for record in ndjson_records(value) {
+        serde_json::to_writer(&mut *out, record)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Returns the file names to write the items of a list to (`zg exec --split-output`): the value of the key in each item
+/// with slashes converted to `__` (e.g., `projects__p__secrets__s1.json`), with `-N` appended to the duplicates.
+/// Items without the key (or with an empty or non-scalar value) are named `unnamed-N.json`.
+pub fn split_file_names(items: &[Value], key: &str) -> Vec<Option<String>> {
+    items
+        .iter()
+        .map(|item| {
+            let name = match item.get(key)? {
+                Value::String(name) => name.clone(),
+                Value::Number(number) => number.to_string(),
+                _ => return None,
+            };
+            let name = name.replace(['/', '\\'], "__");
+            (!name.is_empty() && name != "." && name != "..").then_some(name)
+        })
+        .collect()
+}
+
+/// Writes each item of the list response (an array, or the list field of an object) to its own pretty-printed JSON file
+/// in the directory, named by `split_file_names`. Returns the paths written to, in the order of the items.
+pub fn write_split(dir: &Path, value: &Value, key: &str) -> Result<Vec<PathBuf>, ZgError> {
+    let items = match (value, list_field(value)) {
+        (Value::Array(items), _) | (_, Some((_, items))) => items,
+        _ => return Err(ZgError::Other(
+            "--split-output needs a list response (an array, or an object with one array field)"
+                .to_string(),
+        )),
+    };
+    std::fs::create_dir_all(dir)?;
+    let mut used = HashSet::new();
+    let mut unnamed = 0;
+    let mut paths = Vec::with_capacity(items.len());
+    for (item, name) in items.iter().zip(split_file_names(items, key)) {
+        let base = name.unwrap_or_else(|| {
+            unnamed += 1;
+            format!("unnamed-{}", unnamed)
+        });
+        let mut file_name = format!("{}.json", base);
+        let mut index = 0;
+        while !used.insert(file_name.clone()) {
+            index += 1;
+            file_name = format!("{}-{}.json", base, index);
+        }
+        let path = dir.join(file_name);
+        std::fs::write(&path, format!("{}\n", serde_json::to_string_pretty(item)?))?;
+        paths.push(path);
+    }
+    if unnamed > 0 {
+        warn!(
+            "{} item(s) have no '{}'; written as unnamed-N.json. Choose another key with --split-key.",
+            unnamed, key
+        );
+    }
+    Ok(paths)
+}
+
+/// Prints the result of a command to stdout in the format.
+pub fn print<T: Serialize + ?Sized>(value: &T, format: Format) -> Result<(), ZgError> {
+    print!("{}", serialize(value, format)?);
+    Ok(())
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(None, None), Format::Text);
+        assert_eq!(resolve(None, Some("YAML")), Format::Yaml);
+        assert_eq!(resolve(Some(Format::Text), Some("json")), Format::Text);
+        assert_eq!(resolve(None, Some("table")), Format::Text);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let value = json!({"name": "clusters", "methods": ["get", "list"]});
+        assert_eq!(
+            serialize(&value, Format::Json).unwrap(),
+            "{\n  \"methods\": [\n    \"get\",\n    \"list\"\n  ],\n  \"name\": \"clusters\"\n}\n"
+        );
+        assert_eq!(
+            serialize(&value, Format::Yaml).unwrap(),
+            "methods:\n- get\n- list\nname: clusters\n"
+        );
+        assert_eq!(
+            serialize(&value, Format::Ndjson).unwrap(),
+            "\"get\"\n\"list\"\n"
+        );
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let response = json!({
+            "clusters": [
+                {"name": "prod", "nodePools": [{"name": "default-pool"}]},
+                {"name": "dev\nline", "status": "RUNNING"},
+                {"name": "test"}
+            ],
+            "nextPageToken": "abc",
+            "unreachable": ["us-east1"]
+        });
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &response).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (line, item) in lines.iter().zip(response["clusters"].as_array().unwrap()) {
+            assert_eq!(&serde_json::from_str::<Value>(line).unwrap(), item);
+        }
+
+        // Without a single top-level array, the response is a line by itself
+        let operation = json!({"name": "operation-1", "done": false});
+        let two_arrays = json!({"clusters": [1, 2], "missingZones": ["us-central1-a"]});
+        for value in [operation, two_arrays, json!("text")] {
+            assert_eq!(ndjson_records(&value), vec![&value]);
+        }
+        assert_eq!(ndjson_records(&json!([1, 2])).len(), 2);
+        assert_eq!(
+            list_field(&response).map(|(name, items)| (name, items.len())),
+            Some(("clusters", 3))
+        );
+        assert_eq!(list_field(&json!({"a": [1], "b": [2]})), None);
+        assert_eq!(list_field(&json!([1, 2])), None);
+        assert!(ndjson_records(&json!({"clusters": []})).is_empty());
+    }
+
+    #[test]
+    fn test_write_split() {
+        let dir = std::env::temp_dir().join(format!("zg-split-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let response = json!({
+            "secrets": [
+                {"name": "projects/p/secrets/db-password", "replication": {"automatic": {}}},
+                {"name": "projects/p/secrets/api-key"},
+                {"name": "projects/p/secrets/db-password", "etag": "duplicate"},
+                {"id": 42},
+                {"name": ""},
+                {"name": "projects/p/secrets/db-password-1"}
+            ],
+            "nextPageToken": "abc"
+        });
+        let paths = write_split(&dir, &response, "name").unwrap();
+        let names: Vec<_> = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "projects__p__secrets__db-password.json",
+                "projects__p__secrets__api-key.json",
+                "projects__p__secrets__db-password-1.json",
+                "unnamed-1.json",
+                "unnamed-2.json",
+                // Not to overwrite the duplicate above
+                "projects__p__secrets__db-password-1-1.json",
+            ]
+        );
+        let written: Value =
+            serde_json::from_str(&std::fs::read_to_string(&paths[2]).unwrap()).unwrap();
+        assert_eq!(written["etag"], "duplicate");
+
+        // Another key, and a top-level array
+        assert_eq!(
+            split_file_names(
+                &[json!({"id": 42}), json!({"id": "a/b"}), json!({"id": [1]})],
+                "id"
+            ),
+            vec![Some("42".to_string()), Some("a__b".to_string()), None]
+        );
+
+        let e = write_split(&dir, &json!({"name": "c1"}), "name").unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("--split-output needs a list response"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
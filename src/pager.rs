@@ -0,0 +1,131 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Paging of long outputs through `$PAGER` (`less -FRX` by default) when stdout is a terminal.
+
+use log::debug;
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use super::error::ZgError;
+
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// Prints the content to stdout, through the pager if `enabled`, stdout is a terminal, and the content is taller than
+/// the terminal. Falls back to printing it directly when the pager can't be started.
+pub(crate) fn print(content: &str, enabled: bool) -> Result<(), ZgError> {
+    let is_terminal = std::io::stdout().is_terminal();
+    let command = pager_command(std::env::var("PAGER").ok());
+    if let Some(command) = command.filter(|_| {
+        should_page(
+            enabled && is_terminal,
+            content.lines().count(),
+            terminal_height,
+        )
+    }) {
+        match page(&command, content) {
+            Ok(()) => return Ok(()),
+            Err(e) => debug!("Failed to start the pager {:?}: {}", command, e),
+        }
+    }
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(content.as_bytes())?;
+    Ok(stdout.flush()?)
+}
+
+/// Whether to page the content of the number of lines: only on a terminal shorter than the content.
+/// The height is asked only on a terminal, and the content isn't paged when it's unknown.
+fn should_page(
+    on_terminal: bool,
+    lines: usize,
+    terminal_height: impl FnOnce() -> Option<usize>,
+) -> bool {
+    on_terminal && terminal_height().is_some_and(|height| lines >= height)
+}
+
+/// The pager command from `$PAGER`, or `less -FRX` if it's unset. An empty `$PAGER` disables paging.
+fn pager_command(pager: Option<String>) -> Option<Vec<String>> {
+    let pager = pager.unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    shlex::split(&pager).filter(|words| !words.is_empty())
+}
+
+fn page(command: &[String], content: &str) -> std::io::Result<()> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may quit before reading everything (e.g., `q` in less)
+        let _ = stdin.write_all(content.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// The number of rows of the terminal: `$LINES`, or `stty size` of the controlling terminal.
+fn terminal_height() -> Option<usize> {
+    if let Some(lines) = std::env::var("LINES").ok().and_then(|l| l.parse().ok()) {
+        return Some(lines);
+    }
+    terminal_size().map(|(rows, _)| rows)
+}
+
+/// The rows and columns of the controlling terminal (`stty size`). None on systems without `stty` (e.g., Windows).
+pub(crate) fn terminal_size() -> Option<(usize, usize)> {
+    if !cfg!(unix) {
+        return None;
+    }
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    let output = Command::new("stty")
+        .arg("size")
+        .stdin(tty)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut size = output.split_whitespace().map(|n| n.parse().ok());
+    Some((size.next()??, size.next()??))
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_page() {
+        assert!(should_page(true, 100, || Some(40)));
+        assert!(should_page(true, 40, || Some(40)));
+        assert!(!should_page(true, 39, || Some(40)));
+        // Unknown height
+        assert!(!should_page(true, 100, || None));
+        // Not on a terminal (or --no-pager): the height isn't asked
+        assert!(!should_page(false, 100, || panic!("not asked")));
+    }
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(
+            pager_command(None),
+            Some(vec!["less".to_string(), "-FRX".to_string()])
+        );
+        assert_eq!(
+            pager_command(Some("most -s".to_string())),
+            Some(vec!["most".to_string(), "-s".to_string()])
+        );
+        assert_eq!(pager_command(Some("".to_string())), None);
+        assert_eq!(pager_command(Some("'unbalanced".to_string())), None);
+    }
+}
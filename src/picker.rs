@@ -0,0 +1,500 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An inline fuzzy finder for `zg ls --pick`: the list is filtered as the query is typed, and an item is chosen with the
+//! arrow keys and Enter. The matching and the navigation (`Picker`) are separate from the terminal (`pick`).
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use super::color;
+use super::error::ZgError;
+use super::pager;
+
+/// The number of items shown at most, below the query.
+const MAX_HEIGHT: usize = 12;
+
+/// Scores how well the query matches the text, or None if it doesn't: the characters of the query have to appear in
+/// the text in order. Consecutive characters and characters at the start of words (after `.`, `/`, `_`, `-`, spaces,
+/// or at a camelCase hump) score higher, and gaps between them score lower. Case-insensitive unless the query has an
+/// uppercase character.
+pub(crate) fn score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let case_sensitive = query.iter().any(|c| c.is_uppercase());
+    let fold = |c: char| match case_sensitive {
+        true => c,
+        false => c.to_ascii_lowercase(),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    // The best of the matches starting at each occurrence of the first character
+    (0..chars.len())
+        .filter(|&start| fold(chars[start]) == fold(query[0]))
+        .filter_map(|start| score_from(&query, &chars, start, fold))
+        .max()
+}
+
+fn score_from(
+    query: &[char],
+    chars: &[char],
+    start: usize,
+    fold: impl Fn(char) -> char,
+) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut position = start;
+    for &q in query {
+        let found = (position..chars.len()).find(|&i| fold(chars[i]) == fold(q))?;
+        score += 16;
+        match previous {
+            Some(previous) if found == previous + 1 => score += 15,
+            Some(previous) => score -= (found - previous - 1).min(10) as i64,
+            None => {}
+        }
+        if is_word_start(chars, found) {
+            score += 10;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    match i.checked_sub(1).map(|p| chars[p]) {
+        None => true,
+        Some('.' | '/' | '_' | '-' | ' ' | ':') => true,
+        Some(previous) => previous.is_lowercase() && chars[i].is_uppercase(),
+    }
+}
+
+/// Keys of the picker, decoded from the bytes read from the terminal (see `decode`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Key {
+    Char(char),
+    Backspace,
+    /// Ctrl-U
+    ClearQuery,
+    /// Arrow up or Ctrl-P
+    Up,
+    /// Arrow down or Ctrl-N
+    Down,
+    Enter,
+    /// Escape or Ctrl-C
+    Cancel,
+}
+
+/// Decodes the bytes read from the terminal in raw mode into keys. Unknown control characters and escape sequences are
+/// dropped.
+pub(crate) fn decode(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' => match chars.peek() {
+                // A lone escape
+                None => Some(Key::Cancel),
+                Some('[' | 'O') => {
+                    chars.next();
+                    // The parameters of a sequence, up to its final letter (e.g., `\x1b[1;5A`)
+                    let mut last = None;
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() || c == '~' {
+                            last = Some(c);
+                            break;
+                        }
+                    }
+                    match last {
+                        Some('A') => Some(Key::Up),
+                        Some('B') => Some(Key::Down),
+                        _ => None,
+                    }
+                }
+                Some(_) => None,
+            },
+            '\r' | '\n' => Some(Key::Enter),
+            '\x7f' | '\x08' => Some(Key::Backspace),
+            '\x03' => Some(Key::Cancel),
+            '\x10' => Some(Key::Up),
+            '\x0e' => Some(Key::Down),
+            '\x15' => Some(Key::ClearQuery),
+            c if c.is_control() => None,
+            c => Some(Key::Char(c)),
+        };
+        keys.extend(key);
+    }
+    keys
+}
+
+/// What to do after a key.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Action {
+    Continue,
+    /// The index of the chosen item.
+    Select(usize),
+    Cancel,
+}
+
+/// The state of the picker: the query, the items matching it (best first), the selected one, and the window of `height`
+/// items shown.
+pub(crate) struct Picker {
+    items: Vec<String>,
+    query: String,
+    matches: Vec<usize>,
+    selected: usize,
+    offset: usize,
+    height: usize,
+}
+
+impl Picker {
+    pub(crate) fn new(items: Vec<String>, height: usize) -> Picker {
+        let mut picker = Picker {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            offset: 0,
+            height: height.max(1),
+        };
+        picker.filter();
+        picker
+    }
+
+    pub(crate) fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The number of the items matching the query, and of all the items.
+    pub(crate) fn counts(&self) -> (usize, usize) {
+        (self.matches.len(), self.items.len())
+    }
+
+    /// The items shown, with whether each is selected.
+    pub(crate) fn visible(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.matches
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|(i, &item)| (self.items[item].as_str(), i == self.selected))
+    }
+
+    pub(crate) fn handle(&mut self, key: Key) -> Action {
+        match key {
+            Key::Char(c) => {
+                self.query.push(c);
+                self.filter();
+            }
+            Key::Backspace => {
+                if self.query.pop().is_some() {
+                    self.filter();
+                }
+            }
+            Key::ClearQuery => {
+                self.query.clear();
+                self.filter();
+            }
+            Key::Up => self.select(self.selected.saturating_sub(1)),
+            Key::Down => self.select(self.selected + 1),
+            Key::Enter => {
+                if let Some(&item) = self.matches.get(self.selected) {
+                    return Action::Select(item);
+                }
+            }
+            Key::Cancel => return Action::Cancel,
+        }
+        Action::Continue
+    }
+
+    /// Moves the selection within the matches, scrolling the window to show it.
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.matches.len().saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.height {
+            self.offset = self.selected + 1 - self.height;
+        }
+    }
+
+    /// Finds the items matching the query, best first, and shorter ones first among the equally good. The selection goes
+    /// back to the best.
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| score(&self.query, item).map(|score| (score, i)))
+            .collect();
+        scored.sort_by_key(|&(score, i)| (-score, self.items[i].len()));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+}
+
+/// Lets the user choose one of the items on the terminal, showing `prompt` before the query. Returns the index of the
+/// chosen item, or None if it's canceled (Escape or Ctrl-C). The list is drawn on the terminal (not stdout) and cleared
+/// before returning.
+pub(crate) fn pick(prompt: &str, items: Vec<String>) -> Result<Option<usize>, ZgError> {
+    let mut tty = File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("Failed to open the terminal: {}", e))?;
+    // Some terminals (e.g., a pseudo terminal of `script`) report 0x0
+    let (rows, columns) = pager::terminal_size()
+        .filter(|&(rows, columns)| rows > 0 && columns > 0)
+        .unwrap_or((24, 80));
+    let height = items.len().min(MAX_HEIGHT).min(rows.saturating_sub(2));
+    let mut picker = Picker::new(items, height);
+
+    let _raw = RawMode::enable()?;
+    let result = (|| {
+        let mut buffer = [0; 64];
+        loop {
+            draw(&mut tty, prompt, &picker, columns)?;
+            let n = tty.read(&mut buffer)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            for key in decode(&buffer[..n]) {
+                match picker.handle(key) {
+                    Action::Continue => {}
+                    Action::Select(item) => return Ok(Some(item)),
+                    Action::Cancel => return Ok(None),
+                }
+            }
+        }
+    })();
+    // Clear the list
+    write!(tty, "\r\x1b[J")?;
+    tty.flush()?;
+    result
+}
+
+/// Draws the query, the items, and the counts from the current line, leaving the cursor after the query.
+fn draw(tty: &mut File, prompt: &str, picker: &Picker, columns: usize) -> Result<(), ZgError> {
+    let fit = |line: String| -> String { line.chars().take(columns.saturating_sub(1)).collect() };
+    let mut lines = vec![fit(format!("{}> {}", prompt, picker.query()))];
+    for (item, selected) in picker.visible() {
+        lines.push(match selected {
+            true => color::style(&fit(format!("> {}", item)), color::Style::Bold),
+            false => fit(format!("  {}", item)),
+        });
+    }
+    lines.resize(picker.height + 1, String::new());
+    let (matched, total) = picker.counts();
+    lines.push(color::style(
+        &format!("  {}/{}", matched, total),
+        color::Style::Dim,
+    ));
+
+    let mut frame = String::from("\r");
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            // Raw mode doesn't return the carriage at a newline
+            frame.push_str("\r\n");
+        }
+        frame.push_str("\x1b[2K");
+        frame.push_str(line);
+    }
+    frame.push_str(&format!("\x1b[{}A\r", lines.len() - 1));
+    let column = lines[0].chars().count();
+    if column > 0 {
+        frame.push_str(&format!("\x1b[{}C", column));
+    }
+    tty.write_all(frame.as_bytes())?;
+    tty.flush()?;
+    Ok(())
+}
+
+/// The terminal in raw mode without echo (`stty raw -echo`), restored to the previous settings when dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Result<RawMode, ZgError> {
+        let saved = stty(&["-g"])
+            .map_err(|e| format!("Failed to get the terminal settings with `stty`: {}", e))?;
+        stty(&["raw", "-echo"])
+            .map_err(|e| format!("Failed to set the terminal in raw mode with `stty`: {}", e))?;
+        Ok(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "stty exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn visible(picker: &Picker) -> Vec<(String, bool)> {
+        picker
+            .visible()
+            .map(|(item, selected)| (item.to_string(), selected))
+            .collect()
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("", "anything"), Some(0));
+        assert!(score("plc", "projects.locations.clusters").is_some());
+        assert_eq!(score("xyz", "projects.locations.clusters"), None);
+        // In order only
+        assert_eq!(score("cl", "locations"), None);
+        // Consecutive and word starts score higher
+        assert!(score("clus", "projects.clusters") > score("clus", "projects.cloudUsers"));
+        assert!(score("np", "nodePools") > score("np", "snapshots"));
+        // The best of the occurrences of the first character
+        assert_eq!(
+            score("op", "projects.operations"),
+            score("op", "operations")
+        );
+        // Smart case
+        assert!(score("NP", "nodepools").is_none());
+        assert!(score("NP", "nodePools").is_none());
+        assert!(score("P", "nodePools").is_some());
+        assert!(score("np", "NODEPOOLS").is_some());
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(b"cl\x7f\x1b[A\x1b[B\r"),
+            vec![
+                Key::Char('c'),
+                Key::Char('l'),
+                Key::Backspace,
+                Key::Up,
+                Key::Down,
+                Key::Enter
+            ]
+        );
+        assert_eq!(decode(b"\x1bOA\x10\x0e"), vec![Key::Up, Key::Up, Key::Down]);
+        assert_eq!(decode(b"\x1b"), vec![Key::Cancel]);
+        assert_eq!(decode(b"\x03"), vec![Key::Cancel]);
+        assert_eq!(decode(b"\x15"), vec![Key::ClearQuery]);
+        // Other sequences and control characters are dropped
+        assert_eq!(decode(b"\x1b[1;5C\x1b[3~\x01a"), vec![Key::Char('a')]);
+        assert_eq!(decode("é".as_bytes()), vec![Key::Char('é')]);
+    }
+
+    #[test]
+    fn test_picker_filter() {
+        let mut picker = Picker::new(
+            items(&[
+                "projects.locations.clusters.nodePools",
+                "projects.locations.clusters",
+                "projects.locations.operations",
+                "projects.zones.clusters",
+            ]),
+            10,
+        );
+        assert_eq!(picker.counts(), (4, 4));
+        for c in "clus".chars() {
+            assert_eq!(picker.handle(Key::Char(c)), Action::Continue);
+        }
+        assert_eq!(picker.query(), "clus");
+        assert_eq!(picker.counts(), (3, 4));
+        // Equally good matches: shorter ones first
+        assert_eq!(
+            visible(&picker),
+            vec![
+                ("projects.zones.clusters".to_string(), true),
+                ("projects.locations.clusters".to_string(), false),
+                ("projects.locations.clusters.nodePools".to_string(), false),
+            ]
+        );
+        assert_eq!(picker.handle(Key::Down), Action::Continue);
+        assert_eq!(picker.handle(Key::Enter), Action::Select(1));
+
+        picker.handle(Key::ClearQuery);
+        assert_eq!(picker.counts(), (4, 4));
+        for c in "zzz".chars() {
+            picker.handle(Key::Char(c));
+        }
+        assert_eq!(picker.counts(), (0, 4));
+        // Nothing to choose
+        assert_eq!(picker.handle(Key::Enter), Action::Continue);
+        picker.handle(Key::Backspace);
+        picker.handle(Key::Backspace);
+        picker.handle(Key::Backspace);
+        picker.handle(Key::Backspace);
+        assert_eq!(picker.query(), "");
+        assert_eq!(picker.handle(Key::Cancel), Action::Cancel);
+    }
+
+    #[test]
+    fn test_picker_navigation() {
+        let mut picker = Picker::new(items(&["a", "b", "c", "d", "e"]), 2);
+        let selected = |picker: &Picker| -> Vec<String> {
+            picker
+                .visible()
+                .map(|(item, selected)| format!("{}{}", item, if selected { "*" } else { "" }))
+                .collect()
+        };
+        assert_eq!(selected(&picker), vec!["a*", "b"]);
+        // Not beyond the top
+        picker.handle(Key::Up);
+        assert_eq!(selected(&picker), vec!["a*", "b"]);
+        picker.handle(Key::Down);
+        picker.handle(Key::Down);
+        assert_eq!(selected(&picker), vec!["b", "c*"]);
+        for _ in 0..5 {
+            picker.handle(Key::Down);
+        }
+        // Not beyond the bottom
+        assert_eq!(selected(&picker), vec!["d", "e*"]);
+        picker.handle(Key::Up);
+        picker.handle(Key::Up);
+        picker.handle(Key::Up);
+        assert_eq!(selected(&picker), vec!["b*", "c"]);
+        assert_eq!(picker.handle(Key::Enter), Action::Select(1));
+        // Typing goes back to the best match
+        picker.handle(Key::Char('e'));
+        assert_eq!(selected(&picker), vec!["e*"]);
+    }
+}
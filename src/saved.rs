@@ -0,0 +1,422 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Named requests saved with `zg exec --save-as NAME` (`requests.toml` in the config directory), and executed with
+//! `zg run NAME`. A project can share its own in `.zg/requests.toml`, which take precedence over the user's.
+
+use clap::{Args, Parser};
+use prettytable::{format, row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use super::color;
+use super::config;
+use super::core;
+use super::error::ZgError;
+use super::exec::ExecArgs;
+use super::suggest;
+
+const REQUESTS_FILE: &str = "requests.toml";
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Name of the saved request (see --list).
+    #[arg(required_unless_present = "list")]
+    name: Option<String>,
+
+    /// List the saved requests.
+    #[arg(long, conflicts_with = "name")]
+    list: bool,
+
+    /// Options of `zg exec` for the request (e.g., `-p pageSize=5 --paginate`). -p, -H, and --data override the saved
+    /// ones (by the parameter and header names).
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "EXEC_OPTIONS"
+    )]
+    exec_options: Vec<String>,
+}
+
+impl RunArgs {
+    pub fn is_list(&self) -> bool {
+        self.list
+    }
+}
+
+/// A saved request: the service, the resolved resource and method, and the other arguments of `zg exec` as given
+/// (before the template functions are expanded, so that they're expanded at each run).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct SavedRequest {
+    pub(crate) service: String,
+    pub(crate) resource: String,
+    pub(crate) method: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) values: Vec<String>,
+    /// -p parameters as given, e.g., `pageSize=10` or `validateOnly`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) params: Vec<String>,
+    /// -H headers as given, e.g., `X-Goog-User-Project: my-project`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) headers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) data_binary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) force_body: bool,
+}
+
+impl SavedRequest {
+    /// The `zg exec` command line of the request, quoted for shells.
+    fn command_line(&self) -> String {
+        let mut words = vec!["zg", "exec", &self.service, &self.resource, &self.method];
+        words.extend(self.values.iter().map(String::as_str));
+        for param in &self.params {
+            words.extend(["-p", param]);
+        }
+        for header in &self.headers {
+            words.extend(["-H", header]);
+        }
+        if let Some(data) = &self.data {
+            words.extend(["-d", data]);
+        }
+        if let Some(data) = &self.data_binary {
+            words.extend(["--data-binary", data]);
+        }
+        if let Some(content_type) = &self.content_type {
+            words.extend(["--content-type", content_type]);
+        }
+        if self.force_body {
+            words.push("--force-body");
+        }
+        shlex::try_join(words).unwrap_or_else(|_| format!("{:?}", self))
+    }
+}
+
+fn requests_path() -> PathBuf {
+    core::config_dir().join(REQUESTS_FILE)
+}
+
+/// The files of the saved requests to run: the project-local one (see `config::local_dir`) first, then the user's.
+fn request_paths() -> Vec<PathBuf> {
+    let local = config::local_dir().map(|dir| dir.join(REQUESTS_FILE));
+    local.into_iter().chain([requests_path()]).collect()
+}
+
+/// Saves the request as `name`. When one is saved with the name, asks whether to overwrite it unless `force`,
+/// and fails when the answer can't be asked. Returns whether it's saved.
+pub(crate) fn save(name: &str, request: &SavedRequest, force: bool) -> Result<bool, ZgError> {
+    let path = requests_path();
+    let saved = save_in(&path, name, request, |existing| {
+        if force {
+            return Ok(true);
+        }
+        if !std::io::stdin().is_terminal() {
+            return Err(format!(
+                "A request named '{}' is already saved ({}); overwrite it with --force",
+                name,
+                existing.command_line()
+            )
+            .into());
+        }
+        confirm_overwrite(name, existing, &mut std::io::stdin().lock())
+    })?;
+    match saved {
+        true => eprintln!("Saved the request as '{}' in {}", name, path.display()),
+        false => eprintln!("The request is not saved"),
+    }
+    Ok(saved)
+}
+
+fn confirm_overwrite(
+    name: &str,
+    existing: &SavedRequest,
+    input: &mut impl BufRead,
+) -> Result<bool, ZgError> {
+    eprint!(
+        "Overwrite the saved request '{}' ({})? [y/N] ",
+        name,
+        existing.command_line()
+    );
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Saves the request as `name` in the file, asking `overwrite` when the name is taken.
+fn save_in(
+    path: &Path,
+    name: &str,
+    request: &SavedRequest,
+    overwrite: impl FnOnce(&SavedRequest) -> Result<bool, ZgError>,
+) -> Result<bool, ZgError> {
+    if name.trim().is_empty() {
+        return Err("The name of the saved request can't be empty".into());
+    }
+    let mut requests = load(path)?;
+    if let Some(existing) = requests.get(name) {
+        if existing == request {
+            return Ok(true);
+        }
+        if !overwrite(existing)? {
+            return Ok(false);
+        }
+    }
+    requests.insert(name.to_string(), request.clone());
+    let content = toml::to_string(&requests).map_err(|e| ZgError::Other(e.to_string()))?;
+    core::write_file_atomic(path, content.as_bytes())?;
+    Ok(true)
+}
+
+fn load(path: &Path) -> Result<BTreeMap<String, SavedRequest>, ZgError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    toml::from_str(&content).map_err(|e| {
+        format!(
+            "Failed to read the saved requests in {}: {}",
+            path.display(),
+            e
+        )
+        .into()
+    })
+}
+
+/// Loads the requests of all the files; the ones of an earlier file take precedence over the same names in later ones.
+fn load_all(paths: &[PathBuf]) -> Result<BTreeMap<String, SavedRequest>, ZgError> {
+    let mut requests = BTreeMap::new();
+    for path in paths.iter().rev() {
+        requests.extend(load(path)?);
+    }
+    Ok(requests)
+}
+
+fn find_in(paths: &[PathBuf], name: &str) -> Result<SavedRequest, ZgError> {
+    let mut requests = load_all(paths)?;
+    if let Some(request) = requests.remove(name) {
+        return Ok(request);
+    }
+    let similar = suggest::suggest(name, requests.keys().map(String::as_str));
+    let hint = match similar.is_empty() {
+        true => " See `zg run --list`.".to_string(),
+        false => format!(" Did you mean: {}?", similar.join(", ")),
+    };
+    Err(format!("No saved request named '{}'.{}", name, hint).into())
+}
+
+/// Prints the saved requests with their command lines.
+pub fn list() -> Result<(), ZgError> {
+    let requests = load_all(&request_paths())?;
+    if requests.is_empty() {
+        println!(
+            "No saved requests in {}. Save one with `zg exec ... --save-as NAME`.",
+            requests_path().display()
+        );
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_CLEAN);
+    for (name, request) in &requests {
+        table.add_row(row![b->name, request.command_line()]);
+    }
+    color::print_table(&table)
+}
+
+/// Returns the arguments of `zg exec` to run the saved request, with the options given to `zg run`.
+pub fn exec_args(args: &RunArgs) -> Result<ExecArgs, ZgError> {
+    let name = args.name.as_deref().unwrap_or_default();
+    exec_args_of(&find_in(&request_paths(), name)?, &args.exec_options)
+}
+
+fn exec_args_of(request: &SavedRequest, options: &[String]) -> Result<ExecArgs, ZgError> {
+    #[derive(Parser)]
+    #[command(name = "zg run")]
+    struct Cli {
+        #[command(flatten)]
+        args: ExecArgs,
+    }
+    let words = [
+        "zg run",
+        &request.service,
+        &request.resource,
+        &request.method,
+    ]
+    .into_iter()
+    .chain(request.values.iter().map(String::as_str))
+    .chain(options.iter().map(String::as_str));
+    let cli = Cli::try_parse_from(words).map_err(|e| ZgError::Other(e.to_string()))?;
+    cli.args.with_saved(request)
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> SavedRequest {
+        SavedRequest {
+            service: "gke".to_string(),
+            resource: "projects.locations.clusters".to_string(),
+            method: "list".to_string(),
+            values: vec![],
+            params: vec![
+                "parent=projects/prod/locations/-".to_string(),
+                "requestId={{uuid}}".to_string(),
+            ],
+            headers: vec!["X-Goog-User-Project: billing".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_and_find() {
+        let path = std::env::temp_dir().join(format!("zg-requests-{}.toml", std::process::id()));
+        let paths = [path.clone()];
+        let _ = fs::remove_file(&path);
+        assert!(find_in(&paths, "prod")
+            .unwrap_err()
+            .to_string()
+            .contains("zg run --list"));
+
+        let never = |_: &SavedRequest| -> Result<bool, ZgError> { panic!("asked to overwrite") };
+        assert!(save_in(&path, "prod-clusters", &request(), never).unwrap());
+        let round_trip = find_in(&paths, "prod-clusters").unwrap();
+        assert_eq!(round_trip, request());
+        // Template functions are kept as they are
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("requestId={{uuid}}"));
+
+        // Saving the same request again doesn't ask
+        assert!(save_in(&path, "prod-clusters", &request(), never).unwrap());
+
+        // A different request with the name is saved only when the overwrite is confirmed
+        let other = SavedRequest {
+            method: "get".to_string(),
+            values: vec!["my-cluster".to_string()],
+            ..request()
+        };
+        assert!(!save_in(&path, "prod-clusters", &other, |_| Ok(false)).unwrap());
+        assert_eq!(find_in(&paths, "prod-clusters").unwrap(), request());
+        assert!(save_in(&path, "prod-clusters", &other, |_| Ok(true)).unwrap());
+        assert_eq!(find_in(&paths, "prod-clusters").unwrap(), other);
+
+        assert_eq!(
+            find_in(&paths, "prod-cluster").unwrap_err().to_string(),
+            "No saved request named 'prod-cluster'. Did you mean: prod-clusters?"
+        );
+
+        // The requests of the project-local file take precedence over the user's
+        let local =
+            std::env::temp_dir().join(format!("zg-requests-local-{}.toml", std::process::id()));
+        let never = |_: &SavedRequest| -> Result<bool, ZgError> { panic!("asked to overwrite") };
+        assert!(save_in(&local, "prod-clusters", &request(), never).unwrap());
+        assert!(save_in(&local, "staging", &request(), never).unwrap());
+        let both = [local.clone(), path.clone()];
+        assert_eq!(find_in(&both, "prod-clusters").unwrap(), request());
+        assert_eq!(find_in(&both, "staging").unwrap(), request());
+        assert_eq!(load_all(&both).unwrap().len(), 2);
+        fs::remove_file(&local).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_confirm_overwrite() {
+        assert!(confirm_overwrite("a", &request(), &mut "y\n".as_bytes()).unwrap());
+        assert!(!confirm_overwrite("a", &request(), &mut "\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_exec_args_of() {
+        let options = |options: &[&str]| -> Vec<String> {
+            options.iter().map(|option| option.to_string()).collect()
+        };
+
+        // As saved
+        let args = exec_args_of(&request(), &[]).unwrap();
+        assert_eq!(
+            args.saved_request("gke", "projects.locations.clusters", "list"),
+            request()
+        );
+
+        // -p and -H override the saved ones by name, and other options apply
+        let args = exec_args_of(
+            &request(),
+            &options(&[
+                "-p",
+                "parent=projects/staging/locations/-",
+                "pageSize=5",
+                "-H",
+                "x-goog-user-project: other",
+                "--paginate",
+            ]),
+        )
+        .unwrap();
+        let saved = args.saved_request("gke", "projects.locations.clusters", "list");
+        assert_eq!(
+            saved.params,
+            [
+                "requestId={{uuid}}",
+                "parent=projects/staging/locations/-",
+                "pageSize=5"
+            ]
+        );
+        assert_eq!(saved.headers, ["x-goog-user-project: other"]);
+
+        // Positional values follow the saved ones
+        let get = SavedRequest {
+            method: "get".to_string(),
+            values: vec!["us-central1".to_string()],
+            ..request()
+        };
+        let args = exec_args_of(&get, &options(&["my-cluster", "-d", "{}"])).unwrap();
+        let saved = args.saved_request("gke", "projects.locations.clusters", "get");
+        assert_eq!(saved.values, ["us-central1", "my-cluster"]);
+        assert_eq!(saved.data.as_deref(), Some("{}"));
+
+        // A binary body is saved with its options, and -d given to `zg run` replaces it
+        let upload = SavedRequest {
+            method: "create".to_string(),
+            data_binary: Some("@rows.csv".to_string()),
+            content_type: Some("text/csv".to_string()),
+            force_body: true,
+            ..request()
+        };
+        let args = exec_args_of(&upload, &[]).unwrap();
+        let saved = args.saved_request("gke", "projects.locations.clusters", "create");
+        assert_eq!(saved, upload);
+        assert!(upload
+            .command_line()
+            .ends_with("--data-binary @rows.csv --content-type text/csv --force-body"));
+        let args = exec_args_of(&upload, &options(&["-d", "{}"])).unwrap();
+        let saved = args.saved_request("gke", "projects.locations.clusters", "create");
+        assert_eq!(
+            (saved.data.as_deref(), saved.data_binary),
+            (Some("{}"), None)
+        );
+        assert_eq!(saved.content_type.as_deref(), Some("text/csv"));
+
+        assert!(exec_args_of(&request(), &options(&["--no-such-option"])).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::Args;
+use log::warn;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::future::Future;
+
+use super::core;
+use super::error::ZgError;
+use super::supported_apis::supported_apis;
+
+/// File name of the command history, stored in the config directory.
+const HISTORY_FILE: &str = "shell_history";
+const PROMPT: &str = "zg> ";
+
+/// Commands of the shell itself, completed along with the zg commands.
+const BUILTINS: [&str; 3] = ["exit", "quit", "history"];
+
+/// Commands taking SERVICE, RESOURCE, and METHOD, whose names are completed.
+const API_COMMANDS: [&str; 9] = [
+    "list", "ls", "desc", "describe", "show", "exec", "ex", "execute", "open",
+];
+
+#[derive(Args, Debug)]
+pub struct ShellArgs {}
+
+/// A line read by `zg shell`.
+#[derive(Debug, PartialEq)]
+pub enum Line {
+    Empty,
+    Exit,
+    History,
+    /// Arguments of a zg command, without the leading `zg`.
+    Command(Vec<String>),
+}
+
+/// Splits a line into arguments like POSIX shells (quotes and backslashes). A leading `zg` is optional.
+pub fn parse_line(line: &str) -> Result<Line, ZgError> {
+    let mut words = shlex::split(line).ok_or("Unbalanced quotes")?;
+    if words.first().is_some_and(|word| word == "zg") {
+        words.remove(0);
+    }
+    Ok(match words.first().map(String::as_str) {
+        None => Line::Empty,
+        Some("exit" | "quit") => Line::Exit,
+        Some("history") => Line::History,
+        Some(_) => Line::Command(words),
+    })
+}
+
+/// Completes the commands, and the services, resources, and methods of the API definitions loaded in the session.
+struct ZgHelper {
+    commands: Vec<String>,
+}
+
+impl Completer for ZgHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before
+            .rfind(char::is_whitespace)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let words: Vec<&str> = before[..start].split_whitespace().collect();
+        let candidates = candidates(&words, &before[start..], &self.commands, core::kept_api_of);
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ZgHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ZgHelper {}
+
+impl Validator for ZgHelper {}
+
+impl Helper for ZgHelper {}
+
+/// Returns the candidates of the word starting with `prefix` after `words`: a command first, and then the service,
+/// resource, and method of the commands taking them. Resources and methods are completed only when `api` gives the
+/// API definition of the service; options end the completion of the names.
+fn candidates(
+    words: &[&str],
+    prefix: &str,
+    commands: &[String],
+    api: impl Fn(&str) -> Option<core::ZgApi>,
+) -> Vec<String> {
+    let words = match words.first() {
+        Some(&"zg") => &words[1..],
+        _ => words,
+    };
+    let is_api_command = words
+        .first()
+        .is_some_and(|command| API_COMMANDS.contains(command));
+    let mut candidates: Vec<String> = match words {
+        [] => commands
+            .iter()
+            .cloned()
+            .chain(BUILTINS.map(str::to_string))
+            .collect(),
+        _ if !is_api_command || words.iter().any(|word| word.starts_with('-')) => vec![],
+        [_] => supported_apis(true)
+            .into_iter()
+            .flat_map(|api| std::iter::once(api.name).chain(api.aliases))
+            .collect(),
+        [_, service] => api(service)
+            .map(|api| {
+                api.all_resource_paths()
+                    .into_iter()
+                    .flat_map(|(name, path)| {
+                        let path = path.split_once('.').map(|(_, path)| path.to_string());
+                        std::iter::once(name).chain(path)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        [_, service, resource] => api(service)
+            .and_then(|api| {
+                core::find_api_resource(&api, resource, false)
+                    .ok()
+                    .map(|resource| resource.methods.iter().map(|m| m.name.clone()).collect())
+            })
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Main function to run zg commands interactively, e.g., `ls compute`, then `desc compute instances.list`.
+/// The API definitions and the access token are loaded once and kept during the session.
+/// Lines are edited and recalled with rustyline, kept in the history file across sessions, and Tab completes the
+/// `commands`, as well as the services, and the resources and methods of the APIs used in the session.
+/// Exits on `exit`, `quit`, or EOF (Ctrl-D); an error in a command is printed and doesn't end the session.
+pub async fn main<F, Fut>(commands: Vec<String>, dispatch: F) -> Result<(), ZgError>
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<(), ZgError>>,
+{
+    core::keep_loaded_apis();
+    let history_path = core::config_dir().join(HISTORY_FILE);
+    let mut editor: Editor<ZgHelper, DefaultHistory> = Editor::new().map_err(readline_error)?;
+    editor.set_helper(Some(ZgHelper { commands }));
+    if history_path.exists() {
+        if let Err(e) = editor.load_history(&history_path) {
+            warn!("Failed to load the history from {:?}: {}", history_path, e);
+        }
+    }
+    eprintln!("Type zg commands without `zg` (e.g., `ls compute`). `exit` or Ctrl-D to quit.");
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(readline_error(e)),
+        };
+        let words = match parse_line(&line) {
+            Ok(Line::Empty) => continue,
+            Ok(Line::Exit) => return Ok(()),
+            Ok(Line::History) => {
+                for entry in editor.history().iter() {
+                    println!("{}", entry);
+                }
+                continue;
+            }
+            Ok(Line::Command(words)) => words,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        let saved = editor
+            .add_history_entry(line.trim())
+            .and_then(|_| editor.append_history(&history_path));
+        if let Err(e) = saved {
+            warn!("Failed to save the history to {:?}: {}", history_path, e);
+        }
+        if let Err(e) = dispatch(words).await {
+            eprintln!("Error: {}", e);
+            if let Some(hint) = e.hint() {
+                eprintln!("Hint: {}", hint);
+            }
+        }
+    }
+}
+
+fn readline_error(e: ReadlineError) -> ZgError {
+    ZgError::Other(format!("Failed to read the line: {}", e))
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("").unwrap(), Line::Empty);
+        assert_eq!(parse_line("  \n").unwrap(), Line::Empty);
+        assert_eq!(parse_line("exit\n").unwrap(), Line::Exit);
+        assert_eq!(parse_line("zg quit").unwrap(), Line::Exit);
+        assert_eq!(parse_line("history").unwrap(), Line::History);
+        assert_eq!(
+            parse_line("ls compute\n").unwrap(),
+            Line::Command(vec!["ls".to_string(), "compute".to_string()])
+        );
+        assert_eq!(
+            parse_line(
+                r#"zg ex gke clusters.list -p 'parent=projects/p/locations/-' -d "{\"a\": 1}""#
+            )
+            .unwrap(),
+            Line::Command(vec![
+                "ex".to_string(),
+                "gke".to_string(),
+                "clusters.list".to_string(),
+                "-p".to_string(),
+                "parent=projects/p/locations/-".to_string(),
+                "-d".to_string(),
+                r#"{"a": 1}"#.to_string(),
+            ])
+        );
+        assert!(parse_line("desc 'gke").is_err());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let commands = vec!["list".to_string(), "ls".to_string(), "desc".to_string()];
+        let api = |service: &str| {
+            (service == "gke").then(|| core::ZgApi {
+                resources: vec![core::ZgResource {
+                    name: "clusters".to_string(),
+                    path: Some("container.projects.locations.clusters".to_string()),
+                    methods: vec![
+                        core::ZgMethod {
+                            name: "list".to_string(),
+                            ..core::ZgMethod::testdata()
+                        },
+                        core::ZgMethod {
+                            name: "delete".to_string(),
+                            ..core::ZgMethod::testdata()
+                        },
+                    ],
+                    ..core::ZgResource::testdata()
+                }],
+                ..core::ZgApi::testdata()
+            })
+        };
+        assert_eq!(candidates(&[], "l", &commands, api), ["list", "ls"]);
+        assert_eq!(candidates(&["zg"], "h", &commands, api), ["history"]);
+        assert!(candidates(&["ls"], "", &commands, api).contains(&"gke".to_string()));
+        assert_eq!(
+            candidates(&["ls", "gke"], "", &commands, api),
+            ["clusters", "projects.locations.clusters"]
+        );
+        assert_eq!(
+            candidates(&["desc", "gke", "clusters"], "", &commands, api),
+            ["delete", "list"]
+        );
+        // Not for APIs not loaded yet, other commands, or after options
+        assert!(candidates(&["ls", "compute"], "", &commands, api).is_empty());
+        assert!(candidates(&["config"], "", &commands, api).is_empty());
+        assert!(candidates(&["ls", "-l", "gke"], "", &commands, api).is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A spinner with the elapsed time on stderr while waiting for a slow response, so that the terminal isn't silent.
+
+use std::future::Future;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Responses faster than this don't show the spinner, to avoid flickering.
+const DELAY: Duration = Duration::from_millis(500);
+
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// Clears the current line of the terminal.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Whether to show the spinner: only when stderr is a terminal, not with --quiet, and not when structured output
+/// (e.g., `--format json`) is piped to another command.
+pub(crate) fn enabled(quiet: bool, structured: bool) -> bool {
+    should_spin(
+        std::io::stderr().is_terminal(),
+        std::io::stdout().is_terminal(),
+        quiet,
+        structured,
+    )
+}
+
+fn should_spin(
+    stderr_terminal: bool,
+    stdout_terminal: bool,
+    quiet: bool,
+    structured: bool,
+) -> bool {
+    stderr_terminal && !quiet && (stdout_terminal || !structured)
+}
+
+/// Awaits the future, showing the spinner with the message and the elapsed time on stderr if `enabled`. The line of the
+/// spinner is cleared before returning, so that the output (or an error) is printed as usual.
+pub(crate) async fn with_spinner<T>(
+    enabled: bool,
+    message: String,
+    future: impl Future<Output = T>,
+) -> T {
+    let spinner = Spinner::start(enabled, message);
+    let output = future.await;
+    if let Some(spinner) = spinner {
+        spinner.stop().await;
+    }
+    output
+}
+
+/// The spinner drawn on stderr by a background task until it's stopped.
+struct Spinner {
+    ticker: tokio::task::JoinHandle<()>,
+    drawn: Arc<AtomicBool>,
+}
+
+impl Spinner {
+    /// Starts drawing the spinner after `DELAY`. None (nothing is started) if not `enabled`.
+    fn start(enabled: bool, message: String) -> Option<Spinner> {
+        if !enabled {
+            return None;
+        }
+        let started = Instant::now();
+        let drawn = Arc::new(AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let drawn = drawn.clone();
+            async move {
+                tokio::time::sleep(DELAY).await;
+                let mut interval = tokio::time::interval(INTERVAL);
+                for tick in 0.. {
+                    interval.tick().await;
+                    let mut stderr = std::io::stderr().lock();
+                    let _ = write!(
+                        stderr,
+                        "{}{}",
+                        CLEAR_LINE,
+                        frame(&message, started.elapsed(), tick)
+                    );
+                    let _ = stderr.flush();
+                    drawn.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+        Some(Spinner { ticker, drawn })
+    }
+
+    /// Stops the spinner, and clears its line if it has been drawn.
+    async fn stop(self) {
+        self.ticker.abort();
+        // Wait for the ticker to stop, not to draw again after the line is cleared
+        let _ = self.ticker.await;
+        if self.drawn.load(Ordering::Relaxed) {
+            eprint!("{}", CLEAR_LINE);
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+/// A line of the spinner, e.g., `⠹ compute.instances.aggregatedList (12.3s)`.
+fn frame(message: &str, elapsed: Duration, tick: usize) -> String {
+    format!(
+        "{} {} ({:.1}s)",
+        FRAMES[tick % FRAMES.len()],
+        message,
+        elapsed.as_secs_f64()
+    )
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_spin() {
+        assert!(should_spin(true, true, false, false));
+        assert!(should_spin(true, false, false, false)); // text output piped
+        assert!(should_spin(true, true, false, true));
+        assert!(!should_spin(true, false, false, true)); // --format json piped
+        assert!(!should_spin(true, true, true, false)); // --quiet
+        assert!(!should_spin(false, true, false, false)); // stderr redirected
+    }
+
+    #[test]
+    fn test_frame() {
+        assert_eq!(
+            frame(
+                "compute.instances.aggregatedList",
+                Duration::from_millis(12345),
+                2
+            ),
+            "⠹ compute.instances.aggregatedList (12.3s)"
+        );
+        assert!(frame("m", Duration::ZERO, FRAMES.len()).starts_with('⠋'));
+    }
+
+    #[test]
+    fn test_spinner_disabled() {
+        // Nothing is spawned (which would panic outside of a runtime)
+        assert!(Spinner::start(false, "m".to_string()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_with_spinner() {
+        assert_eq!(with_spinner(false, "m".to_string(), async { 42 }).await, 42);
+
+        // A fast response returns without waiting for the spinner, which isn't drawn
+        let started = Instant::now();
+        let spinner = Spinner::start(true, "m".to_string()).unwrap();
+        let drawn = spinner.drawn.clone();
+        spinner.stop().await;
+        assert!(!drawn.load(Ordering::Relaxed));
+        assert_eq!(
+            with_spinner(true, "m".to_string(), async { "fast" }).await,
+            "fast"
+        );
+        assert!(started.elapsed() < DELAY);
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Template functions in `-p` values and `--data` of `zg exec` (e.g., `{{uuid}}` for a unique request id), and
+//! references to environment variables (`${env:VAR}`, and `$VAR` with `--env-expand`).
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use regex::{Captures, Regex};
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::error::ZgError;
+
+/// `{{uuid}}`, `{{now}}`, `{{now+SECONDS}}` (or `-`), `{{rand:LENGTH}}`, `{{env:VAR}}`, and `${env:VAR}`, plus `${VAR}` and
+/// `$VAR` expanded only with `--env-expand`. Each is optionally escaped with a backslash (`\{{uuid}}`, `\${env:VAR}`).
+/// Other double braces are left as they are.
+static FUNCTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(\\?)(?:\{\{\s*(uuid|now([+-]\d+)?|rand:(\d+)|env:([A-Za-z_][A-Za-z0-9_]*))\s*\}\}",
+        r"|\$\{env:([A-Za-z_][A-Za-z0-9_]*)\}|\$\{([A-Za-z_][A-Za-z0-9_]*)\}|\$([A-Za-z_][A-Za-z0-9_]*))"
+    ))
+    .unwrap()
+});
+
+/// Returns the environment variables referenced in the values but not set in `env`, sorted, to report all of them at
+/// once before anything is expanded.
+pub(crate) fn unset_variables_with<'a>(
+    values: impl IntoIterator<Item = &'a str>,
+    env_expand: bool,
+    env: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    let mut unset = BTreeSet::new();
+    for value in values {
+        for captures in FUNCTION.captures_iter(value) {
+            if !captures[1].is_empty() {
+                continue;
+            }
+            if let Some(name) = variable_name(&captures, env_expand) {
+                if env(name).is_none() {
+                    unset.insert(name.to_string());
+                }
+            }
+        }
+    }
+    unset.into_iter().collect()
+}
+
+/// The name of the environment variable the match refers to, if it's a reference to be expanded.
+fn variable_name<'h>(captures: &Captures<'h>, env_expand: bool) -> Option<&'h str> {
+    let explicit = captures.get(5).or_else(|| captures.get(6));
+    let bare = captures.get(7).or_else(|| captures.get(8));
+    explicit
+        .or(bare.filter(|_| env_expand))
+        .map(|name| name.as_str())
+}
+
+/// Whether the match is a bare reference (`$VAR` or `${VAR}`), left as it is without `--env-expand`.
+fn is_bare(captures: &Captures) -> bool {
+    captures.get(7).is_some() || captures.get(8).is_some()
+}
+
+/// Expands the template functions and the environment variables (looked up by `env`) in the value, at `now`. Bare
+/// references (`$VAR` and `${VAR}`) are expanded only with `env_expand`.
+pub(crate) fn expand_with(
+    value: &str,
+    env_expand: bool,
+    now: SystemTime,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<String, ZgError> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut last = 0;
+    for captures in FUNCTION.captures_iter(value) {
+        let matched = captures.get(0).unwrap();
+        expanded.push_str(&value[last..matched.start()]);
+        last = matched.end();
+        if is_bare(&captures) && !env_expand {
+            expanded.push_str(matched.as_str());
+        } else if !captures[1].is_empty() {
+            // Escaped: keep the function without the backslash
+            expanded.push_str(&matched.as_str()[1..]);
+        } else {
+            expanded.push_str(&call(&captures, env_expand, now, &env)?);
+        }
+    }
+    expanded.push_str(&value[last..]);
+    Ok(expanded)
+}
+
+fn call(
+    captures: &Captures,
+    env_expand: bool,
+    now: SystemTime,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Result<String, ZgError> {
+    if let Some(length) = captures.get(4) {
+        let length: usize = length
+            .as_str()
+            .parse()
+            .map_err(|_| ZgError::Other(format!("Invalid length in '{}'", &captures[0])))?;
+        return Ok(rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(length)
+            .map(char::from)
+            .collect());
+    }
+    if let Some(name) = variable_name(captures, env_expand) {
+        return env(name).ok_or_else(|| {
+            ZgError::Other(format!(
+                "Environment variable '{}' in '{}' is not set",
+                name, &captures[0]
+            ))
+        });
+    }
+    if &captures[2] == "uuid" {
+        return Ok(uuid_v4(rand::random()));
+    }
+    let offset: i64 = match captures.get(3) {
+        Some(offset) => offset
+            .as_str()
+            .trim_start_matches('+')
+            .parse()
+            .map_err(|_| ZgError::Other(format!("Invalid offset in '{}'", &captures[0])))?,
+        None => 0,
+    };
+    let seconds = now
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    Ok(rfc3339(seconds.saturating_add(offset)))
+}
+
+/// Formats random bytes as a version 4 (random) UUID.
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Formats the seconds since the Unix epoch as an RFC 3339 timestamp in UTC (e.g., `2025-01-31T09:00:00Z`).
+pub(crate) fn rfc3339(seconds: i64) -> String {
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // Converts the days to the civil date (http://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn env(name: &str) -> Option<String> {
+        (name == "CLUSTER_NAME").then(|| "prod-1".to_string())
+    }
+
+    fn expand_at(value: &str) -> Result<String, ZgError> {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        expand_with(value, false, now, env)
+    }
+
+    fn expand_bare(value: &str) -> Result<String, ZgError> {
+        expand_with(value, true, SystemTime::now(), env)
+    }
+
+    #[test]
+    fn test_now() {
+        assert_eq!(expand_at("{{now}}").unwrap(), "2023-11-14T22:13:20Z");
+        assert_eq!(expand_at("{{now+3600}}").unwrap(), "2023-11-14T23:13:20Z");
+        assert_eq!(
+            expand_at("{{ now-86400 }}").unwrap(),
+            "2023-11-13T22:13:20Z"
+        );
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(-1), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_uuid_and_rand() {
+        let uuid =
+            Regex::new(r"^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$")
+                .unwrap();
+        let first = expand_at("{{uuid}}").unwrap();
+        assert!(uuid.is_match(&first), "{}", first);
+        assert_ne!(first, expand_at("{{uuid}}").unwrap());
+        assert_eq!(uuid_v4([0xff; 16]), "ffffffff-ffff-4fff-bfff-ffffffffffff");
+
+        let name = expand_at("cluster-{{rand:8}}").unwrap();
+        assert_eq!(name.len(), "cluster-".len() + 8);
+        assert!(name["cluster-".len()..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(expand_at("{{rand:0}}").unwrap(), "");
+    }
+
+    #[test]
+    fn test_env() {
+        assert_eq!(
+            expand_at(r#"{"name": "{{env:CLUSTER_NAME}}"}"#).unwrap(),
+            r#"{"name": "prod-1"}"#
+        );
+        let e = expand_at("{{env:UNSET_VAR}}").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Environment variable 'UNSET_VAR' in '{{env:UNSET_VAR}}' is not set"
+        );
+    }
+
+    #[test]
+    fn test_escape_and_others() {
+        // Escaped functions are kept without the backslash
+        assert_eq!(
+            expand_at(r"\{{uuid}} {{now}}").unwrap(),
+            "{{uuid}} 2023-11-14T22:13:20Z"
+        );
+        // Unknown functions and other double braces are left as they are
+        for value in [
+            "{{name}}",
+            "{{ .Values.x }}",
+            "{{rand}}",
+            "{{now+}}",
+            "{{env:}}",
+            "{}",
+        ] {
+            assert_eq!(expand_at(value).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_env_references() {
+        // ${env:VAR} is always expanded
+        assert_eq!(
+            expand_at(r#"{"name": "${env:CLUSTER_NAME}"}"#).unwrap(),
+            r#"{"name": "prod-1"}"#
+        );
+        // Bare references only with --env-expand
+        assert_eq!(
+            expand_at("$CLUSTER_NAME ${CLUSTER_NAME}").unwrap(),
+            "$CLUSTER_NAME ${CLUSTER_NAME}"
+        );
+        assert_eq!(
+            expand_bare("$CLUSTER_NAME/${CLUSTER_NAME}-a").unwrap(),
+            "prod-1/prod-1-a"
+        );
+        // Unset
+        let e = expand_at("${env:UNSET_VAR}").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Environment variable 'UNSET_VAR' in '${env:UNSET_VAR}' is not set"
+        );
+        assert!(expand_bare("$UNSET_VAR").is_err());
+        // Escaped references are kept without the backslash
+        assert_eq!(
+            expand_at(r"\${env:CLUSTER_NAME} ${env:CLUSTER_NAME}").unwrap(),
+            "${env:CLUSTER_NAME} prod-1"
+        );
+        assert_eq!(expand_bare(r"\$UNSET_VAR").unwrap(), "$UNSET_VAR");
+        // A `$` without a name is left as it is
+        assert_eq!(expand_bare("$ 5$ ${}").unwrap(), "$ 5$ ${}");
+    }
+
+    #[test]
+    fn test_unset_variables() {
+        let values = [
+            "${env:B_UNSET} ${env:CLUSTER_NAME}",
+            "{{env:A_UNSET}} $C_UNSET \\${env:ESCAPED}",
+            "${env:B_UNSET}",
+        ];
+        assert_eq!(
+            unset_variables_with(values, false, env),
+            vec!["A_UNSET", "B_UNSET"]
+        );
+        assert_eq!(
+            unset_variables_with(values, true, env),
+            vec!["A_UNSET", "B_UNSET", "C_UNSET"]
+        );
+        assert!(unset_variables_with(["$CLUSTER_NAME"], true, env).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Maximum number of suggestions shown in a "Did you mean" hint.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns up to three candidates similar to the (mistyped) input, the closest first.
+/// Used for "Did you mean" hints of service, resource, and method names.
+pub fn suggest<'a, I>(input: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| similarity_score(input, candidate).map(|score| (score, candidate)))
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Scores how likely the candidate is what the user meant; lower is closer. None if not similar at all.
+///
+/// - 0: differs only by case (e.g., "nodepools" vs "nodePools")
+/// - 1: singular/plural mix-up (e.g., "cluster" vs "clusters")
+/// - 2: the input is a prefix of the candidate (e.g., "node" vs "nodePools")
+/// - 2+: edit distance within the tolerance based on the input length
+fn similarity_score(input: &str, candidate: &str) -> Option<usize> {
+    let input = input.to_lowercase();
+    let candidate = candidate.to_lowercase();
+
+    if input == candidate {
+        return Some(0);
+    }
+    if is_plural_of(&input, &candidate) || is_plural_of(&candidate, &input) {
+        return Some(1);
+    }
+    if input.len() >= 3 && candidate.starts_with(&input) {
+        return Some(2);
+    }
+
+    let distance = levenshtein(&input, &candidate);
+    let tolerance = (input.chars().count() / 3).clamp(1, 3);
+    (distance <= tolerance).then_some(distance + 1)
+}
+
+/// Checks if `plural` is the (English) plural form of `singular` (e.g., "clusters", "addresses", "policies").
+fn is_plural_of(singular: &str, plural: &str) -> bool {
+    if let Some(stem) = plural.strip_suffix("ies") {
+        if singular.strip_suffix('y') == Some(stem) {
+            return true;
+        }
+    }
+    plural.strip_suffix('s') == Some(singular) || plural.strip_suffix("es") == Some(singular)
+}
+
+/// Levenshtein (edit) distance between two strings.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b_chars.len() + 1];
+        for (j, cb) in b_chars.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b_chars.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("clusters", "clusters"), 0);
+        assert_eq!(levenshtein("clustres", "clusters"), 2);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest_plural_mixups() {
+        let candidates = ["clusters", "operations", "policies", "addresses"];
+        assert_eq!(suggest("cluster", candidates), vec!["clusters"]);
+        assert_eq!(suggest("policy", candidates), vec!["policies"]);
+        assert_eq!(suggest("address", candidates), vec!["addresses"]);
+        assert_eq!(suggest("operationss", candidates), vec!["operations"]);
+    }
+
+    #[test]
+    fn test_suggest_typos() {
+        let candidates = ["clusters", "nodePools", "operations", "locations"];
+        assert_eq!(suggest("clustres", candidates), vec!["clusters"]);
+        assert_eq!(suggest("nodepool", candidates), vec!["nodePools"]);
+        assert_eq!(suggest("node", candidates), vec!["nodePools"]);
+        assert!(suggest("xyz", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_order_and_limit() {
+        let candidates = ["list", "lost", "lust", "last", "list"];
+        let result = suggest("lest", candidates);
+        assert_eq!(result, vec!["last", "list", "lost"]);
+    }
+}
@@ -12,15 +12,187 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::LazyLock;
+use log::debug;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, OnceLock};
 
-#[derive(Debug, Clone)]
+use super::config;
+use super::core;
+use super::error::ZgError;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SupportedApi {
-    pub name: String,     // e.g., "appengine"
-    pub title: String,    // e.g., "App Engine Admin"
-    pub category: String, // e.g., "Compute"
+    pub name: String,  // e.g., "appengine"
+    pub title: String, // e.g., "App Engine Admin"
+    pub category: Category,
+    #[serde(default)]
     pub aliases: Vec<String>,
     pub versions: Vec<String>,
+    #[serde(default)]
+    pub status: ApiStatus,
+    pub notes: Option<String>, // gotchas shown in `zg desc SERVICE` (e.g., which resource zg picks for ambiguous names)
+    pub discovery_url_template: Option<String>, // e.g., "https://{host}/$discovery/rest?version={version}&key={key}"
+    pub endpoint_template: Option<String>, // e.g., "https://{region}-aiplatform.googleapis.com/"
+}
+
+/// Release status of the API as a whole (based on its default version and announcements).
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, clap::ValueEnum,
+)]
+pub enum ApiStatus {
+    #[default]
+    GA,
+    Beta,
+    Deprecated,
+}
+
+/// Category of the API. The declaration order is the order used when sorting by category.
+/// Categories are based on: https://cloud.google.com/terms/services, https://console.cloud.google.com/products, and console UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Compute,
+    Serverless,
+    Storage,
+    Databases,
+    Networking,
+    Analytics,
+    AiMl,
+    Integration,
+    Operations,
+    Management,
+    IdentityAccess,
+    Security,
+    Migration,
+    Developer,
+    Workspace,
+    Custom,
+}
+
+impl Category {
+    pub const ALL: [Category; 16] = [
+        Category::Compute,
+        Category::Serverless,
+        Category::Storage,
+        Category::Databases,
+        Category::Networking,
+        Category::Analytics,
+        Category::AiMl,
+        Category::Integration,
+        Category::Operations,
+        Category::Management,
+        Category::IdentityAccess,
+        Category::Security,
+        Category::Migration,
+        Category::Developer,
+        Category::Workspace,
+        Category::Custom,
+    ];
+
+    /// Returns the display name, e.g., "Identity & Access".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Compute => "Compute",
+            Category::Serverless => "Serverless",
+            Category::Storage => "Storage",
+            Category::Databases => "Databases",
+            Category::Networking => "Networking",
+            Category::Analytics => "Analytics",
+            Category::AiMl => "AI/ML",
+            Category::Integration => "Integration",
+            Category::Operations => "Operations",
+            Category::Management => "Management",
+            Category::IdentityAccess => "Identity & Access",
+            Category::Security => "Security",
+            Category::Migration => "Migration",
+            Category::Developer => "Developer",
+            Category::Workspace => "Workspace",
+            Category::Custom => "Custom",
+        }
+    }
+
+    /// Short names accepted in addition to the display name (e.g., `--category iam`).
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Category::Compute => &["gce", "vm"],
+            Category::Serverless => &["faas"],
+            Category::Storage => &["gcs"],
+            Category::Databases => &["db", "database"],
+            Category::Networking => &["network", "net"],
+            Category::Analytics => &["data"],
+            Category::AiMl => &["ai", "ml"],
+            Category::Integration => &["messaging"],
+            Category::Operations => &["ops", "observability"],
+            Category::Management => &["mgmt"],
+            Category::IdentityAccess => &["iam", "identity"],
+            Category::Security => &["sec"],
+            Category::Migration => &[],
+            Category::Developer => &["dev"],
+            Category::Workspace => &["gws"],
+            Category::Custom => &[],
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = String;
+
+    /// Accepts the display name or an alias, ignoring case, spaces, and symbols (e.g., "ai/ml", "aiml", "ml").
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase()
+        };
+        let given = normalize(s);
+        Category::ALL
+            .into_iter()
+            .find(|category| {
+                normalize(category.as_str()) == given
+                    || category.aliases().iter().any(|alias| *alias == given)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Unknown category '{}'. Valid categories: {}",
+                    s,
+                    Category::ALL.map(|c| c.as_str()).join(", ")
+                )
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for Category {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Category {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl std::fmt::Display for ApiStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiStatus::GA => write!(f, "GA"),
+            ApiStatus::Beta => write!(f, "Beta"),
+            ApiStatus::Deprecated => write!(f, "Deprecated"),
+        }
+    }
 }
 
 impl SupportedApi {
@@ -30,223 +202,606 @@ impl SupportedApi {
             .first()
             .expect("There should be at least one version")
     }
+
+    /// Checks if the API is authenticated with an API key (`--api-key`), i.e., its discovery URL requires the key.
+    pub fn requires_api_key(&self) -> bool {
+        self.discovery_url_template
+            .as_ref()
+            .is_some_and(|template| template.contains("{key}"))
+    }
+
+    /// Expands `discovery_url_template` for APIs not listed in the Discovery Service; returns None for the others.
+    /// Placeholders: {host} ("NAME.googleapis.com"), {name}, {version}, and {key} (the API key, required if present).
+    pub fn discovery_url(
+        &self,
+        version: &str,
+        api_key: Option<&str>,
+    ) -> Option<Result<String, ZgError>> {
+        let template = self.discovery_url_template.as_ref()?;
+        let url = template
+            .replace("{host}", &format!("{}.googleapis.com", self.name))
+            .replace("{name}", &self.name)
+            .replace("{version}", version);
+        Some(match (self.requires_api_key(), api_key) {
+            (true, Some(key)) => Ok(url.replace("{key}", key)),
+            (true, None) => Err(format!(
+                "--api-key is required for standalone API '{}:{}'",
+                self.name, version
+            )
+            .into()),
+            (false, _) => Ok(url),
+        })
+    }
+
+    /// Returns the regional endpoint to send a request for `path` to, expanding {region} in `endpoint_template`
+    /// with the location in the path (e.g., "v1/projects/p/locations/us-central1/endpoints").
+    /// Returns None if the API has no template, or the path has no location or the "global" one. Locations other than
+    /// lowercase letters, digits, and hyphens (or a placeholder like "{locationsId}") are not put in the host either, as
+    /// they come from the user and could point it to another host.
+    pub fn regional_base_url(&self, path: &str) -> Option<String> {
+        let template = self.endpoint_template.as_ref()?;
+        let mut segments = path.split('/');
+        segments.find(|segment| *segment == "locations")?;
+        let location = segments
+            .next()
+            .filter(|location| *location != "global" && is_host_label(location))?;
+        Some(template.replace("{region}", location))
+    }
 }
 
-macro_rules! api {
-    ($name:literal, $title:literal, $category:literal, [$($alias:literal),*], [$($version:literal),+]) => {
-        SupportedApi {
-            name: $name.to_string(),
-            title: $title.to_string(),
-            category: $category.to_string(),
-            aliases: vec![$($alias.to_string()),*],
-            versions: vec![$($version.to_string()),+],
+/// Whether the location can be a part of a host name (`^[a-z0-9-]+$`), or is a placeholder of the path.
+fn is_host_label(location: &str) -> bool {
+    match location
+        .strip_prefix('{')
+        .and_then(|location| location.strip_suffix('}'))
+    {
+        Some(name) => !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()),
+        None => {
+            !location.is_empty()
+                && location
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
         }
+    }
+}
+
+/// Returns the base URL to send a request for `path` to: the regional endpoint if the API serves the location in the path
+/// on a regional host (e.g., "https://us-central1-aiplatform.googleapis.com/"), otherwise the one in the discovery document
+/// (see `ZgApi::base_url_for`).
+pub fn effective_base_url(api: &core::ZgApi, path: &str) -> String {
+    let name = api.id.split(':').next().unwrap_or(&api.id);
+    supported_apis(true)
+        .into_iter()
+        .find(|supported| supported.name == name)
+        .and_then(|supported| supported.regional_base_url(path))
+        .map(|regional| api.base_url_on(&regional, path))
+        .unwrap_or_else(|| api.base_url_for(path))
+}
+
+/// Moves the URL on a `googleapis.com` host (e.g., "https://us-central1-aiplatform.googleapis.com/") to the universe
+/// domain ("https://us-central1-aiplatform.apis.example.com/"), keeping the rest. URLs on other hosts are returned as they are.
+pub fn in_universe(url: &str, universe_domain: &str) -> String {
+    with_googleapis_host(url, |prefix| format!("{}{}", prefix, universe_domain))
+}
+
+/// Moves the URL on a `googleapis.com` host to its mTLS variant (e.g., "https://compute.mtls.googleapis.com/"), which
+/// accepts client certificates for certificate-based access. URLs on other hosts (or already on mTLS ones) are returned
+/// as they are.
+pub fn mtls_url(url: &str) -> String {
+    with_googleapis_host(url, |prefix| match prefix.ends_with("mtls.") {
+        true => format!("{}{}", prefix, config::DEFAULT_UNIVERSE_DOMAIN),
+        false => format!("{}mtls.{}", prefix, config::DEFAULT_UNIVERSE_DOMAIN),
+    })
+}
+
+/// Replaces the host of the URL with `host(prefix)` if it's `googleapis.com` or its subdomain, where `prefix` is the part
+/// before `googleapis.com` (e.g., "compute.").
+fn with_googleapis_host(url: &str, host: impl FnOnce(&str) -> String) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
     };
+    let host_end = rest.find(['/', ':', '?']).unwrap_or(rest.len());
+    let (current, rest) = rest.split_at(host_end);
+    match current.strip_suffix(config::DEFAULT_UNIVERSE_DOMAIN) {
+        Some(prefix) if prefix.is_empty() || prefix.ends_with('.') => {
+            format!("{}://{}{}", scheme, host(prefix), rest)
+        }
+        _ => url.to_string(),
+    }
 }
 
-/// List of APIs that zygen support (undocumented versions are excluded).
-/// Categories are based on: https://cloud.google.com/terms/services, https://console.cloud.google.com/products, and console UI
-#[rustfmt::skip]
-static PRIMARY_SUPPORTED_APIS: LazyLock<Vec<SupportedApi>> = LazyLock::new(||
-    vec![
-        api!("accessapproval"         , "Access Approval"                               , "Identity & Access", ["access-approval"]             , ["v1"]),
-        api!("accesscontextmanager"   , "Access Context Manager"                        , "Identity & Access", ["acm"]                         , ["v1"]),
-        api!("aiplatform"             , "Vertex AI"                                     , "AI/ML"            , ["vertex", "ai"]                , ["v1beta1", "v1"]),
-        api!("alloydb"                , "AlloyDB"                                       , "Databases"        , ["alloy"]                       , ["v1beta", "v1"]),
-        api!("apigateway"             , "API Gateway"                                   , "Serverless"       , ["api-gateway"]                 , ["v1beta", "v1"]),
-        api!("apigee"                 , "Apigee"                                        , "Integration"      , []                              , ["v1"]),
-        api!("appengine"              , "App Engine Admin"                              , "Serverless"       , ["app"]                         , ["v1", "v1beta"]),
-        api!("artifactregistry"       , "Artifact Registry"                             , "Developer"        , ["artifacts"]                   , ["v1"]),
-        api!("assuredworkloads"       , "Assured Workloads"                             , "Security"         , ["assured-workloads"]           , ["v1", "v1beta1"]),
-        api!("backupdr"               , "Google Cloud Backup and DR"                    , "Operations"       , ["backup-dr"]                   , ["v1"]),
-        api!("baremetalsolution"      , "Bare Metal Solution"                           , "Compute"          , ["bms"]                         , ["v2"]),
-        api!("batch"                  , "Batch"                                         , "Compute"          , []                              , ["v1"]),
-        api!("bigquery"               , "BigQuery"                                      , "Analytics"        , ["bq"]                          , ["v2"]),
-        api!("bigtableadmin"          , "Cloud Bigtable Admin"                          , "Databases"        , ["bigtable"]                    , ["v2"]),
-        api!("binaryauthorization"    , "Binary Authorization"                          , "Security"         , ["binary-auth"]                 , ["v1", "v1beta1"]),
-        api!("blockchainnodeengine"   , "Blockchain Node Engine"                        , "Compute"          , ["bne", "blockchain"]           , ["v1"]),
-        api!("certificatemanager"     , "Certificate Manager"                           , "Security"         , ["certificate-manager", "cert"] , ["v1"]),
-        api!("cloudasset"             , "Cloud Asset"                                   , "Management"       , ["asset"]                       , ["v1", "v1p1beta1", "v1p7beta1"]),
-        api!("cloudbuild"             , "Cloud Build"                                   , "Developer"        , ["build"]                       , ["v1", "v2"]),
-        api!("clouddeploy"            , "Cloud Deploy"                                  , "Developer"        , ["deploy"]                      , ["v1"]),
-        api!("cloudfunctions"         , "Cloud Run functions"                           , "Serverless"       , ["functions", "func"]           , ["v2", "v2beta", "v2alpha", "v1"]), // formerly Cloud Functions
-        api!("cloudidentity"          , "Cloud Identity"                                , "Identity & Access", ["identity"]                    , ["v1", "v1beta1"]),
-        api!("cloudkms"               , "Cloud Key Management Service"                  , "Security"         , ["kms"]                         , ["v1"]),
-        api!("cloudprofiler"          , "Cloud Profiler"                                , "Operations"       , ["profiler"]                    , ["v2"]),
-        api!("cloudresourcemanager"   , "Cloud Resource Manager"                        , "Management"       , ["resource-manager", "resource"], ["v3", "v2", "v2beta1", "v1", "v1beta1"]),
-        api!("cloudscheduler"         , "Cloud Scheduler"                               , "Integration"      , ["scheduler"]                   , ["v1", "v1beta1"]),
-        api!("cloudshell"             , "Cloud Shell"                                   , "Management"       , ["shell"]                       , ["v1"]),
-        api!("cloudtasks"             , "Cloud Tasks"                                   , "Integration"      , ["tasks"]                       , ["v2", "v2beta3"]),
-        api!("cloudtrace"             , "Cloud Trace"                                   , "Operations"       , ["trace"]                       , ["v2", "v2beta1", "v1"]),
-        api!("composer"               , "Cloud Composer"                                , "Analytics"        , []                              , ["v1beta1", "v1"]),
-        api!("compute"                , "Compute Engine"                                , "Compute"          , ["gce"]                         , ["v1", "beta"]),
-        api!("contactcenteraiplatform", "Conversational AI"                             , "AI/ML"            , ["conv-ai", "ccai"]             , ["v1alpha1"]), // formerly Contact Center AI (CCAI)
-        api!("container"              , "Google Kubernetes Engine"                      , "Compute"          , ["gke"]                         , ["v1", "v1beta1"]),
-        api!("datacatalog"            , "Google Cloud Data Catalog"                     , "Analytics"        , ["data-catalog"]                , ["v1", "v1beta1"]),
-        api!("dataflow"               , "Dataflow"                                      , "Analytics"        , []                              , ["v1b3"]),
-        api!("dataform"               , "Dataform"                                      , "Analytics"        , []                              , ["v1beta1"]),
-        api!("datafusion"             , "Cloud Data Fusion"                             , "Analytics"        , ["data-fusion"]                 , ["v1beta1", "v1"]),
-        api!("datamigration"          , "Database Migration Service"                    , "Migration"        , ["dms"]                         , ["v1", "v1beta1"]),
-        api!("dataplex"               , "Cloud Dataplex"                                , "Analytics"        , []                              , ["v1"]),
-        api!("dataproc"               , "Cloud Dataproc"                                , "Analytics"        , []                              , ["v1"]),
-        api!("datastore"              , "Cloud Datastore"                               , "Databases"        , []                              , ["v1"]),
-        api!("datastream"             , "Datastream"                                    , "Analytics"        , []                              , ["v1"]),
-        api!("deploymentmanager"      , "Cloud Deployment Manager"                      , "Management"       , ["deployment-manager"]          , ["v2", "v2beta"]),
-        api!("developerconnect"       , "Developer Connect"                             , "Developer"        , ["developer-connect"]           , ["v1"]),
-        api!("dlp"                    , "Cloud Data Loss Prevention"                    , "Security"         , []                              , ["v2"]),
-        api!("dns"                    , "Cloud DNS"                                     , "Networking"       , []                              , ["v1", "v1beta2"]),
-        api!("documentai"             , "Cloud Document AI"                             , "AI/ML"            , ["doc-ai"]                      , ["v1", "v1beta3"]),
-        api!("eventarc"               , "Eventarc"                                      , "Serverless"       , []                              , ["v1"]),
-        api!("file"                   , "Cloud Filestore"                               , "Storage"          , []                              , ["v1", "v1beta1"]),
-        api!("firestore"              , "Cloud Firestore"                               , "Databases"        , []                              , ["v1", "v1beta1", "v1beta2"]),
-        api!("healthcare"             , "Cloud Healthcare"                              , "Analytics"        , []                              , ["v1", "v1beta1"]),
-        api!("iam"                    , "Identity and Access Management"                , "Identity & Access", []                              , ["v1", "v2"]),
-        api!("iap"                    , "Cloud Identity-Aware Proxy"                    , "Identity & Access", []                              , ["v1", "v1beta1"]),
-        api!("ids"                    , "Cloud Intrusion Detection System"              , "Security"         , []                              , ["v1"]),
-        api!("language"               , "Cloud Natural Language"                        , "AI/ML"            , []                              , ["v2", "v1", "v1beta2"]),
-        api!("lifesciences"           , "Cloud Life Sciences"                           , "Analytics"        , []                              , ["v2beta"]), // formerly Google Genomics
-        api!("logging"                , "Cloud Logging"                                 , "Operations"       , ["log"]                         , ["v2"]),
-        api!("looker"                 , "Looker (Google Cloud core)"                    , "Analytics"        , []                              , ["v1"]),
-        api!("managedidentities"      , "Managed Service for Microsoft Active Directory", "Identity & Access", ["managed-ad"]                  , ["v1", "v1beta1"]),
-        api!("migrationcenter"        , "Migration Center"                              , "Migration"        , ["migration-center"]            , ["v1", "v1alpha1"]),
-        api!("monitoring"             , "Cloud Monitoring"                              , "Operations"       , ["mon"]                         , ["v3", "v1"]),
-        api!("networkconnectivity"    , "Network Connectivity Center"                   , "Networking"       , ["ncc"]                         , ["v1", "v1alpha1"]),
-        api!("networkmanagement"      , "Network Intelligence Center"                   , "Networking"       , ["network-management"]          , ["v1", "v1beta1"]),
-        api!("orgpolicy"              , "Organization Policy"                           , "Management"       , []                              , ["v2"]),
-        api!("privateca"              , "Certificate Authority Service"                 , "Security"         , ["cas", "private-ca"]           , ["v1"]),
-        api!("pubsub"                 , "Cloud Pub/Sub"                                 , "Analytics"        , []                              , ["v1"]),
-        api!("recaptchaenterprise"    , "Google Cloud reCAPTCHA Enterprise"             , "Security"         , ["recaptcha"]                   , ["v1"]),
-        api!("recommender"            , "Recommender"                                   , "Management"       , []                              , ["v1", "v1beta1"]),
-        api!("redis"                  , "Memorystore for Redis"                         , "Databases"        , []                              , ["v1", "v1beta1"]),
-        api!("run"                    , "Cloud Run Admin"                               , "Serverless"       , ["cloudrun"]                    , ["v2", "v1"]),
-        api!("secretmanager"          , "Secret Manager"                                , "Security"         , ["secret"]                      , ["v1", "v1beta1"]),
-        api!("securitycenter"         , "Security Command Center"                       , "Security"         , ["scc"]                         , ["v1", "v1beta2", "v1beta1"]),
-        api!("servicedirectory"       , "Service Directory"                             , "Networking"       , ["service-directory"]           , ["v1", "v1beta1"]),
-        api!("serviceusage"           , "Service Usage"                                 , "Management"       , ["service", "svc"]              , ["v1beta1", "v1"]),
-        api!("spanner"                , "Cloud Spanner"                                 , "Databases"        , ["span"]                        , ["v1"]),
-        api!("sqladmin"               , "Cloud SQL Admin"                               , "Databases"        , ["sql"]                         , ["v1beta4", "v1"]),
-        api!("storage"                , "Cloud Storage"                                 , "Storage"          , ["gs", "gcs"]                   , ["v1"]),
-        api!("storagetransfer"        , "Storage Transfer Service"                      , "Migration"        , ["storage-transfer"]            , ["v1"]),
-        api!("trafficdirector"        , "Traffic Director (Cloud Service Mesh)"         , "Networking"       , ["traffic-director"]            , ["v2", "v3"]),
-        api!("transcoder"             , "Transcoder"                                    , "Compute"          , []                              , ["v1"]),
-        api!("translate"              , "Cloud Translation"                             , "AI/ML"            , []                              , ["v3", "v3beta1"]),
-        api!("videointelligence"      , "Cloud Video Intelligence"                      , "AI/ML"            , ["video-intelligence"]          , ["v1", "v1p3beta1"]),
-        api!("vision"                 , "Cloud Vision"                                  , "AI/ML"            , []                              , ["v1"]),
-        api!("vmmigration"            , "Migrate to Virtual Machines (VM Migration)"    , "Migration"        , ["vm-migration"]                , ["v1"]),
-        api!("vmwareengine"           , "Google Cloud VMware Engine (GCVE)"             , "Compute"          , ["gcve"]                        , ["v1"]),
-        api!("webrisk"                , "Web Risk"                                      , "Security"         , []                              , ["v1"]),
-        api!("websecurityscanner"     , "Web Security Scanner"                          , "Security"         , ["web-security-scanner"]        , ["v1", "v1beta"]),
-        api!("workflows"              , "Workflows"                                     , "Serverless"       , []                              , ["v1", "v1beta"]),
-        api!("workloadmanager"        , "Workload Manager"                              , "Compute"          , ["wlm"]                         , ["v1"]),
-        api!("workstations"           , "Cloud Workstations"                            , "Developer"        , []                              , ["v1", "v1beta"]),
-    ]
-);
-
-/// List of APIs that zygen support (undocumented versions are excluded), but
-///   not explicitly mentioned in https://cloud.google.com/terms/services,
-///   or a larger scope service is already included in in PRIMARY, or direct API access is uncommon.
-#[rustfmt::skip]
-static SECONDARY_SUPPORTED_APIS: LazyLock<Vec<SupportedApi>> = LazyLock::new(||
-    vec![
-        api!("advisorynotifications"    , "Advisory Notifications"                , "Security"         , ["advisory-notifications"]                 , ["v1"]),
-        api!("analyticshub"             , "BigQuery Analytics Hub"                , "Analytics"        , ["analytics-hub"]                          , ["v1", "v1beta1"]),
-        api!("apigeeregistry"           , "Apigee Registry"                       , "Integration"      , ["apigee-registry"]                        , ["v1"]),
-        api!("apikeys"                  , "API Keys"                              , "Management"       , []                                         , ["v2"]),
-        api!("apim"                     , "Apigee API Management (Observation)"   , "Integration"      , []                                         , ["v1alpha"]),
-        api!("apphub"                   , "App Hub"                               , "Operations"       , []                                         , ["v1", "v1alpha"]),
-        api!("beyondcorp"               , "Beyondcorp (Chrome Enterprise Premium)", "Security"         , []                                         , ["v1"]),
-        api!("biglake"                  , "BigLake"                               , "Analytics"        , []                                         , ["v1"]),
-        api!("bigqueryconnection"       , "BigQuery Connection"                   , "Analytics"        , ["bq-connection"]                          , ["v1", "v1beta1"]),
-        api!("bigquerydatapolicy"       , "BigQuery Data Policy"                  , "Analytics"        , ["bq-policy"]                              , ["v1"]),
-        api!("bigquerydatatransfer"     , "BigQuery Data Transfer Service"        , "Migration"        , ["bq-dts"]                                 , ["v1"]),
-        api!("bigqueryreservation"      , "BigQuery Reservation"                  , "Analytics"        , ["bq-reservation"]                         , ["v1"]),
-        api!("billingbudgets"           , "Cloud Billing Budget"                  , "Management"       , ["billing-budgets"]                        , ["v1", "v1beta1"]),
-        api!("cloudbilling"             , "Cloud Billing"                         , "Management"       , ["billing"]                                , ["v1beta", "v1"]),
-        api!("cloudchannel"             , "Cloud Channel"                         , "Management"       , []                                         , ["v1"]),
-        api!("cloudcontrolspartner"     , "Cloud Controls Partner"                , "Management"       , []                                         , ["v1", "v1beta"]),
-        api!("clouderrorreporting"      , "Error Reporting"                       , "Operations"       , ["error-reporting"]                        , ["v1beta1"]),
-        api!("cloudsupport"             , "Google Cloud Support"                  , "Management"       , ["support"]                                , ["v2", "v2beta"]),
-        api!("config"                   , "Infrastructure Manager"                , "Management"       , ["infra-manager"]                          , ["v1"]),
-        api!("connectors"               , "Integration Connectors"                , "Integration"      , []                                         , ["v1"]),
-        api!("contactcenterinsights"    , "Conversational Insights"               , "AI/ML"            , ["conv-insights", "ccai-insights"]         , ["v1"]), // formerly Contact Center AI Insights
-        api!("containeranalysis"        , "Container Analysis"                    , "Security"         , ["container-analysis", "artifact-analysis"], ["v1", "v1beta1"]),
-        api!("contentwarehouse"         , "Document AI Warehouse"                 , "AI/ML"            , ["doc-ai-warehouse"]                       , ["v1"]),
-        api!("datalineage"              , "Data Lineage"                          , "Analytics"        , ["data-lineage"]                           , ["v1"]),
-        api!("datapipelines"            , "Data pipelines"                        , "Analytics"        , ["data-pipelines"]                         , ["v1"]),
-        api!("dialogflow"               , "Dialogflow"                            , "AI/ML"            , []                                         , ["v3", "v3beta1", "v2", "v2beta1"]),
-        api!("discoveryengine"          , "Vertex AI Agent Builder"               , "AI/ML"            , ["discovery-engine", "agent-builder"]      , ["v1", "v1beta", "v1alpha"]),
-        api!("domains"                  , "Cloud Domains"                         , "Networking"       , []                                         , ["v1", "v1beta1"]),
-        api!("essentialcontacts"        , "Essential Contacts"                    , "Management"       , ["essential-contacts"]                     , ["v1"]),
-        api!("gkebackup"                , "Backup for GKE"                        , "Storage"          , ["gke-backup"]                             , ["v1"]),
-        api!("gkehub"                   , "GKE Hub (Fleet)"                       , "Compute"          , ["gke-hub", "fleet"]                       , ["v2", "v1beta1", "v2beta", "v2alpha", "v1", "v1beta", "v1alpha"]),
-        api!("gkeonprem"                , "Google Distributed Cloud (GDC) Virtual", "Compute"          , ["gke-onprem"]                             , ["v1"]),
-        api!("iamcredentials"           , "IAM Service Account Credentials"       , "Identity & Access", ["iam-credentials"]                        , ["v1"]),
-        api!("identitytoolkit"          , "Identity Toolkit"                      , "Identity & Access", ["identity-toolkit"]                       , ["v2", "v1"]),
-        api!("integrations"             , "Application Integration"               , "Integration"      , []                                         , ["v1"]),
-        api!("jobs"                     , "Cloud Talent Solution"                 , "AI/ML"            , ["talent-solution"]                        , ["v3", "v3p1beta1"]),
-        api!("kmsinventory"             , "KMS Inventory"                         , "Security"         , ["kms-inventory"]                          , ["v1"]),
-        api!("memcache"                 , "Memorystore for Memcached"             , "Databases"        , []                                         , ["v1", "v1beta2"]),
-        api!("metastore"                , "Dataproc Metastore"                    , "Analytics"        , ["dataproc-metastore"]                     , ["v1", "v1beta", "v1alpha"]),
-        api!("networksecurity"          , "Network Security (Service Mesh)"       , "Networking"       , ["network-security"]                       , ["v1beta1"]),
-        api!("networkservices"          , "Network Services (Service Mesh)"       , "Networking"       , ["network-services"]                       , ["v1", "v1beta1"]),
-        api!("notebooks"                , "Vertex AI Workbench Notebooks"         , "AI/ML"            , []                                         , ["v1", "v2"]),
-        api!("ondemandscanning"         , "On-Demand Scanning"                    , "Security"         , ["ondemand-scanning"]                      , ["v1"]),
-        api!("oracledatabase"           , "Oracle Database@Google Cloud"          , "Databases"        , ["oracle-database"]                        , ["v1"]),
-        api!("osconfig"                 , "OS Config"                             , "Management"       , ["os-config"]                              , ["v1", "v1beta", "v1alpha", "v2beta"]),
-        api!("oslogin"                  , "Cloud OS Login"                        , "Security"         , ["os-login"]                               , ["v1", "v1beta", "v1alpha"]),
-        api!("policysimulator"          , "Policy Simulator"                      , "Security"         , ["policy-simulator"]                       , ["v1", "v1beta"]),
-        api!("policytroubleshooter"     , "Policy Troubleshooter"                 , "Management"       , ["policy-troubleshooter"]                  , ["v1"]),
-        api!("publicca"                 , "Public Certificate Authority"          , "Security"         , ["public-ca"]                              , ["v1"]),
-        api!("pubsublite"               , "Pub/Sub Lite"                          , "Analytics"        , ["pubsub-lite"]                            , ["v1"]),
-        api!("rapidmigrationassessment" , "Rapid Migration Assessment"            , "Migration"        , ["ramp"]                                   , ["v1"]),
-        api!("recommendationengine"     , "Recommendations AI"                    , "AI/ML"            , ["recommendation-engine"]                  , ["v1beta1"]),
-        api!("retail"                   , "Vertex AI Search for Retail"           , "AI/ML"            , []                                         , ["v2", "v2beta", "v2alpha"]),
-        api!("runtimeconfig"            , "Cloud Runtime Configuration"           , "Management"       , ["runtime-config"]                         , ["v1beta1"]),
-        api!("serviceconsumermanagement", "Service Consumer Management"           , "Management"       , ["service-consumer-management"]            , ["v1", "v1beta1"]),
-        api!("servicecontrol"           , "Service Control"                       , "Management"       , ["service-control"]                        , ["v2", "v1"]),
-        api!("servicemanagement"        , "Service Management"                    , "Management"       , ["service-management"]                     , ["v1"]),
-        api!("servicenetworking"        , "Service Networking"                    , "Networking"       , ["service-networking"]                     , ["v1"]),
-        api!("speech"                   , "Cloud Speech-to-Text"                  , "AI/ML"            , ["speech-to-text"]                         , ["v1", "v1p1beta1"]),
-        api!("sts"                      , "Security Token Service"                , "Security"         , []                                         , ["v1"]),
-        api!("texttospeech"             , "Cloud Text-to-Speech"                  , "AI/ML"            , ["text-to-speech"]                         , ["v1", "v1beta1"]),
-        api!("tpu"                      , "Cloud TPU"                             , "Compute"          , []                                         , ["v2", "v2alpha1", "v1", "v1alpha1"]),
-        api!("vpcaccess"                , "Serverless VPC Access"                 , "Networking"       , ["vpc-access"]                             , ["v1", "v1beta1"]),
-        api!("workflowexecutions"       , "Workflow Executions"                   , "Serverless"       , ["workflow-executions"]                    , ["v1", "v1beta"]),
-    ]
-);
-
-/// List of APIs that are not included in the response of the Discovery API (`discovery::DISCOVERY_URL`).
-/// zygen will download these API definitions when needed through `core::lazy_prep_api_file``.
-#[rustfmt::skip]
-static STANDALONE_DISCOVERY_APIS: LazyLock<Vec<SupportedApi>> = LazyLock::new(||
-    vec![
-        api!("generativelanguage", "Gemini", "AI/ML", ["gemini"], ["v1beta"]),
-    ]
-);
-
-/// Returns a list of supported APIs.
-/// If `all_apis` is true, it includes all APIs, otherwise only the primary and the standalone APIs.
+/// Catalog of the APIs built into zygen, embedded from supported_apis.toml.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Catalog {
+    /// APIs listed by default.
+    primary: Vec<SupportedApi>,
+    /// APIs listed with `--all`; less common, or covered by a larger scope service in primary.
+    secondary: Vec<SupportedApi>,
+    /// Google Workspace APIs, listed with `--workspace` or `--all`.
+    workspace: Vec<SupportedApi>,
+    /// APIs that are not included in the response of the Discovery API (`discovery::DISCOVERY_URL`).
+    /// zygen will download these API definitions when needed through `core::lazy_prep_api_file`.
+    standalone: Vec<SupportedApi>,
+}
+
+static CATALOG: LazyLock<Catalog> = LazyLock::new(|| {
+    let catalog: Catalog = toml::from_str(include_str!("supported_apis.toml"))
+        .expect("The embedded supported_apis.toml should be valid");
+    catalog
+});
+
+/// Extra entries loaded from `supported_apis.d/*.toml` in the config directory at startup.
+static EXTRA_APIS: OnceLock<Vec<SupportedApi>> = OnceLock::new();
+
+/// Directory of the user-provided catalog files, which add entries to the supported APIs without recompiling.
+const EXTRA_APIS_DIR: &str = "supported_apis.d";
+
+/// A user-provided catalog file, e.g., `supported_apis.d/my_apis.toml`:
+///
+/// ```toml
+/// [[apis]]
+/// name = "firebasehosting"
+/// title = "Firebase Hosting"
+/// category = "Developer"
+/// aliases = ["hosting"]
+/// versions = ["v1", "v1beta1"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtraApisFile {
+    apis: Vec<SupportedApi>,
+}
+
+/// Returns the path of the directory of the user-provided catalog files.
+pub fn extra_apis_dir() -> PathBuf {
+    core::config_dir().join(EXTRA_APIS_DIR)
+}
+
+/// Loads the `*.toml` files in the directory (in the order of file names), validating them against the built-in APIs and each other.
+/// Returns an empty list if the directory doesn't exist.
+pub fn load_extra_apis(dir: &Path) -> Result<Vec<SupportedApi>, ZgError> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    paths.sort();
+
+    let mut apis = builtin_apis(true);
+    let builtin_count = apis.len();
+    for path in paths {
+        let invalid = |message: String| ZgError::InvalidConfig {
+            path: path.display().to_string(),
+            message,
+        };
+        let content = fs::read_to_string(&path)?;
+        let file: ExtraApisFile =
+            toml::from_str(&content).map_err(|e| invalid(e.to_string().trim_end().to_string()))?;
+        debug!("Loaded {} extra APIs from {:?}", file.apis.len(), path);
+        apis.extend(file.apis);
+        validate(&apis).map_err(invalid)?;
+    }
+    Ok(apis.split_off(builtin_count))
+}
+
+/// Sets the extra APIs available through `supported_apis()`. Only the first call takes effect.
+pub fn init_extra_apis(apis: Vec<SupportedApi>) {
+    let _ = EXTRA_APIS.set(apis);
+}
+
+/// Checks that every API has at least one version, and that names and aliases don't overlap.
+/// The error message names both of the conflicting APIs.
+pub fn validate(apis: &[SupportedApi]) -> Result<(), String> {
+    // Map to track all names and aliases to their corresponding service names
+    let mut name_to_service: HashMap<&str, &str> = HashMap::new();
+    for api in apis {
+        if api.versions.is_empty() {
+            return Err(format!("API '{}' has no versions", api.name));
+        }
+        if let Some(existing) = name_to_service.insert(&api.name, &api.name) {
+            return Err(match existing == api.name {
+                true => format!("API '{}' is defined more than once", api.name),
+                false => format!(
+                    "API name '{}' conflicts with an alias of '{}'",
+                    api.name, existing
+                ),
+            });
+        }
+        for alias in &api.aliases {
+            if let Some(existing) = name_to_service.insert(alias, &api.name) {
+                return Err(format!(
+                    "Alias '{}' of '{}' conflicts with the name or an alias of '{}'",
+                    alias, api.name, existing
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns a list of supported APIs, including the extra APIs in `supported_apis.d` and the custom APIs registered in the config file (`[custom_apis]`).
+/// If `all_apis` is true, it includes all APIs (including Google Workspace APIs), otherwise only the primary, the standalone, the extra, and the custom APIs.
 pub fn supported_apis(all_apis: bool) -> Vec<SupportedApi> {
-    let mut apis = PRIMARY_SUPPORTED_APIS.to_vec();
+    let mut apis = builtin_apis(all_apis);
+    apis.extend(EXTRA_APIS.get().into_iter().flatten().cloned());
+    apis.extend(config::current().custom_supported_apis());
+    apis
+}
+
+/// Returns a list of APIs built into zygen, excluding the extra and the custom APIs.
+pub fn builtin_apis(all_apis: bool) -> Vec<SupportedApi> {
+    let mut apis = CATALOG.primary.clone();
     match all_apis {
         true => {
-            apis.extend(SECONDARY_SUPPORTED_APIS.iter().cloned());
-            apis.extend(STANDALONE_DISCOVERY_APIS.iter().cloned());
+            apis.extend(CATALOG.secondary.iter().cloned());
+            apis.extend(CATALOG.workspace.iter().cloned());
+            apis.extend(CATALOG.standalone.iter().cloned());
         }
-        false => apis.extend(STANDALONE_DISCOVERY_APIS.iter().cloned()),
+        false => apis.extend(CATALOG.standalone.iter().cloned()),
     }
     apis
 }
 
+/// Returns a list of Google Workspace APIs.
+pub fn workspace_apis() -> Vec<SupportedApi> {
+    CATALOG.workspace.clone()
+}
+
+/// Checks if the service (name, not alias) is a Google Workspace API.
+pub fn is_workspace_api(name: &str) -> bool {
+    CATALOG.workspace.iter().any(|api| api.name == name)
+}
+
 /// Returns a list of standalone APIs that are not included in the response of the Discovery API.
+/// Besides the built-in ones, any supported API with `discovery_url_template` (e.g., in `supported_apis.d`) is standalone.
 pub fn standalone_apis() -> Vec<SupportedApi> {
-    STANDALONE_DISCOVERY_APIS.to_vec()
+    supported_apis(true)
+        .into_iter()
+        .filter(|api| api.discovery_url_template.is_some())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Creates an empty temporary directory for the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zg-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_builtin_catalog() {
+        assert!(validate(&builtin_apis(true)).is_ok());
+        assert_eq!(standalone_apis()[0].name, "generativelanguage");
+        assert!(builtin_apis(false)
+            .iter()
+            .all(|api| !is_workspace_api(&api.name)));
+    }
+
+    #[test]
+    fn test_discovery_url() {
+        let gemini = standalone_apis()
+            .into_iter()
+            .find(|api| api.name == "generativelanguage")
+            .unwrap();
+        assert!(gemini.requires_api_key());
+        assert_eq!(
+            gemini
+                .discovery_url("v1beta", Some("KEY123"))
+                .unwrap()
+                .unwrap(),
+            "https://generativelanguage.googleapis.com/$discovery/rest?version=v1beta&key=KEY123"
+        );
+        let err = gemini.discovery_url("v1beta", None).unwrap().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--api-key is required for standalone API 'generativelanguage:v1beta'"
+        );
+
+        // Templates without {key} don't require the key
+        let selfhosted = SupportedApi {
+            name: "myapi".to_string(),
+            discovery_url_template: Some(
+                "https://api.example.com/{name}/{version}/$discovery".to_string(),
+            ),
+            ..gemini.clone()
+        };
+        assert!(!selfhosted.requires_api_key());
+        assert_eq!(
+            selfhosted.discovery_url("v2", None).unwrap().unwrap(),
+            "https://api.example.com/myapi/v2/$discovery"
+        );
+
+        // APIs listed in the Discovery Service have no template
+        let compute = builtin_apis(false)
+            .into_iter()
+            .find(|api| api.name == "compute")
+            .unwrap();
+        assert!(compute.discovery_url("v1", None).is_none());
+    }
+
+    #[test]
+    fn test_regional_base_url() {
+        let find = |name: &str| {
+            builtin_apis(false)
+                .into_iter()
+                .find(|api| api.name == name)
+                .unwrap()
+        };
+        let aiplatform = find("aiplatform");
+        assert_eq!(
+            aiplatform
+                .regional_base_url("v1/projects/p/locations/us-central1/endpoints")
+                .unwrap(),
+            "https://us-central1-aiplatform.googleapis.com/"
+        );
+        // The placeholder is kept as is, e.g., for `zg desc`
+        assert_eq!(
+            aiplatform
+                .regional_base_url("v1/projects/{projectsId}/locations/{locationsId}/endpoints")
+                .unwrap(),
+            "https://{locationsId}-aiplatform.googleapis.com/"
+        );
+        assert!(aiplatform
+            .regional_base_url("v1/projects/p/locations/global/publishers")
+            .is_none());
+        assert!(aiplatform
+            .regional_base_url("v1/publishers/google")
+            .is_none());
+        assert!(aiplatform
+            .regional_base_url("v1/projects/p/locations")
+            .is_none());
+        // Locations that could move the request to another host are not substituted
+        for location in [
+            "x.evil.com#",
+            "evil.com%2F",
+            "US-CENTRAL1",
+            "{x.evil.com#}",
+            "{}",
+        ] {
+            assert!(
+                aiplatform
+                    .regional_base_url(&format!("v1/projects/p/locations/{}/endpoints", location))
+                    .is_none(),
+                "{}",
+                location
+            );
+        }
+
+        // APIs without endpoint_template always use the base_url
+        assert!(find("run")
+            .regional_base_url("v2/projects/p/locations/us-central1/jobs")
+            .is_none());
+    }
+
+    #[test]
+    fn test_in_universe() {
+        let universe = "apis.example-universe.com";
+        assert_eq!(
+            in_universe("https://container.googleapis.com/", universe),
+            "https://container.apis.example-universe.com/"
+        );
+        // Regional endpoints, with the placeholder of `zg desc` kept
+        assert_eq!(
+            in_universe("https://us-central1-aiplatform.googleapis.com/", universe),
+            "https://us-central1-aiplatform.apis.example-universe.com/"
+        );
+        assert_eq!(
+            in_universe("https://{locationsId}-aiplatform.googleapis.com/", universe),
+            "https://{locationsId}-aiplatform.apis.example-universe.com/"
+        );
+        // Ports, paths, and queries are kept
+        assert_eq!(
+            in_universe(
+                "https://discovery.googleapis.com:443/discovery/v1/apis?name=x.googleapis.com",
+                universe
+            ),
+            "https://discovery.apis.example-universe.com:443/discovery/v1/apis?name=x.googleapis.com"
+        );
+        // Other hosts
+        for url in [
+            "https://oauth2.example.com/v1/",
+            "https://notgoogleapis.com/",
+            "http://localhost:8085/",
+            "file:///tmp/doc.json",
+            "not a url",
+        ] {
+            assert_eq!(in_universe(url, universe), url);
+        }
+    }
+
+    #[test]
+    fn test_mtls_url() {
+        assert_eq!(
+            mtls_url("https://compute.googleapis.com/compute/v1/projects/p1/zones?maxResults=1"),
+            "https://compute.mtls.googleapis.com/compute/v1/projects/p1/zones?maxResults=1"
+        );
+        assert_eq!(
+            mtls_url("https://us-central1-aiplatform.googleapis.com/v1/"),
+            "https://us-central1-aiplatform.mtls.googleapis.com/v1/"
+        );
+        // Already on the mTLS host, or on other hosts
+        for url in [
+            "https://compute.mtls.googleapis.com/",
+            "https://private.example.com/",
+            "http://localhost:8085/",
+        ] {
+            assert_eq!(mtls_url(url), url);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let api = |name: &str, aliases: &[&str]| SupportedApi {
+            name: name.to_string(),
+            title: name.to_string(),
+            category: Category::Custom,
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            versions: vec!["v1".to_string()],
+            status: ApiStatus::GA,
+            notes: None,
+            discovery_url_template: None,
+            endpoint_template: None,
+        };
+        assert!(validate(&[api("a", &["x"]), api("b", &["y"])]).is_ok());
+        assert_eq!(
+            validate(&[api("a", &[]), api("a", &[])]).unwrap_err(),
+            "API 'a' is defined more than once"
+        );
+        assert_eq!(
+            validate(&[api("a", &["x"]), api("b", &["x"])]).unwrap_err(),
+            "Alias 'x' of 'b' conflicts with the name or an alias of 'a'"
+        );
+        assert_eq!(
+            validate(&[api("a", &["b"]), api("b", &[])]).unwrap_err(),
+            "API name 'b' conflicts with an alias of 'a'"
+        );
+        let no_versions = SupportedApi {
+            versions: vec![],
+            ..api("a", &[])
+        };
+        assert_eq!(
+            validate(&[no_versions]).unwrap_err(),
+            "API 'a' has no versions"
+        );
+    }
+
+    #[test]
+    fn test_load_extra_apis() {
+        let dir = temp_dir("extra-apis");
+        assert!(load_extra_apis(&dir.join("missing")).unwrap().is_empty());
+
+        fs::write(
+            dir.join("10_firebase.toml"),
+            r#"
+            [[apis]]
+            name = "firebasehosting"
+            title = "Firebase Hosting"
+            category = "Developer"
+            aliases = ["hosting"]
+            versions = ["v1", "v1beta1"]
+            status = "Beta"
+            "#,
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "not a catalog file").unwrap();
+
+        let apis = load_extra_apis(&dir).unwrap();
+        assert_eq!(apis.len(), 1);
+        assert_eq!(apis[0].name, "firebasehosting");
+        assert_eq!(apis[0].default_version(), "v1");
+        assert_eq!(apis[0].status, ApiStatus::Beta);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_extra_apis_malformed() {
+        let dir = temp_dir("extra-apis-malformed");
+        let load_error = |content: &str| {
+            fs::write(dir.join("apis.toml"), content).unwrap();
+            match load_extra_apis(&dir) {
+                Err(ZgError::InvalidConfig { path, message }) => {
+                    assert!(path.ends_with("apis.toml"));
+                    message
+                }
+                other => panic!("Expected InvalidConfig, got {:?}", other),
+            }
+        };
+
+        // Syntax error, pointing to the line
+        let message = load_error("[[apis]]\nname = \"x\n");
+        assert!(message.contains("line 2"), "{}", message);
+
+        // Missing and unknown fields
+        let message = load_error("[[apis]]\nname = \"x\"\ntitle = \"X\"\ncategory = \"Custom\"\n");
+        assert!(message.contains("missing field `versions`"), "{}", message);
+        let message = load_error(
+            "[[apis]]\nname = \"x\"\ntitle = \"X\"\ncategory = \"Custom\"\nversions = [\"v1\"]\nalias = [\"y\"]\n",
+        );
+        assert!(message.contains("unknown field `alias`"), "{}", message);
+
+        // Invalid status
+        let message = load_error(
+            "[[apis]]\nname = \"x\"\ntitle = \"X\"\ncategory = \"Custom\"\nversions = [\"v1\"]\nstatus = \"Alpha\"\n",
+        );
+        assert!(message.contains("unknown variant `Alpha`"), "{}", message);
+
+        // Conflicts with a built-in API
+        let message = load_error(
+            "[[apis]]\nname = \"x\"\ntitle = \"X\"\ncategory = \"Custom\"\nversions = [\"v1\"]\naliases = [\"gke\"]\n",
+        );
+        assert_eq!(
+            message,
+            "Alias 'gke' of 'x' conflicts with the name or an alias of 'container'"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_category_from_str() {
+        for (given, expected) in [
+            ("Identity & Access", Category::IdentityAccess),
+            ("identity-access", Category::IdentityAccess),
+            ("iam", Category::IdentityAccess),
+            ("AI/ML", Category::AiMl),
+            ("ml", Category::AiMl),
+            ("DB", Category::Databases),
+            ("workspace", Category::Workspace),
+        ] {
+            assert_eq!(given.parse::<Category>(), Ok(expected), "{}", given);
+        }
+        let err = "Databse".parse::<Category>().unwrap_err();
+        assert!(err.contains("Unknown category 'Databse'"));
+        assert!(err.contains("Identity & Access"));
+
+        // Display names round-trip
+        for category in Category::ALL {
+            assert_eq!(category.to_string().parse::<Category>(), Ok(category));
+        }
+    }
+
+    #[test]
+    fn test_category_order() {
+        // Sorted by the declaration order, not lexicographically
+        let mut categories = vec![Category::Security, Category::AiMl, Category::Compute];
+        categories.sort();
+        assert_eq!(
+            categories,
+            vec![Category::Compute, Category::AiMl, Category::Security]
+        );
+        let mut sorted = Category::ALL;
+        sorted.sort();
+        assert_eq!(sorted, Category::ALL);
+    }
+
+    #[test]
+    fn test_status_and_notes() {
+        let find = |name: &str| {
+            supported_apis(true)
+                .into_iter()
+                .find(|api| api.name == name)
+                .unwrap()
+        };
+        // Entries without status/notes default to GA without notes
+        assert_eq!(find("run").status, ApiStatus::GA);
+        assert_eq!(find("run").notes, None);
+        assert_eq!(find("dataform").status, ApiStatus::Beta);
+        assert_eq!(find("pubsublite").status, ApiStatus::Deprecated);
+        assert!(find("cloudfunctions")
+            .notes
+            .unwrap()
+            .contains("Cloud Run functions"));
+        assert_eq!(ApiStatus::Deprecated.to_string(), "Deprecated");
+    }
+
     #[test]
     fn test_alias_overlaps() {
         let all_services = supported_apis(true);
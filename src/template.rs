@@ -0,0 +1,153 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering of responses through user-supplied Handlebars templates (`zg exec --template`).
+
+use handlebars::Handlebars;
+use serde_json::Value;
+
+use super::error::ZgError;
+
+const TEMPLATE_NAME: &str = "template";
+
+/// Reads `\n`, `\t`, and `\\` in a template given on the command line as a newline, a tab, and a backslash,
+/// since shells don't turn them into the characters in quoted arguments.
+pub(crate) fn unescape(template: &str) -> String {
+    let mut unescaped = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Renders the value through the Handlebars template, as is (without HTML escaping).
+/// Errors point to the line and column of the template, and list the top-level keys of the value to help fix the paths.
+pub(crate) fn render(template: &str, value: &Value) -> Result<String, ZgError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .register_template_string(TEMPLATE_NAME, template)
+        .map_err(|e| {
+            ZgError::Other(format!(
+                "Invalid template{}: {}",
+                position(e.pos()),
+                e.reason()
+            ))
+        })?;
+    handlebars.render(TEMPLATE_NAME, value).map_err(|e| {
+        ZgError::Other(format!(
+            "Failed to render the template{}: {}{}",
+            position(e.line_no.zip(e.column_no)),
+            e.reason(),
+            top_level_keys(value)
+        ))
+    })
+}
+
+fn position(pos: Option<(usize, usize)>) -> String {
+    match pos {
+        Some((line, column)) => format!(" at line {}, column {}", line, column),
+        None => String::new(),
+    }
+}
+
+fn top_level_keys(value: &Value) -> String {
+    match value {
+        Value::Object(object) if !object.is_empty() => format!(
+            " (top-level keys of the response: {})",
+            object.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+        _ => String::new(),
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn clusters() -> Value {
+        json!({
+            "clusters": [
+                {"name": "prod", "status": "RUNNING", "currentNodeCount": 3, "location": "us-central1"},
+                {"name": "dev <test>", "status": "PROVISIONING", "location": "asia-northeast1-a"}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_render() {
+        let template =
+            unescape(r"{{#each clusters}}{{name}} {{status}} {{currentNodeCount}}\n{{/each}}");
+        assert_eq!(
+            render(&template, &clusters()).unwrap(),
+            "prod RUNNING 3\ndev <test> PROVISIONING \n"
+        );
+
+        let template = "{{#each clusters}}\n{{@index}}\t{{location}}{{#if currentNodeCount}} ({{currentNodeCount}} nodes){{/if}}\n{{/each}}total: {{len clusters}}\n";
+        assert_eq!(
+            render(template, &clusters()).unwrap(),
+            "0\tus-central1 (3 nodes)\n1\tasia-northeast1-a\ntotal: 2\n"
+        );
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let e = render("{{#each clusters}}\n{{name}}\n{{/if}}", &clusters()).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("Invalid template at line 3, column"),
+            "{}",
+            e
+        );
+
+        let e = render(
+            "{{name}}\n{{#each clusters}}{{lookup}}{{/each}}",
+            &clusters(),
+        )
+        .unwrap_err();
+        let message = e.to_string();
+        assert!(
+            message.starts_with("Failed to render the template at line 2, column"),
+            "{}",
+            message
+        );
+        assert!(
+            message.ends_with("(top-level keys of the response: clusters)"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"a\nb\tc\\n\d\"), "a\nb\tc\\n\\d\\");
+    }
+}
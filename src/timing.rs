@@ -0,0 +1,212 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::debug;
+use prettytable::{format, row, Table};
+use std::fmt;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Durations accumulated per phase (e.g., "http"), kept in the order each phase was first recorded.
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Vec<Phase>,
+}
+
+#[derive(Debug)]
+struct Phase {
+    name: &'static str,
+    count: usize,
+    total: Duration,
+}
+
+impl Timings {
+    /// Adds the elapsed time to the phase. A phase recorded multiple times (e.g., two gcloud calls) is summed up.
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|p| p.name == name) {
+            Some(phase) => {
+                phase.count += 1;
+                phase.total += elapsed;
+            }
+            None => self.phases.push(Phase {
+                name,
+                count: 1,
+                total: elapsed,
+            }),
+        }
+    }
+
+    /// Returns the accumulated duration and the number of records of the phase.
+    #[cfg(test)]
+    pub fn get(&self, name: &str) -> Option<(Duration, usize)> {
+        self.phases
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| (p.total, p.count))
+    }
+
+    /// Renders the phases as a table of phase, count, and milliseconds.
+    pub fn render(&self) -> String {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_CLEAN);
+        table.set_titles(row![bu->"phase", b->"count", b->"ms"]);
+        for phase in &self.phases {
+            table.add_row(row![
+                phase.name,
+                r->phase.count,
+                r->format!("{:.1}", phase.total.as_secs_f64() * 1000.0)
+            ]);
+        }
+        table.to_string()
+    }
+}
+
+static TIMINGS: LazyLock<Mutex<Timings>> = LazyLock::new(Default::default);
+
+/// Measures a phase from its creation until it's dropped, and records it to the global timings.
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+/// Starts measuring the phase. Keep the returned value alive (e.g., `let _span = timing::span("http");`) during the phase.
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        debug!("[timing] {} took {:.1?}", self.name, elapsed);
+        if let Ok(mut timings) = TIMINGS.lock() {
+            timings.record(self.name, elapsed);
+        }
+    }
+}
+
+/// Returns the summary table of all phases recorded so far (shown by `--timings`).
+pub fn summary() -> String {
+    TIMINGS
+        .lock()
+        .map(|timings| timings.render())
+        .unwrap_or_default()
+}
+
+/// Latency of an HTTP request: until the response headers arrive (time to first byte), and until the body is read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RequestTiming {
+    pub ttfb: Duration,
+    pub total: Duration,
+}
+
+impl fmt::Display for RequestTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} ms (TTFB {:.1} ms)",
+            self.total.as_secs_f64() * 1000.0,
+            self.ttfb.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Measures a request in two steps: `request` resolves with the response head (e.g., the status and headers),
+/// and `read_body` reads the rest of the response from it.
+pub async fn measure_request<H, B, E, HFut, BFut>(
+    request: HFut,
+    read_body: impl FnOnce(H) -> BFut,
+) -> Result<(B, RequestTiming), E>
+where
+    HFut: Future<Output = Result<H, E>>,
+    BFut: Future<Output = Result<B, E>>,
+{
+    let start = Instant::now();
+    let head = request.await?;
+    let ttfb = start.elapsed();
+    let body = read_body(head).await?;
+    Ok((
+        body,
+        RequestTiming {
+            ttfb,
+            total: start.elapsed(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_per_phase() {
+        let mut timings = Timings::default();
+        timings.record("gcloud", Duration::from_millis(120));
+        timings.record("http", Duration::from_millis(300));
+        timings.record("gcloud", Duration::from_millis(80));
+
+        assert_eq!(timings.get("gcloud"), Some((Duration::from_millis(200), 2)));
+        assert_eq!(timings.get("http"), Some((Duration::from_millis(300), 1)));
+        assert_eq!(timings.get("render"), None);
+
+        // Phases are kept in the first-recorded order
+        let rendered = timings.render();
+        assert!(rendered.find("gcloud").unwrap() < rendered.find("http").unwrap());
+        assert!(rendered.contains("200.0"));
+    }
+
+    #[tokio::test]
+    async fn test_measure_request() {
+        let (body, timing) = measure_request(
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, ()>("head")
+            },
+            |head| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(format!("{} and body", head))
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, "head and body");
+        assert!(timing.ttfb >= Duration::from_millis(50), "{:?}", timing);
+        assert!(
+            timing.total >= timing.ttfb + Duration::from_millis(50),
+            "{:?}",
+            timing
+        );
+
+        // Errors of either step are returned as they are
+        let failed = measure_request(async { Err::<(), _>("refused") }, |_| async { Ok(()) }).await;
+        assert_eq!(failed, Err("refused"));
+
+        let timing = RequestTiming {
+            ttfb: Duration::from_micros(120_340),
+            total: Duration::from_micros(250_060),
+        };
+        assert_eq!(timing.to_string(), "250.1 ms (TTFB 120.3 ms)");
+    }
+
+    #[test]
+    fn test_span_records_on_drop() {
+        {
+            let _span = span("test_span");
+        }
+        assert!(summary().contains("test_span"));
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cloud Trace context of `zg exec --trace`, to find the request in Cloud Trace or give its ids to support.
+
+/// The request header that asks Google APIs to trace the request (https://cloud.google.com/trace/docs/trace-context).
+pub(crate) const HEADER: &str = "X-Cloud-Trace-Context";
+
+/// Response headers that identify the request on the server side, printed with --trace.
+pub(crate) const REQUEST_ID_HEADERS: [&str; 3] =
+    ["x-goog-request-id", "x-guploader-uploadid", "x-request-id"];
+
+/// A trace id (32 hex characters) and the id of the span of the request (a decimal number).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TraceContext {
+    pub(crate) trace_id: String,
+    span_id: u64,
+}
+
+impl TraceContext {
+    /// Generates a random trace context.
+    pub(crate) fn new() -> Self {
+        Self::from_ids(rand::random(), rand::random())
+    }
+
+    fn from_ids(trace_id: u128, span_id: u64) -> Self {
+        TraceContext {
+            // Zero ids are invalid
+            trace_id: format!("{:032x}", trace_id.max(1)),
+            span_id: span_id.max(1),
+        }
+    }
+
+    /// The value of `X-Cloud-Trace-Context`, `TRACE_ID/SPAN_ID;o=1`, where `o=1` asks to record the trace.
+    pub(crate) fn header_value(&self) -> String {
+        format!("{}/{};o=1", self.trace_id, self.span_id)
+    }
+
+    /// The URL of the trace in the Cloud Console.
+    pub(crate) fn console_url(&self, project: &str) -> String {
+        format!(
+            "https://console.cloud.google.com/traces/list?project={}&tid={}",
+            project, self.trace_id
+        )
+    }
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_value() {
+        let trace = TraceContext::from_ids(0x105445aa7843bc8bf206b12000100000, 1);
+        assert_eq!(
+            trace.header_value(),
+            "105445aa7843bc8bf206b12000100000/1;o=1"
+        );
+
+        // Zero-padded to 32 characters, and never zero
+        let trace = TraceContext::from_ids(0xabc, 0);
+        assert_eq!(
+            trace.header_value(),
+            "00000000000000000000000000000abc/1;o=1"
+        );
+        let trace = TraceContext::from_ids(0, u64::MAX);
+        assert_eq!(
+            trace.header_value(),
+            "00000000000000000000000000000001/18446744073709551615;o=1"
+        );
+
+        let header = regex::Regex::new(r"^[0-9a-f]{32}/[1-9][0-9]*;o=1$").unwrap();
+        let value = TraceContext::new().header_value();
+        assert!(header.is_match(&value), "{}", value);
+    }
+
+    #[test]
+    fn test_console_url() {
+        let trace = TraceContext::from_ids(0xabc, 1);
+        assert_eq!(
+            trace.console_url("my-project"),
+            "https://console.cloud.google.com/traces/list?project=my-project&tid=00000000000000000000000000000abc"
+        );
+    }
+}
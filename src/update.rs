@@ -13,74 +13,268 @@
 // limitations under the License.
 
 use clap::Args;
-use log::debug;
+use log::{debug, log_enabled, warn, Level};
 use regex::Regex;
 use rmp_serde::Serializer;
-use serde::Serialize;
-use std::collections::{HashMap, HashSet};
-use std::error::Error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use std::iter::once;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use super::config;
 use super::core;
 use super::discovery;
+use super::error::ZgError;
 use super::flavors::update_flavors as flavors;
-use super::supported_apis::supported_apis;
+use super::logging::log_kv;
+use super::supported_apis::{standalone_apis, supported_apis, Category, SupportedApi};
+use super::timing;
 
 #[derive(Args, Debug)]
 pub struct UpdateArgs {
     /// Targets all APIs
     #[arg(long)]
     all: bool,
+
+    /// Only update APIs in the given category (e.g., 'Analytics', or aliases like 'iam', 'ml', 'db'). Can be repeated.
+    #[arg(long = "category", value_name = "CATEGORY")]
+    categories: Vec<Category>,
+
+    /// Show the APIs that would be downloaded, without downloading them.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Keep all schemas in the API definitions, including ones not referenced by any method (larger cache, slower load).
+    /// Defaults to `keep_all_schemas` in the config file.
+    #[arg(long, overrides_with = "no_keep_all_schemas")]
+    keep_all_schemas: bool,
+
+    /// Drop the schemas not referenced by any method, even if `keep_all_schemas` is set in the config file.
+    #[arg(long, overrides_with = "keep_all_schemas")]
+    no_keep_all_schemas: bool,
+}
+
+impl UpdateArgs {
+    /// Whether to keep all schemas, by the flags or else by the config file.
+    fn keep_all_schemas(&self) -> bool {
+        config::resolve_flag(
+            self.keep_all_schemas,
+            self.no_keep_all_schemas,
+            config::current().keep_all_schemas,
+        )
+    }
+}
+
+pub async fn main(args: &UpdateArgs, api_key: Option<String>) -> Result<(), ZgError> {
+    let summary = result(args, api_key).await?;
+    if args.dry_run {
+        for id in &summary.planned {
+            println!("{}", id);
+        }
+        println!("{} APIs would be downloaded.", summary.planned.len());
+        return Ok(());
+    }
+    if !summary.failed.is_empty() {
+        eprintln!("Failed to extract {} API(s):", summary.failed.len());
+        for failure in &summary.failed {
+            eprintln!("  - {}: {}", failure.file, failure.error);
+        }
+    }
+    summary.check()
+}
+
+/// Result of `zg update`. With `--dry-run`, only `planned` is filled.
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateSummary {
+    /// Ids of the APIs to download (e.g., "container:v1").
+    pub planned: Vec<String>,
+    /// Ids of the APIs downloaded and stored for zg.
+    pub extracted: Vec<String>,
+    /// APIs that failed to be extracted, which are skipped.
+    pub failed: Vec<UpdateFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateFailure {
+    pub file: String,
+    pub error: String,
+}
+
+impl UpdateSummary {
+    /// Fails if any API was skipped, after the summary is shown.
+    pub fn check(&self) -> Result<(), ZgError> {
+        match self.failed.len() {
+            0 => Ok(()),
+            n => Err(format!("{} API(s) were skipped", n).into()),
+        }
+    }
 }
 
-pub async fn main(args: &UpdateArgs) -> Result<(), Box<dyn Error>> {
-    debug!("{:?}", args);
-    let downloaded_files = download().await?;
+/// Downloads and stores the API definitions (only plans them with `--dry-run`), returning what was done.
+/// Progress is printed to stderr so that the summary can be serialized to stdout (`--format json`).
+pub async fn result(args: &UpdateArgs, api_key: Option<String>) -> Result<UpdateSummary, ZgError> {
+    log_kv!(
+        Level::Debug,
+        {
+            categories = format!("{:?}", args.categories),
+            dry_run = args.dry_run,
+            keep_all_schemas = args.keep_all_schemas(),
+        },
+        "{:?}",
+        args
+    );
+    let auth = discovery::DiscoveryAuth::current();
+    let mut discovered_items = discovery::ensure_discovered_apis(true, &auth).await?.items;
+    discovered_items.extend(config::current().custom_directory_items());
+    discovered_items.extend(standalone_directory_items(api_key.as_deref()));
+    let apis_to_download =
+        plan_downloads(discovered_items, &supported_apis(true), &args.categories)?;
+
+    let planned = apis_to_download
+        .iter()
+        .map(|item| item.id.clone())
+        .collect();
+    if args.dry_run {
+        return Ok(UpdateSummary {
+            planned,
+            ..Default::default()
+        });
+    }
+
+    let downloaded_files = download(apis_to_download, &auth).await?;
     debug!("Downloaded files to process: {:?}", downloaded_files);
+    let (extracted, failures) =
+        extract_all(downloaded_files, args.keep_all_schemas(), &core::api_dir());
+    Ok(UpdateSummary {
+        planned,
+        extracted,
+        failed: failures
+            .into_iter()
+            .map(|(file, e)| UpdateFailure {
+                file: file.display().to_string(),
+                error: e.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Extracts and stores each downloaded API definition into `api_dir`, returning the ids of the stored APIs.
+/// An API that fails (e.g., a discovery document in an unexpected shape) is skipped so that the others are still updated;
+/// the failures are returned with their files.
+fn extract_all(
+    downloaded_files: Vec<PathBuf>,
+    keep_all_schemas: bool,
+    api_dir: &Path,
+) -> (Vec<String>, Vec<(PathBuf, ZgError)>) {
+    let mut extracted = Vec::new();
+    let mut failures = Vec::new();
     for api_filepath in downloaded_files {
-        let api = extract_api(api_filepath)?;
-        println!("Extracted API for zg: {}", api.id);
-        let path = core::api_dir().join(format!("{}.msgpack", api.id.replace(":", "_")));
-        store_zgapi_msgpack(api, &path)?;
+        let result = extract_api(api_filepath.clone(), keep_all_schemas).and_then(|api| {
+            let id = api.id.clone();
+            store_zgapi_msgpack(api, &api_dir.join(core::api_file_name(&id)))?;
+            Ok(id)
+        });
+        match result {
+            Ok(id) => {
+                eprintln!("Extracted API for zg: {}", id);
+                extracted.push(id);
+            }
+            Err(e) => {
+                eprintln!(
+                    "  -> Failed to extract {}. Skipping.",
+                    api_filepath.display()
+                );
+                failures.push((api_filepath, e));
+            }
+        }
     }
-    Ok(())
+    (extracted, failures)
 }
 
 /// Serialize and store the ZgApi struct locally using MessagePack format
-pub fn store_zgapi_msgpack(api: core::ZgApi, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    api.serialize(&mut Serializer::new(writer))?;
-    Ok(())
+/// The file is replaced atomically, so an interrupted update never leaves a truncated file.
+pub fn store_zgapi_msgpack(api: core::ZgApi, path: &Path) -> Result<(), ZgError> {
+    let _span = timing::span("store_api");
+    let mut encoded = Vec::new();
+    api.serialize(&mut Serializer::new(&mut encoded))?;
+    core::write_file_atomic(path, &encoded)
 }
 
-/// Download API definition JSONs found both in DISCOVERY_URL response and core::supported_api_ids().
-/// Note that it doesn't remove existing JSON files
-async fn download() -> Result<Vec<PathBuf>, Box<dyn Error>> {
-    let discovered_apis = discovery::ensure_discovered_apis(true).await?;
-
+/// Plans which APIs to download: items found both in DISCOVERY_URL response and the given supported APIs.
+/// When `categories` is not empty, only APIs in the categories are selected.
+fn plan_downloads(
+    discovered_items: Vec<discovery::DiscoveryDirectoryItem>,
+    apis: &[SupportedApi],
+    categories: &[Category],
+) -> Result<Vec<discovery::DiscoveryDirectoryItem>, ZgError> {
     // Collect supported API IDs in the format of "name:version" (e.g., "bigquery:v2")
-    let supported_api_ids: HashSet<String> = supported_apis(true)
+    let supported_api_ids: HashSet<String> = apis
         .iter()
+        .filter(|api| categories.is_empty() || categories.contains(&api.category))
         .flat_map(|api| api.versions.iter().map(|v| format!("{}:{}", api.name, v)))
         .collect();
 
     // From discovered APIs, select supported API IDs, that will be downloaded
-    let apis_to_download: Vec<discovery::DiscoveryDirectoryItem> = discovered_apis
-        .items
+    let apis_to_download: Vec<discovery::DiscoveryDirectoryItem> = discovered_items
         .into_iter()
         .filter(|item| supported_api_ids.contains(&item.id))
         .collect();
     debug!("Total APIs to download: {}", apis_to_download.len());
 
+    Ok(apis_to_download)
+}
+
+/// Builds directory items for the standalone APIs, which are not listed in the Discovery Service.
+/// Versions whose discovery URL can't be built (e.g., no API key is given) are skipped.
+fn standalone_directory_items(api_key: Option<&str>) -> Vec<discovery::DiscoveryDirectoryItem> {
+    standalone_apis()
+        .into_iter()
+        .flat_map(|api| {
+            api.versions
+                .iter()
+                .filter_map(|version| match api.discovery_url(version, api_key)? {
+                    Ok(url) => Some(discovery::DiscoveryDirectoryItem {
+                        kind: "discovery#directoryItem".to_string(),
+                        id: format!("{}:{}", api.name, version),
+                        name: api.name.clone(),
+                        version: version.clone(),
+                        title: api.name.clone(),
+                        description: String::new(),
+                        discovery_rest_url: url,
+                        documentation_link: None,
+                        preferred: false,
+                    }),
+                    Err(e) => {
+                        debug!("Skip standalone API {}:{}: {}", api.name, version, e);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Download API definition JSONs of the planned APIs.
+/// Note that it doesn't remove existing JSON files
+async fn download(
+    apis_to_download: Vec<discovery::DiscoveryDirectoryItem>,
+    auth: &discovery::DiscoveryAuth,
+) -> Result<Vec<PathBuf>, ZgError> {
     let mut downloaded_files = Vec::new();
 
     for item in apis_to_download {
-        if let Some(filepath) =
-            discovery::download_api_definition(item.id, item.discovery_rest_url).await?
+        let headers = config::current().custom_api_headers(&item.name);
+        if let Some(filepath) = discovery::download_api_definition_with_headers(
+            item.id,
+            item.discovery_rest_url,
+            &headers,
+            auth,
+        )
+        .await?
         {
             downloaded_files.push(filepath);
         }
@@ -93,9 +287,15 @@ async fn download() -> Result<Vec<PathBuf>, Box<dyn Error>> {
 ///
 /// Reads a JSON file containing API descriptions, parses it into a `core::ApiDescription`,
 /// processes its resources using the `convert_resource` function, and constructs a `ZgApi` struct.
-pub fn extract_api(api_filepath: PathBuf) -> Result<core::ZgApi, Box<dyn Error>> {
-    let api_description: discovery::ApiDescription =
-        serde_json::from_reader(BufReader::new(File::open(api_filepath)?))?;
+/// Unless `keep_all_schemas` is true, schemas not reachable from any method's request/response are dropped.
+pub fn extract_api(api_filepath: PathBuf, keep_all_schemas: bool) -> Result<core::ZgApi, ZgError> {
+    let _span = timing::span("extract_api");
+    let raw_json: Value = serde_json::from_reader(BufReader::new(File::open(api_filepath)?))?;
+    let raw_schemas = raw_json.get("schemas").cloned().unwrap_or(Value::Null);
+    let api_description = parse_api_description(&raw_json)?;
+
+    let mut schemas = api_description.schemas.unwrap_or_default();
+    let root_refs = collect_root_refs(api_description.resources.as_ref());
 
     let resources = api_description
         .resources
@@ -107,32 +307,187 @@ pub fn extract_api(api_filepath: PathBuf) -> Result<core::ZgApi, Box<dyn Error>>
                 resource_name,
                 resource,
                 None,
-                &api_description.schemas.clone().unwrap_or_default(),
+                &schemas,
             )
         })
         .collect(); // Collect the resources into a Vec<ZgResource>
 
+    if !keep_all_schemas {
+        let reachable = reachable_schemas(root_refs, &raw_schemas);
+        let total = schemas.len();
+        schemas.retain(|name, _| reachable.contains(name));
+        debug!(
+            "Pruned unreferenced schemas of {}: {} -> {}",
+            &api_description.id,
+            total,
+            schemas.len()
+        );
+    }
+
     let api = core::ZgApi {
         id: api_description.id,
         name: api_description.name,
         version: api_description.version,
         revision: api_description.revision,
         base_url: api_description.base_url,
+        root_url: api_description.root_url,
+        service_path: api_description.service_path,
         resources,
-        schemas: api_description.schemas.unwrap_or_default(),
+        schemas,
+        format_version: core::API_FORMAT_VERSION,
+        common_params: collect_common_params(&api_description.parameters),
+        documentation_link: Some(api_description.documentation_link)
+            .filter(|link| !link.is_empty()),
+        scopes: collect_scopes(api_description.auth),
+        resource_index: Default::default(),
     };
 
     match api.id.as_str() {
         // Several API have somewhat "flat (no nest)" resource hierarchy (e.g., bigquery:v2's resources are all top-level).
         // We need to infer the hierarchy based on the method flat_paths and update the resources accordingly.
-        "bigquery:v2" => Ok(rebuild_hierarchy(&mut api.clone())),
-        "compute:v1" => Ok(rebuild_hierarchy(&mut api.clone())),
-        "sqladmin:v1" | "sqladmin:v1beta4" => Ok(rebuild_hierarchy(&mut api.clone())),
-        "storage:v1" => Ok(rebuild_hierarchy(&mut api.clone())),
+        "bigquery:v2" => Ok(rebuild_hierarchy(api)),
+        "compute:v1" => Ok(rebuild_hierarchy(api)),
+        "sqladmin:v1" | "sqladmin:v1beta4" => Ok(rebuild_hierarchy(api)),
+        "storage:v1" => Ok(rebuild_hierarchy(api)),
         _ => Ok(api),
     }
 }
 
+/// Parses the discovery document. On failure, the error tells the API id and the path of the entry zg couldn't parse.
+/// Fields zg doesn't know are ignored, and listed in the debug log.
+fn parse_api_description(raw_json: &Value) -> Result<discovery::ApiDescription, ZgError> {
+    let api_description = match discovery::ApiDescription::deserialize(raw_json) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let id = raw_json
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown API");
+            return Err(ZgError::Deserialize(match find_error_path(raw_json) {
+                Some(path) => format!("{}: {} (at {})", id, e, path),
+                None => format!("{}: {}", id, e),
+            }));
+        }
+    };
+
+    if log_enabled!(Level::Debug) {
+        let mut unknown = BTreeSet::new();
+        let parsed = serde_json::to_value(&api_description)?;
+        collect_unknown_fields(raw_json, &parsed, &mut unknown);
+        if !unknown.is_empty() {
+            debug!(
+                "Ignored fields in {}: {}",
+                api_description.id,
+                unknown.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    Ok(api_description)
+}
+
+/// Finds the path (e.g., "resources.projects.methods.get.parameters.name") of the first entry that fails to parse,
+/// by parsing the parameters, methods, and schemas of the discovery document one by one.
+fn find_error_path(raw_json: &Value) -> Option<String> {
+    fn find_in_map<T: DeserializeOwned>(value: Option<&Value>, path: &str) -> Option<String> {
+        value?.as_object()?.iter().find_map(|(name, entry)| {
+            T::deserialize(entry)
+                .err()
+                .map(|_| format!("{}.{}", path, name))
+        })
+    }
+
+    fn find_in_resources(value: Option<&Value>, path: &str) -> Option<String> {
+        value?.as_object()?.iter().find_map(|(name, resource)| {
+            let path = format!("{}.{}", path, name);
+            let methods = resource.get("methods").and_then(Value::as_object);
+            methods
+                .into_iter()
+                .flatten()
+                .find_map(|(method_name, method)| {
+                    let method_path = format!("{}.methods.{}", path, method_name);
+                    discovery::Method::deserialize(method).err().map(|_| {
+                        let params_path = format!("{}.parameters", method_path);
+                        find_in_map::<discovery::Parameter>(method.get("parameters"), &params_path)
+                            .unwrap_or(method_path)
+                    })
+                })
+                .or_else(|| {
+                    find_in_resources(resource.get("resources"), &format!("{}.resources", path))
+                })
+        })
+    }
+
+    find_in_map::<discovery::Parameter>(raw_json.get("parameters"), "parameters")
+        .or_else(|| find_in_resources(raw_json.get("resources"), "resources"))
+        .or_else(|| find_in_map::<discovery::Schema>(raw_json.get("schemas"), "schemas"))
+}
+
+/// Collects the field names in `raw` missing from `parsed` (the parsed document serialized back), i.e., ignored ones.
+fn collect_unknown_fields(raw: &Value, parsed: &Value, unknown: &mut BTreeSet<String>) {
+    match (raw, parsed) {
+        (Value::Object(raw), Value::Object(parsed)) => {
+            for (key, raw_value) in raw {
+                match parsed.get(key) {
+                    Some(parsed_value) => collect_unknown_fields(raw_value, parsed_value, unknown),
+                    None => {
+                        unknown.insert(key.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(parsed)) => {
+            for (raw_value, parsed_value) in raw.iter().zip(parsed) {
+                collect_unknown_fields(raw_value, parsed_value, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects schema names referenced by methods' request and response (`$ref`) in all resources, recursively.
+fn collect_root_refs(resources: Option<&HashMap<String, discovery::Resource>>) -> Vec<String> {
+    let mut refs = Vec::new();
+    for resource in resources.into_iter().flat_map(|r| r.values()) {
+        for method in resource.methods.iter().flat_map(|m| m.values()) {
+            refs.extend(method.request.as_ref().and_then(|r| r.ref_name.clone()));
+            refs.extend(method.response.as_ref().and_then(|r| r.ref_name.clone()));
+        }
+        refs.extend(collect_root_refs(resource.resources.as_ref()));
+    }
+    refs
+}
+
+/// Returns names of schemas reachable from the given roots, following `$ref`s anywhere in the schema JSON
+/// (properties, array items, additionalProperties, ...) transitively. Cycles are visited only once.
+fn reachable_schemas(roots: Vec<String>, raw_schemas: &Value) -> HashSet<String> {
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map {
+                    match (key.as_str(), v) {
+                        ("$ref", Value::String(name)) => refs.push(name.clone()),
+                        _ => collect_refs(v, refs),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|v| collect_refs(v, refs)),
+            _ => (),
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut queue = roots;
+    while let Some(name) = queue.pop() {
+        if !reachable.insert(name.clone()) {
+            continue; // already visited
+        }
+        if let Some(schema) = raw_schemas.get(&name) {
+            collect_refs(schema, &mut queue);
+        }
+    }
+    reachable
+}
+
 /// Converts a `core::Resource` into a `core::ZgResource`, handling resource hierarchy and paths.
 ///
 /// # Arguments
@@ -207,14 +562,12 @@ fn convert_method(
     method: discovery::Method,
     schemas: &HashMap<String, discovery::Schema>,
 ) -> core::ZgMethod {
-    let request_data_schema = match method.http_method.as_str() {
-        "GET" | "DELETE" => None, // No request body for GET/DELETE
-        _ => method
-            .request
-            .as_ref()
-            .and_then(|req| req.ref_name.as_deref())
-            .and_then(|ref_name| schemas.get(ref_name).cloned()), // Resolve and embed the schema directly
-    };
+    // Whether the method takes a body is up to its `request`, not the HTTP method (a few DELETE methods take one)
+    let request_data_schema = method
+        .request
+        .as_ref()
+        .and_then(|req| req.ref_name.as_deref())
+        .and_then(|ref_name| schemas.get(ref_name).cloned()); // Resolve and embed the schema directly
 
     core::ZgMethod {
         id: method.id.clone(),
@@ -231,11 +584,25 @@ fn convert_method(
                 )
             }),
         query_params: collect_query_params(&method.parameters),
-        // None if http_method is GET or DELETE; otherwise, extract from schema in the API definition
+        // None if the method has no `request` in the API definition
         request_data_schema,
+        deprecated: method.deprecated,
+        parameter_order: method.parameter_order.unwrap_or_default(),
+        response_schema: method.response.and_then(|response| response.ref_name),
+        scopes: method.scopes.unwrap_or_default(),
     }
 }
 
+/// Collects the OAuth scopes of the API (`auth.oauth2.scopes`) with their descriptions.
+fn collect_scopes(auth: Option<discovery::Auth>) -> BTreeMap<String, String> {
+    auth.and_then(|auth| auth.oauth2)
+        .and_then(|oauth2| oauth2.scopes)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(scope, detail)| (scope, detail.description.unwrap_or_default()))
+        .collect()
+}
+
 /// Collects query parameters from the method's parameters.
 fn collect_query_params(
     parameters: &Option<HashMap<String, discovery::Parameter>>,
@@ -249,40 +616,69 @@ fn collect_query_params(
                 // Collect only query parameters; ignore path parameters
                 // Also, exclude parameters for nested objects that contain a dot (".") in their names (e.g., https://cloud.google.com/spanner/docs/reference/rest/v1/projects.instances.backups/create)
                 .filter(|(name, param)| param.location == "query" && !name.contains('.'))
-                .map(|(name, param)| core::ZgQueryParam {
-                    name: name.clone(),
-                    description: param.description.clone(),
-                    // Unlike "path" params, "query" params may not be marked as true in the API definition; instead, description starts with 'Required.'
-                    required: param.required.unwrap_or_else(|| {
-                        param
-                            .description
-                            .as_ref()
-                            .is_some_and(|desc| required_regex.is_match(desc))
-                    }),
-                })
+                .map(|(name, param)| convert_query_param(name, param, &required_regex))
                 .collect()
         })
         .unwrap_or_default()
 }
 
+/// Collects the API-level parameters accepted by every method (e.g., fields, quotaUser, prettyPrint), sorted by name.
+/// Unlike method parameters, names with a dot (e.g., "$.xgafv") are kept as they are not nested objects.
+fn collect_common_params(
+    parameters: &Option<HashMap<String, discovery::Parameter>>,
+) -> Vec<core::ZgQueryParam> {
+    let required_regex = Regex::new(r"(?i)^\s*required\.").unwrap();
+    let mut common_params: Vec<core::ZgQueryParam> = parameters
+        .iter()
+        .flatten()
+        .filter(|(_, param)| param.location == "query")
+        .map(|(name, param)| convert_query_param(name, param, &required_regex))
+        .collect();
+    common_params.sort_by(|a, b| a.name.cmp(&b.name));
+    common_params
+}
+
+fn convert_query_param(
+    name: &str,
+    param: &discovery::Parameter,
+    required_regex: &Regex,
+) -> core::ZgQueryParam {
+    core::ZgQueryParam {
+        name: name.to_string(),
+        description: param.description.clone(),
+        // Unlike "path" params, "query" params may not be marked as true in the API definition; instead, description starts with 'Required.'
+        required: param.required.unwrap_or_else(|| {
+            param
+                .description
+                .as_ref()
+                .is_some_and(|desc| required_regex.is_match(desc))
+        }),
+        repeated: param.repeated,
+        param_type: Some(param.param_type.clone()),
+        enum_values: param.enum_values.clone(),
+        enum_descriptions: param.enum_descriptions.clone(),
+        default: param.default.clone(),
+        format: param.format.clone(),
+        deprecated: param.deprecated,
+    }
+}
+
 /// Rebuilds the resource hierarchy for the given `ZgApi`.
 ///
 /// Updates the given `ZgApi`'s path/parent_path and method ids by calling `update_resource_paths`.
-/// Then, based on these updated paths, rebuild the resource hierarchy and returns new `ZgApi`.
-fn rebuild_hierarchy(original_api: &mut core::ZgApi) -> core::ZgApi {
-    debug_resource_hierarchy(&original_api.resources, 0);
+/// Then, based on these updated paths, moves the child resources under their parents and returns the `ZgApi`.
+fn rebuild_hierarchy(mut api: core::ZgApi) -> core::ZgApi {
+    debug_resource_hierarchy(&api.resources, 0);
 
     // Update resource paths, parent_paths, and method IDs based on methods' flat_paths
-    let mut api = update_resource_paths(original_api);
+    update_resource_paths(&mut api);
 
-    // Prepare children (resources with parent_path) to insert into the hierarchy
-    let mut children_to_insert: Vec<core::ZgResource> = Vec::new();
-    for resource in api.resources.iter_mut() {
-        // If resource has a parent, it's a child
-        if resource.parent_path.is_some() {
-            children_to_insert.push(resource.clone());
-        }
-    }
+    // Move children (resources with parent_path) out of the top-level resources, which retain only the top-level ones
+    let (children_to_insert, top_level): (Vec<_>, Vec<_>) = api
+        .resources
+        .drain(..)
+        .partition(|resource| resource.parent_path.is_some());
+    api.resources = top_level;
     debug!(
         "children_to_insert: {:?}",
         &children_to_insert
@@ -290,9 +686,6 @@ fn rebuild_hierarchy(original_api: &mut core::ZgApi) -> core::ZgApi {
             .map(|r| r.name.as_str())
             .collect::<Vec<_>>()
     );
-
-    // Remove children from the top-level resources; retain only the top-level resources
-    api.resources.retain(|r| r.parent_path.is_none());
     debug!(
         "Initial top-level resources: {:?}",
         &api.resources
@@ -301,23 +694,48 @@ fn rebuild_hierarchy(original_api: &mut core::ZgApi) -> core::ZgApi {
             .collect::<Vec<_>>()
     );
 
-    // Insert children into the resource hierarchy; remove (pop) child from the list.
-    // Finally all children should find their parents, so iterate until the list is empty.
+    for orphan in insert_children(&mut api.resources, children_to_insert) {
+        warn!(
+            "Resource '{}' of {} has no parent resource '{}'; listing it at the top level.",
+            orphan.path.as_deref().unwrap_or(&orphan.name),
+            &api.id,
+            orphan.parent_path.as_deref().unwrap_or_default()
+        );
+        api.resources.push(orphan);
+    }
+    debug_resource_hierarchy(&api.resources, 0);
+
+    api
+}
+
+/// Inserts the children into the resource hierarchy, and returns the ones whose parent is never found (orphans).
+/// A child may be inserted only after another child (its parent) is, so children are retried until every remaining
+/// one has failed in a row since the last insertion.
+fn insert_children(
+    resources: &mut [core::ZgResource],
+    mut children_to_insert: Vec<core::ZgResource>,
+) -> Vec<core::ZgResource> {
+    let mut failures_in_a_row = 0;
+    // Remove (pop) a child from the list and try to find the parent to insert it into; insert it back if it fails.
+    // Use `insert(0, ...)` instead of `push()` to try another child in the next iteration.
     while let Some(child_res) = children_to_insert.pop() {
-        // Try to find the parent and insert; insert back to children_to_insert if it fails.
-        // Use `insert(0, ...)` instead of `push()` to try another child in the next iteration.
-        if !insert_child_resource(&mut api.resources, &child_res) {
-            children_to_insert.insert(0, child_res);
+        match insert_child_resource(resources, child_res) {
+            Ok(()) => failures_in_a_row = 0,
+            Err(child_res) => {
+                children_to_insert.insert(0, child_res);
+                failures_in_a_row += 1;
+                if failures_in_a_row >= children_to_insert.len() {
+                    break;
+                }
+            }
         }
         debug!("Remaining children count: {}", children_to_insert.len());
     }
-    debug_resource_hierarchy(&api.resources, 0);
-
-    api.clone()
+    children_to_insert
 }
 
 /// Updates path/parent_path and method ids for each resource in the `ZgApi` by inspecting the methods' flat paths.
-fn update_resource_paths(api: &mut core::ZgApi) -> core::ZgApi {
+fn update_resource_paths(api: &mut core::ZgApi) {
     let (service_name, version) = api.id.split_once(':').unwrap();
 
     fn recursive(
@@ -377,19 +795,17 @@ fn update_resource_paths(api: &mut core::ZgApi) -> core::ZgApi {
     for resource in api.resources.iter_mut() {
         recursive(resource, service_name, version, None);
     }
-
-    api.clone()
 }
 
 /// Recursively inserts a child resource into the correct parent resource based on the parent path.
 ///
-/// This function traverses the given resource hierarchy and inserts the child resource into
+/// This function traverses the given resource hierarchy and moves the child resource into
 /// the matching parent, identified by `parent_path`. If the parent is found, the child resource
-/// is added to its sub-resources; if not, finally the function returns `false`.
+/// is added to its sub-resources; if not, finally the function gives the child back as `Err`.
 fn insert_child_resource(
     resources: &mut [core::ZgResource],
-    child_resource: &core::ZgResource,
-) -> bool {
+    mut child_resource: core::ZgResource,
+) -> Result<(), core::ZgResource> {
     debug!(
         "trying to insert child_resource ('{}') to its parent '{:?}'",
         &child_resource.name, &child_resource.parent_path
@@ -397,6 +813,10 @@ fn insert_child_resource(
     for resource in resources.iter_mut() {
         debug!("  candidate to be inserted: {:?}", &resource.path);
         if resource.path == child_resource.parent_path {
+            debug!(
+                "  Successfully inserted child_resource: {:?}",
+                &child_resource.path
+            );
             let parent_resources_vec = resource.resources.get_or_insert(Vec::new());
 
             if let Some(existing_child) = parent_resources_vec
@@ -404,18 +824,12 @@ fn insert_child_resource(
                 .find(|r| r.path == child_resource.path)
             {
                 // rare: only observed in "sqladmin:v1" - if eixisting child resource with the same path found, merge methods.
-                existing_child
-                    .methods
-                    .extend(child_resource.methods.clone());
+                existing_child.methods.extend(child_resource.methods);
             } else {
                 // common: insert the child resource into the parent's sub-resources.
-                parent_resources_vec.push(child_resource.clone());
+                parent_resources_vec.push(child_resource);
             }
-            debug!(
-                "  Successfully inserted child_resource: {:?}",
-                &child_resource.path
-            );
-            return true;
+            return Ok(());
         } else if let Some(ref mut children) = resource.resources {
             // Dive into child search only if the child's parent path starts with the current resource's path
             if let Some(ref resource_path) = resource.path {
@@ -423,9 +837,11 @@ fn insert_child_resource(
                     .parent_path
                     .as_ref()
                     .is_some_and(|p| p.starts_with(resource_path))
-                    && insert_child_resource(children, child_resource)
                 {
-                    return true;
+                    match insert_child_resource(children, child_resource) {
+                        Ok(()) => return Ok(()),
+                        Err(child) => child_resource = child,
+                    }
                 }
             }
         }
@@ -434,7 +850,7 @@ fn insert_child_resource(
         "  Failed to insert child_resource: {:?}",
         &child_resource.path
     );
-    false
+    Err(child_resource)
 }
 
 /// Recursively prints the hierarchy of resources for debugging purposes.
@@ -520,16 +936,158 @@ fn is_valid_flat_path(service_name: &str, flat_path: &str) -> bool {
 mod tests {
     use super::*;
 
+    fn directory_items(ids: &[&str]) -> Vec<discovery::DiscoveryDirectoryItem> {
+        ids.iter()
+            .map(|id| {
+                let (name, version) = id.split_once(':').unwrap();
+                discovery::DiscoveryDirectoryItem {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    ..discovery::DiscoveryDirectoryItem::testdata()
+                }
+            })
+            .collect()
+    }
+
     #[test]
-    fn test_extract_api_nested_resources() -> Result<(), Box<dyn Error>> {
+    fn test_plan_downloads_without_category() {
+        let items = directory_items(&["bigquery:v2", "compute:v1", "unsupported:v1"]);
+        let planned = plan_downloads(items, &supported_apis(true), &[]).unwrap();
+        let ids: Vec<&str> = planned.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["bigquery:v2", "compute:v1"]);
+    }
+
+    #[test]
+    fn test_plan_downloads_with_categories() {
+        let items = directory_items(&["bigquery:v2", "compute:v1", "analyticshub:v1", "kms:v1"]);
+
+        // Both primary (bigquery) and secondary (analyticshub) APIs are filtered
+        let planned = plan_downloads(items, &supported_apis(true), &[Category::Analytics]).unwrap();
+        let ids: Vec<&str> = planned.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["bigquery:v2", "analyticshub:v1"]);
+
+        // Multiple categories are combined
+        let items = directory_items(&["bigquery:v2", "compute:v1", "cloudkms:v1"]);
+        let planned = plan_downloads(
+            items,
+            &supported_apis(true),
+            &[Category::Compute, Category::Security],
+        )
+        .unwrap();
+        let ids: Vec<&str> = planned.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["compute:v1", "cloudkms:v1"]);
+    }
+
+    #[test]
+    fn test_plan_downloads_unknown_category() {
+        // Unknown categories are rejected when parsing --category, case-insensitive
+        assert_eq!("analytics".parse::<Category>(), Ok(Category::Analytics));
+        let err = "Analytix".parse::<Category>().unwrap_err();
+        assert!(err.contains("Unknown category 'Analytix'"));
+        assert!(err.contains("Analytics"));
+    }
+
+    #[test]
+    fn test_standalone_directory_items() {
+        // Without an API key, standalone APIs requiring it are skipped
+        assert!(standalone_directory_items(None).is_empty());
+
+        let items = standalone_directory_items(Some("my-key"));
+        let item = items
+            .iter()
+            .find(|i| i.id == "generativelanguage:v1beta")
+            .unwrap();
+        assert_eq!(
+            item.discovery_rest_url,
+            "https://generativelanguage.googleapis.com/$discovery/rest?version=v1beta&key=my-key"
+        );
+        let planned = plan_downloads(items, &supported_apis(true), &[]).unwrap();
+        assert!(planned.iter().any(|i| i.id == "generativelanguage:v1beta"));
+    }
+
+    #[test]
+    fn test_reachable_schemas_nested_refs() {
+        use serde_json::json;
+        let raw_schemas = json!({
+            "Cluster": {
+                "properties": {
+                    "nodePools": { "type": "array", "items": { "$ref": "NodePool" } },
+                    "labels": { "type": "object", "additionalProperties": { "$ref": "Label" } }
+                }
+            },
+            "NodePool": { "properties": { "config": { "$ref": "NodeConfig" } } },
+            "NodeConfig": { "properties": { "parent": { "$ref": "NodePool" } } }, // cycle
+            "Label": { "type": "object" },
+            "Unused": { "properties": { "x": { "$ref": "AlsoUnused" } } },
+            "AlsoUnused": { "type": "object" }
+        });
+
+        let reachable = reachable_schemas(vec!["Cluster".to_string()], &raw_schemas);
+        let mut names: Vec<&str> = reachable.iter().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Cluster", "Label", "NodeConfig", "NodePool"]);
+    }
+
+    #[test]
+    fn test_collect_root_refs() {
+        let method = |request: Option<&str>, response: Option<&str>| discovery::Method {
+            id: "testapi.projects.testres.create".to_string(),
+            http_method: "POST".to_string(),
+            description: "".to_string(),
+            path: "v1/testres".to_string(),
+            flat_path: None,
+            parameter_order: None,
+            parameters: None,
+            request: request.map(|r| discovery::Request {
+                ref_name: Some(r.to_string()),
+            }),
+            response: response.map(|r| discovery::Response {
+                ref_name: Some(r.to_string()),
+            }),
+            scopes: None,
+            deprecated: false,
+        };
+        let nested = discovery::Resource {
+            methods: Some(HashMap::from([(
+                "get".to_string(),
+                method(None, Some("NodePool")),
+            )])),
+            resources: None,
+        };
+        let resources = HashMap::from([(
+            "clusters".to_string(),
+            discovery::Resource {
+                methods: Some(HashMap::from([(
+                    "create".to_string(),
+                    method(Some("CreateClusterRequest"), Some("Operation")),
+                )])),
+                resources: Some(HashMap::from([("nodePools".to_string(), nested)])),
+            },
+        )]);
+
+        let mut refs = collect_root_refs(Some(&resources));
+        refs.sort();
+        assert_eq!(refs, vec!["CreateClusterRequest", "NodePool", "Operation"]);
+    }
+
+    #[test]
+    fn test_extract_api_nested_resources() -> Result<(), ZgError> {
         // Extract ZgApi from a mock JSON file, with nested resources.
-        let api: core::ZgApi =
-            extract_api(PathBuf::from("tests/test_data/container_v1_nested.json"))?;
+        let api: core::ZgApi = extract_api(
+            PathBuf::from("tests/test_data/container_v1_nested.json"),
+            false,
+        )?;
 
         // Check that the API ID and name are parsed correctly
         assert_eq!(api.id, "container:v1");
         assert_eq!(api.name, "Container API");
         assert_eq!(api.base_url, "https://container.googleapis.com/");
+        assert_eq!(
+            api.root_url.as_deref(),
+            Some("https://container.googleapis.com/")
+        );
+        assert_eq!(api.service_path.as_deref(), Some(""));
 
         // Check that the top-level resource is parsed correctly
         assert_eq!(api.resources.len(), 1);
@@ -572,6 +1130,301 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_collect_query_params_repeated() {
+        // Parameters of compute.instances.aggregatedList (shortened), plus a nested-object param to be excluded
+        let parameters: HashMap<String, discovery::Parameter> =
+            serde_json::from_value(serde_json::json!({
+                "project": { "location": "path", "type": "string", "required": true },
+                "filter": { "location": "query", "type": "string" },
+                "fields": { "location": "query", "type": "string", "repeated": true },
+                "backup.name": { "location": "query", "type": "string" },
+            }))
+            .unwrap();
+
+        let mut query_params = collect_query_params(&Some(parameters));
+        query_params.sort_by(|a, b| a.name.cmp(&b.name));
+        let summary: Vec<(&str, bool)> = query_params
+            .iter()
+            .map(|qp| (qp.name.as_str(), qp.repeated))
+            .collect();
+        assert_eq!(summary, vec![("fields", true), ("filter", false)]);
+    }
+
+    #[test]
+    fn test_collect_query_params_enum_metadata() {
+        let parameters: HashMap<String, discovery::Parameter> =
+            serde_json::from_value(serde_json::json!({
+                "view": {
+                    "location": "query",
+                    "type": "string",
+                    "enum": ["BASIC", "FULL"],
+                    "enumDescriptions": ["Basic fields only.", "All fields."],
+                    "default": "BASIC",
+                },
+                "pageSize": { "location": "query", "type": "integer", "format": "int32" },
+                // Params of nested objects are still dropped, even with enum values
+                "policy.kind": { "location": "query", "type": "string", "enum": ["A", "B"] },
+            }))
+            .unwrap();
+
+        let mut query_params = collect_query_params(&Some(parameters));
+        query_params.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(query_params.len(), 2);
+
+        assert_eq!(query_params[0].name, "pageSize");
+        assert_eq!(query_params[0].param_type.as_deref(), Some("integer"));
+        assert_eq!(query_params[0].format.as_deref(), Some("int32"));
+        assert_eq!(query_params[0].enum_values, None);
+
+        assert_eq!(query_params[1].name, "view");
+        assert_eq!(
+            query_params[1].enum_values,
+            Some(vec!["BASIC".to_string(), "FULL".to_string()])
+        );
+        assert_eq!(query_params[1].enum_descriptions.as_ref().unwrap().len(), 2);
+        assert_eq!(query_params[1].default.as_deref(), Some("BASIC"));
+    }
+
+    #[test]
+    fn test_schema_required_round_trip() -> Result<(), ZgError> {
+        let api = extract_api(PathBuf::from("tests/test_data/run_v2_required.json"), false)?;
+        let path = std::env::temp_dir().join(format!("zg-test-run-{}.msgpack", std::process::id()));
+        store_zgapi_msgpack(api, &path)?;
+
+        let decoded: core::ZgApi = rmp_serde::from_read(File::open(&path)?)?;
+        std::fs::remove_file(&path)?;
+        assert!(decoded.schemas["GoogleCloudRunV2Job"].is_property_required("template"));
+        assert!(!decoded.schemas["GoogleCloudRunV2Job"].is_property_required("name"));
+        // Map types keep their value type
+        let labels = &decoded.schemas["GoogleCloudRunV2Job"]
+            .properties
+            .as_ref()
+            .unwrap()["labels"];
+        assert_eq!(
+            labels.additional_properties.as_ref().unwrap().prop_type,
+            Some("string".to_string())
+        );
+        // Embedded request schemas keep the array as well
+        let (_, method) =
+            core::find_method_by_id(&decoded, "run.projects.locations.jobs.create", false)?;
+        assert!(method
+            .request_data_schema
+            .unwrap()
+            .is_property_required("template"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_api_malformed() {
+        let err =
+            extract_api(PathBuf::from("tests/test_data/malformed_v1.json"), false).unwrap_err();
+        let message = err.to_string();
+        assert!(matches!(err, ZgError::Deserialize(_)));
+        assert!(message.contains("malformed:v1"), "{}", message);
+        assert!(
+            message.contains("resources.projects.resources.widgets.methods.list.parameters.filter"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_extract_all_skips_failures() {
+        let dir = std::env::temp_dir().join(format!("zg-test-extract-all-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (extracted, failures) = extract_all(
+            vec![
+                PathBuf::from("tests/test_data/malformed_v1.json"),
+                PathBuf::from("tests/test_data/container_v1_nested.json"),
+            ],
+            false,
+            &dir,
+        );
+
+        // The malformed one is reported, and the other one is still stored
+        assert_eq!(extracted, vec!["container:v1"]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].0,
+            PathBuf::from("tests/test_data/malformed_v1.json")
+        );
+        assert!(dir.join(core::api_file_name("container:v1")).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_unknown_fields() {
+        let raw = serde_json::json!({ "id": "x", "icons": {}, "items": [{ "a": 1, "b": 2 }] });
+        let parsed = serde_json::json!({ "id": "x", "items": [{ "a": 1 }] });
+        let mut unknown = BTreeSet::new();
+        collect_unknown_fields(&raw, &parsed, &mut unknown);
+        assert_eq!(unknown.into_iter().collect::<Vec<_>>(), vec!["b", "icons"]);
+    }
+
+    #[test]
+    fn test_extract_api_common_params() -> Result<(), ZgError> {
+        let api = extract_api(PathBuf::from("tests/test_data/run_v2_required.json"), false)?;
+        let names: Vec<&str> = api.common_params.iter().map(|p| p.name.as_str()).collect();
+        // Sorted, and "$.xgafv" is kept despite the dot
+        assert_eq!(
+            names,
+            vec!["$.xgafv", "alt", "fields", "prettyPrint", "quotaUser"]
+        );
+        assert_eq!(api.common_params[3].param_type.as_deref(), Some("boolean"));
+        assert_eq!(api.common_params[1].default.as_deref(), Some("json"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_api_documentation_link() -> Result<(), ZgError> {
+        let api = extract_api(PathBuf::from("tests/test_data/run_v2_required.json"), false)?;
+        let path =
+            std::env::temp_dir().join(format!("zg-test-run-docs-{}.msgpack", std::process::id()));
+        store_zgapi_msgpack(api, &path)?;
+
+        let decoded: core::ZgApi = rmp_serde::from_read(File::open(&path)?)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            decoded.documentation_link.as_deref(),
+            Some("https://cloud.google.com/run/")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_api_scopes() -> Result<(), ZgError> {
+        let api = extract_api(PathBuf::from("tests/test_data/scopes_v1.json"), false)?;
+        let path =
+            std::env::temp_dir().join(format!("zg-test-scopes-{}.msgpack", std::process::id()));
+        store_zgapi_msgpack(api, &path)?;
+
+        let decoded: core::ZgApi = rmp_serde::from_read(File::open(&path)?)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            decoded.scopes.keys().collect::<Vec<_>>(),
+            vec![
+                "https://www.googleapis.com/auth/cloud-platform",
+                "https://www.googleapis.com/auth/cloud-platform.read-only"
+            ]
+        );
+        assert!(
+            decoded.scopes["https://www.googleapis.com/auth/cloud-platform.read-only"]
+                .starts_with("View your data")
+        );
+        let (_, method) = core::find_method_by_id(&decoded, "scopes.projects.widgets.list", false)?;
+        assert_eq!(method.scopes.len(), 3);
+
+        // Documents without the auth block have no scopes
+        let api = extract_api(PathBuf::from("tests/test_data/run_v2_required.json"), false)?;
+        assert!(api.scopes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deprecated_round_trip() {
+        let method: discovery::Method = serde_json::from_value(serde_json::json!({
+            "id": "testapi.projects.jobs.create",
+            "httpMethod": "POST",
+            "description": "Creates a job.",
+            "path": "v1/{+parent}/jobs",
+            "flatPath": "v1/projects/{projectsId}/jobs",
+            "parameters": {
+                "legacyFilter": { "location": "query", "type": "string", "deprecated": true },
+                "filter": { "location": "query", "type": "string" },
+            },
+            "request": { "$ref": "Job" },
+            "deprecated": true,
+        }))
+        .unwrap();
+        let schemas: HashMap<String, discovery::Schema> =
+            serde_json::from_value(serde_json::json!({
+                "Job": {
+                    "id": "Job",
+                    "properties": {
+                        "legacyName": { "type": "string", "deprecated": true },
+                        "name": { "type": "string" },
+                    },
+                },
+            }))
+            .unwrap();
+
+        let converted = convert_method("create".to_string(), method, &schemas);
+        let mut encoded = Vec::new();
+        converted
+            .serialize(&mut Serializer::new(&mut encoded))
+            .unwrap();
+        let decoded: core::ZgMethod = rmp_serde::from_slice(&encoded).unwrap();
+
+        assert!(decoded.deprecated);
+        let mut params: Vec<(&str, bool)> = decoded
+            .query_params
+            .iter()
+            .map(|qp| (qp.name.as_str(), qp.deprecated))
+            .collect();
+        params.sort();
+        assert_eq!(params, vec![("filter", false), ("legacyFilter", true)]);
+        let properties = decoded.request_data_schema.unwrap().properties.unwrap();
+        assert!(properties["legacyName"].deprecated);
+        assert!(!properties["name"].deprecated);
+    }
+
+    #[test]
+    fn test_convert_method_request_body() {
+        let schemas: HashMap<String, discovery::Schema> = serde_json::from_value(
+            serde_json::json!({ "DeleteRequest": { "id": "DeleteRequest", "properties": { "etag": { "type": "string" } } } }),
+        )
+        .unwrap();
+        let method = |http_method: &str, request: Option<&str>| -> discovery::Method {
+            let mut method = serde_json::json!({
+                "id": "testapi.projects.things.delete",
+                "description": "",
+                "httpMethod": http_method,
+                "path": "v1/{+name}",
+                "flatPath": "v1/projects/{projectsId}/things/{thingsId}",
+            });
+            if let Some(request) = request {
+                method["request"] = serde_json::json!({ "$ref": request });
+            }
+            serde_json::from_value(method).unwrap()
+        };
+
+        // The request schema is kept for DELETE methods that take a body
+        let converted = convert_method(
+            "delete".to_string(),
+            method("DELETE", Some("DeleteRequest")),
+            &schemas,
+        );
+        assert_eq!(
+            converted.request_data_schema.unwrap().id.as_deref(),
+            Some("DeleteRequest")
+        );
+        assert!(
+            convert_method("delete".to_string(), method("DELETE", None), &schemas)
+                .request_data_schema
+                .is_none()
+        );
+        assert!(
+            convert_method("get".to_string(), method("GET", None), &schemas)
+                .request_data_schema
+                .is_none()
+        );
+
+        // The name of the response schema is kept for `zg desc --response-example`
+        let mut get = method("GET", None);
+        get.response = Some(discovery::Response {
+            ref_name: Some("Thing".to_string()),
+        });
+        assert_eq!(
+            convert_method("get".to_string(), get, &schemas)
+                .response_schema
+                .as_deref(),
+            Some("Thing")
+        );
+    }
+
     #[test]
     fn test_convert_resource() {
         // Prepare a mock core::Resource with methods and sub-resources (from container:v1 API)
@@ -590,6 +1443,7 @@ mod tests {
                         request: None,
                         response: None,
                         scopes: None,
+                        deprecated: false,
                     },
                 )
             ]
@@ -615,6 +1469,7 @@ mod tests {
                                 request: None,
                                 response: None,
                                 scopes: None,
+                                deprecated: false,
                             },
                         )]
                         .into_iter()
@@ -749,7 +1604,8 @@ mod tests {
             ..core::ZgApi::testdata()
         };
 
-        let updated_api = update_resource_paths(&mut api);
+        update_resource_paths(&mut api);
+        let updated_api = &api;
 
         let datasets = &updated_api.resources[0];
         assert_eq!(
@@ -801,7 +1657,8 @@ mod tests {
         };
 
         // Call the function to update resource paths
-        let updated_api = update_resource_paths(&mut api);
+        update_resource_paths(&mut api);
+        let updated_api = &api;
 
         // Assert the top-level 'projects' resource
         let projects = &updated_api.resources[0];
@@ -852,7 +1709,7 @@ mod tests {
         };
 
         let mut resources = vec![parent_resource];
-        let inserted = insert_child_resource(&mut resources, &child_resource);
+        let inserted = insert_child_resource(&mut resources, child_resource).is_ok();
 
         assert!(inserted); // The child resource should be inserted successfully
         assert_eq!(resources[0].resources.as_ref().unwrap().len(), 1);
@@ -900,7 +1757,7 @@ mod tests {
         };
 
         let mut resources = vec![grandparent_resource];
-        let inserted = insert_child_resource(&mut resources, &child_resource);
+        let inserted = insert_child_resource(&mut resources, child_resource).is_ok();
         assert!(inserted); // The child resource should be inserted successfully
         let parent_resources = &resources[0].resources.as_ref().unwrap();
         assert_eq!(parent_resources[0].resources.as_ref().unwrap().len(), 1);
@@ -936,9 +1793,9 @@ mod tests {
 
         // Attempt to insert the child resource into a non-existent parent resource
         let mut resources = vec![resource];
-        let result = insert_child_resource(&mut resources, &child_resource);
+        let result = insert_child_resource(&mut resources, child_resource);
 
-        assert!(!result);
+        assert!(result.is_err());
         assert!(resources[0].resources.as_ref().unwrap().is_empty());
     }
 
@@ -985,7 +1842,7 @@ mod tests {
 
         let mut resources = vec![parent_resource];
 
-        let inserted_first = insert_child_resource(&mut resources, &instances1);
+        let inserted_first = insert_child_resource(&mut resources, instances1).is_ok();
         assert!(
             inserted_first,
             "First child resource should be inserted successfully"
@@ -996,7 +1853,7 @@ mod tests {
             "instances"
         );
 
-        let inserted_second = insert_child_resource(&mut resources, &instances2);
+        let inserted_second = insert_child_resource(&mut resources, instances2).is_ok();
         assert!(
             inserted_second,
             "Second child resource should be inserted successfully"
@@ -1016,4 +1873,155 @@ mod tests {
             "Second method should exist"
         );
     }
+
+    #[test]
+    fn test_rebuild_hierarchy_large_api() {
+        // Synthetic API of compute:v1's size: flat resources under "projects", and many large schemas
+        let method = |resource: &str, name: &str| core::ZgMethod {
+            id: format!("bigquery.{}.{}", resource, name),
+            name: name.to_string(),
+            flat_path: format!(
+                "projects/{{project}}/datasets/{{dataset}}/{}/{{id}}",
+                resource
+            ),
+            ..core::ZgMethod::testdata()
+        };
+        let mut resources = vec![core::ZgResource {
+            name: "projects".to_string(),
+            path: None,
+            parent_path: None,
+            methods: vec![core::ZgMethod {
+                id: "bigquery.projects.get".to_string(),
+                name: "get".to_string(),
+                flat_path: "projects/{project}".to_string(),
+                ..core::ZgMethod::testdata()
+            }],
+            resources: None,
+        }];
+        resources.push(core::ZgResource {
+            name: "datasets".to_string(),
+            path: None,
+            parent_path: None,
+            methods: vec![core::ZgMethod {
+                id: "bigquery.datasets.list".to_string(),
+                name: "list".to_string(),
+                flat_path: "projects/{project}/datasets".to_string(),
+                ..core::ZgMethod::testdata()
+            }],
+            resources: None,
+        });
+        resources.extend((0..300).map(|i| {
+            let name = format!("resource{}", i);
+            core::ZgResource {
+                methods: ["get", "list", "insert", "delete", "patch", "setLabels"]
+                    .iter()
+                    .map(|m| method(&name, m))
+                    .collect(),
+                name,
+                path: None,
+                parent_path: None,
+                resources: None,
+            }
+        }));
+        let properties: Value = (0..50)
+            .map(|p| {
+                let property =
+                    serde_json::json!({"type": "string", "description": "x".repeat(200)});
+                (format!("property{}", p), property)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        let schema = |i: usize| discovery::Schema {
+            id: Some(format!("Schema{}", i)),
+            description: Some("A schema with many properties. ".repeat(20)),
+            properties: Some(serde_json::from_value(properties.clone()).unwrap()),
+            ..discovery::Schema::testdata()
+        };
+        let api = core::ZgApi {
+            id: "bigquery:v2".to_string(),
+            name: "bigquery".to_string(),
+            resources,
+            schemas: (0..1000)
+                .map(|i| (format!("Schema{}", i), schema(i)))
+                .collect(),
+            ..core::ZgApi::testdata()
+        };
+
+        let api = rebuild_hierarchy(api);
+
+        assert_eq!(api.resources.len(), 1);
+        let datasets = &api.resources[0].resources.as_ref().unwrap()[0];
+        assert_eq!(datasets.path.as_deref(), Some("bigquery.projects.datasets"));
+        let children = datasets.resources.as_ref().unwrap();
+        assert_eq!(children.len(), 300);
+        let resource0 = children.iter().find(|r| r.name == "resource0").unwrap();
+        assert_eq!(
+            resource0.methods[0].id,
+            "bigquery.projects.datasets.resource0.get"
+        );
+        assert_eq!(api.schemas.len(), 1000);
+    }
+
+    #[test]
+    fn test_insert_children_with_orphan() {
+        let resource = |name: &str, path: &str, parent_path: Option<&str>| core::ZgResource {
+            name: name.to_string(),
+            path: Some(path.to_string()),
+            parent_path: parent_path.map(str::to_string),
+            methods: vec![],
+            resources: None,
+        };
+        let children = vec![
+            resource("orphan", "testapi.missing.orphan", Some("testapi.missing")),
+            // Inserted after its parent, which comes later in the list
+            resource(
+                "grandchild",
+                "testapi.projects.child.grandchild",
+                Some("testapi.projects.child"),
+            ),
+            resource("child", "testapi.projects.child", Some("testapi.projects")),
+        ];
+
+        // Used to loop forever on the orphan
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut resources = vec![resource("projects", "testapi.projects", None)];
+            let orphans = insert_children(&mut resources, children);
+            sender.send((resources, orphans)).unwrap();
+        });
+        let (resources, orphans) = receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .expect("insert_children should not hang");
+
+        let orphan_paths: Vec<_> = orphans.iter().map(|r| r.path.as_deref()).collect();
+        assert_eq!(orphan_paths, [Some("testapi.missing.orphan")]);
+        let child = &resources[0].resources.as_ref().unwrap()[0];
+        assert_eq!(child.name, "child");
+        assert_eq!(child.resources.as_ref().unwrap()[0].name, "grandchild");
+
+        // The orphan is listed at the top level by rebuild_hierarchy
+        let api = core::ZgApi {
+            id: "bigquery:v2".to_string(),
+            resources: vec![core::ZgResource {
+                name: "jobs".to_string(),
+                path: None,
+                parent_path: None,
+                methods: vec![core::ZgMethod {
+                    id: "bigquery.jobs.get".to_string(),
+                    name: "get".to_string(),
+                    flat_path: "projects/{projectsId}/jobs/{jobsId}".to_string(),
+                    ..core::ZgMethod::testdata()
+                }],
+                resources: None,
+            }],
+            ..core::ZgApi::testdata()
+        };
+        let api = rebuild_hierarchy(api);
+        assert_eq!(api.resources.len(), 1);
+        assert_eq!(api.resources[0].name, "jobs");
+        assert_eq!(
+            api.resources[0].parent_path.as_deref(),
+            Some("bigquery.projects")
+        );
+    }
 }
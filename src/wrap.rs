@@ -0,0 +1,207 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Word wrapping of long text (e.g., descriptions of query parameters) in the text output, at the terminal width,
+//! and truncation of text that has to fit in a column.
+
+use std::io::IsTerminal;
+
+use super::pager;
+
+/// The width used on a terminal whose width is unknown.
+const DEFAULT_WIDTH: usize = 100;
+
+/// The width to wrap the text output at: `given` (e.g., `--width`) or the width of the terminal. None (no wrapping)
+/// when stdout is not a terminal, so that piped output keeps one line per item.
+pub(crate) fn output_width(given: Option<usize>) -> Option<usize> {
+    resolve_width(given, std::io::stdout().is_terminal(), terminal_width)
+}
+
+fn resolve_width(
+    given: Option<usize>,
+    on_terminal: bool,
+    terminal_width: impl FnOnce() -> Option<usize>,
+) -> Option<usize> {
+    match on_terminal {
+        true => Some(given.or_else(terminal_width).unwrap_or(DEFAULT_WIDTH)),
+        false => None,
+    }
+}
+
+/// The number of columns of the terminal: `$COLUMNS`, or `stty size` of the controlling terminal.
+fn terminal_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(columns);
+    }
+    pager::terminal_size().map(|(_, columns)| columns)
+}
+
+/// Wraps the text at spaces into lines of at most `width` characters (unlimited if None). The first line starts with
+/// `prefix`, and the continuation lines are indented by `indent` spaces, or by 4 when `indent` would take more than
+/// half of the width. Each line of the text starts a new line. A word longer than the line (e.g., a URL) is put on
+/// a line by itself as it is, not broken.
+pub(crate) fn wrap(text: &str, width: Option<usize>, prefix: &str, indent: usize) -> Vec<String> {
+    let width = width.unwrap_or(usize::MAX);
+    let indent = match indent > width / 2 {
+        true => " ".repeat(indent.min(4)),
+        false => " ".repeat(indent),
+    };
+    let mut lines = Vec::new();
+    let mut line = prefix.to_string();
+    let mut empty = true;
+    for paragraph in text.lines().filter(|l| !l.trim().is_empty()) {
+        if !empty {
+            lines.push(std::mem::replace(&mut line, indent.clone()));
+            empty = true;
+        }
+        for word in paragraph.split_whitespace() {
+            if !empty && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::replace(&mut line, indent.clone()));
+                empty = true;
+            }
+            if !empty {
+                line.push(' ');
+            }
+            line.push_str(word);
+            empty = false;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Cuts the text to at most `width` characters, ending with `...` when it's cut.
+pub(crate) fn truncate(text: &str, width: usize) -> String {
+    match text.chars().count() > width {
+        true => format!(
+            "{}...",
+            text.chars()
+                .take(width.saturating_sub(3))
+                .collect::<String>()
+        ),
+        false => text.to_string(),
+    }
+}
+
+/// Wraps the list items (lines starting with `- `) of the text, indenting their continuation lines under the item.
+/// The other lines (e.g., JSON) are kept as they are.
+pub(crate) fn wrap_list_items(text: &str, width: Option<usize>) -> String {
+    text.split('\n')
+        .map(|line| match line.strip_prefix("- ") {
+            Some(item) if width.is_some() => wrap(item, width, "- ", 2).join("\n"),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// ---------------------- Unit tests ----------------------------- //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str =
+        "The maximum number of clusters to return. The service may return fewer than this value.";
+
+    #[test]
+    fn test_resolve_width() {
+        assert_eq!(resolve_width(None, true, || Some(80)), Some(80));
+        assert_eq!(resolve_width(None, true, || None), Some(DEFAULT_WIDTH));
+        assert_eq!(
+            resolve_width(Some(60), true, || panic!("not asked")),
+            Some(60)
+        );
+        // Piped
+        assert_eq!(resolve_width(Some(60), false, || Some(80)), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("cloud.google.com/run", 20), "cloud.google.com/run");
+        assert_eq!(
+            truncate("cloud.google.com/run/docs", 20),
+            "cloud.google.com/..."
+        );
+        assert_eq!(truncate("日本語のテキスト", 6), "日本語...");
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn test_wrap_at_widths() {
+        assert_eq!(
+            wrap(TEXT, Some(40), "- pageSize: ", 12),
+            vec![
+                "- pageSize: The maximum number of",
+                "            clusters to return. The",
+                "            service may return fewer",
+                "            than this value.",
+            ]
+        );
+        assert_eq!(
+            wrap(TEXT, Some(60), "- pageSize: ", 12),
+            vec![
+                "- pageSize: The maximum number of clusters to return. The",
+                "            service may return fewer than this value.",
+            ]
+        );
+        assert_eq!(
+            wrap(TEXT, Some(120), "- pageSize: ", 12),
+            vec![format!("- pageSize: {}", TEXT)]
+        );
+        assert_eq!(
+            wrap(TEXT, None, "- pageSize: ", 12),
+            vec![format!("- pageSize: {}", TEXT)]
+        );
+        // A narrow width falls back to a shallow indentation
+        assert_eq!(
+            wrap("Max clusters to return.", Some(20), "- pageSize: ", 12),
+            vec!["- pageSize: Max", "    clusters to", "    return."]
+        );
+    }
+
+    #[test]
+    fn test_wrap_long_tokens() {
+        let url = "https://cloud.google.com/kubernetes-engine/docs/reference/rest/v1/projects.locations.clusters";
+        assert_eq!(
+            wrap(&format!("See {} for details.", url), Some(40), "- ", 2),
+            vec![
+                "- See".to_string(),
+                format!("  {}", url),
+                "  for details.".to_string()
+            ]
+        );
+        // A long first word stays on the first line
+        assert_eq!(wrap(url, Some(10), "- ", 2), vec![format!("- {}", url)]);
+    }
+
+    #[test]
+    fn test_wrap_paragraphs() {
+        assert_eq!(
+            wrap("First paragraph.\n\nSecond one.", Some(80), "notes: ", 7),
+            vec!["notes: First paragraph.", "       Second one."]
+        );
+        assert_eq!(wrap("", Some(80), "- name", 2), vec!["- name"]);
+    }
+
+    #[test]
+    fn test_wrap_list_items() {
+        let text = "--data '{\n  \"query\": \"SELECT 1\"\n}'\n\nnotes:\n- Set useLegacySql to false for standard SQL queries.\n";
+        assert_eq!(
+            wrap_list_items(text, Some(30)),
+            "--data '{\n  \"query\": \"SELECT 1\"\n}'\n\nnotes:\n- Set useLegacySql to false\n  for standard SQL queries.\n"
+        );
+        assert_eq!(wrap_list_items(text, None), text);
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exercises the public library API with the container:v1 test data.
+
+use std::path::PathBuf;
+
+use zygen::core::{self, ZgApi};
+use zygen::error::ZgError;
+use zygen::extract_api;
+use zygen::Client;
+
+fn container_api() -> ZgApi {
+    extract_api(
+        PathBuf::from("tests/test_data/container_v1_nested.json"),
+        false,
+    )
+    .expect("test data should be extracted")
+}
+
+#[test]
+fn build_url_from_method_id() {
+    let client = Client::new(container_api());
+    let request = client
+        .request("container.projects.locations.clusters.nodePools.get")
+        .unwrap()
+        .param("projectId", "my-project")
+        .param("location", "us-central1")
+        .param("clusterId", "c1")
+        .param("nodePoolId", "np1")
+        .param("fields", "name");
+
+    assert_eq!(request.http_method(), "GET");
+    assert_eq!(
+        request.build_url().unwrap(),
+        "https://container.googleapis.com/v1/projects/my-project/locations/us-central1/clusters/c1/nodePools/np1?fields=name"
+    );
+}
+
+#[test]
+fn find_resources_and_methods() {
+    let api = container_api();
+    let resource = core::find_resource(&api.id, &api.resources, "clusters", true).unwrap();
+    assert_eq!(
+        resource.path.as_deref(),
+        Some("container.projects.locations.clusters")
+    );
+    assert_eq!(core::find_method(resource, "get").unwrap().name, "get");
+
+    let client = Client::new(api);
+    assert!(matches!(
+        client.request("container.projects.locations.clusters.delete"),
+        Err(ZgError::MethodNotFound { .. })
+    ));
+}